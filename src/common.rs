@@ -1,3 +1,27 @@
+pub mod audit;
+pub mod block_iter;
+pub mod cache;
+pub mod chainspec;
+pub mod config;
+pub mod coverage;
 pub mod db;
+pub mod db_lock;
+pub mod disk_space;
+pub mod http_server;
+pub mod human;
 pub mod lmdb_utils;
+pub mod merkle_body;
+pub mod network;
+pub mod output;
 pub mod progress;
+pub mod schema;
+pub mod stamp;
+pub mod state_store;
+pub mod storage;
+pub mod switch_blocks;
+pub mod temp;
+pub mod throttle;
+pub mod trie_file;
+pub mod vacuum;
+pub mod value_guard;
+pub mod zstd_utils;
@@ -0,0 +1,237 @@
+//! Append-only log of the subcommands which modified a database directory.
+//!
+//! Every run of a subcommand modifying the databases of a directory appends
+//! a line to the `db-utils-audit.log` file there, holding a JSON object with
+//! the time of the run, the versions of this tool and of the node it is
+//! compatible with, the command line, the tables whose number of entries
+//! changed and the outcome. It tells what touched a database long after the
+//! fact.
+
+use std::{
+    collections::BTreeMap,
+    fs::OpenOptions,
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use casper_types::Timestamp;
+use lmdb::Error as LmdbError;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{Error as SerializationError, Value as JsonValue};
+use thiserror::Error as ThisError;
+
+use super::{
+    db::{self, STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+    lmdb_utils,
+};
+
+/// Name of the audit log file in a database directory.
+pub const AUDIT_LOG_FILE_NAME: &str = "db-utils-audit.log";
+
+/// Errors encountered when writing to the audit log.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error appending to the audit log file.
+    #[error("Error appending to audit log {0}: {1}")]
+    Io(PathBuf, IoError),
+    /// Error serializing a record.
+    #[error("Error serializing audit record: {0}")]
+    Serialization(#[from] SerializationError),
+}
+
+/// Number of entries of a table before and after a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EntryCounts {
+    pub before: u64,
+    pub after: u64,
+}
+
+/// How a run ended.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    /// The run failed with the error described by `error`.
+    Failure {
+        error: JsonValue,
+    },
+}
+
+/// A line of the audit log.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: Timestamp,
+    pub tool_version: String,
+    /// Version of casper-node whose databases the tool is compatible with.
+    pub node_version: String,
+    pub subcommand: String,
+    /// Command line arguments, without the name of the executable.
+    pub arguments: Vec<String>,
+    /// Tables whose number of entries changed, keyed by file and table name.
+    pub entry_counts: BTreeMap<String, EntryCounts>,
+    pub outcome: Outcome,
+}
+
+/// Returns the number of entries of every table of the LMDB files in
+/// `db_dir`, keyed by `<file name>/<table name>`.
+fn entry_counts(db_dir: &Path) -> Result<BTreeMap<String, u64>, LmdbError> {
+    let mut counts = BTreeMap::new();
+    for file_name in [STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME] {
+        let path = db_dir.join(file_name);
+        if !path.exists() {
+            continue;
+        }
+        let env = db::db_env(&path)?;
+        let txn = env.begin_ro_txn()?;
+        for (db_name, stat) in lmdb_utils::db_stats(&txn)? {
+            counts.insert(format!("{}/{}", file_name, db_name), stat.entries as u64);
+        }
+    }
+    Ok(counts)
+}
+
+/// Audit of a run of a subcommand modifying the databases of a directory.
+/// The environments are only opened while counting entries, before and
+/// after the run, as LMDB doesn't allow opening one twice in a process.
+pub struct Audit {
+    db_dir: PathBuf,
+    subcommand: String,
+    arguments: Vec<String>,
+    /// Entry counts before the run, `None` if they couldn't be read.
+    maybe_counts_before: Option<BTreeMap<String, u64>>,
+}
+
+impl Audit {
+    /// Starts the audit of a run of `subcommand` with `arguments` on the
+    /// databases in `db_dir`, counting their entries.
+    pub fn start<P: AsRef<Path>>(db_dir: P, subcommand: &str, arguments: Vec<String>) -> Self {
+        let db_dir = db_dir.as_ref().to_path_buf();
+        let maybe_counts_before = match entry_counts(&db_dir) {
+            Ok(counts) => Some(counts),
+            Err(lmdb_err) => {
+                warn!(
+                    "Couldn't count the entries of {} for the audit log: {}",
+                    db_dir.display(),
+                    lmdb_err
+                );
+                None
+            }
+        };
+        Self {
+            db_dir,
+            subcommand: subcommand.to_string(),
+            arguments,
+            maybe_counts_before,
+        }
+    }
+
+    /// Counts the entries again and appends the record of the run, ended
+    /// with `outcome`, to the audit log of the directory.
+    pub fn finish(self, outcome: Outcome) -> Result<AuditRecord, Error> {
+        let mut entry_counts_changes = BTreeMap::new();
+        match (self.maybe_counts_before, entry_counts(&self.db_dir)) {
+            (Some(counts_before), Ok(counts_after)) => {
+                let table_names = counts_before.keys().chain(counts_after.keys());
+                for table_name in table_names {
+                    let counts = EntryCounts {
+                        before: counts_before.get(table_name).copied().unwrap_or_default(),
+                        after: counts_after.get(table_name).copied().unwrap_or_default(),
+                    };
+                    if counts.before != counts.after {
+                        entry_counts_changes.insert(table_name.clone(), counts);
+                    }
+                }
+            }
+            (None, _) => (),
+            (Some(_), Err(lmdb_err)) => warn!(
+                "Couldn't count the entries of {} for the audit log: {}",
+                self.db_dir.display(),
+                lmdb_err
+            ),
+        }
+        let record = AuditRecord {
+            timestamp: Timestamp::now(),
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            node_version: env!("CASPER_NODE_VERSION").to_string(),
+            subcommand: self.subcommand,
+            arguments: self.arguments,
+            entry_counts: entry_counts_changes,
+            outcome,
+        };
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let path = self.db_dir.join(AUDIT_LOG_FILE_NAME);
+        // A single write to a file opened for appending keeps concurrent
+        // records from interleaving.
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(&line))
+            .map_err(|io_err| Error::Io(path, io_err))?;
+        Ok(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use lmdb::{Transaction, WriteFlags};
+    use serde_json::json;
+
+    use super::{Audit, AuditRecord, EntryCounts, Outcome, AUDIT_LOG_FILE_NAME};
+    use crate::{
+        common::db::{BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+        test_utils::LmdbTestFixture,
+    };
+
+    #[test]
+    fn audit_should_append_records() {
+        let fixture = LmdbTestFixture::new(
+            vec![BlockHeaderDatabase::db_name(), "unchanged"],
+            Some(STORAGE_FILE_NAME),
+        );
+        let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        txn.put(header_db, &[0u8; 32], &[0u8], WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+
+        let audit = Audit::start(
+            fixture.tmp_dir.path(),
+            "remove-block",
+            vec!["remove-block".to_string(), "-d".to_string()],
+        );
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        txn.del(header_db, &[0u8; 32], None).unwrap();
+        txn.commit().unwrap();
+        let first_record = audit.finish(Outcome::Success).unwrap();
+        assert_eq!(first_record.entry_counts.len(), 1);
+        assert_eq!(
+            first_record.entry_counts["storage.lmdb/block_header"],
+            EntryCounts {
+                before: 1,
+                after: 0
+            }
+        );
+
+        let failure = Outcome::Failure {
+            error: json!({ "message": "failed" }),
+        };
+        let second_record = Audit::start(fixture.tmp_dir.path(), "remove-era", vec![])
+            .finish(failure.clone())
+            .unwrap();
+        assert!(second_record.entry_counts.is_empty());
+
+        let log = fs::read_to_string(fixture.tmp_dir.path().join(AUDIT_LOG_FILE_NAME)).unwrap();
+        let records: Vec<AuditRecord> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(records, vec![first_record, second_record]);
+        assert_eq!(records[1].outcome, failure);
+    }
+}
@@ -0,0 +1,401 @@
+//! Traversal of the blocks of a storage database in height order.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    result::Result,
+    vec::IntoIter,
+};
+
+use bincode::Error as BincodeError;
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader};
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database,
+            DeployDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase, TransferDatabase,
+        },
+        merkle_body::{self, Error as MerkleBodyError},
+        storage::{Error as StorageError, StorageReader},
+        switch_blocks::{self, Error as SwitchBlockError},
+        value_guard::{self, SkipCounter},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+/// Default number of blocks read ahead of the one being yielded.
+pub const DEFAULT_PREFETCH: usize = 64;
+
+/// Errors encountered when traversing the blocks of a storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on the body of a block.
+    #[error("Error parsing body of block {0}: {1}")]
+    BodyParsing(BlockHash, BincodeError),
+    /// Parsing error on the header of a block.
+    #[error("Error parsing header of block {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    /// The key at index in the block header database isn't a block hash.
+    #[error("Error deserializing raw key of block header DB element: {0}")]
+    InvalidKey(usize),
+    /// Error reading the merkle body of a block.
+    #[error("Error reading merkle body of block {0}: {1}")]
+    MerkleBody(BlockHash, MerkleBodyError),
+    /// The header of a block is missing.
+    #[error("Header of block {0} is missing")]
+    MissingHeader(BlockHash),
+}
+
+/// A block yielded by [`BlockIterator`]. The body is `None` if any part of
+/// it is missing from the database.
+pub type BlockEntry = (BlockHash, BlockHeader, Option<BlockBody>);
+
+/// Reads the header of the block `block_hash`, failing if it's missing.
+pub fn read_header<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+) -> Result<BlockHeader, Error> {
+    let raw_header = reader
+        .get(BlockHeaderDatabase::db_name(), block_hash.as_ref())?
+        .ok_or(Error::MissingHeader(*block_hash))?;
+    bincode::deserialize(&raw_header)
+        .map_err(|bincode_err| Error::HeaderParsing(*block_hash, bincode_err))
+}
+
+/// Reads the body of a block from either the legacy or the merkle body
+/// databases. Returns `None` if any part of the body is missing, or if the
+/// legacy body is empty or oversized.
+pub fn read_body<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+) -> Result<Option<BlockBody>, Error> {
+    // Look the body up by hash rather than by the hashing algorithm version
+    // of the header, as the version switchover differs between networks.
+    match reader.get(BlockBodyDatabase::db_name(), header.body_hash().as_ref())? {
+        Some(raw_body)
+            if !value_guard::is_parsable(
+                BlockBodyDatabase::db_name(),
+                header.body_hash().as_ref(),
+                &raw_body,
+            ) =>
+        {
+            Ok(None)
+        }
+        Some(raw_body) => bincode::deserialize(&raw_body)
+            .map(Some)
+            .map_err(|bincode_err| Error::BodyParsing(*block_hash, bincode_err)),
+        None => match merkle_body::read_merkle_body(reader, header.body_hash()) {
+            Ok(Some(merkle_body)) => Ok(Some(merkle_body.body)),
+            Ok(None) | Err(MerkleBodyError::MissingPart(..)) => Ok(None),
+            Err(merkle_body_err) => Err(Error::MerkleBody(*block_hash, merkle_body_err)),
+        },
+    }
+}
+
+/// Keys of the entries making up a block, as listed by [`block_records`].
+#[derive(Debug, PartialEq, Eq)]
+pub struct BlockRecords {
+    /// Database name and key of each entry. Any of them may be missing from
+    /// the database.
+    pub keys: Vec<(&'static str, Digest)>,
+    /// Whether the body was found. The deploys of the block are only listed
+    /// if it was.
+    pub has_body: bool,
+}
+
+/// Lists the entries holding the block `block_hash`: its header,
+/// signatures, transfers and body, followed by the deploys, execution
+/// results and finalized approvals of the deploys it executed.
+pub fn block_records<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+) -> Result<BlockRecords, Error> {
+    let mut keys = vec![
+        (BlockHeaderDatabase::db_name(), *block_hash.inner()),
+        (BlockMetadataDatabase::db_name(), *block_hash.inner()),
+        (TransferDatabase::db_name(), *block_hash.inner()),
+    ];
+    let maybe_raw_body = if reader.has_table(BlockBodyDatabase::db_name())? {
+        reader.get(BlockBodyDatabase::db_name(), header.body_hash().as_ref())?
+    } else {
+        None
+    };
+    let maybe_body: Option<BlockBody> = match maybe_raw_body {
+        Some(raw_body) => {
+            keys.push((BlockBodyDatabase::db_name(), *header.body_hash()));
+            Some(
+                bincode::deserialize(&raw_body)
+                    .map_err(|bincode_err| Error::BodyParsing(*block_hash, bincode_err))?,
+            )
+        }
+        None => match merkle_body::read_merkle_body(reader, header.body_hash()) {
+            Ok(Some(merkle_body)) => {
+                keys.extend(merkle_body.entries());
+                Some(merkle_body.body)
+            }
+            Ok(None) | Err(MerkleBodyError::MissingPart(..)) => None,
+            Err(merkle_body_err) => return Err(Error::MerkleBody(*block_hash, merkle_body_err)),
+        },
+    };
+    let has_body = maybe_body.is_some();
+    if let Some(body) = maybe_body {
+        for deploy_hash in body.deploy_hashes.iter().chain(body.transfer_hashes.iter()) {
+            for db_name in [
+                DeployDatabase::db_name(),
+                DeployMetadataDatabase::db_name(),
+                FinalizedApprovalsDatabase::db_name(),
+            ] {
+                keys.push((db_name, *deploy_hash.inner()));
+            }
+        }
+    }
+    Ok(BlockRecords { keys, has_body })
+}
+
+/// Returns the height and hash of every block of the storage database
+/// `reader` reads from, in ascending height order. Blocks whose header is
+/// empty or oversized are skipped with a warning.
+pub fn blocks_by_height<R: StorageReader>(reader: &R) -> Result<Vec<(u64, BlockHash)>, Error> {
+    let mut blocks = vec![];
+    let mut idx = 0;
+    let mut skip_counter = SkipCounter::default();
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            let block_hash =
+                BlockHash::new(raw_key.try_into().map_err(|_| Error::InvalidKey(idx))?);
+            idx += 1;
+            if !skip_counter.is_parsable(BlockHeaderDatabase::db_name(), raw_key, raw_val) {
+                return Ok(());
+            }
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+            blocks.push((header.height(), block_hash));
+            Ok(())
+        },
+    )?;
+    skip_counter.log(BlockHeaderDatabase::db_name());
+    blocks.sort_unstable();
+    Ok(blocks)
+}
+
+/// Hash and header of every block of a storage database, in ascending height
+/// order, along with the number of headers referencing each body. Bodies are
+/// content-addressed, so blocks with the same body share its entries.
+pub struct BlockIndex {
+    blocks: Vec<(BlockHash, BlockHeader)>,
+    body_refs: HashMap<Digest, usize>,
+}
+
+impl BlockIndex {
+    /// Reads every header of the storage database `reader` reads from.
+    /// Blocks whose header is empty or oversized are skipped with a warning.
+    pub fn new<R: StorageReader>(reader: &R) -> Result<Self, Error> {
+        let mut blocks = vec![];
+        let mut body_refs = HashMap::new();
+        let mut idx = 0;
+        let mut skip_counter = SkipCounter::default();
+        reader.scan(
+            BlockHeaderDatabase::db_name(),
+            |raw_key, raw_val| -> Result<(), Error> {
+                let block_hash =
+                    BlockHash::new(raw_key.try_into().map_err(|_| Error::InvalidKey(idx))?);
+                idx += 1;
+                if !skip_counter.is_parsable(BlockHeaderDatabase::db_name(), raw_key, raw_val) {
+                    return Ok(());
+                }
+                let header: BlockHeader = bincode::deserialize(raw_val)
+                    .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+                *body_refs.entry(*header.body_hash()).or_default() += 1;
+                blocks.push((block_hash, header));
+                Ok(())
+            },
+        )?;
+        skip_counter.log(BlockHeaderDatabase::db_name());
+        blocks.sort_unstable_by_key(|(block_hash, header)| (header.height(), *block_hash));
+        Ok(Self { blocks, body_refs })
+    }
+
+    /// Returns the blocks whose header matches `filter`, in ascending height
+    /// order.
+    pub fn find<F: Fn(&BlockHeader) -> bool>(&self, filter: F) -> Vec<(BlockHash, BlockHeader)> {
+        self.blocks
+            .iter()
+            .filter(|(_, header)| filter(header))
+            .cloned()
+            .collect()
+    }
+
+    /// Records the removal of the block `block_hash` with `header`, returning
+    /// whether its body is no longer referenced by any remaining header.
+    ///
+    /// Every subcommand removing blocks goes through here, so this is where
+    /// switch blocks are protected: unless `keep_header` is set, removing one
+    /// fails as checked by [`switch_blocks::check_removal`], and the index is
+    /// left unchanged.
+    pub fn release_block(
+        &mut self,
+        block_hash: &BlockHash,
+        header: &BlockHeader,
+        keep_header: bool,
+        allow_switch_block_removal: bool,
+    ) -> Result<bool, SwitchBlockError> {
+        if !keep_header {
+            switch_blocks::check_removal(block_hash, header, allow_switch_block_removal)?;
+        }
+        match self.body_refs.get_mut(header.body_hash()) {
+            Some(refs) if *refs > 1 => {
+                *refs -= 1;
+                Ok(false)
+            }
+            _ => {
+                self.body_refs.remove(header.body_hash());
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Iterator over the blocks of a storage database in ascending height order,
+/// yielding each block's hash, header and body.
+///
+/// The block header database is keyed by hash, so creating the iterator
+/// reads every header once to sort the blocks by height. Headers and bodies
+/// are then read again in batches of `prefetch` blocks ahead of the one being
+/// yielded.
+pub struct BlockIterator<'a, R: StorageReader> {
+    reader: &'a R,
+    /// Hashes of the blocks not read yet, by ascending height.
+    order: IntoIter<(u64, BlockHash)>,
+    prefetched: VecDeque<Result<BlockEntry, Error>>,
+    prefetch: usize,
+}
+
+impl<'a, R: StorageReader> BlockIterator<'a, R> {
+    /// Creates an iterator over the blocks of the storage database `reader`
+    /// reads from.
+    pub fn new(reader: &'a R) -> Result<Self, Error> {
+        Ok(Self {
+            reader,
+            order: blocks_by_height(reader)?.into_iter(),
+            prefetched: VecDeque::new(),
+            prefetch: DEFAULT_PREFETCH,
+        })
+    }
+
+    /// Sets the number of blocks read ahead of the one being yielded.
+    pub fn with_prefetch(mut self, prefetch: usize) -> Self {
+        self.prefetch = prefetch.max(1);
+        self
+    }
+
+    fn read_block(&self, block_hash: BlockHash) -> Result<BlockEntry, Error> {
+        let header = read_header(self.reader, &block_hash)?;
+        let body = read_body(self.reader, &block_hash, &header)?;
+        Ok((block_hash, header, body))
+    }
+}
+
+impl<'a, R: StorageReader> Iterator for BlockIterator<'a, R> {
+    type Item = Result<BlockEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.prefetched.is_empty() {
+            let batch: Vec<(u64, BlockHash)> = self.order.by_ref().take(self.prefetch).collect();
+            for (_height, block_hash) in batch {
+                let entry = self.read_block(block_hash);
+                self.prefetched.push_back(entry);
+            }
+        }
+        self.prefetched.pop_front()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.order.len() + self.prefetched.len();
+        (len, Some(len))
+    }
+}
+
+impl<'a, R: StorageReader> ExactSizeIterator for BlockIterator<'a, R> {}
+
+#[cfg(test)]
+mod tests {
+    use casper_node::types::BlockHash;
+    use lmdb::{Transaction, WriteFlags};
+
+    use crate::{
+        common::{
+            db::{BlockBodyDatabase, BlockHeaderDatabase, Database},
+            storage::LmdbReader,
+        },
+        subcommands::execution_results_summary::block_body::BlockBody,
+        test_utils::{mock_block_header, mock_deploy_hash, LmdbTestFixture},
+    };
+
+    use super::BlockIterator;
+
+    #[test]
+    fn blocks_in_height_order() {
+        let fixture = LmdbTestFixture::new(
+            vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+            None,
+        );
+        // Store blocks with hashes in the reverse order of their heights,
+        // the one at height 1 without a body.
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        let mut block_hashes = vec![];
+        for height in 0..5u8 {
+            let (block_hash, mut header) = mock_block_header(10 - height);
+            header.height = height as u64;
+            txn.put(
+                *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+                &block_hash,
+                &bincode::serialize(&header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+            if height != 1 {
+                txn.put(
+                    *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+                    &header.body_hash,
+                    &bincode::serialize(&BlockBody::new(vec![mock_deploy_hash(height)])).unwrap(),
+                    WriteFlags::empty(),
+                )
+                .unwrap();
+            }
+            block_hashes.push(block_hash);
+        }
+        // An empty header is skipped rather than failing the traversal.
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &mock_block_header(20).0,
+            &[],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+
+        let txn = fixture.env.begin_ro_txn().unwrap();
+        let reader = LmdbReader::new(&txn);
+        let blocks = BlockIterator::new(&reader).unwrap().with_prefetch(2);
+        assert_eq!(blocks.len(), 5);
+        let blocks: Vec<_> = blocks.map(Result::unwrap).collect();
+        let read_hashes: Vec<BlockHash> = blocks.iter().map(|(hash, _, _)| *hash).collect();
+        assert_eq!(read_hashes, block_hashes);
+        for (height, (_, header, maybe_body)) in blocks.iter().enumerate() {
+            assert_eq!(header.height(), height as u64);
+            match maybe_body {
+                Some(body) => assert_eq!(body.deploy_hashes, vec![mock_deploy_hash(height as u8)]),
+                None => assert_eq!(height, 1),
+            }
+        }
+        txn.commit().unwrap();
+    }
+}
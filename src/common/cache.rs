@@ -0,0 +1,171 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::Hash,
+    result::Result,
+};
+
+use log::info;
+
+/// Default number of entries held by a cache.
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Number of lookups served from a cache and of lookups which had to go to
+/// the database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Small least recently used cache, meant to avoid repeatedly fetching and
+/// deserializing the same database entries, such as switch block headers.
+pub struct LruCache<K, V> {
+    /// Maximum number of entries held.
+    capacity: usize,
+    /// Cached values along with the tick of their last use.
+    entries: HashMap<K, (V, u64)>,
+    /// Keys ordered by the tick of their last use, oldest first.
+    recency: BTreeMap<u64, K>,
+    /// Monotonic counter incremented on every use of an entry.
+    tick: u64,
+    stats: CacheStats,
+}
+
+impl<K: Clone + Eq + Hash, V> LruCache<K, V> {
+    /// Creates a cache holding at most `capacity` entries. A zero capacity is
+    /// raised to one.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Returns the value cached for `key`, marking it as the most recently
+    /// used one, and records the lookup as a hit or a miss.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        match self.entries.get_mut(key) {
+            Some((value, last_use)) => {
+                self.stats.hits += 1;
+                let key = self
+                    .recency
+                    .remove(last_use)
+                    .expect("cached key should have a recency entry");
+                *last_use = self.tick;
+                self.recency.insert(self.tick, key);
+                Some(value)
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches `value` for `key`, evicting the least recently used entry if
+    /// the cache is full.
+    pub fn insert(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if let Some((_, last_use)) = self.entries.remove(&key) {
+            self.recency.remove(&last_use);
+        } else if self.entries.len() == self.capacity {
+            if let Some((_, evicted_key)) = self.recency.pop_first() {
+                self.entries.remove(&evicted_key);
+            }
+        }
+        self.recency.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    /// Returns the value cached for `key`, or caches and returns the value
+    /// produced by `fetch` if there is none.
+    pub fn get_or_try_insert_with<E, F: FnOnce() -> Result<V, E>>(
+        &mut self,
+        key: &K,
+        fetch: F,
+    ) -> Result<&V, E> {
+        if self.get(key).is_none() {
+            let value = fetch()?;
+            self.insert(key.clone(), value);
+        }
+        Ok(&self.entries.get(key).expect("value should be cached").0)
+    }
+
+    /// Drops all cached entries, keeping the statistics. Needed whenever the
+    /// underlying database entries are modified.
+    #[cfg(test)]
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    /// Logs the hit and miss counts of the cache under the given name.
+    pub fn log_stats(&self, name: &str) {
+        let stats = self.stats();
+        info!(
+            "{} cache: {} hits, {} misses.",
+            name, stats.hits, stats.misses
+        );
+    }
+}
+
+impl<K: Clone + Eq + Hash, V> Default for LruCache<K, V> {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::{CacheStats, LruCache};
+
+    #[test]
+    fn lru_eviction_and_stats() {
+        let mut cache = LruCache::new(2);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        // Using 1 makes 2 the least recently used entry.
+        assert_eq!(cache.get(&1), Some(&"one"));
+        cache.insert(3, "three");
+        assert_eq!(cache.get(&2), None);
+        assert_eq!(cache.get(&1), Some(&"one"));
+        assert_eq!(cache.get(&3), Some(&"three"));
+
+        // Overwriting an entry doesn't evict anything.
+        cache.insert(3, "THREE");
+        assert_eq!(cache.get(&1), Some(&"one"));
+        assert_eq!(cache.get(&3), Some(&"THREE"));
+
+        let mut fetched = 0;
+        for _ in 0..2 {
+            let value = cache
+                .get_or_try_insert_with(&4, || {
+                    fetched += 1;
+                    Ok::<_, Infallible>("four")
+                })
+                .unwrap();
+            assert_eq!(*value, "four");
+        }
+        assert_eq!(fetched, 1);
+        assert!(cache
+            .get_or_try_insert_with(&5, || Err("not found"))
+            .is_err());
+        assert_eq!(cache.get(&5), None);
+
+        assert_eq!(cache.stats(), CacheStats { hits: 6, misses: 4 });
+
+        cache.clear();
+        assert_eq!(cache.get(&4), None);
+        assert_eq!(cache.stats(), CacheStats { hits: 6, misses: 5 });
+    }
+}
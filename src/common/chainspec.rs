@@ -0,0 +1,185 @@
+use std::{
+    fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use casper_execution_engine::{
+    core::engine_state::{
+        engine_config::{self, DEFAULT_MAX_QUERY_DEPTH},
+        EngineConfig,
+    },
+    shared::{system_config::SystemConfig, wasm_config::WasmConfig},
+};
+use casper_types::{EraId, ProtocolVersion, TimeDiff, Timestamp};
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+use toml::de::Error as TomlError;
+
+/// Name of the chainspec file in the config directory of a node.
+pub const CHAINSPEC_FILE_NAME: &str = "chainspec.toml";
+
+/// Errors encountered when reading a chainspec.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error parsing the chainspec.
+    #[error("Error parsing chainspec {0}: {1}")]
+    Parse(PathBuf, TomlError),
+    /// Error reading the chainspec.
+    #[error("Error reading chainspec {0}: {1}")]
+    Read(PathBuf, IoError),
+}
+
+/// The first era of a protocol version, or the genesis timestamp for the
+/// first protocol version of a network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum ActivationPoint {
+    EraId(EraId),
+    Genesis(Timestamp),
+}
+
+impl ActivationPoint {
+    /// Returns the first era of the protocol version.
+    pub fn era_id(&self) -> EraId {
+        match self {
+            ActivationPoint::EraId(era_id) => *era_id,
+            ActivationPoint::Genesis(_) => EraId::new(0),
+        }
+    }
+}
+
+/// The settings of the `[protocol]` table of the chainspec.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProtocolConfig {
+    pub version: ProtocolVersion,
+    pub activation_point: ActivationPoint,
+}
+
+/// The settings of the `[core]` table of the chainspec this tool depends on.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CoreConfig {
+    /// Minimum duration of an era.
+    pub era_duration: TimeDiff,
+    /// Minimum number of blocks of an era.
+    pub minimum_era_height: u64,
+    pub validator_slots: u32,
+    pub auction_delay: u64,
+    pub max_associated_keys: u32,
+    pub max_runtime_call_stack_height: u32,
+    pub max_stored_value_size: u32,
+    pub minimum_delegation_amount: u64,
+    /// Number of era infos pruned after each block, none if 0.
+    #[serde(default)]
+    pub prune_batch_size: u64,
+}
+
+/// The settings of the `[highway]` table of the chainspec this tool depends
+/// on.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HighwayConfig {
+    /// Binary logarithm of the shortest round length, in milliseconds.
+    pub minimum_round_exponent: u8,
+}
+
+impl HighwayConfig {
+    /// Returns the shortest time between two blocks.
+    pub fn minimum_block_time(&self) -> TimeDiff {
+        TimeDiff::from_millis(1u64 << self.minimum_round_exponent)
+    }
+}
+
+/// The parts of the chainspec of a network this tool depends on. The node
+/// doesn't export its own chainspec type.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Chainspec {
+    pub protocol: ProtocolConfig,
+    pub core: CoreConfig,
+    pub highway: HighwayConfig,
+    pub wasm: WasmConfig,
+    pub system_costs: SystemConfig,
+}
+
+impl Chainspec {
+    /// Returns the config of the execution engine of a node running with
+    /// this chainspec.
+    pub fn engine_config(&self) -> EngineConfig {
+        EngineConfig::new(
+            DEFAULT_MAX_QUERY_DEPTH,
+            self.core.max_associated_keys,
+            self.core.max_runtime_call_stack_height,
+            self.core.max_stored_value_size,
+            engine_config::compute_max_delegator_size_limit(
+                self.core.max_stored_value_size,
+                self.core.auction_delay,
+                self.core.validator_slots,
+            ),
+            self.core.minimum_delegation_amount,
+            self.wasm,
+            self.system_costs,
+        )
+    }
+}
+
+/// Reads the chainspec at `path`, or in the `chainspec.toml` file in it if
+/// it's a directory.
+pub fn read_chainspec<P: AsRef<Path>>(path: P) -> Result<Chainspec, Error> {
+    let mut path = path.as_ref().to_path_buf();
+    if path.is_dir() {
+        path.push(CHAINSPEC_FILE_NAME);
+    }
+    let contents = fs::read_to_string(&path).map_err(|io_err| Error::Read(path.clone(), io_err))?;
+    toml::from_str(&contents).map_err(|toml_err| Error::Parse(path, toml_err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use casper_types::{EraId, ProtocolVersion};
+
+    use super::{read_chainspec, ActivationPoint, Error, CHAINSPEC_FILE_NAME};
+    use crate::test_utils;
+
+    #[test]
+    fn read_chainspec_should_skip_unrelated_settings() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let chainspec = test_utils::mock_chainspec();
+        let mut value = toml::Value::try_from(&chainspec).unwrap();
+        let table = value.as_table_mut().unwrap();
+        table["protocol"]
+            .as_table_mut()
+            .unwrap()
+            .insert("hard_reset".to_string(), toml::Value::Boolean(false));
+        table.insert(
+            "network".to_string(),
+            "name = 'casper-test'".parse().unwrap(),
+        );
+        fs::write(
+            tmp_dir.path().join(CHAINSPEC_FILE_NAME),
+            toml::to_string(&value).unwrap(),
+        )
+        .unwrap();
+
+        let parsed = read_chainspec(tmp_dir.path()).unwrap();
+        assert_eq!(parsed.protocol.version, ProtocolVersion::V1_0_0);
+        assert_eq!(
+            parsed.protocol.activation_point,
+            ActivationPoint::EraId(EraId::new(0))
+        );
+        assert_eq!(parsed.core.era_duration, chainspec.core.era_duration);
+        assert_eq!(parsed.core.validator_slots, 100);
+        assert_eq!(
+            parsed.engine_config().max_stored_value_size(),
+            chainspec.engine_config().max_stored_value_size()
+        );
+        assert_eq!(parsed.wasm, chainspec.wasm);
+        assert_eq!(parsed.system_costs, chainspec.system_costs);
+
+        assert!(matches!(
+            read_chainspec(tmp_dir.path().join("missing.toml")),
+            Err(Error::Read(..))
+        ));
+    }
+}
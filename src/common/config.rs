@@ -0,0 +1,424 @@
+//! Defaults for the arguments of the subcommands, read from a TOML file.
+//!
+//! Keys are the long names of the options they set, such as `db-path`. Keys
+//! at the top level apply to every subcommand taking the option, and keys in
+//! a table named after a subcommand, such as `[archive.create]`, apply to it
+//! and to its own subcommands only. Arguments given on the command line
+//! override the defaults.
+//!
+//! Defaults can also be derived from the config file of a node, pointing the
+//! options taking the path of a database at the directory where the node
+//! keeps its databases.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    ffi::OsString,
+    fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use clap::Command;
+use serde::Deserialize;
+use thiserror::Error as ThisError;
+use toml::{value::Table, Value};
+
+use crate::common::network::DATA_DIR_OPTIONS;
+
+/// Long name of the option giving the path of the config file.
+pub const CONFIG: &str = "config";
+/// Long name of the option giving the path of the config file of a node.
+pub const NODE_CONFIG: &str = "node-config";
+/// Name of the chainspec file, kept by nodes next to their config file.
+const CHAINSPEC_FILE_NAME: &str = "chainspec.toml";
+/// Long name of the option taking the name of the network.
+const NETWORK_NAME_OPTION: &str = "network-name";
+/// Path of the config file relative to the config directory of the user.
+const DEFAULT_CONFIG_PATH: &str = "casper-db-utils/config.toml";
+
+/// Errors encountered when reading the config file.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error parsing the config file.
+    #[error("Error parsing config file {0}: {1}")]
+    Parse(PathBuf, toml::de::Error),
+    /// Error reading the config file.
+    #[error("Error reading config file {0}: {1}")]
+    Read(PathBuf, IoError),
+    /// A key doesn't match any option or subcommand in its scope.
+    #[error("Unknown config key `{0}`")]
+    UnknownKey(String),
+    /// The value of a key isn't a string, integer or float, or sets an
+    /// option which doesn't take a value.
+    #[error("Unsupported value for config key `{0}`")]
+    UnsupportedValue(String),
+}
+
+/// Returns the value given to the option with the long name `long` in
+/// `args`, if any. The command line can't be parsed before the defaults of
+/// the config are applied to it, so the option is looked up by hand.
+pub fn value_from_args(args: &[OsString], long: &str) -> Option<OsString> {
+    let flag = format!("--{}", long);
+    let prefix = format!("{}=", flag);
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let arg = arg.to_string_lossy();
+        if arg == flag {
+            return args.next().cloned();
+        }
+        if let Some(value) = arg.strip_prefix(&prefix) {
+            return Some(OsString::from(value));
+        }
+    }
+    None
+}
+
+/// Returns the path of the config file in the config directory of the user,
+/// `$XDG_CONFIG_HOME` or `~/.config`.
+pub fn default_path() -> Option<PathBuf> {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|config_home| !config_home.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+        .map(|config_dir| config_dir.join(DEFAULT_CONFIG_PATH))
+}
+
+/// The settings of a node config file needed to locate its databases.
+#[derive(Deserialize)]
+struct NodeConfig {
+    storage: NodeStorageConfig,
+}
+
+#[derive(Deserialize)]
+struct NodeStorageConfig {
+    path: PathBuf,
+}
+
+/// The settings of a chainspec file needed to locate the databases of a node.
+#[derive(Deserialize)]
+struct Chainspec {
+    network: NetworkConfig,
+}
+
+#[derive(Deserialize)]
+struct NetworkConfig {
+    name: String,
+}
+
+fn read_toml<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T, Error> {
+    let contents =
+        fs::read_to_string(path).map_err(|io_err| Error::Read(path.to_path_buf(), io_err))?;
+    toml::from_str(&contents).map_err(|toml_err| Error::Parse(path.to_path_buf(), toml_err))
+}
+
+/// Defaults for the arguments of the subcommands.
+#[derive(Debug, Default)]
+pub struct Config {
+    table: Table,
+    /// Whether keys matching no option are errors, rather than ignored.
+    strict: bool,
+}
+
+impl Config {
+    /// Reads the config file at `path`.
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(Self {
+            table: read_toml(path.as_ref())?,
+            strict: true,
+        })
+    }
+
+    /// Derives defaults from the node config file at `path` and the
+    /// chainspec next to it: the directory of the databases of the node,
+    /// which is the storage path of the config joined with the network name,
+    /// and the network name itself.
+    pub fn from_node_config<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let node_config: NodeConfig = read_toml(path)?;
+        let config_dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let chainspec: Chainspec = read_toml(&config_dir.join(CHAINSPEC_FILE_NAME))?;
+        // Relative storage paths are relative to the directory of the config.
+        let data_dir = config_dir
+            .join(node_config.storage.path)
+            .join(&chainspec.network.name);
+
+        let data_dir = data_dir.to_string_lossy();
+        let mut table: Table = DATA_DIR_OPTIONS
+            .iter()
+            .map(|long| (long.to_string(), Value::String(data_dir.to_string())))
+            .collect();
+        table.insert(
+            NETWORK_NAME_OPTION.to_string(),
+            Value::String(chainspec.network.name),
+        );
+        Ok(Self {
+            table,
+            strict: false,
+        })
+    }
+
+    /// Reads the config file at `maybe_path` if given, otherwise the one at
+    /// the default path if it exists.
+    pub fn load(maybe_path: Option<&Path>) -> Result<Self, Error> {
+        match maybe_path {
+            Some(path) => Self::read(path),
+            None => match default_path() {
+                Some(path) if path.exists() => Self::read(path),
+                _ => Ok(Self::default()),
+            },
+        }
+    }
+
+    /// Sets the values of the config as the defaults of the matching
+    /// options of `cli` and its subcommands.
+    pub fn apply(&self, cli: Command<'static>) -> Result<Command<'static>, Error> {
+        apply_table(cli, &BTreeMap::new(), Some(&self.table), "", self.strict)
+    }
+}
+
+/// Returns whether `cmd` or any of its subcommands has an option with the
+/// long name `long`.
+fn has_option(cmd: &Command, long: &str) -> bool {
+    cmd.get_arguments().any(|arg| arg.get_long() == Some(long))
+        || cmd.get_subcommands().any(|subcmd| has_option(subcmd, long))
+}
+
+/// Applies the defaults in `inherited`, overridden by the ones in `table`,
+/// to the options of `cmd`, then recurses into its subcommands with the
+/// tables named after them. `scope` is the path of the table, for errors.
+/// Keys matching no option or subcommand are errors if `strict` is set, and
+/// ignored otherwise.
+fn apply_table(
+    mut cmd: Command<'static>,
+    inherited: &BTreeMap<String, &'static str>,
+    table: Option<&Table>,
+    scope: &str,
+    strict: bool,
+) -> Result<Command<'static>, Error> {
+    let mut defaults = inherited.clone();
+    let mut subtables = BTreeMap::new();
+    for (key, value) in table.into_iter().flatten() {
+        let scoped_key = format!("{}{}", scope, key);
+        if let Value::Table(subtable) = value {
+            if !cmd.get_subcommands().any(|subcmd| subcmd.get_name() == key) {
+                return Err(Error::UnknownKey(scoped_key));
+            }
+            subtables.insert(key.as_str(), subtable);
+            continue;
+        }
+        if !has_option(&cmd, key) {
+            if strict {
+                return Err(Error::UnknownKey(scoped_key));
+            }
+            continue;
+        }
+        let value = match value {
+            Value::String(string) => string.clone(),
+            Value::Integer(integer) => integer.to_string(),
+            Value::Float(float) => float.to_string(),
+            _ => return Err(Error::UnsupportedValue(scoped_key)),
+        };
+        // Defaults are kept by clap for the whole run, and the config is
+        // read once.
+        defaults.insert(key.clone(), Box::leak(value.into_boxed_str()));
+    }
+
+    let ids: Vec<(&'static str, &'static str)> = cmd
+        .get_arguments()
+        .filter_map(|arg| {
+            let long = arg.get_long()?;
+            defaults
+                .get(long)
+                .map(|default_value| (arg.get_id(), *default_value))
+        })
+        .collect();
+    for (id, default_value) in ids {
+        if let Some(arg) = cmd.get_arguments().find(|arg| arg.get_id() == id) {
+            if !arg.is_takes_value_set() {
+                return Err(Error::UnsupportedValue(format!(
+                    "{}{}",
+                    scope,
+                    arg.get_long().unwrap_or(id)
+                )));
+            }
+        }
+        cmd = cmd.mut_arg(id, |arg| arg.default_value(default_value).required(false));
+    }
+
+    for subcmd in cmd.get_subcommands_mut() {
+        let name = subcmd.get_name().to_string();
+        let subscope = format!("{}{}.", scope, name);
+        *subcmd = apply_table(
+            std::mem::take(subcmd),
+            &defaults,
+            subtables.get(name.as_str()).copied(),
+            &subscope,
+            strict,
+        )?;
+    }
+    Ok(cmd)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsString, fs};
+
+    use clap::{Arg, Command};
+    use tempfile::tempdir;
+
+    use super::{value_from_args, Config, Error, CONFIG, NODE_CONFIG};
+
+    fn test_cli() -> Command<'static> {
+        let db_path = || {
+            Arg::new("db-path")
+                .required(true)
+                .short('d')
+                .long("db-path")
+                .takes_value(true)
+        };
+        Command::new("db-tool")
+            .arg(Arg::new("logging").long("logging").takes_value(true))
+            .subcommand(Command::new("check").arg(db_path()))
+            .subcommand(
+                Command::new("set-state-store").arg(db_path()).arg(
+                    Arg::new("network-name")
+                        .long("network-name")
+                        .takes_value(true),
+                ),
+            )
+            .subcommand(
+                Command::new("archive").subcommand(
+                    Command::new("create")
+                        .arg(db_path())
+                        .arg(Arg::new("level").long("level").takes_value(true))
+                        .arg(Arg::new("overwrite").long("overwrite")),
+                ),
+            )
+    }
+
+    fn config(contents: &str) -> Result<Config, Error> {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("config.toml");
+        fs::write(&path, contents).unwrap();
+        Config::load(Some(&path))
+    }
+
+    #[test]
+    fn config_should_provide_defaults_under_cli_overrides() {
+        let config = config(
+            r#"
+            db-path = "/data"
+            logging = "/var/log/db-tool.log"
+
+            [archive.create]
+            db-path = "/archive"
+            level = 15
+            "#,
+        )
+        .unwrap();
+        let matches = |args: &[&str]| config.apply(test_cli()).unwrap().get_matches_from(args);
+
+        let root_matches = matches(&["db-tool", "check"]);
+        assert_eq!(
+            root_matches.value_of("logging"),
+            Some("/var/log/db-tool.log")
+        );
+        let check_matches = root_matches.subcommand_matches("check").unwrap();
+        assert_eq!(check_matches.value_of("db-path"), Some("/data"));
+
+        let root_matches = matches(&["db-tool", "archive", "create"]);
+        let create_matches = root_matches
+            .subcommand_matches("archive")
+            .and_then(|matches| matches.subcommand_matches("create"))
+            .unwrap();
+        assert_eq!(create_matches.value_of("db-path"), Some("/archive"));
+        assert_eq!(create_matches.value_of("level"), Some("15"));
+
+        let root_matches = matches(&["db-tool", "check", "-d", "/other"]);
+        let check_matches = root_matches.subcommand_matches("check").unwrap();
+        assert_eq!(check_matches.value_of("db-path"), Some("/other"));
+    }
+
+    #[test]
+    fn invalid_config_should_fail() {
+        let apply = |contents: &str| config(contents).unwrap().apply(test_cli()).map(|_| ());
+        assert!(matches!(
+            apply("unknown = 1"),
+            Err(Error::UnknownKey(key)) if key == "unknown"
+        ));
+        assert!(matches!(
+            apply("[archive.unknown]\nlevel = 1"),
+            Err(Error::UnknownKey(key)) if key == "archive.unknown"
+        ));
+        assert!(matches!(
+            apply("[check]\nlevel = 1"),
+            Err(Error::UnknownKey(key)) if key == "check.level"
+        ));
+        assert!(matches!(
+            apply("overwrite = true"),
+            Err(Error::UnsupportedValue(key)) if key == "overwrite"
+        ));
+        assert!(matches!(
+            apply("overwrite = \"yes\""),
+            Err(Error::UnsupportedValue(key)) if key == "archive.create.overwrite"
+        ));
+        assert!(matches!(config("db-path = "), Err(Error::Parse(..))));
+    }
+
+    #[test]
+    fn config_paths_should_be_read_from_args() {
+        let args = |args: &[&str]| -> Vec<OsString> { args.iter().map(OsString::from).collect() };
+        assert_eq!(
+            value_from_args(&args(&["db-tool", "--config", "a.toml", "check"]), CONFIG),
+            Some(OsString::from("a.toml"))
+        );
+        assert_eq!(
+            value_from_args(&args(&["db-tool", "check", "--config=b.toml"]), CONFIG),
+            Some(OsString::from("b.toml"))
+        );
+        assert_eq!(value_from_args(&args(&["db-tool", "check"]), CONFIG), None);
+        assert_eq!(
+            value_from_args(
+                &args(&["db-tool", "--config", "a.toml", "check"]),
+                NODE_CONFIG
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn node_config_should_locate_databases() {
+        let tmp_dir = tempdir().unwrap();
+        let config_path = tmp_dir.path().join("config.toml");
+        fs::write(
+            &config_path,
+            "[node]\nsync_to_genesis = true\n\n[storage]\npath = 'storage'\n",
+        )
+        .unwrap();
+        fs::write(
+            tmp_dir.path().join("chainspec.toml"),
+            "[network]\nname = 'casper-test'\n",
+        )
+        .unwrap();
+        let config = Config::from_node_config(&config_path).unwrap();
+
+        let root_matches = config
+            .apply(test_cli())
+            .unwrap()
+            .get_matches_from(["db-tool", "set-state-store"]);
+        let matches = root_matches.subcommand_matches("set-state-store").unwrap();
+        let data_dir = tmp_dir.path().join("storage").join("casper-test");
+        assert_eq!(
+            matches.value_of("db-path"),
+            Some(data_dir.to_string_lossy().as_ref())
+        );
+        assert_eq!(matches.value_of("network-name"), Some("casper-test"));
+
+        fs::remove_file(tmp_dir.path().join("chainspec.toml")).unwrap();
+        assert!(matches!(
+            Config::from_node_config(&config_path),
+            Err(Error::Read(..))
+        ));
+    }
+}
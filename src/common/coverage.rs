@@ -0,0 +1,172 @@
+//! Coverage of the history of a storage database which may only hold part
+//! of it.
+//!
+//! A node which synced its history fast only holds the headers of the blocks
+//! before the point it synced to, without their bodies or execution results.
+//! With the `--partial` argument, the reports built from bodies and results
+//! skip such blocks instead of failing, and list the heights they lack.
+
+use clap::{Arg, ArgMatches};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+const PARTIAL: &str = "partial";
+
+/// An inclusive range of block heights.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeightRange {
+    pub from: u64,
+    pub to: u64,
+}
+
+/// Extends the last range of `ranges` with `height` if it directly follows
+/// it, or starts a new range otherwise. Heights must come in ascending
+/// order.
+fn push_height(ranges: &mut Vec<HeightRange>, height: u64) {
+    match ranges.last_mut() {
+        Some(range) if range.to == height || range.to + 1 == height => range.to = height,
+        _ => ranges.push(HeightRange {
+            from: height,
+            to: height,
+        }),
+    }
+}
+
+/// Which blocks of a database have their body and execution results.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Coverage {
+    pub blocks: usize,
+    /// Number of blocks with their body and all their execution results.
+    pub complete_blocks: usize,
+    /// Heights of the blocks without a body.
+    pub missing_bodies: Vec<HeightRange>,
+    /// Heights of the blocks with a body but without the execution results
+    /// of some of their deploys.
+    pub missing_results: Vec<HeightRange>,
+}
+
+impl Coverage {
+    /// Records a block with its body and all its execution results.
+    pub fn record_complete(&mut self) {
+        self.blocks += 1;
+        self.complete_blocks += 1;
+    }
+
+    /// Records the block at `height` as lacking its body.
+    pub fn record_missing_body(&mut self, height: u64) {
+        self.blocks += 1;
+        push_height(&mut self.missing_bodies, height);
+    }
+
+    /// Records the block at `height` as lacking execution results.
+    pub fn record_missing_results(&mut self, height: u64) {
+        self.blocks += 1;
+        push_height(&mut self.missing_results, height);
+    }
+
+    /// Returns whether every block has its body and execution results.
+    pub fn is_complete(&self) -> bool {
+        self.complete_blocks == self.blocks
+    }
+
+    /// Returns whether the database only holds block headers, as after a
+    /// fast sync.
+    pub fn is_headers_only(&self) -> bool {
+        self.blocks > 0 && self.complete_blocks == 0 && self.missing_results.is_empty()
+    }
+
+    /// Logs the share of blocks covered and the ranges of heights which
+    /// aren't.
+    pub fn log(&self) {
+        if self.is_complete() {
+            info!("All {} blocks have their body and results.", self.blocks);
+            return;
+        }
+        if self.is_headers_only() {
+            warn!(
+                "The database only holds the headers of its {} blocks, none of \
+                them were included.",
+                self.blocks
+            );
+            return;
+        }
+        warn!(
+            "Only {} of the {} blocks have their body and results, the others \
+            weren't included.",
+            self.complete_blocks, self.blocks
+        );
+        for range in self.missing_bodies.iter() {
+            warn!("Blocks {} to {} have no body.", range.from, range.to);
+        }
+        for range in self.missing_results.iter() {
+            warn!(
+                "Blocks {} to {} lack execution results.",
+                range.from, range.to
+            );
+        }
+    }
+}
+
+/// Returns the argument making reports skip the blocks lacking their body
+/// or execution results.
+pub fn partial_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(PARTIAL)
+        .display_order(display_order)
+        .required(false)
+        .long(PARTIAL)
+        .takes_value(false)
+        .help(
+            "Accept databases holding only part of the history, like the \
+            headers-only ones of fast-synced nodes: skip the blocks lacking \
+            their body or execution results instead of failing, and report \
+            the ranges of heights skipped.",
+        )
+}
+
+/// Reads the argument returned by `partial_arg`.
+pub fn is_partial(matches: &ArgMatches) -> bool {
+    matches.is_present(PARTIAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Coverage, HeightRange};
+
+    #[test]
+    fn coverage_should_merge_consecutive_heights() {
+        let mut coverage = Coverage::default();
+        for height in 0..3 {
+            coverage.record_missing_body(height);
+        }
+        coverage.record_missing_results(3);
+        coverage.record_complete();
+        coverage.record_missing_results(5);
+        // Orphaned blocks share the height of another one.
+        coverage.record_missing_body(7);
+        coverage.record_missing_body(7);
+        coverage.record_missing_body(8);
+        assert_eq!(coverage.blocks, 9);
+        assert_eq!(coverage.complete_blocks, 1);
+        assert_eq!(
+            coverage.missing_bodies,
+            [
+                HeightRange { from: 0, to: 2 },
+                HeightRange { from: 7, to: 8 }
+            ]
+        );
+        assert_eq!(
+            coverage.missing_results,
+            [
+                HeightRange { from: 3, to: 3 },
+                HeightRange { from: 5, to: 5 }
+            ]
+        );
+        assert!(!coverage.is_complete());
+        assert!(!coverage.is_headers_only());
+
+        let mut coverage = Coverage::default();
+        assert!(coverage.is_complete());
+        coverage.record_missing_body(0);
+        assert!(coverage.is_headers_only());
+    }
+}
@@ -5,6 +5,7 @@ mod block_metadata_db;
 mod deploy_hashes_db;
 mod deploy_metadata_db;
 mod deploys_db;
+mod env;
 mod finalized_approvals_db;
 mod proposers_db;
 mod state_store_db;
@@ -20,6 +21,7 @@ pub use block_metadata_db::BlockMetadataDatabase;
 pub use deploy_hashes_db::DeployHashesDatabase;
 pub use deploy_metadata_db::DeployMetadataDatabase;
 pub use deploys_db::DeployDatabase;
+pub use env::{get_optional, scan_after, StorageEnv, TrieEnv};
 pub use finalized_approvals_db::FinalizedApprovalsDatabase;
 pub use proposers_db::ProposerDatabase;
 pub use state_store_db::StateStoreDatabase;
@@ -28,21 +30,35 @@ pub use transfer_hashes_db::TransferHashesDatabase;
 
 use std::{
     fmt::{Display, Formatter, Result as FormatterResult},
-    path::Path,
+    path::{Path, PathBuf},
     result::Result,
 };
 
 use bincode::Error as BincodeError;
-use lmdb::{Cursor, Environment, EnvironmentFlags, Error as LmdbError, RoCursor, Transaction};
-use log::info;
+use clap::{Arg, ArgMatches};
+use lmdb::{Environment, EnvironmentFlags, Error as LmdbError};
+use log::{error, info};
 use thiserror::Error;
 
 use casper_types::bytesrepr::Error as BytesreprError;
 
+use crate::common::{
+    storage::Error as StorageError,
+    value_guard::{self, Suspicious},
+};
+
 pub const STORAGE_FILE_NAME: &str = "storage.lmdb";
 pub const TRIE_STORE_FILE_NAME: &str = "data.lmdb";
-const ENTRY_LOG_INTERVAL: usize = 100_000;
+/// Name of the database of the trie store holding the tries, as created by
+/// the execution engine.
+pub const TRIE_DB_NAME: &str = "TRIE_STORE";
+/// Name of the data file of an LMDB environment in the directory layout.
+pub const DIR_LAYOUT_DATA_FILE_NAME: &str = "data.mdb";
+pub(crate) const ENTRY_LOG_INTERVAL: usize = 100_000;
 const MAX_DB_READERS: u32 = 100;
+const NO_META_SYNC: &str = "nometasync";
+const NO_READAHEAD: &str = "no-readahead";
+const WRITE_MAP: &str = "writemap";
 
 #[derive(Debug, Error)]
 pub enum DeserializationError {
@@ -50,6 +66,8 @@ pub enum DeserializationError {
     BincodeError(#[from] BincodeError),
     #[error("failed parsing struct with bytesrepr")]
     BytesreprError(String),
+    #[error("refused to parse {0}, which may be corrupt")]
+    Suspicious(Suspicious),
 }
 
 impl From<BytesreprError> for DeserializationError {
@@ -58,87 +76,300 @@ impl From<BytesreprError> for DeserializationError {
     }
 }
 
+impl From<Suspicious> for DeserializationError {
+    fn from(suspicious: Suspicious) -> Self {
+        Self::Suspicious(suspicious)
+    }
+}
+
 /// Errors encountered when operating on the storage database.
 #[derive(Debug, Error)]
 pub enum Error {
-    /// Errors accumulated when parsing a database with "--no-failfast".
-    Accumulated(Vec<Self>),
+    /// Number of parsing errors found in a database with "--no-failfast".
+    Accumulated(usize),
+    /// Parsing stopped after the maximum number of errors was reached.
+    MaxErrors(usize),
     /// Parsing error on entry at index in the database.
     Parsing(usize, DeserializationError),
     /// Database operation error.
     Database(#[from] LmdbError),
+    /// Error reading a storage backend other than LMDB.
+    Storage(#[from] StorageError),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
         match self {
             Self::Database(e) => write!(f, "Error operating the database: {e}"),
+            Self::Storage(e) => write!(f, "Error reading the database: {e}"),
             Self::Parsing(idx, inner) => write!(f, "Error parsing element {idx}: {inner}"),
-            Self::Accumulated(accumulated_errors) => {
-                writeln!(f, "Errors caught:")?;
-                for error in accumulated_errors {
-                    writeln!(f, "{error}")?;
-                }
-                Ok(())
-            }
+            Self::Accumulated(error_count) => write!(
+                f,
+                "Found {error_count} invalid entries, see the log for details"
+            ),
+            Self::MaxErrors(max_errors) => write!(
+                f,
+                "Stopped after reaching the maximum of {max_errors} invalid entries"
+            ),
         }
     }
 }
 
+/// Tuning of the LMDB environment for a given workload.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EnvTuning {
+    /// Let the OS read ahead of the pages accessed, which speeds up
+    /// sequential scans but wastes I/O on random lookups.
+    pub readahead: bool,
+    /// Use a writeable memory map, avoiding a copy on writes.
+    pub write_map: bool,
+    /// Skip flushing the meta page on commit, trading durability of the
+    /// last transaction for write throughput.
+    pub no_meta_sync: bool,
+}
+
+impl EnvTuning {
+    /// Tuning for workloads doing random lookups.
+    pub const RANDOM_ACCESS: Self = Self {
+        readahead: false,
+        write_map: false,
+        no_meta_sync: false,
+    };
+
+    /// Tuning for workloads scanning whole databases with cursors.
+    pub const SEQUENTIAL: Self = Self {
+        readahead: true,
+        write_map: false,
+        no_meta_sync: false,
+    };
+
+    /// Reads the tuning from the arguments returned by `tuning_args`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        let no_readahead: bool = matches
+            .value_of(NO_READAHEAD)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated");
+        Self {
+            readahead: !no_readahead,
+            write_map: matches.is_present(WRITE_MAP),
+            no_meta_sync: matches.is_present(NO_META_SYNC),
+        }
+    }
+
+    fn flags(&self) -> EnvironmentFlags {
+        let mut flags = EnvironmentFlags::NO_TLS;
+        if !self.readahead {
+            flags |= EnvironmentFlags::NO_READAHEAD;
+        }
+        if self.write_map {
+            flags |= EnvironmentFlags::WRITE_MAP;
+        }
+        if self.no_meta_sync {
+            flags |= EnvironmentFlags::NO_META_SYNC;
+        }
+        flags
+    }
+}
+
+impl Default for EnvTuning {
+    fn default() -> Self {
+        Self::RANDOM_ACCESS
+    }
+}
+
+/// Returns the arguments exposing the LMDB environment tuning, starting at
+/// `display_order`. The default of `--no-readahead` is taken from `defaults`.
+pub fn tuning_args(display_order: usize, defaults: EnvTuning) -> [Arg<'static>; 3] {
+    [
+        Arg::new(NO_READAHEAD)
+            .display_order(display_order)
+            .long(NO_READAHEAD)
+            .takes_value(true)
+            .value_name("BOOL")
+            .possible_values(["true", "false"])
+            .min_values(0)
+            .max_values(1)
+            .require_equals(true)
+            .default_missing_value("true")
+            .default_value(if defaults.readahead { "false" } else { "true" })
+            .help(
+                "Disable OS readahead on the database file. Readahead speeds up \
+                sequential scans but slows down random lookups.",
+            ),
+        Arg::new(WRITE_MAP)
+            .display_order(display_order + 1)
+            .long(WRITE_MAP)
+            .takes_value(false)
+            .help("Use a writeable memory map for the database file."),
+        Arg::new(NO_META_SYNC)
+            .display_order(display_order + 2)
+            .long(NO_META_SYNC)
+            .takes_value(false)
+            .help(
+                "Don't flush the meta page after each commit. Faster writes, but \
+                the last transaction may be lost on a system crash.",
+            ),
+    ]
+}
+
+/// Returns whether the LMDB environment at `path` is in the directory
+/// layout, a directory holding `data.mdb` and `lock.mdb`, rather than in the
+/// layout of the node, a data file with a `-lock` file next to it. New
+/// environments are created in the layout of the node.
+pub fn is_dir_layout<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().is_dir()
+}
+
+/// Returns the path of the data file of the LMDB environment at `path`,
+/// whatever its layout.
+pub fn data_file_path<P: AsRef<Path>>(path: P) -> PathBuf {
+    if is_dir_layout(&path) {
+        path.as_ref().join(DIR_LAYOUT_DATA_FILE_NAME)
+    } else {
+        path.as_ref().to_path_buf()
+    }
+}
+
+/// Returns the flags opening the LMDB environment at `path` in its layout.
+fn layout_flags(path: &Path) -> EnvironmentFlags {
+    if is_dir_layout(path) {
+        EnvironmentFlags::empty()
+    } else {
+        EnvironmentFlags::NO_SUB_DIR
+    }
+}
+
+/// Opens the LMDB environment at `path` tuned for random lookups.
 pub fn db_env<P: AsRef<Path>>(path: P) -> Result<Environment, LmdbError> {
+    db_env_with_tuning(path, EnvTuning::default())
+}
+
+/// Opens the LMDB environment at `path` with the given tuning, in whichever
+/// layout it is.
+pub fn db_env_with_tuning<P: AsRef<Path>>(
+    path: P,
+    tuning: EnvTuning,
+) -> Result<Environment, LmdbError> {
+    let env = Environment::new()
+        .set_flags(tuning.flags() | layout_flags(path.as_ref()))
+        .set_max_dbs(MAX_DB_READERS)
+        .open(path.as_ref())?;
+    Ok(env)
+}
+
+/// Opens the LMDB environment at `path`, creating it if needed, with room
+/// for `map_size` bytes.
+pub fn db_env_with_map_size<P: AsRef<Path>>(
+    path: P,
+    map_size: usize,
+) -> Result<Environment, LmdbError> {
     let env = Environment::new()
-        .set_flags(
-            EnvironmentFlags::NO_SUB_DIR
-                | EnvironmentFlags::NO_TLS
-                | EnvironmentFlags::NO_READAHEAD,
-        )
+        .set_flags(EnvTuning::default().flags() | layout_flags(path.as_ref()))
         .set_max_dbs(MAX_DB_READERS)
+        .set_map_size(map_size)
         .open(path.as_ref())?;
     Ok(env)
 }
 
+/// Callback receiving the key of an element and the result of parsing its
+/// value.
+pub type ElementCallback<'a> =
+    dyn FnMut(&[u8], Result<(), DeserializationError>) -> Result<(), Error> + 'a;
+
 pub trait Database {
     fn db_name() -> &'static str;
 
     /// Parses a value of an entry in a database.
     fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError>;
 
+    /// Parses an entry in a database. Only the value is parsed unless the
+    /// database stores values of different types depending on their key.
+    fn parse_entry(_key: &[u8], bytes: &[u8]) -> Result<(), DeserializationError> {
+        Self::parse_element(bytes)
+    }
+
+    /// Parses an entry like `parse_entry`, failing on empty or oversized
+    /// values rather than handing them to the deserializers.
+    fn check_entry(key: &[u8], bytes: &[u8]) -> Result<(), DeserializationError> {
+        value_guard::check(bytes)?;
+        Self::parse_entry(key, bytes)
+    }
+
     /// Parses all elements of a database by trying to deserialize them sequentially.
-    fn parse_elements(mut cursor: RoCursor, failfast: bool, start_at: usize) -> Result<(), Error> {
+    ///
+    /// Unless `failfast` is set, parsing errors are logged as they occur and
+    /// only their count is returned at the end. Parsing stops once
+    /// `max_errors` errors were found, if given. Empty or oversized values
+    /// aren't parsed but count as errors. The elements are read in batches,
+    /// so a live node growing the map doesn't end the parsing, see
+    /// `scan_after`.
+    fn parse_elements(
+        env: &Environment,
+        failfast: bool,
+        start_at: usize,
+        max_errors: Option<usize>,
+    ) -> Result<(), Error> {
         if start_at > 0 {
             info!("Skipping {} entries.", start_at);
         }
-        let mut error_buffer = vec![];
-        for (idx, (_raw_key, raw_val)) in cursor.iter().skip(start_at).enumerate() {
-            if let Err(e) =
-                Self::parse_element(raw_val).map_err(|parsing_err| Error::Parsing(idx, parsing_err))
+        let mut idx = 0;
+        let mut error_count = 0;
+        scan_after(env, Self::db_name(), None, |raw_key, raw_val| {
+            let entry_idx = idx;
+            idx += 1;
+            if entry_idx < start_at {
+                return Ok(());
+            }
+            if let Err(e) = Self::check_entry(raw_key, raw_val)
+                .map_err(|parsing_err| Error::Parsing(entry_idx, parsing_err))
             {
                 if failfast {
                     return Err(e);
-                } else {
-                    error_buffer.push(e);
+                }
+                error!("{} database: {}", Self::db_name(), e);
+                error_count += 1;
+                if max_errors == Some(error_count) {
+                    return Err(Error::MaxErrors(error_count));
                 }
             }
-            if idx % ENTRY_LOG_INTERVAL == 0 {
-                info!("Parsed {} entries...", idx);
+            if (entry_idx - start_at).is_multiple_of(ENTRY_LOG_INTERVAL) {
+                info!("Parsed {} entries...", entry_idx - start_at);
             }
-        }
+            Ok(())
+        })?;
         info!("Parsing complete.");
-        if !failfast && !error_buffer.is_empty() {
-            return Err(Error::Accumulated(error_buffer));
+        if error_count > 0 {
+            return Err(Error::Accumulated(error_count));
         }
         Ok(())
     }
 
+    /// Parses the elements of the database in key order, starting right after
+    /// the element with key `after_key` if given, and passes the key of each
+    /// element along with its parsing result to `on_element`. Empty or
+    /// oversized values aren't parsed and are passed as failing to.
+    /// Parsing stops at the first error returned by `on_element`. The
+    /// elements are read in batches, so a live node growing the map doesn't
+    /// end the parsing, see `scan_after`.
+    fn parse_elements_after(
+        env: &Environment,
+        after_key: Option<&[u8]>,
+        on_element: &mut ElementCallback,
+    ) -> Result<(), Error> {
+        scan_after(env, Self::db_name(), after_key, |raw_key, raw_val| {
+            on_element(raw_key, Self::check_entry(raw_key, raw_val))
+        })
+    }
+
     /// Validates the database by ensuring every value of an entry can be parsed.
-    fn check_db(env: &Environment, failfast: bool, start_at: usize) -> Result<(), Error> {
+    fn check_db(
+        env: &Environment,
+        failfast: bool,
+        start_at: usize,
+        max_errors: Option<usize>,
+    ) -> Result<(), Error> {
         info!("Checking {} database.", Self::db_name());
-        let txn = env.begin_ro_txn()?;
-        let db = unsafe { txn.open_db(Some(Self::db_name()))? };
-
-        if let Ok(cursor) = txn.open_ro_cursor(db) {
-            Self::parse_elements(cursor, failfast, start_at)?;
-        }
-        Ok(())
+        Self::parse_elements(env, failfast, start_at, max_errors)
     }
 }
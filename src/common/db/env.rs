@@ -0,0 +1,305 @@
+use std::{ops::Deref, path::Path, result::Result};
+
+use lmdb::{
+    Cursor, Database as LmdbDatabase, DatabaseFlags, Environment, Error as LmdbError,
+    RoTransaction, RwTransaction, Transaction,
+};
+use log::warn;
+
+use super::{
+    db_env_with_map_size, db_env_with_tuning, Database, EnvTuning, STORAGE_FILE_NAME, TRIE_DB_NAME,
+    TRIE_STORE_FILE_NAME,
+};
+use crate::common::{lmdb_utils, throttle};
+
+/// Number of entries a scan visits in each of its read-only transactions.
+const SCAN_BATCH_SIZE: usize = 10_000;
+/// Number of times in a row a transaction may fail to start because another
+/// process grew the map before giving up.
+const MAX_MAP_RESIZES: usize = 10;
+
+/// Begins a read-only transaction of `env`. If a node writing to `env` grew
+/// its map since it was opened, adopts the new size and tries again.
+fn begin_ro_txn(env: &Environment) -> Result<RoTransaction<'_>, LmdbError> {
+    let mut resizes = 0;
+    loop {
+        match env.begin_ro_txn() {
+            Err(LmdbError::MapResized) if resizes < MAX_MAP_RESIZES => {
+                resizes += 1;
+                warn!("The database map was resized by another process, adopting the new size.");
+                lmdb_utils::adopt_map_size(env)?;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Runs `f` in a read-only transaction of `env`.
+fn read<R, E, F>(env: &Environment, f: F) -> Result<R, E>
+where
+    E: From<LmdbError>,
+    F: FnOnce(&RoTransaction) -> Result<R, E>,
+{
+    let txn = begin_ro_txn(env)?;
+    let result = f(&txn)?;
+    txn.commit()?;
+    Ok(result)
+}
+
+/// Calls `visit` on every entry of the database named `db_name` in ascending
+/// key order, starting right after `after_key` if given, and stopping at the
+/// first error.
+///
+/// The entries are read `SCAN_BATCH_SIZE` at a time, each batch in its own
+/// read-only transaction, so a long scan of an environment a live node writes
+/// to neither keeps the node from reusing freed pages nor dies when the node
+/// grows the map: the next batch adopts the new size and resumes after the
+/// last visited key. The scan is thus not a consistent view of the database.
+/// Must not be called while another transaction of `env` is active.
+pub fn scan_after<E, F>(
+    env: &Environment,
+    db_name: &str,
+    after_key: Option<&[u8]>,
+    visit: F,
+) -> Result<(), E>
+where
+    E: From<LmdbError>,
+    F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+{
+    scan_in_batches(env, db_name, after_key, SCAN_BATCH_SIZE, visit)
+}
+
+pub(super) fn scan_in_batches<E, F>(
+    env: &Environment,
+    db_name: &str,
+    after_key: Option<&[u8]>,
+    batch_size: usize,
+    mut visit: F,
+) -> Result<(), E>
+where
+    E: From<LmdbError>,
+    F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+{
+    let mut last_key = after_key.map(<[u8]>::to_vec);
+    loop {
+        let txn = begin_ro_txn(env)?;
+        let db = unsafe { txn.open_db(Some(db_name))? };
+        let mut cursor = txn.open_ro_cursor(db)?;
+        let iter = match last_key.as_deref() {
+            Some(key) => cursor.iter_from(key),
+            None => cursor.iter(),
+        };
+        let mut visited = 0;
+        let mut batch_last_key = None;
+        for (raw_key, raw_val) in iter {
+            if Some(raw_key) == last_key.as_deref() {
+                continue;
+            }
+            throttle::consume(raw_key.len() + raw_val.len());
+            visit(raw_key, raw_val)?;
+            visited += 1;
+            if visited == batch_size {
+                batch_last_key = Some(raw_key.to_vec());
+                break;
+            }
+        }
+        drop(cursor);
+        txn.commit()?;
+        match batch_last_key {
+            Some(key) => last_key = Some(key),
+            None => return Ok(()),
+        }
+    }
+}
+
+/// Runs `f` in a read-write transaction of `env`, committing it if `f`
+/// succeeds and aborting it otherwise.
+fn write<R, E, F>(env: &Environment, f: F) -> Result<R, E>
+where
+    E: From<LmdbError>,
+    F: FnOnce(&mut RwTransaction) -> Result<R, E>,
+{
+    let mut txn = env.begin_rw_txn()?;
+    let result = f(&mut txn)?;
+    txn.commit()?;
+    Ok(result)
+}
+
+/// The LMDB environment of a node's `storage.lmdb` file.
+pub struct StorageEnv {
+    env: Environment,
+}
+
+impl StorageEnv {
+    /// Opens the storage database in the directory `db_dir`, tuned for random
+    /// lookups.
+    pub fn open<P: AsRef<Path>>(db_dir: P) -> Result<Self, LmdbError> {
+        Self::open_with_tuning(db_dir, EnvTuning::default())
+    }
+
+    /// Opens the storage database in the directory `db_dir` with the given
+    /// tuning.
+    pub fn open_with_tuning<P: AsRef<Path>>(
+        db_dir: P,
+        tuning: EnvTuning,
+    ) -> Result<Self, LmdbError> {
+        let env = db_env_with_tuning(db_dir.as_ref().join(STORAGE_FILE_NAME), tuning)?;
+        Ok(Self { env })
+    }
+
+    /// Opens the storage database in the directory `db_dir`, creating it if
+    /// needed, with room for `map_size` bytes.
+    pub fn create<P: AsRef<Path>>(db_dir: P, map_size: usize) -> Result<Self, LmdbError> {
+        let env = db_env_with_map_size(db_dir.as_ref().join(STORAGE_FILE_NAME), map_size)?;
+        Ok(Self { env })
+    }
+
+    /// Returns the handle of the database `D`, failing with
+    /// `LmdbError::NotFound` if it doesn't exist.
+    pub fn db<D: Database>(&self) -> Result<LmdbDatabase, LmdbError> {
+        self.env.open_db(Some(D::db_name()))
+    }
+
+    /// Returns the handle of the database `D`, or `None` if it doesn't exist.
+    pub fn optional_db<D: Database>(&self) -> Result<Option<LmdbDatabase>, LmdbError> {
+        match self.db::<D>() {
+            Ok(db) => Ok(Some(db)),
+            Err(LmdbError::NotFound) => Ok(None),
+            Err(lmdb_err) => Err(lmdb_err),
+        }
+    }
+
+    /// Returns the handle of the database `D`, creating it if it doesn't
+    /// exist.
+    pub fn get_or_create_db<D: Database>(&self) -> Result<LmdbDatabase, LmdbError> {
+        self.env
+            .create_db(Some(D::db_name()), DatabaseFlags::empty())
+    }
+
+    /// Runs `f` in a read-only transaction.
+    pub fn read<R, E, F>(&self, f: F) -> Result<R, E>
+    where
+        E: From<LmdbError>,
+        F: FnOnce(&RoTransaction) -> Result<R, E>,
+    {
+        read(&self.env, f)
+    }
+
+    /// Calls `visit` on every entry of the database `D` in ascending key
+    /// order, stopping at the first error. See `scan_after` for how the scan
+    /// survives a live node growing the map.
+    pub fn scan<D, E, F>(&self, visit: F) -> Result<(), E>
+    where
+        D: Database,
+        E: From<LmdbError>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        scan_after(&self.env, D::db_name(), None, visit)
+    }
+
+    /// Runs `f` in a read-write transaction, committing it if `f` succeeds
+    /// and aborting it otherwise.
+    pub fn write<R, E, F>(&self, f: F) -> Result<R, E>
+    where
+        E: From<LmdbError>,
+        F: FnOnce(&mut RwTransaction) -> Result<R, E>,
+    {
+        write(&self.env, f)
+    }
+}
+
+impl Deref for StorageEnv {
+    type Target = Environment;
+
+    fn deref(&self) -> &Environment {
+        &self.env
+    }
+}
+
+/// The LMDB environment of a node's `data.lmdb` trie store, which keeps all
+/// the tries in the database named `TRIE_STORE`.
+pub struct TrieEnv {
+    env: Environment,
+}
+
+impl TrieEnv {
+    /// Opens the trie store in the directory `db_dir`, tuned for random
+    /// lookups.
+    pub fn open<P: AsRef<Path>>(db_dir: P) -> Result<Self, LmdbError> {
+        Self::open_with_tuning(db_dir, EnvTuning::default())
+    }
+
+    /// Opens the trie store in the directory `db_dir` with the given tuning.
+    pub fn open_with_tuning<P: AsRef<Path>>(
+        db_dir: P,
+        tuning: EnvTuning,
+    ) -> Result<Self, LmdbError> {
+        let env = db_env_with_tuning(db_dir.as_ref().join(TRIE_STORE_FILE_NAME), tuning)?;
+        Ok(Self { env })
+    }
+
+    /// Opens the trie store in the directory `db_dir`, creating it and its
+    /// database if needed, with room for `map_size` bytes.
+    pub fn create<P: AsRef<Path>>(db_dir: P, map_size: usize) -> Result<Self, LmdbError> {
+        let env = db_env_with_map_size(db_dir.as_ref().join(TRIE_STORE_FILE_NAME), map_size)?;
+        env.create_db(Some(TRIE_DB_NAME), DatabaseFlags::empty())?;
+        Ok(Self { env })
+    }
+
+    /// Returns the handle of the database holding the tries.
+    pub fn db(&self) -> Result<LmdbDatabase, LmdbError> {
+        self.env.open_db(Some(TRIE_DB_NAME))
+    }
+
+    /// Runs `f` in a read-only transaction.
+    pub fn read<R, E, F>(&self, f: F) -> Result<R, E>
+    where
+        E: From<LmdbError>,
+        F: FnOnce(&RoTransaction) -> Result<R, E>,
+    {
+        read(&self.env, f)
+    }
+
+    /// Calls `visit` on every trie in ascending key order, stopping at the
+    /// first error. See `scan_after` for how the scan survives a live node
+    /// growing the map.
+    pub fn scan<E, F>(&self, visit: F) -> Result<(), E>
+    where
+        E: From<LmdbError>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        scan_after(&self.env, TRIE_DB_NAME, None, visit)
+    }
+
+    /// Runs `f` in a read-write transaction, committing it if `f` succeeds
+    /// and aborting it otherwise.
+    pub fn write<R, E, F>(&self, f: F) -> Result<R, E>
+    where
+        E: From<LmdbError>,
+        F: FnOnce(&mut RwTransaction) -> Result<R, E>,
+    {
+        write(&self.env, f)
+    }
+}
+
+impl Deref for TrieEnv {
+    type Target = Environment;
+
+    fn deref(&self) -> &Environment {
+        &self.env
+    }
+}
+
+/// Returns the value stored under `key` in `db`, or `None` if there is no
+/// such entry.
+pub fn get_optional<'txn, T: Transaction, K: AsRef<[u8]>>(
+    txn: &'txn T,
+    db: LmdbDatabase,
+    key: &K,
+) -> Result<Option<&'txn [u8]>, LmdbError> {
+    match txn.get(db, key) {
+        Ok(raw_val) => Ok(Some(raw_val)),
+        Err(LmdbError::NotFound) => Ok(None),
+        Err(lmdb_err) => Err(lmdb_err),
+    }
+}
@@ -3,9 +3,8 @@ use std::{
     result::Result,
 };
 
-use casper_types::bytesrepr::FromBytes;
-
 use super::{Database, DeserializationError};
+use crate::common::state_store;
 
 pub struct StateStoreDatabase;
 
@@ -20,8 +19,12 @@ impl Database for StateStoreDatabase {
         "state_store"
     }
 
-    fn parse_element(bytes: &[u8]) -> Result<(), DeserializationError> {
-        let _: u64 = FromBytes::from_bytes(bytes)?.0;
+    /// Values can only be decoded along with their key, see `parse_entry`.
+    fn parse_element(_bytes: &[u8]) -> Result<(), DeserializationError> {
         Ok(())
     }
+
+    fn parse_entry(key: &[u8], bytes: &[u8]) -> Result<(), DeserializationError> {
+        state_store::parse_entry(key, bytes).map(|_| ())
+    }
 }
@@ -1,9 +1,23 @@
-use lmdb::{Database as LmdbDatabase, Environment, Transaction, WriteFlags};
+use casper_execution_engine::storage::{
+    store::StoreExt,
+    transaction_source::{lmdb::LmdbEnvironment, TransactionSource},
+    trie_store::lmdb::LmdbTrieStore,
+};
+use casper_types::bytesrepr::ToBytes;
+use clap::Command;
+use lmdb::{Database as LmdbDatabase, DatabaseFlags, Environment, Transaction, WriteFlags};
 use rand::{self, prelude::ThreadRng, Rng, RngCore};
 use serde::{Deserialize, Serialize};
 
-use super::{Database, DeserializationError};
-use crate::test_utils::LmdbTestFixture;
+use super::{
+    db_env_with_tuning, env::scan_in_batches, get_optional, tuning_args, BlockHeaderDatabase,
+    Database, DeployDatabase, DeserializationError, EnvTuning, Error, StorageEnv, TrieEnv,
+};
+use crate::{
+    common::{lmdb_utils, value_guard::Suspicious},
+    subcommands::trie_compact::tests::create_data,
+    test_utils::LmdbTestFixture,
+};
 
 fn gen_bytes(rng: &mut ThreadRng) -> Vec<u8> {
     let mock = MockStruct::random(rng);
@@ -144,10 +158,10 @@ fn good_db_should_pass_check() {
     let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
     populate_db(&fixture.env, fixture.db(Some(MockDb::db_name())).unwrap());
 
-    assert!(MockDb::check_db(&fixture.env, true, 0).is_ok());
-    assert!(MockDb::check_db(&fixture.env, false, 0).is_ok());
-    assert!(MockDb::check_db(&fixture.env, true, 4).is_ok());
-    assert!(MockDb::check_db(&fixture.env, false, 4).is_ok());
+    assert!(MockDb::check_db(&fixture.env, true, 0, None).is_ok());
+    assert!(MockDb::check_db(&fixture.env, false, 0, None).is_ok());
+    assert!(MockDb::check_db(&fixture.env, true, 4, None).is_ok());
+    assert!(MockDb::check_db(&fixture.env, false, 4, None).is_ok());
 }
 
 #[test]
@@ -155,8 +169,237 @@ fn bad_db_should_fail_check() {
     let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
     populate_faulty_db(&fixture.env, fixture.db(Some(MockDb::db_name())).unwrap());
 
-    assert!(MockDb::check_db(&fixture.env, true, 0).is_err());
-    assert!(MockDb::check_db(&fixture.env, false, 0).is_err());
-    assert!(MockDb::check_db(&fixture.env, true, 4).is_err());
-    assert!(MockDb::check_db(&fixture.env, false, 4).is_err());
+    assert!(MockDb::check_db(&fixture.env, true, 0, None).is_err());
+    assert!(MockDb::check_db(&fixture.env, false, 0, None).is_err());
+    assert!(MockDb::check_db(&fixture.env, true, 4, None).is_err());
+    assert!(MockDb::check_db(&fixture.env, false, 4, None).is_err());
+}
+
+#[test]
+fn bad_db_check_should_count_errors() {
+    let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
+    populate_faulty_db(&fixture.env, fixture.db(Some(MockDb::db_name())).unwrap());
+
+    // Every 5th entry is faulty, starting with the first one.
+    let entry_count = {
+        let txn = fixture.env.begin_ro_txn().unwrap();
+        let db = *fixture.db(Some(MockDb::db_name())).unwrap();
+        let count = lmdb_utils::entry_count(&txn, db).unwrap();
+        txn.commit().unwrap();
+        count
+    };
+    let expected_errors = entry_count.div_ceil(5);
+    match MockDb::check_db(&fixture.env, false, 0, None) {
+        Err(Error::Accumulated(error_count)) => assert_eq!(error_count, expected_errors),
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    match MockDb::check_db(&fixture.env, false, 0, Some(2)) {
+        Err(Error::MaxErrors(2)) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    // The limit isn't reached if there are fewer errors.
+    match MockDb::check_db(&fixture.env, false, 0, Some(expected_errors + 1)) {
+        Err(Error::Accumulated(error_count)) => assert_eq!(error_count, expected_errors),
+        other => panic!("Unexpected result: {other:?}"),
+    }
+}
+
+#[test]
+fn check_should_report_empty_values() {
+    let fixture = LmdbTestFixture::new(vec![MockDb::db_name()], None);
+    let db = *fixture.db(Some(MockDb::db_name())).unwrap();
+    populate_db(&fixture.env, &db);
+    // An empty value, and a garbage one claiming a huge string length.
+    let mut rw_tx = fixture.env.begin_rw_txn().unwrap();
+    rw_tx
+        .put(db, &u32::MAX.to_le_bytes(), &[], WriteFlags::empty())
+        .unwrap();
+    let mut garbage = vec![0u8; 4];
+    garbage.extend_from_slice(&u64::MAX.to_le_bytes());
+    rw_tx
+        .put(
+            db,
+            &(u32::MAX - 1).to_le_bytes(),
+            &garbage,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    rw_tx.commit().unwrap();
+
+    // The empty value is reported along with the garbage one.
+    match MockDb::check_db(&fixture.env, false, 0, None) {
+        Err(Error::Accumulated(2)) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    match MockDb::check_db(&fixture.env, false, 0, Some(2)) {
+        Err(Error::MaxErrors(2)) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    // The garbage value comes first in key order, the empty one right after.
+    let empty_idx = match MockDb::check_db(&fixture.env, true, 0, None) {
+        Err(Error::Parsing(idx, DeserializationError::BincodeError(_))) => idx + 1,
+        other => panic!("Unexpected result: {other:?}"),
+    };
+    match MockDb::check_db(&fixture.env, true, empty_idx, None) {
+        Err(Error::Parsing(idx, DeserializationError::Suspicious(Suspicious::Empty)))
+            if idx == empty_idx => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+
+    let mut results = vec![];
+    MockDb::parse_elements_after(&fixture.env, None, &mut |raw_key, parse_result| {
+        results.push((raw_key.to_vec(), parse_result.is_ok()));
+        Ok(())
+    })
+    .unwrap();
+    let failed: Vec<Vec<u8>> = results
+        .into_iter()
+        .filter_map(|(raw_key, is_ok)| (!is_ok).then_some(raw_key))
+        .collect();
+    assert_eq!(
+        failed,
+        vec![
+            (u32::MAX - 1).to_le_bytes().to_vec(),
+            u32::MAX.to_le_bytes().to_vec()
+        ]
+    );
+}
+
+#[test]
+fn env_tuning_from_args() {
+    let command = |defaults| Command::new("test").args(tuning_args(0, defaults));
+
+    let matches = command(EnvTuning::SEQUENTIAL).get_matches_from(["test"]);
+    assert_eq!(EnvTuning::from_matches(&matches), EnvTuning::SEQUENTIAL);
+    let matches = command(EnvTuning::RANDOM_ACCESS).get_matches_from(["test"]);
+    assert_eq!(EnvTuning::from_matches(&matches), EnvTuning::RANDOM_ACCESS);
+
+    let matches = command(EnvTuning::SEQUENTIAL).get_matches_from(["test", "--no-readahead"]);
+    assert!(!EnvTuning::from_matches(&matches).readahead);
+    let matches = command(EnvTuning::RANDOM_ACCESS).get_matches_from([
+        "test",
+        "--no-readahead=false",
+        "--writemap",
+        "--nometasync",
+    ]);
+    assert_eq!(
+        EnvTuning::from_matches(&matches),
+        EnvTuning {
+            readahead: true,
+            write_map: true,
+            no_meta_sync: true,
+        }
+    );
+
+    // The environment opens with any combination of flags.
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let env = db_env_with_tuning(
+        tmp_dir.path().join("tuned.lmdb"),
+        EnvTuning::from_matches(&matches),
+    )
+    .unwrap();
+    assert!(env.begin_rw_txn().unwrap().commit().is_ok());
+}
+
+#[test]
+fn storage_env_round_trip() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let env = StorageEnv::create(tmp_dir.path(), 1 << 20).unwrap();
+    env.write(|txn| -> Result<(), lmdb::Error> {
+        let db =
+            unsafe { txn.create_db(Some(BlockHeaderDatabase::db_name()), DatabaseFlags::empty())? };
+        txn.put(db, &[1u8], &[2u8], WriteFlags::empty())
+    })
+    .unwrap();
+    drop(env);
+
+    let env = StorageEnv::open(tmp_dir.path()).unwrap();
+    assert!(env.optional_db::<DeployDatabase>().unwrap().is_none());
+    let db = env.optional_db::<BlockHeaderDatabase>().unwrap().unwrap();
+    let (present, absent) = env
+        .read(|txn| -> Result<_, lmdb::Error> {
+            Ok((
+                get_optional(txn, db, &[1u8])?.map(<[u8]>::to_vec),
+                get_optional(txn, db, &[3u8])?.map(<[u8]>::to_vec),
+            ))
+        })
+        .unwrap();
+    assert_eq!(present, Some(vec![2u8]));
+    assert_eq!(absent, None);
+}
+
+#[test]
+fn trie_env_should_read_execution_engine_trie_store() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let data = create_data();
+    {
+        let env = LmdbEnvironment::new(tmp_dir.path(), 1 << 20, 512, true).unwrap();
+        let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
+        let mut txn = env.create_read_write_txn().unwrap();
+        store
+            .put_many(&mut txn, data.iter().map(Into::into))
+            .unwrap();
+        txn.commit().unwrap();
+        env.env().sync(true).unwrap();
+    }
+
+    let env = TrieEnv::open(tmp_dir.path()).unwrap();
+    let db = env.db().unwrap();
+    let raw_tries = env
+        .read(|txn| -> Result<_, lmdb::Error> {
+            data.iter()
+                .map(|test_data| Ok(get_optional(txn, db, &test_data.0)?.map(<[u8]>::to_vec)))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .unwrap();
+    for (test_data, raw_trie) in data.iter().zip(raw_tries) {
+        assert_eq!(raw_trie, Some(test_data.1.to_bytes().unwrap()));
+    }
+}
+
+#[test]
+fn scan_in_batches_should_resume_after_last_key() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let env = StorageEnv::create(tmp_dir.path(), 1 << 20).unwrap();
+    env.write(|txn| -> Result<(), lmdb::Error> {
+        let db =
+            unsafe { txn.create_db(Some(BlockHeaderDatabase::db_name()), DatabaseFlags::empty())? };
+        for key in 0..7u8 {
+            txn.put(db, &[key], &[key * 2], WriteFlags::empty())?;
+        }
+        Ok(())
+    })
+    .unwrap();
+
+    let scan = |after_key: Option<&[u8]>, batch_size| {
+        let mut entries = vec![];
+        scan_in_batches(
+            &env,
+            BlockHeaderDatabase::db_name(),
+            after_key,
+            batch_size,
+            |raw_key, raw_val| -> Result<(), lmdb::Error> {
+                entries.push((raw_key[0], raw_val[0]));
+                Ok(())
+            },
+        )
+        .unwrap();
+        entries
+    };
+    let all_entries: Vec<(u8, u8)> = (0..7).map(|key| (key, key * 2)).collect();
+    // Batches of 3 end in the middle of the database, one of 7 at its end.
+    assert_eq!(scan(None, 3), all_entries);
+    assert_eq!(scan(None, 7), all_entries);
+    assert_eq!(scan(Some(&[2u8]), 2), all_entries[3..]);
+    assert!(scan(Some(&[6u8]), 2).is_empty());
+
+    // Adopting the size of an unchanged map keeps the environment usable.
+    lmdb_utils::adopt_map_size(&env).unwrap();
+    let mut count = 0;
+    env.scan::<BlockHeaderDatabase, _, _>(|_, _| -> Result<(), lmdb::Error> {
+        count += 1;
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!(count, 7);
 }
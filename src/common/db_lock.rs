@@ -0,0 +1,116 @@
+//! Advisory lock keeping runs of this tool from modifying the same storage
+//! database concurrently.
+//!
+//! LMDB serializes write transactions, but a subcommand modifying a database
+//! usually spreads its changes over several of them, so two such runs, like
+//! a scheduled purge and a manual removal, could interleave their changes.
+//! The subcommands modifying a database hold an exclusive `flock` on a file
+//! next to it while they run, which the system releases when the process
+//! exits, however it exits. The other subcommands don't take the lock, but
+//! warn when it is held as what they read may be changing.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error as IoError, ErrorKind},
+    os::unix::io::AsRawFd,
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use thiserror::Error as ThisError;
+
+/// Name of the lock file in a database directory.
+pub const LOCK_FILE_NAME: &str = "storage.lmdb.db-utils.lock";
+
+/// Errors encountered when taking or probing the lock of a database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening or locking the lock file.
+    #[error("Error locking {0}: {1}")]
+    Io(PathBuf, IoError),
+    /// Another run holds the lock.
+    #[error(
+        "Another run of casper-db-utils is modifying the database in {0}, \
+        as it holds the lock {1}"
+    )]
+    Locked(PathBuf, PathBuf),
+}
+
+/// Applies the `flock` `operation` to `file` without blocking. Returns
+/// `false` if another open file holds a conflicting lock.
+fn try_flock(file: &File, operation: libc::c_int) -> Result<bool, IoError> {
+    let result = unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) };
+    if result == 0 {
+        return Ok(true);
+    }
+    let io_err = IoError::last_os_error();
+    match io_err.kind() {
+        ErrorKind::WouldBlock => Ok(false),
+        _ => Err(io_err),
+    }
+}
+
+/// Exclusive lock on the database of a directory, released when dropped.
+#[derive(Debug)]
+pub struct DbLock {
+    _file: File,
+}
+
+impl DbLock {
+    /// Takes the lock on the database in `db_dir`, creating the lock file if
+    /// needed. Fails without waiting if another run holds it.
+    pub fn acquire<P: AsRef<Path>>(db_dir: P) -> Result<Self, Error> {
+        let db_dir = db_dir.as_ref();
+        let path = db_dir.join(LOCK_FILE_NAME);
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(|io_err| Error::Io(path.clone(), io_err))?;
+        match try_flock(&file, libc::LOCK_EX) {
+            Ok(true) => Ok(Self { _file: file }),
+            Ok(false) => Err(Error::Locked(db_dir.to_path_buf(), path)),
+            Err(io_err) => Err(Error::Io(path, io_err)),
+        }
+    }
+}
+
+/// Returns whether a run holds the lock on the database in `db_dir`. The
+/// lock file isn't created if it doesn't exist.
+pub fn is_locked<P: AsRef<Path>>(db_dir: P) -> Result<bool, Error> {
+    let path = db_dir.as_ref().join(LOCK_FILE_NAME);
+    let file = match File::open(&path) {
+        Ok(file) => file,
+        Err(io_err) if io_err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(io_err) => return Err(Error::Io(path, io_err)),
+    };
+    // The shared lock is released along with the file right away.
+    try_flock(&file, libc::LOCK_SH)
+        .map(|acquired| !acquired)
+        .map_err(|io_err| Error::Io(path, io_err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_locked, DbLock, Error, LOCK_FILE_NAME};
+
+    #[test]
+    fn db_lock_should_be_exclusive() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        assert!(!is_locked(tmp_dir.path()).unwrap());
+        assert!(!tmp_dir.path().join(LOCK_FILE_NAME).exists());
+
+        let lock = DbLock::acquire(tmp_dir.path()).unwrap();
+        assert!(is_locked(tmp_dir.path()).unwrap());
+        match DbLock::acquire(tmp_dir.path()) {
+            Err(Error::Locked(db_dir, _)) => assert_eq!(db_dir, tmp_dir.path()),
+            Err(error) => panic!("Got unexpected error: {error:?}"),
+            Ok(_) => panic!("Lock unexpectedly acquired twice"),
+        }
+
+        drop(lock);
+        assert!(!is_locked(tmp_dir.path()).unwrap());
+        let _lock = DbLock::acquire(tmp_dir.path()).unwrap();
+    }
+}
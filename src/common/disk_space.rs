@@ -0,0 +1,118 @@
+use std::{
+    ffi::CString,
+    fs,
+    io::{Error as IoError, ErrorKind},
+    mem::MaybeUninit,
+    os::unix::{ffi::OsStrExt, fs::MetadataExt},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use log::{info, warn};
+use thiserror::Error as ThisError;
+
+/// Errors encountered when checking the space available at a destination.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error querying the file system.
+    #[error("Couldn't probe available space at {0}: {1}")]
+    Probe(PathBuf, IoError),
+    /// The destination doesn't have enough free space.
+    #[error(
+        "Not enough free space at {path}: about {required} bytes are needed but only \
+        {available} bytes are available. Use `--force` to proceed anyway."
+    )]
+    Insufficient {
+        path: PathBuf,
+        required: u64,
+        available: u64,
+    },
+}
+
+/// Returns the number of bytes available to unprivileged users on the file
+/// system holding `path`. If `path` doesn't exist yet, its closest existing
+/// ancestor is queried instead.
+pub fn available_space<P: AsRef<Path>>(path: P) -> Result<u64, IoError> {
+    let mut probed_path = path.as_ref();
+    while !probed_path.exists() {
+        probed_path = match probed_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+    }
+    let c_path = CString::new(probed_path.as_os_str().as_bytes())
+        .map_err(|nul_err| IoError::new(ErrorKind::InvalidInput, nul_err))?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    let result = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if result != 0 {
+        return Err(IoError::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Ok(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+/// Returns the number of bytes actually allocated on disk for the file at
+/// `path`, which for sparse LMDB files can be much less than their length.
+pub fn allocated_size<P: AsRef<Path>>(path: P) -> Result<u64, IoError> {
+    Ok(fs::metadata(path)?.blocks() * 512)
+}
+
+/// Checks that at least `required` bytes are available at `path`. When
+/// `force` is set, a lack of space is only logged as a warning.
+pub fn check_available_space<P: AsRef<Path>>(
+    path: P,
+    required: u64,
+    force: bool,
+) -> Result<(), Error> {
+    let path = path.as_ref();
+    let available =
+        available_space(path).map_err(|io_err| Error::Probe(path.to_path_buf(), io_err))?;
+    if available >= required {
+        info!(
+            "{} bytes available at {}, about {} bytes needed.",
+            available,
+            path.display(),
+            required
+        );
+        return Ok(());
+    }
+    let error = Error::Insufficient {
+        path: path.to_path_buf(),
+        required,
+        available,
+    };
+    if force {
+        warn!("{}", error);
+        Ok(())
+    } else {
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{allocated_size, available_space, check_available_space, Error};
+
+    #[test]
+    fn space_checks() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let available = available_space(tmp_dir.path()).unwrap();
+        assert!(available > 0);
+        // Paths which don't exist yet are resolved to their closest ancestor.
+        assert!(available_space(tmp_dir.path().join("not/yet/created")).is_ok());
+
+        assert!(check_available_space(tmp_dir.path(), 1, false).is_ok());
+        assert!(matches!(
+            check_available_space(tmp_dir.path(), u64::MAX, false),
+            Err(Error::Insufficient { .. })
+        ));
+        assert!(check_available_space(tmp_dir.path(), u64::MAX, true).is_ok());
+
+        let file_path = tmp_dir.path().join("file");
+        fs::write(&file_path, vec![1u8; 8192]).unwrap();
+        assert!(allocated_size(&file_path).unwrap() > 0);
+    }
+}
@@ -0,0 +1,85 @@
+//! HTTP servers answering queries on a storage database, shared by the
+//! `serve` and `rpc-shim` subcommands.
+
+use std::{
+    collections::BTreeMap, convert::Infallible, future::Future, io::Error as IoError,
+    net::SocketAddr, result::Result, sync::Arc,
+};
+
+use casper_node::types::BlockHash;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Error as HyperError, Request, Response, Server,
+};
+use log::info;
+use thiserror::Error as ThisError;
+use tokio::runtime::Builder as TokioRuntimeBuilder;
+
+use super::{
+    block_iter::{self, Error as BlockIterError},
+    db::StorageEnv,
+    storage::{Error as StorageError, LmdbReader},
+};
+
+/// Errors encountered when running an HTTP server.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error creating the async runtime.
+    #[error("Error creating tokio runtime: {0}")]
+    Runtime(IoError),
+    /// HTTP server error.
+    #[error("HTTP server error: {0}")]
+    Server(#[from] HyperError),
+}
+
+/// Returns the hashes of the blocks of the storage database in `env` indexed
+/// by height, for servers to look blocks up by height.
+pub fn index_heights(env: &StorageEnv) -> Result<BTreeMap<u64, BlockHash>, BlockIterError> {
+    info!("Indexing block heights.");
+    let txn = env.begin_ro_txn().map_err(StorageError::from)?;
+    let heights: BTreeMap<u64, BlockHash> = block_iter::blocks_by_height(&LmdbReader::new(&txn))?
+        .into_iter()
+        .collect();
+    info!("Indexed {} blocks.", heights.len());
+    Ok(heights)
+}
+
+/// Answers the requests sent to `address` with `handler` until interrupted,
+/// logging the URL of `path` once listening. Handlers should read the
+/// database in `tokio::task::spawn_blocking`, as the reads block.
+pub fn serve<S, H, F>(
+    address: SocketAddr,
+    path: &str,
+    state: Arc<S>,
+    handler: H,
+) -> Result<(), Error>
+where
+    S: Send + Sync + 'static,
+    H: Fn(Arc<S>, Request<Body>) -> F + Copy + Send + Sync + 'static,
+    F: Future<Output = Response<Body>> + Send + 'static,
+{
+    let runtime = TokioRuntimeBuilder::new_multi_thread()
+        .enable_all()
+        .build()
+        .map_err(Error::Runtime)?;
+    runtime.block_on(async move {
+        let make_service = make_service_fn(move |_conn| {
+            let state = Arc::clone(&state);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = Arc::clone(&state);
+                    async move { Ok::<_, Infallible>(handler(state, request).await) }
+                }))
+            }
+        });
+        let server = Server::try_bind(&address)?.serve(make_service);
+        info!("Listening on http://{}{}", server.local_addr(), path);
+        server
+            .with_graceful_shutdown(async {
+                let _ = tokio::signal::ctrl_c().await;
+                info!("Shutting down.");
+            })
+            .await?;
+        Ok(())
+    })
+}
@@ -0,0 +1,206 @@
+//! Human readable sizes and durations in the JSON reports.
+//!
+//! With the `--human` argument, every size or duration field of a report is
+//! followed by a `<field>_human` field holding it formatted with units, like
+//! `450.00 GiB` or `2h 5m 10s`. The raw fields are kept as they are, so
+//! parsers of the report aren't affected.
+
+use std::io::Write;
+
+use clap::{Arg, ArgMatches};
+use serde::Serialize;
+use serde_json::{Error as SerializationError, Map, Value};
+
+const HUMAN: &str = "human";
+const SIZE_UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+const DURATION_UNITS: [(&str, u64); 3] = [("d", 86_400), ("h", 3_600), ("m", 60)];
+
+/// Unit of the raw value of a report field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Bytes,
+    Seconds,
+}
+
+/// Formats `bytes` with the largest binary unit it holds at least one of.
+pub fn format_size(bytes: f64) -> String {
+    let mut size = bytes;
+    let mut unit_idx = 0;
+    while size >= 1024.0 && unit_idx < SIZE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_idx += 1;
+    }
+    if unit_idx == 0 {
+        format!("{:.0} B", size)
+    } else {
+        format!("{:.2} {}", size, SIZE_UNITS[unit_idx])
+    }
+}
+
+/// Formats `secs` in days, hours, minutes and seconds, omitting the leading
+/// zero units. Durations under a minute keep a decimal.
+pub fn format_duration(secs: f64) -> String {
+    if secs < 60.0 {
+        return format!("{:.1}s", secs);
+    }
+    let mut remaining = secs.round() as u64;
+    let mut parts = vec![];
+    for (unit, unit_secs) in DURATION_UNITS {
+        if remaining >= unit_secs || !parts.is_empty() {
+            parts.push(format!("{}{}", remaining / unit_secs, unit));
+            remaining %= unit_secs;
+        }
+    }
+    parts.push(format!("{}s", remaining));
+    parts.join(" ")
+}
+
+fn format_value(value: &Value, unit: Unit) -> Option<Value> {
+    match value {
+        Value::Number(number) => {
+            let number = number.as_f64()?;
+            Some(Value::String(match unit {
+                Unit::Bytes => format_size(number),
+                Unit::Seconds => format_duration(number),
+            }))
+        }
+        // Statistics of a field, like its average and maximum.
+        Value::Object(object) => Some(Value::Object(
+            object
+                .iter()
+                .filter_map(|(key, value)| Some((key.clone(), format_value(value, unit)?)))
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// Adds a `<field>_human` field after each of the `fields` in `value`, at
+/// any depth, formatted with the units of the field.
+pub fn humanize(value: &mut Value, fields: &[(&str, Unit)]) {
+    match value {
+        Value::Object(object) => {
+            let mut humanized = Map::new();
+            for (key, mut field_value) in std::mem::take(object) {
+                humanize(&mut field_value, fields);
+                let maybe_human = fields
+                    .iter()
+                    .find(|(name, _)| *name == key)
+                    .and_then(|(_, unit)| format_value(&field_value, *unit));
+                let human_key = format!("{}_human", key);
+                humanized.insert(key, field_value);
+                if let Some(human) = maybe_human {
+                    humanized.insert(human_key, human);
+                }
+            }
+            *object = humanized;
+        }
+        Value::Array(values) => {
+            for element in values {
+                humanize(element, fields);
+            }
+        }
+        _ => (),
+    }
+}
+
+/// Writes `report` as pretty JSON, with the human readable `fields` added
+/// if `human` is set.
+pub fn to_writer_pretty<W: Write, T: Serialize>(
+    writer: W,
+    report: &T,
+    human: bool,
+    fields: &[(&str, Unit)],
+) -> Result<(), SerializationError> {
+    if !human {
+        return serde_json::to_writer_pretty(writer, report);
+    }
+    let mut value = serde_json::to_value(report)?;
+    humanize(&mut value, fields);
+    serde_json::to_writer_pretty(writer, &value)
+}
+
+/// Returns the argument adding human readable sizes and durations to the
+/// JSON output.
+pub fn human_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(HUMAN)
+        .display_order(display_order)
+        .required(false)
+        .long(HUMAN)
+        .takes_value(false)
+        .help(
+            "Follow each size and duration in the output with a `_human` \
+            field holding it in units like KiB, MiB, GiB or hours, keeping \
+            the raw values.",
+        )
+}
+
+/// Reads the argument returned by `human_arg`.
+pub fn is_human(matches: &ArgMatches) -> bool {
+    matches.is_present(HUMAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{format_duration, format_size, humanize, Unit};
+
+    #[test]
+    fn format_size_should_use_binary_units() {
+        assert_eq!(format_size(0.0), "0 B");
+        assert_eq!(format_size(1023.0), "1023 B");
+        assert_eq!(format_size(1536.0), "1.50 KiB");
+        assert_eq!(format_size(8.0 * 1024.0 * 1024.0), "8.00 MiB");
+        assert_eq!(format_size(483_183_820_800.0), "450.00 GiB");
+    }
+
+    #[test]
+    fn format_duration_should_skip_leading_zero_units() {
+        assert_eq!(format_duration(4.34), "4.3s");
+        assert_eq!(format_duration(60.0), "1m 0s");
+        assert_eq!(format_duration(7_510.0), "2h 5m 10s");
+        assert_eq!(format_duration(90_000.0), "1d 1h 0m 0s");
+    }
+
+    #[test]
+    fn humanize_should_keep_raw_values() {
+        let mut value = json!({
+            "size": { "average": 1536.0, "max": 2048 },
+            "blocks": [{ "height": 1, "byte_size": 4096 }],
+            "eta_secs": null,
+            "elapsed_secs": 61.5,
+        });
+        humanize(
+            &mut value,
+            &[
+                ("size", Unit::Bytes),
+                ("byte_size", Unit::Bytes),
+                ("eta_secs", Unit::Seconds),
+                ("elapsed_secs", Unit::Seconds),
+            ],
+        );
+        assert_eq!(
+            value,
+            json!({
+                "size": { "average": 1536.0, "max": 2048 },
+                "size_human": { "average": "1.50 KiB", "max": "2.00 KiB" },
+                "blocks": [{ "height": 1, "byte_size": 4096, "byte_size_human": "4.00 KiB" }],
+                "eta_secs": null,
+                "elapsed_secs": 61.5,
+                "elapsed_secs_human": "1m 2s",
+            })
+        );
+        assert_eq!(
+            value.as_object().unwrap().keys().collect::<Vec<_>>(),
+            [
+                "size",
+                "size_human",
+                "blocks",
+                "eta_secs",
+                "elapsed_secs",
+                "elapsed_secs_human"
+            ]
+        );
+    }
+}
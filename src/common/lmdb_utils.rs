@@ -1,10 +1,62 @@
-use std::result::Result;
+//! Statistics and copies of LMDB environments and their databases.
+//!
+//! These wrap the `mdb_stat`, `mdb_env_info`, `mdb_env_copy2` and
+//! `mdb_env_set_mapsize` calls of the LMDB C library, which the `lmdb` crate
+//! only partially exposes, so tools built on this library don't need to call
+//! into it themselves.
 
-use lmdb::{Database, Error, Transaction};
-use lmdb_sys::{mdb_stat, MDB_stat};
+use std::{
+    collections::BTreeMap, ffi::CString, os::unix::ffi::OsStrExt, path::Path, ptr, result::Result,
+};
 
-/// Retrieves the number of entries in a database.
-pub fn entry_count<T: Transaction>(txn: &'_ T, database: Database) -> Result<usize, Error> {
+use lmdb::{Cursor, Database, Environment, Error, Transaction};
+use lmdb_sys::{
+    mdb_cursor_close, mdb_cursor_get, mdb_cursor_open, mdb_env_copy2, mdb_env_info,
+    mdb_env_set_mapsize, mdb_stat, MDB_cursor, MDB_dbi, MDB_envinfo, MDB_stat, MDB_val,
+    MDB_CP_COMPACT, MDB_NEXT, MDB_NOTFOUND,
+};
+use serde::{Deserialize, Serialize};
+
+/// Handle of the database in which LMDB keeps the free list of an
+/// environment.
+const FREE_DBI: MDB_dbi = 0;
+
+/// Statistics of a database, as returned by `mdb_stat`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbStat {
+    /// Depth of the B-tree of the database.
+    pub depth: u32,
+    pub branch_pages: usize,
+    pub leaf_pages: usize,
+    /// Pages holding values too large to fit in a leaf page.
+    pub overflow_pages: usize,
+    pub entries: usize,
+}
+
+impl DbStat {
+    /// Returns the number of pages the database takes.
+    pub fn pages(&self) -> u64 {
+        (self.branch_pages + self.leaf_pages + self.overflow_pages) as u64
+    }
+}
+
+/// Information on an environment, as returned by `mdb_env_info`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvInfo {
+    /// Size of the memory map, the maximum size of the data file.
+    pub map_size: usize,
+    pub page_size: u32,
+    /// Number of the last page in use.
+    pub last_page_number: usize,
+    /// Id of the last committed transaction.
+    pub last_transaction_id: usize,
+    pub max_readers: u32,
+    /// Number of reader slots in use.
+    pub readers: u32,
+}
+
+/// Retrieves the statistics of a database.
+pub fn db_stat<T: Transaction>(txn: &'_ T, database: Database) -> Result<DbStat, Error> {
     let mut stat = MDB_stat {
         ms_psize: 0,
         ms_depth: 0,
@@ -17,7 +69,150 @@ pub fn entry_count<T: Transaction>(txn: &'_ T, database: Database) -> Result<usi
     if result != 0 {
         Err(Error::from_err_code(result))
     } else {
-        Ok(stat.ms_entries)
+        Ok(DbStat {
+            depth: stat.ms_depth,
+            branch_pages: stat.ms_branch_pages,
+            leaf_pages: stat.ms_leaf_pages,
+            overflow_pages: stat.ms_overflow_pages,
+            entries: stat.ms_entries,
+        })
+    }
+}
+
+/// Retrieves the number of entries in a database.
+pub fn entry_count<T: Transaction>(txn: &'_ T, database: Database) -> Result<usize, Error> {
+    Ok(db_stat(txn, database)?.entries)
+}
+
+/// Retrieves the number of pages a database takes, counting its branch,
+/// leaf and overflow pages.
+pub fn page_count<T: Transaction>(txn: &'_ T, database: Database) -> Result<u64, Error> {
+    Ok(db_stat(txn, database)?.pages())
+}
+
+/// Retrieves the names of the named databases of an environment, which are
+/// the keys of its unnamed database. Names which aren't valid UTF-8 are
+/// skipped, as such databases can't be opened by name.
+pub fn db_names<T: Transaction>(txn: &'_ T) -> Result<Vec<String>, Error> {
+    let main_db = unsafe { txn.open_db(None)? };
+    let mut cursor = txn.open_ro_cursor(main_db)?;
+    Ok(cursor
+        .iter()
+        .filter_map(|(raw_name, _)| std::str::from_utf8(raw_name).ok())
+        .map(str::to_string)
+        .collect())
+}
+
+/// Retrieves the statistics of every named database of an environment,
+/// keyed by name.
+pub fn db_stats<T: Transaction>(txn: &'_ T) -> Result<BTreeMap<String, DbStat>, Error> {
+    let mut stats = BTreeMap::new();
+    for db_name in db_names(txn)? {
+        let database = unsafe { txn.open_db(Some(&db_name))? };
+        let stat = db_stat(txn, database)?;
+        stats.insert(db_name, stat);
+    }
+    Ok(stats)
+}
+
+/// Retrieves the number of pages in the free list of an environment. These
+/// pages hold no data anymore and are reused by later writes, but they only
+/// leave the data file when it is copied with compaction.
+pub fn free_page_count<T: Transaction>(txn: &'_ T) -> Result<u64, Error> {
+    let mut cursor: *mut MDB_cursor = ptr::null_mut();
+    let result = unsafe { mdb_cursor_open(txn.txn(), FREE_DBI, &mut cursor) };
+    if result != 0 {
+        return Err(Error::from_err_code(result));
+    }
+    let mut free_pages = 0;
+    // Each entry holds the pages freed by a transaction, as a list of page
+    // numbers prefixed by its length.
+    let result = loop {
+        let mut key = MDB_val {
+            mv_size: 0,
+            mv_data: ptr::null_mut(),
+        };
+        let mut data = MDB_val {
+            mv_size: 0,
+            mv_data: ptr::null_mut(),
+        };
+        let result = unsafe { mdb_cursor_get(cursor, &mut key, &mut data, MDB_NEXT) };
+        if result != 0 {
+            break result;
+        }
+        if data.mv_size >= std::mem::size_of::<usize>() {
+            free_pages += unsafe { ptr::read_unaligned(data.mv_data as *const usize) } as u64;
+        }
+    };
+    unsafe { mdb_cursor_close(cursor) };
+    if result == MDB_NOTFOUND {
+        Ok(free_pages)
+    } else {
+        Err(Error::from_err_code(result))
+    }
+}
+
+/// Retrieves the information on an environment.
+pub fn env_info(env: &Environment) -> Result<EnvInfo, Error> {
+    let page_size = env.stat()?.page_size();
+    let mut info = MDB_envinfo {
+        me_mapaddr: ptr::null_mut(),
+        me_mapsize: 0,
+        me_last_pgno: 0,
+        me_last_txnid: 0,
+        me_maxreaders: 0,
+        me_numreaders: 0,
+    };
+    let result = unsafe { mdb_env_info(env.env(), &mut info as *mut MDB_envinfo) };
+    if result != 0 {
+        Err(Error::from_err_code(result))
+    } else {
+        Ok(EnvInfo {
+            map_size: info.me_mapsize,
+            page_size,
+            last_page_number: info.me_last_pgno,
+            last_transaction_id: info.me_last_txnid,
+            max_readers: info.me_maxreaders,
+            readers: info.me_numreaders,
+        })
+    }
+}
+
+/// Retrieves the number of bytes taken by the pages in use in an environment,
+/// which is the size of its data file once the unused space at its end is
+/// removed.
+pub fn used_size(env: &Environment) -> Result<u64, Error> {
+    let info = env_info(env)?;
+    Ok((info.last_page_number as u64 + 1) * info.page_size as u64)
+}
+
+/// Copies the environment to the file at `path`, which must not exist, as
+/// seen by a single read transaction. The copy is thus consistent even if
+/// the environment is written to meanwhile. With `compact`, free pages are
+/// left out and the copy is only as large as the data it holds.
+pub fn copy_env(env: &Environment, path: &Path, compact: bool) -> Result<(), Error> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).map_err(|_| Error::Invalid)?;
+    let flags = if compact { MDB_CP_COMPACT } else { 0 };
+    let result = unsafe { mdb_env_copy2(env.env(), c_path.as_ptr(), flags) };
+    if result != 0 {
+        Err(Error::from_err_code(result))
+    } else {
+        Ok(())
+    }
+}
+
+/// Adopts the size of the memory map set by another process writing to the
+/// environment, after a transaction failed to start with
+/// `Error::MapResized`. Must only be called while no transaction of the
+/// environment is active in this process.
+pub fn adopt_map_size(env: &Environment) -> Result<(), Error> {
+    // A size of zero keeps the size in use by the environment, which is at
+    // least the size set by the other process.
+    let result = unsafe { mdb_env_set_mapsize(env.env(), 0) };
+    if result != 0 {
+        Err(Error::from_err_code(result))
+    } else {
+        Ok(())
     }
 }
 
@@ -27,7 +222,7 @@ mod tests {
 
     use crate::test_utils::LmdbTestFixture;
 
-    use super::entry_count;
+    use super::{db_names, db_stat, entry_count, env_info, page_count, used_size};
 
     #[test]
     fn db_entry_count() {
@@ -87,4 +282,39 @@ mod tests {
             txn.commit().unwrap();
         };
     }
+
+    #[test]
+    fn db_stat_should_count_pages_and_names() {
+        let fixture = LmdbTestFixture::new(vec!["first", "second"], None);
+        let env = &fixture.env;
+        let db = *fixture.db(Some("second")).unwrap();
+
+        let mut txn = env.begin_rw_txn().unwrap();
+        for key in 0u32..500 {
+            txn.put(db, &key.to_be_bytes(), &[0u8; 64], WriteFlags::empty())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        assert_eq!(db_names(&txn).unwrap(), ["first", "second"]);
+        let stat = db_stat(&txn, db).unwrap();
+        assert_eq!(stat.entries, 500);
+        // 500 entries of 68 bytes don't fit in a single page.
+        assert_eq!(stat.depth, 2);
+        assert!(stat.branch_pages >= 1);
+        assert!(stat.leaf_pages > 1);
+        assert_eq!(stat.overflow_pages, 0);
+        assert_eq!(page_count(&txn, db).unwrap(), stat.pages());
+        txn.commit().unwrap();
+
+        let info = env_info(env).unwrap();
+        assert_eq!(info.map_size, 4096 * 1024);
+        assert_eq!(info.max_readers, 12);
+        assert!(info.last_transaction_id >= 1);
+        assert_eq!(
+            used_size(env).unwrap(),
+            (info.last_page_number as u64 + 1) * info.page_size as u64
+        );
+    }
 }
@@ -0,0 +1,278 @@
+use std::{borrow::Cow, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::DeployHash;
+use casper_types::bytesrepr::{Error as BytesreprError, FromBytes, ToBytes};
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::{
+        db::{
+            BlockBodyMerkleDatabase, Database, DeployHashesDatabase, ProposerDatabase,
+            TransferHashesDatabase,
+        },
+        storage::{Error as StorageError, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+/// Number of parts in a merkle block body: the deploy hashes, the transfer
+/// hashes and the proposer, in this order.
+pub const PARTS_COUNT: usize = 3;
+
+/// Errors encountered when reading a merkle block body.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on a node or part of a merkle block body.
+    #[error("Error parsing merkle block body part with hash {0}: {1}")]
+    MerkleParsing(Digest, BytesreprError),
+    /// A node or part of a merkle block body is missing while its root is
+    /// present.
+    #[error("Merkle block body part with hash {1} not present in the {0} database")]
+    MissingPart(&'static str, Digest),
+}
+
+/// A block body reconstructed from the merkle body databases, along with the
+/// keys of the entries it was read from.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleBody {
+    /// Hashes of the linked list nodes in `block_body_merkle`, the first one
+    /// being the body hash.
+    pub node_hashes: [Digest; PARTS_COUNT],
+    /// Hashes of the parts in `deploy_hashes`, `transfer_hashes` and
+    /// `proposers` respectively.
+    pub part_hashes: [Digest; PARTS_COUNT],
+    pub body: BlockBody,
+}
+
+impl MerkleBody {
+    /// Names of the databases holding the parts, in the order of the parts.
+    pub fn part_db_names() -> [&'static str; PARTS_COUNT] {
+        [
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            ProposerDatabase::db_name(),
+        ]
+    }
+
+    /// Returns the database name and key of every entry making up the body.
+    pub fn entries(&self) -> Vec<(&'static str, Digest)> {
+        self.node_hashes
+            .iter()
+            .map(|node_hash| (BlockBodyMerkleDatabase::db_name(), *node_hash))
+            .chain(
+                Self::part_db_names()
+                    .into_iter()
+                    .zip(self.part_hashes.iter().copied()),
+            )
+            .collect()
+    }
+}
+
+/// An entry storing a node or part of a merkle block body, as
+/// `(db_name, key, value)`.
+pub type MerkleEntry = (&'static str, Digest, Vec<u8>);
+
+/// Returns the entries storing `body` as a merkle linked list, the first
+/// node of the list being keyed by `body_hash`. Every other node and part is
+/// keyed by its own hash, so they are shared with the other bodies holding
+/// the same parts.
+pub fn merkle_entries(
+    body: &BlockBody,
+    body_hash: Digest,
+) -> Result<Vec<MerkleEntry>, BytesreprError> {
+    let digests = |hashes: &[DeployHash]| hashes.iter().copied().map(Digest::from).collect();
+    let raw_proposer = body.proposer().to_bytes()?;
+    let parts = [
+        (
+            Digest::hash_vec_merkle_tree(digests(body.deploy_hashes())),
+            body.deploy_hashes().to_bytes()?,
+        ),
+        (
+            Digest::hash_vec_merkle_tree(digests(&body.transfer_hashes)),
+            body.transfer_hashes.to_bytes()?,
+        ),
+        (Digest::hash(&raw_proposer), raw_proposer),
+    ];
+    let mut entries = vec![];
+    // The list is built from its end, which is the sentinel hash.
+    let mut rest_hash = Digest::SENTINEL_RFOLD;
+    for (idx, ((part_hash, raw_part), db_name)) in parts
+        .into_iter()
+        .zip(MerkleBody::part_db_names())
+        .enumerate()
+        .rev()
+    {
+        let node_hash = if idx == 0 {
+            body_hash
+        } else {
+            Digest::hash_pair(part_hash, rest_hash)
+        };
+        entries.push((
+            BlockBodyMerkleDatabase::db_name(),
+            node_hash,
+            (part_hash, rest_hash).to_bytes()?,
+        ));
+        entries.push((db_name, part_hash, raw_part));
+        rest_hash = node_hash;
+    }
+    Ok(entries)
+}
+
+/// Returns the value stored under `key` in the table named `table`, or
+/// `None` if either the table or the entry don't exist.
+fn get_optional<'a, R: StorageReader>(
+    reader: &'a R,
+    table: &str,
+    key: &Digest,
+) -> Result<Option<Cow<'a, [u8]>>, StorageError> {
+    if !reader.has_table(table)? {
+        return Ok(None);
+    }
+    reader.get(table, key.as_ref())
+}
+
+/// Reads and parses a node or part of a merkle block body, failing if it's
+/// missing.
+fn read_part<R: StorageReader, P: FromBytes>(
+    reader: &R,
+    db_name: &'static str,
+    part_hash: &Digest,
+) -> Result<P, Error> {
+    let raw_part =
+        get_optional(reader, db_name, part_hash)?.ok_or(Error::MissingPart(db_name, *part_hash))?;
+    Ok(P::from_bytes(&raw_part)
+        .map_err(|bytesrepr_err| Error::MerkleParsing(*part_hash, bytesrepr_err))?
+        .0)
+}
+
+/// Reconstructs the block body with the given hash by walking its linked
+/// list in `block_body_merkle`. Returns `None` if there is no list starting
+/// at `body_hash`, and an error if the list or any of its parts is
+/// incomplete.
+pub fn read_merkle_body<R: StorageReader>(
+    reader: &R,
+    body_hash: &Digest,
+) -> Result<Option<MerkleBody>, Error> {
+    if get_optional(reader, BlockBodyMerkleDatabase::db_name(), body_hash)?.is_none() {
+        return Ok(None);
+    }
+    let mut node_hashes = [*body_hash; PARTS_COUNT];
+    let mut part_hashes = [*body_hash; PARTS_COUNT];
+    let mut current_node = *body_hash;
+    for (node_hash, part_hash) in node_hashes.iter_mut().zip(part_hashes.iter_mut()) {
+        let (part, rest): (Digest, Digest) =
+            read_part(reader, BlockBodyMerkleDatabase::db_name(), &current_node)?;
+        *node_hash = current_node;
+        *part_hash = part;
+        current_node = rest;
+    }
+    let [deploy_hashes_db, transfer_hashes_db, proposers_db] = MerkleBody::part_db_names();
+    let body = BlockBody::from_parts(
+        read_part(reader, proposers_db, &part_hashes[2])?,
+        read_part(reader, deploy_hashes_db, &part_hashes[0])?,
+        read_part(reader, transfer_hashes_db, &part_hashes[1])?,
+    );
+    Ok(Some(MerkleBody {
+        node_hashes,
+        part_hashes,
+        body,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use lmdb::{Transaction, WriteFlags};
+
+    use crate::{
+        common::{
+            db::{
+                BlockBodyMerkleDatabase, Database, DeployHashesDatabase, ProposerDatabase,
+                TransferHashesDatabase,
+            },
+            storage::LmdbReader,
+        },
+        subcommands::execution_results_summary::block_body::BlockBody,
+        test_utils::{mock_deploy_hash, mock_merkle_body, LmdbTestFixture, KEYS},
+    };
+
+    use super::{read_merkle_body, Error};
+
+    #[test]
+    fn merkle_body_reconstruction() {
+        let fixture = LmdbTestFixture::new(
+            vec![
+                BlockBodyMerkleDatabase::db_name(),
+                DeployHashesDatabase::db_name(),
+                TransferHashesDatabase::db_name(),
+                ProposerDatabase::db_name(),
+            ],
+            None,
+        );
+        let body = BlockBody::from_parts(
+            KEYS[0].clone(),
+            vec![mock_deploy_hash(0), mock_deploy_hash(1)],
+            vec![mock_deploy_hash(2)],
+        );
+        let (body_hash, entries) = mock_merkle_body(&body);
+        let (proposer_db_name, proposer_hash, _) = entries
+            .iter()
+            .find(|(db_name, _, _)| *db_name == ProposerDatabase::db_name())
+            .cloned()
+            .unwrap();
+
+        let env = &fixture.env;
+        let mut txn = env.begin_rw_txn().unwrap();
+        for (db_name, key, value) in entries.iter() {
+            txn.put(
+                *fixture.db(Some(db_name)).unwrap(),
+                key,
+                value,
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        let merkle_body = read_merkle_body(&LmdbReader::new(&txn), &body_hash)
+            .unwrap()
+            .unwrap();
+        assert_eq!(merkle_body.body, body);
+        assert_eq!(merkle_body.node_hashes[0], body_hash);
+        let mut stored_entries: Vec<_> = entries
+            .iter()
+            .map(|(db_name, key, _)| (*db_name, *key))
+            .collect();
+        let mut read_entries = merkle_body.entries();
+        stored_entries.sort();
+        read_entries.sort();
+        assert_eq!(read_entries, stored_entries);
+        // A hash which isn't the root of a merkle body.
+        assert_eq!(
+            read_merkle_body(&LmdbReader::new(&txn), &proposer_hash).unwrap(),
+            None
+        );
+        txn.commit().unwrap();
+
+        // Missing parts are reported.
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.del(
+            *fixture.db(Some(proposer_db_name)).unwrap(),
+            &proposer_hash,
+            None,
+        )
+        .unwrap();
+        txn.commit().unwrap();
+        let txn = env.begin_ro_txn().unwrap();
+        assert!(matches!(
+            read_merkle_body(&LmdbReader::new(&txn), &body_hash),
+            Err(Error::MissingPart(db_name, part_hash))
+                if db_name == proposer_db_name && part_hash == proposer_hash
+        ));
+        txn.commit().unwrap();
+    }
+}
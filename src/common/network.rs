@@ -0,0 +1,216 @@
+//! Locating the databases of a network in a directory holding the databases
+//! of several networks, one subdirectory per network as nodes lay them out.
+
+use std::{
+    fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+    subcommands::latest_block_summary::block_info::parse_network_name,
+};
+
+/// Long names of the options taking the path of a directory with the
+/// `storage.lmdb` or `data.lmdb` files to read, which nodes keep in the same
+/// directory.
+pub(crate) const DATA_DIR_OPTIONS: [&str; 6] = [
+    "db-dir",
+    "db-path",
+    "source-db-path",
+    "src-storage",
+    "src-trie",
+    "storage-path",
+];
+
+/// Errors encountered when selecting the databases of a network.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The directory holds the databases of several networks and none was
+    /// selected.
+    #[error(
+        "{0} holds the databases of networks {}, select one with --network",
+        .1.join(", ")
+    )]
+    AmbiguousNetwork(PathBuf, Vec<String>),
+    /// Error listing the directory.
+    #[error("Error listing networks in {0}: {1}")]
+    Io(PathBuf, IoError),
+    /// The directory doesn't hold the databases of the selected network.
+    #[error(
+        "{1} doesn't hold the databases of network {0}, found networks: [{}]",
+        .2.join(", ")
+    )]
+    UnknownNetwork(String, PathBuf, Vec<String>),
+}
+
+/// Returns whether `dir` holds a storage or trie store database.
+pub fn has_databases(dir: &Path) -> bool {
+    dir.join(STORAGE_FILE_NAME).is_file() || dir.join(TRIE_STORE_FILE_NAME).is_file()
+}
+
+/// Returns the directory holding `path` if it is a `storage.lmdb` or
+/// `data.lmdb` file, as the subcommands take the directory of the databases,
+/// or `path` as is otherwise.
+pub fn database_dir(path: &Path) -> &Path {
+    let is_database_file = path.is_file()
+        && path
+            .file_name()
+            .is_some_and(|name| name == STORAGE_FILE_NAME || name == TRIE_STORE_FILE_NAME);
+    match path.parent() {
+        Some(parent) if is_database_file && parent.as_os_str().is_empty() => Path::new("."),
+        Some(parent) if is_database_file => parent,
+        _ => path,
+    }
+}
+
+/// Returns the names and directories of the networks with databases in the
+/// subdirectories of `dir`, sorted by name.
+pub fn list_networks<P: AsRef<Path>>(dir: P) -> Result<Vec<(String, PathBuf)>, Error> {
+    let dir = dir.as_ref();
+    let io_err = |io_err| Error::Io(dir.to_path_buf(), io_err);
+    let mut networks = vec![];
+    for entry in fs::read_dir(dir).map_err(io_err)? {
+        let path = entry.map_err(io_err)?.path();
+        if path.is_dir() && has_databases(&path) {
+            networks.push((parse_network_name(&path).map_err(io_err)?, path));
+        }
+    }
+    networks.sort();
+    Ok(networks)
+}
+
+/// Returns the directory with the databases of the network `maybe_network`
+/// in `path`, or of its only network if none is given. A path to one of the
+/// database files stands for its directory. `path` is returned as is if it
+/// holds the databases itself, or if it isn't a directory, such as an archive
+/// or a directory to be created, for the subcommand to handle.
+pub fn select_network<P: AsRef<Path>>(
+    path: P,
+    maybe_network: Option<&str>,
+) -> Result<PathBuf, Error> {
+    let path = database_dir(path.as_ref());
+    if !path.is_dir() {
+        return Ok(path.to_path_buf());
+    }
+    let network_names =
+        |networks: Vec<(String, PathBuf)>| networks.into_iter().map(|(name, _dir)| name).collect();
+    match maybe_network {
+        Some(network) => {
+            if has_databases(path) {
+                let io_err = |io_err| Error::Io(path.to_path_buf(), io_err);
+                if parse_network_name(path).map_err(io_err)? == network {
+                    return Ok(path.to_path_buf());
+                }
+            }
+            let network_dir = path.join(network);
+            if has_databases(&network_dir) {
+                return Ok(network_dir);
+            }
+            Err(Error::UnknownNetwork(
+                network.to_string(),
+                path.to_path_buf(),
+                network_names(list_networks(path)?),
+            ))
+        }
+        None => {
+            if has_databases(path) {
+                return Ok(path.to_path_buf());
+            }
+            let mut networks = list_networks(path)?;
+            match networks.len() {
+                0 => Ok(path.to_path_buf()),
+                1 => Ok(networks.remove(0).1),
+                _ => Err(Error::AmbiguousNetwork(
+                    path.to_path_buf(),
+                    network_names(networks),
+                )),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempfile::tempdir;
+
+    use super::{database_dir, list_networks, select_network, Error};
+    use crate::common::db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME};
+
+    #[test]
+    fn networks_should_be_selected() {
+        let root_dir = tempdir().unwrap();
+        let mainnet_dir = root_dir.path().join("casper");
+        let testnet_dir = root_dir.path().join("casper-test");
+        fs::create_dir_all(&mainnet_dir).unwrap();
+        fs::create_dir_all(&testnet_dir).unwrap();
+        fs::create_dir_all(root_dir.path().join("empty")).unwrap();
+        fs::write(mainnet_dir.join(STORAGE_FILE_NAME), []).unwrap();
+        fs::write(testnet_dir.join(TRIE_STORE_FILE_NAME), []).unwrap();
+
+        let networks = list_networks(root_dir.path()).unwrap();
+        assert_eq!(
+            networks,
+            vec![
+                ("casper".to_string(), mainnet_dir.clone()),
+                ("casper-test".to_string(), testnet_dir.clone())
+            ]
+        );
+
+        assert_eq!(
+            select_network(root_dir.path(), Some("casper-test")).unwrap(),
+            testnet_dir
+        );
+        assert_eq!(
+            select_network(&mainnet_dir, Some("casper")).unwrap(),
+            mainnet_dir
+        );
+        assert_eq!(select_network(&mainnet_dir, None).unwrap(), mainnet_dir);
+        assert!(matches!(
+            select_network(root_dir.path(), None),
+            Err(Error::AmbiguousNetwork(_, names)) if names.len() == 2
+        ));
+        assert!(matches!(
+            select_network(root_dir.path(), Some("empty")),
+            Err(Error::UnknownNetwork(..))
+        ));
+        assert!(matches!(
+            select_network(&mainnet_dir, Some("casper-test")),
+            Err(Error::UnknownNetwork(..))
+        ));
+
+        fs::remove_dir_all(&testnet_dir).unwrap();
+        assert_eq!(select_network(root_dir.path(), None).unwrap(), mainnet_dir);
+        let missing_dir = root_dir.path().join("missing");
+        assert_eq!(
+            select_network(&missing_dir, Some("casper")).unwrap(),
+            missing_dir
+        );
+    }
+
+    #[test]
+    fn database_files_should_stand_for_their_dir() {
+        let db_dir = tempdir().unwrap();
+        let storage_path = db_dir.path().join(STORAGE_FILE_NAME);
+        let trie_path = db_dir.path().join(TRIE_STORE_FILE_NAME);
+        fs::write(&storage_path, []).unwrap();
+        fs::write(&trie_path, []).unwrap();
+        assert_eq!(database_dir(&storage_path), db_dir.path());
+        assert_eq!(database_dir(&trie_path), db_dir.path());
+        assert_eq!(database_dir(db_dir.path()), db_dir.path());
+
+        // Other files and missing databases are left for the subcommand.
+        let other_path = db_dir.path().join("chainspec.toml");
+        fs::write(&other_path, []).unwrap();
+        assert_eq!(database_dir(&other_path), other_path);
+        let missing_path = db_dir.path().join("missing").join(STORAGE_FILE_NAME);
+        assert_eq!(database_dir(&missing_path), missing_path);
+
+        assert_eq!(select_network(&trie_path, None).unwrap(), db_dir.path());
+    }
+}
@@ -0,0 +1,506 @@
+//! Output of the subcommands, written to a file, to standard output or
+//! posted to an HTTP endpoint.
+//!
+//! The output path given to a subcommand is read as:
+//! - `-`, for standard output, which is also used if no path is given;
+//! - `http://...` or `https://...`, for an endpoint the whole output is sent
+//!   to in the body of a single POST request once complete, retried on
+//!   network errors and server errors. The output is spooled to a temporary
+//!   file until then, and streamed from it;
+//! - `file://PATH` or any other value, for a file path.
+//!
+//! Every subcommand taking an output path also takes the argument returned
+//! by [`compress_arg`]. The subcommands writing databases, like
+//! `export-sqlite` or `backup create`, take it too and compress their files
+//! once complete, as databases can't be written as a stream.
+
+use std::{
+    ffi::OsString,
+    fs::{self, File, OpenOptions},
+    io::{self, BufWriter, Error as IoError, ErrorKind, Seek, SeekFrom, Stdout, Write},
+    path::{Path, PathBuf},
+    result::Result,
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use clap::{Arg, ArgMatches};
+use log::{info, warn};
+use reqwest::{header::CONTENT_LENGTH, Body, Url};
+use tokio::runtime::Builder as TokioRuntimeBuilder;
+use zstd::Encoder;
+
+use super::zstd_utils;
+
+const COMPRESS: &str = "compress";
+/// Extension appended to the name of the files compressed once complete.
+pub const ZSTD_EXTENSION: &str = ".zst";
+/// Compression level used when `zstd` is requested without a level.
+const DEFAULT_ZSTD_LEVEL: i32 = 3;
+/// Number of times a POST request is sent before giving up.
+const HTTP_ATTEMPTS: u32 = 4;
+/// Delay before the first retry of a POST request, doubled after each
+/// attempt.
+#[cfg(not(test))]
+const HTTP_RETRY_DELAY: Duration = Duration::from_secs(1);
+#[cfg(test)]
+const HTTP_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// Compression applied to the output of a subcommand.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Zstd compression with the given level.
+    Zstd(i32),
+}
+
+impl FromStr for Compression {
+    type Err = String;
+
+    /// Parses `zstd` or `zstd:LEVEL`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let mut parts = value.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("zstd"), None) => Ok(Compression::Zstd(DEFAULT_ZSTD_LEVEL)),
+            (Some("zstd"), Some(level)) => {
+                let level: i32 = level
+                    .parse()
+                    .map_err(|_| format!("invalid zstd level \"{}\"", level))?;
+                if zstd::compression_level_range().contains(&level) {
+                    Ok(Compression::Zstd(level))
+                } else {
+                    Err(format!(
+                        "zstd level must be in the range {:?}",
+                        zstd::compression_level_range()
+                    ))
+                }
+            }
+            _ => Err(format!(
+                "unknown compression \"{}\", expected \"zstd\" or \"zstd:LEVEL\"",
+                value
+            )),
+        }
+    }
+}
+
+impl Compression {
+    /// Reads the compression from the argument returned by `compress_arg`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        matches
+            .value_of(COMPRESS)
+            .map(|value| value.parse().expect("should be validated"))
+            .unwrap_or_default()
+    }
+}
+
+/// Returns the argument selecting the compression of the output.
+pub fn compress_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(COMPRESS)
+        .display_order(display_order)
+        .required(false)
+        .short('z')
+        .long(COMPRESS)
+        .takes_value(true)
+        .value_name("zstd[:LEVEL]")
+        .validator(|value| value.parse::<Compression>().map(|_| ()))
+        .help("Compress the output with zstd, optionally at the given level.")
+}
+
+/// Destination of the output of a subcommand.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Sink {
+    File(PathBuf),
+    Stdout,
+    /// Endpoint the output is posted to.
+    Http(Url),
+}
+
+impl Sink {
+    /// Reads an output path as described in the [module documentation](self).
+    pub fn from_path<P: AsRef<Path>>(maybe_path: Option<P>) -> Result<Self, IoError> {
+        let path = match maybe_path {
+            Some(path) => path.as_ref().to_path_buf(),
+            None => return Ok(Sink::Stdout),
+        };
+        let value = match path.to_str() {
+            Some(value) => value,
+            None => return Ok(Sink::File(path)),
+        };
+        if value == "-" {
+            Ok(Sink::Stdout)
+        } else if let Some(file_path) = value.strip_prefix("file://") {
+            Ok(Sink::File(PathBuf::from(file_path)))
+        } else if value.starts_with("http://") || value.starts_with("https://") {
+            Url::parse(value).map(Sink::Http).map_err(|url_err| {
+                IoError::new(
+                    ErrorKind::InvalidInput,
+                    format!("invalid output URL \"{}\": {}", value, url_err),
+                )
+            })
+        } else {
+            Ok(Sink::File(path))
+        }
+    }
+}
+
+/// Writer for a [`Sink`]. The output sent over HTTP is spooled to an
+/// anonymous temporary file until the writer is finished.
+pub enum SinkWriter {
+    File(File),
+    Stdout(Stdout),
+    Http { url: Url, spool: BufWriter<File> },
+}
+
+impl SinkWriter {
+    /// Opens `sink`. Unless `overwrite` is set, an output file must not
+    /// already exist.
+    fn open(sink: Sink, overwrite: bool) -> Result<Self, IoError> {
+        match sink {
+            Sink::File(path) => OpenOptions::new()
+                .create_new(!overwrite)
+                .create(overwrite)
+                .truncate(overwrite)
+                .write(true)
+                .open(path)
+                .map(SinkWriter::File),
+            Sink::Stdout => Ok(SinkWriter::Stdout(io::stdout())),
+            Sink::Http(url) => Ok(SinkWriter::Http {
+                url,
+                spool: BufWriter::new(tempfile::tempfile()?),
+            }),
+        }
+    }
+
+    /// Flushes the output, posting it if it's sent over HTTP.
+    fn finish(self) -> Result<(), IoError> {
+        match self {
+            SinkWriter::File(mut file) => file.flush(),
+            SinkWriter::Stdout(mut stdout) => stdout.flush(),
+            SinkWriter::Http { url, spool } => post_with_retries(
+                &url,
+                spool
+                    .into_inner()
+                    .map_err(|into_inner_err| into_inner_err.into_error())?,
+            ),
+        }
+    }
+}
+
+impl Write for SinkWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        match self {
+            SinkWriter::File(file) => file.write(buf),
+            SinkWriter::Stdout(stdout) => stdout.write(buf),
+            SinkWriter::Http { spool, .. } => spool.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        match self {
+            SinkWriter::File(file) => file.flush(),
+            SinkWriter::Stdout(stdout) => stdout.flush(),
+            SinkWriter::Http { spool, .. } => spool.flush(),
+        }
+    }
+}
+
+/// Posts the contents of `spool` to `url`, streaming them from the file,
+/// retrying up to [`HTTP_ATTEMPTS`] times in total on network errors and
+/// server errors, waiting longer after each attempt. Client errors aren't
+/// retried.
+fn post_with_retries(url: &Url, spool: File) -> Result<(), IoError> {
+    let runtime = TokioRuntimeBuilder::new_current_thread()
+        .enable_all()
+        .build()?;
+    let client = reqwest::Client::new();
+    let len = spool.metadata()?.len();
+    let mut delay = HTTP_RETRY_DELAY;
+    let mut attempt = 1;
+    loop {
+        let mut body = spool.try_clone()?;
+        body.seek(SeekFrom::Start(0))?;
+        let result = runtime.block_on(
+            client
+                .post(url.clone())
+                .header(CONTENT_LENGTH, len)
+                .body(Body::from(tokio::fs::File::from_std(body)))
+                .send(),
+        );
+        let error = match result {
+            Ok(response) if response.status().is_success() => {
+                info!(
+                    "Posted {} bytes of output to {}, got status {}.",
+                    len,
+                    url,
+                    response.status()
+                );
+                return Ok(());
+            }
+            Ok(response) if response.status().is_server_error() => {
+                format!("got status {}", response.status())
+            }
+            Ok(response) => {
+                return Err(IoError::other(format!(
+                    "posting output to {} got status {}",
+                    url,
+                    response.status()
+                )))
+            }
+            Err(reqwest_err) => reqwest_err.to_string(),
+        };
+        if attempt == HTTP_ATTEMPTS {
+            return Err(IoError::other(format!(
+                "posting output to {} failed after {} attempts, last error: {}",
+                url, attempt, error
+            )));
+        }
+        warn!(
+            "Attempt {} of {} posting output to {} failed: {}, retrying in {:?}.",
+            attempt, HTTP_ATTEMPTS, url, error, delay
+        );
+        thread::sleep(delay);
+        delay *= 2;
+        attempt += 1;
+    }
+}
+
+/// Returns the path of the file `path` is compressed to by [`compress_file`].
+pub fn compressed_path(path: &Path) -> PathBuf {
+    let mut compressed_path = OsString::from(path.as_os_str());
+    compressed_path.push(ZSTD_EXTENSION);
+    PathBuf::from(compressed_path)
+}
+
+/// Compresses the file at `path` once complete, for the subcommands which
+/// write databases rather than streams, replacing it with the file of the
+/// same name with [`ZSTD_EXTENSION`] appended, which must not exist. Returns
+/// the path of the resulting file, which is `path` without compression.
+pub fn compress_file(path: &Path, compression: Compression) -> Result<PathBuf, IoError> {
+    let level = match compression {
+        Compression::None => return Ok(path.to_path_buf()),
+        Compression::Zstd(level) => level,
+    };
+    let compressed_path = compressed_path(path);
+    let compressed_file = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(&compressed_path)?;
+    let mut encoder = zstd_utils::zstd_encode_stream_with_level(compressed_file, level)
+        .map_err(IoError::other)?;
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder
+        .finish()?
+        .into_inner()
+        .map_err(|into_inner_err| into_inner_err.into_error())?
+        .sync_all()?;
+    fs::remove_file(path)?;
+    Ok(compressed_path)
+}
+
+/// Writer for the output of a subcommand, optionally compressed.
+pub enum OutputWriter {
+    Plain(SinkWriter),
+    Zstd(Encoder<'static, BufWriter<SinkWriter>>),
+}
+
+impl OutputWriter {
+    /// Opens the output at `maybe_path`, read as described in the
+    /// [module documentation](self), or standard output if no path is given.
+    /// Unless `overwrite` is set, an output file must not already exist.
+    pub fn new<P: AsRef<Path>>(
+        maybe_path: Option<P>,
+        overwrite: bool,
+        compression: Compression,
+    ) -> Result<Self, IoError> {
+        let writer = SinkWriter::open(Sink::from_path(maybe_path)?, overwrite)?;
+        match compression {
+            Compression::None => Ok(OutputWriter::Plain(writer)),
+            Compression::Zstd(level) => zstd_utils::zstd_encode_stream_with_level(writer, level)
+                .map(OutputWriter::Zstd)
+                .map_err(IoError::other),
+        }
+    }
+
+    /// Returns whether the output goes to standard output, where it would be
+    /// interleaved with progress logs.
+    pub fn is_stdout(&self) -> bool {
+        let writer = match self {
+            OutputWriter::Plain(writer) => writer,
+            OutputWriter::Zstd(encoder) => encoder.get_ref().get_ref(),
+        };
+        matches!(writer, SinkWriter::Stdout(_))
+    }
+
+    /// Flushes the output, writing the end of the compressed stream if
+    /// needed, and posts it if it's sent over HTTP. Must be called once all
+    /// the output has been written.
+    pub fn finish(self) -> Result<(), IoError> {
+        match self {
+            OutputWriter::Plain(writer) => writer.finish(),
+            OutputWriter::Zstd(encoder) => encoder
+                .finish()?
+                .into_inner()
+                .map_err(|into_inner_err| into_inner_err.into_error())?
+                .finish(),
+        }
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        match self {
+            OutputWriter::Plain(writer) => writer.write(buf),
+            OutputWriter::Zstd(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        match self {
+            OutputWriter::Plain(writer) => writer.flush(),
+            OutputWriter::Zstd(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        path::{Path, PathBuf},
+        thread,
+    };
+
+    use super::{compress_file, Compression, OutputWriter, Sink, HTTP_ATTEMPTS};
+
+    /// Serves one request per status in `statuses` on a local port, returning
+    /// the URL to post to and a handle to the bodies of the requests.
+    fn serve_statuses(statuses: Vec<u16>) -> (String, thread::JoinHandle<Vec<Vec<u8>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/collect", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut bodies = vec![];
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end().to_lowercase();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some(value) = line.strip_prefix("content-length:") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0u8; content_length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.push(body);
+                write!(
+                    reader.get_mut(),
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+            bodies
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn parse_compression() {
+        assert_eq!("zstd".parse(), Ok(Compression::Zstd(3)));
+        assert_eq!("zstd:19".parse(), Ok(Compression::Zstd(19)));
+        assert!("zstd:".parse::<Compression>().is_err());
+        assert!("zstd:100".parse::<Compression>().is_err());
+        assert!("gzip".parse::<Compression>().is_err());
+    }
+
+    #[test]
+    fn zstd_output_roundtrip() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let out_path = tmp_dir.path().join("output.json.zst");
+        let payload = b"{\"some\": \"json\"}".repeat(100);
+
+        let mut writer = OutputWriter::new(Some(&out_path), false, Compression::Zstd(5)).unwrap();
+        writer.write_all(&payload).unwrap();
+        writer.finish().unwrap();
+        let decoded = zstd::decode_all(File::open(&out_path).unwrap()).unwrap();
+        assert_eq!(decoded, payload);
+
+        // The output file already exists.
+        assert!(OutputWriter::new(Some(&out_path), false, Compression::None).is_err());
+        let mut writer = OutputWriter::new(Some(&out_path), true, Compression::None).unwrap();
+        writer.write_all(&payload[..10]).unwrap();
+        writer.finish().unwrap();
+        assert_eq!(fs::read(&out_path).unwrap(), &payload[..10]);
+    }
+
+    #[test]
+    fn compress_file_should_replace_file() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("storage.lmdb");
+        let payload = b"database".repeat(100);
+        fs::write(&path, &payload).unwrap();
+
+        assert_eq!(compress_file(&path, Compression::None).unwrap(), path);
+        let compressed_path = compress_file(&path, Compression::Zstd(3)).unwrap();
+        assert_eq!(compressed_path, tmp_dir.path().join("storage.lmdb.zst"));
+        assert!(!path.exists());
+        let decoded = zstd::decode_all(File::open(&compressed_path).unwrap()).unwrap();
+        assert_eq!(decoded, payload);
+    }
+
+    #[test]
+    fn parse_sink() {
+        assert_eq!(Sink::from_path(None::<&Path>).unwrap(), Sink::Stdout);
+        assert_eq!(Sink::from_path(Some("-")).unwrap(), Sink::Stdout);
+        assert_eq!(
+            Sink::from_path(Some("file:///tmp/out.json")).unwrap(),
+            Sink::File(PathBuf::from("/tmp/out.json"))
+        );
+        assert_eq!(
+            Sink::from_path(Some("out.json")).unwrap(),
+            Sink::File(PathBuf::from("out.json"))
+        );
+        assert!(matches!(
+            Sink::from_path(Some("https://example.com/collect")).unwrap(),
+            Sink::Http(url) if url.as_str() == "https://example.com/collect"
+        ));
+        assert!(Sink::from_path(Some("http://")).is_err());
+    }
+
+    #[test]
+    fn http_output_should_retry_server_errors() {
+        let payload = b"{\"some\": \"json\"}";
+        let (url, server) = serve_statuses(vec![503, 500, 200]);
+        let mut writer = OutputWriter::new(Some(&url), false, Compression::None).unwrap();
+        assert!(!writer.is_stdout());
+        writer.write_all(payload).unwrap();
+        writer.finish().unwrap();
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies, vec![payload.to_vec(); 3]);
+
+        // Client errors aren't retried.
+        let (url, server) = serve_statuses(vec![404]);
+        let mut writer = OutputWriter::new(Some(&url), false, Compression::None).unwrap();
+        writer.write_all(payload).unwrap();
+        assert!(writer.finish().is_err());
+        assert_eq!(server.join().unwrap().len(), 1);
+
+        // Nor are server errors after the last attempt.
+        let (url, server) = serve_statuses(vec![503; HTTP_ATTEMPTS as usize]);
+        let mut writer = OutputWriter::new(Some(&url), false, Compression::Zstd(3)).unwrap();
+        writer.write_all(payload).unwrap();
+        assert!(writer.finish().is_err());
+        let bodies = server.join().unwrap();
+        assert_eq!(bodies.len(), HTTP_ATTEMPTS as usize);
+        assert_eq!(zstd::decode_all(bodies[0].as_slice()).unwrap(), payload);
+    }
+}
@@ -28,7 +28,7 @@ pub struct ProgressTracker {
     /// using `ProgressTracker::advance_by`. The purpose of this function
     /// is to allow users to create custom log messages for their specific
     /// operation.
-    log_progress: Box<dyn Fn(u64)>,
+    log_progress: Box<dyn Fn(u64) + Send>,
 }
 
 impl ProgressTracker {
@@ -36,7 +36,7 @@ impl ProgressTracker {
     /// amount to be processed and a log function.
     pub fn new(
         total_to_process: usize,
-        log_progress: Box<dyn Fn(u64)>,
+        log_progress: Box<dyn Fn(u64) + Send>,
     ) -> Result<Self, &'static str> {
         if total_to_process == 0 {
             Err(NULL_TOTAL_TO_PROCESS_ERROR)
@@ -0,0 +1,131 @@
+//! Versioning of the layout of the JSON reports.
+//!
+//! The fields of a report are output in the order they are declared in, and
+//! new fields are only ever appended to it. Each layout change bumps
+//! [`SchemaVersion::CURRENT`], and the previous layouts stay available
+//! through the `--schema-version` argument for parsers which can't handle
+//! the new fields yet.
+
+use std::{
+    fmt::{Display, Formatter, Result as FormatterResult},
+    str::FromStr,
+};
+
+use clap::{Arg, ArgMatches};
+use serde::{Deserialize, Serialize};
+
+const SCHEMA_VERSION: &str = "schema-version";
+
+/// Version of the layout of a JSON report, output as its `schema_version`
+/// field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SchemaVersion(u32);
+
+impl SchemaVersion {
+    /// Layout of the reports before they carried their version: no
+    /// `schema_version` field, and none of the fields added since, like the
+    /// state store of `latest-block-summary` or the top blocks of
+    /// `execution-results-summary`.
+    pub const V1: Self = Self(1);
+    /// Layout with the `schema_version` field first.
+    pub const V2: Self = Self(2);
+    /// Layout output by default.
+    pub const CURRENT: Self = Self::V2;
+
+    /// Returns whether this is [`SchemaVersion::V1`], whose reports don't
+    /// have a `schema_version` field.
+    pub fn is_v1(&self) -> bool {
+        *self == Self::V1
+    }
+
+    /// Reads the version from the argument returned by `schema_version_arg`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        matches
+            .value_of(SCHEMA_VERSION)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated")
+    }
+}
+
+/// Reports without a `schema_version` field have the first layout.
+impl Default for SchemaVersion {
+    fn default() -> Self {
+        Self::V1
+    }
+}
+
+impl FromStr for SchemaVersion {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.parse::<u32>() {
+            Ok(version) if (Self::V1.0..=Self::CURRENT.0).contains(&version) => Ok(Self(version)),
+            Ok(version) => Err(format!("unknown schema version {}", version)),
+            Err(parse_err) => Err(parse_err.to_string()),
+        }
+    }
+}
+
+impl Display for SchemaVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Returns the argument selecting the layout of the JSON output.
+pub fn schema_version_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(SCHEMA_VERSION)
+        .display_order(display_order)
+        .long(SCHEMA_VERSION)
+        .takes_value(true)
+        .value_name("VERSION")
+        .possible_values(["1", "2"])
+        .default_value("2")
+        .help(
+            "Layout of the JSON output. Version 1 is the layout from before \
+            the output carried a `schema_version` field.",
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::SchemaVersion;
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Report {
+        #[serde(skip_serializing_if = "SchemaVersion::is_v1", default)]
+        schema_version: SchemaVersion,
+        value: u64,
+    }
+
+    #[test]
+    fn schema_version_should_only_be_output_after_v1() {
+        let report = Report {
+            schema_version: SchemaVersion::CURRENT,
+            value: 1,
+        };
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(json, r#"{"schema_version":2,"value":1}"#);
+        assert_eq!(serde_json::from_str::<Report>(&json).unwrap(), report);
+
+        let report = Report {
+            schema_version: SchemaVersion::V1,
+            value: 1,
+        };
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(json, r#"{"value":1}"#);
+        assert_eq!(serde_json::from_str::<Report>(&json).unwrap(), report);
+    }
+
+    #[test]
+    fn schema_version_should_parse_known_versions() {
+        assert_eq!("1".parse(), Ok(SchemaVersion::V1));
+        assert_eq!("2".parse(), Ok(SchemaVersion::V2));
+        assert!("0".parse::<SchemaVersion>().is_err());
+        assert!("3".parse::<SchemaVersion>().is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use std::{
+    fs::{self, File},
+    io::{Error as IoError, ErrorKind},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use casper_types::ProtocolVersion;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::subcommands::latest_block_summary::block_info::parse_network_name;
+
+/// Name of the file describing the chain a directory written by this tool
+/// holds data of.
+pub const STAMP_FILE_NAME: &str = "casper-db-utils-stamp.json";
+
+/// Errors encountered when reading, writing or validating stamps.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Two directories are stamped with different chain names.
+    #[error("{0} holds data of chain {1} but {2} holds data of chain {3}")]
+    ChainMismatch(PathBuf, String, PathBuf, String),
+    /// Error reading or writing a stamp file.
+    #[error("Error accessing stamp file {0}: {1}")]
+    Io(PathBuf, IoError),
+    /// Error (de)serializing a stamp.
+    #[error("Error (de)serializing stamp: {0}")]
+    Serialization(#[from] SerializationError),
+}
+
+/// Describes which chain, and which part of it, the data in a directory
+/// comes from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub chain_name: Option<String>,
+    pub protocol_version: Option<ProtocolVersion>,
+    /// Lowest block height in the storage database, if known.
+    pub lowest_height: Option<u64>,
+    /// Highest block height in the storage database, if known.
+    pub highest_height: Option<u64>,
+}
+
+impl Stamp {
+    /// Reads the stamp of `dir`, returning `None` if it has none.
+    pub fn read<P: AsRef<Path>>(dir: P) -> Result<Option<Self>, Error> {
+        let path = dir.as_ref().join(STAMP_FILE_NAME);
+        match File::open(&path) {
+            Ok(file) => Ok(Some(serde_json::from_reader(file)?)),
+            Err(io_err) if io_err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(io_err) => Err(Error::Io(path, io_err)),
+        }
+    }
+
+    /// Writes the stamp to `dir`, replacing any existing one.
+    pub fn write<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let path = dir.as_ref().join(STAMP_FILE_NAME);
+        fs::write(&path, serde_json::to_vec_pretty(self)?).map_err(|io_err| Error::Io(path, io_err))
+    }
+}
+
+/// Returns the name of the chain `dir` holds data of: the one in its stamp
+/// if it has one, otherwise the name of the directory itself.
+pub fn chain_name<P: AsRef<Path>>(dir: P) -> Result<Option<String>, Error> {
+    if let Some(stamp) = Stamp::read(&dir)? {
+        if stamp.chain_name.is_some() {
+            return Ok(stamp.chain_name);
+        }
+    }
+    match parse_network_name(&dir) {
+        Ok(name) => Ok(Some(name)),
+        Err(io_err) => {
+            warn!("Couldn't derive chain name from path: {}", io_err);
+            Ok(None)
+        }
+    }
+}
+
+/// Fails if any two of `dirs` are stamped with different chain names.
+/// Directories without a stamp or without a chain name in their stamp are
+/// not taken into account, as their names are often arbitrary.
+pub fn ensure_same_chain<P: AsRef<Path>>(dirs: &[P]) -> Result<(), Error> {
+    let mut first: Option<(&Path, String)> = None;
+    for dir in dirs {
+        let chain_name = match Stamp::read(dir)?.and_then(|stamp| stamp.chain_name) {
+            Some(chain_name) => chain_name,
+            None => continue,
+        };
+        match first.as_ref() {
+            Some((first_dir, first_chain_name)) if *first_chain_name != chain_name => {
+                return Err(Error::ChainMismatch(
+                    first_dir.to_path_buf(),
+                    first_chain_name.clone(),
+                    dir.as_ref().to_path_buf(),
+                    chain_name,
+                ));
+            }
+            Some(_) => {}
+            None => first = Some((dir.as_ref(), chain_name)),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::{chain_name, ensure_same_chain, Error, Stamp};
+
+    #[test]
+    fn stamped_chains_must_match() {
+        let mainnet_dir = tempdir().unwrap();
+        let testnet_dir = tempdir().unwrap();
+        let unstamped_dir = tempdir().unwrap();
+        assert_eq!(Stamp::read(unstamped_dir.path()).unwrap(), None);
+
+        let mainnet_stamp = Stamp {
+            chain_name: Some("casper".to_string()),
+            highest_height: Some(10),
+            ..Default::default()
+        };
+        mainnet_stamp.write(mainnet_dir.path()).unwrap();
+        assert_eq!(
+            Stamp::read(mainnet_dir.path()).unwrap(),
+            Some(mainnet_stamp)
+        );
+        assert_eq!(
+            chain_name(mainnet_dir.path()).unwrap(),
+            Some("casper".to_string())
+        );
+        Stamp {
+            chain_name: Some("casper-test".to_string()),
+            ..Default::default()
+        }
+        .write(testnet_dir.path())
+        .unwrap();
+
+        ensure_same_chain(&[mainnet_dir.path(), unstamped_dir.path()]).unwrap();
+        assert!(matches!(
+            ensure_same_chain(&[mainnet_dir.path(), unstamped_dir.path(), testnet_dir.path()]),
+            Err(Error::ChainMismatch(_, first, _, second))
+                if first == "casper" && second == "casper-test"
+        ));
+    }
+}
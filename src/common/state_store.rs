@@ -0,0 +1,449 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    result::Result,
+};
+
+use bincode::Error as BincodeError;
+use casper_node::types::{Approval, Block, BlockHash, BlockHeader, DeployHash, DeployHeader};
+use casper_types::{Motes, Timestamp};
+use serde::{Deserialize, Serialize};
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{Database, DeserializationError, StateStoreDatabase},
+    storage::{Error as StorageError, StorageReader, StorageWriter},
+};
+
+/// Prefix of the key under which the linear chain synchronizer saves its
+/// state, followed by the network name.
+pub const LINEAR_CHAIN_SYNC_KEY_PREFIX: &str = "linear_chain_sync:network_name=";
+/// Key under which the block proposer saves its pending deploys.
+pub const BLOCK_PROPOSER_KEY: &[u8] = b"block proposer";
+
+/// Errors encountered when reading the state store.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error encoding the value of an entry.
+    #[error("Error encoding state store entry: {0}")]
+    Encoding(BincodeError),
+    /// Markers were set on a linear chain synchronizer which isn't syncing,
+    /// without a trusted hash to start syncing from.
+    #[error("Linear chain synchronizer isn't syncing, set a trusted hash to start")]
+    NotSyncing,
+    /// Parsing error on the entry with the given key.
+    #[error("Error parsing state store entry {0}: {1}")]
+    Parsing(String, DeserializationError),
+}
+
+// The types below mirror the ones the node saves in the state store, which
+// aren't part of the public interface of `casper-node`. Their fields must stay
+// in the same order for bincode to decode them.
+
+/// Mirror of `linear_chain_sync::State`.
+#[derive(Serialize, Deserialize)]
+#[allow(dead_code)]
+pub(crate) enum LinearChainSyncState {
+    None,
+    SyncingTrustedHash {
+        trusted_hash: BlockHash,
+        highest_block_header: Option<Box<BlockHeader>>,
+        highest_block_seen: u64,
+        linear_chain: Vec<Block>,
+        latest_block: Box<Option<Block>>,
+        last_switch_block_height: Option<u64>,
+    },
+    SyncingDescendants {
+        trusted_hash: BlockHash,
+        latest_block: Box<Block>,
+        highest_block_seen: u64,
+        last_switch_block_height: Option<u64>,
+    },
+    Done(Option<Box<Block>>),
+}
+
+/// Mirror of `block_proposer::DeployInfo`.
+#[derive(Serialize, Deserialize)]
+#[allow(dead_code)]
+struct DeployInfo {
+    header: DeployHeader,
+    payment_amount: Motes,
+    size: usize,
+}
+
+/// Mirror of `block_proposer::PendingDeployInfo`.
+#[derive(Serialize, Deserialize)]
+#[allow(dead_code)]
+struct PendingDeployInfo {
+    approvals: BTreeSet<Approval>,
+    info: DeployInfo,
+    timestamp: Timestamp,
+}
+
+/// Mirror of `block_proposer::CachedState`.
+#[derive(Serialize, Deserialize, Default)]
+struct BlockProposerState {
+    pending_deploys: HashMap<DeployHash, PendingDeployInfo>,
+    pending_transfers: HashMap<DeployHash, PendingDeployInfo>,
+}
+
+/// Phase the linear chain synchronizer was in when it last saved its state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncStatus {
+    None,
+    SyncingTrustedHash,
+    SyncingDescendants,
+    Done,
+}
+
+/// Saved state of the linear chain synchronizer. A node finding this entry
+/// on restart resumes synchronizing from it instead of starting over.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinearChainSyncSummary {
+    pub network_name: String,
+    pub status: SyncStatus,
+    pub trusted_hash: Option<BlockHash>,
+    pub highest_block_seen: Option<u64>,
+    /// Most recent block the synchronizer started to execute or, once done,
+    /// the highest block it saw.
+    pub latest_block_hash: Option<BlockHash>,
+    pub latest_block_height: Option<u64>,
+    pub last_switch_block_height: Option<u64>,
+}
+
+impl From<(String, LinearChainSyncState)> for LinearChainSyncSummary {
+    fn from((network_name, state): (String, LinearChainSyncState)) -> Self {
+        let (status, trusted_hash, highest_block_seen, latest_block, last_switch_block_height) =
+            match state {
+                LinearChainSyncState::None => (SyncStatus::None, None, None, None, None),
+                LinearChainSyncState::SyncingTrustedHash {
+                    trusted_hash,
+                    highest_block_seen,
+                    latest_block,
+                    last_switch_block_height,
+                    ..
+                } => (
+                    SyncStatus::SyncingTrustedHash,
+                    Some(trusted_hash),
+                    Some(highest_block_seen),
+                    *latest_block,
+                    last_switch_block_height,
+                ),
+                LinearChainSyncState::SyncingDescendants {
+                    trusted_hash,
+                    latest_block,
+                    highest_block_seen,
+                    last_switch_block_height,
+                } => (
+                    SyncStatus::SyncingDescendants,
+                    Some(trusted_hash),
+                    Some(highest_block_seen),
+                    Some(*latest_block),
+                    last_switch_block_height,
+                ),
+                LinearChainSyncState::Done(maybe_block) => {
+                    (SyncStatus::Done, None, None, maybe_block.map(|b| *b), None)
+                }
+            };
+        Self {
+            network_name,
+            status,
+            trusted_hash,
+            highest_block_seen,
+            latest_block_hash: latest_block.as_ref().map(|block| *block.hash()),
+            latest_block_height: latest_block.as_ref().map(|block| block.height()),
+            last_switch_block_height,
+        }
+    }
+}
+
+/// Number of deploys the block proposer had pending when it last saved its
+/// state.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockProposerSummary {
+    pub pending_deploys: usize,
+    pub pending_transfers: usize,
+}
+
+/// A decoded entry of the state store.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StateStoreEntry {
+    LinearChainSync(LinearChainSyncSummary),
+    BlockProposer(BlockProposerSummary),
+    /// An entry whose key isn't known, holding the key.
+    Unknown(String),
+}
+
+/// Decoded contents of the state store.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateStoreSummary {
+    /// One entry per network the node synchronized with.
+    pub linear_chain_sync: Vec<LinearChainSyncSummary>,
+    pub block_proposer: Option<BlockProposerSummary>,
+    pub unknown_keys: Vec<String>,
+}
+
+/// A state store key the node is known to write.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WellKnownKey {
+    /// State of the linear chain synchronizer for the given network.
+    LinearChainSync(String),
+    BlockProposer,
+}
+
+impl WellKnownKey {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::LinearChainSync(network_name) => {
+                format!("{}{}", LINEAR_CHAIN_SYNC_KEY_PREFIX, network_name).into_bytes()
+            }
+            Self::BlockProposer => BLOCK_PROPOSER_KEY.to_vec(),
+        }
+    }
+
+    /// Returns the encoded value the node would write for this key when it
+    /// has nothing to carry over: no linear chain synchronization configured
+    /// or no pending deploys.
+    pub fn reset_value(&self) -> Result<Vec<u8>, BincodeError> {
+        match self {
+            Self::LinearChainSync(_) => bincode::serialize(&LinearChainSyncState::None),
+            Self::BlockProposer => bincode::serialize(&BlockProposerState::default()),
+        }
+    }
+}
+
+/// New values of the markers the linear chain synchronizer resumes from, left
+/// unchanged when `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncMarkers {
+    pub trusted_hash: Option<BlockHash>,
+    pub highest_block_seen: Option<u64>,
+    pub last_switch_block_height: Option<u64>,
+}
+
+impl SyncMarkers {
+    /// Returns the encoded linear chain synchronizer state `maybe_state` with
+    /// the markers set. A synchronizer which isn't syncing, or whose state is
+    /// missing, is given the state a node configured with the trusted hash
+    /// starts from, so the trusted hash must be set.
+    pub(crate) fn apply(
+        &self,
+        maybe_state: Option<LinearChainSyncState>,
+    ) -> Result<Vec<u8>, Error> {
+        let mut state = match (maybe_state, self.trusted_hash) {
+            (
+                Some(
+                    state @ (LinearChainSyncState::SyncingTrustedHash { .. }
+                    | LinearChainSyncState::SyncingDescendants { .. }),
+                ),
+                _,
+            ) => state,
+            (_, Some(trusted_hash)) => LinearChainSyncState::SyncingTrustedHash {
+                trusted_hash,
+                highest_block_header: None,
+                highest_block_seen: 0,
+                linear_chain: vec![],
+                latest_block: Box::new(None),
+                last_switch_block_height: None,
+            },
+            (_, None) => return Err(Error::NotSyncing),
+        };
+        if let LinearChainSyncState::SyncingTrustedHash {
+            trusted_hash,
+            highest_block_seen,
+            last_switch_block_height,
+            ..
+        }
+        | LinearChainSyncState::SyncingDescendants {
+            trusted_hash,
+            highest_block_seen,
+            last_switch_block_height,
+            ..
+        } = &mut state
+        {
+            if let Some(new_trusted_hash) = self.trusted_hash {
+                *trusted_hash = new_trusted_hash;
+            }
+            if let Some(new_highest_block_seen) = self.highest_block_seen {
+                *highest_block_seen = new_highest_block_seen;
+            }
+            if let Some(new_height) = self.last_switch_block_height {
+                *last_switch_block_height = Some(new_height);
+            }
+        }
+        bincode::serialize(&state).map_err(Error::Encoding)
+    }
+}
+
+/// Decodes a state store entry according to its key.
+pub fn parse_entry(
+    raw_key: &[u8],
+    raw_val: &[u8],
+) -> Result<StateStoreEntry, DeserializationError> {
+    if raw_key == BLOCK_PROPOSER_KEY {
+        let state: BlockProposerState = bincode::deserialize(raw_val)?;
+        return Ok(StateStoreEntry::BlockProposer(BlockProposerSummary {
+            pending_deploys: state.pending_deploys.len(),
+            pending_transfers: state.pending_transfers.len(),
+        }));
+    }
+    let key = String::from_utf8_lossy(raw_key).to_string();
+    match key.strip_prefix(LINEAR_CHAIN_SYNC_KEY_PREFIX) {
+        Some(network_name) => {
+            let state: LinearChainSyncState = bincode::deserialize(raw_val)?;
+            Ok(StateStoreEntry::LinearChainSync(
+                (network_name.to_string(), state).into(),
+            ))
+        }
+        None => Ok(StateStoreEntry::Unknown(key)),
+    }
+}
+
+/// Decodes every entry of the state store. Returns `None` if the database
+/// doesn't exist.
+pub fn read_state_store<R: StorageReader>(reader: &R) -> Result<Option<StateStoreSummary>, Error> {
+    if !reader.has_table(StateStoreDatabase::db_name())? {
+        return Ok(None);
+    }
+    let mut summary = StateStoreSummary::default();
+    reader.scan(
+        StateStoreDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            let entry = parse_entry(raw_key, raw_val).map_err(|parsing_err| {
+                Error::Parsing(String::from_utf8_lossy(raw_key).to_string(), parsing_err)
+            })?;
+            match entry {
+                StateStoreEntry::LinearChainSync(sync_summary) => {
+                    summary.linear_chain_sync.push(sync_summary)
+                }
+                StateStoreEntry::BlockProposer(proposer_summary) => {
+                    summary.block_proposer = Some(proposer_summary)
+                }
+                StateStoreEntry::Unknown(key) => summary.unknown_keys.push(key),
+            }
+            Ok(())
+        },
+    )?;
+    Ok(Some(summary))
+}
+
+/// Resets the linear chain synchronizer entries whose latest block, highest
+/// block seen or last switch block is above `height`, to the value given by
+/// [`WellKnownKey::reset_value`]. A node restarting from such an entry would
+/// resume from blocks which are no longer in the database. Returns the
+/// network names of the entries which were reset.
+pub fn reset_sync_markers_above<W: StorageWriter>(
+    writer: &mut W,
+    height: u64,
+) -> Result<Vec<String>, Error> {
+    let summary = match read_state_store(writer)? {
+        Some(summary) => summary,
+        None => return Ok(vec![]),
+    };
+    let mut reset_networks = vec![];
+    for sync_summary in summary.linear_chain_sync {
+        let is_above = [
+            sync_summary.latest_block_height,
+            sync_summary.highest_block_seen,
+            sync_summary.last_switch_block_height,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|marker_height| marker_height > height);
+        if !is_above {
+            continue;
+        }
+        let key = WellKnownKey::LinearChainSync(sync_summary.network_name.clone());
+        let raw_val = key.reset_value().map_err(Error::Encoding)?;
+        writer.put(StateStoreDatabase::db_name(), &key.to_bytes(), &raw_val)?;
+        reset_networks.push(sync_summary.network_name);
+    }
+    Ok(reset_networks)
+}
+
+#[cfg(test)]
+mod tests {
+    use casper_node::types::BlockHash;
+    use lmdb::{Transaction, WriteFlags};
+
+    use crate::{
+        common::{
+            db::{Database, StateStoreDatabase},
+            storage::LmdbReader,
+        },
+        test_utils::LmdbTestFixture,
+    };
+
+    use super::{
+        read_state_store, BlockProposerState, BlockProposerSummary, LinearChainSyncState,
+        LinearChainSyncSummary, StateStoreSummary, SyncStatus, BLOCK_PROPOSER_KEY,
+        LINEAR_CHAIN_SYNC_KEY_PREFIX,
+    };
+
+    #[test]
+    fn state_store_decoding() {
+        let fixture = LmdbTestFixture::new(vec![StateStoreDatabase::db_name()], None);
+        let db = *fixture.db(Some(StateStoreDatabase::db_name())).unwrap();
+        let trusted_hash = BlockHash::new([1u8; 32].into());
+        let sync_state = LinearChainSyncState::SyncingTrustedHash {
+            trusted_hash,
+            highest_block_header: None,
+            highest_block_seen: 42,
+            linear_chain: vec![],
+            latest_block: Box::new(None),
+            last_switch_block_height: Some(40),
+        };
+
+        let env = &fixture.env;
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.put(
+            db,
+            &format!("{}casper", LINEAR_CHAIN_SYNC_KEY_PREFIX),
+            &bincode::serialize(&sync_state).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            db,
+            &BLOCK_PROPOSER_KEY,
+            &bincode::serialize(&BlockProposerState::default()).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(db, b"other", &[0u8], WriteFlags::empty()).unwrap();
+        txn.commit().unwrap();
+
+        let txn = env.begin_ro_txn().unwrap();
+        let summary = read_state_store(&LmdbReader::new(&txn)).unwrap().unwrap();
+        assert_eq!(
+            summary,
+            StateStoreSummary {
+                linear_chain_sync: vec![LinearChainSyncSummary {
+                    network_name: "casper".to_string(),
+                    status: SyncStatus::SyncingTrustedHash,
+                    trusted_hash: Some(trusted_hash),
+                    highest_block_seen: Some(42),
+                    latest_block_hash: None,
+                    latest_block_height: None,
+                    last_switch_block_height: Some(40),
+                }],
+                block_proposer: Some(BlockProposerSummary::default()),
+                unknown_keys: vec!["other".to_string()],
+            }
+        );
+        txn.commit().unwrap();
+
+        // A value which doesn't match its key is reported.
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.put(db, &BLOCK_PROPOSER_KEY, &[1u8], WriteFlags::empty())
+            .unwrap();
+        txn.commit().unwrap();
+        let txn = env.begin_ro_txn().unwrap();
+        assert!(read_state_store(&LmdbReader::new(&txn)).is_err());
+        txn.commit().unwrap();
+    }
+}
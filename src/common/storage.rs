@@ -0,0 +1,357 @@
+//! Backend-agnostic access to the named tables of a storage database.
+//!
+//! Read-only commands go through [`StorageReader`] rather than LMDB
+//! transactions, so they work with any key-value store providing ordered
+//! tables. LMDB databases are accessed through [`LmdbReader`] and
+//! [`LmdbWriter`]. When built with the `rocksdb` feature, the RocksDB
+//! databases of experimental node builds can be read through `RocksReader`.
+
+#[cfg(feature = "rocksdb")]
+mod rocks;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    borrow::Cow,
+    fmt::{Display, Formatter, Result as FormatterResult},
+    path::Path,
+    result::Result,
+    str::FromStr,
+};
+
+use clap::{Arg, ArgMatches};
+use lmdb::{
+    Cursor, Database as LmdbDatabase, DatabaseFlags, Error as LmdbError, RoTransaction,
+    RwTransaction, Transaction, WriteFlags,
+};
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{EnvTuning, StorageEnv},
+    lmdb_utils, throttle,
+};
+#[cfg(feature = "rocksdb")]
+pub use rocks::{RocksReader, ROCKSDB_DIR_NAME};
+
+const BACKEND: &str = "backend";
+
+/// Errors of the storage backend.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// LMDB operation error.
+    #[error("{0}")]
+    Lmdb(#[from] LmdbError),
+    /// The table doesn't exist in the database.
+    #[error("Table {0} not found")]
+    MissingTable(String),
+    /// RocksDB operation error.
+    #[cfg(feature = "rocksdb")]
+    #[error("{0}")]
+    RocksDb(#[from] rocksdb::Error),
+    /// The backend isn't compiled in.
+    #[error("Support for the {0} backend requires building with the `{0}` feature")]
+    UnsupportedBackend(Backend),
+}
+
+/// Key-value store holding a storage database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// A `storage.lmdb` file, as written by the node.
+    #[default]
+    Lmdb,
+    /// A `storage.rocksdb` directory, as written by experimental node builds.
+    RocksDb,
+}
+
+impl Backend {
+    /// Reads the backend from the argument returned by `backend_arg`.
+    pub fn from_matches(matches: &ArgMatches) -> Self {
+        matches
+            .value_of(BACKEND)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated")
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "lmdb" => Ok(Self::Lmdb),
+            "rocksdb" => Ok(Self::RocksDb),
+            _ => Err(format!("unknown backend {}", value)),
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::Lmdb => write!(f, "lmdb"),
+            Self::RocksDb => write!(f, "rocksdb"),
+        }
+    }
+}
+
+/// Returns the argument selecting the storage backend.
+pub fn backend_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(BACKEND)
+        .display_order(display_order)
+        .long(BACKEND)
+        .takes_value(true)
+        .value_name("BACKEND")
+        .possible_values(["lmdb", "rocksdb"])
+        .default_value("lmdb")
+        .help(
+            "Key-value store holding the storage database. The `rocksdb` \
+            backend reads the `storage.rocksdb` directory of experimental \
+            node builds.",
+        )
+}
+
+/// Read access to the tables of a storage database.
+pub trait StorageReader {
+    /// Returns whether the table named `table` exists.
+    fn has_table(&self, table: &str) -> Result<bool, Error>;
+
+    /// Returns the value stored under `key` in `table`, or `None` if there is
+    /// no such entry. Fails if the table doesn't exist.
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Error>;
+
+    /// Returns the number of entries in `table`. Fails if the table doesn't
+    /// exist.
+    fn entry_count(&self, table: &str) -> Result<usize, Error>;
+
+    /// Calls `visit` on every entry of `table` in ascending key order,
+    /// stopping at the first error. Fails if the table doesn't exist.
+    fn scan<E, F>(&self, table: &str, visit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>;
+}
+
+/// Write access to the tables of a storage database.
+pub trait StorageWriter: StorageReader {
+    /// Stores `value` under `key` in `table`, creating the table if needed.
+    fn put(&mut self, table: &str, key: &[u8], value: &[u8]) -> Result<(), Error>;
+
+    /// Removes the entry under `key` from `table`, returning whether it was
+    /// there.
+    fn delete(&mut self, table: &str, key: &[u8]) -> Result<bool, Error>;
+}
+
+/// Opens the LMDB database named `table`, or returns `None` if it doesn't
+/// exist.
+fn open_table<T: Transaction>(txn: &T, table: &str) -> Result<Option<LmdbDatabase>, LmdbError> {
+    match unsafe { txn.open_db(Some(table)) } {
+        Ok(db) => Ok(Some(db)),
+        Err(LmdbError::NotFound) => Ok(None),
+        Err(lmdb_err) => Err(lmdb_err),
+    }
+}
+
+fn get<'txn, T: Transaction>(
+    txn: &'txn T,
+    table: &str,
+    key: &[u8],
+) -> Result<Option<Cow<'txn, [u8]>>, Error> {
+    let db = unsafe { txn.open_db(Some(table))? };
+    match txn.get(db, &key) {
+        Ok(raw_val) => {
+            throttle::consume(key.len() + raw_val.len());
+            Ok(Some(Cow::Borrowed(raw_val)))
+        }
+        Err(LmdbError::NotFound) => Ok(None),
+        Err(lmdb_err) => Err(lmdb_err.into()),
+    }
+}
+
+fn entry_count<T: Transaction>(txn: &T, table: &str) -> Result<usize, Error> {
+    let db = unsafe { txn.open_db(Some(table))? };
+    Ok(lmdb_utils::entry_count(txn, db)?)
+}
+
+/// Scans `table` in the single transaction `txn`. Unlike `db::scan_after`,
+/// which reads in batches of short transactions, the readers mix scans with
+/// lookups which must all see the same snapshot of the database, so their
+/// scans can't move on to newer snapshots half way.
+fn scan<T, E, F>(txn: &T, table: &str, mut visit: F) -> Result<(), E>
+where
+    T: Transaction,
+    E: From<Error>,
+    F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+{
+    let db = unsafe { txn.open_db(Some(table)) }.map_err(Error::from)?;
+    let mut cursor = txn.open_ro_cursor(db).map_err(Error::from)?;
+    for (raw_key, raw_val) in cursor.iter() {
+        throttle::consume(raw_key.len() + raw_val.len());
+        visit(raw_key, raw_val)?;
+    }
+    Ok(())
+}
+
+/// Reads a storage database through an LMDB transaction.
+pub struct LmdbReader<'a, T: Transaction> {
+    txn: &'a T,
+}
+
+impl<'a, T: Transaction> LmdbReader<'a, T> {
+    pub fn new(txn: &'a T) -> Self {
+        Self { txn }
+    }
+}
+
+impl<'a, T: Transaction> StorageReader for LmdbReader<'a, T> {
+    fn has_table(&self, table: &str) -> Result<bool, Error> {
+        Ok(open_table(self.txn, table)?.is_some())
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        get(self.txn, table, key)
+    }
+
+    fn entry_count(&self, table: &str) -> Result<usize, Error> {
+        entry_count(self.txn, table)
+    }
+
+    fn scan<E, F>(&self, table: &str, visit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        scan(self.txn, table, visit)
+    }
+}
+
+/// Reads and writes a storage database through an LMDB read-write
+/// transaction. Changes are kept only once the transaction is committed.
+pub struct LmdbWriter<'a, 'env> {
+    txn: &'a mut RwTransaction<'env>,
+}
+
+impl<'a, 'env> LmdbWriter<'a, 'env> {
+    pub fn new(txn: &'a mut RwTransaction<'env>) -> Self {
+        Self { txn }
+    }
+}
+
+impl<'a, 'env> StorageReader for LmdbWriter<'a, 'env> {
+    fn has_table(&self, table: &str) -> Result<bool, Error> {
+        Ok(open_table(&*self.txn, table)?.is_some())
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        get(&*self.txn, table, key)
+    }
+
+    fn entry_count(&self, table: &str) -> Result<usize, Error> {
+        entry_count(&*self.txn, table)
+    }
+
+    fn scan<E, F>(&self, table: &str, visit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        scan(&*self.txn, table, visit)
+    }
+}
+
+impl<'a, 'env> StorageWriter for LmdbWriter<'a, 'env> {
+    fn put(&mut self, table: &str, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let db = unsafe { self.txn.create_db(Some(table), DatabaseFlags::empty())? };
+        self.txn.put(db, &key, &value, WriteFlags::empty())?;
+        Ok(())
+    }
+
+    fn delete(&mut self, table: &str, key: &[u8]) -> Result<bool, Error> {
+        let db = unsafe { self.txn.open_db(Some(table))? };
+        match self.txn.del(db, &key, None) {
+            Ok(()) => Ok(true),
+            Err(LmdbError::NotFound) => Ok(false),
+            Err(lmdb_err) => Err(lmdb_err.into()),
+        }
+    }
+}
+
+/// A storage database opened read-only with any of the backends.
+pub enum Storage {
+    Lmdb(StorageEnv),
+    #[cfg(feature = "rocksdb")]
+    RocksDb(RocksReader),
+}
+
+impl Storage {
+    /// Opens the storage database in the directory `db_dir` with `backend`.
+    /// The tuning only applies to LMDB.
+    pub fn open<P: AsRef<Path>>(
+        db_dir: P,
+        backend: Backend,
+        tuning: EnvTuning,
+    ) -> Result<Self, Error> {
+        match backend {
+            Backend::Lmdb => Ok(Self::Lmdb(StorageEnv::open_with_tuning(db_dir, tuning)?)),
+            #[cfg(feature = "rocksdb")]
+            Backend::RocksDb => Ok(Self::RocksDb(RocksReader::open(db_dir)?)),
+            #[cfg(not(feature = "rocksdb"))]
+            Backend::RocksDb => Err(Error::UnsupportedBackend(backend)),
+        }
+    }
+
+    /// Returns a consistent view of the database to read from.
+    pub fn view(&self) -> Result<StorageView<'_>, Error> {
+        match self {
+            Self::Lmdb(env) => Ok(StorageView::Lmdb(env.begin_ro_txn()?)),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(reader) => Ok(StorageView::RocksDb(reader)),
+        }
+    }
+}
+
+/// A consistent view of a [`Storage`], reading from a transaction for LMDB
+/// and from the read-only database for RocksDB.
+pub enum StorageView<'a> {
+    Lmdb(RoTransaction<'a>),
+    #[cfg(feature = "rocksdb")]
+    RocksDb(&'a RocksReader),
+}
+
+impl<'a> StorageReader for StorageView<'a> {
+    fn has_table(&self, table: &str) -> Result<bool, Error> {
+        match self {
+            Self::Lmdb(txn) => Ok(open_table(txn, table)?.is_some()),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(reader) => reader.has_table(table),
+        }
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        match self {
+            Self::Lmdb(txn) => get(txn, table, key),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(reader) => reader.get(table, key),
+        }
+    }
+
+    fn entry_count(&self, table: &str) -> Result<usize, Error> {
+        match self {
+            Self::Lmdb(txn) => entry_count(txn, table),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(reader) => reader.entry_count(table),
+        }
+    }
+
+    fn scan<E, F>(&self, table: &str, visit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        match self {
+            Self::Lmdb(txn) => scan(txn, table, visit),
+            #[cfg(feature = "rocksdb")]
+            Self::RocksDb(reader) => reader.scan(table, visit),
+        }
+    }
+}
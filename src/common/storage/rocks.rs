@@ -0,0 +1,70 @@
+use std::{borrow::Cow, path::Path, result::Result};
+
+use rocksdb::{ColumnFamily, IteratorMode, Options, DB};
+
+use super::{Error, StorageReader};
+use crate::common::throttle;
+
+/// Name of the directory holding the RocksDB storage database, next to where
+/// the node keeps `storage.lmdb`.
+pub const ROCKSDB_DIR_NAME: &str = "storage.rocksdb";
+
+/// Reads a RocksDB storage database, which keeps each table of the LMDB
+/// layout in a column family of the same name.
+pub struct RocksReader {
+    db: DB,
+}
+
+impl RocksReader {
+    /// Opens the RocksDB storage database in the directory `db_dir`
+    /// read-only, with all its column families.
+    pub fn open<P: AsRef<Path>>(db_dir: P) -> Result<Self, Error> {
+        let path = db_dir.as_ref().join(ROCKSDB_DIR_NAME);
+        let options = Options::default();
+        let tables = DB::list_cf(&options, &path)?;
+        let db = DB::open_cf_for_read_only(&options, &path, tables, false)?;
+        Ok(Self { db })
+    }
+
+    fn table(&self, table: &str) -> Result<&ColumnFamily, Error> {
+        self.db
+            .cf_handle(table)
+            .ok_or_else(|| Error::MissingTable(table.to_string()))
+    }
+}
+
+impl StorageReader for RocksReader {
+    fn has_table(&self, table: &str) -> Result<bool, Error> {
+        Ok(self.db.cf_handle(table).is_some())
+    }
+
+    fn get(&self, table: &str, key: &[u8]) -> Result<Option<Cow<'_, [u8]>>, Error> {
+        let maybe_raw_val = self.db.get_cf(self.table(table)?, key)?;
+        if let Some(raw_val) = maybe_raw_val.as_ref() {
+            throttle::consume(key.len() + raw_val.len());
+        }
+        Ok(maybe_raw_val.map(Cow::Owned))
+    }
+
+    fn entry_count(&self, table: &str) -> Result<usize, Error> {
+        let mut count = 0;
+        for entry in self.db.iterator_cf(self.table(table)?, IteratorMode::Start) {
+            entry?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    fn scan<E, F>(&self, table: &str, mut visit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(&[u8], &[u8]) -> Result<(), E>,
+    {
+        for entry in self.db.iterator_cf(self.table(table)?, IteratorMode::Start) {
+            let (raw_key, raw_val) = entry.map_err(Error::from)?;
+            throttle::consume(raw_key.len() + raw_val.len());
+            visit(&raw_key, &raw_val)?;
+        }
+        Ok(())
+    }
+}
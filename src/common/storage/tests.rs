@@ -0,0 +1,106 @@
+use clap::Command;
+use lmdb::{Error as LmdbError, Transaction, WriteFlags};
+
+use crate::{
+    common::db::{EnvTuning, STORAGE_FILE_NAME},
+    test_utils::LmdbTestFixture,
+};
+
+use super::{
+    backend_arg, Backend, Error, LmdbReader, LmdbWriter, Storage, StorageReader, StorageWriter,
+};
+
+#[test]
+fn lmdb_round_trip() {
+    let fixture = LmdbTestFixture::new(vec!["table"], None);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    let mut writer = LmdbWriter::new(&mut txn);
+    for key in [3u8, 1, 2] {
+        writer.put("table", &[key], &[key * 10]).unwrap();
+    }
+    assert!(writer.delete("table", &[2]).unwrap());
+    assert!(!writer.delete("table", &[2]).unwrap());
+    txn.commit().unwrap();
+
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    assert!(reader.has_table("table").unwrap());
+    assert!(!reader.has_table("missing").unwrap());
+    assert_eq!(reader.get("table", &[1]).unwrap().unwrap().as_ref(), &[10]);
+    assert!(reader.get("table", &[2]).unwrap().is_none());
+    assert_eq!(reader.entry_count("table").unwrap(), 2);
+    assert!(matches!(
+        reader.get("missing", &[1]),
+        Err(Error::Lmdb(LmdbError::NotFound))
+    ));
+
+    let mut entries = vec![];
+    reader
+        .scan("table", |key, value| -> Result<(), Error> {
+            entries.push((key.to_vec(), value.to_vec()));
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(entries, vec![(vec![1], vec![10]), (vec![3], vec![30])]);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn storage_open_with_backend() {
+    let fixture = LmdbTestFixture::new(vec!["table"], Some(STORAGE_FILE_NAME));
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    LmdbWriter::new(&mut txn).put("table", &[1], &[2]).unwrap();
+    txn.commit().unwrap();
+
+    let matches = Command::new("test")
+        .arg(backend_arg(0))
+        .get_matches_from(vec!["test"]);
+    let backend = Backend::from_matches(&matches);
+    assert_eq!(backend, Backend::Lmdb);
+    let storage = Storage::open(fixture.tmp_dir.path(), backend, EnvTuning::default()).unwrap();
+    let view = storage.view().unwrap();
+    assert_eq!(view.get("table", &[1]).unwrap().unwrap().as_ref(), &[2]);
+    assert_eq!(view.entry_count("table").unwrap(), 1);
+
+    let matches = Command::new("test")
+        .arg(backend_arg(0))
+        .get_matches_from(vec!["test", "--backend", "rocksdb"]);
+    assert_eq!(Backend::from_matches(&matches), Backend::RocksDb);
+    #[cfg(not(feature = "rocksdb"))]
+    assert!(matches!(
+        Storage::open(
+            fixture.tmp_dir.path(),
+            Backend::RocksDb,
+            EnvTuning::default()
+        ),
+        Err(Error::UnsupportedBackend(Backend::RocksDb))
+    ));
+}
+
+#[test]
+fn lmdb_scan_should_see_transaction_snapshot() {
+    let fixture = LmdbTestFixture::new(vec!["table"], None);
+    let db = *fixture.db(Some("table")).unwrap();
+    let put = |key: u8| {
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        txn.put(db, &[key], &[key], WriteFlags::empty()).unwrap();
+        txn.commit().unwrap();
+    };
+    put(1);
+    put(3);
+
+    // Entries written during the scan aren't seen, as the whole scan runs in
+    // the transaction of the reader, like its lookups.
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    let mut keys = vec![];
+    reader
+        .scan("table", |raw_key, _raw_val| -> Result<(), Error> {
+            keys.push(raw_key[0]);
+            put(raw_key[0] + 1);
+            Ok(())
+        })
+        .unwrap();
+    assert_eq!(keys, vec![1, 3]);
+    assert!(reader.get("table", &[2]).unwrap().is_none());
+}
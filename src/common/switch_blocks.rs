@@ -0,0 +1,104 @@
+//! Guard keeping the destructive subcommands from removing switch blocks.
+//!
+//! The era end of a switch block holds the validator weights of the next
+//! era, which are needed to prove the finality of that era's blocks. The
+//! subcommands removing blocks release each of them from the
+//! [`BlockIndex`](crate::common::block_iter::BlockIndex), which checks them
+//! here and refuses to remove a switch block unless
+//! `--allow-switch-block-removal` is given.
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::EraId;
+use clap::{Arg, ArgMatches};
+use log::warn;
+use thiserror::Error as ThisError;
+
+const ALLOW_SWITCH_BLOCK_REMOVAL: &str = "allow-switch-block-removal";
+
+/// Errors encountered when checking the blocks about to be removed.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// A switch block would be removed without the override.
+    #[error(
+        "Refusing to remove switch block {block_hash} at height {height}, whose \
+        era end holds the validator weights of era {next_era_id}. Use \
+        `--allow-switch-block-removal` to remove it anyway."
+    )]
+    Protected {
+        block_hash: BlockHash,
+        height: u64,
+        next_era_id: EraId,
+    },
+}
+
+/// Checks that the block `block_hash` with `header` can be removed, which
+/// fails for switch blocks. When `allowed` is set, removing a switch block is
+/// only logged as a warning.
+pub fn check_removal(
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+    allowed: bool,
+) -> Result<(), Error> {
+    if !header.is_switch_block() {
+        return Ok(());
+    }
+    let error = Error::Protected {
+        block_hash: *block_hash,
+        height: header.height(),
+        next_era_id: header.next_block_era_id(),
+    };
+    if !allowed {
+        return Err(error);
+    }
+    warn!("{}", error);
+    Ok(())
+}
+
+pub fn allow_removal_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(ALLOW_SWITCH_BLOCK_REMOVAL)
+        .display_order(display_order)
+        .required(false)
+        .long(ALLOW_SWITCH_BLOCK_REMOVAL)
+        .takes_value(false)
+        .help(
+            "Allow removing switch blocks. Their era end holds the validator \
+            weights needed to prove the finality of the next era, so they are \
+            kept by default.",
+        )
+}
+
+/// Reads the argument returned by `allow_removal_arg`.
+pub fn is_removal_allowed(matches: &ArgMatches) -> bool {
+    matches.is_present(ALLOW_SWITCH_BLOCK_REMOVAL)
+}
+
+#[cfg(test)]
+mod tests {
+    use casper_node::types::BlockHeader;
+    use casper_types::EraId;
+    use serde::Serialize;
+
+    use super::{check_removal, Error};
+    use crate::test_utils::{mock_block_header, mock_switch_block_header};
+
+    fn into_header<T: Serialize>(mock_header: &T) -> BlockHeader {
+        bincode::deserialize(&bincode::serialize(mock_header).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn check_removal_should_protect_switch_blocks() {
+        let (block_hash, header) = mock_block_header(0);
+        assert!(check_removal(&block_hash, &into_header(&header), false).is_ok());
+
+        let (switch_block_hash, mut switch_header) = mock_switch_block_header(1);
+        switch_header.era_id = EraId::new(3);
+        switch_header.height = 7;
+        let switch_header = into_header(&switch_header);
+        assert!(matches!(
+            check_removal(&switch_block_hash, &switch_header, false),
+            Err(Error::Protected { block_hash, height: 7, next_era_id })
+                if block_hash == switch_block_hash && next_era_id == EraId::new(4)
+        ));
+        assert!(check_removal(&switch_block_hash, &switch_header, true).is_ok());
+    }
+}
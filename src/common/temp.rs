@@ -0,0 +1,170 @@
+//! Temporary directories of the subcommands.
+//!
+//! Unpacked archives and snapshots can be as large as the databases they come
+//! from, so `--tmpdir` lets them land on a scratch disk rather than in the
+//! system's temporary directory. Every directory created here is removed when
+//! dropped, and also when the program is interrupted by `SIGINT` or `SIGTERM`,
+//! which would otherwise skip the destructors. `--keep-temp` keeps them all
+//! for debugging.
+
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, Once,
+    },
+    thread,
+};
+
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+use tokio::{
+    runtime::Builder as RuntimeBuilder,
+    signal::unix::{signal, SignalKind},
+};
+
+/// Long name of the option giving the directory for temporary files.
+pub const TMPDIR: &str = "tmpdir";
+/// Long name of the flag keeping the temporary files.
+pub const KEEP_TEMP: &str = "keep-temp";
+
+const PREFIX: &str = "casper-db-utils-";
+
+/// Directory in which temporary directories are created, if set by `init`.
+static ROOT: OnceCell<PathBuf> = OnceCell::new();
+static KEEP: AtomicBool = AtomicBool::new(false);
+/// Temporary directories which still exist.
+static REGISTERED: Lazy<Mutex<BTreeSet<PathBuf>>> = Lazy::new(Default::default);
+static SIGNAL_CLEANUP: Once = Once::new();
+
+/// Creates temporary directories in `maybe_root`, if set, instead of the
+/// system's temporary directory, and keeps them if `keep` is set.
+pub fn init(maybe_root: Option<PathBuf>, keep: bool) {
+    if let Some(root) = maybe_root {
+        let _ = ROOT.set(root);
+    }
+    KEEP.store(keep, Ordering::SeqCst);
+}
+
+/// Returns the directory in which temporary directories are created.
+pub fn root() -> PathBuf {
+    ROOT.get().cloned().unwrap_or_else(env::temp_dir)
+}
+
+fn remove(path: &Path) {
+    if KEEP.load(Ordering::SeqCst) {
+        info!("Keeping temporary directory {}.", path.display());
+    } else if let Err(io_err) = fs::remove_dir_all(path) {
+        warn!(
+            "Couldn't remove temporary directory {}: {}",
+            path.display(),
+            io_err
+        );
+    }
+}
+
+/// Removes the registered directories and exits once the program receives
+/// `SIGINT` or `SIGTERM`. Installed along with the first directory, so that
+/// subcommands without temporary directories keep their own handling of the
+/// signals.
+fn install_signal_cleanup() {
+    let spawn_result = thread::Builder::new()
+        .name("temp-cleanup".to_string())
+        .spawn(|| {
+            let wait_for_signal = async {
+                let mut sigint = signal(SignalKind::interrupt())?;
+                let mut sigterm = signal(SignalKind::terminate())?;
+                // Exit codes of a shell for a process killed by the signal.
+                let exit_code = tokio::select! {
+                    _ = sigint.recv() => 130,
+                    _ = sigterm.recv() => 143,
+                };
+                Ok::<_, IoError>(exit_code)
+            };
+            let exit_code = match RuntimeBuilder::new_current_thread()
+                .enable_all()
+                .build()
+                .and_then(|runtime| runtime.block_on(wait_for_signal))
+            {
+                Ok(exit_code) => exit_code,
+                Err(io_err) => {
+                    warn!("Couldn't watch signals to remove temporary directories: {io_err}");
+                    return;
+                }
+            };
+            let paths = std::mem::take(&mut *REGISTERED.lock().expect("poisoned lock"));
+            for path in paths {
+                remove(&path);
+            }
+            process::exit(exit_code);
+        });
+    if let Err(io_err) = spawn_result {
+        warn!("Couldn't watch signals to remove temporary directories: {io_err}");
+    }
+}
+
+/// A temporary directory, removed when dropped unless `--keep-temp` is set.
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates a directory in the directory given by `--tmpdir`, or in the
+    /// system's temporary directory if unset.
+    pub fn new() -> Result<Self, IoError> {
+        Self::new_in(root())
+    }
+
+    /// Creates a directory in `parent_dir`.
+    pub fn new_in<P: AsRef<Path>>(parent_dir: P) -> Result<Self, IoError> {
+        let path = tempfile::Builder::new()
+            .prefix(PREFIX)
+            .tempdir_in(parent_dir)?
+            .into_path();
+        SIGNAL_CLEANUP.call_once(install_signal_cleanup);
+        REGISTERED
+            .lock()
+            .expect("poisoned lock")
+            .insert(path.clone());
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        // The signal handler may be removing it already.
+        if REGISTERED.lock().expect("poisoned lock").remove(&self.path) {
+            remove(&self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::{TempDir, REGISTERED};
+
+    #[test]
+    fn temp_dir_should_be_removed_on_drop() {
+        let parent_dir = tempfile::tempdir().unwrap();
+        let temp_dir = TempDir::new_in(parent_dir.path()).unwrap();
+        let path = temp_dir.path().to_path_buf();
+        assert!(path.starts_with(parent_dir.path()));
+        fs::write(path.join("file"), b"contents").unwrap();
+        assert!(REGISTERED.lock().unwrap().contains(&path));
+
+        drop(temp_dir);
+        assert!(!path.exists());
+        assert!(!REGISTERED.lock().unwrap().contains(&path));
+    }
+}
@@ -0,0 +1,227 @@
+//! Controls keeping the heavy scans from starving the node process of disk
+//! bandwidth when run on a production host.
+//!
+//! `--io-nice` lowers the I/O scheduling priority of the process, and
+//! `--throttle-mb-s` caps the rate at which the scans read the databases.
+//! The scans report the bytes they read through [`consume`], which sleeps
+//! between batches of entries whenever the reads got ahead of the rate.
+
+use std::{
+    result::Result,
+    str::FromStr,
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use clap::{Arg, ArgMatches};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+
+const IO_NICE: &str = "io-nice";
+const THROTTLE_MB_S: &str = "throttle-mb-s";
+
+const BYTES_PER_MB: f64 = 1024.0 * 1024.0;
+/// Number of batches per second of throttled reads, between which the rate
+/// is checked.
+const BATCHES_PER_SEC: f64 = 10.0;
+
+/// Throttle installed by [`init_from_matches`], if any.
+static THROTTLE: OnceCell<Mutex<Throttle>> = OnceCell::new();
+
+/// I/O scheduling priority of the process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IoNice {
+    /// Only get disk time when no other process needs it.
+    Idle,
+    /// Best-effort priority level, from 0, the highest, to 7.
+    BestEffort(u8),
+}
+
+impl FromStr for IoNice {
+    type Err = String;
+
+    /// Parses `idle` or a best-effort level from 0 to 7.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value == "idle" {
+            return Ok(IoNice::Idle);
+        }
+        match value.parse::<u8>() {
+            Ok(level) if level <= 7 => Ok(IoNice::BestEffort(level)),
+            _ => Err(format!(
+                "invalid I/O priority \"{}\", expected \"idle\" or a level from 0 to 7",
+                value
+            )),
+        }
+    }
+}
+
+impl IoNice {
+    /// Sets the I/O scheduling priority of the whole process.
+    #[cfg(target_os = "linux")]
+    fn apply(self) -> Result<(), std::io::Error> {
+        const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+        const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+        const IOPRIO_CLASS_BE: libc::c_int = 2;
+        const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+
+        let priority = match self {
+            IoNice::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            IoNice::BestEffort(level) => {
+                (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | libc::c_int::from(level)
+            }
+        };
+        let result =
+            unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, priority) };
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn apply(self) -> Result<(), std::io::Error> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "I/O priorities are only supported on Linux",
+        ))
+    }
+}
+
+/// Rate limit on the bytes read by the scans.
+#[derive(Debug)]
+struct Throttle {
+    bytes_per_sec: f64,
+    /// Bytes read between two checks of the rate.
+    batch_bytes: u64,
+    started: Instant,
+    total_bytes: u64,
+    unchecked_bytes: u64,
+}
+
+impl Throttle {
+    fn new(mb_per_sec: f64) -> Self {
+        let bytes_per_sec = mb_per_sec * BYTES_PER_MB;
+        Self {
+            bytes_per_sec,
+            batch_bytes: ((bytes_per_sec / BATCHES_PER_SEC) as u64).max(1),
+            started: Instant::now(),
+            total_bytes: 0,
+            unchecked_bytes: 0,
+        }
+    }
+
+    /// Records `bytes` read, returning how long to wait for the reads to get
+    /// back to the rate. The rate is only checked once per batch.
+    fn consume(&mut self, bytes: u64) -> Duration {
+        self.total_bytes += bytes;
+        self.unchecked_bytes += bytes;
+        if self.unchecked_bytes < self.batch_bytes {
+            return Duration::ZERO;
+        }
+        self.unchecked_bytes = 0;
+        let due = Duration::from_secs_f64(self.total_bytes as f64 / self.bytes_per_sec);
+        due.saturating_sub(self.started.elapsed())
+    }
+}
+
+/// Records `bytes` read by a scan, sleeping if the reads are ahead of the
+/// rate set with `--throttle-mb-s`. Does nothing without a throttle.
+pub fn consume(bytes: usize) {
+    let throttle = match THROTTLE.get() {
+        Some(throttle) => throttle,
+        None => return,
+    };
+    let delay = throttle
+        .lock()
+        .expect("throttle lock should not be poisoned")
+        .consume(bytes as u64);
+    if !delay.is_zero() {
+        thread::sleep(delay);
+    }
+}
+
+pub fn io_nice_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(IO_NICE)
+        .display_order(display_order)
+        .long(IO_NICE)
+        .takes_value(true)
+        .value_name("idle|LEVEL")
+        .min_values(0)
+        .max_values(1)
+        .require_equals(true)
+        .default_missing_value("idle")
+        .validator(|value| value.parse::<IoNice>().map(|_| ()))
+        .help(
+            "Lower the I/O scheduling priority of the process, to the idle \
+            class by default or to the given best-effort level from 0 to 7. \
+            Linux only.",
+        )
+}
+
+pub fn throttle_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(THROTTLE_MB_S)
+        .display_order(display_order)
+        .long(THROTTLE_MB_S)
+        .takes_value(true)
+        .value_name("MIB_PER_S")
+        .validator(|value| match value.parse::<f64>() {
+            Ok(rate) if rate > 0.0 && rate.is_finite() => Ok(()),
+            Ok(_) => Err("rate must be a positive number".to_string()),
+            Err(parse_err) => Err(parse_err.to_string()),
+        })
+        .help(
+            "Limit the rate at which the databases are read, in MiB per \
+            second, pausing between batches of entries.",
+        )
+}
+
+/// Applies the arguments returned by `io_nice_arg` and `throttle_arg`. A
+/// priority which can't be set is only logged as a warning.
+pub fn init_from_matches(matches: &ArgMatches) {
+    if let Some(io_nice) = matches
+        .value_of(IO_NICE)
+        .map(|value| value.parse::<IoNice>().expect("should be validated"))
+    {
+        match io_nice.apply() {
+            Ok(()) => info!("Set the I/O priority to {:?}.", io_nice),
+            Err(io_err) => warn!("Couldn't set the I/O priority: {}", io_err),
+        }
+    }
+    if let Some(mb_per_sec) = matches
+        .value_of(THROTTLE_MB_S)
+        .map(|value| value.parse::<f64>().expect("should be validated"))
+    {
+        if THROTTLE.set(Mutex::new(Throttle::new(mb_per_sec))).is_ok() {
+            info!("Throttling reads to {} MiB/s.", mb_per_sec);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{IoNice, Throttle, BYTES_PER_MB};
+
+    #[test]
+    fn parse_io_nice() {
+        assert_eq!("idle".parse(), Ok(IoNice::Idle));
+        assert_eq!("0".parse(), Ok(IoNice::BestEffort(0)));
+        assert_eq!("7".parse(), Ok(IoNice::BestEffort(7)));
+        assert!("8".parse::<IoNice>().is_err());
+        assert!("low".parse::<IoNice>().is_err());
+    }
+
+    #[test]
+    fn throttle_should_delay_reads_ahead_of_rate() {
+        let mut throttle = Throttle::new(1.0);
+        // Reads within a batch aren't checked.
+        assert_eq!(throttle.consume(1), Duration::ZERO);
+        // Two seconds worth of reads at once.
+        let delay = throttle.consume(2 * BYTES_PER_MB as u64 - 1);
+        assert!(delay > Duration::from_millis(1900), "{:?}", delay);
+        assert!(delay <= Duration::from_secs(2), "{:?}", delay);
+    }
+}
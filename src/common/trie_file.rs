@@ -0,0 +1,197 @@
+//! Portable flat file format for the nodes of global state tries, written
+//! by `trie-export` and read by `trie-import`.
+//!
+//! A file starts with the `CSPRTRIE` magic bytes, the format version and the
+//! state roots it holds the tries of. Then come the trie nodes, each as its
+//! 32-byte hash, the length of the node as a little-endian `u32` and the
+//! `bytesrepr` encoded node. An all-zero hash with a zero length marks the
+//! end of the nodes, followed by their count as a little-endian `u64`, so
+//! that a truncated file is detected. The whole file may be compressed with
+//! zstd.
+
+use std::io::{Error as IoError, ErrorKind, Read, Write};
+
+use casper_hashing::Digest;
+
+/// Magic bytes starting every trie file.
+pub const MAGIC: &[u8; 8] = b"CSPRTRIE";
+/// Version of the format written.
+pub const VERSION: u32 = 1;
+/// Hash marking the end of the nodes.
+const END_MARKER: [u8; Digest::LENGTH] = [0; Digest::LENGTH];
+
+fn invalid_data(message: String) -> IoError {
+    IoError::new(ErrorKind::InvalidData, message)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, IoError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_digest<R: Read>(reader: &mut R) -> Result<Digest, IoError> {
+    let mut bytes = [0u8; Digest::LENGTH];
+    reader.read_exact(&mut bytes)?;
+    Ok(Digest::from(bytes))
+}
+
+/// Writes trie nodes in the trie file format.
+pub struct TrieFileWriter<W: Write> {
+    writer: W,
+    node_count: u64,
+}
+
+impl<W: Write> TrieFileWriter<W> {
+    /// Writes the header of a file holding the tries under `state_roots`.
+    pub fn new(mut writer: W, state_roots: &[Digest]) -> Result<Self, IoError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(state_roots.len() as u32).to_le_bytes())?;
+        for state_root in state_roots {
+            writer.write_all(state_root.as_ref())?;
+        }
+        Ok(Self {
+            writer,
+            node_count: 0,
+        })
+    }
+
+    /// Writes the node `raw_trie` stored under `trie_key`.
+    pub fn write_node(&mut self, trie_key: &Digest, raw_trie: &[u8]) -> Result<(), IoError> {
+        let len = u32::try_from(raw_trie.len())
+            .map_err(|_| invalid_data(format!("trie node {} is too large", trie_key)))?;
+        self.writer.write_all(trie_key.as_ref())?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(raw_trie)?;
+        self.node_count += 1;
+        Ok(())
+    }
+
+    /// Returns the number of nodes written so far.
+    pub fn node_count(&self) -> u64 {
+        self.node_count
+    }
+
+    /// Writes the end of the file, returning the inner writer.
+    pub fn finish(mut self) -> Result<W, IoError> {
+        self.writer.write_all(&END_MARKER)?;
+        self.writer.write_all(&0u32.to_le_bytes())?;
+        self.writer.write_all(&self.node_count.to_le_bytes())?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads trie nodes from a file in the trie file format.
+pub struct TrieFileReader<R: Read> {
+    reader: R,
+    state_roots: Vec<Digest>,
+    node_count: u64,
+    done: bool,
+}
+
+impl<R: Read> TrieFileReader<R> {
+    /// Reads the header of the file, failing if it isn't a trie file of a
+    /// known version.
+    pub fn new(mut reader: R) -> Result<Self, IoError> {
+        let mut magic = [0u8; MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid_data("not a trie file".to_string()));
+        }
+        let version = read_u32(&mut reader)?;
+        if version != VERSION {
+            return Err(invalid_data(format!(
+                "unsupported trie file version {}",
+                version
+            )));
+        }
+        let state_root_count = read_u32(&mut reader)?;
+        let state_roots = (0..state_root_count)
+            .map(|_| read_digest(&mut reader))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            reader,
+            state_roots,
+            node_count: 0,
+            done: false,
+        })
+    }
+
+    /// Returns the state roots whose tries the file holds.
+    pub fn state_roots(&self) -> &[Digest] {
+        &self.state_roots
+    }
+
+    /// Returns the next node and the hash it's stored under, or `None` once
+    /// all nodes were read and the file was found complete.
+    pub fn next_node(&mut self) -> Result<Option<(Digest, Vec<u8>)>, IoError> {
+        if self.done {
+            return Ok(None);
+        }
+        let trie_key = read_digest(&mut self.reader)?;
+        let len = read_u32(&mut self.reader)?;
+        if trie_key.value() == END_MARKER && len == 0 {
+            let mut count_bytes = [0u8; 8];
+            self.reader.read_exact(&mut count_bytes)?;
+            let expected_count = u64::from_le_bytes(count_bytes);
+            if expected_count != self.node_count {
+                return Err(invalid_data(format!(
+                    "trie file should hold {} nodes, found {}",
+                    expected_count, self.node_count
+                )));
+            }
+            self.done = true;
+            return Ok(None);
+        }
+        let mut raw_trie = vec![0u8; len as usize];
+        self.reader.read_exact(&mut raw_trie)?;
+        self.node_count += 1;
+        Ok(Some((trie_key, raw_trie)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use casper_hashing::Digest;
+
+    use super::{TrieFileReader, TrieFileWriter};
+
+    #[test]
+    fn trie_file_roundtrip() {
+        let state_roots = vec![Digest::hash([1u8]), Digest::hash([2u8])];
+        let nodes = vec![
+            (Digest::hash([3u8]), vec![3u8; 10]),
+            (Digest::hash([4u8]), vec![]),
+        ];
+        let mut writer = TrieFileWriter::new(vec![], &state_roots).unwrap();
+        for (trie_key, raw_trie) in nodes.iter() {
+            writer.write_node(trie_key, raw_trie).unwrap();
+        }
+        let bytes = writer.finish().unwrap();
+
+        let mut reader = TrieFileReader::new(bytes.as_slice()).unwrap();
+        assert_eq!(reader.state_roots(), state_roots.as_slice());
+        let mut read_nodes = vec![];
+        while let Some(node) = reader.next_node().unwrap() {
+            read_nodes.push(node);
+        }
+        assert_eq!(read_nodes, nodes);
+
+        // A truncated file is an error rather than fewer nodes.
+        let mut reader = TrieFileReader::new(&bytes[..bytes.len() - 20]).unwrap();
+        reader.next_node().unwrap();
+        reader.next_node().unwrap();
+        assert_eq!(
+            reader.next_node().unwrap_err().kind(),
+            ErrorKind::UnexpectedEof
+        );
+        assert_eq!(
+            TrieFileReader::new(&b"CSPRTRIX"[..]).err().unwrap().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+}
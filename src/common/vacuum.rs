@@ -0,0 +1,185 @@
+//! Estimates of the space a compaction would reclaim once entries were
+//! removed from a database.
+//!
+//! LMDB never shrinks its data file: the pages freed by removals go to its
+//! free list and are reused by later writes. Only a copy with compaction,
+//! like `mdb_copy -c`, leaves them out, while the `unsparse` subcommand only
+//! trims the unused space at the end of the file.
+
+use std::{collections::BTreeMap, result::Result};
+
+use lmdb::{Environment, Error as LmdbError, Transaction};
+use log::info;
+use serde::Serialize;
+
+use super::lmdb_utils;
+
+/// Share of the pages in use which must be free for a compaction to be
+/// worth copying the whole file.
+const WORTHWHILE_FREE_SHARE: f64 = 0.1;
+
+/// Pages taken by a database before and after removals.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct DatabasePages {
+    pub before: u64,
+    pub after: u64,
+}
+
+impl DatabasePages {
+    /// Returns the number of pages the removals freed.
+    pub fn freed(&self) -> u64 {
+        self.before.saturating_sub(self.after)
+    }
+}
+
+/// Freed space of an environment after removals.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VacuumReport {
+    pub page_size: u32,
+    pub databases: BTreeMap<String, DatabasePages>,
+    /// Number of pages in the free list of the environment.
+    pub free_pages: u64,
+    /// Bytes taken by the pages in use, including the free ones.
+    pub used_bytes: u64,
+    /// Bytes a compaction would remove from the data file.
+    pub reclaimable_bytes: u64,
+}
+
+impl VacuumReport {
+    /// Returns whether enough of the data file is free for a compaction to
+    /// be worthwhile.
+    pub fn compaction_worthwhile(&self) -> bool {
+        self.used_bytes > 0
+            && self.reclaimable_bytes as f64 >= self.used_bytes as f64 * WORTHWHILE_FREE_SHARE
+    }
+
+    /// Logs the freed pages of each database, the reclaimable space and
+    /// whether to compact the data file.
+    pub fn log(&self) {
+        for (db_name, pages) in &self.databases {
+            info!(
+                "Database {} went from {} to {} pages, {} bytes freed.",
+                db_name,
+                pages.before,
+                pages.after,
+                pages.freed() * self.page_size as u64
+            );
+        }
+        info!(
+            "{} of the {} bytes in use are in free pages and would be reclaimed \
+            by a compaction.",
+            self.reclaimable_bytes, self.used_bytes
+        );
+        if self.compaction_worthwhile() {
+            info!(
+                "Compacting the database is worthwhile, e.g. with `mdb_copy -c`, \
+                followed by `unsparse` on the copy."
+            );
+        } else {
+            info!("Compacting the database isn't worthwhile yet.");
+        }
+    }
+}
+
+/// Returns the pages taken by each of the databases named `db_names` in
+/// `env`, skipping the ones which don't exist.
+pub fn page_counts(
+    env: &Environment,
+    db_names: &[&str],
+) -> Result<BTreeMap<String, u64>, LmdbError> {
+    let txn = env.begin_ro_txn()?;
+    let mut page_counts = BTreeMap::new();
+    for db_name in db_names {
+        match unsafe { txn.open_db(Some(db_name)) } {
+            Ok(db) => {
+                page_counts.insert(db_name.to_string(), lmdb_utils::page_count(&txn, db)?);
+            }
+            Err(LmdbError::NotFound) => (),
+            Err(lmdb_err) => return Err(lmdb_err),
+        }
+    }
+    Ok(page_counts)
+}
+
+/// Compares the pages taken by the databases of `env` with the counts taken
+/// by `page_counts` before removals, and counts the free pages.
+pub fn vacuum_report(
+    env: &Environment,
+    pages_before: BTreeMap<String, u64>,
+) -> Result<VacuumReport, LmdbError> {
+    let db_names: Vec<&str> = pages_before.keys().map(String::as_str).collect();
+    let pages_after = page_counts(env, &db_names)?;
+    let databases = pages_before
+        .iter()
+        .map(|(db_name, before)| {
+            let pages = DatabasePages {
+                before: *before,
+                after: pages_after.get(db_name).copied().unwrap_or_default(),
+            };
+            (db_name.clone(), pages)
+        })
+        .collect();
+    let page_size = env.stat()?.page_size();
+    let free_pages = lmdb_utils::free_page_count(&env.begin_ro_txn()?)?;
+    Ok(VacuumReport {
+        page_size,
+        databases,
+        free_pages,
+        used_bytes: lmdb_utils::used_size(env)?,
+        reclaimable_bytes: free_pages * page_size as u64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lmdb::{Transaction, WriteFlags};
+
+    use super::{page_counts, vacuum_report};
+    use crate::test_utils::LmdbTestFixture;
+
+    #[test]
+    fn vacuum_report_should_count_freed_pages() {
+        let fixture = LmdbTestFixture::new(vec!["kept", "purged"], None);
+        let value = vec![7u8; 1024];
+        for db_name in ["kept", "purged"] {
+            let mut txn = fixture.env.begin_rw_txn().unwrap();
+            for key in 0u32..200 {
+                txn.put(
+                    *fixture.db(Some(db_name)).unwrap(),
+                    &key.to_le_bytes(),
+                    &value,
+                    WriteFlags::empty(),
+                )
+                .unwrap();
+            }
+            txn.commit().unwrap();
+        }
+
+        let pages_before = page_counts(&fixture.env, &["kept", "missing", "purged"]).unwrap();
+        assert_eq!(pages_before.len(), 2);
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        for key in 0u32..200 {
+            txn.del(
+                *fixture.db(Some("purged")).unwrap(),
+                &key.to_le_bytes(),
+                None,
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let report = vacuum_report(&fixture.env, pages_before).unwrap();
+        let kept = report.databases["kept"];
+        let purged = report.databases["purged"];
+        assert_eq!(kept.freed(), 0);
+        assert!(kept.after > 0);
+        assert_eq!(purged.after, 0);
+        assert!(purged.before > 0);
+        assert!(report.free_pages >= purged.before);
+        assert_eq!(
+            report.reclaimable_bytes,
+            report.free_pages * report.page_size as u64
+        );
+        assert!(report.compaction_worthwhile());
+    }
+}
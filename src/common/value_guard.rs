@@ -0,0 +1,188 @@
+//! Keeps the scans from handing suspicious values to the deserializers.
+//!
+//! An empty value, or one larger than any the node writes, points to a
+//! corrupt entry rather than to a value worth parsing: bincode would at best
+//! report a misleading error for it, at worst try to allocate whatever a
+//! garbage length prefix claims. The checks report the suspicious values
+//! found by [`check`] as invalid entries, while the other scans pass each
+//! value through [`is_parsable`], which logs a warning with the key of the
+//! suspicious ones so they can be skipped. `--max-value-mb` sets the size
+//! above which values are suspicious.
+
+use std::fmt::{Display, Formatter, Result as FormatterResult};
+
+use clap::{Arg, ArgMatches};
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+
+const MAX_VALUE_MB: &str = "max-value-mb";
+
+const BYTES_PER_MB: usize = 1024 * 1024;
+/// Size above which values are suspicious unless set with `--max-value-mb`.
+pub const DEFAULT_MAX_VALUE_SIZE: usize = 256 * BYTES_PER_MB;
+
+/// Size limit installed by [`init_from_matches`], if any.
+static MAX_VALUE_SIZE: OnceCell<usize> = OnceCell::new();
+
+/// Why a value isn't handed to a deserializer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Suspicious {
+    /// The value has no bytes at all.
+    Empty,
+    /// The value is larger than the limit.
+    Oversized { size: usize, max_size: usize },
+}
+
+impl Display for Suspicious {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FormatterResult {
+        match self {
+            Self::Empty => write!(f, "empty value"),
+            Self::Oversized { size, max_size } => write!(
+                f,
+                "value of {size} bytes, above the limit of {max_size} bytes"
+            ),
+        }
+    }
+}
+
+/// Returns why `raw_val` is suspicious under the size limit `max_size`, if
+/// it is.
+pub fn inspect(raw_val: &[u8], max_size: usize) -> Option<Suspicious> {
+    if raw_val.is_empty() {
+        Some(Suspicious::Empty)
+    } else if raw_val.len() > max_size {
+        Some(Suspicious::Oversized {
+            size: raw_val.len(),
+            max_size,
+        })
+    } else {
+        None
+    }
+}
+
+/// Returns the size above which values are suspicious.
+pub fn max_value_size() -> usize {
+    MAX_VALUE_SIZE
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_MAX_VALUE_SIZE)
+}
+
+/// Returns why `raw_val` is suspicious under the size limit in use, as an
+/// error, if it is.
+pub fn check(raw_val: &[u8]) -> Result<(), Suspicious> {
+    match inspect(raw_val, max_value_size()) {
+        Some(suspicious) => Err(suspicious),
+        None => Ok(()),
+    }
+}
+
+/// Returns whether the value under `raw_key` in the database `db_name` can be
+/// handed to a deserializer, logging a warning with the key if it can't.
+pub fn is_parsable(db_name: &str, raw_key: &[u8], raw_val: &[u8]) -> bool {
+    match inspect(raw_val, max_value_size()) {
+        Some(suspicious) => {
+            warn!(
+                "{} database: skipping {} under key {}, which may be corrupt",
+                db_name,
+                suspicious,
+                hex::encode(raw_key)
+            );
+            false
+        }
+        None => true,
+    }
+}
+
+pub fn max_value_arg(display_order: usize) -> Arg<'static> {
+    Arg::new(MAX_VALUE_MB)
+        .display_order(display_order)
+        .long(MAX_VALUE_MB)
+        .takes_value(true)
+        .value_name("MIB")
+        .validator(|value| match value.parse::<usize>() {
+            Ok(0) => Err("size must be greater than 0".to_string()),
+            Ok(_) => Ok(()),
+            Err(parse_err) => Err(parse_err.to_string()),
+        })
+        .help(
+            "Size in MiB above which values are considered corrupt, along \
+            with empty values. They are reported as invalid entries by the \
+            checks and skipped with a warning by the other commands instead \
+            of being parsed. Defaults to 256.",
+        )
+}
+
+/// Applies the argument returned by `max_value_arg`.
+pub fn init_from_matches(matches: &ArgMatches) {
+    if let Some(max_value_mb) = matches
+        .value_of(MAX_VALUE_MB)
+        .map(|value| value.parse::<usize>().expect("should be validated"))
+    {
+        let max_value_size = max_value_mb.saturating_mul(BYTES_PER_MB);
+        if MAX_VALUE_SIZE.set(max_value_size).is_ok() {
+            info!("Skipping values above {} MiB.", max_value_mb);
+        }
+    }
+}
+
+/// Counts the values a scan skipped as suspicious.
+#[derive(Debug, Default)]
+pub struct SkipCounter {
+    skipped: usize,
+}
+
+impl SkipCounter {
+    /// Same as [`is_parsable`], counting the values which aren't.
+    pub fn is_parsable(&mut self, db_name: &str, raw_key: &[u8], raw_val: &[u8]) -> bool {
+        let parsable = is_parsable(db_name, raw_key, raw_val);
+        if !parsable {
+            self.skipped += 1;
+        }
+        parsable
+    }
+
+    /// Returns the number of values skipped.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Logs a warning with the number of values skipped in the database
+    /// `db_name`, if any.
+    pub fn log(&self, db_name: &str) {
+        if self.skipped > 0 {
+            warn!(
+                "{} database: skipped {} empty or oversized values.",
+                db_name, self.skipped
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inspect, SkipCounter, Suspicious};
+
+    #[test]
+    fn inspect_should_flag_empty_and_oversized_values() {
+        assert_eq!(inspect(&[], 4), Some(Suspicious::Empty));
+        assert_eq!(inspect(&[0; 4], 4), None);
+        assert_eq!(
+            inspect(&[0; 5], 4),
+            Some(Suspicious::Oversized {
+                size: 5,
+                max_size: 4
+            })
+        );
+    }
+
+    #[test]
+    fn skip_counter_should_count_suspicious_values() {
+        let mut counter = SkipCounter::default();
+        assert!(counter.is_parsable("mock", b"key", &[1]));
+        assert!(!counter.is_parsable("mock", b"key", &[]));
+        assert!(counter.is_parsable("mock", b"key", &[0; 1024]));
+        assert!(!counter.is_parsable("mock", b"other key", &[]));
+        assert_eq!(counter.skipped(), 2);
+    }
+}
@@ -0,0 +1,198 @@
+mod parallel;
+
+use std::{
+    io::{BufReader, BufWriter, Error as IoError, Read, Write},
+    result::Result,
+};
+
+use log::info;
+use thiserror::Error as ThisError;
+use zstd::{Decoder, Encoder};
+
+const COMPRESSION_LEVEL: i32 = 15;
+pub(crate) const WINDOW_LOG_MAX_SIZE: u32 = 31;
+/// Log of the uncompressed size of the independent frames written by
+/// [`FrameEncoder`], which is also the largest useful window for them.
+#[cfg(not(test))]
+const FRAME_WINDOW_LOG: u32 = 25;
+#[cfg(test)]
+const FRAME_WINDOW_LOG: u32 = 14;
+/// Uncompressed size of the independent frames written by [`FrameEncoder`],
+/// 32 MiB. Each thread decoding a stream in parallel buffers about a frame.
+pub(crate) const FRAME_SIZE: usize = 1 << FRAME_WINDOW_LOG;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error enabling frame checksums on zstd stream: {0}")]
+    Checksum(IoError),
+    #[error("Error setting up zstd decoding stream: {0}")]
+    Decode(IoError),
+    #[error("Error setting up zstd encoding stream: {0}")]
+    Encode(IoError),
+    #[error("Error setting zstd window log: {0}")]
+    WindowLog(IoError),
+}
+
+pub fn zstd_decode_stream<'a, R: Read>(stream: R) -> Result<Decoder<'a, BufReader<R>>, Error> {
+    let mut decoder = Decoder::new(stream).map_err(Error::Decode)?;
+    decoder
+        .window_log_max(WINDOW_LOG_MAX_SIZE)
+        .map_err(Error::Decode)?;
+    info!("Set zstd window log max size to {}.", WINDOW_LOG_MAX_SIZE);
+
+    Ok(decoder)
+}
+
+/// Decodes `stream`, splitting it into frames which are decoded on up to
+/// `threads` threads in parallel. With a single thread, this is the same as
+/// [`zstd_decode_stream`].
+///
+/// Only streams made of several frames, such as the ones written by
+/// [`FrameEncoder`], are sped up. A single frame is still decoded by one
+/// thread, while the compressed stream is read on another.
+pub fn zstd_decode_stream_parallel<R: Read + Send + 'static>(
+    stream: R,
+    threads: usize,
+) -> Result<Box<dyn Read>, Error> {
+    if threads <= 1 {
+        return Ok(Box::new(zstd_decode_stream(stream)?));
+    }
+    info!("Decoding zstd frames on {} threads.", threads);
+    Ok(Box::new(parallel::ParallelDecoder::new(stream, threads)))
+}
+
+pub fn zstd_encode_stream<'a, W: Write>(stream: W) -> Result<Encoder<'a, BufWriter<W>>, Error> {
+    let mut encoder =
+        Encoder::new(BufWriter::new(stream), COMPRESSION_LEVEL).map_err(Error::Encode)?;
+    encoder
+        .window_log(WINDOW_LOG_MAX_SIZE)
+        .map_err(Error::WindowLog)?;
+    encoder.include_checksum(true).map_err(Error::Checksum)?;
+    Ok(encoder)
+}
+
+/// Creates an encoder with the given compression level and the default window
+/// size, so that its output can be decoded by the `zstd` command line tool
+/// without any extra flags.
+pub fn zstd_encode_stream_with_level<'a, W: Write>(
+    stream: W,
+    level: i32,
+) -> Result<Encoder<'a, BufWriter<W>>, Error> {
+    let mut encoder = Encoder::new(BufWriter::new(stream), level).map_err(Error::Encode)?;
+    encoder.include_checksum(true).map_err(Error::Checksum)?;
+    Ok(encoder)
+}
+
+/// Encoder writing the stream as independent frames of [`FRAME_SIZE`]
+/// uncompressed bytes, so that it can be decoded in parallel by
+/// [`zstd_decode_stream_parallel`]. Any zstd decoder reads the frames as a
+/// single stream.
+pub struct FrameEncoder<'a, W: Write> {
+    /// Encoder of the current frame, only `None` while switching frames.
+    encoder: Option<Encoder<'a, W>>,
+    frame_len: usize,
+}
+
+fn frame_encoder<'a, W: Write>(stream: W) -> Result<Encoder<'a, W>, IoError> {
+    let mut encoder = Encoder::new(stream, COMPRESSION_LEVEL)?;
+    encoder.window_log(FRAME_WINDOW_LOG)?;
+    encoder.include_checksum(true)?;
+    Ok(encoder)
+}
+
+impl<'a, W: Write> FrameEncoder<'a, W> {
+    fn encoder(&mut self) -> &mut Encoder<'a, W> {
+        self.encoder.as_mut().expect("should have a frame encoder")
+    }
+
+    /// Ends the last frame, returning the underlying stream.
+    pub fn finish(mut self) -> Result<W, IoError> {
+        self.encoder
+            .take()
+            .expect("should have a frame encoder")
+            .finish()
+    }
+}
+
+impl<'a, W: Write> Write for FrameEncoder<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        if self.frame_len == FRAME_SIZE {
+            let stream = self
+                .encoder
+                .take()
+                .expect("should have a frame encoder")
+                .finish()?;
+            self.encoder = Some(frame_encoder(stream)?);
+            self.frame_len = 0;
+        }
+        let len = buf.len().min(FRAME_SIZE - self.frame_len);
+        let bytes_written = self.encoder().write(&buf[..len])?;
+        self.frame_len += bytes_written;
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.encoder().flush()
+    }
+}
+
+/// Creates an encoder writing independent frames, see [`FrameEncoder`].
+pub fn zstd_encode_frames<'a, W: Write>(
+    stream: W,
+) -> Result<FrameEncoder<'a, BufWriter<W>>, Error> {
+    Ok(FrameEncoder {
+        encoder: Some(frame_encoder(BufWriter::new(stream)).map_err(Error::Encode)?),
+        frame_len: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use super::*;
+
+    fn encoded_frames(payload: &[u8]) -> Vec<u8> {
+        let mut encoder = zstd_encode_frames(vec![]).unwrap();
+        encoder.write_all(payload).unwrap();
+        encoder.finish().unwrap().into_inner().unwrap()
+    }
+
+    fn decode_parallel(encoded: Vec<u8>, threads: usize) -> Result<Vec<u8>, IoError> {
+        let mut decoded = vec![];
+        zstd_decode_stream_parallel(Cursor::new(encoded), threads)
+            .unwrap()
+            .read_to_end(&mut decoded)?;
+        Ok(decoded)
+    }
+
+    #[test]
+    fn frames_should_decode_in_parallel() {
+        // A few frames and a half, with some repetitions to compress.
+        let payload: Vec<u8> = (0..FRAME_SIZE * 7 / 2)
+            .map(|idx| (idx % 251) as u8 ^ (idx / 4096) as u8)
+            .collect();
+        let encoded = encoded_frames(&payload);
+
+        // Any decoder reads the frames as a single stream.
+        let mut decoded = vec![];
+        zstd_decode_stream(encoded.as_slice())
+            .unwrap()
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, payload);
+
+        for threads in [1, 2, 8] {
+            assert_eq!(decode_parallel(encoded.clone(), threads).unwrap(), payload);
+        }
+    }
+
+    #[test]
+    fn corrupt_frames_should_fail_to_decode() {
+        let payload = vec![7u8; FRAME_SIZE * 2];
+        let mut encoded = encoded_frames(&payload);
+        encoded.truncate(encoded.len() - 1);
+        assert!(decode_parallel(encoded, 4).is_err());
+        assert!(decode_parallel(b"not a zstd stream".to_vec(), 4).is_err());
+    }
+}
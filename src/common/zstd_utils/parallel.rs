@@ -0,0 +1,287 @@
+use std::{
+    io::{self, BufRead, BufReader, Error as IoError, ErrorKind, Read},
+    result::Result,
+    sync::mpsc::{self, Receiver, SyncSender},
+    thread,
+};
+
+use zstd::Decoder;
+
+use super::{FRAME_SIZE, WINDOW_LOG_MAX_SIZE};
+
+/// Magic number starting every zstd frame.
+const FRAME_MAGIC: u32 = 0xFD2F_B528;
+/// Magic numbers of skippable frames, once masked with
+/// `SKIPPABLE_FRAME_MASK`.
+const SKIPPABLE_FRAME_MAGIC: u32 = 0x184D_2A50;
+const SKIPPABLE_FRAME_MASK: u32 = 0xFFFF_FFF0;
+/// Size of the chunks of compressed and decompressed data passed between
+/// threads.
+#[cfg(not(test))]
+const CHUNK_SIZE: usize = 1 << 20;
+#[cfg(test)]
+const CHUNK_SIZE: usize = 1 << 10;
+/// Number of chunks buffered on each side of the decoder of a frame, enough
+/// to hold a whole frame written by `FrameEncoder`.
+const CHUNKS_PER_FRAME: usize = FRAME_SIZE / CHUNK_SIZE;
+
+type Chunk = Result<Vec<u8>, IoError>;
+
+/// Reads the chunks sent through a channel until it's closed.
+struct ChunkReader {
+    chunks: Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        while self.offset == self.chunk.len() {
+            match self.chunks.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk;
+                    self.offset = 0;
+                }
+                Err(_) => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.offset);
+        buf[..len].copy_from_slice(&self.chunk[self.offset..self.offset + len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
+
+/// Decodes the single frame sent through `input`, sending the decompressed
+/// chunks through `output` until the frame ends or an error occurs.
+fn decode_frame(input: Receiver<Vec<u8>>, output: SyncSender<Chunk>) {
+    let chunk_reader = ChunkReader {
+        chunks: input,
+        chunk: vec![],
+        offset: 0,
+    };
+    let mut decoder = match Decoder::new(chunk_reader).and_then(|mut decoder| {
+        decoder.window_log_max(WINDOW_LOG_MAX_SIZE)?;
+        Ok(decoder.single_frame())
+    }) {
+        Ok(decoder) => decoder,
+        Err(io_err) => {
+            let _ = output.send(Err(io_err));
+            return;
+        }
+    };
+    loop {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        match (&mut decoder)
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)
+        {
+            Ok(0) => return,
+            Ok(_) => {
+                if output.send(Ok(chunk)).is_err() {
+                    return;
+                }
+            }
+            Err(io_err) => {
+                let _ = output.send(Err(io_err));
+                return;
+            }
+        }
+    }
+}
+
+/// The compressed bytes of a frame being forwarded to its decoder.
+struct FrameInput {
+    sender: SyncSender<Vec<u8>>,
+    chunk: Vec<u8>,
+}
+
+impl FrameInput {
+    /// Forwards the next `len` bytes of `reader` to the decoder of the frame.
+    /// Returns `false` if the decoder stopped, having failed already.
+    fn copy<R: Read>(&mut self, reader: &mut R, len: u64) -> Result<bool, IoError> {
+        let mut remaining = len;
+        while remaining > 0 {
+            if self.chunk.len() >= CHUNK_SIZE && !self.flush() {
+                return Ok(false);
+            }
+            let to_read = remaining.min((CHUNK_SIZE - self.chunk.len()) as u64);
+            let bytes_read = reader.take(to_read).read_to_end(&mut self.chunk)?;
+            if bytes_read == 0 {
+                return Err(IoError::new(
+                    ErrorKind::UnexpectedEof,
+                    "incomplete zstd frame",
+                ));
+            }
+            remaining -= bytes_read as u64;
+        }
+        Ok(true)
+    }
+
+    fn flush(&mut self) -> bool {
+        let chunk = std::mem::replace(&mut self.chunk, Vec::with_capacity(CHUNK_SIZE));
+        chunk.is_empty() || self.sender.send(chunk).is_ok()
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32, IoError> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+/// Length of a frame header after its descriptor byte, following the zstd
+/// format specification.
+fn frame_header_len(descriptor: u8) -> Result<u64, IoError> {
+    if descriptor & 0x08 != 0 {
+        return Err(IoError::new(
+            ErrorKind::InvalidData,
+            "reserved bit set in zstd frame header",
+        ));
+    }
+    let single_segment = descriptor & 0x20 != 0;
+    let window_len = if single_segment { 0 } else { 1 };
+    let dictionary_id_len = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+    let content_size_len = match descriptor >> 6 {
+        0 if single_segment => 1,
+        0 => 0,
+        1 => 2,
+        2 => 4,
+        _ => 8,
+    };
+    Ok(window_len + dictionary_id_len + content_size_len)
+}
+
+/// Forwards the frame starting after its magic number in `reader` to a new
+/// decoder thread, whose output is queued in `frames`. Returns `false` if
+/// decoding should stop.
+fn split_frame<R: Read>(
+    reader: &mut R,
+    frames: &SyncSender<Receiver<Chunk>>,
+) -> Result<bool, IoError> {
+    let (input_sender, input_receiver) = mpsc::sync_channel(CHUNKS_PER_FRAME);
+    let (output_sender, output_receiver) = mpsc::sync_channel(CHUNKS_PER_FRAME);
+    // The output is queued before the decoder starts, so that no more than
+    // the capacity of the queue plus the frame being read are decoded at
+    // the same time.
+    if frames.send(output_receiver).is_err() {
+        return Ok(false);
+    }
+    thread::spawn(move || decode_frame(input_receiver, output_sender));
+
+    let mut frame = FrameInput {
+        sender: input_sender,
+        chunk: FRAME_MAGIC.to_le_bytes().to_vec(),
+    };
+    let mut descriptor = [0u8; 1];
+    reader.read_exact(&mut descriptor)?;
+    frame.chunk.push(descriptor[0]);
+    if !frame.copy(reader, frame_header_len(descriptor[0])?)? {
+        return Ok(false);
+    }
+    loop {
+        let mut block_header = [0u8; 3];
+        reader.read_exact(&mut block_header)?;
+        frame.chunk.extend_from_slice(&block_header);
+        let block_header =
+            u32::from_le_bytes([block_header[0], block_header[1], block_header[2], 0]);
+        let block_len = match (block_header >> 1) & 0x03 {
+            // Raw and compressed blocks.
+            0 | 2 => block_header >> 3,
+            // RLE blocks hold a single byte.
+            1 => 1,
+            _ => {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "reserved zstd block type",
+                ))
+            }
+        };
+        if !frame.copy(reader, block_len as u64)? {
+            return Ok(false);
+        }
+        if block_header & 0x01 != 0 {
+            break;
+        }
+    }
+    let checksum_len = if descriptor[0] & 0x04 != 0 { 4 } else { 0 };
+    Ok(frame.copy(reader, checksum_len)? && frame.flush())
+}
+
+/// Splits the zstd stream read from `stream` into frames decoded by their
+/// own threads, queuing their output in `frames` in order.
+fn split_frames<R: Read>(stream: R, frames: SyncSender<Receiver<Chunk>>) {
+    let mut reader = BufReader::with_capacity(CHUNK_SIZE, stream);
+    let result = (|| -> Result<(), IoError> {
+        while !reader.fill_buf()?.is_empty() {
+            let magic = read_u32(&mut reader)?;
+            if magic & SKIPPABLE_FRAME_MASK == SKIPPABLE_FRAME_MAGIC {
+                let len = read_u32(&mut reader)? as u64;
+                io::copy(&mut (&mut reader).take(len), &mut io::sink())?;
+            } else if magic != FRAME_MAGIC {
+                return Err(IoError::new(
+                    ErrorKind::InvalidData,
+                    "unknown zstd frame magic number",
+                ));
+            } else if !split_frame(&mut reader, &frames)? {
+                return Ok(());
+            }
+        }
+        Ok(())
+    })();
+    if let Err(io_err) = result {
+        let (error_sender, error_receiver) = mpsc::sync_channel(1);
+        let _ = error_sender.send(Err(io_err));
+        let _ = frames.send(error_receiver);
+    }
+}
+
+/// Reader over a zstd stream whose frames are decoded in parallel. The
+/// compressed stream is read and split into frames on a thread of its own.
+pub(super) struct ParallelDecoder {
+    frames: Receiver<Receiver<Chunk>>,
+    maybe_frame: Option<Receiver<Chunk>>,
+    chunk: Vec<u8>,
+    offset: usize,
+}
+
+impl ParallelDecoder {
+    pub(super) fn new<R: Read + Send + 'static>(stream: R, threads: usize) -> Self {
+        // The frame being read by the splitting thread is decoded along with
+        // the queued ones.
+        let (frames_sender, frames_receiver) = mpsc::sync_channel(threads.saturating_sub(1));
+        thread::spawn(move || split_frames(stream, frames_sender));
+        Self {
+            frames: frames_receiver,
+            maybe_frame: None,
+            chunk: vec![],
+            offset: 0,
+        }
+    }
+}
+
+impl Read for ParallelDecoder {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        while self.offset == self.chunk.len() {
+            let frame = match self.maybe_frame.as_ref() {
+                Some(frame) => frame,
+                None => match self.frames.recv() {
+                    Ok(frame) => self.maybe_frame.insert(frame),
+                    // The whole stream was decoded.
+                    Err(_) => return Ok(0),
+                },
+            };
+            match frame.recv() {
+                Ok(chunk) => {
+                    self.chunk = chunk?;
+                    self.offset = 0;
+                }
+                Err(_) => self.maybe_frame = None,
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.offset);
+        buf[..len].copy_from_slice(&self.chunk[self.offset..self.offset + len]);
+        self.offset += len;
+        Ok(len)
+    }
+}
@@ -0,0 +1,13 @@
+//! Utilities for working with databases of the Casper blockchain.
+//!
+//! The `casper-db-utils` binary is built on top of this library. Besides the
+//! subcommands, it exposes [`BlockIterator`] for traversing the blocks of a
+//! storage database in height order, and [`common::lmdb_utils`] for the
+//! statistics of LMDB environments and their databases.
+
+pub mod common;
+pub mod subcommands;
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+pub use common::block_iter::{BlockEntry, BlockIterator};
@@ -1,6 +1,13 @@
-use std::io::Write;
+use std::{
+    io::{self, Write},
+    str::FromStr,
+    sync::{Mutex, PoisonError},
+};
 
-use log::{LevelFilter, SetLoggerError};
+use casper_db_utils::subcommands::Error;
+use casper_types::Timestamp;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+use serde_json::{json, Value as JsonValue};
 use simplelog::{ColorChoice, ConfigBuilder, TermLogger, TerminalMode, WriteLogger};
 
 pub fn init_write_logger<W: Write + Send + 'static>(writer: W) -> Result<(), SetLoggerError> {
@@ -12,16 +19,139 @@ pub fn init_write_logger<W: Write + Send + 'static>(writer: W) -> Result<(), Set
     WriteLogger::init(LevelFilter::Info, config, writer)
 }
 
-pub fn init_term_logger() -> Result<(), SetLoggerError> {
+/// Initializes a logger writing to the terminal. Errors go to standard error
+/// and other messages to standard output, unless `quiet` is set, in which
+/// case all messages go to standard error, leaving standard output to the
+/// output of the subcommand.
+pub fn init_term_logger(quiet: bool) -> Result<(), SetLoggerError> {
     let config = ConfigBuilder::default()
         .set_max_level(LevelFilter::Info)
         .set_time_level(LevelFilter::Info)
         .set_time_format_rfc3339()
         .build();
-    TermLogger::init(
-        LevelFilter::Info,
-        config,
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )
+    let mode = if quiet {
+        TerminalMode::Stderr
+    } else {
+        TerminalMode::Mixed
+    };
+    TermLogger::init(LevelFilter::Info, config, mode, ColorChoice::Auto)
+}
+
+/// Format of the log messages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("unknown log format {}", value)),
+        }
+    }
+}
+
+type Writer = Mutex<Box<dyn Write + Send>>;
+
+/// Logger writing each message as a JSON object on its own line.
+pub struct JsonLogger {
+    writer: Writer,
+    /// Destination of the error messages, if not `writer`.
+    maybe_error_writer: Option<Writer>,
+}
+
+impl JsonLogger {
+    fn write_line(&self, level: Level, line: JsonValue) {
+        let writer = match self.maybe_error_writer.as_ref() {
+            Some(error_writer) if level == Level::Error => error_writer,
+            _ => &self.writer,
+        };
+        let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = writeln!(writer, "{}", line);
+        let _ = writer.flush();
+    }
+
+    /// Logs the failure of the command, with the machine readable
+    /// description of the error under the `error` key.
+    pub fn log_failure(&self, error: &Error) {
+        self.write_line(
+            Level::Error,
+            json!({
+                "timestamp": Timestamp::now().to_string(),
+                "level": Level::Error.as_str(),
+                "message": error.to_string(),
+                "error": error.to_json(),
+            }),
+        );
+    }
+}
+
+impl Log for JsonLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LevelFilter::Info
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        self.write_line(
+            record.level(),
+            json!({
+                "timestamp": Timestamp::now().to_string(),
+                "level": record.level().as_str(),
+                "target": record.target(),
+                "message": record.args().to_string(),
+            }),
+        );
+    }
+
+    fn flush(&self) {
+        let _ = self
+            .writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush();
+    }
+}
+
+fn init_json_logger(logger: JsonLogger) -> Result<&'static JsonLogger, SetLoggerError> {
+    let logger: &'static JsonLogger = Box::leak(Box::new(logger));
+    log::set_logger(logger)?;
+    log::set_max_level(LevelFilter::Info);
+    Ok(logger)
+}
+
+/// Initializes a logger writing JSON lines to `writer`.
+pub fn init_json_write_logger<W: Write + Send + 'static>(
+    writer: W,
+) -> Result<&'static JsonLogger, SetLoggerError> {
+    init_json_logger(JsonLogger {
+        writer: Mutex::new(Box::new(writer)),
+        maybe_error_writer: None,
+    })
+}
+
+/// Initializes a logger writing JSON lines to the terminal, splitting the
+/// messages between standard output and standard error like
+/// `init_term_logger`.
+pub fn init_json_term_logger(quiet: bool) -> Result<&'static JsonLogger, SetLoggerError> {
+    let (writer, maybe_error_writer): (Box<dyn Write + Send>, _) = if quiet {
+        (Box::new(io::stderr()), None)
+    } else {
+        (
+            Box::new(io::stdout()),
+            Some(Box::new(io::stderr()) as Box<_>),
+        )
+    };
+    init_json_logger(JsonLogger {
+        writer: Mutex::new(writer),
+        maybe_error_writer: maybe_error_writer.map(Mutex::new),
+    })
 }
@@ -1,31 +1,103 @@
-mod common;
 mod logging;
-mod subcommands;
-#[cfg(test)]
-pub(crate) mod test_utils;
 
-use std::{fs::OpenOptions, process};
+use std::{
+    env,
+    ffi::OsString,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    process,
+};
 
-use clap::{crate_description, crate_name, crate_version, Arg, Command};
-use log::error;
+use clap::{crate_description, crate_name, crate_version, Arg, ArgMatches, Command};
+use log::{error, warn};
 
-use subcommands::{
-    archive, check, execution_results_summary, extract_slice, latest_block_summary,
-    purge_signatures, remove_block, trie_compact, unsparse, Error,
+use casper_db_utils::{
+    common::{
+        audit::{Audit, Outcome},
+        config::{self, Config, CONFIG, NODE_CONFIG},
+        db_lock::{self, DbLock},
+        temp::{self, KEEP_TEMP, TMPDIR},
+    },
+    subcommands::{
+        self, archive, backfill_exec_results, backup, bench, bids, block_composition, body_info,
+        check, completions, contracts, convert_bodies, db_stat, decode, deploy_graph,
+        execution_results_summary, expiry_report, export_sqlite, extract_slice, failure_report,
+        fleet_report, follow, gas_report, gen_man, genesis_audit, latest_block_summary,
+        make_sync_seed, manifest, merge_signatures, networks, orphans, participation,
+        purge_signatures, remove_block, remove_era, replay, rollback, rpc_shim, salvage,
+        scan_pages, search_state, serve, set_state_store, stage, stats, supply, sync_rate,
+        sync_storage, timestamp_audit, trie_compact, trie_export, trie_import, unsparse,
+        verify_bodies, verify_chain, verify_deploys, verify_indexes, verify_state_roots,
+        wasm_stats, Error,
+    },
 };
 
+use logging::LogFormat;
+
+const DB_PATH: &str = "db-path";
 const LOGGING: &str = "logging";
+const LOG_FORMAT: &str = "log-format";
+const QUIET: &str = "quiet";
 
 enum DisplayOrder {
     Archive,
+    BackfillExecResults,
+    Backup,
+    Bench,
+    Bids,
+    BlockComposition,
+    BodyInfo,
     Check,
+    Completions,
+    Contracts,
+    ConvertBodies,
+    DbStat,
+    Decode,
+    DeployGraph,
     ExecutionResults,
+    ExpiryReport,
+    ExportSqlite,
     ExtractSlice,
+    FailureReport,
+    FleetReport,
+    Follow,
+    GasReport,
+    GenMan,
+    GenesisAudit,
     LatestBlock,
+    MakeSyncSeed,
+    Manifest,
+    MergeSignatures,
+    Networks,
+    Orphans,
+    Participation,
     PurgeSignatures,
     RemoveBlock,
+    RemoveEra,
+    Replay,
+    Rollback,
+    RpcShim,
+    Salvage,
+    ScanPages,
+    SearchState,
+    Serve,
+    SetStateStore,
+    Stage,
+    Stats,
+    Supply,
+    SyncRate,
+    SyncStorage,
+    TimestampAudit,
     TrieCompact,
+    TrieExport,
+    TrieImport,
     Unsparse,
+    VerifyBodies,
+    VerifyChain,
+    VerifyDeploys,
+    VerifyIndexes,
+    VerifyStateRoots,
+    WasmStats,
 }
 
 const VERSION_STRING: &str = concat!(
@@ -43,20 +115,89 @@ fn cli() -> Command<'static> {
         .about(crate_description!())
         .arg_required_else_help(true)
         .subcommand(archive::command(DisplayOrder::Archive as usize))
+        .subcommand(backfill_exec_results::command(
+            DisplayOrder::BackfillExecResults as usize,
+        ))
+        .subcommand(backup::command(DisplayOrder::Backup as usize))
+        .subcommand(bench::command(DisplayOrder::Bench as usize))
+        .subcommand(bids::command(DisplayOrder::Bids as usize))
+        .subcommand(block_composition::command(
+            DisplayOrder::BlockComposition as usize,
+        ))
+        .subcommand(body_info::command(DisplayOrder::BodyInfo as usize))
         .subcommand(check::command(DisplayOrder::Check as usize))
+        .subcommand(completions::command(DisplayOrder::Completions as usize))
+        .subcommand(contracts::command(DisplayOrder::Contracts as usize))
+        .subcommand(convert_bodies::command(
+            DisplayOrder::ConvertBodies as usize,
+        ))
+        .subcommand(db_stat::command(DisplayOrder::DbStat as usize))
+        .subcommand(decode::command(DisplayOrder::Decode as usize))
+        .subcommand(deploy_graph::command(DisplayOrder::DeployGraph as usize))
         .subcommand(execution_results_summary::command(
             DisplayOrder::ExecutionResults as usize,
         ))
+        .subcommand(expiry_report::command(DisplayOrder::ExpiryReport as usize))
+        .subcommand(export_sqlite::command(DisplayOrder::ExportSqlite as usize))
         .subcommand(extract_slice::command(DisplayOrder::ExtractSlice as usize))
+        .subcommand(failure_report::command(
+            DisplayOrder::FailureReport as usize,
+        ))
+        .subcommand(fleet_report::command(DisplayOrder::FleetReport as usize))
+        .subcommand(follow::command(DisplayOrder::Follow as usize))
+        .subcommand(gas_report::command(DisplayOrder::GasReport as usize))
+        .subcommand(gen_man::command(DisplayOrder::GenMan as usize))
+        .subcommand(genesis_audit::command(DisplayOrder::GenesisAudit as usize))
         .subcommand(latest_block_summary::command(
             DisplayOrder::LatestBlock as usize,
         ))
+        .subcommand(make_sync_seed::command(DisplayOrder::MakeSyncSeed as usize))
+        .subcommand(manifest::command(DisplayOrder::Manifest as usize))
+        .subcommand(merge_signatures::command(
+            DisplayOrder::MergeSignatures as usize,
+        ))
+        .subcommand(networks::command(DisplayOrder::Networks as usize))
+        .subcommand(orphans::command(DisplayOrder::Orphans as usize))
+        .subcommand(participation::command(DisplayOrder::Participation as usize))
         .subcommand(purge_signatures::command(
             DisplayOrder::PurgeSignatures as usize,
         ))
         .subcommand(remove_block::command(DisplayOrder::RemoveBlock as usize))
+        .subcommand(remove_era::command(DisplayOrder::RemoveEra as usize))
+        .subcommand(replay::command(DisplayOrder::Replay as usize))
+        .subcommand(rollback::command(DisplayOrder::Rollback as usize))
+        .subcommand(rpc_shim::command(DisplayOrder::RpcShim as usize))
+        .subcommand(salvage::command(DisplayOrder::Salvage as usize))
+        .subcommand(scan_pages::command(DisplayOrder::ScanPages as usize))
+        .subcommand(search_state::command(DisplayOrder::SearchState as usize))
+        .subcommand(serve::command(DisplayOrder::Serve as usize))
+        .subcommand(set_state_store::command(
+            DisplayOrder::SetStateStore as usize,
+        ))
+        .subcommand(stage::command(DisplayOrder::Stage as usize))
+        .subcommand(stats::command(DisplayOrder::Stats as usize))
+        .subcommand(supply::command(DisplayOrder::Supply as usize))
+        .subcommand(sync_rate::command(DisplayOrder::SyncRate as usize))
+        .subcommand(sync_storage::command(DisplayOrder::SyncStorage as usize))
+        .subcommand(timestamp_audit::command(
+            DisplayOrder::TimestampAudit as usize,
+        ))
         .subcommand(trie_compact::command(DisplayOrder::TrieCompact as usize))
+        .subcommand(trie_export::command(DisplayOrder::TrieExport as usize))
+        .subcommand(trie_import::command(DisplayOrder::TrieImport as usize))
         .subcommand(unsparse::command(DisplayOrder::Unsparse as usize))
+        .subcommand(verify_bodies::command(DisplayOrder::VerifyBodies as usize))
+        .subcommand(verify_chain::command(DisplayOrder::VerifyChain as usize))
+        .subcommand(verify_deploys::command(
+            DisplayOrder::VerifyDeploys as usize,
+        ))
+        .subcommand(verify_indexes::command(
+            DisplayOrder::VerifyIndexes as usize,
+        ))
+        .subcommand(verify_state_roots::command(
+            DisplayOrder::VerifyStateRoots as usize,
+        ))
+        .subcommand(wasm_stats::command(DisplayOrder::WasmStats as usize))
         .arg(
             Arg::new(LOGGING)
                 .short('l')
@@ -65,23 +206,185 @@ fn cli() -> Command<'static> {
                 .value_name("LOGFILE_PATH")
                 .help("Path to file where program will dump log messages."),
         )
+        .arg(
+            Arg::new(LOG_FORMAT)
+                .long(LOG_FORMAT)
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["text", "json"])
+                .default_value("text")
+                .help(
+                    "Format of the log messages. With `json`, each message is \
+                    a JSON object on its own line, and a failure is logged \
+                    with its error code and the chain of errors causing it.",
+                ),
+        )
+        .arg(
+            Arg::new(CONFIG)
+                .long(CONFIG)
+                .global(true)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path of a TOML file with defaults for the options of the \
+                    subcommands, keyed by their long names. Defaults to \
+                    `~/.config/casper-db-utils/config.toml` if it exists.",
+                ),
+        )
+        .arg(
+            Arg::new(networks::NETWORK)
+                .long(networks::NETWORK)
+                .global(true)
+                .takes_value(true)
+                .value_name("NAME")
+                .help(
+                    "Network whose databases to operate on, when a database \
+                    path points at a directory with a subdirectory per \
+                    network. Defaults to the only network found there.",
+                ),
+        )
+        .arg(
+            Arg::new(NODE_CONFIG)
+                .long(NODE_CONFIG)
+                .global(true)
+                .takes_value(true)
+                .value_name("PATH_TO_NODE_CONFIG_TOML")
+                .help(
+                    "Path of the config file of a node, with its chainspec in \
+                    the same directory. Options taking the path of a database \
+                    or the network name default to the ones of the node, \
+                    overriding the defaults of the config file.",
+                ),
+        )
+        .arg(
+            Arg::new(TMPDIR)
+                .long(TMPDIR)
+                .global(true)
+                .takes_value(true)
+                .value_name("DIR")
+                .help(
+                    "Directory in which to create temporary files, such as \
+                    unpacked archives and snapshots, which can be as large as \
+                    the databases. Defaults to the system's temporary \
+                    directory.",
+                ),
+        )
+        .arg(
+            Arg::new(KEEP_TEMP)
+                .long(KEEP_TEMP)
+                .global(true)
+                .takes_value(false)
+                .help(
+                    "Keep the temporary files instead of removing them when \
+                    done or interrupted, for debugging.",
+                ),
+        )
+        .arg(
+            Arg::new(QUIET)
+                .short('q')
+                .long(QUIET)
+                .global(true)
+                .takes_value(false)
+                .help(
+                    "Write all log messages to standard error, so that standard \
+                    output holds only the output of the subcommand, such as a \
+                    JSON document to pipe to another program.",
+                ),
+        )
+}
+
+/// Returns the CLI selecting the network given by `--network`, with the
+/// defaults of the config file, overridden by the ones derived from the node
+/// config file if given.
+fn configured_cli(args: &[OsString]) -> Result<Command<'static>, config::Error> {
+    let maybe_network = config::value_from_args(args, networks::NETWORK)
+        .map(|network| network.to_string_lossy().into_owned());
+    let cli = networks::select_network(cli(), maybe_network);
+    let config_path = config::value_from_args(args, CONFIG).map(PathBuf::from);
+    let cli = Config::load(config_path.as_deref())?.apply(cli)?;
+    match config::value_from_args(args, NODE_CONFIG) {
+        Some(node_config_path) => Config::from_node_config(node_config_path)?.apply(cli),
+        None => Ok(cli),
+    }
+}
+
+/// Warns if a run modifying the database given to a read-only subcommand by
+/// its `db-path` argument holds the lock of the database.
+fn warn_if_locked(matches: &ArgMatches) {
+    let maybe_db_path = matches
+        .try_get_raw(DB_PATH)
+        .ok()
+        .flatten()
+        .and_then(|mut values| values.next())
+        .map(Path::new);
+    let db_path = match maybe_db_path {
+        Some(db_path) if db_path.is_dir() => db_path,
+        _ => return,
+    };
+    match db_lock::is_locked(db_path) {
+        Ok(true) => warn!(
+            "Another run of casper-db-utils is modifying the database in {}, \
+            what this run reads may change under it.",
+            db_path.display()
+        ),
+        Ok(false) => (),
+        Err(lock_err) => warn!("{}", lock_err),
+    }
 }
 
 fn main() {
-    let arg_matches = cli().get_matches();
+    // The config is read before parsing the command line, as it provides the
+    // defaults of the options, so errors can't be logged yet.
+    let args: Vec<_> = env::args_os().collect();
+    let arguments: Vec<String> = args
+        .iter()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    let arg_matches = match configured_cli(&args) {
+        Ok(cli) => cli.get_matches_from(args),
+        Err(config_err) => {
+            eprintln!("{}", config_err);
+            process::exit(1);
+        }
+    };
 
     // Initialize logger.
-    arg_matches.value_of(LOGGING).map_or_else(
-        || logging::init_term_logger().expect("Couldn't initialize terminal logger"),
-        |path| {
-            let logfile = OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(path)
-                .expect("Couldn't open logfile");
-            let line_writer = std::io::LineWriter::new(logfile);
+    let log_format: LogFormat = arg_matches
+        .value_of(LOG_FORMAT)
+        .expect("should have a default")
+        .parse()
+        .expect("should be validated");
+    let quiet = arg_matches.is_present(QUIET);
+    let maybe_line_writer = arg_matches.value_of(LOGGING).map(|path| {
+        let logfile = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(path)
+            .expect("Couldn't open logfile");
+        std::io::LineWriter::new(logfile)
+    });
+    let maybe_json_logger = match (log_format, maybe_line_writer) {
+        (LogFormat::Text, None) => {
+            logging::init_term_logger(quiet).expect("Couldn't initialize terminal logger");
+            None
+        }
+        (LogFormat::Text, Some(line_writer)) => {
             logging::init_write_logger(line_writer).expect("Couldn't initialize logger to file");
-        },
+            None
+        }
+        (LogFormat::Json, None) => Some(
+            logging::init_json_term_logger(quiet).expect("Couldn't initialize terminal logger"),
+        ),
+        (LogFormat::Json, Some(line_writer)) => Some(
+            logging::init_json_write_logger(line_writer)
+                .expect("Couldn't initialize logger to file"),
+        ),
+    };
+
+    temp::init(
+        arg_matches.value_of(TMPDIR).map(PathBuf::from),
+        arg_matches.is_present(KEEP_TEMP),
     );
 
     let (subcommand_name, matches) = arg_matches.subcommand().unwrap_or_else(|| {
@@ -92,25 +395,112 @@ fn main() {
         process::exit(1);
     });
 
+    // Runs modifying a database hold its lock while they run, and are
+    // recorded in its audit log, whatever their outcome. Other runs only warn
+    // if the lock is held.
+    let maybe_modified_db_dir = subcommands::modified_db_dir(subcommand_name, matches);
+    let _maybe_lock = match maybe_modified_db_dir {
+        Some(db_dir) if db_dir.is_dir() => match DbLock::acquire(db_dir) {
+            Ok(lock) => Some(lock),
+            Err(lock_err) => {
+                error!("{}", lock_err);
+                process::exit(1);
+            }
+        },
+        Some(_) => None,
+        None => {
+            warn_if_locked(matches);
+            None
+        }
+    };
+    let maybe_audit =
+        maybe_modified_db_dir.map(|db_dir| Audit::start(db_dir, subcommand_name, arguments));
+
     let result: Result<(), Error> = match subcommand_name {
         archive::COMMAND_NAME => archive::run(matches).map_err(Error::from),
+        backfill_exec_results::COMMAND_NAME => {
+            backfill_exec_results::run(matches).map_err(Error::from)
+        }
+        backup::COMMAND_NAME => backup::run(matches).map_err(Error::from),
+        bench::COMMAND_NAME => bench::run(matches).map_err(Error::from),
+        bids::COMMAND_NAME => bids::run(matches).map_err(Error::from),
+        block_composition::COMMAND_NAME => block_composition::run(matches).map_err(Error::from),
+        body_info::COMMAND_NAME => body_info::run(matches).map_err(Error::from),
         check::COMMAND_NAME => check::run(matches).map_err(Error::from),
+        completions::COMMAND_NAME => completions::run(matches, cli()).map_err(Error::from),
+        contracts::COMMAND_NAME => contracts::run(matches).map_err(Error::from),
+        convert_bodies::COMMAND_NAME => convert_bodies::run(matches).map_err(Error::from),
+        db_stat::COMMAND_NAME => db_stat::run(matches).map_err(Error::from),
+        decode::COMMAND_NAME => decode::run(matches).map_err(Error::from),
+        deploy_graph::COMMAND_NAME => deploy_graph::run(matches).map_err(Error::from),
         execution_results_summary::COMMAND_NAME => {
             execution_results_summary::run(matches).map_err(Error::from)
         }
+        expiry_report::COMMAND_NAME => expiry_report::run(matches).map_err(Error::from),
+        export_sqlite::COMMAND_NAME => export_sqlite::run(matches).map_err(Error::from),
         extract_slice::COMMAND_NAME => extract_slice::run(matches).map_err(Error::from),
+        failure_report::COMMAND_NAME => failure_report::run(matches).map_err(Error::from),
+        fleet_report::COMMAND_NAME => fleet_report::run(matches).map_err(Error::from),
+        follow::COMMAND_NAME => follow::run(matches).map_err(Error::from),
+        gas_report::COMMAND_NAME => gas_report::run(matches).map_err(Error::from),
+        gen_man::COMMAND_NAME => gen_man::run(matches, cli()).map_err(Error::from),
+        genesis_audit::COMMAND_NAME => genesis_audit::run(matches).map_err(Error::from),
         latest_block_summary::COMMAND_NAME => {
             latest_block_summary::run(matches).map_err(Error::from)
         }
+        make_sync_seed::COMMAND_NAME => make_sync_seed::run(matches).map_err(Error::from),
+        manifest::COMMAND_NAME => manifest::run(matches).map_err(Error::from),
+        merge_signatures::COMMAND_NAME => merge_signatures::run(matches).map_err(Error::from),
+        networks::COMMAND_NAME => networks::run(matches).map_err(Error::from),
+        orphans::COMMAND_NAME => orphans::run(matches).map_err(Error::from),
+        participation::COMMAND_NAME => participation::run(matches).map_err(Error::from),
         purge_signatures::COMMAND_NAME => purge_signatures::run(matches).map_err(Error::from),
         remove_block::COMMAND_NAME => remove_block::run(matches).map_err(Error::from),
+        remove_era::COMMAND_NAME => remove_era::run(matches).map_err(Error::from),
+        replay::COMMAND_NAME => replay::run(matches).map_err(Error::from),
+        rollback::COMMAND_NAME => rollback::run(matches).map_err(Error::from),
+        rpc_shim::COMMAND_NAME => rpc_shim::run(matches).map_err(Error::from),
+        salvage::COMMAND_NAME => salvage::run(matches).map_err(Error::from),
+        scan_pages::COMMAND_NAME => scan_pages::run(matches).map_err(Error::from),
+        search_state::COMMAND_NAME => search_state::run(matches).map_err(Error::from),
+        serve::COMMAND_NAME => serve::run(matches).map_err(Error::from),
+        set_state_store::COMMAND_NAME => set_state_store::run(matches).map_err(Error::from),
+        stage::COMMAND_NAME => stage::run(matches).map_err(Error::from),
+        stats::COMMAND_NAME => stats::run(matches).map_err(Error::from),
+        supply::COMMAND_NAME => supply::run(matches).map_err(Error::from),
+        sync_rate::COMMAND_NAME => sync_rate::run(matches).map_err(Error::from),
+        sync_storage::COMMAND_NAME => sync_storage::run(matches).map_err(Error::from),
+        timestamp_audit::COMMAND_NAME => timestamp_audit::run(matches).map_err(Error::from),
         trie_compact::COMMAND_NAME => trie_compact::run(matches).map_err(Error::from),
+        trie_export::COMMAND_NAME => trie_export::run(matches).map_err(Error::from),
+        trie_import::COMMAND_NAME => trie_import::run(matches).map_err(Error::from),
         unsparse::COMMAND_NAME => unsparse::run(matches).map_err(Error::from),
+        verify_bodies::COMMAND_NAME => verify_bodies::run(matches).map_err(Error::from),
+        verify_chain::COMMAND_NAME => verify_chain::run(matches).map_err(Error::from),
+        verify_deploys::COMMAND_NAME => verify_deploys::run(matches).map_err(Error::from),
+        verify_indexes::COMMAND_NAME => verify_indexes::run(matches).map_err(Error::from),
+        verify_state_roots::COMMAND_NAME => verify_state_roots::run(matches).map_err(Error::from),
+        wasm_stats::COMMAND_NAME => wasm_stats::run(matches).map_err(Error::from),
         _ => unreachable!("{} should be handled above", subcommand_name),
     };
 
+    if let Some(audit) = maybe_audit {
+        let outcome = match &result {
+            Ok(()) => Outcome::Success,
+            Err(run_err) => Outcome::Failure {
+                error: run_err.to_json(),
+            },
+        };
+        if let Err(audit_err) = audit.finish(outcome) {
+            warn!("{}", audit_err);
+        }
+    }
+
     if let Err(run_err) = result {
-        error!("{}", run_err);
+        match maybe_json_logger {
+            Some(json_logger) => json_logger.log_failure(&run_err),
+            None => error!("{}", run_err),
+        }
         process::exit(1);
     }
 }
@@ -1,45 +1,551 @@
 pub mod archive;
+pub mod backfill_exec_results;
+pub mod backup;
+pub mod bench;
+pub mod bids;
+pub mod block_composition;
+pub mod body_info;
 pub mod check;
+pub mod completions;
+pub mod contracts;
+pub mod convert_bodies;
+pub mod db_stat;
+pub mod decode;
+pub mod deploy_graph;
 pub mod execution_results_summary;
+pub mod expiry_report;
+pub mod export_sqlite;
 pub mod extract_slice;
+pub mod failure_report;
+pub mod fleet_report;
+pub mod follow;
+pub mod gas_report;
+pub mod gen_man;
+pub mod genesis_audit;
 pub mod latest_block_summary;
+pub mod make_sync_seed;
+pub mod manifest;
+pub mod merge_signatures;
+pub mod networks;
+pub mod orphans;
+pub mod participation;
 pub mod purge_signatures;
 pub mod remove_block;
+pub mod remove_era;
+pub mod replay;
+pub mod rollback;
+pub mod rpc_shim;
+pub mod salvage;
+pub mod scan_pages;
+pub mod search_state;
+pub mod serve;
+pub mod set_state_store;
+pub mod stage;
+pub mod stats;
+pub mod supply;
+pub mod sync_rate;
+pub mod sync_storage;
+pub mod timestamp_audit;
 pub mod trie_compact;
+pub mod trie_export;
+pub mod trie_import;
 pub mod unsparse;
+pub mod verify_bodies;
+pub mod verify_chain;
+pub mod verify_deploys;
+pub mod verify_indexes;
+pub mod verify_state_roots;
+pub mod wasm_stats;
 
+use std::{error::Error as StdError, io::Error as IoError, path::Path};
+
+use bincode::{Error as BincodeError, ErrorKind as BincodeErrorKind};
+use clap::ArgMatches;
+use lmdb::Error as LmdbError;
+use reqwest::Error as ReqwestError;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
 use thiserror::Error as ThisError;
 
-use archive::{CreateError, UnpackError};
+use archive::{CreateError, InspectError, UnpackError};
+use backfill_exec_results::Error as BackfillExecResultsError;
+use backup::{CreateError as BackupCreateError, RestoreError as BackupRestoreError};
+use bench::Error as BenchError;
+use bids::Error as BidsError;
+use block_composition::Error as BlockCompositionError;
+use body_info::Error as BodyInfoError;
 use check::Error as CheckError;
+use completions::Error as CompletionsError;
+use contracts::Error as ContractsError;
+use convert_bodies::Error as ConvertBodiesError;
+use db_stat::Error as DbStatError;
+use decode::Error as DecodeError;
+use deploy_graph::Error as DeployGraphError;
 use execution_results_summary::Error as ExecutionResultsSummaryError;
+use expiry_report::Error as ExpiryReportError;
+use export_sqlite::Error as ExportSqliteError;
 use extract_slice::Error as ExtractSliceError;
+use failure_report::Error as FailureReportError;
+use fleet_report::Error as FleetReportError;
+use follow::Error as FollowError;
+use gas_report::Error as GasReportError;
+use gen_man::Error as GenManError;
+use genesis_audit::Error as GenesisAuditError;
 use latest_block_summary::Error as LatestBlockSummaryError;
+use make_sync_seed::Error as MakeSyncSeedError;
+use manifest::{CreateError as ManifestCreateError, VerifyError as ManifestVerifyError};
+use merge_signatures::Error as MergeSignaturesError;
+use networks::Error as NetworksError;
+use orphans::Error as OrphansError;
+use participation::Error as ParticipationError;
 use purge_signatures::Error as PurgeSignaturesError;
 use remove_block::Error as RemoveBlockError;
+use remove_era::Error as RemoveEraError;
+use replay::Error as ReplayError;
+use rollback::Error as RollbackError;
+use rpc_shim::Error as RpcShimError;
+use salvage::Error as SalvageError;
+use scan_pages::Error as ScanPagesError;
+use search_state::Error as SearchStateError;
+use serve::Error as ServeError;
+use set_state_store::Error as SetStateStoreError;
+use stage::Error as StageError;
+use stats::Error as StatsError;
+use supply::Error as SupplyError;
+use sync_rate::Error as SyncRateError;
+use sync_storage::Error as SyncStorageError;
+use timestamp_audit::Error as TimestampAuditError;
 use trie_compact::Error as TrieCompactError;
+use trie_export::Error as TrieExportError;
+use trie_import::Error as TrieImportError;
 use unsparse::Error as UnsparseError;
+use verify_bodies::Error as VerifyBodiesError;
+use verify_chain::Error as VerifyChainError;
+use verify_deploys::Error as VerifyDeploysError;
+use verify_indexes::Error as VerifyIndexesError;
+use verify_state_roots::Error as VerifyStateRootsError;
+use wasm_stats::Error as WasmStatsError;
 
 #[derive(ThisError, Debug)]
 pub enum Error {
     #[error("Archive create failed: {0}")]
     ArchiveCreate(#[from] CreateError),
+    #[error("Archive inspect failed: {0}")]
+    ArchiveInspect(#[from] InspectError),
     #[error("Archive unpack failed: {0}")]
     ArchiveUnpack(#[from] UnpackError),
+    #[error("Backfill execution results failed: {0}")]
+    BackfillExecResults(#[from] BackfillExecResultsError),
+    #[error("Backup create failed: {0}")]
+    BackupCreate(#[from] BackupCreateError),
+    #[error("Backup restore failed: {0}")]
+    BackupRestore(#[from] BackupRestoreError),
+    #[error("Bench command failed: {0}")]
+    Bench(#[from] BenchError),
+    #[error("Bids command failed: {0}")]
+    Bids(#[from] BidsError),
+    #[error("Block composition failed: {0}")]
+    BlockComposition(#[from] BlockCompositionError),
+    #[error("Body info command failed: {0}")]
+    BodyInfo(#[from] BodyInfoError),
     #[error("Check command failed: {0}")]
     Check(#[from] CheckError),
+    #[error("Completions command failed: {0}")]
+    Completions(#[from] CompletionsError),
+    #[error("Contracts command failed: {0}")]
+    Contracts(#[from] ContractsError),
+    #[error("Convert bodies command failed: {0}")]
+    ConvertBodies(#[from] ConvertBodiesError),
+    #[error("DB stat command failed: {0}")]
+    DbStat(#[from] DbStatError),
+    #[error("Decode command failed: {0}")]
+    Decode(#[from] DecodeError),
+    #[error("Deploy graph command failed: {0}")]
+    DeployGraph(#[from] DeployGraphError),
     #[error("Execution results summary command failed: {0}")]
     ExecutionResultsSummary(#[from] ExecutionResultsSummaryError),
+    #[error("Expiry report failed: {0}")]
+    ExpiryReport(#[from] ExpiryReportError),
+    #[error("Export to SQLite failed: {0}")]
+    ExportSqlite(#[from] ExportSqliteError),
     #[error("Extract slice command failed: {0}")]
     ExtractSlice(#[from] ExtractSliceError),
+    #[error("Failure report failed: {0}")]
+    FailureReport(#[from] FailureReportError),
+    #[error("Fleet report failed: {0}")]
+    FleetReport(#[from] FleetReportError),
+    #[error("Follow command failed: {0}")]
+    Follow(#[from] FollowError),
+    #[error("Gas report failed: {0}")]
+    GasReport(#[from] GasReportError),
+    #[error("Man page generation failed: {0}")]
+    GenMan(#[from] GenManError),
+    #[error("Genesis audit failed: {0}")]
+    GenesisAudit(#[from] GenesisAuditError),
     #[error("Latest block summary command failed: {0}")]
     LatestBlockSummary(#[from] LatestBlockSummaryError),
+    #[error("Make sync seed failed: {0}")]
+    MakeSyncSeed(#[from] MakeSyncSeedError),
+    #[error("Manifest create failed: {0}")]
+    ManifestCreate(#[from] ManifestCreateError),
+    #[error("Manifest verify failed: {0}")]
+    ManifestVerify(#[from] ManifestVerifyError),
+    #[error("Merge signatures failed: {0}")]
+    MergeSignatures(#[from] MergeSignaturesError),
+    #[error("Networks command failed: {0}")]
+    Networks(#[from] NetworksError),
+    #[error("Orphans command failed: {0}")]
+    Orphans(#[from] OrphansError),
+    #[error("Participation report failed: {0}")]
+    Participation(#[from] ParticipationError),
     #[error("Purge signatures failed: {0}")]
     PurgeSignatures(#[from] PurgeSignaturesError),
     #[error("Remove block failed: {0}")]
     RemoveBlock(#[from] RemoveBlockError),
+    #[error("Remove era failed: {0}")]
+    RemoveEra(#[from] RemoveEraError),
+    #[error("Replay failed: {0}")]
+    Replay(#[from] ReplayError),
+    #[error("Rollback failed: {0}")]
+    Rollback(#[from] RollbackError),
+    #[error("RPC shim command failed: {0}")]
+    RpcShim(#[from] RpcShimError),
+    #[error("Salvage failed: {0}")]
+    Salvage(#[from] SalvageError),
+    #[error("Scan pages failed: {0}")]
+    ScanPages(#[from] ScanPagesError),
+    #[error("Search state failed: {0}")]
+    SearchState(#[from] SearchStateError),
+    #[error("Serve command failed: {0}")]
+    Serve(#[from] ServeError),
+    #[error("Set state store failed: {0}")]
+    SetStateStore(#[from] SetStateStoreError),
+    #[error("Stage command failed: {0}")]
+    Stage(#[from] StageError),
+    #[error("Stats command failed: {0}")]
+    Stats(#[from] StatsError),
+    #[error("Supply command failed: {0}")]
+    Supply(#[from] SupplyError),
+    #[error("Sync rate command failed: {0}")]
+    SyncRate(#[from] SyncRateError),
+    #[error("Sync storage failed: {0}")]
+    SyncStorage(#[from] SyncStorageError),
+    #[error("Timestamp audit failed: {0}")]
+    TimestampAudit(#[from] TimestampAuditError),
     #[error("Trie compact failed: {0}")]
     TrieCompact(#[from] TrieCompactError),
+    #[error("Trie export failed: {0}")]
+    TrieExport(#[from] TrieExportError),
+    #[error("Trie import failed: {0}")]
+    TrieImport(#[from] TrieImportError),
     #[error("Unsparse failed: {0}")]
     Unsparse(#[from] UnsparseError),
+    #[error("Verify bodies failed: {0}")]
+    VerifyBodies(#[from] VerifyBodiesError),
+    #[error("Verify chain failed: {0}")]
+    VerifyChain(#[from] VerifyChainError),
+    #[error("Verify deploys failed: {0}")]
+    VerifyDeploys(#[from] VerifyDeploysError),
+    #[error("Verify indexes failed: {0}")]
+    VerifyIndexes(#[from] VerifyIndexesError),
+    #[error("Verify state roots failed: {0}")]
+    VerifyStateRoots(#[from] VerifyStateRootsError),
+    #[error("Wasm stats failed: {0}")]
+    WasmStats(#[from] WasmStatsError),
+}
+
+/// Category of a failure, for tooling to react to without parsing the
+/// messages. Derived from the lowest-level error found in the source chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// LMDB operation which may succeed if retried, like when the reader
+    /// table is full.
+    TransientDatabase,
+    /// The database file is damaged.
+    CorruptDatabase,
+    /// Any other database operation error.
+    Database,
+    /// An entry of a database couldn't be decoded.
+    CorruptData,
+    /// A network request failed.
+    Network,
+    /// A file operation failed.
+    Io,
+    /// The error wasn't recognized.
+    Other,
+}
+
+impl ErrorCode {
+    /// Returns whether running the command again may succeed.
+    pub fn is_transient(&self) -> bool {
+        matches!(self, Self::TransientDatabase | Self::Network)
+    }
+
+    fn from_lmdb_error(lmdb_err: &LmdbError) -> Self {
+        match lmdb_err {
+            LmdbError::ReadersFull
+            | LmdbError::MapResized
+            | LmdbError::BadRslot
+            | LmdbError::TlsFull => Self::TransientDatabase,
+            LmdbError::Corrupted
+            | LmdbError::PageNotFound
+            | LmdbError::Invalid
+            | LmdbError::VersionMismatch => Self::CorruptDatabase,
+            _ => Self::Database,
+        }
+    }
+
+    /// Returns the code of the first known error in the chain starting at
+    /// `error`.
+    fn from_chain(error: &(dyn StdError + 'static)) -> Self {
+        let mut maybe_error = Some(error);
+        while let Some(error) = maybe_error {
+            if let Some(lmdb_err) = error.downcast_ref::<LmdbError>() {
+                return Self::from_lmdb_error(lmdb_err);
+            }
+            if error.is::<BincodeError>() || error.is::<BincodeErrorKind>() {
+                return Self::CorruptData;
+            }
+            if error.is::<ReqwestError>() {
+                return Self::Network;
+            }
+            if error.is::<IoError>() {
+                return Self::Io;
+            }
+            maybe_error = error.source();
+        }
+        Self::Other
+    }
+}
+
+/// Machine readable description of a failed command.
+#[derive(Debug, Serialize)]
+struct ErrorReport {
+    command: &'static str,
+    code: ErrorCode,
+    transient: bool,
+    message: String,
+    /// Messages of the errors which caused the failure, outermost first.
+    sources: Vec<String>,
+}
+
+impl Error {
+    /// Returns the name of the subcommand which failed.
+    pub fn command(&self) -> &'static str {
+        match self {
+            Self::ArchiveCreate(_) => "archive create",
+            Self::ArchiveInspect(_) => "archive inspect",
+            Self::ArchiveUnpack(_) => "archive unpack",
+            Self::BackfillExecResults(_) => backfill_exec_results::COMMAND_NAME,
+            Self::BackupCreate(_) => "backup create",
+            Self::BackupRestore(_) => "backup restore",
+            Self::Bench(_) => bench::COMMAND_NAME,
+            Self::Bids(_) => bids::COMMAND_NAME,
+            Self::BlockComposition(_) => block_composition::COMMAND_NAME,
+            Self::BodyInfo(_) => body_info::COMMAND_NAME,
+            Self::Check(_) => check::COMMAND_NAME,
+            Self::Completions(_) => completions::COMMAND_NAME,
+            Self::Contracts(_) => contracts::COMMAND_NAME,
+            Self::ConvertBodies(_) => convert_bodies::COMMAND_NAME,
+            Self::DbStat(_) => db_stat::COMMAND_NAME,
+            Self::Decode(_) => decode::COMMAND_NAME,
+            Self::DeployGraph(_) => deploy_graph::COMMAND_NAME,
+            Self::ExecutionResultsSummary(_) => execution_results_summary::COMMAND_NAME,
+            Self::ExpiryReport(_) => expiry_report::COMMAND_NAME,
+            Self::ExportSqlite(_) => export_sqlite::COMMAND_NAME,
+            Self::ExtractSlice(_) => extract_slice::COMMAND_NAME,
+            Self::FailureReport(_) => failure_report::COMMAND_NAME,
+            Self::FleetReport(_) => fleet_report::COMMAND_NAME,
+            Self::Follow(_) => follow::COMMAND_NAME,
+            Self::GasReport(_) => gas_report::COMMAND_NAME,
+            Self::GenMan(_) => gen_man::COMMAND_NAME,
+            Self::GenesisAudit(_) => genesis_audit::COMMAND_NAME,
+            Self::LatestBlockSummary(_) => latest_block_summary::COMMAND_NAME,
+            Self::MakeSyncSeed(_) => make_sync_seed::COMMAND_NAME,
+            Self::ManifestCreate(_) => "manifest create",
+            Self::ManifestVerify(_) => "manifest verify",
+            Self::MergeSignatures(_) => merge_signatures::COMMAND_NAME,
+            Self::Networks(_) => networks::COMMAND_NAME,
+            Self::Orphans(_) => orphans::COMMAND_NAME,
+            Self::Participation(_) => participation::COMMAND_NAME,
+            Self::PurgeSignatures(_) => purge_signatures::COMMAND_NAME,
+            Self::RemoveBlock(_) => remove_block::COMMAND_NAME,
+            Self::RemoveEra(_) => remove_era::COMMAND_NAME,
+            Self::Replay(_) => replay::COMMAND_NAME,
+            Self::Rollback(_) => rollback::COMMAND_NAME,
+            Self::RpcShim(_) => rpc_shim::COMMAND_NAME,
+            Self::Salvage(_) => salvage::COMMAND_NAME,
+            Self::ScanPages(_) => scan_pages::COMMAND_NAME,
+            Self::SearchState(_) => search_state::COMMAND_NAME,
+            Self::Serve(_) => serve::COMMAND_NAME,
+            Self::SetStateStore(_) => set_state_store::COMMAND_NAME,
+            Self::Stage(_) => stage::COMMAND_NAME,
+            Self::Stats(_) => stats::COMMAND_NAME,
+            Self::Supply(_) => supply::COMMAND_NAME,
+            Self::SyncRate(_) => sync_rate::COMMAND_NAME,
+            Self::SyncStorage(_) => sync_storage::COMMAND_NAME,
+            Self::TimestampAudit(_) => timestamp_audit::COMMAND_NAME,
+            Self::TrieCompact(_) => trie_compact::COMMAND_NAME,
+            Self::TrieExport(_) => trie_export::COMMAND_NAME,
+            Self::TrieImport(_) => trie_import::COMMAND_NAME,
+            Self::Unsparse(_) => unsparse::COMMAND_NAME,
+            Self::VerifyBodies(_) => verify_bodies::COMMAND_NAME,
+            Self::VerifyChain(_) => verify_chain::COMMAND_NAME,
+            Self::VerifyDeploys(_) => verify_deploys::COMMAND_NAME,
+            Self::VerifyIndexes(_) => verify_indexes::COMMAND_NAME,
+            Self::VerifyStateRoots(_) => verify_state_roots::COMMAND_NAME,
+            Self::WasmStats(_) => wasm_stats::COMMAND_NAME,
+        }
+    }
+
+    /// Returns the category of the failure.
+    pub fn code(&self) -> ErrorCode {
+        ErrorCode::from_chain(self)
+    }
+
+    /// Returns the messages of the errors which caused this one, outermost
+    /// first.
+    pub fn sources(&self) -> Vec<String> {
+        let mut sources = vec![];
+        let mut maybe_source = self.source();
+        while let Some(source) = maybe_source {
+            sources.push(source.to_string());
+            maybe_source = source.source();
+        }
+        sources
+    }
+
+    /// Returns the JSON object describing this error, output on failure
+    /// with `--log-format json`.
+    pub fn to_json(&self) -> JsonValue {
+        let code = self.code();
+        let report = ErrorReport {
+            command: self.command(),
+            code,
+            transient: code.is_transient(),
+            message: self.to_string(),
+            sources: self.sources(),
+        };
+        serde_json::to_value(report).expect("should serialize error report")
+    }
+}
+
+/// Returns the directory of the databases the subcommand `subcommand_name`
+/// modifies in place, whose audit log records the run, or `None` if it
+/// doesn't modify any. Arguments are looked up by their long names, and the
+/// commands grouping others are looked up through their subcommand.
+pub fn modified_db_dir<'a>(subcommand_name: &str, matches: &'a ArgMatches) -> Option<&'a Path> {
+    let arg_name = match subcommand_name {
+        archive::COMMAND_NAME => match matches.subcommand()? {
+            (archive::unpack::COMMAND_NAME, unpack_matches) => {
+                return unpack_matches.value_of("output").map(Path::new)
+            }
+            _ => return None,
+        },
+        backup::COMMAND_NAME => match matches.subcommand()? {
+            (backup::restore::COMMAND_NAME, restore_matches) => {
+                return restore_matches.value_of("db-path").map(Path::new)
+            }
+            _ => return None,
+        },
+        remove_block::COMMAND_NAME if matches.is_present(remove_block::DRY_RUN) => return None,
+        unsparse::COMMAND_NAME if matches.is_present(unsparse::OUTPUT) => return None,
+        // The database file is reduced in place, in the directory holding it.
+        unsparse::COMMAND_NAME => {
+            return matches.value_of(unsparse::DB_PATH).map(|file_path| {
+                match Path::new(file_path).parent() {
+                    Some(db_dir) if !db_dir.as_os_str().is_empty() => db_dir,
+                    _ => Path::new("."),
+                }
+            })
+        }
+        backfill_exec_results::COMMAND_NAME
+        | convert_bodies::COMMAND_NAME
+        | merge_signatures::COMMAND_NAME
+        | purge_signatures::COMMAND_NAME
+        | remove_block::COMMAND_NAME
+        | remove_era::COMMAND_NAME
+        | rollback::COMMAND_NAME
+        | set_state_store::COMMAND_NAME
+        | trie_import::COMMAND_NAME => "db-path",
+        orphans::COMMAND_NAME if matches.is_present("purge") => "db-path",
+        sync_storage::COMMAND_NAME => "dest-storage",
+        trie_compact::COMMAND_NAME => "dest-trie",
+        _ => return None,
+    };
+    matches.value_of(arg_name).map(Path::new)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use clap::Command;
+    use lmdb::Error as LmdbError;
+
+    use super::{
+        archive, backup, modified_db_dir, stats::Error as StatsError, unsparse, Error, ErrorCode,
+    };
+    use crate::common::storage::Error as StorageError;
+
+    #[test]
+    fn error_code_should_come_from_source_chain() {
+        let error = Error::from(StatsError::Database(StorageError::Lmdb(
+            LmdbError::ReadersFull,
+        )));
+        assert_eq!(error.command(), "stats");
+        assert_eq!(error.code(), ErrorCode::TransientDatabase);
+        assert_eq!(error.sources().len(), 3);
+
+        let json = error.to_json();
+        assert_eq!(json["command"], "stats");
+        assert_eq!(json["code"], "transient_database");
+        assert_eq!(json["transient"], true);
+        assert_eq!(json["message"], error.to_string());
+
+        let error = Error::from(StatsError::Database(StorageError::Lmdb(
+            LmdbError::Corrupted,
+        )));
+        assert_eq!(error.code(), ErrorCode::CorruptDatabase);
+        assert!(!error.code().is_transient());
+
+        let error = Error::from(StatsError::InvalidKey(0));
+        assert_eq!(error.code(), ErrorCode::Other);
+        assert_eq!(error.sources().len(), 1);
+    }
+
+    #[test]
+    fn modified_db_dir_should_descend_into_groups() {
+        let cli = Command::new("test")
+            .subcommand(archive::command(0))
+            .subcommand(backup::command(1))
+            .subcommand(unsparse::command(2));
+        let modified = |args: &[&str]| {
+            let matches = cli.clone().get_matches_from(args);
+            let (subcommand_name, matches) = matches.subcommand().unwrap();
+            modified_db_dir(subcommand_name, matches).map(Path::to_path_buf)
+        };
+
+        assert_eq!(
+            modified(&["test", "backup", "restore", "-d", "db", "backup-1"]),
+            Some("db".into())
+        );
+        assert_eq!(
+            modified(&["test", "backup", "create", "-d", "db", "-o", "backup-1"]),
+            None
+        );
+        assert_eq!(
+            modified(&["test", "unsparse", "db/storage.lmdb"]),
+            Some("db".into())
+        );
+        assert_eq!(
+            modified(&["test", "unsparse", "storage.lmdb"]),
+            Some(".".into())
+        );
+        assert_eq!(
+            modified(&["test", "unsparse", "db/storage.lmdb", "-o", "out.lmdb"]),
+            None
+        );
+    }
 }
@@ -4,21 +4,26 @@ use clap::{ArgMatches, Command};
 use thiserror::Error as ThisError;
 
 pub use create::Error as CreateError;
+pub use inspect::Error as InspectError;
 pub use unpack::Error as UnpackError;
 
 use super::Error as SubcommandError;
 
 mod create;
+mod inspect;
+mod manifest;
 mod ring_buffer;
+mod signature;
+pub(crate) mod snapshot;
 mod tar_utils;
-mod unpack;
-mod zstd_utils;
+pub(crate) mod unpack;
 
 pub const COMMAND_NAME: &str = "archive";
 
 enum DisplayOrder {
     Create,
     Unpack,
+    Inspect,
 }
 
 #[derive(ThisError, Debug)]
@@ -27,6 +32,8 @@ pub enum Error {
     Create(#[from] CreateError),
     #[error("unpack: {0}")]
     Unpack(#[from] UnpackError),
+    #[error("inspect: {0}")]
+    Inspect(#[from] InspectError),
 }
 
 impl From<Error> for SubcommandError {
@@ -34,6 +41,7 @@ impl From<Error> for SubcommandError {
         match err {
             Error::Create(create_err) => SubcommandError::ArchiveCreate(create_err),
             Error::Unpack(unpack_err) => SubcommandError::ArchiveUnpack(unpack_err),
+            Error::Inspect(inspect_err) => SubcommandError::ArchiveInspect(inspect_err),
         }
     }
 }
@@ -44,6 +52,7 @@ pub fn command(display_order: usize) -> Command<'static> {
         .about("Utilities for working with a compressed archive of a casper-node storage instance.")
         .subcommand(create::command(DisplayOrder::Create as usize))
         .subcommand(unpack::command(DisplayOrder::Unpack as usize))
+        .subcommand(inspect::command(DisplayOrder::Inspect as usize))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
@@ -54,6 +63,7 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
     match subcommand_name {
         create::COMMAND_NAME => create::run(matches).map_err(Error::Create),
         unpack::COMMAND_NAME => unpack::run(matches).map_err(Error::Unpack),
+        inspect::COMMAND_NAME => inspect::run(matches).map_err(Error::Inspect),
         _ => unreachable!("{} should be handled above", subcommand_name),
     }
 }
@@ -4,16 +4,19 @@ mod tests;
 
 use std::io::Error as IoError;
 
+use casper_types::{crypto::ErrorExt as CryptoError, SecretKey};
 use clap::{Arg, ArgMatches, Command};
 use log::error;
 use thiserror::Error as ThisError;
 
-use super::zstd_utils::Error as ZstdError;
+use super::manifest::Error as ManifestError;
+use crate::common::zstd_utils::Error as ZstdError;
 
 pub const COMMAND_NAME: &str = "create";
 const OVERWRITE: &str = "overwrite";
 const OUTPUT: &str = "output";
 const DB: &str = "db-dir";
+const SECRET_KEY: &str = "secret-key";
 
 #[derive(Debug, ThisError)]
 pub enum Error {
@@ -21,6 +24,12 @@ pub enum Error {
     ArchiveStream,
     #[error("Error creating destination archive file: {0}")]
     Destination(IoError),
+    #[error("Error building archive manifest: {0}")]
+    Manifest(#[from] ManifestError),
+    #[error("Error reading the secret key: {0}")]
+    SecretKey(CryptoError),
+    #[error("Error writing the archive signature file: {0}")]
+    Signature(IoError),
     #[error("Error streaming from tarball to zstd encoder: {0}")]
     Streaming(IoError),
     #[error("Zstd error: {0}")]
@@ -31,13 +40,16 @@ enum DisplayOrder {
     Db,
     Output,
     Overwrite,
+    SecretKey,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
     Command::new(COMMAND_NAME)
         .display_order(display_order)
         .about(
-            "Packs a casper-node storage instance to a tarball and then compresses it with zstd.",
+            "Packs a casper-node storage instance to a tarball and then compresses it with zstd. \
+            The archive starts with a manifest describing the chain and the checksums of \
+            the archived files.",
         )
         .arg(
             Arg::new(DB)
@@ -71,11 +83,29 @@ pub fn command(display_order: usize) -> Command<'static> {
                     directory.",
                 ),
         )
+        .arg(
+            Arg::new(SECRET_KEY)
+                .display_order(DisplayOrder::SecretKey as usize)
+                .short('k')
+                .long(SECRET_KEY)
+                .takes_value(true)
+                .value_name("SECRET_KEY_PATH")
+                .help(
+                    "Path of the PEM file of a secret key to sign the archive \
+                    with. The hex encoded detached signature is written next \
+                    to the archive, with the `.sig` suffix.",
+                ),
+        )
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
     let db_path = matches.value_of(DB).unwrap();
     let dest = matches.value_of(OUTPUT).unwrap();
     let overwrite = matches.is_present(OVERWRITE);
-    pack::create_archive(db_path, dest, overwrite)
+    let maybe_secret_key = matches
+        .value_of(SECRET_KEY)
+        .map(SecretKey::from_file)
+        .transpose()
+        .map_err(Error::SecretKey)?;
+    pack::create_archive(db_path, dest, overwrite, maybe_secret_key.as_ref())
 }
@@ -1,10 +1,24 @@
-use std::{fs::OpenOptions, io as std_io, path::Path, result::Result, thread};
+use std::{
+    ffi::OsString,
+    fs::OpenOptions,
+    io::{self as std_io, Write},
+    path::Path,
+    result::Result,
+    thread,
+};
 
+use casper_types::SecretKey;
 use log::info;
 
 use super::Error;
-use crate::subcommands::archive::{
-    ring_buffer::BlockingRingBuffer, tar_utils::ArchiveStream, zstd_utils,
+use crate::{
+    common::zstd_utils,
+    subcommands::archive::{
+        manifest::{Manifest, MANIFEST_FILE_NAME},
+        ring_buffer::BlockingRingBuffer,
+        signature::{self, HashingStream, SIGNATURE_SUFFIX},
+        tar_utils::ArchiveStream,
+    },
 };
 
 #[cfg(not(test))]
@@ -17,7 +31,17 @@ pub fn create_archive<P1: AsRef<Path>, P2: AsRef<Path>>(
     db_dir_path: P1,
     dest: P2,
     overwrite: bool,
+    maybe_secret_key: Option<&SecretKey>,
 ) -> Result<(), Error> {
+    let output_file = OpenOptions::new()
+        .create_new(!overwrite)
+        .write(true)
+        .open(&dest)
+        .map_err(Error::Destination)?;
+
+    info!("Building the archive manifest.");
+    let manifest_bytes = Manifest::new(&db_dir_path)?.to_bytes()?;
+
     let ring_buffer = BlockingRingBuffer::new(BUFFER_CAPACITY);
     let (producer, mut consumer) = ring_buffer.split();
 
@@ -31,26 +55,49 @@ pub fn create_archive<P1: AsRef<Path>, P2: AsRef<Path>>(
                     io_err
                 )
             });
+        archive_stream
+            .append_bytes(MANIFEST_FILE_NAME, &manifest_bytes)
+            .expect("Couldn't add manifest to the archive");
         archive_stream.pack().expect("Couldn't archive files");
     });
 
-    let output_file = OpenOptions::new()
-        .create_new(!overwrite)
-        .write(true)
-        .open(&dest)
-        .map_err(Error::Destination)?;
-
-    let mut encoder = zstd_utils::zstd_encode_stream(output_file)?;
+    let (output_stream, digest) = HashingStream::new(output_file);
+    let mut encoder = zstd_utils::zstd_encode_frames(output_stream)?;
     let _ = std_io::copy(&mut consumer, &mut encoder).map_err(Error::Streaming)?;
-    encoder.finish().map_err(Error::Streaming)?;
-
-    handle
-        .join()
-        .map(|_| {
-            info!(
-                "Finished encoding tarball with zstd, compressed archive at {}",
-                dest.as_ref().display()
-            )
-        })
-        .map_err(|_| Error::ArchiveStream)
+    encoder
+        .finish()
+        .map_err(Error::Streaming)?
+        .flush()
+        .map_err(Error::Streaming)?;
+    // The stream also ends if packing failed half way, so only sign the
+    // archive once the packing thread is known to have completed.
+    handle.join().map_err(|_| Error::ArchiveStream)?;
+    info!(
+        "Finished encoding tarball with zstd, compressed archive at {}",
+        dest.as_ref().display()
+    );
+
+    if let Some(secret_key) = maybe_secret_key {
+        let mut signature_path = OsString::from(dest.as_ref());
+        signature_path.push(SIGNATURE_SUFFIX);
+        let mut signature_file = OpenOptions::new()
+            .create_new(!overwrite)
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .open(&signature_path)
+            .map_err(Error::Signature)?;
+        writeln!(
+            signature_file,
+            "{}",
+            signature::sign(&digest.digest(), secret_key)
+        )
+        .map_err(Error::Signature)?;
+        info!(
+            "Signed the archive, signature at {}",
+            signature_path.to_string_lossy()
+        );
+    }
+
+    Ok(())
 }
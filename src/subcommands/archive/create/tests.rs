@@ -3,13 +3,27 @@ use std::{
     path::Path,
 };
 
+use casper_types::{AsymmetricType, PublicKey, SecretKey};
 use once_cell::sync::Lazy;
 use rand::{self, RngCore};
 use tar::Archive;
 use tempfile::{NamedTempFile, TempDir};
 use zstd::Decoder;
 
-use crate::subcommands::archive::{create::pack, zstd_utils::WINDOW_LOG_MAX_SIZE};
+use crate::{
+    common::{
+        stamp::Stamp,
+        zstd_utils::{self, WINDOW_LOG_MAX_SIZE},
+    },
+    subcommands::archive::{
+        create::pack,
+        inspect,
+        manifest::{self, Manifest, MANIFEST_FILE_NAME},
+        signature::{ArchiveSignature, Error as SignatureError, SIGNATURE_SUFFIX},
+        tar_utils::ArchiveStream,
+        unpack::{file_stream, Error as UnpackError},
+    },
+};
 
 const NUM_TEST_FILES: usize = 10usize;
 const TEST_FILE_SIZE: usize = 10000usize;
@@ -50,7 +64,7 @@ fn archive_create_roundtrip() {
     let out_dir = tempfile::tempdir().unwrap();
     let archive_path = dst_dir.path().join("test_archive.tar.zst");
     // Create the compressed archive.
-    assert!(pack::create_archive(src_dir, &archive_path, false).is_ok());
+    assert!(pack::create_archive(src_dir, &archive_path, false, None).is_ok());
     // Unpack and then delete the archive.
     unpack_mock_archive(&archive_path, &out_dir);
     for idx in 0..NUM_TEST_FILES {
@@ -73,9 +87,9 @@ fn archive_create_overwrite() {
     fs::write(&archive_path, "dummy input").unwrap();
     // File already exists, so creating the archive without the overwrite flag
     // should fail.
-    assert!(pack::create_archive(src_dir, &archive_path, false).is_err());
+    assert!(pack::create_archive(src_dir, &archive_path, false, None).is_err());
     // Create the compressed archive with the overwrite set.
-    assert!(pack::create_archive(src_dir, &archive_path, true).is_ok());
+    assert!(pack::create_archive(src_dir, &archive_path, true, None).is_ok());
     // Unpack and then delete the archive.
     unpack_mock_archive(&archive_path, &out_dir);
     for idx in 0..NUM_TEST_FILES {
@@ -93,11 +107,13 @@ fn archive_create_bad_input() {
     let inexistent_file_path = root_dst.path().join("bogus_path");
 
     // Source doesn't exist.
-    assert!(pack::create_archive(&inexistent_file_path, &inexistent_file_path, false).is_err());
+    assert!(
+        pack::create_archive(&inexistent_file_path, &inexistent_file_path, false, None).is_err()
+    );
 
     // Source is not a directory.
     let file = NamedTempFile::new().unwrap();
-    assert!(pack::create_archive(file.path(), &inexistent_file_path, false).is_err());
+    assert!(pack::create_archive(file.path(), &inexistent_file_path, false, None).is_err());
 
     // Destination directory doesn't exist.
     let root_dst = tempfile::tempdir().unwrap();
@@ -105,11 +121,133 @@ fn archive_create_bad_input() {
         src_dir,
         root_dst.path().join("bogus_dest/test_archive.tar.zst"),
         false,
+        None,
     )
     .is_err());
 
     // Destination directory isn't empty.
     let root_dst = tempfile::tempdir().unwrap();
     let existing_file = NamedTempFile::new_in(&root_dst).unwrap();
-    assert!(pack::create_archive(src_dir, existing_file.path(), false).is_err());
+    assert!(pack::create_archive(src_dir, existing_file.path(), false, None).is_err());
+}
+
+#[test]
+fn archive_create_manifest() {
+    let src_dir = &MOCK_DIR.0;
+    let test_payloads = &MOCK_DIR.1;
+    let dst_dir = tempfile::tempdir().unwrap();
+    let archive_path = dst_dir.path().join("test_archive.tar.zst");
+    pack::create_archive(src_dir, &archive_path, false, None).unwrap();
+
+    let manifest = inspect::inspect(archive_path.to_str().unwrap()).unwrap();
+    // There is no storage database in the source directory.
+    assert!(manifest.highest_block.is_none());
+    assert!(manifest.protocol_version.is_none());
+    assert_eq!(manifest.files.len(), NUM_TEST_FILES);
+    for idx in 0..NUM_TEST_FILES {
+        assert_eq!(
+            manifest.files.get(&format!("file_{idx}")).unwrap(),
+            &manifest::sha256_hex(test_payloads.payloads[idx].as_slice()).unwrap()
+        );
+    }
+
+    // Unpacking verifies the checksums and doesn't extract the manifest, but
+    // stamps the destination with the chain it describes.
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_path = out_dir.path().join("out");
+    file_stream::file_stream_and_unpack_archive(
+        &archive_path,
+        &out_path,
+        &[],
+        false,
+        None,
+        1,
+        false,
+    )
+    .unwrap();
+    assert!(!out_path.join(MANIFEST_FILE_NAME).exists());
+    assert_eq!(
+        Stamp::read(&out_path).unwrap().unwrap().chain_name,
+        manifest.chain_name
+    );
+    assert_eq!(fs::read_dir(&out_path).unwrap().count(), NUM_TEST_FILES + 1);
+}
+
+#[test]
+fn archive_unpack_checksum_mismatch() {
+    let src_dir = tempfile::tempdir().unwrap();
+    fs::write(src_dir.path().join("file_0"), "original").unwrap();
+    let dst_dir = tempfile::tempdir().unwrap();
+    let archive_path = dst_dir.path().join("test_archive.tar.zst");
+    {
+        // Build the manifest from the original file, then tamper with it
+        // before it gets archived.
+        let manifest_bytes = Manifest::new(&src_dir).unwrap().to_bytes().unwrap();
+        fs::write(src_dir.path().join("file_0"), "tampered").unwrap();
+        let output_file = File::create(&archive_path).unwrap();
+        let encoder = zstd_utils::zstd_encode_stream(output_file)
+            .unwrap()
+            .auto_finish();
+        let mut archive_stream = ArchiveStream::new(&src_dir, encoder).unwrap();
+        archive_stream
+            .append_bytes(MANIFEST_FILE_NAME, &manifest_bytes)
+            .unwrap();
+        archive_stream.pack().unwrap();
+    }
+
+    let out_dir = tempfile::tempdir().unwrap();
+    assert!(matches!(
+        file_stream::file_stream_and_unpack_archive(&archive_path, out_dir.path().join("out"), &[], false, None, 1, false),
+        Err(UnpackError::ChecksumMismatch(file_name)) if file_name == "file_0"
+    ));
+}
+
+#[test]
+fn archive_signature_roundtrip() {
+    let src_dir = &MOCK_DIR.0;
+    let dst_dir = tempfile::tempdir().unwrap();
+    let archive_path = dst_dir.path().join("test_archive.tar.zst");
+    let secret_key = SecretKey::ed25519_from_bytes([1u8; 32]).unwrap();
+    pack::create_archive(src_dir, &archive_path, false, Some(&secret_key)).unwrap();
+    let signature_hex = fs::read_to_string(format!(
+        "{}{}",
+        archive_path.to_str().unwrap(),
+        SIGNATURE_SUFFIX
+    ))
+    .unwrap();
+
+    let public_key_hex = PublicKey::from(&secret_key).to_hex();
+    let signature = ArchiveSignature::from_hex(&public_key_hex, &signature_hex).unwrap();
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_path = out_dir.path().join("out");
+    file_stream::file_stream_and_unpack_archive(
+        &archive_path,
+        &out_path,
+        &[],
+        false,
+        Some(&signature),
+        1,
+        false,
+    )
+    .unwrap();
+    assert!(out_path.join("file_0").exists());
+
+    // A signature by another key is rejected and nothing is left extracted.
+    let other_key = SecretKey::ed25519_from_bytes([2u8; 32]).unwrap();
+    let wrong_signature =
+        ArchiveSignature::from_hex(&PublicKey::from(&other_key).to_hex(), &signature_hex).unwrap();
+    let out_path = out_dir.path().join("wrong");
+    assert!(matches!(
+        file_stream::file_stream_and_unpack_archive(
+            &archive_path,
+            &out_path,
+            &[],
+            false,
+            Some(&wrong_signature),
+            1,
+            false,
+        ),
+        Err(UnpackError::Signature(SignatureError::Verification(..)))
+    ));
+    assert!(!out_path.join("file_0").exists());
 }
@@ -0,0 +1,79 @@
+use std::{
+    io::{self, Error as IoError, Read, Write},
+    result::Result,
+};
+
+use clap::{Arg, ArgMatches, Command};
+use tar::Archive;
+use thiserror::Error as ThisError;
+
+use super::{
+    manifest::{Error as ManifestError, Manifest, MANIFEST_FILE_NAME},
+    unpack::{download_stream, file_stream, Error as UnpackError},
+};
+
+pub const COMMAND_NAME: &str = "inspect";
+const SOURCE: &str = "source";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error reading archive manifest: {0}")]
+    Manifest(#[from] ManifestError),
+    #[error("Archive has no manifest")]
+    NoManifest,
+    #[error("Error writing output: {0}")]
+    Output(IoError),
+    #[error("Error opening archive: {0}")]
+    Source(#[from] UnpackError),
+    #[error("Error reading archive: {0}")]
+    Streaming(IoError),
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Prints the manifest of a zstd tar archive of a casper-node storage instance \
+            without extracting it.",
+        )
+        .arg(
+            Arg::new(SOURCE)
+                .required(true)
+                .value_name("URL|FILE_PATH")
+                .help(
+                    "URL or path of the compressed archive. Values starting with \
+                    `http://` or `https://` are treated as URLs.",
+                ),
+        )
+}
+
+/// Reads the manifest, which is always the first entry of an archive.
+fn read_manifest<R: Read>(mut archive: Archive<R>) -> Result<Manifest, Error> {
+    let mut entries = archive.entries().map_err(Error::Streaming)?;
+    let mut entry = match entries.next() {
+        Some(entry) => entry.map_err(Error::Streaming)?,
+        None => return Err(Error::NoManifest),
+    };
+    if entry.path().map_err(Error::Streaming)?.as_os_str() != MANIFEST_FILE_NAME {
+        return Err(Error::NoManifest);
+    }
+    Ok(Manifest::from_reader(&mut entry)?)
+}
+
+pub(super) fn inspect(source: &str) -> Result<Manifest, Error> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        read_manifest(download_stream::download_archive(source)?)
+    } else {
+        read_manifest(file_stream::file_stream_archive(source)?)
+    }
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let source = matches.value_of(SOURCE).unwrap();
+    let manifest = inspect(source)?;
+    let mut stdout = io::stdout();
+    stdout
+        .write_all(&manifest.to_bytes()?)
+        .and_then(|_| writeln!(stdout))
+        .map_err(Error::Output)
+}
@@ -0,0 +1,163 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Error as IoError, Read},
+    path::Path,
+    result::Result,
+};
+
+use bincode::Error as BincodeError;
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{ProtocolVersion, Timestamp};
+use lmdb::{Cursor, Error as LmdbError, Transaction};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Error as SerializationError;
+use sha2::{Digest as Sha2Digest, Sha256};
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{self, BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+    stamp::{self, Error as StampError, Stamp},
+};
+
+/// Name of the manifest entry, which is always the first entry of an archive.
+pub const MANIFEST_FILE_NAME: &str = "casper-db-utils-manifest.json";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error operating the storage database: {0}")]
+    Database(#[from] LmdbError),
+    #[error("Error hashing {0}: {1}")]
+    Hashing(String, IoError),
+    #[error("Invalid block hash at index {0}")]
+    InvalidKey(usize),
+    #[error("Error parsing block header at index {0}: {1}")]
+    Parsing(usize, BincodeError),
+    #[error("Error (de)serializing the manifest: {0}")]
+    Serialization(#[from] SerializationError),
+    #[error("Error reading the stamp of the database directory: {0}")]
+    Stamp(#[from] StampError),
+}
+
+/// The highest block found in the archived storage database.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HighestBlock {
+    pub height: u64,
+    pub hash: BlockHash,
+}
+
+/// Metadata describing the contents of an archive.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Manifest {
+    /// Name of the chain, taken from the stamp of the database directory or
+    /// derived from its name.
+    pub chain_name: Option<String>,
+    /// Highest block in the storage database, if any.
+    pub highest_block: Option<HighestBlock>,
+    /// Protocol version of the highest block, if any.
+    pub protocol_version: Option<ProtocolVersion>,
+    /// Hex encoded SHA-256 digests of the archived files, by file name.
+    pub files: BTreeMap<String, String>,
+    /// Time at which the archive was created.
+    pub created_at: Timestamp,
+}
+
+impl Manifest {
+    /// Builds the manifest for the files in `db_dir_path`.
+    pub fn new<P: AsRef<Path>>(db_dir_path: P) -> Result<Self, Error> {
+        let db_dir_path = db_dir_path.as_ref();
+        let chain_name = stamp::chain_name(db_dir_path)?;
+        let storage_path = db_dir_path.join(STORAGE_FILE_NAME);
+        let maybe_header = if storage_path.exists() {
+            highest_block_header(&storage_path)?
+        } else {
+            warn!("No {} in the database directory.", STORAGE_FILE_NAME);
+            None
+        };
+        let (highest_block, protocol_version) = match maybe_header {
+            Some((hash, header)) => (
+                Some(HighestBlock {
+                    height: header.height(),
+                    hash,
+                }),
+                Some(header.protocol_version()),
+            ),
+            None => (None, None),
+        };
+
+        let mut files = BTreeMap::new();
+        for entry in fs::read_dir(db_dir_path)
+            .map_err(|io_err| Error::Hashing(db_dir_path.to_string_lossy().to_string(), io_err))?
+            .flatten()
+        {
+            let path = entry.path();
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            info!("Hashing {}.", path.to_string_lossy());
+            let file =
+                File::open(&path).map_err(|io_err| Error::Hashing(file_name.clone(), io_err))?;
+            let digest =
+                sha256_hex(file).map_err(|io_err| Error::Hashing(file_name.clone(), io_err))?;
+            files.insert(file_name, digest);
+        }
+
+        Ok(Self {
+            chain_name,
+            highest_block,
+            protocol_version,
+            files,
+            created_at: Timestamp::now(),
+        })
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Error> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Returns the stamp describing the archived chain.
+    pub fn stamp(&self) -> Stamp {
+        Stamp {
+            chain_name: self.chain_name.clone(),
+            protocol_version: self.protocol_version,
+            lowest_height: None,
+            highest_height: self.highest_block.as_ref().map(|block| block.height),
+        }
+    }
+}
+
+/// Returns the hex encoded SHA-256 digest of everything read from `reader`.
+pub fn sha256_hex<R: Read>(mut reader: R) -> Result<String, IoError> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut reader, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn highest_block_header<P: AsRef<Path>>(
+    storage_path: P,
+) -> Result<Option<(BlockHash, BlockHeader)>, Error> {
+    let env = db::db_env(storage_path)?;
+    let txn = env.begin_ro_txn()?;
+    let db = match unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name())) } {
+        Ok(db) => db,
+        Err(LmdbError::NotFound) => return Ok(None),
+        Err(lmdb_err) => return Err(lmdb_err.into()),
+    };
+    let mut highest: Option<(BlockHash, BlockHeader)> = None;
+    for (idx, (raw_key, raw_val)) in txn.open_ro_cursor(db)?.iter().enumerate() {
+        let header: BlockHeader = bincode::deserialize(raw_val)
+            .map_err(|bincode_err| Error::Parsing(idx, bincode_err))?;
+        if let Some((_, highest_header)) = highest.as_ref() {
+            if highest_header.height() >= header.height() {
+                continue;
+            }
+        }
+        let digest = Digest::try_from(raw_key).map_err(|_| Error::InvalidKey(idx))?;
+        highest = Some((digest.into(), header));
+    }
+    Ok(highest)
+}
@@ -0,0 +1,112 @@
+use std::{
+    io::{Error as IoError, Read, Write},
+    result::Result,
+    sync::{Arc, Mutex},
+};
+
+use casper_types::{
+    crypto::{self, Error as CryptoError},
+    AsymmetricType, PublicKey, SecretKey, Signature,
+};
+use sha2::{Digest as Sha2Digest, Sha256};
+use thiserror::Error as ThisError;
+
+/// Suffix appended to the name or URL of an archive to get the one of its
+/// detached signature.
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Invalid public key: {0}")]
+    PublicKey(CryptoError),
+    #[error("Invalid signature: {0}")]
+    Signature(CryptoError),
+    #[error("The archive doesn't match its signature by {0}: {1}")]
+    Verification(String, CryptoError),
+}
+
+/// Hashes everything read or written through it with SHA-256. The digest is
+/// shared with a [`DigestHandle`], so that it can be taken once the stream is
+/// consumed by readers owning it.
+pub(crate) struct HashingStream<S> {
+    stream: S,
+    hasher: Arc<Mutex<Sha256>>,
+}
+
+/// Handle to the digest of a [`HashingStream`].
+pub(crate) struct DigestHandle(Arc<Mutex<Sha256>>);
+
+impl DigestHandle {
+    /// Returns the digest of the bytes streamed so far.
+    pub(crate) fn digest(&self) -> Vec<u8> {
+        self.0
+            .lock()
+            .expect("poisoned lock")
+            .clone()
+            .finalize()
+            .to_vec()
+    }
+}
+
+impl<S> HashingStream<S> {
+    pub(crate) fn new(stream: S) -> (Self, DigestHandle) {
+        let hasher = Arc::new(Mutex::new(Sha256::new()));
+        let handle = DigestHandle(Arc::clone(&hasher));
+        (Self { stream, hasher }, handle)
+    }
+}
+
+impl<R: Read> Read for HashingStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        let bytes_read = self.stream.read(buf)?;
+        self.hasher
+            .lock()
+            .expect("poisoned lock")
+            .update(&buf[..bytes_read]);
+        Ok(bytes_read)
+    }
+}
+
+impl<W: Write> Write for HashingStream<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, IoError> {
+        let bytes_written = self.stream.write(buf)?;
+        self.hasher
+            .lock()
+            .expect("poisoned lock")
+            .update(&buf[..bytes_written]);
+        Ok(bytes_written)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        self.stream.flush()
+    }
+}
+
+/// Signs the SHA-256 `digest` of a compressed archive, returning the hex
+/// encoded detached signature.
+pub(crate) fn sign(digest: &[u8], secret_key: &SecretKey) -> String {
+    crypto::sign(digest, secret_key, &PublicKey::from(secret_key)).to_hex()
+}
+
+/// The detached signature of a compressed archive, made over the SHA-256
+/// digest of the archive by the key of its publisher.
+pub struct ArchiveSignature {
+    public_key: PublicKey,
+    signature: Signature,
+}
+
+impl ArchiveSignature {
+    /// Parses the hex encoded `public_key` and `signature`.
+    pub fn from_hex(public_key: &str, signature: &str) -> Result<Self, Error> {
+        Ok(Self {
+            public_key: PublicKey::from_hex(public_key.trim()).map_err(Error::PublicKey)?,
+            signature: Signature::from_hex(signature.trim()).map_err(Error::Signature)?,
+        })
+    }
+
+    /// Checks the signature against the SHA-256 `digest` of the archive.
+    pub(crate) fn verify(&self, digest: &[u8]) -> Result<(), Error> {
+        crypto::verify(digest, &self.signature, &self.public_key)
+            .map_err(|crypto_err| Error::Verification(self.public_key.to_hex(), crypto_err))
+    }
+}
@@ -0,0 +1,107 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::info;
+
+use super::unpack::{file_stream, Error};
+use crate::common::{network, stamp::Stamp, temp::TempDir};
+
+pub(crate) use super::unpack::Include;
+
+/// Extension of the compressed archives which read-only subcommands accept
+/// in place of a database directory.
+pub(super) const ARCHIVE_EXTENSION: &str = ".tar.zst";
+
+/// A database directory which is either given directly or unpacked from a
+/// compressed archive into a temporary directory, in the one given by
+/// `--tmpdir` if set. In the latter case, the temporary directory is removed
+/// when this is dropped.
+pub(crate) struct DbDir {
+    path: PathBuf,
+    _temp_dir: Option<TempDir>,
+}
+
+impl DbDir {
+    /// Returns the directory `path`, or the one holding it if it is one of
+    /// the database files, checking it holds some of the `includes`
+    /// databases. If `path` is a `.tar.zst` archive, the `includes` files are
+    /// unpacked from it into a temporary directory instead.
+    pub(crate) fn open<P: AsRef<Path>>(path: P, includes: &[Include]) -> Result<Self, Error> {
+        let path = network::database_dir(path.as_ref());
+        let metadata =
+            fs::metadata(path).map_err(|io_err| Error::DbPath(path.to_path_buf(), io_err))?;
+        let archive_stem = match path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.strip_suffix(ARCHIVE_EXTENSION))
+        {
+            Some(stem) if metadata.is_file() => stem.to_string(),
+            _ if metadata.is_file() => return Err(Error::NotDbPath(path.to_path_buf())),
+            _ => {
+                check_databases(path, includes)?;
+                return Ok(Self {
+                    path: path.to_path_buf(),
+                    _temp_dir: None,
+                });
+            }
+        };
+
+        let temp_dir = TempDir::new().map_err(Error::Destination)?;
+        info!(
+            "Unpacking {} into {}.",
+            path.display(),
+            temp_dir.path().display()
+        );
+        file_stream::file_stream_and_unpack_archive(
+            path,
+            temp_dir.path(),
+            includes,
+            false,
+            None,
+            1,
+            false,
+        )?;
+        // Archives without a manifest leave the temporary directory unstamped,
+        // so the chain name would otherwise be derived from its random name.
+        if Stamp::read(temp_dir.path())?.is_none() {
+            Stamp {
+                chain_name: Some(archive_stem),
+                ..Default::default()
+            }
+            .write(temp_dir.path())?;
+        }
+        Ok(Self {
+            path: temp_dir.path().to_path_buf(),
+            _temp_dir: Some(temp_dir),
+        })
+    }
+
+    /// The directory holding the database files.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Checks that `dir` holds at least one of the `includes` databases, listing
+/// the files it holds otherwise, as a wrong path is the most common mistake.
+fn check_databases(dir: &Path, includes: &[Include]) -> Result<(), Error> {
+    let expected: Vec<&'static str> = includes.iter().filter_map(Include::final_file).collect();
+    if expected.is_empty() || expected.iter().any(|name| dir.join(name).is_file()) {
+        return Ok(());
+    }
+    let io_err = |io_err| Error::DbPath(dir.to_path_buf(), io_err);
+    let mut found = vec![];
+    for entry in fs::read_dir(dir).map_err(io_err)? {
+        found.push(
+            entry
+                .map_err(io_err)?
+                .file_name()
+                .to_string_lossy()
+                .into_owned(),
+        );
+    }
+    found.sort();
+    Err(Error::NoDatabase(dir.to_path_buf(), expected, found))
+}
@@ -6,7 +6,7 @@ use std::{
 };
 
 use log::info;
-use tar::{Archive, Builder};
+use tar::{Archive, Builder, Header};
 
 pub struct ArchiveStream<W: Write> {
     file_paths: VecDeque<PathBuf>,
@@ -26,6 +26,15 @@ impl<W: Write> ArchiveStream<W> {
         })
     }
 
+    /// Appends an entry named `name` with the given contents to the archive.
+    pub fn append_bytes(&mut self, name: &str, data: &[u8]) -> Result<(), IoError> {
+        let mut header = Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        self.builder.append_data(&mut header, name, data)
+    }
+
     pub fn pack(&mut self) -> Result<(), IoError> {
         while let Some(path) = self.file_paths.pop_front() {
             let mut file = OpenOptions::new()
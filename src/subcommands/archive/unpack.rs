@@ -1,37 +1,85 @@
-mod download_stream;
-mod file_stream;
+pub(super) mod download_stream;
+pub(super) mod file_stream;
+pub(crate) mod sparse_file;
 #[cfg(test)]
 mod tests;
 
 use std::{
+    collections::HashSet,
+    ffi::OsString,
     fs,
-    io::{Error as IoError, ErrorKind},
+    io::{self, Error as IoError, ErrorKind, Read},
     path::{Path, PathBuf},
 };
 
 use clap::{Arg, ArgGroup, ArgMatches, Command};
-use log::error;
+use log::{error, info, warn};
 use reqwest::Error as ReqwestError;
+use tar::Archive;
 use thiserror::Error as ThisError;
 
-use super::zstd_utils::Error as ZstdError;
+use super::{
+    manifest::{self, Error as ManifestError, Manifest, MANIFEST_FILE_NAME},
+    signature::{ArchiveSignature, DigestHandle, Error as SignatureError, SIGNATURE_SUFFIX},
+};
+use crate::common::{
+    db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+    disk_space::Error as DiskSpaceError,
+    stamp::{Error as StampError, Stamp},
+    zstd_utils::Error as ZstdError,
+};
 
 pub const COMMAND_NAME: &str = "unpack";
+const DECOMPRESS_THREADS: &str = "decompress-threads";
 const FILE: &str = "file";
+const FORCE: &str = "force";
+const INCLUDE: &str = "include";
 const INPUT_SOURCE: &str = "input-source";
 const OUTPUT: &str = "output";
+const PUBLIC_KEY: &str = "public-key";
+const SIGNATURE_URL: &str = "signature-url";
+const SPARSE: &str = "sparse";
 const URL: &str = "url";
 
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error("Checksum of extracted file {0} doesn't match the archive manifest")]
+    ChecksumMismatch(String),
+    #[error("Error reading database path {}: {}", .0.display(), .1)]
+    DbPath(PathBuf, IoError),
     #[error("Error validating destination directory: {0}")]
     Destination(IoError),
+    #[error("Disk space check failed: {0}")]
+    DiskSpace(#[from] DiskSpaceError),
+    #[error("Error reading archive manifest: {0}")]
+    Manifest(#[from] ManifestError),
+    #[error(
+        "{} holds none of the expected database files {}, found: [{}]",
+        .0.display(),
+        .1.join(", "),
+        .2.join(", ")
+    )]
+    NoDatabase(PathBuf, Vec<&'static str>, Vec<String>),
+    #[error(
+        "{} is neither a database directory, a {} or {} file, nor a {} archive",
+        .0.display(),
+        STORAGE_FILE_NAME,
+        TRIE_STORE_FILE_NAME,
+        super::snapshot::ARCHIVE_EXTENSION
+    )]
+    NotDbPath(PathBuf),
     #[error("HTTP request error: {0}")]
     Request(#[from] ReqwestError),
     #[error("Error creating tokio runtime: {0}")]
     Runtime(IoError),
+    #[error("Archive signature check failed: {0}")]
+    Signature(#[from] SignatureError),
+    #[error("Error reading the archive signature file: {0}")]
+    SignatureFile(IoError),
     #[error("Error reading source archive file: {0}")]
     Source(IoError),
+    #[error("Error stamping the destination: {0}")]
+    Stamp(#[from] StampError),
     #[error("Error streaming from zstd decoder to destination file: {0}")]
     Streaming(IoError),
     #[error("Zstd error: {0}")]
@@ -42,6 +90,182 @@ enum DisplayOrder {
     Url,
     File,
     Output,
+    Include,
+    PublicKey,
+    SignatureUrl,
+    DecompressThreads,
+    Sparse,
+    Force,
+}
+
+/// Kinds of files which can be selectively extracted from an archive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Include {
+    /// The storage database, `storage.lmdb`.
+    Storage,
+    /// The global state database, `data.lmdb`.
+    Trie,
+    /// Any file which isn't one of the databases.
+    Config,
+}
+
+impl Include {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "storage" => Some(Include::Storage),
+            "trie" => Some(Include::Trie),
+            "config" => Some(Include::Config),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, file_name: &str) -> bool {
+        let is_storage = file_name.starts_with(STORAGE_FILE_NAME);
+        let is_trie = file_name.starts_with(TRIE_STORE_FILE_NAME);
+        match self {
+            Include::Storage => is_storage,
+            Include::Trie => is_trie,
+            Include::Config => !is_storage && !is_trie,
+        }
+    }
+
+    /// Whether `path` in an archive is one of the databases.
+    fn is_database(path: &Path) -> bool {
+        path == Path::new(STORAGE_FILE_NAME) || path == Path::new(TRIE_STORE_FILE_NAME)
+    }
+
+    /// The name of the file which, once extracted, means there is nothing
+    /// more to extract for this kind.
+    pub(super) fn final_file(&self) -> Option<&'static str> {
+        match self {
+            Include::Storage => Some(STORAGE_FILE_NAME),
+            Include::Trie => Some(TRIE_STORE_FILE_NAME),
+            Include::Config => None,
+        }
+    }
+}
+
+/// A detached signature to check against the digest of the compressed
+/// archive once it's read in full.
+struct SignatureCheck<'a> {
+    signature: &'a ArchiveSignature,
+    digest: DigestHandle,
+}
+
+/// Unpacks the entries of `archive` into `dest`. If `includes` is not empty,
+/// only files matching one of the given kinds are extracted, and reading the
+/// archive stops as soon as all the requested databases were extracted.
+///
+/// If a signature check is given, the whole archive is read and the
+/// extracted files are removed if it doesn't match the signature. If the
+/// archive has a manifest, the checksums of the extracted files are verified
+/// against it and, unless the archive holds a stamp already, the destination
+/// is stamped with the chain described by the manifest.
+///
+/// The databases are written to files sized up front, see
+/// [`sparse_file::write_file`].
+fn unpack_entries<R: Read, P: AsRef<Path>>(
+    mut archive: Archive<R>,
+    dest: P,
+    includes: &[Include],
+    maybe_signature_check: Option<SignatureCheck>,
+    sparse: bool,
+) -> Result<(), Error> {
+    let dest = dest.as_ref();
+    let mut pending_files: HashSet<&'static str> = includes
+        .iter()
+        .filter_map(|include| include.final_file())
+        .collect();
+    let stop_early = maybe_signature_check.is_none()
+        && !includes.is_empty()
+        && !includes.contains(&Include::Config);
+    let mut maybe_manifest = None;
+    let mut extracted_files = vec![];
+    fs::create_dir_all(dest).map_err(Error::Streaming)?;
+    for entry in archive.entries().map_err(Error::Streaming)? {
+        let mut entry = entry.map_err(Error::Streaming)?;
+        let path = entry.path().map_err(Error::Streaming)?.into_owned();
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        if path.as_os_str() == MANIFEST_FILE_NAME {
+            maybe_manifest = Some(Manifest::from_reader(&mut entry)?);
+            continue;
+        }
+        if includes.is_empty() || includes.iter().any(|include| include.matches(&file_name)) {
+            info!("Extracting {}.", path.to_string_lossy());
+            if Include::is_database(&path) && entry.header().entry_type().is_file() {
+                let len = entry.size();
+                let holes_len = sparse_file::write_file(&mut entry, len, &dest.join(&path), sparse)
+                    .map_err(Error::Streaming)?;
+                if sparse {
+                    info!(
+                        "Left {} bytes of zero pages of {} as holes.",
+                        holes_len,
+                        path.to_string_lossy()
+                    );
+                }
+            } else {
+                entry.unpack_in(dest).map_err(Error::Streaming)?;
+            }
+            pending_files.remove(file_name.as_str());
+            extracted_files.push(file_name);
+        }
+        if stop_early && pending_files.is_empty() {
+            info!("Extracted all requested files.");
+            break;
+        }
+    }
+
+    if let Some(signature_check) = maybe_signature_check {
+        // The signature covers the whole archive, including the end of the
+        // compressed stream after the last entry.
+        io::copy(&mut archive.into_inner(), &mut io::sink()).map_err(Error::Streaming)?;
+        if let Err(signature_err) = signature_check
+            .signature
+            .verify(&signature_check.digest.digest())
+        {
+            for file_name in extracted_files {
+                let _ = fs::remove_file(dest.join(file_name));
+            }
+            return Err(signature_err.into());
+        }
+        info!("The archive matches its signature.");
+    }
+
+    match maybe_manifest {
+        Some(manifest) => {
+            verify_checksums(&manifest, dest, &extracted_files)?;
+            if Stamp::read(dest)?.is_none() {
+                manifest.stamp().write(dest)?;
+            }
+            Ok(())
+        }
+        None => {
+            warn!("Archive has no manifest, skipping checksum verification.");
+            Ok(())
+        }
+    }
+}
+
+fn verify_checksums(manifest: &Manifest, dest: &Path, file_names: &[String]) -> Result<(), Error> {
+    for file_name in file_names {
+        let expected = match manifest.files.get(file_name) {
+            Some(checksum) => checksum,
+            None => {
+                warn!("{} is not listed in the archive manifest.", file_name);
+                continue;
+            }
+        };
+        info!("Verifying checksum of {}.", file_name);
+        let file = fs::File::open(dest.join(file_name)).map_err(Error::Streaming)?;
+        let actual = manifest::sha256_hex(file).map_err(Error::Streaming)?;
+        if &actual != expected {
+            return Err(Error::ChecksumMismatch(file_name.clone()));
+        }
+    }
+    Ok(())
 }
 
 enum Input {
@@ -76,11 +300,51 @@ fn validate_destination_path<P: AsRef<Path>>(path: P) -> Result<(), Error> {
     }
 }
 
-fn unpack<P: AsRef<Path>>(input: Input, dest: P) -> Result<(), Error> {
+/// Reads the hex encoded detached signature of `input`, from
+/// `maybe_signature_url` if given, otherwise from next to the archive.
+fn read_signature(input: &Input, maybe_signature_url: Option<&str>) -> Result<String, Error> {
+    match (maybe_signature_url, input) {
+        (Some(signature_url), _) => download_stream::download_text(signature_url),
+        (None, Input::Url(url)) => {
+            download_stream::download_text(&format!("{}{}", url, SIGNATURE_SUFFIX))
+        }
+        (None, Input::File(path)) => {
+            let mut signature_path = OsString::from(path);
+            signature_path.push(SIGNATURE_SUFFIX);
+            fs::read_to_string(signature_path).map_err(Error::SignatureFile)
+        }
+    }
+}
+
+fn unpack<P: AsRef<Path>>(
+    input: Input,
+    dest: P,
+    includes: &[Include],
+    force: bool,
+    maybe_signature: Option<&ArchiveSignature>,
+    decompress_threads: usize,
+    sparse: bool,
+) -> Result<(), Error> {
     validate_destination_path(&dest)?;
     match input {
-        Input::Url(url) => download_stream::download_and_unpack_archive(&url, dest),
-        Input::File(path) => file_stream::file_stream_and_unpack_archive(path, dest),
+        Input::Url(url) => download_stream::download_and_unpack_archive(
+            &url,
+            dest,
+            includes,
+            force,
+            maybe_signature,
+            decompress_threads,
+            sparse,
+        ),
+        Input::File(path) => file_stream::file_stream_and_unpack_archive(
+            path,
+            dest,
+            includes,
+            force,
+            maybe_signature,
+            decompress_threads,
+            sparse,
+        ),
     }
 }
 
@@ -121,6 +385,88 @@ pub fn command(display_order: usize) -> Command<'static> {
                     directories.",
                 ),
         )
+        .arg(
+            Arg::new(INCLUDE)
+                .display_order(DisplayOrder::Include as usize)
+                .short('i')
+                .long(INCLUDE)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .possible_values(["storage", "trie", "config"])
+                .value_name("KIND")
+                .help(
+                    "Only extract the given kinds of files from the archive: \
+                    `storage` for the storage database, `trie` for the global \
+                    state database and `config` for any other file. Can be \
+                    repeated or given as a comma separated list. If \
+                    unspecified, all files are extracted.",
+                ),
+        )
+        .arg(
+            Arg::new(PUBLIC_KEY)
+                .display_order(DisplayOrder::PublicKey as usize)
+                .short('k')
+                .long(PUBLIC_KEY)
+                .takes_value(true)
+                .value_name("PUBLIC_KEY_HEX")
+                .help(
+                    "Hex encoded public key of the publisher of the archive. \
+                    If given, the detached signature of the archive is \
+                    fetched from next to it, with the `.sig` suffix, and the \
+                    archive is only kept if it matches.",
+                ),
+        )
+        .arg(
+            Arg::new(SIGNATURE_URL)
+                .display_order(DisplayOrder::SignatureUrl as usize)
+                .long(SIGNATURE_URL)
+                .takes_value(true)
+                .requires(PUBLIC_KEY)
+                .value_name("URL")
+                .help("URL of the detached signature of the archive."),
+        )
+        .arg(
+            Arg::new(DECOMPRESS_THREADS)
+                .display_order(DisplayOrder::DecompressThreads as usize)
+                .long(DECOMPRESS_THREADS)
+                .takes_value(true)
+                .value_name("COUNT")
+                .default_value("1")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(0) => Err("decompress-threads must be at least 1".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Number of threads decompressing the archive. Only \
+                    archives made of several zstd frames, such as the ones \
+                    written by `archive create`, are decompressed in \
+                    parallel. Each thread buffers up to 64 MiB.",
+                ),
+        )
+        .arg(
+            Arg::new(SPARSE)
+                .display_order(DisplayOrder::Sparse as usize)
+                .long(SPARSE)
+                .takes_value(false)
+                .help(
+                    "Leave the pages of the databases which are all zeros as \
+                    holes in the extracted files, as if `unsparse` was never \
+                    run on them. Otherwise, the disk space of the databases is \
+                    reserved before extracting them.",
+                ),
+        )
+        .arg(
+            Arg::new(FORCE)
+                .display_order(DisplayOrder::Force as usize)
+                .long(FORCE)
+                .takes_value(false)
+                .help(
+                    "Proceed even if the destination directory doesn't seem to \
+                    have enough free space for the archive.",
+                ),
+        )
         .group(
             ArgGroup::new(INPUT_SOURCE)
                 .required(true)
@@ -139,5 +485,32 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
                 .unwrap_or_else(|| panic!("Should have one of {FILE} or {URL}"))
         });
     let dest = matches.value_of(OUTPUT).unwrap();
-    unpack(input, dest)
+    let includes: Vec<Include> = matches
+        .values_of(INCLUDE)
+        .map(|values| {
+            values
+                .map(|value| Include::from_name(value).expect("should be a possible value"))
+                .collect()
+        })
+        .unwrap_or_default();
+    let maybe_signature = match matches.value_of(PUBLIC_KEY) {
+        Some(public_key) => {
+            let signature = read_signature(&input, matches.value_of(SIGNATURE_URL))?;
+            Some(ArchiveSignature::from_hex(public_key, &signature)?)
+        }
+        None => None,
+    };
+    unpack(
+        input,
+        dest,
+        &includes,
+        matches.is_present(FORCE),
+        maybe_signature.as_ref(),
+        matches
+            .value_of(DECOMPRESS_THREADS)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated"),
+        matches.is_present(SPARSE),
+    )
 }
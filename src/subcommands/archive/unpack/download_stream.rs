@@ -6,17 +6,22 @@ use std::{
 
 use futures::{io, AsyncRead, AsyncReadExt, TryStreamExt};
 use log::{info, warn};
+use tar::Archive;
 use tokio::runtime::{Builder as TokioRuntimeBuilder, Runtime};
 
-use super::Error;
+use super::{unpack_entries, Error, Include, SignatureCheck};
 use crate::{
-    common::progress::ProgressTracker,
-    subcommands::archive::{tar_utils, zstd_utils},
+    common::{disk_space, progress::ProgressTracker, zstd_utils},
+    subcommands::archive::{
+        signature::{ArchiveSignature, HashingStream},
+        tar_utils,
+    },
 };
 
 struct HttpStream {
     runtime: Runtime,
-    reader: Box<dyn AsyncRead + Unpin>,
+    content_length: Option<u64>,
+    reader: Box<dyn AsyncRead + Unpin + Send>,
     maybe_progress_tracker: Option<ProgressTracker>,
 }
 
@@ -26,10 +31,10 @@ impl HttpStream {
             let response_fut = reqwest::get(url).await;
             match response_fut {
                 Ok(response) => {
-                    let maybe_len = response.content_length().and_then(|len| {
+                    let maybe_len = response.content_length();
+                    if let Some(len) = maybe_len {
                         info!("Download size: {} bytes.", len);
-                        len.try_into().ok()
-                    });
+                    }
                     Ok((
                         response.bytes_stream().map_err(|reqwest_err| {
                             io::Error::new(io::ErrorKind::Other, reqwest_err)
@@ -42,9 +47,9 @@ impl HttpStream {
         };
         let (http_stream, maybe_content_length) = runtime.block_on(response_future)?;
         let http_stream = http_stream.into_async_read();
-        let reader = Box::new(http_stream) as Box<dyn AsyncRead + Unpin>;
+        let reader = Box::new(http_stream) as Box<dyn AsyncRead + Unpin + Send>;
         let mut maybe_progress_tracker = None;
-        match maybe_content_length {
+        match maybe_content_length.and_then(|len| len.try_into().ok()) {
             Some(len) => match ProgressTracker::new(
                 len,
                 Box::new(|completion| info!("Download {}% complete...", completion)),
@@ -62,6 +67,7 @@ impl HttpStream {
 
         Ok(Self {
             runtime,
+            content_length: maybe_content_length,
             reader,
             maybe_progress_tracker,
         })
@@ -79,15 +85,68 @@ impl Read for HttpStream {
     }
 }
 
-pub fn download_and_unpack_archive<P: AsRef<Path>>(url: &str, dest: P) -> Result<(), Error> {
-    let runtime = TokioRuntimeBuilder::new_current_thread()
+fn new_runtime() -> Result<Runtime, Error> {
+    TokioRuntimeBuilder::new_current_thread()
         .enable_time()
         .enable_io()
         .build()
-        .map_err(Error::Runtime)?;
-    let http_stream = HttpStream::new(runtime, url)?;
-    let decoder = zstd_utils::zstd_decode_stream(http_stream)?;
-    let mut unpacker = tar_utils::unarchive_stream(decoder);
-    unpacker.unpack(&dest).map_err(Error::Streaming)?;
-    Ok(())
+        .map_err(Error::Runtime)
+}
+
+fn open_http_stream(url: &str) -> Result<HttpStream, Error> {
+    HttpStream::new(new_runtime()?, url)
+}
+
+fn decompressed_archive<R: Read + Send + 'static>(
+    stream: R,
+    decompress_threads: usize,
+) -> Result<Archive<impl Read>, Error> {
+    let decoder = zstd_utils::zstd_decode_stream_parallel(stream, decompress_threads)?;
+    Ok(tar_utils::unarchive_stream(decoder))
+}
+
+/// Downloads the text at `url`, such as the detached signature of an
+/// archive.
+pub fn download_text(url: &str) -> Result<String, Error> {
+    let text_future = async { reqwest::get(url).await?.error_for_status()?.text().await };
+    Ok(new_runtime()?.block_on(text_future)?)
+}
+
+/// Starts downloading the archive at `url`, returning a reader over the
+/// decompressed tar stream.
+pub fn download_archive(url: &str) -> Result<Archive<impl Read>, Error> {
+    decompressed_archive(open_http_stream(url)?, 1)
+}
+
+pub fn download_and_unpack_archive<P: AsRef<Path>>(
+    url: &str,
+    dest: P,
+    includes: &[Include],
+    force: bool,
+    maybe_signature: Option<&ArchiveSignature>,
+    decompress_threads: usize,
+    sparse: bool,
+) -> Result<(), Error> {
+    let http_stream = open_http_stream(url)?;
+    // The archive is compressed, so its size is only a lower bound of the
+    // space needed.
+    match http_stream.content_length {
+        Some(len) => disk_space::check_available_space(&dest, len, force)?,
+        None => warn!("Unknown download size, skipping the disk space check."),
+    }
+    match maybe_signature {
+        Some(signature) => {
+            let (hashing_stream, digest) = HashingStream::new(http_stream);
+            let signature_check = SignatureCheck { signature, digest };
+            let unpacker = decompressed_archive(hashing_stream, decompress_threads)?;
+            unpack_entries(unpacker, dest, includes, Some(signature_check), sparse)
+        }
+        None => unpack_entries(
+            decompressed_archive(http_stream, decompress_threads)?,
+            dest,
+            includes,
+            None,
+            sparse,
+        ),
+    }
 }
@@ -1,16 +1,20 @@
 use std::{
-    fs::OpenOptions,
+    fs::{self, File, OpenOptions},
     io::{Error as IoError, Read},
     path::Path,
     result::Result,
 };
 
 use log::{info, warn};
+use tar::Archive;
 
-use super::Error;
+use super::{unpack_entries, Error, Include, SignatureCheck};
 use crate::{
-    common::progress::ProgressTracker,
-    subcommands::archive::{tar_utils, zstd_utils},
+    common::{disk_space, progress::ProgressTracker, zstd_utils},
+    subcommands::archive::{
+        signature::{ArchiveSignature, HashingStream},
+        tar_utils,
+    },
 };
 
 struct FileStream<R> {
@@ -59,10 +63,7 @@ impl<R: Read> Read for FileStream<R> {
     }
 }
 
-pub fn file_stream_and_unpack_archive<P1: AsRef<Path>, P2: AsRef<Path>>(
-    path: P1,
-    dest: P2,
-) -> Result<(), Error> {
+fn open_file_stream<P: AsRef<Path>>(path: P) -> Result<FileStream<File>, Error> {
     let input_file = OpenOptions::new()
         .read(true)
         .open(path)
@@ -71,9 +72,50 @@ pub fn file_stream_and_unpack_archive<P1: AsRef<Path>, P2: AsRef<Path>>(
         .metadata()
         .ok()
         .and_then(|metadata| metadata.len().try_into().ok());
-    let file_stream = FileStream::new(input_file, file_len);
-    let decoder = zstd_utils::zstd_decode_stream(file_stream)?;
-    let mut unpacker = tar_utils::unarchive_stream(decoder);
-    unpacker.unpack(dest).map_err(Error::Streaming)?;
-    Ok(())
+    Ok(FileStream::new(input_file, file_len))
+}
+
+fn decompressed_archive<R: Read + Send + 'static>(
+    stream: R,
+    decompress_threads: usize,
+) -> Result<Archive<impl Read>, Error> {
+    let decoder = zstd_utils::zstd_decode_stream_parallel(stream, decompress_threads)?;
+    Ok(tar_utils::unarchive_stream(decoder))
+}
+
+/// Opens the archive at `path`, returning a reader over the decompressed tar
+/// stream.
+pub fn file_stream_archive<P: AsRef<Path>>(path: P) -> Result<Archive<impl Read>, Error> {
+    decompressed_archive(open_file_stream(path)?, 1)
+}
+
+pub fn file_stream_and_unpack_archive<P1: AsRef<Path>, P2: AsRef<Path>>(
+    path: P1,
+    dest: P2,
+    includes: &[Include],
+    force: bool,
+    maybe_signature: Option<&ArchiveSignature>,
+    decompress_threads: usize,
+    sparse: bool,
+) -> Result<(), Error> {
+    // The archive is compressed, so its size is only a lower bound of the
+    // space needed.
+    let archive_len = fs::metadata(&path).map_err(Error::Source)?.len();
+    disk_space::check_available_space(&dest, archive_len, force)?;
+    let file_stream = open_file_stream(path)?;
+    match maybe_signature {
+        Some(signature) => {
+            let (hashing_stream, digest) = HashingStream::new(file_stream);
+            let signature_check = SignatureCheck { signature, digest };
+            let unpacker = decompressed_archive(hashing_stream, decompress_threads)?;
+            unpack_entries(unpacker, dest, includes, Some(signature_check), sparse)
+        }
+        None => unpack_entries(
+            decompressed_archive(file_stream, decompress_threads)?,
+            dest,
+            includes,
+            None,
+            sparse,
+        ),
+    }
 }
@@ -0,0 +1,104 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Error as IoError, ErrorKind, Read, Seek, SeekFrom, Write},
+    path::Path,
+    result::Result,
+};
+
+/// Size of the pages which are left as holes when writing sparse files, the
+/// LMDB page size on common platforms.
+const PAGE_SIZE: usize = 4096;
+/// Size of the chunks read at once from the archive.
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Reserves `len` bytes of disk space for `file`, so that a full disk is
+/// noticed before extracting and the file isn't fragmented.
+#[cfg(target_os = "linux")]
+fn preallocate(file: &File, len: u64) -> Result<(), IoError> {
+    use std::os::unix::io::AsRawFd;
+
+    let result = unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len as libc::off_t) };
+    if result == 0 {
+        return Ok(());
+    }
+    let io_err = IoError::last_os_error();
+    match io_err.raw_os_error() {
+        // Not every file system can reserve space.
+        Some(libc::EOPNOTSUPP) => file.set_len(len),
+        _ => Err(io_err),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn preallocate(file: &File, len: u64) -> Result<(), IoError> {
+    file.set_len(len)
+}
+
+fn is_zero(page: &[u8]) -> bool {
+    page.iter().all(|byte| *byte == 0)
+}
+
+/// Writes the `len` bytes read from `reader` to a new file at `path`, sized
+/// up front. If `sparse` is set, the pages which are all zeros are skipped,
+/// leaving holes in the file, otherwise the space of the whole file is
+/// reserved before writing. Returns the number of bytes left as holes.
+pub(crate) fn write_file<R: Read>(
+    reader: &mut R,
+    len: u64,
+    path: &Path,
+    sparse: bool,
+) -> Result<u64, IoError> {
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    if sparse {
+        file.set_len(len)?;
+    } else {
+        preallocate(&file, len)?;
+    }
+
+    let mut reader = reader.take(len);
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+    let mut bytes_read = 0u64;
+    let mut holes_len = 0u64;
+    loop {
+        chunk.clear();
+        if (&mut reader)
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)?
+            == 0
+        {
+            break;
+        }
+        bytes_read += chunk.len() as u64;
+        // Write the runs of pages with data at once, and seek over the runs
+        // of zero pages.
+        let mut offset = 0;
+        while offset < chunk.len() {
+            let page_end = |start: usize| (start + PAGE_SIZE).min(chunk.len());
+            let zero = sparse && is_zero(&chunk[offset..page_end(offset)]);
+            let mut run_end = page_end(offset);
+            while run_end < chunk.len()
+                && (sparse && is_zero(&chunk[run_end..page_end(run_end)])) == zero
+            {
+                run_end = page_end(run_end);
+            }
+            if zero {
+                file.seek(SeekFrom::Current((run_end - offset) as i64))?;
+                holes_len += (run_end - offset) as u64;
+            } else {
+                file.write_all(&chunk[offset..run_end])?;
+            }
+            offset = run_end;
+        }
+    }
+    if bytes_read != len {
+        return Err(IoError::new(
+            ErrorKind::UnexpectedEof,
+            format!("{} ends before its size", path.display()),
+        ));
+    }
+    Ok(holes_len)
+}
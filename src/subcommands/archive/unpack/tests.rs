@@ -10,9 +10,15 @@ use rand::{self, RngCore};
 use tar::Builder;
 use zstd::Encoder;
 
-use crate::subcommands::archive::{
-    unpack::{download_stream, file_stream},
-    zstd_utils,
+use crate::{
+    common::{
+        db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+        stamp, zstd_utils,
+    },
+    subcommands::archive::{
+        snapshot::DbDir,
+        unpack::{download_stream, file_stream, Error, Include},
+    },
 };
 
 const TEST_ADDR: &str = "127.0.0.1:9876";
@@ -133,7 +139,7 @@ fn archive_unpack_decode_network() {
     http_addr.push_str(TEST_ADDR);
 
     // Download the file with zstd encoding.
-    download_stream::download_and_unpack_archive(&http_addr, &temp_dir)
+    download_stream::download_and_unpack_archive(&http_addr, &temp_dir, &[], false, None, 1, false)
         .expect("Error downloading and decoding payload");
 
     // Check that the downloaded contents are the same as our payload.
@@ -177,8 +183,16 @@ fn archive_unpack_decode_file() {
     let temp_dir = tempfile::tempdir().unwrap();
 
     // Stream the file with zstd encoding.
-    file_stream::file_stream_and_unpack_archive(&compressed_archive_path, &temp_dir)
-        .expect("Error downloading and decoding payload");
+    file_stream::file_stream_and_unpack_archive(
+        &compressed_archive_path,
+        &temp_dir,
+        &[],
+        false,
+        None,
+        1,
+        false,
+    )
+    .expect("Error downloading and decoding payload");
 
     // Check that the streamed contents are the same as our payload.
     let output_bytes = fs::read(temp_dir.path().join(TEST_FILE))
@@ -186,17 +200,94 @@ fn archive_unpack_decode_file() {
     assert_eq!(payload.to_vec(), output_bytes);
 }
 
+#[test]
+fn archive_unpack_selected_files() {
+    const CONFIG_FILE: &str = "chainspec.toml";
+    let src_dir = tempfile::tempdir().unwrap();
+    let compressed_archive_path = src_dir.path().join(TEST_COMPRESSED_ARCHIVE);
+    {
+        let compressed_archive = File::create(&compressed_archive_path).unwrap();
+        let encoder = Encoder::new(compressed_archive, 0).unwrap();
+        let mut archive = Builder::new(encoder);
+        for (file_name, contents) in [
+            (CONFIG_FILE, b"config".as_slice()),
+            (STORAGE_FILE_NAME, b"storage".as_slice()),
+            (TRIE_STORE_FILE_NAME, b"trie".as_slice()),
+        ] {
+            let file_path = src_dir.path().join(file_name);
+            fs::write(&file_path, contents).unwrap();
+            archive
+                .append_file(file_name, &mut File::open(&file_path).unwrap())
+                .unwrap();
+        }
+        let _ = archive.into_inner().unwrap().finish().unwrap();
+    }
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dest_path = temp_dir.path().join("storage_only");
+    file_stream::file_stream_and_unpack_archive(
+        &compressed_archive_path,
+        &dest_path,
+        &[Include::Storage],
+        false,
+        None,
+        1,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        fs::read(dest_path.join(STORAGE_FILE_NAME)).unwrap(),
+        b"storage"
+    );
+    assert!(!dest_path.join(TRIE_STORE_FILE_NAME).exists());
+    assert!(!dest_path.join(CONFIG_FILE).exists());
+
+    let dest_path = temp_dir.path().join("trie_and_config");
+    file_stream::file_stream_and_unpack_archive(
+        &compressed_archive_path,
+        &dest_path,
+        &[Include::Trie, Include::Config],
+        false,
+        None,
+        1,
+        false,
+    )
+    .unwrap();
+    assert!(!dest_path.join(STORAGE_FILE_NAME).exists());
+    assert_eq!(
+        fs::read(dest_path.join(TRIE_STORE_FILE_NAME)).unwrap(),
+        b"trie"
+    );
+    assert_eq!(fs::read(dest_path.join(CONFIG_FILE)).unwrap(), b"config");
+}
+
 #[test]
 fn archive_unpack_invalid_url() {
     let temp_dir = tempfile::tempdir().unwrap();
     let dest_path = temp_dir.path().join(TEST_FILE);
 
     // No HTTP schema.
-    assert!(download_stream::download_and_unpack_archive("localhost:10000", &dest_path).is_err());
+    assert!(download_stream::download_and_unpack_archive(
+        "localhost:10000",
+        &dest_path,
+        &[],
+        false,
+        None,
+        1,
+        false
+    )
+    .is_err());
     // No server running at `localhost:10000`.
-    assert!(
-        download_stream::download_and_unpack_archive("http://localhost:10000", dest_path).is_err()
-    );
+    assert!(download_stream::download_and_unpack_archive(
+        "http://localhost:10000",
+        dest_path,
+        &[],
+        false,
+        None,
+        1,
+        false
+    )
+    .is_err());
 }
 
 #[test]
@@ -209,7 +300,16 @@ fn archive_unpack_existing_destination() {
     let _ = File::create(&dest_path).unwrap();
     // Download should fail because a file is already present at the destination
     // directory. Address doesn't matter because the file check is performed first.
-    assert!(download_stream::download_and_unpack_archive("bogus_address", dest_path).is_err());
+    assert!(download_stream::download_and_unpack_archive(
+        "bogus_address",
+        dest_path,
+        &[],
+        false,
+        None,
+        1,
+        false
+    )
+    .is_err());
 }
 
 #[test]
@@ -220,7 +320,16 @@ fn archive_unpack_missing_file() {
 
     // Streaming from file should fail because the source is missing. Destination
     // doesn't matter because the source check is performed first.
-    assert!(file_stream::file_stream_and_unpack_archive(missing_src_path, "bogus_path").is_err());
+    assert!(file_stream::file_stream_and_unpack_archive(
+        missing_src_path,
+        "bogus_path",
+        &[],
+        false,
+        None,
+        1,
+        false
+    )
+    .is_err());
 }
 
 #[test]
@@ -239,5 +348,141 @@ fn archive_unpack_file_existing_destination() {
     // File streaming should fail because the destination file is already present.
     // The source doesn't matter because the existing destination check is
     // performed first.
-    assert!(file_stream::file_stream_and_unpack_archive(src_path, dest_path).is_err());
+    assert!(file_stream::file_stream_and_unpack_archive(
+        src_path,
+        dest_path,
+        &[],
+        false,
+        None,
+        1,
+        false
+    )
+    .is_err());
+}
+
+#[test]
+fn db_dir_from_archive() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let compressed_archive_path = src_dir.path().join("casper-test.tar.zst");
+    {
+        let compressed_archive = File::create(&compressed_archive_path).unwrap();
+        let encoder = Encoder::new(compressed_archive, 0).unwrap();
+        let mut archive = Builder::new(encoder);
+        for (file_name, contents) in [
+            (STORAGE_FILE_NAME, b"storage".as_slice()),
+            (TRIE_STORE_FILE_NAME, b"trie".as_slice()),
+        ] {
+            let file_path = src_dir.path().join(file_name);
+            fs::write(&file_path, contents).unwrap();
+            archive
+                .append_file(file_name, &mut File::open(&file_path).unwrap())
+                .unwrap();
+        }
+        let _ = archive.into_inner().unwrap().finish().unwrap();
+    }
+
+    // A directory is used as is.
+    let db_dir = DbDir::open(src_dir.path(), &[Include::Storage]).unwrap();
+    assert_eq!(db_dir.path(), src_dir.path());
+
+    // An archive is unpacked into a temporary directory, stamped with the
+    // chain name taken from the archive name.
+    let db_dir = DbDir::open(&compressed_archive_path, &[Include::Storage]).unwrap();
+    let unpacked_path = db_dir.path().to_path_buf();
+    assert_ne!(unpacked_path, src_dir.path());
+    assert_eq!(
+        fs::read(unpacked_path.join(STORAGE_FILE_NAME)).unwrap(),
+        b"storage"
+    );
+    assert!(!unpacked_path.join(TRIE_STORE_FILE_NAME).exists());
+    assert_eq!(
+        stamp::chain_name(&unpacked_path).unwrap(),
+        Some("casper-test".to_string())
+    );
+    drop(db_dir);
+    assert!(!unpacked_path.exists());
+}
+
+#[test]
+fn db_dir_should_detect_database_files() {
+    let src_dir = tempfile::tempdir().unwrap();
+    let storage_path = src_dir.path().join(STORAGE_FILE_NAME);
+    fs::write(&storage_path, b"storage").unwrap();
+    fs::write(src_dir.path().join("chainspec.toml"), b"").unwrap();
+
+    // A database file stands for its directory.
+    let db_dir = DbDir::open(&storage_path, &[Include::Storage]).unwrap();
+    assert_eq!(db_dir.path(), src_dir.path());
+
+    // A directory without the expected databases lists what it holds.
+    match DbDir::open(src_dir.path(), &[Include::Trie]) {
+        Err(Error::NoDatabase(dir, expected, found)) => {
+            assert_eq!(dir, src_dir.path());
+            assert_eq!(expected, vec![TRIE_STORE_FILE_NAME]);
+            assert_eq!(found, vec!["chainspec.toml", STORAGE_FILE_NAME]);
+        }
+        Err(error) => panic!("Got unexpected error: {error:?}"),
+        Ok(_) => panic!("Opened a directory without a trie store"),
+    }
+    assert!(matches!(
+        DbDir::open(src_dir.path().join("chainspec.toml"), &[Include::Storage]),
+        Err(Error::NotDbPath(_))
+    ));
+    assert!(matches!(
+        DbDir::open(src_dir.path().join("missing"), &[Include::Storage]),
+        Err(Error::DbPath(..))
+    ));
+}
+
+#[test]
+fn archive_unpack_sparse_database() {
+    const PAGE_SIZE: usize = 4096;
+    // Pages of data around runs of zero pages, including at the end.
+    let mut contents = vec![0u8; PAGE_SIZE * 600];
+    contents[..PAGE_SIZE].fill(1);
+    contents[PAGE_SIZE * 300..PAGE_SIZE * 302].fill(2);
+    contents[PAGE_SIZE * 302 + 10] = 3;
+
+    let src_dir = tempfile::tempdir().unwrap();
+    let compressed_archive_path = src_dir.path().join(TEST_COMPRESSED_ARCHIVE);
+    {
+        let compressed_archive = File::create(&compressed_archive_path).unwrap();
+        let encoder = Encoder::new(compressed_archive, 0).unwrap();
+        let mut archive = Builder::new(encoder);
+        let file_path = src_dir.path().join(STORAGE_FILE_NAME);
+        fs::write(&file_path, &contents).unwrap();
+        archive
+            .append_file(STORAGE_FILE_NAME, &mut File::open(&file_path).unwrap())
+            .unwrap();
+        let _ = archive.into_inner().unwrap().finish().unwrap();
+    }
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    for sparse in [false, true] {
+        let dest_path = temp_dir.path().join(format!("sparse_{sparse}"));
+        file_stream::file_stream_and_unpack_archive(
+            &compressed_archive_path,
+            &dest_path,
+            &[],
+            false,
+            None,
+            1,
+            sparse,
+        )
+        .unwrap();
+        let db_path = dest_path.join(STORAGE_FILE_NAME);
+        assert_eq!(fs::read(&db_path).unwrap(), contents);
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::MetadataExt;
+
+            // Only the pages with data take disk space in the sparse file.
+            let allocated = fs::metadata(&db_path).unwrap().blocks() * 512;
+            if sparse {
+                assert!(allocated < contents.len() as u64 / 2);
+            } else {
+                assert!(allocated >= contents.len() as u64);
+            }
+        }
+    }
 }
@@ -0,0 +1,110 @@
+mod backfill;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::PathBuf};
+
+use bincode::Error as BincodeError;
+use casper_node::types::{BlockHash, DeployHash};
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    block_iter::Error as BlockIterError, stamp::Error as StampError, storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "backfill-exec-results";
+const DB_PATH: &str = "db-path";
+const DONOR_DB_PATH: &str = "donor-db-path";
+
+/// Errors encountered when backfilling execution results from a donor
+/// database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the donor database directory or unpacking it from an archive.
+    #[error("Error opening the donor database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// The databases hold different blocks at the same height.
+    #[error("Block at height {0} is {1} in the database but {2} in the donor")]
+    HashMismatch(u64, BlockHash, BlockHash),
+    /// Parsing error on the metadata of a deploy.
+    #[error("Error parsing execution results of deploy {0}: {1}")]
+    Parsing(DeployHash, BincodeError),
+    /// Error serializing the updated metadata of a deploy.
+    #[error("Error serializing execution results of deploy {0}: {1}")]
+    Serialization(DeployHash, BincodeError),
+    /// Error reading the size of a database.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+    /// The databases hold data of different chains.
+    #[error("Stamp validation failed: {0}")]
+    Stamp(#[from] StampError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Self::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    DonorDbPath,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Inserts the execution results missing from the deploys of the \
+            blocks in a storage database, such as the ones of a fast-synced \
+            node, by reading them from a donor archival database. The blocks \
+            of both databases must match at every height they share.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file to backfill."),
+        )
+        .arg(
+            Arg::new(DONOR_DB_PATH)
+                .display_order(DisplayOrder::DonorDbPath as usize)
+                .required(true)
+                .short('s')
+                .long(DONOR_DB_PATH)
+                .takes_value(true)
+                .value_name("DONOR_DB_PATH")
+                .help(
+                    "Path of the directory with the donor `storage.lmdb` file, \
+                    or of a `.tar.zst` archive of it.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let donor_dir = DbDir::open(
+        matches
+            .value_of(DONOR_DB_PATH)
+            .expect("should have donor-db-path arg"),
+        &[Include::Storage],
+    )?;
+    backfill::backfill_exec_results(db_path, donor_dir.path())?;
+    Ok(())
+}
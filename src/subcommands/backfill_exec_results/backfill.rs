@@ -0,0 +1,187 @@
+use std::{collections::HashMap, fs, path::Path, result::Result};
+
+use casper_node::types::{BlockHash, DeployHash, DeployMetadata};
+use lmdb::Transaction;
+use log::{info, warn};
+
+use crate::common::{
+    block_iter,
+    db::{BlockHeaderDatabase, Database, DeployMetadataDatabase, StorageEnv, STORAGE_FILE_NAME},
+    progress::ProgressTracker,
+    stamp,
+    storage::{LmdbReader, LmdbWriter, StorageReader, StorageWriter},
+};
+
+use super::Error;
+
+/// Number of blocks backfilled per transaction.
+const BLOCKS_PER_COMMIT: usize = 100;
+
+/// Counts of the blocks and execution results found while backfilling.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct BackfillSummary {
+    /// Blocks of the database.
+    pub(crate) blocks: usize,
+    /// Blocks which aren't in the donor.
+    pub(crate) missing_in_donor: usize,
+    /// Execution results inserted into the database.
+    pub(crate) backfilled: usize,
+    /// Execution results missing from both databases.
+    pub(crate) still_missing: usize,
+}
+
+fn file_size(path: &Path) -> Result<usize, Error> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len() as usize)
+        .map_err(|io_err| Error::Source(path.to_path_buf(), io_err))
+}
+
+/// Reads the metadata of `deploy_hash`, or returns `None` if there is none.
+fn read_metadata<R: StorageReader>(
+    reader: &R,
+    deploy_hash: &DeployHash,
+) -> Result<Option<DeployMetadata>, Error> {
+    if !reader.has_table(DeployMetadataDatabase::db_name())? {
+        return Ok(None);
+    }
+    match reader.get(DeployMetadataDatabase::db_name(), deploy_hash.as_ref())? {
+        Some(raw_metadata) => bincode::deserialize(&raw_metadata)
+            .map(Some)
+            .map_err(|bincode_err| Error::Parsing(*deploy_hash, bincode_err)),
+        None => Ok(None),
+    }
+}
+
+/// Inserts the result of executing `deploy_hash` in `block_hash` into the
+/// metadata of the deploy in `writer`, taking it from `donor` if it's
+/// missing.
+fn backfill_deploy<R: StorageReader, W: StorageWriter>(
+    donor: &R,
+    writer: &mut W,
+    block_hash: &BlockHash,
+    deploy_hash: &DeployHash,
+    summary: &mut BackfillSummary,
+) -> Result<(), Error> {
+    let mut metadata = read_metadata(writer, deploy_hash)?.unwrap_or_default();
+    if metadata.execution_results.contains_key(block_hash) {
+        return Ok(());
+    }
+    let maybe_result = read_metadata(donor, deploy_hash)?
+        .and_then(|mut donor_metadata| donor_metadata.execution_results.remove(block_hash));
+    match maybe_result {
+        Some(execution_result) => {
+            metadata
+                .execution_results
+                .insert(*block_hash, execution_result);
+            let raw_metadata = bincode::serialize(&metadata)
+                .map_err(|bincode_err| Error::Serialization(*deploy_hash, bincode_err))?;
+            writer.put(
+                DeployMetadataDatabase::db_name(),
+                deploy_hash.as_ref(),
+                &raw_metadata,
+            )?;
+            summary.backfilled += 1;
+        }
+        None => summary.still_missing += 1,
+    }
+    Ok(())
+}
+
+/// Inserts the execution results missing from the deploys of the blocks in
+/// the storage database in `db_path`, reading them from the one in
+/// `donor_path`. Fails before writing anything if the databases hold
+/// different blocks at the same height.
+pub(crate) fn backfill_exec_results<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    donor_path: P2,
+) -> Result<BackfillSummary, Error> {
+    let db_path = db_path.as_ref();
+    let donor_path = donor_path.as_ref();
+    stamp::ensure_same_chain(&[db_path, donor_path])?;
+
+    let donor_env = StorageEnv::open(donor_path)?;
+    let donor_txn = donor_env.begin_ro_txn()?;
+    let donor = LmdbReader::new(&donor_txn);
+    let donor_blocks: HashMap<u64, BlockHash> = if donor.has_table(BlockHeaderDatabase::db_name())?
+    {
+        block_iter::blocks_by_height(&donor)?.into_iter().collect()
+    } else {
+        HashMap::new()
+    };
+
+    // Leave room for the execution results of every deploy of the donor.
+    let db_size = file_size(&db_path.join(STORAGE_FILE_NAME))?;
+    let donor_size = file_size(&donor_path.join(STORAGE_FILE_NAME))?;
+    let env = StorageEnv::create(db_path, db_size + donor_size)?;
+    let blocks = {
+        let txn = env.begin_ro_txn()?;
+        block_iter::blocks_by_height(&LmdbReader::new(&txn))?
+    };
+
+    let mut summary = BackfillSummary {
+        blocks: blocks.len(),
+        ..Default::default()
+    };
+    let mut shared_blocks = vec![];
+    for (height, block_hash) in blocks {
+        match donor_blocks.get(&height) {
+            Some(donor_block_hash) if *donor_block_hash != block_hash => {
+                return Err(Error::HashMismatch(height, block_hash, *donor_block_hash))
+            }
+            Some(_) => shared_blocks.push(block_hash),
+            None => summary.missing_in_donor += 1,
+        }
+    }
+    if summary.missing_in_donor > 0 {
+        warn!(
+            "{} blocks aren't in the donor, their execution results can't be backfilled.",
+            summary.missing_in_donor
+        );
+    }
+
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        shared_blocks.len(),
+        Box::new(|completion| info!("Backfill {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    for batch in shared_blocks.chunks(BLOCKS_PER_COMMIT) {
+        let mut txn = env.begin_rw_txn()?;
+        let mut writer = LmdbWriter::new(&mut txn);
+        for block_hash in batch {
+            let header = block_iter::read_header(&writer, block_hash)?;
+            match block_iter::read_body(&writer, block_hash, &header)? {
+                Some(body) => {
+                    for deploy_hash in body.deploy_hashes.iter().chain(body.transfer_hashes.iter())
+                    {
+                        backfill_deploy(
+                            &donor,
+                            &mut writer,
+                            block_hash,
+                            deploy_hash,
+                            &mut summary,
+                        )?;
+                    }
+                }
+                None => warn!("Block {} has no body, skipping it.", block_hash),
+            }
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+        }
+        txn.commit()?;
+    }
+
+    info!(
+        "Backfilled {} execution results, {} are missing from the donor too.",
+        summary.backfilled, summary.still_missing
+    );
+    Ok(summary)
+}
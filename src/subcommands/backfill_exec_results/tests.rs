@@ -0,0 +1,150 @@
+use casper_node::types::{BlockHash, DeployMetadata};
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    backfill::{backfill_exec_results, BackfillSummary},
+    Error,
+};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployMetadataDatabase, StorageEnv,
+            STORAGE_FILE_NAME,
+        },
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn new_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+/// Stores the block with hash index `idx` at `height`, with a body executing
+/// the deploy of index `height`.
+fn put_block(fixture: &LmdbTestFixture, idx: u8, height: u8) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(idx);
+    header.height = height as u64;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &header.body_hash,
+        &bincode::serialize(&BlockBody::new(vec![test_utils::mock_deploy_hash(height)])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    block_hash
+}
+
+fn put_metadata(fixture: &LmdbTestFixture, deploy_idx: u8, metadata: &DeployMetadata) {
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+        &test_utils::mock_deploy_hash(deploy_idx),
+        &bincode::serialize(metadata).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn backfill_should_insert_missing_results() {
+    let fixture = new_fixture();
+    let donor_fixture = new_fixture();
+    let block_hashes: Vec<BlockHash> = (0..3).map(|idx| put_block(&fixture, idx, idx)).collect();
+    for idx in 0..2 {
+        put_block(&donor_fixture, idx, idx);
+    }
+    // The deploy of block 0 has metadata without results in the database,
+    // the one of block 1 has none. The donor has both results, and another
+    // result for the deploy of block 0 in a block the database doesn't have,
+    // which isn't copied.
+    let other_block_hash = test_utils::mock_block_header(7).0;
+    put_metadata(&fixture, 0, &DeployMetadata::default());
+    put_metadata(
+        &donor_fixture,
+        0,
+        &test_utils::mock_deploy_metadata(&[block_hashes[0], other_block_hash]),
+    );
+    put_metadata(
+        &donor_fixture,
+        1,
+        &test_utils::mock_deploy_metadata(&[block_hashes[1]]),
+    );
+
+    let summary =
+        backfill_exec_results(fixture.tmp_dir.path(), donor_fixture.tmp_dir.path()).unwrap();
+    assert_eq!(
+        summary,
+        BackfillSummary {
+            blocks: 3,
+            missing_in_donor: 1,
+            backfilled: 2,
+            still_missing: 0,
+        }
+    );
+
+    let env = StorageEnv::open(fixture.tmp_dir.path()).unwrap();
+    let txn = env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    for idx in 0..2u8 {
+        let raw_metadata = reader
+            .get(
+                DeployMetadataDatabase::db_name(),
+                test_utils::mock_deploy_hash(idx).as_ref(),
+            )
+            .unwrap()
+            .unwrap();
+        let metadata: DeployMetadata = bincode::deserialize(&raw_metadata).unwrap();
+        assert_eq!(
+            metadata,
+            test_utils::mock_deploy_metadata(&[block_hashes[idx as usize]])
+        );
+    }
+    txn.commit().unwrap();
+
+    // Running again finds nothing left to backfill.
+    let summary =
+        backfill_exec_results(fixture.tmp_dir.path(), donor_fixture.tmp_dir.path()).unwrap();
+    assert_eq!(summary.backfilled, 0);
+}
+
+#[test]
+fn backfill_with_mismatched_donor_should_fail() {
+    let fixture = new_fixture();
+    let donor_fixture = new_fixture();
+    let block_hash = put_block(&fixture, 0, 0);
+    let donor_block_hash = put_block(&donor_fixture, 9, 0);
+    put_metadata(
+        &donor_fixture,
+        0,
+        &test_utils::mock_deploy_metadata(&[block_hash]),
+    );
+
+    assert!(matches!(
+        backfill_exec_results(fixture.tmp_dir.path(), donor_fixture.tmp_dir.path()),
+        Err(Error::HashMismatch(0, hash, donor_hash))
+            if hash == block_hash && donor_hash == donor_block_hash
+    ));
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let db = *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap();
+    assert!(txn.get(db, &test_utils::mock_deploy_hash(0)).is_err());
+    txn.commit().unwrap();
+}
@@ -0,0 +1,62 @@
+use std::process;
+
+use clap::{ArgMatches, Command};
+use thiserror::Error as ThisError;
+
+pub use create::Error as CreateError;
+pub use restore::Error as RestoreError;
+
+use super::Error as SubcommandError;
+
+mod create;
+mod manifest;
+pub(crate) mod restore;
+#[cfg(test)]
+mod tests;
+
+pub const COMMAND_NAME: &str = "backup";
+
+enum DisplayOrder {
+    Create,
+    Restore,
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("create: {0}")]
+    Create(#[from] CreateError),
+    #[error("restore: {0}")]
+    Restore(#[from] RestoreError),
+}
+
+impl From<Error> for SubcommandError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Create(create_err) => SubcommandError::BackupCreate(create_err),
+            Error::Restore(restore_err) => SubcommandError::BackupRestore(restore_err),
+        }
+    }
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Incremental backups of a storage database: a full backup followed \
+            by backups of the blocks added since the previous one.",
+        )
+        .subcommand(create::command(DisplayOrder::Create as usize))
+        .subcommand(restore::command(DisplayOrder::Restore as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let (subcommand_name, matches) = matches.subcommand().unwrap_or_else(|| {
+        process::exit(1);
+    });
+
+    match subcommand_name {
+        create::COMMAND_NAME => create::run(matches).map_err(Error::Create),
+        restore::COMMAND_NAME => restore::run(matches).map_err(Error::Restore),
+        _ => unreachable!("{} should be handled above", subcommand_name),
+    }
+}
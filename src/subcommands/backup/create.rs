@@ -0,0 +1,341 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Error as IoError, ErrorKind},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use casper_node::types::BlockHash;
+use casper_types::Timestamp;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use log::{info, warn};
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::{
+        block_iter::{self, Error as BlockIterError},
+        db::{BlockHeaderDatabase, Database, StorageEnv, STORAGE_FILE_NAME},
+        output::{self, Compression},
+        progress::ProgressTracker,
+        stamp::{self, Error as StampError},
+        storage::{Error as StorageError, LmdbReader, StorageReader},
+    },
+    subcommands::{salvage::copy::Writer, stats::collect},
+};
+
+use super::manifest::{BackupManifest, Error as ManifestError, Mark};
+
+pub const COMMAND_NAME: &str = "create";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const SINCE: &str = "since";
+
+/// Databases copied in full to every backup, as their entries are
+/// overwritten in place rather than added along with blocks.
+const FULL_COPY_DATABASES: [&str; 1] = ["state_store"];
+
+/// Errors encountered when backing up a storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The block the base backup was taken at isn't in the database.
+    #[error(
+        "Block {0} of the base backup is not in the database, it may have been \
+        rolled back since"
+    )]
+    BaseNotFound(BlockHash),
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// The base backup was taken from a database of another chain.
+    #[error("The base backup is of chain {0} but the database is of chain {1}")]
+    ChainMismatch(String, String),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error reading the manifest of the base backup or writing the new one.
+    #[error("Error operating the backup manifest: {0}")]
+    Manifest(#[from] ManifestError),
+    /// Error creating the output directory.
+    #[error("Error creating the output: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the size of the source database.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+    /// Error reading the stamp of the database directory.
+    #[error("Error reading the stamp of the database directory: {0}")]
+    Stamp(#[from] StampError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Self::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Since,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Backs up the blocks of a storage database along with their \
+            deploys. Given a previous backup, only the blocks above the one it \
+            was taken at are copied, so nightly backups stay small. The trie \
+            store isn't backed up.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .required(true)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("BACKUP_DIR")
+                .help(
+                    "Path of the directory where the program will output the \
+                    backup. The directory must not exist when running this \
+                    command.",
+                ),
+        )
+        .arg(
+            Arg::new(SINCE)
+                .display_order(DisplayOrder::Since as usize)
+                .short('s')
+                .long(SINCE)
+                .takes_value(true)
+                .value_name("BASE_BACKUP_DIR")
+                .help(
+                    "Path of the directory of the previous backup, full or \
+                    incremental. Only the blocks added since it was taken are \
+                    backed up. Without it, a full backup is taken.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+/// Copies entries of the source storage database to the backup, keeping
+/// count of the entries copied from each database.
+struct DeltaWriter<'a, R: StorageReader> {
+    reader: &'a R,
+    writer: Writer<'a>,
+    copied: BTreeMap<&'static str, usize>,
+}
+
+impl<'a, R: StorageReader> DeltaWriter<'a, R> {
+    /// Copies the entry under `key` of the database `db_name`, returning
+    /// whether it was found.
+    fn copy(&mut self, db_name: &'static str, key: &[u8]) -> Result<bool, Error> {
+        if !self.reader.has_table(db_name)? {
+            return Ok(false);
+        }
+        match self.reader.get(db_name, key)? {
+            Some(raw_val) => {
+                self.writer.put(db_name, key, &raw_val)?;
+                *self.copied.entry(db_name).or_default() += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Copies every entry of the database `db_name`, if it exists.
+    fn copy_all(&mut self, db_name: &'static str) -> Result<(), Error> {
+        if !self.reader.has_table(db_name)? {
+            return Ok(());
+        }
+        let writer = &mut self.writer;
+        let copied = self.copied.entry(db_name).or_default();
+        self.reader
+            .scan(db_name, |raw_key, raw_val| -> Result<(), Error> {
+                writer.put(db_name, raw_key, raw_val)?;
+                *copied += 1;
+                Ok(())
+            })
+    }
+
+    /// Copies a block along with its body, signatures, transfers and the
+    /// deploys it executed.
+    fn copy_block(&mut self, block_hash: &BlockHash) -> Result<(), Error> {
+        let header = block_iter::read_header(self.reader, block_hash)?;
+        let records = block_iter::block_records(self.reader, block_hash, &header)?;
+        if !records.has_body {
+            warn!(
+                "Block {} has no body, its deploys aren't backed up.",
+                block_hash
+            );
+        }
+        for (db_name, key) in records.keys {
+            self.copy(db_name, key.as_ref())?;
+        }
+        Ok(())
+    }
+}
+
+/// Backs up the storage database in `db_path` to the new directory `output`.
+/// With the directory of a previous backup as `since`, only the blocks above
+/// the one it was taken at are copied, along with their deploys. The copied
+/// database is compressed with `compression` once complete.
+pub(crate) fn create_backup<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: P2,
+    since: Option<&Path>,
+    compression: Compression,
+) -> Result<BackupManifest, Error> {
+    let output = output.as_ref();
+    if output.exists() {
+        return Err(Error::Output(ErrorKind::AlreadyExists.into()));
+    }
+    let chain_name = stamp::chain_name(&db_path)?;
+    let base = match since {
+        Some(base_dir) => {
+            let base_manifest = BackupManifest::read(base_dir)?;
+            if let (Some(base_chain_name), Some(chain_name)) =
+                (base_manifest.chain_name, chain_name.as_ref())
+            {
+                if base_chain_name != *chain_name {
+                    return Err(Error::ChainMismatch(base_chain_name, chain_name.clone()));
+                }
+            }
+            base_manifest.mark
+        }
+        None => None,
+    };
+
+    let source_path = db_path.as_ref().join(STORAGE_FILE_NAME);
+    let map_size = fs::metadata(&source_path)
+        .map_err(|io_err| Error::Source(source_path.clone(), io_err))?
+        .len() as usize;
+    let source_env = StorageEnv::open(&db_path)?;
+    let txn = source_env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+
+    if let Some(base) = base.as_ref() {
+        if !reader.has_table(BlockHeaderDatabase::db_name())?
+            || reader
+                .get(BlockHeaderDatabase::db_name(), base.block_hash.as_ref())?
+                .is_none()
+        {
+            return Err(Error::BaseNotFound(base.block_hash));
+        }
+    }
+    let blocks: Vec<(u64, BlockHash)> = if reader.has_table(BlockHeaderDatabase::db_name())? {
+        let base_height = base.as_ref().map(|base| base.height);
+        block_iter::blocks_by_height(&reader)?
+            .into_iter()
+            .filter(
+                |(height, _)| !matches!(base_height, Some(base_height) if *height <= base_height),
+            )
+            .collect()
+    } else {
+        vec![]
+    };
+    let highest = blocks
+        .last()
+        .copied()
+        .or_else(|| base.as_ref().map(|base| (base.height, base.block_hash)));
+
+    fs::create_dir_all(output)?;
+    let env = StorageEnv::create(output, map_size)?;
+    let mut delta = DeltaWriter {
+        reader: &reader,
+        writer: Writer::new(&env)?,
+        copied: BTreeMap::new(),
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Backup {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    for (_height, block_hash) in blocks.iter() {
+        delta.copy_block(block_hash)?;
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+    for db_name in FULL_COPY_DATABASES {
+        delta.copy_all(db_name)?;
+    }
+    delta.writer.commit()?;
+
+    // Entries added to the source which don't belong to the backed up
+    // blocks, such as those of blocks synced below the base, are missed.
+    let entry_counts = collect::entry_counts(&reader)?;
+    for (db_name, count) in entry_counts.iter() {
+        if FULL_COPY_DATABASES.contains(db_name) {
+            continue;
+        }
+        let base_count = base
+            .as_ref()
+            .and_then(|base| base.entry_counts.get(*db_name))
+            .copied()
+            .unwrap_or_default();
+        let copied = delta.copied.get(db_name).copied().unwrap_or_default();
+        let added = count.saturating_sub(base_count);
+        if added > copied {
+            warn!(
+                "{} database has {} more entries than at the base backup, but \
+                only {} of them belong to the backed up blocks. The others \
+                aren't in the backup.",
+                db_name, added, copied
+            );
+        }
+    }
+    // The backup environment must be closed before its file is compressed.
+    drop(delta);
+    drop(env);
+    output::compress_file(&output.join(STORAGE_FILE_NAME), compression)?;
+
+    let manifest = BackupManifest {
+        chain_name,
+        base,
+        mark: highest.map(|(height, block_hash)| Mark {
+            height,
+            block_hash,
+            entry_counts: entry_counts
+                .into_iter()
+                .map(|(db_name, count)| (db_name.to_string(), count))
+                .collect(),
+        }),
+        created_at: Timestamp::now(),
+    };
+    manifest.write(output)?;
+    info!(
+        "Backed up {} blocks to {}.",
+        blocks.len(),
+        output.to_string_lossy()
+    );
+    Ok(manifest)
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let output = matches.value_of(OUTPUT).expect("should have output arg");
+    let since = matches.value_of(SINCE).map(Path::new);
+    create_backup(db_path, output, since, Compression::from_matches(matches))?;
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::Error as IoError,
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use bincode::Error as BincodeError;
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::Timestamp;
+use serde::{Deserialize, Serialize};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{BlockHeaderDatabase, Database},
+    storage::{Error as StorageError, StorageReader},
+};
+
+/// Name of the manifest file in a backup directory.
+pub const MANIFEST_FILE_NAME: &str = "backup-manifest.json";
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error operating the storage database: {0}")]
+    Database(#[from] StorageError),
+    #[error("Invalid block hash at index {0}")]
+    InvalidKey(usize),
+    #[error("Error accessing {0}: {1}")]
+    Io(PathBuf, IoError),
+    #[error("Error parsing block header at index {0}: {1}")]
+    Parsing(usize, BincodeError),
+    #[error("Error (de)serializing the manifest: {0}")]
+    Serialization(#[from] SerializationError),
+}
+
+/// Position of a storage database at the time a backup was taken.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Mark {
+    /// Height of the highest block.
+    pub height: u64,
+    /// Hash of the highest block.
+    pub block_hash: BlockHash,
+    /// Number of entries of each database.
+    pub entry_counts: BTreeMap<String, usize>,
+}
+
+/// Metadata describing the contents of a backup.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BackupManifest {
+    /// Name of the chain, taken from the stamp of the database directory or
+    /// derived from its name.
+    pub chain_name: Option<String>,
+    /// Mark of the backup this one holds the changes since, `None` for a
+    /// full backup.
+    pub base: Option<Mark>,
+    /// Mark of the database when the backup was taken, `None` if it had no
+    /// blocks.
+    pub mark: Option<Mark>,
+    /// Time at which the backup was taken.
+    pub created_at: Timestamp,
+}
+
+impl BackupManifest {
+    /// Reads the manifest of the backup in the directory `backup_dir`.
+    pub fn read<P: AsRef<Path>>(backup_dir: P) -> Result<Self, Error> {
+        let path = backup_dir.as_ref().join(MANIFEST_FILE_NAME);
+        let file = File::open(&path).map_err(|io_err| Error::Io(path, io_err))?;
+        Ok(serde_json::from_reader(file)?)
+    }
+
+    /// Writes the manifest to the backup directory `backup_dir`.
+    pub fn write<P: AsRef<Path>>(&self, backup_dir: P) -> Result<(), Error> {
+        let path = backup_dir.as_ref().join(MANIFEST_FILE_NAME);
+        fs::write(&path, serde_json::to_vec_pretty(self)?).map_err(|io_err| Error::Io(path, io_err))
+    }
+}
+
+/// Returns the height and hash of the highest block in the storage database
+/// `reader` reads from, or `None` if it has no blocks.
+pub fn highest_block<R: StorageReader>(reader: &R) -> Result<Option<(u64, BlockHash)>, Error> {
+    if !reader.has_table(BlockHeaderDatabase::db_name())? {
+        return Ok(None);
+    }
+    let mut highest: Option<(u64, BlockHash)> = None;
+    let mut idx = 0;
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            let block_hash =
+                BlockHash::new(raw_key.try_into().map_err(|_| Error::InvalidKey(idx))?);
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::Parsing(idx, bincode_err))?;
+            match highest {
+                Some((height, _)) if height >= header.height() => {}
+                _ => highest = Some((header.height(), block_hash)),
+            }
+            idx += 1;
+            Ok(())
+        },
+    )?;
+    Ok(highest)
+}
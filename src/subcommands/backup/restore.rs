@@ -0,0 +1,256 @@
+use std::{
+    fs::{self, File},
+    io::{self, Error as IoError},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use casper_node::types::BlockHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::{Error as LmdbError, Transaction};
+use log::info;
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::{
+        db::{StorageEnv, STORAGE_FILE_NAME},
+        output,
+        stamp::{Error as StampError, Stamp},
+        storage::{Error as StorageError, LmdbReader, LmdbWriter, StorageReader, StorageWriter},
+        temp::TempDir,
+        zstd_utils,
+    },
+    subcommands::stats::collect::STATS_DATABASES,
+};
+
+use super::manifest::{self, BackupManifest, Error as ManifestError};
+
+pub const COMMAND_NAME: &str = "restore";
+const DB_PATH: &str = "db-path";
+const BACKUPS: &str = "backups";
+
+/// Errors encountered when restoring backups of a storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The backup wasn't taken on top of the state of the database.
+    #[error("Backup {0} applies on top of {1}, but the database holds {2}")]
+    BaseMismatch(PathBuf, String, String),
+    /// The backup was taken from a database of another chain.
+    #[error("Backup {0} is of chain {1} but the database is of chain {2}")]
+    ChainMismatch(PathBuf, String, String),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error decompressing the database of a compressed backup.
+    #[error("Error decompressing backup {0}: {1}")]
+    Decompression(PathBuf, IoError),
+    /// Error finding the highest block of the database.
+    #[error("Error reading the highest block: {0}")]
+    HighestBlock(ManifestError),
+    /// Error reading the manifest of a backup.
+    #[error("Error reading the backup manifest: {0}")]
+    Manifest(ManifestError),
+    /// Error creating the database directory.
+    #[error("Error creating the database directory: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the size of a database.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+    /// Error operating the stamp of the database directory.
+    #[error("Error operating the stamp of the database directory: {0}")]
+    Stamp(#[from] StampError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Self::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Backups,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Applies backups to a storage database in the given order. Each \
+            backup must have been taken on top of the one applied before it, \
+            starting with a full backup when restoring to an empty directory.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file to \
+                    restore to. It is created if it doesn't exist.",
+                ),
+        )
+        .arg(
+            Arg::new(BACKUPS)
+                .display_order(DisplayOrder::Backups as usize)
+                .required(true)
+                .multiple_values(true)
+                .value_name("BACKUP_DIR")
+                .help("Paths of the directories of the backups to apply, in order."),
+        )
+}
+
+fn describe(maybe_block: Option<(u64, BlockHash)>) -> String {
+    match maybe_block {
+        Some((height, block_hash)) => format!("block {} at height {}", block_hash, height),
+        None => "no blocks".to_string(),
+    }
+}
+
+fn file_size(path: &Path) -> Result<usize, Error> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len() as usize)
+        .map_err(|io_err| Error::Source(path.to_path_buf(), io_err))
+}
+
+/// Returns the directory holding the database of the backup in `backup_dir`,
+/// which is decompressed into a temporary directory if the backup was
+/// compressed when taken.
+fn backup_db_dir(backup_dir: &Path) -> Result<(PathBuf, Option<TempDir>), Error> {
+    let compressed_path = output::compressed_path(&backup_dir.join(STORAGE_FILE_NAME));
+    if !compressed_path.exists() {
+        return Ok((backup_dir.to_path_buf(), None));
+    }
+    let decompress = || -> Result<TempDir, IoError> {
+        let temp_dir = TempDir::new()?;
+        info!(
+            "Decompressing {} into {}.",
+            compressed_path.display(),
+            temp_dir.path().display()
+        );
+        let mut decoder = zstd_utils::zstd_decode_stream(File::open(&compressed_path)?)
+            .map_err(IoError::other)?;
+        io::copy(
+            &mut decoder,
+            &mut File::create(temp_dir.path().join(STORAGE_FILE_NAME))?,
+        )?;
+        Ok(temp_dir)
+    };
+    let temp_dir =
+        decompress().map_err(|io_err| Error::Decompression(backup_dir.to_path_buf(), io_err))?;
+    Ok((temp_dir.path().to_path_buf(), Some(temp_dir)))
+}
+
+/// Applies the backup in `backup_dir` to the storage database in `db_path`,
+/// in a single transaction.
+fn apply_backup(db_path: &Path, backup_dir: &Path) -> Result<(), Error> {
+    let manifest = BackupManifest::read(backup_dir).map_err(Error::Manifest)?;
+    let maybe_stamp = Stamp::read(db_path)?;
+    if let (Some(backup_chain_name), Some(db_chain_name)) = (
+        manifest.chain_name.as_ref(),
+        maybe_stamp
+            .as_ref()
+            .and_then(|stamp| stamp.chain_name.as_ref()),
+    ) {
+        if backup_chain_name != db_chain_name {
+            return Err(Error::ChainMismatch(
+                backup_dir.to_path_buf(),
+                backup_chain_name.clone(),
+                db_chain_name.clone(),
+            ));
+        }
+    }
+
+    // Leave room for the entries of the backup to take more space once
+    // merged than in the backup itself.
+    let storage_path = db_path.join(STORAGE_FILE_NAME);
+    let db_size = if storage_path.exists() {
+        file_size(&storage_path)?
+    } else {
+        0
+    };
+    let (backup_db_dir, _temp_dir) = backup_db_dir(backup_dir)?;
+    let backup_size = file_size(&backup_db_dir.join(STORAGE_FILE_NAME))?;
+    let env = StorageEnv::create(db_path, db_size + 2 * backup_size)?;
+
+    let current = {
+        let txn = env.begin_ro_txn()?;
+        manifest::highest_block(&LmdbReader::new(&txn)).map_err(Error::HighestBlock)?
+    };
+    let mark = manifest
+        .mark
+        .as_ref()
+        .map(|mark| (mark.height, mark.block_hash));
+    let base = manifest
+        .base
+        .as_ref()
+        .map(|base| (base.height, base.block_hash));
+    if current != base {
+        return Err(Error::BaseMismatch(
+            backup_dir.to_path_buf(),
+            describe(base),
+            describe(current),
+        ));
+    }
+
+    let backup_env = StorageEnv::open(&backup_db_dir)?;
+    let backup_txn = backup_env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&backup_txn);
+    // The backup is applied in a single transaction, so a restore which
+    // fails half way leaves the database as it was and can be run again.
+    let mut txn = env.begin_rw_txn()?;
+    let mut writer = LmdbWriter::new(&mut txn);
+    let mut copied = 0;
+    for db_name in STATS_DATABASES {
+        if !reader.has_table(db_name)? {
+            continue;
+        }
+        reader.scan(db_name, |raw_key, raw_val| -> Result<(), Error> {
+            writer.put(db_name, raw_key, raw_val)?;
+            copied += 1;
+            Ok(())
+        })?;
+    }
+    txn.commit()?;
+
+    if maybe_stamp.is_none() {
+        Stamp {
+            chain_name: manifest.chain_name.clone(),
+            ..Default::default()
+        }
+        .write(db_path)?;
+    }
+    info!(
+        "Applied {} entries of backup {}, the database now holds {}.",
+        copied,
+        backup_dir.to_string_lossy(),
+        describe(mark)
+    );
+    Ok(())
+}
+
+/// Applies the backups in `backup_dirs` in order to the storage database in
+/// `db_path`, creating it if it doesn't exist.
+pub(crate) fn restore<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    backup_dirs: &[P2],
+) -> Result<(), Error> {
+    let db_path = db_path.as_ref();
+    fs::create_dir_all(db_path)?;
+    for backup_dir in backup_dirs {
+        apply_backup(db_path, backup_dir.as_ref())?;
+    }
+    Ok(())
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let backup_dirs: Vec<&str> = matches
+        .values_of(BACKUPS)
+        .expect("should have backups arg")
+        .collect();
+    restore(db_path, &backup_dirs)
+}
@@ -0,0 +1,222 @@
+use casper_node::types::BlockHash;
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    create::{self, Error as CreateError},
+    manifest::BackupManifest,
+    restore::{self, Error as RestoreError},
+};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase, StateStoreDatabase,
+            StorageEnv, STORAGE_FILE_NAME,
+        },
+        output::{self, Compression},
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+const STATE_STORE_KEY: &[u8] = b"key";
+
+fn new_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            StateStoreDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+/// Stores the block at `height` with a body executing a single deploy, and
+/// overwrites the state store entry with its height.
+fn put_block(fixture: &LmdbTestFixture, height: u8) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    let deploy_hash = test_utils::mock_deploy_hash(height);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &header.body_hash,
+        &bincode::serialize(&BlockBody::new(vec![deploy_hash])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+        &deploy_hash,
+        &[height],
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(StateStoreDatabase::db_name())).unwrap(),
+        &STATE_STORE_KEY,
+        &[height],
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    block_hash
+}
+
+fn entry_count<R: StorageReader>(reader: &R, db_name: &str) -> usize {
+    reader.entry_count(db_name).unwrap()
+}
+
+#[test]
+fn backups_should_restore_in_order() {
+    let fixture = new_fixture();
+    put_block(&fixture, 0);
+    let block_hash_1 = put_block(&fixture, 1);
+    let backups_dir = tempfile::tempdir().unwrap();
+    let full_dir = backups_dir.path().join("full");
+    let full_manifest =
+        create::create_backup(fixture.tmp_dir.path(), &full_dir, None, Compression::None).unwrap();
+    assert_eq!(full_manifest.base, None);
+    let full_mark = full_manifest.mark.clone().unwrap();
+    assert_eq!(full_mark.height, 1);
+    assert_eq!(full_mark.block_hash, block_hash_1);
+    assert_eq!(full_mark.entry_counts[BlockHeaderDatabase::db_name()], 2);
+    assert_eq!(BackupManifest::read(&full_dir).unwrap(), full_manifest);
+
+    let block_hash_2 = put_block(&fixture, 2);
+    let delta_dir = backups_dir.path().join("delta");
+    let delta_manifest = create::create_backup(
+        fixture.tmp_dir.path(),
+        &delta_dir,
+        Some(&full_dir),
+        Compression::None,
+    )
+    .unwrap();
+    assert_eq!(delta_manifest.base, Some(full_mark));
+    assert_eq!(delta_manifest.mark.as_ref().unwrap().height, 2);
+
+    // The delta only holds the new block, its deploy and the state store.
+    {
+        let delta_env = StorageEnv::open(&delta_dir).unwrap();
+        let txn = delta_env.begin_ro_txn().unwrap();
+        let reader = LmdbReader::new(&txn);
+        assert_eq!(entry_count(&reader, BlockHeaderDatabase::db_name()), 1);
+        assert_eq!(entry_count(&reader, BlockBodyDatabase::db_name()), 1);
+        assert_eq!(entry_count(&reader, DeployDatabase::db_name()), 1);
+        assert!(reader
+            .get(BlockHeaderDatabase::db_name(), block_hash_2.as_ref())
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            reader
+                .get(StateStoreDatabase::db_name(), STATE_STORE_KEY)
+                .unwrap()
+                .unwrap()
+                .as_ref(),
+            &[2]
+        );
+    }
+
+    // The delta doesn't apply to an empty database.
+    let restore_dir = tempfile::tempdir().unwrap();
+    let restore_path = restore_dir.path().join("restored");
+    assert!(matches!(
+        restore::restore(&restore_path, &[&delta_dir]),
+        Err(RestoreError::BaseMismatch(..))
+    ));
+
+    restore::restore(&restore_path, &[&full_dir, &delta_dir]).unwrap();
+    let restored_env = StorageEnv::open(&restore_path).unwrap();
+    let txn = restored_env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    assert_eq!(entry_count(&reader, BlockHeaderDatabase::db_name()), 3);
+    assert_eq!(entry_count(&reader, BlockBodyDatabase::db_name()), 3);
+    assert_eq!(entry_count(&reader, DeployDatabase::db_name()), 3);
+    assert_eq!(
+        reader
+            .get(StateStoreDatabase::db_name(), STATE_STORE_KEY)
+            .unwrap()
+            .unwrap()
+            .as_ref(),
+        &[2]
+    );
+    txn.commit().unwrap();
+
+    // Applying the delta twice isn't allowed.
+    assert!(matches!(
+        restore::restore(&restore_path, &[&delta_dir]),
+        Err(RestoreError::BaseMismatch(..))
+    ));
+}
+
+#[test]
+fn backup_since_rolled_back_block_should_fail() {
+    let fixture = new_fixture();
+    put_block(&fixture, 0);
+    let block_hash_1 = put_block(&fixture, 1);
+    let backups_dir = tempfile::tempdir().unwrap();
+    let full_dir = backups_dir.path().join("full");
+    create::create_backup(fixture.tmp_dir.path(), &full_dir, None, Compression::None).unwrap();
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.del(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash_1,
+        None,
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let delta_dir = backups_dir.path().join("delta");
+    assert!(matches!(
+        create::create_backup(fixture.tmp_dir.path(), &delta_dir, Some(&full_dir), Compression::None),
+        Err(CreateError::BaseNotFound(block_hash)) if block_hash == block_hash_1
+    ));
+    assert!(!delta_dir.exists());
+}
+
+#[test]
+fn compressed_backups_should_restore() {
+    let fixture = new_fixture();
+    put_block(&fixture, 0);
+    let backups_dir = tempfile::tempdir().unwrap();
+    let full_dir = backups_dir.path().join("full");
+    create::create_backup(
+        fixture.tmp_dir.path(),
+        &full_dir,
+        None,
+        Compression::Zstd(3),
+    )
+    .unwrap();
+    assert!(!full_dir.join(STORAGE_FILE_NAME).exists());
+    assert!(output::compressed_path(&full_dir.join(STORAGE_FILE_NAME)).is_file());
+
+    // A compressed backup can be the base of an uncompressed one.
+    put_block(&fixture, 1);
+    let delta_dir = backups_dir.path().join("delta");
+    create::create_backup(
+        fixture.tmp_dir.path(),
+        &delta_dir,
+        Some(&full_dir),
+        Compression::None,
+    )
+    .unwrap();
+
+    let restore_dir = tempfile::tempdir().unwrap();
+    restore::restore(restore_dir.path(), &[&full_dir, &delta_dir]).unwrap();
+    let restored_env = StorageEnv::open(restore_dir.path()).unwrap();
+    let txn = restored_env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    assert_eq!(entry_count(&reader, BlockHeaderDatabase::db_name()), 2);
+    assert_eq!(entry_count(&reader, DeployDatabase::db_name()), 2);
+    txn.commit().unwrap();
+}
@@ -0,0 +1,124 @@
+mod measure;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{self, EnvTuning},
+    output::{self, Compression},
+};
+
+pub const COMMAND_NAME: &str = "bench";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const SAMPLES: &str = "samples";
+const DEFAULT_SAMPLES: &str = "10000";
+
+/// Errors encountered when benchmarking the storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    Samples,
+    EnvTuning,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Measures the sequential scan throughput and the random lookup \
+            rate of each database in a storage instance.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the results in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(SAMPLES)
+                .display_order(DisplayOrder::Samples as usize)
+                .short('n')
+                .long(SAMPLES)
+                .takes_value(true)
+                .value_name("COUNT")
+                .default_value(DEFAULT_SAMPLES)
+                .validator(|value| value.parse::<usize>().map(|_| ()))
+                .help(
+                    "Number of keys looked up at random in each database. The \
+                    keys are sampled during the sequential scan, so lookups on \
+                    databases smaller than the available memory are mostly \
+                    served from the page cache.",
+                ),
+        )
+        .args(db::tuning_args(
+            DisplayOrder::EnvTuning as usize,
+            EnvTuning::RANDOM_ACCESS,
+        ))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let compression = Compression::from_matches(matches);
+    let samples = matches
+        .value_of(SAMPLES)
+        .expect("should have a default")
+        .parse()
+        .expect("should be validated");
+    let tuning = EnvTuning::from_matches(matches);
+    measure::bench(path, output, overwrite, compression, samples, tuning)
+}
@@ -0,0 +1,164 @@
+use std::{
+    collections::BTreeMap,
+    hint,
+    io::Write,
+    path::Path,
+    result::Result,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use lmdb::{Cursor, Database, Environment, Error as LmdbError, Transaction};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        db::{EnvTuning, StorageEnv},
+        output::{Compression, OutputWriter},
+    },
+    subcommands::stats::STATS_DATABASES,
+};
+
+use super::Error;
+
+const BYTES_PER_MB: f64 = 1_000_000.0;
+
+/// Results of benchmarking a single database.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct DatabaseBench {
+    /// Number of entries read by the sequential scan.
+    pub(crate) entries: usize,
+    /// Total size of the keys and values read by the sequential scan.
+    pub(crate) bytes: u64,
+    pub(crate) scan_seconds: f64,
+    pub(crate) scan_mb_per_second: f64,
+    /// Number of random lookups performed.
+    pub(crate) lookups: usize,
+    pub(crate) lookup_seconds: f64,
+    pub(crate) lookups_per_second: f64,
+}
+
+/// Benchmark results for all databases in a storage instance.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Bench {
+    pub(crate) databases: BTreeMap<String, DatabaseBench>,
+}
+
+/// Minimal xorshift generator, good enough to pick and shuffle sample keys.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos() as u64)
+            .unwrap_or_default();
+        // The state must never be zero.
+        Self(seed | 1)
+    }
+
+    /// Returns a pseudo-random number in `0..bound`.
+    fn next_below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+fn rate(amount: f64, elapsed: Duration) -> f64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        amount / seconds
+    } else {
+        0.0
+    }
+}
+
+/// Scans `db` sequentially, then looks up to `samples` of its keys, picked
+/// uniformly during the scan, in random order.
+fn bench_database<T: Transaction>(
+    txn: &T,
+    db: Database,
+    samples: usize,
+    rng: &mut XorShift,
+) -> Result<DatabaseBench, LmdbError> {
+    let mut entries = 0;
+    let mut bytes = 0u64;
+    let mut sampled_keys: Vec<Vec<u8>> = Vec::with_capacity(samples);
+    let scan_start = Instant::now();
+    for (key, value) in txn.open_ro_cursor(db)?.iter() {
+        entries += 1;
+        bytes += (key.len() + value.len()) as u64;
+        // Reservoir sampling keeps every key with the same probability.
+        if sampled_keys.len() < samples {
+            sampled_keys.push(key.to_vec());
+        } else if samples > 0 {
+            let slot = rng.next_below(entries);
+            if slot < samples {
+                sampled_keys[slot] = key.to_vec();
+            }
+        }
+    }
+    let scan_elapsed = scan_start.elapsed();
+
+    // Shuffle the keys so that they're not looked up in database order.
+    for idx in (1..sampled_keys.len()).rev() {
+        sampled_keys.swap(idx, rng.next_below(idx + 1));
+    }
+    let lookup_start = Instant::now();
+    for key in sampled_keys.iter() {
+        hint::black_box(txn.get(db, key)?);
+    }
+    let lookup_elapsed = lookup_start.elapsed();
+
+    Ok(DatabaseBench {
+        entries,
+        bytes,
+        scan_seconds: scan_elapsed.as_secs_f64(),
+        scan_mb_per_second: rate(bytes as f64 / BYTES_PER_MB, scan_elapsed),
+        lookups: sampled_keys.len(),
+        lookup_seconds: lookup_elapsed.as_secs_f64(),
+        lookups_per_second: rate(sampled_keys.len() as f64, lookup_elapsed),
+    })
+}
+
+fn bench_env(env: &Environment, samples: usize) -> Result<Bench, Error> {
+    let txn = env.begin_ro_txn()?;
+    let mut rng = XorShift::new();
+    let mut databases = BTreeMap::new();
+    for db_name in STATS_DATABASES {
+        let db = match unsafe { txn.open_db(Some(db_name)) } {
+            Ok(db) => db,
+            Err(LmdbError::NotFound) => continue,
+            Err(lmdb_err) => return Err(lmdb_err.into()),
+        };
+        info!("Benchmarking {} database.", db_name);
+        let db_bench = bench_database(&txn, db, samples, &mut rng)?;
+        info!(
+            "{}: scanned {} entries at {:.2} MB/s, {:.0} lookups/s.",
+            db_name, db_bench.entries, db_bench.scan_mb_per_second, db_bench.lookups_per_second
+        );
+        databases.insert(db_name.to_string(), db_bench);
+    }
+    Ok(Bench { databases })
+}
+
+pub fn bench<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: Option<P2>,
+    overwrite: bool,
+    compression: Compression,
+    samples: usize,
+    tuning: EnvTuning,
+) -> Result<(), Error> {
+    let env = StorageEnv::open_with_tuning(&db_path, tuning)?;
+    // Validate the output file early so that, in case this fails
+    // we don't unnecessarily read the whole database.
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    let bench = bench_env(&env, samples)?;
+    serde_json::to_writer_pretty(&mut out_writer, &bench)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
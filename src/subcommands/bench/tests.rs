@@ -0,0 +1,83 @@
+use std::fs;
+
+use lmdb::{Transaction, WriteFlags};
+
+use super::measure::{self, Bench};
+use crate::{
+    common::{
+        db::{BlockBodyDatabase, BlockHeaderDatabase, Database, EnvTuning, STORAGE_FILE_NAME},
+        output::Compression,
+    },
+    test_utils::LmdbTestFixture,
+};
+
+#[test]
+fn bench_should_scan_and_look_up_each_database() {
+    const ENTRY_COUNT: u32 = 50;
+
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("bench.json");
+
+    let env = &fixture.env;
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        for idx in 0..ENTRY_COUNT {
+            txn.put(
+                *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+                &idx.to_be_bytes(),
+                &[0u8; 16],
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    };
+
+    measure::bench(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        10,
+        EnvTuning::RANDOM_ACCESS,
+    )
+    .unwrap();
+    let bench: Bench = serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    let header_bench = bench.databases.get(BlockHeaderDatabase::db_name()).unwrap();
+    assert_eq!(header_bench.entries, ENTRY_COUNT as usize);
+    assert_eq!(header_bench.bytes, ENTRY_COUNT as u64 * 20);
+    assert_eq!(header_bench.lookups, 10);
+    // Empty databases are reported, but there is nothing to look up.
+    let body_bench = bench.databases.get(BlockBodyDatabase::db_name()).unwrap();
+    assert_eq!(body_bench.entries, 0);
+    assert_eq!(body_bench.lookups, 0);
+    // Databases which don't exist aren't reported.
+    assert!(!bench.databases.contains_key("state_store"));
+
+    // The output file exists already.
+    assert!(measure::bench(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        10,
+        EnvTuning::RANDOM_ACCESS,
+    )
+    .is_err());
+    // There can't be more lookups than entries.
+    measure::bench(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        1000,
+        EnvTuning::SEQUENTIAL,
+    )
+    .unwrap();
+    let bench: Bench = serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    let header_bench = bench.databases.get(BlockHeaderDatabase::db_name()).unwrap();
+    assert_eq!(header_bench.lookups, ENTRY_COUNT as usize);
+}
@@ -0,0 +1,138 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use casper_hashing::Digest;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::search_state::Error as SearchStateError;
+use crate::common::output::{self, Compression, OutputWriter};
+use report::Format;
+
+pub const COMMAND_NAME: &str = "bids";
+const DB_PATH: &str = "db-path";
+const FORMAT: &str = "format";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT: &str = "state-root";
+
+/// Errors encountered when reporting on the bids in global state.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the bids and unbonding purses from global state.
+    #[error("Error reading global state: {0}")]
+    Search(#[from] SearchStateError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    StateRoot,
+    Format,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Decodes the auction bids, delegations and unbonding purses in the \
+            global state under a state root, and outputs the stake of every \
+            validator and of its delegators, the amounts being unbonded from \
+            it and the totals over all validators.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STATE_ROOT)
+                .display_order(DisplayOrder::StateRoot as usize)
+                .required(true)
+                .short('r')
+                .long(STATE_ROOT)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help("Hash of the state root to read the bids from, in hex."),
+        )
+        .arg(
+            Arg::new(FORMAT)
+                .display_order(DisplayOrder::Format as usize)
+                .short('f')
+                .long(FORMAT)
+                .takes_value(true)
+                .value_name("json|csv")
+                .possible_values(["json", "csv"])
+                .default_value("json")
+                .help(
+                    "Format of the output. The CSV output only has a row per \
+                    validator, without the totals.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let state_root_hash = Digest::from_hex(
+        matches
+            .value_of(STATE_ROOT)
+            .expect("should have state-root arg"),
+    )
+    .expect("should be validated");
+    let format = match matches.value_of(FORMAT) {
+        Some("csv") => Format::Csv,
+        _ => Format::Json,
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::bids_report(path, state_root_hash)?;
+    report::write_report(&report, format, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,184 @@
+use std::{cmp::Reverse, collections::BTreeMap, io::Write, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_types::{
+    system::auction::DelegationRate, AsymmetricType, KeyTag, PublicKey, StoredValue, U512,
+};
+use serde::Serialize;
+
+use crate::subcommands::search_state::search::{self, Filter};
+
+use super::Error;
+
+/// Header of the CSV output, in the order `ValidatorStake::write_csv`
+/// writes the fields.
+const CSV_HEADER: &str = "public_key,inactive,delegation_rate,staked_amount,delegated_amount,\
+    total_stake,delegators,unbonding_amount,unbonding_purses";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Csv,
+}
+
+/// The stake of a validator and of its delegators.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ValidatorStake {
+    pub(crate) public_key: PublicKey,
+    pub(crate) inactive: bool,
+    pub(crate) delegation_rate: DelegationRate,
+    /// Stake of the validator itself.
+    pub(crate) staked_amount: U512,
+    /// Stake of the delegators of the validator.
+    pub(crate) delegated_amount: U512,
+    pub(crate) total_stake: U512,
+    pub(crate) delegators: usize,
+    /// Amount being unbonded from the validator, by itself or its
+    /// delegators.
+    pub(crate) unbonding_amount: U512,
+    pub(crate) unbonding_purses: usize,
+}
+
+impl ValidatorStake {
+    fn new(public_key: PublicKey) -> Self {
+        Self {
+            public_key,
+            inactive: false,
+            delegation_rate: 0,
+            staked_amount: U512::zero(),
+            delegated_amount: U512::zero(),
+            total_stake: U512::zero(),
+            delegators: 0,
+            unbonding_amount: U512::zero(),
+            unbonding_purses: 0,
+        }
+    }
+
+    fn write_csv<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), Error> {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            self.public_key.to_hex(),
+            self.inactive,
+            self.delegation_rate,
+            self.staked_amount,
+            self.delegated_amount,
+            self.total_stake,
+            self.delegators,
+            self.unbonding_amount,
+            self.unbonding_purses
+        )?;
+        Ok(())
+    }
+}
+
+/// The stakes of the validators under a state root, along with their
+/// totals.
+#[derive(Debug, Serialize)]
+pub(crate) struct BidsReport {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) validators: usize,
+    pub(crate) active_validators: usize,
+    pub(crate) delegators: usize,
+    pub(crate) total_stake: U512,
+    pub(crate) total_delegated: U512,
+    pub(crate) total_unbonding: U512,
+    pub(crate) unbonding_purses: usize,
+    /// The validators, the ones with the largest total stake first.
+    pub(crate) stakes: Vec<ValidatorStake>,
+}
+
+/// Reads the bids and unbonding purses in the global state under
+/// `state_root_hash` in the trie store in `db_path`, and sums the stakes of
+/// each validator.
+pub(crate) fn bids_report<P: AsRef<Path>>(
+    db_path: P,
+    state_root_hash: Digest,
+) -> Result<BidsReport, Error> {
+    let filter = Filter {
+        key_tags: vec![KeyTag::Bid, KeyTag::Withdraw],
+        ..Default::default()
+    };
+    let mut stakes: BTreeMap<PublicKey, ValidatorStake> = BTreeMap::new();
+    search::search_state(db_path, state_root_hash, &filter, None, |found| {
+        match found.value {
+            StoredValue::Bid(bid) => {
+                let public_key = bid.validator_public_key();
+                let stake = stakes
+                    .entry(public_key.clone())
+                    .or_insert_with(|| ValidatorStake::new(public_key.clone()));
+                stake.inactive = bid.inactive();
+                stake.delegation_rate = *bid.delegation_rate();
+                stake.staked_amount = *bid.staked_amount();
+                stake.delegators = bid.delegators().len();
+                stake.delegated_amount = bid
+                    .delegators()
+                    .values()
+                    .map(|delegator| *delegator.staked_amount())
+                    .fold(U512::zero(), |total, amount| total + amount);
+            }
+            StoredValue::Withdraw(unbonding_purses) => {
+                for unbonding_purse in unbonding_purses {
+                    let public_key = unbonding_purse.validator_public_key();
+                    let stake = stakes
+                        .entry(public_key.clone())
+                        .or_insert_with(|| ValidatorStake::new(public_key.clone()));
+                    stake.unbonding_amount += *unbonding_purse.amount();
+                    stake.unbonding_purses += 1;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    let mut report = BidsReport {
+        state_root_hash,
+        validators: stakes.len(),
+        active_validators: 0,
+        delegators: 0,
+        total_stake: U512::zero(),
+        total_delegated: U512::zero(),
+        total_unbonding: U512::zero(),
+        unbonding_purses: 0,
+        stakes: Vec::with_capacity(stakes.len()),
+    };
+    for mut stake in stakes.into_values() {
+        stake.total_stake = stake.staked_amount + stake.delegated_amount;
+        if !stake.inactive {
+            report.active_validators += 1;
+        }
+        report.delegators += stake.delegators;
+        report.total_stake += stake.total_stake;
+        report.total_delegated += stake.delegated_amount;
+        report.total_unbonding += stake.unbonding_amount;
+        report.unbonding_purses += stake.unbonding_purses;
+        report.stakes.push(stake);
+    }
+    report
+        .stakes
+        .sort_by_key(|stake| Reverse(stake.total_stake));
+    Ok(report)
+}
+
+/// Writes `report` to `writer` in `format`. The CSV output lists the
+/// validators only.
+pub(crate) fn write_report<W: Write + ?Sized>(
+    report: &BidsReport,
+    format: Format,
+    writer: &mut W,
+) -> Result<(), Error> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *writer, report)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => {
+            writeln!(writer, "{}", CSV_HEADER)?;
+            for stake in report.stakes.iter() {
+                stake.write_csv(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId, storage::global_state::StateProvider,
+};
+use casper_hashing::Digest;
+use casper_types::{
+    system::auction::{Bid, Delegator, UnbondingPurse},
+    AccessRights, AsymmetricType, EraId, Key, StoredValue, URef, U512,
+};
+use tempfile::TempDir;
+
+use super::report::{bids_report, write_report, Format};
+use crate::{
+    subcommands::trie_compact::{create_execution_engine, DEFAULT_MAX_DB_SIZE},
+    test_utils::KEYS,
+};
+
+/// Creates a trie store with an active validator of `KEYS[0]` with
+/// delegations from `KEYS[2]` and `KEYS[3]`, an inactive validator of
+/// `KEYS[1]` and unbonding purses of `KEYS[1]` and `KEYS[2]`.
+fn new_global_state() -> (TempDir, Digest) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), DEFAULT_MAX_DB_SIZE.parse().unwrap(), true)
+            .unwrap();
+
+    let bonding_purse = URef::new([1; 32], AccessRights::READ_ADD_WRITE);
+    let mut bid = Bid::unlocked(KEYS[0].clone(), bonding_purse, U512::from(100), 10);
+    for (idx, amount) in [(2, 20), (3, 30)] {
+        bid.delegators_mut().insert(
+            KEYS[idx].clone(),
+            Delegator::unlocked(
+                KEYS[idx].clone(),
+                U512::from(amount),
+                bonding_purse,
+                KEYS[0].clone(),
+            ),
+        );
+    }
+    let mut inactive_bid = Bid::unlocked(KEYS[1].clone(), bonding_purse, U512::from(40), 5);
+    inactive_bid.deactivate();
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::Bid(KEYS[0].to_account_hash()),
+        StoredValue::Bid(Box::new(bid)),
+    );
+    stored_values.insert(
+        Key::Bid(KEYS[1].to_account_hash()),
+        StoredValue::Bid(Box::new(inactive_bid)),
+    );
+    stored_values.insert(
+        Key::Withdraw(KEYS[1].to_account_hash()),
+        StoredValue::Withdraw(vec![UnbondingPurse::new(
+            bonding_purse,
+            KEYS[1].clone(),
+            KEYS[1].clone(),
+            EraId::new(3),
+            U512::from(60),
+        )]),
+    );
+    stored_values.insert(
+        Key::Withdraw(KEYS[2].to_account_hash()),
+        StoredValue::Withdraw(vec![
+            UnbondingPurse::new(
+                bonding_purse,
+                KEYS[0].clone(),
+                KEYS[2].clone(),
+                EraId::new(3),
+                U512::from(5),
+            ),
+            UnbondingPurse::new(
+                bonding_purse,
+                KEYS[0].clone(),
+                KEYS[2].clone(),
+                EraId::new(4),
+                U512::from(7),
+            ),
+        ]),
+    );
+
+    let global_state = engine_state.get_state();
+    let state_root_hash = global_state
+        .put_stored_values(
+            CorrelationId::new(),
+            global_state.empty_root(),
+            stored_values,
+        )
+        .unwrap();
+    engine_state.flush_environment().unwrap();
+    (tmp_dir, state_root_hash)
+}
+
+#[test]
+fn bids_report_should_sum_stakes_and_unbonding() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+
+    let report = bids_report(tmp_dir.path(), state_root_hash).unwrap();
+    assert_eq!(report.state_root_hash, state_root_hash);
+    assert_eq!(report.validators, 2);
+    assert_eq!(report.active_validators, 1);
+    assert_eq!(report.delegators, 2);
+    assert_eq!(report.total_stake, U512::from(190));
+    assert_eq!(report.total_delegated, U512::from(50));
+    assert_eq!(report.total_unbonding, U512::from(72));
+    assert_eq!(report.unbonding_purses, 3);
+
+    assert_eq!(report.stakes.len(), 2);
+    let stake = &report.stakes[0];
+    assert_eq!(stake.public_key, KEYS[0]);
+    assert!(!stake.inactive);
+    assert_eq!(stake.delegation_rate, 10);
+    assert_eq!(stake.staked_amount, U512::from(100));
+    assert_eq!(stake.delegated_amount, U512::from(50));
+    assert_eq!(stake.total_stake, U512::from(150));
+    assert_eq!(stake.delegators, 2);
+    assert_eq!(stake.unbonding_amount, U512::from(12));
+    assert_eq!(stake.unbonding_purses, 2);
+
+    let stake = &report.stakes[1];
+    assert_eq!(stake.public_key, KEYS[1]);
+    assert!(stake.inactive);
+    assert_eq!(stake.total_stake, U512::from(40));
+    assert_eq!(stake.delegators, 0);
+    assert_eq!(stake.unbonding_amount, U512::from(60));
+    assert_eq!(stake.unbonding_purses, 1);
+}
+
+#[test]
+fn write_report_should_output_json_and_csv() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    let report = bids_report(tmp_dir.path(), state_root_hash).unwrap();
+
+    let mut json = vec![];
+    write_report(&report, Format::Json, &mut json).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&json).unwrap();
+    assert_eq!(json["validators"], 2);
+    assert_eq!(json["total_stake"], "190");
+    assert_eq!(json["stakes"][0]["public_key"], KEYS[0].to_hex());
+
+    let mut csv = vec![];
+    write_report(&report, Format::Csv, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("public_key,"));
+    assert_eq!(
+        lines[1],
+        format!("{},false,10,100,50,150,2,12,2", KEYS[0].to_hex())
+    );
+    assert_eq!(
+        lines[2],
+        format!("{},true,5,40,0,40,0,60,1", KEYS[1].to_hex())
+    );
+}
@@ -0,0 +1,181 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    output::{self, Compression, OutputWriter},
+};
+use report::BlockLimits;
+
+pub const COMMAND_NAME: &str = "block-composition";
+const BUCKET_SIZE: &str = "bucket-size";
+const DB_PATH: &str = "db-path";
+const MAX_DEPLOYS: &str = "max-deploys";
+const MAX_TRANSFERS: &str = "max-transfers";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when building the block composition report.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    BucketSize,
+    MaxDeploys,
+    MaxTransfers,
+}
+
+fn positive_count(value: &str) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(0) => Err("value must be greater than 0".to_string()),
+        Ok(_) => Ok(()),
+        Err(parse_err) => Err(parse_err.to_string()),
+    }
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs the number of native transfers and of other deploys per \
+            block in a storage database, aggregated over buckets of block \
+            heights, in JSON format. If the block limits of the chain are \
+            given, also outputs how close to them the blocks were.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(BUCKET_SIZE)
+                .display_order(DisplayOrder::BucketSize as usize)
+                .short('b')
+                .long(BUCKET_SIZE)
+                .takes_value(true)
+                .value_name("BLOCKS")
+                .default_value("10000")
+                .validator(positive_count)
+                .help("Number of consecutive heights aggregated in each bucket."),
+        )
+        .arg(
+            Arg::new(MAX_DEPLOYS)
+                .display_order(DisplayOrder::MaxDeploys as usize)
+                .long(MAX_DEPLOYS)
+                .takes_value(true)
+                .value_name("COUNT")
+                .validator(positive_count)
+                .help(
+                    "Maximum number of deploys other than native transfers in \
+                    a block, i.e. `deploys.block_max_deploy_count` in the \
+                    chainspec.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_TRANSFERS)
+                .display_order(DisplayOrder::MaxTransfers as usize)
+                .long(MAX_TRANSFERS)
+                .takes_value(true)
+                .value_name("COUNT")
+                .validator(positive_count)
+                .help(
+                    "Maximum number of native transfers in a block, i.e. \
+                    `deploys.block_max_transfer_count` in the chainspec.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let parse_count = |name| {
+        matches
+            .value_of(name)
+            .map(|value| value.parse().expect("should be validated"))
+    };
+    let bucket_size = parse_count(BUCKET_SIZE).expect("should have bucket-size arg");
+    let limits = BlockLimits {
+        max_deploys: parse_count(MAX_DEPLOYS),
+        max_transfers: parse_count(MAX_TRANSFERS),
+    };
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::block_composition(db_dir.path(), bucket_size, limits)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use std::{collections::BTreeMap, path::Path, result::Result};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{block_iter::BlockIterator, db::StorageEnv, storage::LmdbReader};
+
+use super::Error;
+
+/// Limits on the contents of a block, as set in the chainspec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct BlockLimits {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_deploys: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) max_transfers: Option<u64>,
+}
+
+/// Statistics of the number of deploys or transfers per block.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct CountStats {
+    pub(crate) total: u64,
+    pub(crate) average: f64,
+    pub(crate) max: u64,
+    /// Average share of the limit used per block, between 0 and 1.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) utilization: Option<f64>,
+    /// Number of blocks which reached the limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) full_blocks: Option<usize>,
+}
+
+impl CountStats {
+    fn add(&mut self, count: u64, limit: Option<u64>) {
+        self.total += count;
+        self.max = self.max.max(count);
+        if let Some(limit) = limit {
+            *self.full_blocks.get_or_insert(0) += usize::from(count >= limit);
+        }
+    }
+
+    fn finish(&mut self, blocks: usize, limit: Option<u64>) {
+        self.average = self.total as f64 / blocks as f64;
+        self.utilization = limit.map(|limit| self.average / limit as f64);
+    }
+}
+
+/// Composition of the blocks with heights in `first_height..=last_height`.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct HeightBucket {
+    pub(crate) first_height: u64,
+    pub(crate) last_height: u64,
+    /// Number of blocks in the bucket whose body is present.
+    pub(crate) blocks: usize,
+    /// Deploys other than native transfers.
+    pub(crate) deploys: CountStats,
+    pub(crate) transfers: CountStats,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct CompositionReport {
+    pub(crate) bucket_size: u64,
+    pub(crate) limits: BlockLimits,
+    pub(crate) missing_bodies: usize,
+    pub(crate) buckets: Vec<HeightBucket>,
+}
+
+/// Counts the deploys and native transfers of each block in the storage
+/// database at `db_path`, aggregated over buckets of `bucket_size` heights.
+pub(crate) fn block_composition<P: AsRef<Path>>(
+    db_path: P,
+    bucket_size: u64,
+    limits: BlockLimits,
+) -> Result<CompositionReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+
+    let mut missing_bodies = 0;
+    let mut buckets: BTreeMap<u64, HeightBucket> = BTreeMap::new();
+    for block in BlockIterator::new(&reader)? {
+        let (block_hash, header, maybe_body) = block?;
+        let body = match maybe_body {
+            Some(body) => body,
+            None => {
+                warn!("Missing body for block {}.", block_hash);
+                missing_bodies += 1;
+                continue;
+            }
+        };
+        let bucket_idx = header.height() / bucket_size;
+        let bucket = buckets.entry(bucket_idx).or_insert_with(|| HeightBucket {
+            first_height: bucket_idx * bucket_size,
+            last_height: bucket_idx * bucket_size + bucket_size - 1,
+            ..Default::default()
+        });
+        bucket.blocks += 1;
+        bucket
+            .deploys
+            .add(body.deploy_hashes.len() as u64, limits.max_deploys);
+        bucket
+            .transfers
+            .add(body.transfer_hashes.len() as u64, limits.max_transfers);
+    }
+
+    let buckets: Vec<HeightBucket> = buckets
+        .into_values()
+        .map(|mut bucket| {
+            bucket.deploys.finish(bucket.blocks, limits.max_deploys);
+            bucket.transfers.finish(bucket.blocks, limits.max_transfers);
+            bucket
+        })
+        .collect();
+    info!(
+        "Aggregated {} blocks into {} buckets.",
+        buckets.iter().map(|bucket| bucket.blocks).sum::<usize>(),
+        buckets.len()
+    );
+    Ok(CompositionReport {
+        bucket_size,
+        limits,
+        missing_bodies,
+        buckets,
+    })
+}
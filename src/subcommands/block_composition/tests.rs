@@ -0,0 +1,94 @@
+use casper_node::types::BlockHeader;
+use lmdb::{Transaction, WriteFlags};
+
+use super::report::{block_composition, BlockLimits, CountStats};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database, DeployDatabase,
+        DeployMetadataDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{mock_block_header, mock_deploy_hash, LmdbTestFixture},
+};
+
+#[test]
+fn block_composition_should_aggregate_buckets() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+
+    // Blocks at heights 0, 1 and 2 with (deploys, transfers) of (0, 2),
+    // (2, 0) and (1, 1), and a block at height 3 without a body.
+    let compositions = [(0u8, 2u8), (2, 0), (1, 1), (0, 0)];
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, (deploy_count, transfer_count)) in compositions.iter().enumerate() {
+        let (_, mut mock_header) = mock_block_header(idx as u8);
+        mock_header.height = idx as u64;
+        let header: BlockHeader =
+            bincode::deserialize(&bincode::serialize(&mock_header).unwrap()).unwrap();
+        txn.put(
+            header_db,
+            &header.hash(),
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        if idx == 3 {
+            continue;
+        }
+        let mut body = BlockBody::new((0..*deploy_count).map(mock_deploy_hash).collect());
+        body.transfer_hashes = (0..*transfer_count)
+            .map(|transfer_idx| mock_deploy_hash(100 + transfer_idx))
+            .collect();
+        txn.put(
+            body_db,
+            header.body_hash(),
+            &bincode::serialize(&body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let report = block_composition(fixture.tmp_dir.path(), 2, BlockLimits::default()).unwrap();
+    assert_eq!(report.missing_bodies, 1);
+    assert_eq!(report.buckets.len(), 2);
+    assert_eq!(report.buckets[0].first_height, 0);
+    assert_eq!(report.buckets[0].last_height, 1);
+    assert_eq!(report.buckets[0].blocks, 2);
+    assert_eq!(
+        report.buckets[0].deploys,
+        CountStats {
+            total: 2,
+            average: 1.0,
+            max: 2,
+            utilization: None,
+            full_blocks: None,
+        }
+    );
+    assert_eq!(report.buckets[1].first_height, 2);
+    assert_eq!(report.buckets[1].blocks, 1);
+
+    let limits = BlockLimits {
+        max_deploys: Some(2),
+        max_transfers: Some(4),
+    };
+    let report = block_composition(fixture.tmp_dir.path(), 10, limits).unwrap();
+    assert_eq!(report.buckets.len(), 1);
+    let bucket = &report.buckets[0];
+    assert_eq!(bucket.blocks, 3);
+    assert_eq!(bucket.deploys.utilization, Some(0.5));
+    assert_eq!(bucket.deploys.full_blocks, Some(1));
+    assert_eq!(bucket.transfers.total, 3);
+    assert_eq!(bucket.transfers.utilization, Some(0.25));
+    assert_eq!(bucket.transfers.full_blocks, Some(0));
+}
@@ -0,0 +1,173 @@
+mod lookup;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use casper_hashing::Digest;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    merkle_body::Error as MerkleBodyError,
+    output::{self, Compression},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "body-info";
+const BODY_HASH: &str = "body-hash";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const RESOLVE_HEADER: &str = "resolve-header";
+
+/// Errors encountered when looking up a block body.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Parsing error on entry in the block body database.
+    #[error("Error parsing block body with hash {0}: {1}")]
+    BodyParsing(Digest, BincodeError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on entry at index in the block header database.
+    #[error("Error parsing block header at index {0}: {1}")]
+    HeaderParsing(usize, BincodeError),
+    /// Key of an entry in the block header database is not a valid hash.
+    #[error("Invalid key at index {0} in block header DB")]
+    InvalidKey(usize),
+    /// Error reading a merkle block body.
+    #[error("Error reading merkle block body: {0}")]
+    MerkleBody(#[from] MerkleBodyError),
+    /// The body is in neither of the block body databases.
+    #[error("Block body with hash {0} not present in the database")]
+    NotFound(Digest),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    BodyHash,
+    ResolveHeader,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs the proposer and the deploy and transfer hashes of a \
+            block body, stored either in the legacy or in the merkle format, \
+            in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(BODY_HASH)
+                .display_order(DisplayOrder::BodyHash as usize)
+                .required(true)
+                .short('b')
+                .long(BODY_HASH)
+                .takes_value(true)
+                .value_name("BODY_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help("Hash of the block body, in hex format."),
+        )
+        .arg(
+            Arg::new(RESOLVE_HEADER)
+                .display_order(DisplayOrder::ResolveHeader as usize)
+                .required(false)
+                .short('r')
+                .long(RESOLVE_HEADER)
+                .takes_value(false)
+                .help(
+                    "Also report the hash and height of the blocks whose header \
+                    references the body. This reads every block header, so it \
+                    can take a while.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the body information \
+                    in JSON format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let path = db_dir.path();
+    let body_hash = matches
+        .value_of(BODY_HASH)
+        .map(|body_hash_str| Digest::from_hex(body_hash_str).expect("should be validated"))
+        .expect("should have body-hash arg");
+    let resolve_header = matches.is_present(RESOLVE_HEADER);
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let compression = Compression::from_matches(matches);
+    lookup::body_info(
+        path,
+        body_hash,
+        resolve_header,
+        output,
+        overwrite,
+        compression,
+    )
+}
@@ -0,0 +1,144 @@
+use std::{io::Write, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader, DeployHash};
+use casper_types::PublicKey;
+use lmdb::Transaction;
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        db::{BlockBodyDatabase, BlockHeaderDatabase, Database as _, StorageEnv},
+        merkle_body,
+        output::{Compression, OutputWriter},
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+/// Format under which a block body is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum BodyFormat {
+    /// The whole body is a single entry in `block_body`.
+    Legacy,
+    /// The body is a linked list in `block_body_merkle` whose nodes point to
+    /// entries in `deploy_hashes`, `transfer_hashes` and `proposers`.
+    Merkle,
+}
+
+/// A block referencing the body through its header.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct BlockRef {
+    pub(crate) block_hash: BlockHash,
+    pub(crate) height: u64,
+}
+
+/// Contents of a block body.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct BodyInfo {
+    pub(crate) body_hash: Digest,
+    pub(crate) format: BodyFormat,
+    pub(crate) proposer: PublicKey,
+    pub(crate) deploy_hashes: Vec<DeployHash>,
+    pub(crate) transfer_hashes: Vec<DeployHash>,
+    /// Blocks whose header references the body, only filled in when asked to
+    /// resolve the headers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) referenced_by: Option<Vec<BlockRef>>,
+}
+
+/// Looks the body up in `block_body` first, then walks its merkle linked
+/// list in `block_body_merkle`.
+fn read_body<R: StorageReader>(reader: &R, body_hash: &Digest) -> Result<BodyInfo, Error> {
+    let maybe_raw_body = if reader.has_table(BlockBodyDatabase::db_name())? {
+        reader.get(BlockBodyDatabase::db_name(), body_hash.as_ref())?
+    } else {
+        None
+    };
+    let (format, body) = match maybe_raw_body {
+        Some(raw_body) => {
+            let body: BlockBody = bincode::deserialize(&raw_body)
+                .map_err(|bincode_err| Error::BodyParsing(*body_hash, bincode_err))?;
+            (BodyFormat::Legacy, body)
+        }
+        None => {
+            let merkle_body = merkle_body::read_merkle_body(reader, body_hash)?
+                .ok_or(Error::NotFound(*body_hash))?;
+            (BodyFormat::Merkle, merkle_body.body)
+        }
+    };
+    Ok(BodyInfo {
+        body_hash: *body_hash,
+        format,
+        proposer: body.proposer().clone(),
+        deploy_hashes: body.deploy_hashes,
+        transfer_hashes: body.transfer_hashes,
+        referenced_by: None,
+    })
+}
+
+/// Scans the block header database for headers referencing `body_hash`.
+fn find_referencing_blocks<R: StorageReader>(
+    reader: &R,
+    body_hash: &Digest,
+) -> Result<Vec<BlockRef>, Error> {
+    let mut referenced_by = vec![];
+    let mut idx = 0;
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            let entry_idx = idx;
+            idx += 1;
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::HeaderParsing(entry_idx, bincode_err))?;
+            if header.body_hash() != body_hash {
+                return Ok(());
+            }
+            let block_hash: BlockHash = Digest::try_from(raw_key)
+                .map_err(|_| Error::InvalidKey(entry_idx))?
+                .into();
+            referenced_by.push(BlockRef {
+                block_hash,
+                height: header.height(),
+            });
+            Ok(())
+        },
+    )?;
+    Ok(referenced_by)
+}
+
+pub(crate) fn read_body_info<R: StorageReader>(
+    reader: &R,
+    body_hash: &Digest,
+    resolve_header: bool,
+) -> Result<BodyInfo, Error> {
+    let mut body_info = read_body(reader, body_hash)?;
+    if resolve_header {
+        info!("Scanning {} database.", BlockHeaderDatabase::db_name());
+        body_info.referenced_by = Some(find_referencing_blocks(reader, body_hash)?);
+    }
+    Ok(body_info)
+}
+
+pub fn body_info<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    body_hash: Digest,
+    resolve_header: bool,
+    output: Option<P2>,
+    overwrite: bool,
+    compression: Compression,
+) -> Result<(), Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    let txn = env.begin_ro_txn()?;
+    let body_info = read_body_info(&LmdbReader::new(&txn), &body_hash, resolve_header)?;
+    txn.commit()?;
+    serde_json::to_writer_pretty(&mut out_writer, &body_info)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
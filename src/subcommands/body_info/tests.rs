@@ -0,0 +1,132 @@
+use casper_hashing::Digest;
+use casper_types::PublicKey;
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    lookup::{read_body_info, BlockRef, BodyFormat},
+    Error,
+};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+            DeployHashesDatabase, ProposerDatabase, TransferHashesDatabase, STORAGE_FILE_NAME,
+        },
+        merkle_body::Error as MerkleBodyError,
+        storage::LmdbReader,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{mock_block_header, mock_deploy_hash, mock_merkle_body, LmdbTestFixture, KEYS},
+};
+
+#[test]
+fn body_info_should_read_legacy_and_merkle_bodies() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            ProposerDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let (legacy_block_hash, mut legacy_header) = mock_block_header(0);
+    legacy_header.height = 10;
+    let legacy_body = BlockBody::new(vec![mock_deploy_hash(0)]);
+
+    let merkle_body = BlockBody::from_parts(
+        KEYS[0].clone(),
+        vec![mock_deploy_hash(1), mock_deploy_hash(2)],
+        vec![mock_deploy_hash(3)],
+    );
+    let (merkle_body_hash, merkle_entries) = mock_merkle_body(&merkle_body);
+    // Hold the proposer part back to check that missing parts are reported.
+    let (proposer_db_name, proposer_hash, raw_proposer) = merkle_entries
+        .iter()
+        .find(|(db_name, _, _)| *db_name == ProposerDatabase::db_name())
+        .cloned()
+        .unwrap();
+
+    let env = &fixture.env;
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &legacy_block_hash,
+            &bincode::serialize(&legacy_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &legacy_header.body_hash,
+            &bincode::serialize(&legacy_body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        for (db_name, key, value) in merkle_entries.iter() {
+            if *key == proposer_hash {
+                continue;
+            }
+            txn.put(
+                *fixture.db(Some(db_name)).unwrap(),
+                key,
+                value,
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    };
+
+    let txn = env.begin_ro_txn().unwrap();
+    let legacy_info =
+        read_body_info(&LmdbReader::new(&txn), &legacy_header.body_hash, true).unwrap();
+    assert_eq!(legacy_info.format, BodyFormat::Legacy);
+    assert_eq!(legacy_info.proposer, PublicKey::System);
+    assert_eq!(legacy_info.deploy_hashes, vec![mock_deploy_hash(0)]);
+    assert!(legacy_info.transfer_hashes.is_empty());
+    assert_eq!(
+        legacy_info.referenced_by,
+        Some(vec![BlockRef {
+            block_hash: legacy_block_hash,
+            height: 10
+        }])
+    );
+
+    // The proposer part isn't stored yet.
+    assert!(matches!(
+        read_body_info(&LmdbReader::new(&txn), &merkle_body_hash, false),
+        Err(Error::MerkleBody(MerkleBodyError::MissingPart(db_name, part_hash)))
+            if db_name == proposer_db_name && part_hash == proposer_hash
+    ));
+    txn.commit().unwrap();
+
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        txn.put(
+            *fixture.db(Some(proposer_db_name)).unwrap(),
+            &proposer_hash,
+            &raw_proposer,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    };
+
+    let txn = env.begin_ro_txn().unwrap();
+    let merkle_info = read_body_info(&LmdbReader::new(&txn), &merkle_body_hash, true).unwrap();
+    assert_eq!(merkle_info.format, BodyFormat::Merkle);
+    assert_eq!(&merkle_info.proposer, merkle_body.proposer());
+    assert_eq!(merkle_info.deploy_hashes, merkle_body.deploy_hashes);
+    assert_eq!(merkle_info.transfer_hashes, merkle_body.transfer_hashes);
+    // No header references the merkle body.
+    assert_eq!(merkle_info.referenced_by, Some(vec![]));
+
+    let unknown_hash: Digest = [7u8; Digest::LENGTH].into();
+    assert!(matches!(
+        read_body_info(&LmdbReader::new(&txn), &unknown_hash, false),
+        Err(Error::NotFound(body_hash)) if body_hash == unknown_hash
+    ));
+    txn.commit().unwrap();
+}
@@ -1,35 +1,143 @@
-use std::path::{Path, PathBuf};
+mod checkpoint;
+mod snapshot;
+#[cfg(feature = "rocksdb")]
+mod table;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
 
 use clap::{Arg, ArgMatches, Command};
-use lmdb::Error as LmdbError;
+use lmdb::{Environment, Error as LmdbError};
+use serde_json::Error as JsonError;
 use thiserror::Error as ThisError;
 
-use crate::common::db::{
-    db_env, BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, BlockMetadataDatabase,
-    Database, DeployDatabase, DeployHashesDatabase, DeployMetadataDatabase, Error as DbError,
-    FinalizedApprovalsDatabase, ProposerDatabase, StateStoreDatabase, TransferDatabase,
-    TransferHashesDatabase, STORAGE_FILE_NAME,
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    db::{
+        self, BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase,
+        BlockMetadataDatabase, Database, DeployDatabase, DeployHashesDatabase,
+        DeployMetadataDatabase, DeserializationError, ElementCallback, EnvTuning, Error as DbError,
+        FinalizedApprovalsDatabase, ProposerDatabase, StateStoreDatabase, TransferDatabase,
+        TransferHashesDatabase,
+    },
+    storage::{self, Backend, Error as StorageError, Storage},
+    throttle, value_guard,
 };
 
+use checkpoint::CheckState;
+use snapshot::Snapshot;
+
 pub const COMMAND_NAME: &str = "check";
+const CHECKPOINT: &str = "checkpoint";
+const CHECKPOINT_INTERVAL: &str = "checkpoint-interval";
 const DB_PATH: &str = "db-path";
+const MAX_ERRORS: &str = "max-errors";
 const NO_FAILFAST: &str = "no-failfast";
+const RESUME: &str = "resume";
+const SNAPSHOT: &str = "snapshot";
+const SNAPSHOT_DIR: &str = "snapshot-dir";
 const SPECIFIC: &str = "specific";
 const START_AT: &str = "start-at";
 
+/// Signature of `Database::check_db`.
+type CheckFn = fn(&Environment, bool, usize, Option<usize>) -> Result<(), DbError>;
+/// Signature of `Database::parse_elements_after`.
+type ParseFn = fn(&Environment, Option<&[u8]>, &mut ElementCallback) -> Result<(), DbError>;
+/// Signature of `Database::parse_entry`.
+pub(crate) type EntryParseFn = fn(&[u8], &[u8]) -> Result<(), DeserializationError>;
+
+/// A database which can be checked, with the functions checking and parsing
+/// its elements.
+struct CheckedDatabase {
+    name: &'static str,
+    check: CheckFn,
+    parse: ParseFn,
+    parse_entry: EntryParseFn,
+}
+
+macro_rules! checked_database {
+    ($name:literal, $db:ty) => {
+        CheckedDatabase {
+            name: $name,
+            check: <$db>::check_db,
+            parse: <$db>::parse_elements_after,
+            parse_entry: <$db>::parse_entry,
+        }
+    };
+}
+
+/// Databases checked when no specific one is requested, in checking order.
+static DATABASES: [CheckedDatabase; 12] = [
+    checked_database!("block_body", BlockBodyDatabase),
+    checked_database!("block_body_merkle", BlockBodyMerkleDatabase),
+    checked_database!("block_header", BlockHeaderDatabase),
+    checked_database!("block_metadata", BlockMetadataDatabase),
+    checked_database!("deploy_hashes", DeployHashesDatabase),
+    checked_database!("deploy_metadata", DeployMetadataDatabase),
+    checked_database!("deploys", DeployDatabase),
+    checked_database!("finalized_approvals", FinalizedApprovalsDatabase),
+    checked_database!("proposers", ProposerDatabase),
+    checked_database!("state_store", StateStoreDatabase),
+    checked_database!("transfer", TransferDatabase),
+    checked_database!("transfer_hashes", TransferHashesDatabase),
+];
+
+/// How the check reacts to invalid entries.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct ErrorHandling {
+    /// Stop at the first invalid entry.
+    pub(crate) failfast: bool,
+    /// Otherwise, stop after this many invalid entries, if set.
+    pub(crate) max_errors: Option<usize>,
+}
+
 enum DisplayOrder {
     NoFailfast,
+    MaxErrors,
     DbPath,
     Specific,
     StartAt,
+    Checkpoint,
+    CheckpointInterval,
+    Resume,
+    Snapshot,
+    SnapshotDir,
+    Backend,
+    IoNice,
+    Throttle,
+    MaxValueSize,
+    EnvTuning,
 }
 
 #[derive(ThisError, Debug)]
 pub enum Error {
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    #[error("Checkpoints are only supported with the lmdb backend, not {0}")]
+    CheckpointBackend(Backend),
     #[error("Error checking the database: {0}")]
     Database(#[from] DbError),
-    #[error("Error initializing lmdb environment at {0}: {1}")]
-    Path(PathBuf, LmdbError),
+    #[error("Error opening the storage database at {0}: {1}")]
+    Path(PathBuf, StorageError),
+    #[error("Error copying {0} to a snapshot: {1}")]
+    Snapshot(PathBuf, LmdbError),
+    #[error("Snapshots are only supported with the lmdb backend, not {0}")]
+    SnapshotBackend(Backend),
+    #[error("Error creating a snapshot directory in {0}: {1}")]
+    SnapshotDir(PathBuf, IoError),
+    #[error("Error accessing check state file {0}: {1}")]
+    State(PathBuf, IoError),
+    #[error("Error parsing check state file {0}: {1}")]
+    StateParsing(PathBuf, JsonError),
+    #[error("\"--{START_AT}\" needs a single database, but {0} were selected")]
+    StartAtMultipleDbs(usize),
     #[error("Unknown database {0}")]
     UnknownDb(String),
 }
@@ -51,6 +159,23 @@ pub fn command(display_order: usize) -> Command<'static> {
                     "Program will not terminate when failing to parse an element in the database.",
                 ),
         )
+        .arg(
+            Arg::new(MAX_ERRORS)
+                .display_order(DisplayOrder::MaxErrors as usize)
+                .long(MAX_ERRORS)
+                .takes_value(true)
+                .value_name("N")
+                .requires(NO_FAILFAST)
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(0) => Err("maximum must be greater than 0".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Stop after finding N invalid entries. Requires \"--no-failfast\" \
+                    parameter to be set. Invalid entries are logged as they are found.",
+                ),
+        )
         .arg(
             Arg::new(DB_PATH)
                 .display_order(DisplayOrder::DbPath as usize)
@@ -59,7 +184,10 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .long(DB_PATH)
                 .takes_value(true)
                 .value_name("DB_PATH")
-                .help("Path of the directory with the `storage.lmdb` file."),
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
         )
         .arg(
             Arg::new(SPECIFIC)
@@ -67,8 +195,12 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .short('s')
                 .long(SPECIFIC)
                 .takes_value(true)
-                .value_name("DB_NAME")
-                .help("Parse a specific database."),
+                .value_name("DB_NAMES")
+                .help(
+                    "Parse only the given databases, as a comma separated list of \
+                    names or glob patterns, such as \"block_*,deploy_metadata\". \
+                    Quote patterns to keep the shell from expanding them.",
+                ),
         )
         .arg(
             Arg::new(START_AT)
@@ -81,14 +213,97 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .default_value("0")
                 .help(
                     "Entry index from which parsing will start. Requires \"--specific\" parameter \
-                    to be set.",
+                    to be set to a single database.",
                 ),
         )
+        .arg(
+            Arg::new(CHECKPOINT)
+                .display_order(DisplayOrder::Checkpoint as usize)
+                .long(CHECKPOINT)
+                .takes_value(true)
+                .value_name("STATE_FILE")
+                .conflicts_with_all(&[RESUME, START_AT])
+                .help(
+                    "Periodically save the progress of the check and the errors found \
+                    so far to the given file, so that the run can be continued with \
+                    \"--resume\" if interrupted.",
+                ),
+        )
+        .arg(
+            Arg::new(CHECKPOINT_INTERVAL)
+                .display_order(DisplayOrder::CheckpointInterval as usize)
+                .long(CHECKPOINT_INTERVAL)
+                .takes_value(true)
+                .value_name("ENTRIES")
+                .default_value("100000")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(0) => Err("interval must be greater than 0".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help("Number of entries checked between two saves of the state file."),
+        )
+        .arg(
+            Arg::new(RESUME)
+                .display_order(DisplayOrder::Resume as usize)
+                .long(RESUME)
+                .takes_value(true)
+                .value_name("STATE_FILE")
+                .conflicts_with_all(&[SPECIFIC, START_AT])
+                .help(
+                    "Continue the check saved in the given state file, skipping the \
+                    databases and entries already checked. Progress keeps being \
+                    saved to the same file.",
+                ),
+        )
+        .arg(
+            Arg::new(SNAPSHOT)
+                .display_order(DisplayOrder::Snapshot as usize)
+                .long(SNAPSHOT)
+                .takes_value(false)
+                .help(
+                    "Check a compacted copy of the storage file taken in a single \
+                    read transaction instead of the live file, so that a node \
+                    writing to it meanwhile can't abort a long check. The copy \
+                    is removed once the check is done.",
+                ),
+        )
+        .arg(
+            Arg::new(SNAPSHOT_DIR)
+                .display_order(DisplayOrder::SnapshotDir as usize)
+                .long(SNAPSHOT_DIR)
+                .takes_value(true)
+                .value_name("DIR")
+                .requires(SNAPSHOT)
+                .help(
+                    "Directory in which the snapshot is copied. Defaults to the \
+                    directory given by `--tmpdir`, or to the system's temporary \
+                    directory, which needs room for the used size of the \
+                    storage file.",
+                ),
+        )
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+        .arg(throttle::io_nice_arg(DisplayOrder::IoNice as usize))
+        .arg(throttle::throttle_arg(DisplayOrder::Throttle as usize))
+        .arg(value_guard::max_value_arg(
+            DisplayOrder::MaxValueSize as usize,
+        ))
+        .args(db::tuning_args(
+            DisplayOrder::EnvTuning as usize,
+            EnvTuning::SEQUENTIAL,
+        ))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
-    let path = matches.value_of(DB_PATH).unwrap();
-    let failfast = !matches.is_present(NO_FAILFAST);
+    throttle::init_from_matches(matches);
+    value_guard::init_from_matches(matches);
+    let db_dir = DbDir::open(matches.value_of(DB_PATH).unwrap(), &[Include::Storage])?;
+    let error_handling = ErrorHandling {
+        failfast: !matches.is_present(NO_FAILFAST),
+        max_errors: matches
+            .value_of(MAX_ERRORS)
+            .map(|value| value.parse().expect("should be validated")),
+    };
     let specific = matches.value_of(SPECIFIC);
     let start_at: usize = matches
         .value_of(START_AT)
@@ -96,51 +311,181 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
         .parse()
         .unwrap_or_else(|_| panic!("Value of \"--{START_AT}\" must be an integer."));
 
-    check_db(path, failfast, specific, start_at)
+    let backend = Backend::from_matches(matches);
+    let tuning = EnvTuning::from_matches(matches);
+    let interval: usize = matches
+        .value_of(CHECKPOINT_INTERVAL)
+        .expect("should have a default")
+        .parse()
+        .expect("should be validated");
+
+    if backend != Backend::Lmdb && (matches.is_present(RESUME) || matches.is_present(CHECKPOINT)) {
+        return Err(Error::CheckpointBackend(backend));
+    }
+    if backend != Backend::Lmdb && matches.is_present(SNAPSHOT) {
+        return Err(Error::SnapshotBackend(backend));
+    }
+    let maybe_snapshot = if matches.is_present(SNAPSHOT) {
+        Some(Snapshot::create(
+            db_dir.path(),
+            matches.value_of(SNAPSHOT_DIR).map(Path::new),
+        )?)
+    } else {
+        None
+    };
+    let path = maybe_snapshot
+        .as_ref()
+        .map_or(db_dir.path(), Snapshot::path);
+    if let Some(state_path) = matches.value_of(RESUME) {
+        let state = CheckState::load(state_path)?;
+        checkpoint::check_db_with_checkpoint(
+            path,
+            error_handling,
+            tuning,
+            state_path,
+            interval,
+            state,
+        )
+    } else if let Some(state_path) = matches.value_of(CHECKPOINT) {
+        let state = CheckState::new(specific);
+        checkpoint::check_db_with_checkpoint(
+            path,
+            error_handling,
+            tuning,
+            state_path,
+            interval,
+            state,
+        )
+    } else {
+        check_db(path, error_handling, specific, start_at, backend, tuning)
+    }
+}
+
+/// Whether `name` matches the glob `pattern`, in which `*` matches any
+/// sequence of characters and `?` any single character.
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // Position in the pattern after the last `*` seen, and the position in
+    // the name it was tried at.
+    let mut maybe_star: Option<(usize, usize)> = None;
+    let (mut pattern_idx, mut name_idx) = (0, 0);
+    while name_idx < name.len() {
+        match pattern.get(pattern_idx) {
+            Some('*') => {
+                pattern_idx += 1;
+                maybe_star = Some((pattern_idx, name_idx));
+            }
+            Some(&pattern_char) if pattern_char == '?' || pattern_char == name[name_idx] => {
+                pattern_idx += 1;
+                name_idx += 1;
+            }
+            _ => match maybe_star {
+                // Let the last `*` match one more character.
+                Some((star_pattern_idx, star_name_idx)) => {
+                    pattern_idx = star_pattern_idx;
+                    name_idx = star_name_idx + 1;
+                    maybe_star = Some((star_pattern_idx, name_idx));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[pattern_idx..]
+        .iter()
+        .all(|pattern_char| *pattern_char == '*')
+}
+
+/// Returns the databases matching the comma separated names or glob patterns
+/// in `specific`, in checking order. Fails if any of them matches no
+/// database.
+fn select_databases(specific: &str) -> Result<Vec<&'static CheckedDatabase>, Error> {
+    let patterns: Vec<&str> = specific
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .collect();
+    if let Some(unmatched) = patterns.iter().find(|pattern| {
+        !DATABASES
+            .iter()
+            .any(|database| glob_matches(pattern, database.name))
+    }) {
+        return Err(Error::UnknownDb(unmatched.to_string()));
+    }
+    Ok(DATABASES
+        .iter()
+        .filter(|database| {
+            patterns
+                .iter()
+                .any(|pattern| glob_matches(pattern, database.name))
+        })
+        .collect())
+}
+
+/// Returns the function parsing a single entry of the database `db_name`.
+pub(crate) fn entry_parser(db_name: &str) -> Option<EntryParseFn> {
+    DATABASES
+        .iter()
+        .find(|database| database.name == db_name)
+        .map(|database| database.parse_entry)
 }
 
 fn check_db<P: AsRef<Path>>(
     path: P,
-    failfast: bool,
+    error_handling: ErrorHandling,
     specific: Option<&str>,
     start_at: usize,
+    backend: Backend,
+    tuning: EnvTuning,
 ) -> Result<(), Error> {
-    let storage_path = path.as_ref().join(STORAGE_FILE_NAME);
-    let env = db_env(storage_path)
-        .map_err(|lmdb_err| Error::Path(path.as_ref().to_path_buf(), lmdb_err))?;
-    if let Some(db_name) = specific {
-        match db_name.trim() {
-            "block_body" => BlockBodyDatabase::check_db(&env, failfast, start_at)?,
-            "block_body_merkle" => BlockBodyMerkleDatabase::check_db(&env, failfast, start_at)?,
-            "block_header" => BlockHeaderDatabase::check_db(&env, failfast, start_at)?,
-            "block_metadata" => BlockMetadataDatabase::check_db(&env, failfast, start_at)?,
-            "deploy_hashes" => DeployHashesDatabase::check_db(&env, failfast, start_at)?,
-            "deploy_metadata" => DeployMetadataDatabase::check_db(&env, failfast, start_at)?,
-            "deploys" => DeployDatabase::check_db(&env, failfast, start_at)?,
-            "finalized_approvals" => {
-                FinalizedApprovalsDatabase::check_db(&env, failfast, start_at)?
+    let databases = match specific {
+        Some(specific) => {
+            let databases = select_databases(specific)?;
+            if start_at != 0 && databases.len() > 1 {
+                return Err(Error::StartAtMultipleDbs(databases.len()));
             }
-            "proposers" => ProposerDatabase::check_db(&env, failfast, start_at)?,
-            "state_store" => StateStoreDatabase::check_db(&env, failfast, start_at)?,
-            "transfer" => TransferDatabase::check_db(&env, failfast, start_at)?,
-            "transfer_hashes" => TransferHashesDatabase::check_db(&env, failfast, start_at)?,
-            _ => return Err(Error::UnknownDb(db_name.to_string())),
+            databases
+        }
+        None => {
+            // Sanity check for `start_at`, already validated in arg parser.
+            assert_eq!(start_at, 0);
+            DATABASES.iter().collect()
         }
-    } else {
-        // Sanity check for `start_at`, already validated in arg parser.
-        assert_eq!(start_at, 0);
-        BlockBodyDatabase::check_db(&env, failfast, start_at)?;
-        BlockBodyMerkleDatabase::check_db(&env, failfast, start_at)?;
-        BlockHeaderDatabase::check_db(&env, failfast, start_at)?;
-        BlockMetadataDatabase::check_db(&env, failfast, start_at)?;
-        DeployHashesDatabase::check_db(&env, failfast, start_at)?;
-        DeployMetadataDatabase::check_db(&env, failfast, start_at)?;
-        DeployDatabase::check_db(&env, failfast, start_at)?;
-        FinalizedApprovalsDatabase::check_db(&env, failfast, start_at)?;
-        ProposerDatabase::check_db(&env, failfast, start_at)?;
-        StateStoreDatabase::check_db(&env, failfast, start_at)?;
-        TransferDatabase::check_db(&env, failfast, start_at)?;
-        TransferHashesDatabase::check_db(&env, failfast, start_at)?;
     };
+    let storage = Storage::open(&path, backend, tuning)
+        .map_err(|storage_err| Error::Path(path.as_ref().to_path_buf(), storage_err))?;
+    // Keep going through all databases when not failing fast, so that the
+    // total number of errors is reported.
+    let mut error_count = 0;
+    for database in databases {
+        let remaining_errors = error_handling
+            .max_errors
+            .map(|max_errors| max_errors - error_count);
+        let result = match &storage {
+            Storage::Lmdb(env) => {
+                (database.check)(env, error_handling.failfast, start_at, remaining_errors)
+            }
+            #[cfg(feature = "rocksdb")]
+            Storage::RocksDb(_) => table::check_table(
+                &storage.view().map_err(DbError::from)?,
+                database.name,
+                database.parse_entry,
+                error_handling.failfast,
+                start_at,
+                remaining_errors,
+            ),
+        };
+        match result {
+            Ok(()) => {}
+            Err(DbError::Accumulated(db_error_count)) => error_count += db_error_count,
+            Err(DbError::MaxErrors(db_error_count)) => {
+                return Err(DbError::MaxErrors(error_count + db_error_count).into())
+            }
+            Err(db_error) => return Err(db_error.into()),
+        }
+    }
+    if error_count > 0 {
+        return Err(DbError::Accumulated(error_count).into());
+    }
     Ok(())
 }
@@ -0,0 +1,191 @@
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use lmdb::Environment;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::common::db::{self, EnvTuning, Error as DbError, STORAGE_FILE_NAME};
+
+use super::{select_databases, CheckedDatabase, Error, ErrorHandling, ParseFn, DATABASES};
+
+/// Progress of the check of a single database.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DbProgress {
+    pub(crate) db_name: String,
+    /// Number of entries checked so far.
+    pub(crate) entries_checked: usize,
+    /// Key of the last entry checked.
+    pub(crate) last_key: Vec<u8>,
+}
+
+/// State of a `check` run, persisted so that it can be resumed.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct CheckState {
+    /// Names or glob patterns of the databases checked, if the run was
+    /// started with `--specific`.
+    pub(crate) specific: Option<String>,
+    /// Databases which were fully checked.
+    pub(crate) completed: Vec<String>,
+    /// Database being checked when the state was saved.
+    pub(crate) current: Option<DbProgress>,
+    /// Number of invalid entries found so far.
+    pub(crate) error_count: usize,
+}
+
+impl CheckState {
+    pub(crate) fn new(specific: Option<&str>) -> Self {
+        Self {
+            specific: specific.map(|db_name| db_name.trim().to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub(crate) fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|io_err| Error::State(path.to_path_buf(), io_err))?;
+        serde_json::from_reader(BufReader::new(file))
+            .map_err(|json_err| Error::StateParsing(path.to_path_buf(), json_err))
+    }
+
+    /// Writes the state to a temporary file next to `path` and moves it in
+    /// place, so that an interrupted save doesn't corrupt the previous state.
+    pub(crate) fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let bytes = serde_json::to_vec_pretty(self)
+            .map_err(|json_err| Error::StateParsing(path.to_path_buf(), json_err))?;
+        fs::write(&tmp_path, bytes)
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|io_err| Error::State(path.to_path_buf(), io_err))
+    }
+
+    /// Returns the databases this run covers.
+    fn databases(&self) -> Result<Vec<&'static CheckedDatabase>, Error> {
+        match &self.specific {
+            Some(specific) => select_databases(specific),
+            None => Ok(DATABASES.iter().collect()),
+        }
+    }
+}
+
+/// Checks the databases covered by `state`, skipping the ones already
+/// completed and continuing the current one after its last checked entry.
+/// The state is saved to `state_path` every `interval` entries and after
+/// each database.
+///
+/// Invalid entries are logged as they are found and only their count is kept
+/// in the state, which also counts towards the maximum number of errors.
+pub(super) fn check_db_with_checkpoint<P1: AsRef<Path>, P2: AsRef<Path>>(
+    path: P1,
+    error_handling: ErrorHandling,
+    tuning: EnvTuning,
+    state_path: P2,
+    interval: usize,
+    mut state: CheckState,
+) -> Result<(), Error> {
+    let state_path = state_path.as_ref();
+    // Validate all names before starting.
+    let databases = state.databases()?;
+    let storage_path = path.as_ref().join(STORAGE_FILE_NAME);
+    let env = db::db_env_with_tuning(storage_path, tuning)
+        .map_err(|lmdb_err| Error::Path(path.as_ref().to_path_buf(), lmdb_err.into()))?;
+
+    for database in databases {
+        let db_name = database.name.to_string();
+        let parse = database.parse;
+        if state.completed.contains(&db_name) {
+            info!("Skipping {} database, already checked.", db_name);
+            continue;
+        }
+        let mut progress = match state.current.take() {
+            Some(progress) if progress.db_name == db_name => {
+                info!(
+                    "Resuming check of {} database after {} entries.",
+                    db_name, progress.entries_checked
+                );
+                progress
+            }
+            _ => {
+                info!("Checking {} database.", db_name);
+                DbProgress {
+                    db_name: db_name.clone(),
+                    ..Default::default()
+                }
+            }
+        };
+        let result = check_one(
+            &env,
+            parse,
+            error_handling,
+            state_path,
+            interval,
+            &mut state,
+            &mut progress,
+        );
+        match result {
+            Ok(()) => {
+                state.completed.push(db_name);
+                state.save(state_path)?;
+            }
+            Err(error) => {
+                // Save where we stopped so that the run can be resumed.
+                state.current = Some(progress);
+                state.save(state_path)?;
+                return Err(error);
+            }
+        }
+    }
+
+    if state.error_count > 0 {
+        return Err(DbError::Accumulated(state.error_count).into());
+    }
+    Ok(())
+}
+
+fn check_one(
+    env: &Environment,
+    parse: ParseFn,
+    error_handling: ErrorHandling,
+    state_path: &Path,
+    interval: usize,
+    state: &mut CheckState,
+    progress: &mut DbProgress,
+) -> Result<(), Error> {
+    let after_key = (progress.entries_checked > 0).then(|| progress.last_key.clone());
+    parse(env, after_key.as_deref(), &mut |key, parse_result| {
+        let idx = progress.entries_checked;
+        progress.entries_checked += 1;
+        progress.last_key = key.to_vec();
+        if let Err(parsing_err) = parse_result {
+            let error = DbError::Parsing(idx, parsing_err);
+            state.error_count += 1;
+            if error_handling.failfast {
+                return Err(error);
+            }
+            error!("{} database: {}", progress.db_name, error);
+            if error_handling.max_errors == Some(state.error_count) {
+                return Err(DbError::MaxErrors(state.error_count));
+            }
+        }
+        if progress.entries_checked.is_multiple_of(interval) {
+            state.current = Some(progress.clone());
+            // A failed save isn't fatal, the next one may succeed.
+            match state.save(state_path) {
+                Ok(()) => info!(
+                    "Checked {} entries of {} database, state saved.",
+                    progress.entries_checked, progress.db_name
+                ),
+                Err(error) => warn!("{}", error),
+            }
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
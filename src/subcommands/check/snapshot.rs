@@ -0,0 +1,53 @@
+use std::path::Path;
+
+use log::info;
+
+use crate::common::{
+    db::{self, EnvTuning, STORAGE_FILE_NAME},
+    lmdb_utils,
+    temp::{self, TempDir},
+};
+
+use super::Error;
+
+/// A compacted copy of the storage file, taken with a single read
+/// transaction so that the check sees a stable view of the database while
+/// the node keeps writing to it. The copy is removed when this is dropped.
+pub(super) struct Snapshot {
+    dir: TempDir,
+}
+
+impl Snapshot {
+    /// Copies the `storage.lmdb` file in `db_dir` to a new temporary
+    /// directory in `maybe_parent_dir`, or in the one given by `--tmpdir` if
+    /// unset.
+    pub(super) fn create(db_dir: &Path, maybe_parent_dir: Option<&Path>) -> Result<Self, Error> {
+        let storage_path = db_dir.join(STORAGE_FILE_NAME);
+        let env = db::db_env_with_tuning(&storage_path, EnvTuning::SEQUENTIAL)
+            .map_err(|lmdb_err| Error::Snapshot(storage_path.clone(), lmdb_err))?;
+        let used_size = lmdb_utils::used_size(&env)
+            .map_err(|lmdb_err| Error::Snapshot(storage_path.clone(), lmdb_err))?;
+        let parent_dir = maybe_parent_dir
+            .map(Path::to_path_buf)
+            .unwrap_or_else(temp::root);
+        let dir = TempDir::new_in(&parent_dir)
+            .map_err(|io_err| Error::SnapshotDir(parent_dir, io_err))?;
+        let snapshot_path = dir.path().join(STORAGE_FILE_NAME);
+        // Free pages are left out, so the copy takes at most the used size.
+        info!(
+            "Copying {} to {}, which takes up to {} bytes.",
+            storage_path.display(),
+            snapshot_path.display(),
+            used_size
+        );
+        lmdb_utils::copy_env(&env, &snapshot_path, true)
+            .map_err(|lmdb_err| Error::Snapshot(storage_path, lmdb_err))?;
+        info!("Checking the snapshot at {}.", snapshot_path.display());
+        Ok(Self { dir })
+    }
+
+    /// The directory holding the copied storage file.
+    pub(super) fn path(&self) -> &Path {
+        self.dir.path()
+    }
+}
@@ -0,0 +1,58 @@
+use log::{error, info};
+
+use crate::common::{
+    db::{DeserializationError, Error as DbError, ENTRY_LOG_INTERVAL},
+    storage::StorageReader,
+    value_guard,
+};
+
+use super::EntryParseFn;
+
+/// Validates the table `db_name` read through `reader` by ensuring every
+/// value of an entry can be parsed with `parse`, the way
+/// `Database::check_db` does for an LMDB environment.
+pub(super) fn check_table<R: StorageReader>(
+    reader: &R,
+    db_name: &str,
+    parse: EntryParseFn,
+    failfast: bool,
+    start_at: usize,
+    max_errors: Option<usize>,
+) -> Result<(), DbError> {
+    info!("Checking {} database.", db_name);
+    if start_at > 0 {
+        info!("Skipping {} entries.", start_at);
+    }
+    let mut idx = 0;
+    let mut error_count = 0;
+    reader.scan(db_name, |raw_key, raw_val| -> Result<(), DbError> {
+        let entry_idx = idx;
+        idx += 1;
+        if entry_idx < start_at {
+            return Ok(());
+        }
+        let parse_result = value_guard::check(raw_val)
+            .map_err(DeserializationError::from)
+            .and_then(|()| parse(raw_key, raw_val));
+        if let Err(parsing_err) = parse_result {
+            let error = DbError::Parsing(entry_idx, parsing_err);
+            if failfast {
+                return Err(error);
+            }
+            error!("{} database: {}", db_name, error);
+            error_count += 1;
+            if max_errors == Some(error_count) {
+                return Err(DbError::MaxErrors(error_count));
+            }
+        }
+        if (entry_idx - start_at) % ENTRY_LOG_INTERVAL == 0 {
+            info!("Parsed {} entries...", entry_idx - start_at);
+        }
+        Ok(())
+    })?;
+    info!("Parsing complete.");
+    if error_count > 0 {
+        return Err(DbError::Accumulated(error_count));
+    }
+    Ok(())
+}
@@ -0,0 +1,278 @@
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    check_db,
+    checkpoint::{self, CheckState, DbProgress},
+    glob_matches, select_databases,
+    snapshot::Snapshot,
+    Error, ErrorHandling,
+};
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, EnvTuning, Error as DbError, STORAGE_FILE_NAME},
+        storage::Backend,
+    },
+    test_utils::{self, LmdbTestFixture},
+};
+
+const FAILFAST: ErrorHandling = ErrorHandling {
+    failfast: true,
+    max_errors: None,
+};
+const NO_FAILFAST: ErrorHandling = ErrorHandling {
+    failfast: false,
+    max_errors: None,
+};
+
+// Stores 6 block headers, the ones at indices 1 and 4 being invalid.
+fn populate_headers(fixture: &LmdbTestFixture) -> Vec<Vec<u8>> {
+    let mut keys = vec![];
+    let env = &fixture.env;
+    let mut txn = env.begin_rw_txn().unwrap();
+    for idx in 0..6u8 {
+        let (block_hash, block_header) = test_utils::mock_block_header(idx);
+        // Use ordered keys so that the indices match the database order.
+        let mut key = block_hash.as_ref().to_vec();
+        key[0] = idx;
+        let value = if idx == 1 || idx == 4 {
+            vec![0u8, 1, 2]
+        } else {
+            bincode::serialize(&block_header).unwrap()
+        };
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &key,
+            &value,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        keys.push(key);
+    }
+    txn.commit().unwrap();
+    keys
+}
+
+#[test]
+fn checkpointed_check_should_save_and_resume() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let keys = populate_headers(&fixture);
+    let state_dir = tempfile::tempdir().unwrap();
+    let state_path = state_dir.path().join("check_state.json");
+
+    // A full run without failfast reports all errors and completes.
+    match checkpoint::check_db_with_checkpoint(
+        fixture.tmp_dir.as_ref(),
+        NO_FAILFAST,
+        EnvTuning::SEQUENTIAL,
+        &state_path,
+        2,
+        CheckState::new(Some(BlockHeaderDatabase::db_name())),
+    ) {
+        Err(Error::Database(DbError::Accumulated(2))) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    let state = CheckState::load(&state_path).unwrap();
+    assert_eq!(state.completed, vec![BlockHeaderDatabase::db_name()]);
+    assert_eq!(state.error_count, 2);
+
+    // With failfast, the run stops at the first invalid entry and saves it as
+    // the last one checked.
+    assert!(checkpoint::check_db_with_checkpoint(
+        fixture.tmp_dir.as_ref(),
+        FAILFAST,
+        EnvTuning::SEQUENTIAL,
+        &state_path,
+        2,
+        CheckState::new(Some(BlockHeaderDatabase::db_name())),
+    )
+    .is_err());
+    let state = CheckState::load(&state_path).unwrap();
+    assert!(state.completed.is_empty());
+    assert_eq!(
+        state.current,
+        Some(DbProgress {
+            db_name: BlockHeaderDatabase::db_name().to_string(),
+            entries_checked: 2,
+            last_key: keys[1].clone(),
+        })
+    );
+    assert_eq!(state.error_count, 1);
+
+    // Resuming continues after the invalid entry, up to the next one.
+    assert!(checkpoint::check_db_with_checkpoint(
+        fixture.tmp_dir.as_ref(),
+        FAILFAST,
+        EnvTuning::SEQUENTIAL,
+        &state_path,
+        2,
+        state,
+    )
+    .is_err());
+    let state = CheckState::load(&state_path).unwrap();
+    assert_eq!(state.current.as_ref().unwrap().entries_checked, 5);
+    assert_eq!(state.current.as_ref().unwrap().last_key, keys[4]);
+    assert_eq!(state.error_count, 2);
+
+    // Resuming once more finishes the database, reporting the errors of all
+    // the previous runs.
+    match checkpoint::check_db_with_checkpoint(
+        fixture.tmp_dir.as_ref(),
+        FAILFAST,
+        EnvTuning::SEQUENTIAL,
+        &state_path,
+        2,
+        state,
+    ) {
+        Err(Error::Database(DbError::Accumulated(2))) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    let state = CheckState::load(&state_path).unwrap();
+    assert_eq!(state.completed, vec![BlockHeaderDatabase::db_name()]);
+
+    // Without failfast, the run stops once the maximum number of errors is
+    // reached, counting the errors of the previous runs.
+    let limited = ErrorHandling {
+        failfast: false,
+        max_errors: Some(1),
+    };
+    match checkpoint::check_db_with_checkpoint(
+        fixture.tmp_dir.as_ref(),
+        limited,
+        EnvTuning::SEQUENTIAL,
+        &state_path,
+        2,
+        CheckState::new(Some(BlockHeaderDatabase::db_name())),
+    ) {
+        Err(Error::Database(DbError::MaxErrors(1))) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    let state = CheckState::load(&state_path).unwrap();
+    assert_eq!(state.current.as_ref().unwrap().last_key, keys[1]);
+    let limited = ErrorHandling {
+        failfast: false,
+        max_errors: Some(2),
+    };
+    match checkpoint::check_db_with_checkpoint(
+        fixture.tmp_dir.as_ref(),
+        limited,
+        EnvTuning::SEQUENTIAL,
+        &state_path,
+        2,
+        state,
+    ) {
+        Err(Error::Database(DbError::MaxErrors(2))) => {}
+        other => panic!("Unexpected result: {other:?}"),
+    }
+    let state = CheckState::load(&state_path).unwrap();
+    assert_eq!(state.current.as_ref().unwrap().last_key, keys[4]);
+
+    // Unknown databases are rejected before anything is checked.
+    assert!(matches!(
+        checkpoint::check_db_with_checkpoint(
+            fixture.tmp_dir.as_ref(),
+            NO_FAILFAST,
+            EnvTuning::SEQUENTIAL,
+            &state_path,
+            2,
+            CheckState::new(Some("bogus")),
+        ),
+        Err(Error::UnknownDb(_))
+    ));
+}
+
+#[test]
+fn snapshot_should_keep_the_view_it_was_taken_with() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    populate_headers(&fixture);
+    let parent_dir = tempfile::tempdir().unwrap();
+    let snapshot = Snapshot::create(fixture.tmp_dir.path(), Some(parent_dir.path())).unwrap();
+    let snapshot_dir = snapshot.path().to_path_buf();
+    assert!(snapshot_dir.starts_with(parent_dir.path()));
+
+    // Invalid headers written to the live database after the snapshot was
+    // taken aren't seen by the check.
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for idx in 10..13u8 {
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &[idx; 32],
+            &[0u8, 1, 2],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    match check_db(
+        &snapshot_dir,
+        NO_FAILFAST,
+        Some(BlockHeaderDatabase::db_name()),
+        0,
+        Backend::Lmdb,
+        EnvTuning::SEQUENTIAL,
+    ) {
+        Err(Error::Database(DbError::Accumulated(2))) => {}
+        other => panic!("unexpected result {other:?}"),
+    }
+    match check_db(
+        fixture.tmp_dir.path(),
+        NO_FAILFAST,
+        Some(BlockHeaderDatabase::db_name()),
+        0,
+        Backend::Lmdb,
+        EnvTuning::SEQUENTIAL,
+    ) {
+        Err(Error::Database(DbError::Accumulated(5))) => {}
+        other => panic!("unexpected result {other:?}"),
+    }
+
+    // The copy is removed once the check is done.
+    drop(snapshot);
+    assert!(!snapshot_dir.exists());
+}
+
+#[test]
+fn glob_should_match_names() {
+    assert!(glob_matches("block_*", "block_body_merkle"));
+    assert!(glob_matches("*_hashes", "transfer_hashes"));
+    assert!(glob_matches("deploy?", "deploys"));
+    assert!(glob_matches("*", "proposers"));
+    assert!(glob_matches("b*_*_m*", "block_body_merkle"));
+    assert!(!glob_matches("block_*", "deploys"));
+    assert!(!glob_matches("deploy?", "deploy_hashes"));
+    assert!(!glob_matches("transfer", "transfer_hashes"));
+}
+
+#[test]
+fn specific_should_select_databases_in_check_order() {
+    let names = |specific| {
+        select_databases(specific)
+            .unwrap()
+            .into_iter()
+            .map(|database| database.name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names("deploys"), vec!["deploys"]);
+    assert_eq!(
+        names(" deploy_metadata, block_* "),
+        vec![
+            "block_body",
+            "block_body_merkle",
+            "block_header",
+            "block_metadata",
+            "deploy_metadata"
+        ]
+    );
+    // Databases matched by several patterns are checked once.
+    assert_eq!(names("transfer*,*_hashes").len(), 3);
+    assert!(matches!(
+        select_databases("block_*,bogus*"),
+        Err(Error::UnknownDb(pattern)) if pattern == "bogus*"
+    ));
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use clap::{value_parser, Arg, ArgMatches, Command};
+use clap_complete::Shell;
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+
+pub const COMMAND_NAME: &str = "completions";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const SHELL: &str = "shell";
+
+/// Errors encountered when generating shell completions.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+}
+
+enum DisplayOrder {
+    Shell,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs a completion script for the given shell, covering every \
+            subcommand and option of this program.",
+        )
+        .arg(
+            Arg::new(SHELL)
+                .display_order(DisplayOrder::Shell as usize)
+                .required(true)
+                .takes_value(true)
+                .value_name("SHELL")
+                .value_parser(value_parser!(Shell))
+                .help("Shell to generate the completion script for."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the script. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+/// Writes the completion script of `shell` for `cli` to `out`.
+pub(crate) fn generate<W: Write>(
+    shell: Shell,
+    mut cli: Command,
+    out: &mut W,
+) -> Result<(), IoError> {
+    let bin_name = cli.get_name().to_string();
+    // The generators panic on write errors, so the script is rendered in
+    // memory and written out here instead.
+    let mut script = vec![];
+    clap_complete::generate(shell, &mut cli, bin_name, &mut script);
+    out.write_all(&script)
+}
+
+pub fn run(matches: &ArgMatches, cli: Command) -> Result<(), Error> {
+    let shell = *matches
+        .get_one::<Shell>(SHELL)
+        .expect("should have shell arg");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    generate(shell, cli, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
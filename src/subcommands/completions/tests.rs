@@ -0,0 +1,74 @@
+use clap::{Arg, Command};
+use clap_complete::Shell;
+
+use super::generate;
+
+/// A command line with a global flag, a nested subcommand and options with
+/// and without restricted values.
+fn test_cli() -> Command<'static> {
+    Command::new("db-tool")
+        .version("1.0.0")
+        .about("Works with databases.")
+        .arg(
+            Arg::new("quiet")
+                .short('q')
+                .long("quiet")
+                .global(true)
+                .help("Log to stderr."),
+        )
+        .subcommand(
+            Command::new("archive")
+                .about("Archive utilities.")
+                .subcommand(
+                    Command::new("create")
+                        .about("Creates an archive.")
+                        .arg(
+                            Arg::new("db-path")
+                                .short('d')
+                                .long("db-path")
+                                .takes_value(true)
+                                .value_name("DB_PATH")
+                                .help("Path of the database."),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .long("level")
+                                .takes_value(true)
+                                .possible_values(["fast", "best"])
+                                .help("Compression [level]: it's fast or best."),
+                        ),
+                ),
+        )
+}
+
+fn generate_string(shell: Shell) -> String {
+    let mut out = vec![];
+    generate(shell, test_cli(), &mut out).unwrap();
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn completions_should_cover_nested_subcommands() {
+    let bash = generate_string(Shell::Bash);
+    assert!(bash.contains("db__tool__archive__create)"));
+    assert!(bash.contains("opts=\"-d -h -q --db-path --level --help --quiet\""));
+    assert!(bash.contains("COMPREPLY=($(compgen -W \"fast best\" -- \"${cur}\"))"));
+    assert!(bash.contains("complete -F _db-tool -o bashdefault -o default db-tool"));
+
+    let fish = generate_string(Shell::Fish);
+    assert!(fish.contains(
+        "complete -c db-tool -n \"__fish_seen_subcommand_from archive; and \
+        __fish_seen_subcommand_from create\" -l level -d 'Compression [level]: it\\'s fast \
+        or best.' -r -f -a \"{fast\t,best\t}\""
+    ));
+    assert!(fish.contains("-f -a \"create\" -d 'Creates an archive.'"));
+
+    let zsh = generate_string(Shell::Zsh);
+    assert!(zsh.starts_with("#compdef db-tool\n"));
+    assert!(zsh.contains("'--db-path=[Path of the database.]:DB_PATH: '"));
+    assert!(
+        zsh.contains("'--level=[Compression \\[level\\]: it'\\''s fast or best.]: :(fast best)'")
+    );
+    assert!(zsh.contains("'--quiet[Log to stderr.]'"));
+    assert!(zsh.contains("'create:Creates an archive.'"));
+}
@@ -0,0 +1,150 @@
+mod inventory;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use casper_hashing::Digest;
+use casper_types::bytesrepr::Error as BytesreprError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+
+pub const COMMAND_NAME: &str = "contracts";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT: &str = "state-root";
+const TOP: &str = "top";
+
+/// Errors encountered when listing the contracts in global state.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the trie store: {0}")]
+    Database(#[from] LmdbError),
+    /// A node of the trie is missing.
+    #[error("Trie node {0} is missing")]
+    MissingNode(Digest),
+    /// The trie store is missing from the database directory.
+    #[error("Trie store not found at {0}")]
+    MissingTrieStore(PathBuf),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on a trie node.
+    #[error("Error parsing trie node {0}: {1}")]
+    Parsing(Digest, BytesreprError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    StateRoot,
+    Top,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Lists the contracts and contract packages in the global state \
+            under a state root, with the entry points, named key count and \
+            wasm size of each contract, and outputs them in JSON format \
+            along with the total size of the wasm stored.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STATE_ROOT)
+                .display_order(DisplayOrder::StateRoot as usize)
+                .required(true)
+                .short('r')
+                .long(STATE_ROOT)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help("Hash of the state root whose contracts to list, in hex."),
+        )
+        .arg(
+            Arg::new(TOP)
+                .display_order(DisplayOrder::Top as usize)
+                .required(false)
+                .short('t')
+                .long(TOP)
+                .takes_value(true)
+                .value_name("N")
+                .validator(|value| value.parse::<usize>().map(|_| ()))
+                .help(
+                    "List only the N contracts with the largest wasm. The \
+                    counts and total size still cover every contract.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let state_root_hash = Digest::from_hex(
+        matches
+            .value_of(STATE_ROOT)
+            .expect("should have state-root arg"),
+    )
+    .expect("should be validated");
+    let maybe_top_count = matches
+        .value_of(TOP)
+        .map(|value| value.parse().expect("should be validated"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = inventory::list_contracts(path, state_root_hash, maybe_top_count)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
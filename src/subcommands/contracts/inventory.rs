@@ -0,0 +1,156 @@
+use std::{cmp::Reverse, collections::HashMap, path::Path, result::Result};
+
+use casper_execution_engine::storage::trie::Trie;
+use casper_hashing::Digest;
+use casper_types::{
+    bytesrepr, ContractHash, ContractPackageHash, ContractWasmHash, Key, KeyTag, ProtocolVersion,
+    StoredValue,
+};
+use log::info;
+use serde::Serialize;
+
+use crate::common::db::{self, TrieEnv, TRIE_STORE_FILE_NAME};
+
+use super::Error;
+
+/// Number of leaves visited between two progress messages.
+const LEAVES_PER_PROGRESS_LOG: usize = 1_000_000;
+
+/// A contract stored in global state.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ContractInfo {
+    pub(crate) contract_hash: ContractHash,
+    pub(crate) package_hash: ContractPackageHash,
+    pub(crate) wasm_hash: ContractWasmHash,
+    /// Size of the wasm of the contract, if it's in global state.
+    pub(crate) wasm_size: Option<usize>,
+    pub(crate) protocol_version: ProtocolVersion,
+    pub(crate) entry_points: Vec<String>,
+    pub(crate) named_keys: usize,
+}
+
+/// A contract package stored in global state.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct PackageInfo {
+    pub(crate) package_hash: ContractPackageHash,
+    pub(crate) versions: usize,
+    pub(crate) disabled_versions: usize,
+    pub(crate) locked: bool,
+}
+
+/// The contracts and packages under a state root, along with the total size
+/// of the wasm stored there.
+#[derive(Debug, Serialize)]
+pub(crate) struct ContractsReport {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) contract_count: usize,
+    pub(crate) package_count: usize,
+    pub(crate) wasm_count: usize,
+    pub(crate) total_wasm_size: usize,
+    /// The contracts, the ones with the largest wasm first.
+    pub(crate) contracts: Vec<ContractInfo>,
+    pub(crate) packages: Vec<PackageInfo>,
+}
+
+/// Lists the contracts and contract packages in the global state under
+/// `state_root_hash` in the trie store in `db_path`. If `maybe_top_count`
+/// is set, only that many contracts with the largest wasm are listed, while
+/// the counts still cover all of them.
+pub(crate) fn list_contracts<P: AsRef<Path>>(
+    db_path: P,
+    state_root_hash: Digest,
+    maybe_top_count: Option<usize>,
+) -> Result<ContractsReport, Error> {
+    let trie_path = db_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    if !trie_path.exists() {
+        return Err(Error::MissingTrieStore(trie_path));
+    }
+    let trie_env = TrieEnv::open(&db_path)?;
+    let trie_db = trie_env.db()?;
+    let txn = trie_env.begin_ro_txn()?;
+
+    let mut contracts = vec![];
+    let mut packages = vec![];
+    let mut wasm_sizes = HashMap::new();
+    let mut leaf_count = 0;
+    let mut pending = vec![state_root_hash];
+    while let Some(trie_key) = pending.pop() {
+        let raw_trie =
+            db::get_optional(&txn, trie_db, &trie_key)?.ok_or(Error::MissingNode(trie_key))?;
+        // A first byte of `0` indicates a leaf, followed by its key, whose
+        // first byte is its tag. Contracts, packages and wasm are all stored
+        // under hash keys, so the other leaves aren't parsed.
+        if raw_trie.first() == Some(&0) {
+            leaf_count += 1;
+            if leaf_count % LEAVES_PER_PROGRESS_LOG == 0 {
+                info!("Visited {} leaves...", leaf_count);
+            }
+            if raw_trie.get(1) != Some(&(KeyTag::Hash as u8)) {
+                continue;
+            }
+        }
+        let trie: Trie<Key, StoredValue> = bytesrepr::deserialize(raw_trie.to_vec())
+            .map_err(|bytesrepr_err| Error::Parsing(trie_key, bytesrepr_err))?;
+        match trie {
+            Trie::Leaf {
+                key: Key::Hash(hash_addr),
+                value,
+            } => match value {
+                StoredValue::Contract(contract) => contracts.push(ContractInfo {
+                    contract_hash: ContractHash::new(hash_addr),
+                    package_hash: contract.contract_package_hash(),
+                    wasm_hash: contract.contract_wasm_hash(),
+                    wasm_size: None,
+                    protocol_version: contract.protocol_version(),
+                    entry_points: contract.entry_points().keys().cloned().collect(),
+                    named_keys: contract.named_keys().len(),
+                }),
+                StoredValue::ContractPackage(package) => packages.push(PackageInfo {
+                    package_hash: ContractPackageHash::new(hash_addr),
+                    versions: package.versions().len(),
+                    disabled_versions: package.disabled_versions().len(),
+                    locked: package.is_locked(),
+                }),
+                StoredValue::ContractWasm(wasm) => {
+                    wasm_sizes.insert(ContractWasmHash::new(hash_addr), wasm.bytes().len());
+                }
+                _ => {}
+            },
+            Trie::Leaf { .. } => {}
+            Trie::Node { pointer_block } => pending.extend(
+                pointer_block
+                    .as_indexed_pointers()
+                    .map(|(_index, pointer)| pointer.into_hash()),
+            ),
+            Trie::Extension { pointer, .. } => pending.push(pointer.into_hash()),
+        }
+    }
+
+    for contract in contracts.iter_mut() {
+        contract.wasm_size = wasm_sizes.get(&contract.wasm_hash).copied();
+    }
+    contracts.sort_by_key(|contract| (Reverse(contract.wasm_size), contract.contract_hash));
+    packages.sort_by_key(|package| package.package_hash);
+    let contract_count = contracts.len();
+    if let Some(top_count) = maybe_top_count {
+        contracts.truncate(top_count);
+    }
+    let report = ContractsReport {
+        state_root_hash,
+        contract_count,
+        package_count: packages.len(),
+        wasm_count: wasm_sizes.len(),
+        total_wasm_size: wasm_sizes.values().sum(),
+        contracts,
+        packages,
+    };
+    info!(
+        "Found {} contracts, {} packages and {} wasm totaling {} bytes under state root {}.",
+        report.contract_count,
+        report.package_count,
+        report.wasm_count,
+        report.total_wasm_size,
+        state_root_hash
+    );
+    Ok(report)
+}
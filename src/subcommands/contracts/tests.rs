@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap, HashMap};
+
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId, storage::global_state::StateProvider,
+};
+use casper_hashing::Digest;
+use casper_types::{
+    contracts::{ContractPackageStatus, ContractVersionKey, NamedKeys},
+    AccessRights, CLType, CLValue, Contract, ContractHash, ContractPackage, ContractPackageHash,
+    ContractWasm, ContractWasmHash, EntryPoint, EntryPointAccess, EntryPointType, EntryPoints, Key,
+    ProtocolVersion, StoredValue, URef,
+};
+use tempfile::TempDir;
+
+use super::{
+    inventory::{list_contracts, ContractInfo, PackageInfo},
+    Error,
+};
+use crate::subcommands::trie_compact::{create_execution_engine, DEFAULT_MAX_DB_SIZE};
+
+fn mock_contract(idx: u8, entry_point_names: &[&str], named_key_count: u8) -> Contract {
+    let mut entry_points = EntryPoints::new();
+    for name in entry_point_names {
+        entry_points.add_entry_point(EntryPoint::new(
+            *name,
+            vec![],
+            CLType::Unit,
+            EntryPointAccess::Public,
+            EntryPointType::Contract,
+        ));
+    }
+    let named_keys: NamedKeys = (0..named_key_count)
+        .map(|key_idx| (key_idx.to_string(), Key::Hash([key_idx; 32])))
+        .collect();
+    Contract::new(
+        ContractPackageHash::new([10; 32]),
+        ContractWasmHash::new([20 + idx; 32]),
+        named_keys,
+        entry_points,
+        ProtocolVersion::V1_0_0,
+    )
+}
+
+/// Creates a trie store with a package holding contract 1, with 10 bytes of
+/// wasm, and contract 2, with 100 bytes of wasm, along with contract 3
+/// whose wasm is missing and an unrelated value.
+fn new_global_state() -> (TempDir, Digest) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), DEFAULT_MAX_DB_SIZE.parse().unwrap(), true)
+            .unwrap();
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::Hash([1; 32]),
+        StoredValue::Contract(mock_contract(1, &["call"], 2)),
+    );
+    stored_values.insert(
+        Key::Hash([2; 32]),
+        StoredValue::Contract(mock_contract(2, &["mint", "transfer"], 0)),
+    );
+    stored_values.insert(
+        Key::Hash([3; 32]),
+        StoredValue::Contract(mock_contract(3, &[], 1)),
+    );
+    stored_values.insert(
+        Key::Hash([21; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![0; 10])),
+    );
+    stored_values.insert(
+        Key::Hash([22; 32]),
+        StoredValue::ContractWasm(ContractWasm::new(vec![0; 100])),
+    );
+    let versions = BTreeMap::from([
+        (ContractVersionKey::new(1, 1), ContractHash::new([1; 32])),
+        (ContractVersionKey::new(1, 2), ContractHash::new([2; 32])),
+    ]);
+    stored_values.insert(
+        Key::Hash([10; 32]),
+        StoredValue::ContractPackage(ContractPackage::new(
+            URef::new([0; 32], AccessRights::READ_ADD_WRITE),
+            versions,
+            [ContractVersionKey::new(1, 1)].into_iter().collect(),
+            Default::default(),
+            ContractPackageStatus::new(false),
+        )),
+    );
+    stored_values.insert(
+        Key::Hash([30; 32]),
+        StoredValue::CLValue(CLValue::from_t(7u64).unwrap()),
+    );
+
+    let global_state = engine_state.get_state();
+    let state_root_hash = global_state
+        .put_stored_values(
+            CorrelationId::new(),
+            global_state.empty_root(),
+            stored_values,
+        )
+        .unwrap();
+    engine_state.flush_environment().unwrap();
+    (tmp_dir, state_root_hash)
+}
+
+#[test]
+fn list_contracts_should_find_contracts_and_packages() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    let report = list_contracts(tmp_dir.path(), state_root_hash, None).unwrap();
+    assert_eq!(report.contract_count, 3);
+    assert_eq!(report.package_count, 1);
+    assert_eq!(report.wasm_count, 2);
+    assert_eq!(report.total_wasm_size, 110);
+    assert_eq!(
+        report.contracts,
+        vec![
+            ContractInfo {
+                contract_hash: ContractHash::new([2; 32]),
+                package_hash: ContractPackageHash::new([10; 32]),
+                wasm_hash: ContractWasmHash::new([22; 32]),
+                wasm_size: Some(100),
+                protocol_version: ProtocolVersion::V1_0_0,
+                entry_points: vec!["mint".to_string(), "transfer".to_string()],
+                named_keys: 0,
+            },
+            ContractInfo {
+                contract_hash: ContractHash::new([1; 32]),
+                package_hash: ContractPackageHash::new([10; 32]),
+                wasm_hash: ContractWasmHash::new([21; 32]),
+                wasm_size: Some(10),
+                protocol_version: ProtocolVersion::V1_0_0,
+                entry_points: vec!["call".to_string()],
+                named_keys: 2,
+            },
+            ContractInfo {
+                contract_hash: ContractHash::new([3; 32]),
+                package_hash: ContractPackageHash::new([10; 32]),
+                wasm_hash: ContractWasmHash::new([23; 32]),
+                wasm_size: None,
+                protocol_version: ProtocolVersion::V1_0_0,
+                entry_points: vec![],
+                named_keys: 1,
+            },
+        ]
+    );
+    assert_eq!(
+        report.packages,
+        vec![PackageInfo {
+            package_hash: ContractPackageHash::new([10; 32]),
+            versions: 2,
+            disabled_versions: 1,
+            locked: false,
+        }]
+    );
+}
+
+#[test]
+fn list_contracts_should_keep_top_contracts() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    let report = list_contracts(tmp_dir.path(), state_root_hash, Some(1)).unwrap();
+    assert_eq!(report.contract_count, 3);
+    assert_eq!(report.contracts.len(), 1);
+    assert_eq!(
+        report.contracts[0].contract_hash,
+        ContractHash::new([2; 32])
+    );
+
+    assert!(matches!(
+        list_contracts(tmp_dir.path(), Digest::hash([0u8]), None),
+        Err(Error::MissingNode(_))
+    ));
+}
@@ -0,0 +1,160 @@
+mod convert;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use bincode::Error as BincodeError;
+use casper_node::types::BlockHash;
+use casper_types::bytesrepr::Error as BytesreprError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError, merkle_body::Error as MerkleBodyError,
+    storage::Error as StorageError,
+};
+use convert::BodyFormat;
+
+pub const COMMAND_NAME: &str = "convert-bodies";
+const DB_PATH: &str = "db-path";
+const FROM_HEIGHT: &str = "from-height";
+const REMOVE_SOURCE: &str = "remove-source";
+const TO: &str = "to";
+const TO_HEIGHT: &str = "to-height";
+
+/// Errors encountered when converting block bodies between formats.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error encoding a merkle body part.
+    #[error("Error encoding merkle body of block {0}: {1}")]
+    Encoding(BlockHash, BytesreprError),
+    /// The height range is empty.
+    #[error("Invalid height range: {0} is above {1}")]
+    InvalidRange(u64, u64),
+    /// Error reading a merkle block body.
+    #[error("Error reading merkle body of block {0}: {1}")]
+    MerkleBody(BlockHash, MerkleBodyError),
+    /// Parsing error on a legacy block body.
+    #[error("Error parsing legacy body of block {0}: {1}")]
+    Parsing(BlockHash, BincodeError),
+    /// Error serializing a legacy block body.
+    #[error("Error serializing legacy body of block {0}: {1}")]
+    Serialization(BlockHash, BincodeError),
+    /// Error reading the size of the database.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    To,
+    FromHeight,
+    ToHeight,
+    RemoveSource,
+}
+
+fn height_arg(name: &'static str, short: char, display_order: DisplayOrder) -> Arg<'static> {
+    Arg::new(name)
+        .display_order(display_order as usize)
+        .required(false)
+        .short(short)
+        .long(name)
+        .takes_value(true)
+        .value_name("HEIGHT")
+        .validator(|value| value.parse::<u64>().map(|_| ()))
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Converts the block bodies of a storage database between the \
+            legacy format, where each body is a single `block_body` entry, \
+            and the merkle format, where it is a linked list in \
+            `block_body_merkle` pointing to parts in `deploy_hashes`, \
+            `transfer_hashes` and `proposers`. Converted bodies are stored \
+            under the body hash of their header, so the blocks keep \
+            referencing them. The node looks a body up in the format given \
+            by the hashing version of its header, so blocks whose header \
+            version doesn't match the target format are converted with a \
+            warning and keep their original body.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file to convert."),
+        )
+        .arg(
+            Arg::new(TO)
+                .display_order(DisplayOrder::To as usize)
+                .required(true)
+                .long(TO)
+                .takes_value(true)
+                .value_name("FORMAT")
+                .possible_values(["merkle", "legacy"])
+                .help("Format to convert the bodies to."),
+        )
+        .arg(
+            height_arg(FROM_HEIGHT, 'f', DisplayOrder::FromHeight)
+                .help("Height of the lowest block to convert. Defaults to the genesis block."),
+        )
+        .arg(
+            height_arg(TO_HEIGHT, 't', DisplayOrder::ToHeight)
+                .help("Height of the highest block to convert. Defaults to the highest block."),
+        )
+        .arg(
+            Arg::new(REMOVE_SOURCE)
+                .display_order(DisplayOrder::RemoveSource as usize)
+                .required(false)
+                .long(REMOVE_SOURCE)
+                .takes_value(false)
+                .help(
+                    "Remove the bodies in the original format once converted. \
+                    Merkle parts are shared between bodies, so only the first \
+                    node of each linked list is removed.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let target = match matches.value_of(TO) {
+        Some("legacy") => BodyFormat::Legacy,
+        _ => BodyFormat::Merkle,
+    };
+    let height = |name| {
+        matches
+            .value_of(name)
+            .map(|value| value.parse::<u64>().expect("should be validated"))
+    };
+    let from = height(FROM_HEIGHT).unwrap_or_default();
+    let to = height(TO_HEIGHT).unwrap_or(u64::MAX);
+    if from > to {
+        return Err(Error::InvalidRange(from, to));
+    }
+    let remove_source = matches.is_present(REMOVE_SOURCE);
+    convert::convert_bodies(path, target, from..=to, remove_source)?;
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use std::{fs, ops::RangeInclusive, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, HashingAlgorithmVersion};
+use lmdb::Transaction;
+use log::{info, warn};
+
+use crate::{
+    common::{
+        block_iter,
+        db::{BlockBodyDatabase, BlockBodyMerkleDatabase, Database, StorageEnv, STORAGE_FILE_NAME},
+        merkle_body,
+        progress::ProgressTracker,
+        storage::{LmdbReader, LmdbWriter, StorageReader, StorageWriter},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+/// Number of blocks converted per transaction.
+const BLOCKS_PER_COMMIT: usize = 100;
+
+/// Format in which a block body is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum BodyFormat {
+    /// The whole body is a single entry in `block_body`.
+    Legacy,
+    /// The body is a linked list in `block_body_merkle` pointing to its
+    /// parts.
+    Merkle,
+}
+
+impl BodyFormat {
+    /// Returns the format in which the node looks up the bodies of headers
+    /// hashed with `version`.
+    fn of_version(version: HashingAlgorithmVersion) -> Self {
+        match version {
+            HashingAlgorithmVersion::V1 => BodyFormat::Legacy,
+            HashingAlgorithmVersion::V2 => BodyFormat::Merkle,
+        }
+    }
+}
+
+/// Counts of the blocks found while converting bodies.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ConversionSummary {
+    /// Blocks in the height range.
+    pub(crate) blocks: usize,
+    /// Bodies written in the target format.
+    pub(crate) converted: usize,
+    /// Bodies which were already stored in the target format.
+    pub(crate) already_converted: usize,
+    /// Blocks without a body in either format.
+    pub(crate) missing: usize,
+    /// Blocks whose body is in the target format, but which the node looks
+    /// up in the other format because of the hashing version of their
+    /// header.
+    pub(crate) unread: usize,
+}
+
+/// Reads the legacy body stored under `body_hash`, or returns `None` if
+/// there is none.
+fn read_legacy_body<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    body_hash: &Digest,
+) -> Result<Option<BlockBody>, Error> {
+    if !reader.has_table(BlockBodyDatabase::db_name())? {
+        return Ok(None);
+    }
+    match reader.get(BlockBodyDatabase::db_name(), body_hash.as_ref())? {
+        Some(raw_body) => bincode::deserialize(&raw_body)
+            .map(Some)
+            .map_err(|bincode_err| Error::Parsing(*block_hash, bincode_err)),
+        None => Ok(None),
+    }
+}
+
+/// Reads the merkle body stored under `body_hash`, or returns `None` if
+/// there is none.
+fn read_merkle_body<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    body_hash: &Digest,
+) -> Result<Option<BlockBody>, Error> {
+    merkle_body::read_merkle_body(reader, body_hash)
+        .map(|maybe_merkle_body| maybe_merkle_body.map(|merkle_body| merkle_body.body))
+        .map_err(|merkle_body_err| Error::MerkleBody(*block_hash, merkle_body_err))
+}
+
+/// Stores the body of `block_hash` in the `target` format under the body
+/// hash of its header, removing the entry of the other format if
+/// `remove_source` is set. The source entry is kept for blocks whose header
+/// version makes the node read their body from the other format.
+fn convert_block<W: StorageWriter>(
+    writer: &mut W,
+    block_hash: &BlockHash,
+    target: BodyFormat,
+    remove_source: bool,
+    summary: &mut ConversionSummary,
+) -> Result<(), Error> {
+    let header = block_iter::read_header(writer, block_hash)?;
+    let version = header.hashing_algorithm_version();
+    let node_format = BodyFormat::of_version(version);
+    let body_hash = *header.body_hash();
+    let (maybe_target_body, maybe_source_body, source_db_name) = match target {
+        BodyFormat::Legacy => (
+            read_legacy_body(writer, block_hash, &body_hash)?,
+            read_merkle_body(writer, block_hash, &body_hash)?,
+            BlockBodyMerkleDatabase::db_name(),
+        ),
+        BodyFormat::Merkle => (
+            read_merkle_body(writer, block_hash, &body_hash)?,
+            read_legacy_body(writer, block_hash, &body_hash)?,
+            BlockBodyDatabase::db_name(),
+        ),
+    };
+    let has_source_body = match (maybe_target_body, maybe_source_body) {
+        (Some(_), maybe_source_body) => {
+            summary.already_converted += 1;
+            maybe_source_body.is_some()
+        }
+        (None, Some(body)) => {
+            match target {
+                BodyFormat::Legacy => {
+                    let raw_body = bincode::serialize(&body)
+                        .map_err(|bincode_err| Error::Serialization(*block_hash, bincode_err))?;
+                    writer.put(BlockBodyDatabase::db_name(), body_hash.as_ref(), &raw_body)?;
+                }
+                BodyFormat::Merkle => {
+                    let entries = merkle_body::merkle_entries(&body, body_hash)
+                        .map_err(|bytesrepr_err| Error::Encoding(*block_hash, bytesrepr_err))?;
+                    for (db_name, key, value) in entries {
+                        writer.put(db_name, key.as_ref(), &value)?;
+                    }
+                }
+            }
+            summary.converted += 1;
+            true
+        }
+        (None, None) => {
+            warn!("Block {} has no body, skipping it.", block_hash);
+            summary.missing += 1;
+            return Ok(());
+        }
+    };
+    if node_format != target {
+        warn!(
+            "Block {} has a header of hashing version {:?}, so the node doesn't read its \
+            body in the {:?} format{}.",
+            block_hash,
+            version,
+            target,
+            if has_source_body {
+                ", keeping the original one"
+            } else {
+                ""
+            }
+        );
+        summary.unread += 1;
+        return Ok(());
+    }
+    if !has_source_body || !remove_source {
+        return Ok(());
+    }
+    // The rest of a merkle list and its parts may be shared with other
+    // bodies, so only its first node is removed.
+    writer.delete(source_db_name, body_hash.as_ref())?;
+    Ok(())
+}
+
+/// Converts the bodies of the blocks in `range` of the storage database in
+/// `db_path` to the `target` format. The converted bodies are stored under
+/// the body hash of their header, as the headers, and thus the block hashes,
+/// can't change. Blocks whose header version makes the node look their body up
+/// in the other format are converted too, but keep their original body.
+pub(crate) fn convert_bodies<P: AsRef<Path>>(
+    db_path: P,
+    target: BodyFormat,
+    range: RangeInclusive<u64>,
+    remove_source: bool,
+) -> Result<ConversionSummary, Error> {
+    let storage_path = db_path.as_ref().join(STORAGE_FILE_NAME);
+    // Leave room for a copy of every body in the target format.
+    let db_size = fs::metadata(&storage_path)
+        .map(|metadata| metadata.len() as usize)
+        .map_err(|io_err| Error::Source(storage_path.clone(), io_err))?;
+    let env = StorageEnv::create(db_path, db_size * 2)?;
+    let blocks: Vec<BlockHash> = {
+        let txn = env.begin_ro_txn()?;
+        block_iter::blocks_by_height(&LmdbReader::new(&txn))?
+            .into_iter()
+            .filter(|(height, _block_hash)| range.contains(height))
+            .map(|(_height, block_hash)| block_hash)
+            .collect()
+    };
+
+    let mut summary = ConversionSummary {
+        blocks: blocks.len(),
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Conversion {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    for batch in blocks.chunks(BLOCKS_PER_COMMIT) {
+        let mut txn = env.begin_rw_txn()?;
+        let mut writer = LmdbWriter::new(&mut txn);
+        for block_hash in batch {
+            convert_block(&mut writer, block_hash, target, remove_source, &mut summary)?;
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+        }
+        txn.commit()?;
+    }
+
+    info!(
+        "Converted {} of {} bodies, {} were already converted, {} are missing and {} aren't \
+        read by the node in the target format because of their header version.",
+        summary.converted,
+        summary.blocks,
+        summary.already_converted,
+        summary.missing,
+        summary.unread
+    );
+    Ok(summary)
+}
@@ -0,0 +1,229 @@
+use casper_node::types::{BlockHash, BlockHeader, HashingAlgorithmVersion};
+use casper_types::ProtocolVersion;
+use lmdb::{Transaction, WriteFlags};
+
+use super::convert::{convert_bodies, BodyFormat, ConversionSummary};
+use crate::{
+    common::{
+        block_iter,
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+            DeployHashesDatabase, ProposerDatabase, StorageEnv, TransferHashesDatabase,
+            STORAGE_FILE_NAME,
+        },
+        merkle_body,
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture, KEYS},
+};
+
+/// Reads the body of `header` the way the node does, from the format given by
+/// the hashing version of the header.
+fn read_node_body<R: StorageReader>(reader: &R, header: &BlockHeader) -> Option<BlockBody> {
+    match header.hashing_algorithm_version() {
+        HashingAlgorithmVersion::V1 => reader
+            .get(BlockBodyDatabase::db_name(), header.body_hash().as_ref())
+            .unwrap()
+            .map(|raw_body| bincode::deserialize(&raw_body).unwrap()),
+        HashingAlgorithmVersion::V2 => merkle_body::read_merkle_body(reader, header.body_hash())
+            .unwrap()
+            .map(|merkle_body| merkle_body.body),
+    }
+}
+
+fn mock_body(idx: u8) -> BlockBody {
+    BlockBody::from_parts(
+        KEYS[idx as usize].clone(),
+        vec![test_utils::mock_deploy_hash(idx)],
+        vec![test_utils::mock_deploy_hash(idx + 100)],
+    )
+}
+
+/// Protocol version from which the node hashes headers, and looks bodies up,
+/// with the merkle scheme.
+const MERKLE_PROTOCOL_VERSION: ProtocolVersion = ProtocolVersion::from_parts(9001, 0, 0);
+
+/// Stores the header of the block at `height`, hashed with the merkle scheme
+/// if `merkle` is set, with its legacy body unless `with_body` is unset.
+fn put_block(fixture: &LmdbTestFixture, height: u8, merkle: bool, with_body: bool) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    if merkle {
+        header.protocol_version = MERKLE_PROTOCOL_VERSION;
+    }
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    if with_body {
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &header.body_hash,
+            &bincode::serialize(&mock_body(height)).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    block_hash
+}
+
+#[test]
+fn convert_bodies_should_follow_header_version() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Blocks 0 to 2 have merkle headers but legacy bodies, block 2 has no
+    // body and block 3 has a legacy header.
+    let block_hashes: Vec<BlockHash> = (0..4)
+        .map(|height| put_block(&fixture, height, height != 3, height != 2))
+        .collect();
+
+    // Block 0 is out of the range.
+    let summary = convert_bodies(fixture.tmp_dir.path(), BodyFormat::Merkle, 1..=3, true).unwrap();
+    assert_eq!(
+        summary,
+        ConversionSummary {
+            blocks: 3,
+            converted: 2,
+            already_converted: 0,
+            missing: 1,
+            unread: 1,
+        }
+    );
+
+    let env = StorageEnv::open(fixture.tmp_dir.path()).unwrap();
+    {
+        let txn = env.begin_ro_txn().unwrap();
+        let reader = LmdbReader::new(&txn);
+        for db_name in [
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            ProposerDatabase::db_name(),
+        ] {
+            assert!(reader.has_table(db_name).unwrap());
+        }
+        for (height, block_hash) in block_hashes.iter().enumerate() {
+            let header = block_iter::read_header(&reader, block_hash).unwrap();
+            let maybe_merkle_body =
+                merkle_body::read_merkle_body(&reader, header.body_hash()).unwrap();
+            let legacy_body = reader
+                .get(BlockBodyDatabase::db_name(), header.body_hash().as_ref())
+                .unwrap();
+            match height {
+                1 => {
+                    assert_eq!(maybe_merkle_body.unwrap().body, mock_body(height as u8));
+                    assert!(legacy_body.is_none());
+                }
+                2 => assert!(maybe_merkle_body.is_none() && legacy_body.is_none()),
+                // The node reads the body of block 3 from the legacy format,
+                // so it is kept next to the converted one.
+                3 => {
+                    assert_eq!(maybe_merkle_body.unwrap().body, mock_body(height as u8));
+                    assert!(legacy_body.is_some());
+                }
+                // Block 0 is out of the range.
+                _ => {
+                    assert!(maybe_merkle_body.is_none());
+                    assert!(legacy_body.is_some());
+                }
+            }
+            // The node finds the body of the converted block and of the
+            // legacy one through the version of their header.
+            assert_eq!(
+                read_node_body(&reader, &header),
+                (height % 2 == 1).then(|| mock_body(height as u8))
+            );
+        }
+    }
+    drop(env);
+
+    // Only block 3 reads its body from the legacy format, so it is the only
+    // one whose merkle body is removed.
+    let summary = convert_bodies(fixture.tmp_dir.path(), BodyFormat::Legacy, 0..=3, true).unwrap();
+    assert_eq!(
+        summary,
+        ConversionSummary {
+            blocks: 4,
+            converted: 1,
+            already_converted: 2,
+            missing: 1,
+            unread: 2,
+        }
+    );
+    let env = StorageEnv::open(fixture.tmp_dir.path()).unwrap();
+    let txn = env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    for height in [1, 3] {
+        let header = block_iter::read_header(&reader, &block_hashes[height]).unwrap();
+        assert_eq!(
+            read_node_body(&reader, &header),
+            Some(mock_body(height as u8))
+        );
+        assert!(reader
+            .get(BlockBodyDatabase::db_name(), header.body_hash().as_ref())
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            merkle_body::read_merkle_body(&reader, header.body_hash())
+                .unwrap()
+                .is_some(),
+            height == 1
+        );
+    }
+}
+
+#[test]
+fn convert_bodies_should_convert_legacy_headers_to_merkle() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Headers of protocol versions below the merkle one, as in a real chain.
+    let block_hashes: Vec<BlockHash> = (0..3)
+        .map(|height| put_block(&fixture, height, false, true))
+        .collect();
+
+    let summary = convert_bodies(fixture.tmp_dir.path(), BodyFormat::Merkle, 0..=2, true).unwrap();
+    assert_eq!(
+        summary,
+        ConversionSummary {
+            blocks: 3,
+            converted: 3,
+            already_converted: 0,
+            missing: 0,
+            unread: 3,
+        }
+    );
+
+    let env = StorageEnv::open(fixture.tmp_dir.path()).unwrap();
+    let txn = env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    for (height, block_hash) in block_hashes.iter().enumerate() {
+        let header = block_iter::read_header(&reader, block_hash).unwrap();
+        assert_eq!(
+            header.hashing_algorithm_version(),
+            HashingAlgorithmVersion::V1
+        );
+        // The merkle body is stored under the body hash of the header, and
+        // the legacy body the node reads is kept.
+        assert_eq!(
+            merkle_body::read_merkle_body(&reader, header.body_hash())
+                .unwrap()
+                .unwrap()
+                .body,
+            mock_body(height as u8)
+        );
+        assert_eq!(
+            read_node_body(&reader, &header),
+            Some(mock_body(height as u8))
+        );
+    }
+}
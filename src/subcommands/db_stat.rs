@@ -0,0 +1,112 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    human,
+    output::{self, Compression},
+};
+
+pub const COMMAND_NAME: &str = "db-stat";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when gathering the LMDB statistics of a database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database at {0}: {1}")]
+    Database(PathBuf, LmdbError),
+    /// No LMDB file was found at the given path.
+    #[error("No storage.lmdb or data.lmdb file found in {0}")]
+    NoDatabase(PathBuf),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    Human,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs the LMDB statistics of a database: the entries, pages \
+            and B-tree depth of each of its databases, and the space in use \
+            and free in its data file.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of an LMDB file, or of a directory in which the \
+                    `storage.lmdb` and `data.lmdb` files are reported.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the statistics in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(human::human_arg(DisplayOrder::Human as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let compression = Compression::from_matches(matches);
+    report::db_stat(
+        path,
+        output,
+        overwrite,
+        compression,
+        human::is_human(matches),
+    )
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use lmdb::{Environment, Error as LmdbError, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::common::{
+    db::{self, EnvTuning, STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+    human::{self, Unit},
+    lmdb_utils::{self, DbStat, EnvInfo},
+    output::{Compression, OutputWriter},
+};
+
+use super::Error;
+
+/// Fields of the statistics given in units with `--human`.
+const HUMAN_FIELDS: &[(&str, Unit)] = &[
+    ("map_size", Unit::Bytes),
+    ("used_bytes", Unit::Bytes),
+    ("free_bytes", Unit::Bytes),
+];
+
+/// LMDB statistics of a data file.
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct FileStat {
+    pub(crate) env: EnvInfo,
+    /// Bytes taken by the pages in use, including the free ones.
+    pub(crate) used_bytes: u64,
+    pub(crate) free_pages: u64,
+    pub(crate) free_bytes: u64,
+    pub(crate) databases: BTreeMap<String, DbStat>,
+}
+
+/// Returns the LMDB files to report for `path`: the file itself, or the
+/// storage and trie store files if it is a directory.
+pub(crate) fn lmdb_files(path: &Path) -> Result<Vec<PathBuf>, Error> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let files: Vec<PathBuf> = [STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME]
+        .iter()
+        .map(|file_name| path.join(file_name))
+        .filter(|file_path| file_path.is_file())
+        .collect();
+    if files.is_empty() {
+        return Err(Error::NoDatabase(path.to_path_buf()));
+    }
+    Ok(files)
+}
+
+fn collect_stat(env: &Environment) -> Result<FileStat, LmdbError> {
+    let env_info = lmdb_utils::env_info(env)?;
+    let txn = env.begin_ro_txn()?;
+    let free_pages = lmdb_utils::free_page_count(&txn)?;
+    let databases = lmdb_utils::db_stats(&txn)?;
+    txn.commit()?;
+    Ok(FileStat {
+        env: env_info,
+        used_bytes: lmdb_utils::used_size(env)?,
+        free_pages,
+        free_bytes: free_pages * env_info.page_size as u64,
+        databases,
+    })
+}
+
+/// Gathers the statistics of the LMDB file at `path`.
+pub(crate) fn file_stat(path: &Path) -> Result<FileStat, Error> {
+    db::db_env_with_tuning(path, EnvTuning::default())
+        .and_then(|env| collect_stat(&env))
+        .map_err(|lmdb_err| Error::Database(path.to_path_buf(), lmdb_err))
+}
+
+/// Writes the statistics of the LMDB files at `path` as JSON, keyed by the
+/// path of each file.
+pub(crate) fn db_stat(
+    path: &Path,
+    output: Option<&Path>,
+    overwrite: bool,
+    compression: Compression,
+    human: bool,
+) -> Result<(), Error> {
+    let mut stats = BTreeMap::new();
+    for file_path in lmdb_files(path)? {
+        let stat = file_stat(&file_path)?;
+        stats.insert(file_path.display().to_string(), stat);
+    }
+
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    human::to_writer_pretty(&mut out_writer, &stats, human, HUMAN_FIELDS)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
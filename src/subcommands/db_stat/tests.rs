@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, fs};
+
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    report::{self, FileStat},
+    Error,
+};
+use crate::{
+    common::{db::STORAGE_FILE_NAME, output::Compression},
+    test_utils::LmdbTestFixture,
+};
+
+#[test]
+fn db_stat_should_report_each_database() {
+    let fixture = LmdbTestFixture::new(vec!["big", "empty", "small"], Some(STORAGE_FILE_NAME));
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for key in 0u32..3 {
+        txn.put(
+            *fixture.db(Some("small")).unwrap(),
+            &key.to_le_bytes(),
+            &[0u8; 8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    // Values larger than a page go to overflow pages.
+    txn.put(
+        *fixture.db(Some("big")).unwrap(),
+        &[0u8],
+        &vec![1u8; 10_000],
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("db_stat.json");
+    report::db_stat(
+        fixture.tmp_dir.path(),
+        Some(&out_file_path),
+        false,
+        Compression::None,
+        false,
+    )
+    .unwrap();
+    let stats: BTreeMap<String, FileStat> =
+        serde_json::from_slice(&fs::read(&out_file_path).unwrap()).unwrap();
+    assert_eq!(stats.len(), 1);
+    let (file_path, stat) = stats.iter().next().unwrap();
+    assert!(file_path.ends_with(STORAGE_FILE_NAME));
+    assert_eq!(
+        stat.databases.keys().collect::<Vec<_>>(),
+        ["big", "empty", "small"]
+    );
+    assert_eq!(stat.databases["small"].entries, 3);
+    assert_eq!(stat.databases["small"].depth, 1);
+    assert_eq!(stat.databases["empty"].entries, 0);
+    assert_eq!(stat.databases["empty"].pages(), 0);
+    assert_eq!(stat.databases["big"].entries, 1);
+    assert!(stat.databases["big"].overflow_pages > 1);
+    assert_eq!(
+        stat.used_bytes,
+        (stat.env.last_page_number as u64 + 1) * stat.env.page_size as u64
+    );
+
+    // Without `--overwrite`, the existing output isn't replaced.
+    assert!(matches!(
+        report::db_stat(
+            fixture.tmp_dir.path(),
+            Some(&out_file_path),
+            false,
+            Compression::None,
+            false
+        ),
+        Err(Error::Output(_))
+    ));
+}
+
+#[test]
+fn db_stat_should_fail_without_database() {
+    let empty_dir = tempfile::tempdir().unwrap();
+    assert!(matches!(
+        report::lmdb_files(empty_dir.path()),
+        Err(Error::NoDatabase(_))
+    ));
+}
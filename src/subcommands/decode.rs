@@ -0,0 +1,213 @@
+mod blob;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    fs,
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use base64::DecodeError as Base64Error;
+use clap::{Arg, ArgMatches, Command};
+use hex::FromHexError;
+use log::info;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+use blob::{Encoding, ValueEncoding};
+
+pub const COMMAND_NAME: &str = "decode";
+const BLOB: &str = "blob";
+const ENCODING: &str = "encoding";
+const INPUT: &str = "input";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const TYPE: &str = "type";
+const VALUE_ENCODING: &str = "value-encoding";
+
+/// Errors encountered when decoding a blob.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The blob is not valid base64.
+    #[error("Error decoding base64 blob: {0}")]
+    Base64(#[from] Base64Error),
+    /// The blob is not valid hex.
+    #[error("Error decoding hex blob: {0}")]
+    Hex(#[from] FromHexError),
+    /// Error reading the blob from a file.
+    #[error("Error reading blob from {0}: {1}")]
+    Input(PathBuf, IoError),
+    /// The blob decoded as none of the known types.
+    #[error("Blob of {0} bytes doesn't decode as any of the known types")]
+    NoMatch(usize),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    Blob,
+    Input,
+    Encoding,
+    Type,
+    ValueEncoding,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Decodes a hex or base64 blob, such as a database value or a \
+            payload found in the logs, as the known node and global state \
+            types, in both the bincode and the bytesrepr formats. Outputs \
+            every successful decode in JSON format, or the bytes of the blob \
+            themselves.",
+        )
+        .arg(
+            Arg::new(BLOB)
+                .display_order(DisplayOrder::Blob as usize)
+                .required_unless_present(INPUT)
+                .conflicts_with(INPUT)
+                .short('b')
+                .long(BLOB)
+                .takes_value(true)
+                .value_name("BLOB")
+                .help("Blob to decode."),
+        )
+        .arg(
+            Arg::new(INPUT)
+                .display_order(DisplayOrder::Input as usize)
+                .short('i')
+                .long(INPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help("Path of a file holding the blob to decode."),
+        )
+        .arg(
+            Arg::new(ENCODING)
+                .display_order(DisplayOrder::Encoding as usize)
+                .short('e')
+                .long(ENCODING)
+                .takes_value(true)
+                .value_name("auto|hex|base64")
+                .possible_values(["auto", "hex", "base64"])
+                .default_value("auto")
+                .help(
+                    "Encoding of the blob. If `auto`, the blob is decoded as \
+                    hex if it only has hex digits, and as base64 otherwise.",
+                ),
+        )
+        .arg(
+            Arg::new(TYPE)
+                .display_order(DisplayOrder::Type as usize)
+                .short('t')
+                .long(TYPE)
+                .takes_value(true)
+                .value_name("TYPE")
+                .possible_values(blob::type_names())
+                .help("Only decode the blob as this type."),
+        )
+        .arg(
+            Arg::new(VALUE_ENCODING)
+                .display_order(DisplayOrder::ValueEncoding as usize)
+                .long(VALUE_ENCODING)
+                .takes_value(true)
+                .value_name("raw|hex|json|bytesrepr-json")
+                .possible_values(["raw", "hex", "json", "bytesrepr-json"])
+                .default_value("json")
+                .help(
+                    "Encoding of the output. `raw` outputs the bytes of the \
+                    blob untouched, for a byte-exact restore, and `hex` outputs \
+                    them in hex. `json` outputs the values the blob decodes to \
+                    in both formats, and `bytesrepr-json` only the ones it \
+                    decodes to in the bytesrepr format.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the decoded values. \
+                    If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let text = match matches.value_of(INPUT) {
+        Some(input) => {
+            fs::read_to_string(input).map_err(|io_err| Error::Input(input.into(), io_err))?
+        }
+        None => matches
+            .value_of(BLOB)
+            .expect("should have blob arg")
+            .to_string(),
+    };
+    let encoding = match matches.value_of(ENCODING) {
+        Some("hex") => Encoding::Hex,
+        Some("base64") => Encoding::Base64,
+        _ => Encoding::Auto,
+    };
+    let maybe_type_name = matches.value_of(TYPE);
+    let value_encoding = match matches.value_of(VALUE_ENCODING) {
+        Some("raw") => ValueEncoding::Raw,
+        Some("hex") => ValueEncoding::Hex,
+        Some("bytesrepr-json") => ValueEncoding::BytesreprJson,
+        _ => ValueEncoding::Json,
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let bytes = blob::parse_blob(&text, encoding)?;
+    // Decode before creating the output, so nothing is written on failure.
+    let contents = match value_encoding {
+        ValueEncoding::Raw => bytes,
+        ValueEncoding::Hex => format!("{}\n", hex::encode(&bytes)).into_bytes(),
+        ValueEncoding::Json | ValueEncoding::BytesreprJson => {
+            let decoded = blob::decode(&bytes, maybe_type_name, value_encoding.format())?;
+            if decoded.is_empty() {
+                return Err(Error::NoMatch(bytes.len()));
+            }
+            if decoded.len() > 1 {
+                info!(
+                    "Blob of {} bytes decodes as {} types.",
+                    bytes.len(),
+                    decoded.len()
+                );
+            }
+            let mut json = serde_json::to_vec_pretty(&decoded)?;
+            json.push(b'\n');
+            json
+        }
+    };
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    out_writer.write_all(&contents)?;
+    out_writer.finish()?;
+    Ok(())
+}
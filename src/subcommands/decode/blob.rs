@@ -0,0 +1,194 @@
+use bincode::Options;
+use casper_execution_engine::storage::trie::Trie;
+use casper_node::types::{
+    BlockBody, BlockHeader, BlockSignatures, Deploy, DeployMetadata, FinalizedApprovals,
+};
+use casper_types::{
+    bytesrepr::{self, FromBytes},
+    ExecutionResult, Key, StoredValue,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+use super::Error;
+
+/// Textual encoding of a blob.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Encoding {
+    /// Hex if the blob only has hex digits, base64 otherwise.
+    Auto,
+    Base64,
+    Hex,
+}
+
+/// How the value held by a blob is output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ValueEncoding {
+    /// The bytes of the blob, untouched.
+    Raw,
+    /// The bytes of the blob, in hex.
+    Hex,
+    /// The values the blob decodes to, in every format, as JSON.
+    Json,
+    /// The values the blob decodes to in the bytesrepr format, as JSON.
+    BytesreprJson,
+}
+
+impl ValueEncoding {
+    /// Returns the format the blob is decoded in for this encoding, if
+    /// restricted to one.
+    pub(crate) fn format(&self) -> Option<Format> {
+        match self {
+            ValueEncoding::BytesreprJson => Some(Format::Bytesrepr),
+            ValueEncoding::Raw | ValueEncoding::Hex | ValueEncoding::Json => None,
+        }
+    }
+}
+
+/// Binary serialization format of a decoded value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Format {
+    /// The format of the values in the storage database.
+    Bincode,
+    /// The format of the values in the global state and on the wire.
+    Bytesrepr,
+}
+
+/// A value the blob decoded to.
+#[derive(Debug, Serialize)]
+pub(crate) struct Decoded {
+    #[serde(rename = "type")]
+    pub(crate) type_name: &'static str,
+    pub(crate) format: Format,
+    pub(crate) value: Value,
+}
+
+type Decoder = fn(&[u8]) -> Result<Option<Value>, Error>;
+
+/// The types a blob is decoded as, with the formats they are stored in.
+const DECODERS: &[(&str, Format, Decoder)] = &[
+    ("deploy", Format::Bincode, bincode_decode::<Deploy>),
+    ("deploy", Format::Bytesrepr, bytesrepr_decode::<Deploy>),
+    (
+        "block-header",
+        Format::Bincode,
+        bincode_decode::<BlockHeader>,
+    ),
+    (
+        "block-header",
+        Format::Bytesrepr,
+        bytesrepr_decode::<BlockHeader>,
+    ),
+    ("block-body", Format::Bincode, bincode_decode::<BlockBody>),
+    (
+        "block-body",
+        Format::Bytesrepr,
+        bytesrepr_decode::<BlockBody>,
+    ),
+    (
+        "block-signatures",
+        Format::Bincode,
+        bincode_decode::<BlockSignatures>,
+    ),
+    (
+        "deploy-metadata",
+        Format::Bincode,
+        bincode_decode::<DeployMetadata>,
+    ),
+    (
+        "finalized-approvals",
+        Format::Bincode,
+        bincode_decode::<FinalizedApprovals>,
+    ),
+    (
+        "execution-result",
+        Format::Bincode,
+        bincode_decode::<ExecutionResult>,
+    ),
+    (
+        "execution-result",
+        Format::Bytesrepr,
+        bytesrepr_decode::<ExecutionResult>,
+    ),
+    (
+        "trie",
+        Format::Bytesrepr,
+        bytesrepr_decode::<Trie<Key, StoredValue>>,
+    ),
+    (
+        "stored-value",
+        Format::Bytesrepr,
+        bytesrepr_decode::<StoredValue>,
+    ),
+];
+
+/// Returns the names of the types a blob can be decoded as.
+pub(crate) fn type_names() -> Vec<&'static str> {
+    let mut type_names: Vec<&'static str> = DECODERS.iter().map(|(name, ..)| *name).collect();
+    type_names.dedup();
+    type_names
+}
+
+/// Decodes the textual `blob`, trimming surrounding whitespace and quotes
+/// and a `0x` prefix.
+pub(crate) fn parse_blob(blob: &str, encoding: Encoding) -> Result<Vec<u8>, Error> {
+    let blob = blob.trim().trim_matches('"');
+    let hex_blob = blob.strip_prefix("0x").unwrap_or(blob);
+    let is_hex =
+        hex_blob.len().is_multiple_of(2) && hex_blob.bytes().all(|byte| byte.is_ascii_hexdigit());
+    match encoding {
+        Encoding::Hex => Ok(hex::decode(hex_blob)?),
+        Encoding::Auto if is_hex => Ok(hex::decode(hex_blob)?),
+        Encoding::Auto | Encoding::Base64 => Ok(base64::decode(blob)?),
+    }
+}
+
+/// Bincode decodes `bytes` with the options of the storage database,
+/// failing if any byte is left over. The size limit keeps a corrupt length
+/// prefix from allocating more than the blob could hold.
+fn bincode_decode<T: DeserializeOwned + Serialize>(bytes: &[u8]) -> Result<Option<Value>, Error> {
+    match bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(bytes.len() as u64)
+        .reject_trailing_bytes()
+        .deserialize::<T>(bytes)
+    {
+        Ok(value) => Ok(Some(serde_json::to_value(value)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Bytesrepr decodes `bytes`, failing if any byte is left over.
+fn bytesrepr_decode<T: FromBytes + Serialize>(bytes: &[u8]) -> Result<Option<Value>, Error> {
+    match bytesrepr::deserialize::<T>(bytes.to_vec()) {
+        Ok(value) => Ok(Some(serde_json::to_value(value)?)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Decodes `bytes` as every known type, or only as `maybe_type_name`, in
+/// every format, or only in `maybe_format`, returning the values it decoded
+/// to.
+pub(crate) fn decode(
+    bytes: &[u8],
+    maybe_type_name: Option<&str>,
+    maybe_format: Option<Format>,
+) -> Result<Vec<Decoded>, Error> {
+    let mut decoded = vec![];
+    for (type_name, format, decoder) in DECODERS {
+        if maybe_type_name.is_some_and(|name| name != *type_name)
+            || maybe_format.is_some_and(|wanted_format| wanted_format != *format)
+        {
+            continue;
+        }
+        if let Some(value) = decoder(bytes)? {
+            decoded.push(Decoded {
+                type_name,
+                format: *format,
+                value,
+            });
+        }
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,89 @@
+use casper_types::{bytesrepr::ToBytes, TimeDiff, Timestamp};
+
+use super::{
+    blob::{self, Encoding, Format, ValueEncoding},
+    Error,
+};
+use crate::test_utils;
+
+#[test]
+fn parse_blob_should_detect_encoding() {
+    let bytes = vec![0xde, 0xad, 0xbe, 0xef];
+    assert_eq!(
+        blob::parse_blob(" 0xdeadbeef\n", Encoding::Auto).unwrap(),
+        bytes
+    );
+    assert_eq!(
+        blob::parse_blob("\"DEADBEEF\"", Encoding::Auto).unwrap(),
+        bytes
+    );
+    assert_eq!(
+        blob::parse_blob(&base64::encode(&bytes), Encoding::Auto).unwrap(),
+        bytes
+    );
+    // Valid hex, but forced to be decoded as base64.
+    assert_eq!(
+        blob::parse_blob("deadbeef", Encoding::Base64).unwrap(),
+        base64::decode("deadbeef").unwrap()
+    );
+    assert!(matches!(
+        blob::parse_blob("not a blob", Encoding::Hex),
+        Err(Error::Hex(_))
+    ));
+    assert!(matches!(
+        blob::parse_blob("not a blob", Encoding::Auto),
+        Err(Error::Base64(_))
+    ));
+}
+
+#[test]
+fn decode_should_find_deploy_in_both_formats() {
+    let deploy = test_utils::mock_deploy(Timestamp::from(1_000), TimeDiff::from_seconds(60));
+    let expected_value = serde_json::to_value(&deploy).unwrap();
+
+    let bincode_bytes = bincode::serialize(&deploy).unwrap();
+    let decoded = blob::decode(&bincode_bytes, None, None).unwrap();
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].type_name, "deploy");
+    assert_eq!(decoded[0].format, Format::Bincode);
+    assert_eq!(decoded[0].value, expected_value);
+
+    let bytesrepr_bytes = deploy.to_bytes().unwrap();
+    let decoded = blob::decode(&bytesrepr_bytes, None, None).unwrap();
+    assert_eq!(decoded.len(), 1);
+    assert_eq!(decoded[0].type_name, "deploy");
+    assert_eq!(decoded[0].format, Format::Bytesrepr);
+    assert_eq!(decoded[0].value, expected_value);
+
+    // Only bytesrepr decodes are kept for the `bytesrepr-json` encoding.
+    let bytesrepr_format = ValueEncoding::BytesreprJson.format();
+    assert!(blob::decode(&bincode_bytes, None, bytesrepr_format)
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        blob::decode(&bytesrepr_bytes, None, bytesrepr_format)
+            .unwrap()
+            .len(),
+        1
+    );
+
+    // Restricted to another type, or with trailing bytes, nothing matches.
+    assert!(blob::decode(&bincode_bytes, Some("block-header"), None)
+        .unwrap()
+        .is_empty());
+    let mut trailing_bytes = bincode_bytes;
+    trailing_bytes.push(0);
+    assert!(blob::decode(&trailing_bytes, Some("deploy"), None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn decode_should_find_block_header() {
+    let (_, header) = test_utils::mock_block_header(3);
+    let bytes = bincode::serialize(&header).unwrap();
+    let decoded = blob::decode(&bytes, None, None).unwrap();
+    assert!(decoded
+        .iter()
+        .any(|decoded| decoded.type_name == "block-header" && decoded.format == Format::Bincode));
+}
@@ -0,0 +1,170 @@
+mod graph;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use casper_node::types::DeployHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+use graph::Format;
+
+pub const COMMAND_NAME: &str = "deploy-graph";
+const DB_PATH: &str = "db-path";
+const FORMAT: &str = "format";
+const FROM_HEIGHT: &str = "from-height";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const TO_HEIGHT: &str = "to-height";
+
+/// Errors encountered when extracting the deploy dependency graph.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// The height range is empty.
+    #[error("Invalid height range: {0} is above {1}")]
+    InvalidRange(u64, u64),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on a deploy.
+    #[error("Error parsing deploy {0}: {1}")]
+    Parsing(DeployHash, BincodeError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    FromHeight,
+    ToHeight,
+    Format,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+fn height_arg(name: &'static str, short: char, display_order: DisplayOrder) -> Arg<'static> {
+    Arg::new(name)
+        .display_order(display_order as usize)
+        .required(false)
+        .short(short)
+        .long(name)
+        .takes_value(true)
+        .value_name("HEIGHT")
+        .validator(|value| value.parse::<u64>().map(|_| ()))
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Extracts the dependencies declared in the headers of the deploys \
+            of a range of blocks, and outputs the graph of the deploys linked \
+            by them in JSON or DOT format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            height_arg(FROM_HEIGHT, 'f', DisplayOrder::FromHeight)
+                .help("Height of the lowest block to read. Defaults to the genesis block."),
+        )
+        .arg(
+            height_arg(TO_HEIGHT, 't', DisplayOrder::ToHeight)
+                .help("Height of the highest block to read. Defaults to the highest block."),
+        )
+        .arg(
+            Arg::new(FORMAT)
+                .display_order(DisplayOrder::Format as usize)
+                .long(FORMAT)
+                .takes_value(true)
+                .value_name("json|dot")
+                .possible_values(["json", "dot"])
+                .default_value("json")
+                .help(
+                    "Format of the output. The DOT output only has the graph, \
+                    without the counts of blocks and deploys read.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the graph. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let height = |name| {
+        matches
+            .value_of(name)
+            .map(|value| value.parse::<u64>().expect("should be validated"))
+    };
+    let from = height(FROM_HEIGHT).unwrap_or_default();
+    let to = height(TO_HEIGHT).unwrap_or(u64::MAX);
+    if from > to {
+        return Err(Error::InvalidRange(from, to));
+    }
+    let format = match matches.value_of(FORMAT) {
+        Some("dot") => Format::Dot,
+        _ => Format::Json,
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let graph = graph::deploy_graph(path, from..=to)?;
+    graph::write_graph(&graph, format, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
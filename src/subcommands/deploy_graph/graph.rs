@@ -0,0 +1,205 @@
+use std::{collections::BTreeMap, io::Write, ops::RangeInclusive, path::Path, result::Result};
+
+use casper_node::types::{BlockHash, Deploy, DeployHash};
+use casper_types::PublicKey;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    block_iter,
+    db::{BlockHeaderDatabase, Database, DeployDatabase, StorageEnv},
+    progress::ProgressTracker,
+    storage::{LmdbReader, StorageReader},
+};
+
+use super::Error;
+
+/// Number of hex digits of the deploy hashes labelling the DOT nodes.
+const DOT_LABEL_DIGITS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Dot,
+}
+
+/// A deploy linked to others by a dependency. The block fields are only
+/// set for the deploys executed within the height range.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct DeployNode {
+    pub(crate) deploy_hash: DeployHash,
+    pub(crate) account: Option<PublicKey>,
+    pub(crate) block_hash: Option<BlockHash>,
+    pub(crate) height: Option<u64>,
+}
+
+/// A dependency of a deploy on another one.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Edge {
+    pub(crate) deploy_hash: DeployHash,
+    pub(crate) dependency: DeployHash,
+}
+
+/// The deploys of a range of blocks which depend on others or are depended
+/// on, and the dependencies between them.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct DeployGraph {
+    pub(crate) blocks: usize,
+    pub(crate) deploys: usize,
+    pub(crate) missing_deploys: usize,
+    pub(crate) nodes: Vec<DeployNode>,
+    pub(crate) edges: Vec<Edge>,
+}
+
+impl DeployGraph {
+    fn write_dot<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), Error> {
+        writeln!(writer, "digraph deploys {{")?;
+        for node in self.nodes.iter() {
+            let id = hex::encode(node.deploy_hash);
+            let location = match node.height {
+                Some(height) => format!("height {}", height),
+                None => "out of range".to_string(),
+            };
+            writeln!(
+                writer,
+                "  \"{}\" [label=\"{}\\n{}\"];",
+                id,
+                &id[..DOT_LABEL_DIGITS],
+                location
+            )?;
+        }
+        for edge in self.edges.iter() {
+            writeln!(
+                writer,
+                "  \"{}\" -> \"{}\";",
+                hex::encode(edge.deploy_hash),
+                hex::encode(edge.dependency)
+            )?;
+        }
+        writeln!(writer, "}}")?;
+        Ok(())
+    }
+}
+
+/// Reads the dependencies of the deploys of the blocks within `heights` in
+/// the storage database at `db_path`. Dependencies on deploys executed
+/// outside of the range are kept, without their block.
+pub(crate) fn deploy_graph<P: AsRef<Path>>(
+    db_path: P,
+    heights: RangeInclusive<u64>,
+) -> Result<DeployGraph, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+    let blocks: Vec<_> = if reader.has_table(BlockHeaderDatabase::db_name())? {
+        block_iter::blocks_by_height(&reader)?
+            .into_iter()
+            .filter(|(height, _)| heights.contains(height))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut graph = DeployGraph {
+        blocks: blocks.len(),
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Graph extraction {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    // Deploys executed within the range, with their account and block.
+    let mut executed: BTreeMap<DeployHash, (PublicKey, BlockHash, u64)> = BTreeMap::new();
+    for (height, block_hash) in blocks {
+        let header = block_iter::read_header(&reader, &block_hash)?;
+        match block_iter::read_body(&reader, &block_hash, &header)? {
+            Some(body) => {
+                for deploy_hash in body.deploy_hashes.iter().chain(body.transfer_hashes.iter()) {
+                    let deploy: Deploy =
+                        match reader.get(DeployDatabase::db_name(), deploy_hash.as_ref())? {
+                            Some(raw_deploy) => bincode::deserialize(&raw_deploy)
+                                .map_err(|bincode_err| Error::Parsing(*deploy_hash, bincode_err))?,
+                            None => {
+                                warn!("Missing deploy {} of block {}.", deploy_hash, block_hash);
+                                graph.missing_deploys += 1;
+                                continue;
+                            }
+                        };
+                    graph.deploys += 1;
+                    for dependency in deploy.header().dependencies() {
+                        graph.edges.push(Edge {
+                            deploy_hash: *deploy_hash,
+                            dependency: *dependency,
+                        });
+                    }
+                    executed.insert(
+                        *deploy_hash,
+                        (deploy.header().account().clone(), block_hash, height),
+                    );
+                }
+            }
+            None => warn!("Block {} has no body, skipping it.", block_hash),
+        }
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+
+    let mut linked: Vec<DeployHash> = graph
+        .edges
+        .iter()
+        .flat_map(|edge| [edge.deploy_hash, edge.dependency])
+        .collect();
+    linked.sort_unstable();
+    linked.dedup();
+    graph.nodes = linked
+        .into_iter()
+        .map(|deploy_hash| match executed.remove(&deploy_hash) {
+            Some((account, block_hash, height)) => DeployNode {
+                deploy_hash,
+                account: Some(account),
+                block_hash: Some(block_hash),
+                height: Some(height),
+            },
+            None => DeployNode {
+                deploy_hash,
+                account: None,
+                block_hash: None,
+                height: None,
+            },
+        })
+        .collect();
+    info!(
+        "Found {} dependencies between {} of the {} deploys of {} blocks.",
+        graph.edges.len(),
+        graph.nodes.len(),
+        graph.deploys,
+        graph.blocks
+    );
+    Ok(graph)
+}
+
+/// Writes `graph` to `writer` in the given format.
+pub(crate) fn write_graph<W: Write + ?Sized>(
+    graph: &DeployGraph,
+    format: Format,
+    writer: &mut W,
+) -> Result<(), Error> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *writer, graph)?;
+            writeln!(writer)?;
+        }
+        Format::Dot => graph.write_dot(writer)?,
+    }
+    Ok(())
+}
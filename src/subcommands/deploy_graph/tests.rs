@@ -0,0 +1,137 @@
+use casper_node::types::{BlockHash, Deploy, DeployHash};
+use casper_types::Timestamp;
+use lmdb::{Transaction, WriteFlags};
+
+use super::graph::{self, DeployNode, Edge, Format};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn mock_deploy(idx: u64, dependencies: Vec<DeployHash>) -> Deploy {
+    test_utils::mock_deploy_with_dependencies(
+        Timestamp::from(idx),
+        "1h".parse().unwrap(),
+        dependencies,
+    )
+}
+
+/// Stores the block at `height` executing `deploys`.
+fn put_block(fixture: &LmdbTestFixture, height: u8, deploys: &[&Deploy]) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    let deploy_hashes = deploys.iter().map(|deploy| *deploy.id()).collect();
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &header.body_hash,
+        &bincode::serialize(&BlockBody::new(deploy_hashes)).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    for deploy in deploys {
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            deploy.id(),
+            &bincode::serialize(deploy).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    block_hash
+}
+
+#[test]
+fn deploy_graph_should_link_dependencies() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Deploy 1 depends on deploy 0, deploy 3 on deploys 1 and 2, and
+    // deploy 4 on nothing.
+    let deploy_0 = mock_deploy(0, vec![]);
+    let deploy_1 = mock_deploy(1, vec![*deploy_0.id()]);
+    let deploy_2 = mock_deploy(2, vec![]);
+    let deploy_3 = mock_deploy(3, vec![*deploy_1.id(), *deploy_2.id()]);
+    let deploy_4 = mock_deploy(4, vec![]);
+    put_block(&fixture, 0, &[&deploy_0]);
+    let block_1 = put_block(&fixture, 1, &[&deploy_1, &deploy_4]);
+    let block_2 = put_block(&fixture, 2, &[&deploy_2, &deploy_3]);
+
+    // Deploy 0 is depended on but executed out of range.
+    let graph = graph::deploy_graph(fixture.tmp_dir.path(), 1..=2).unwrap();
+    assert_eq!(graph.blocks, 2);
+    assert_eq!(graph.deploys, 4);
+    assert_eq!(graph.missing_deploys, 0);
+    let mut expected_edges = vec![
+        Edge {
+            deploy_hash: *deploy_1.id(),
+            dependency: *deploy_0.id(),
+        },
+        Edge {
+            deploy_hash: *deploy_3.id(),
+            dependency: *deploy_1.id(),
+        },
+        Edge {
+            deploy_hash: *deploy_3.id(),
+            dependency: *deploy_2.id(),
+        },
+    ];
+    // Edges are in execution order, the order of the deploys in a block
+    // being kept.
+    assert_eq!(graph.edges, expected_edges);
+    let account = deploy_1.header().account().clone();
+    let node = |deploy: &Deploy, maybe_block: Option<(BlockHash, u64)>| DeployNode {
+        deploy_hash: *deploy.id(),
+        account: maybe_block.map(|_| account.clone()),
+        block_hash: maybe_block.map(|(block_hash, _)| block_hash),
+        height: maybe_block.map(|(_, height)| height),
+    };
+    let mut expected_nodes = vec![
+        node(&deploy_0, None),
+        node(&deploy_1, Some((block_1, 1))),
+        node(&deploy_2, Some((block_2, 2))),
+        node(&deploy_3, Some((block_2, 2))),
+    ];
+    expected_nodes.sort_unstable_by_key(|node| node.deploy_hash);
+    assert_eq!(graph.nodes, expected_nodes);
+
+    let mut dot = vec![];
+    graph::write_graph(&graph, Format::Dot, &mut dot).unwrap();
+    let dot = String::from_utf8(dot).unwrap();
+    assert!(dot.starts_with("digraph deploys {\n"));
+    assert!(dot.ends_with("}\n"));
+    let deploy_0_id = hex::encode(deploy_0.id());
+    assert!(dot.contains(&format!(
+        "\"{}\" [label=\"{}\\nout of range\"];",
+        deploy_0_id,
+        &deploy_0_id[..10]
+    )));
+    assert!(dot.contains(&format!(
+        "\"{}\" -> \"{}\";",
+        hex::encode(deploy_1.id()),
+        deploy_0_id
+    )));
+    assert_eq!(dot.matches(" -> ").count(), 3);
+
+    // Only deploy 3 and its dependencies are linked in the last block.
+    let graph = graph::deploy_graph(fixture.tmp_dir.path(), 2..=2).unwrap();
+    expected_edges.remove(0);
+    assert_eq!(graph.edges, expected_edges);
+    assert_eq!(graph.nodes.len(), 3);
+}
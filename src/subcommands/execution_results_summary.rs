@@ -1,5 +1,5 @@
 pub(crate) mod block_body;
-mod read_db;
+pub(crate) mod read_db;
 mod summary;
 #[cfg(test)]
 mod tests;
@@ -13,21 +13,50 @@ use lmdb::Error as LmdbError;
 use serde_json::Error as JsonSerializationError;
 use thiserror::Error as ThisError;
 
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    coverage,
+    db::{self, BlockBodyDatabase, BlockHeaderDatabase, Database, EnvTuning},
+    human,
+    merkle_body::Error as MerkleBodyError,
+    output::{self, Compression},
+    schema::{self, SchemaVersion},
+    storage::{self, Backend, Error as StorageError, Storage},
+    throttle, value_guard,
+};
+use summary::CHUNK_SIZE_BYTES;
+
 pub const COMMAND_NAME: &str = "execution-results-summary";
+const CHUNK_SIZE: &str = "chunk-size";
 const DB_PATH: &str = "db-path";
 const OVERWRITE: &str = "overwrite";
 const OUTPUT: &str = "output";
+const TOP: &str = "top";
+const WINDOW: &str = "window";
 
 /// Errors encountered when operating on the storage database.
 #[derive(Debug, ThisError)]
 pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
     /// Database operation error.
     #[error("Error operating the database: {0}")]
-    Database(#[from] LmdbError),
+    Database(#[from] StorageError),
     #[error("Error deserializing raw key of block header DB element: {0}")]
     InvalidKey(usize),
     #[error("Error serializing output: {0}")]
     JsonSerialize(#[from] JsonSerializationError),
+    /// Error reading the merkle body of a block.
+    #[error("Error reading merkle body of block {0}: {1}")]
+    MerkleBody(BlockHash, MerkleBodyError),
+    /// Missing entry for a block in the database.
+    #[error("Missing element for block hash {0} in {1} DB")]
+    Missing(BlockHash, String),
     #[error("Error writing output: {0}")]
     Output(#[from] IoError),
     /// Parsing error on entry at index in the database.
@@ -37,10 +66,53 @@ pub enum Error {
     Serialize(#[from] BincodeError),
 }
 
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+impl From<BlockIterError> for Error {
+    fn from(block_err: BlockIterError) -> Self {
+        match block_err {
+            BlockIterError::Database(storage_err) => Error::Database(storage_err),
+            BlockIterError::BodyParsing(block_hash, bincode_err) => Error::Parsing(
+                block_hash,
+                BlockBodyDatabase::db_name().to_string(),
+                bincode_err,
+            ),
+            BlockIterError::HeaderParsing(block_hash, bincode_err) => Error::Parsing(
+                block_hash,
+                BlockHeaderDatabase::db_name().to_string(),
+                bincode_err,
+            ),
+            BlockIterError::InvalidKey(idx) => Error::InvalidKey(idx),
+            BlockIterError::MerkleBody(block_hash, merkle_body_err) => {
+                Error::MerkleBody(block_hash, merkle_body_err)
+            }
+            BlockIterError::MissingHeader(block_hash) => {
+                Error::Missing(block_hash, BlockHeaderDatabase::db_name().to_string())
+            }
+        }
+    }
+}
+
 enum DisplayOrder {
     DbPath,
     Output,
     Overwrite,
+    Compress,
+    ChunkSize,
+    Top,
+    Window,
+    SchemaVersion,
+    Human,
+    Partial,
+    Backend,
+    IoNice,
+    Throttle,
+    MaxValueSize,
+    EnvTuning,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -58,7 +130,10 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .long(DB_PATH)
                 .takes_value(true)
                 .value_name("DB_PATH")
-                .help("Path of the directory with the `storage.lmdb` file."),
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
         )
         .arg(
             Arg::new(OUTPUT)
@@ -66,10 +141,13 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .short('o')
                 .long(OUTPUT)
                 .takes_value(true)
-                .value_name("FILE_PATH")
+                .value_name("FILE_PATH|URL")
                 .help(
-                    "Path to where the program will output the summary. \
-                    If unspecified, defaults to standard output.",
+                    "Path to where the program will output the summary, \
+                    optionally prefixed with `file://`, `-` for standard \
+                    output, or an `http://` or `https://` URL to post it to, \
+                    retrying on failures. If unspecified, defaults to \
+                    standard output.",
                 ),
         )
         .arg(
@@ -85,11 +163,123 @@ pub fn command(display_order: usize) -> Command<'static> {
                     directory.",
                 ),
         )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(CHUNK_SIZE)
+                .display_order(DisplayOrder::ChunkSize as usize)
+                .required(false)
+                .long(CHUNK_SIZE)
+                .takes_value(true)
+                .value_name("BYTES")
+                .validator(|value| match value.parse::<usize>() {
+                    Ok(0) => Err("chunk size must be greater than 0".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Size of the chunks execution results are split into when \
+                    computing chunk counts, in bytes. Defaults to 8 MiB.",
+                ),
+        )
+        .arg(
+            Arg::new(TOP)
+                .display_order(DisplayOrder::Top as usize)
+                .required(false)
+                .short('t')
+                .long(TOP)
+                .takes_value(true)
+                .value_name("N")
+                .validator(|value| value.parse::<usize>().map(|_| ()))
+                .help(
+                    "Include in the summary the N blocks with the largest execution \
+                    results, along with their height, hash, size, chunk count and \
+                    deploy count.",
+                ),
+        )
+        .arg(
+            Arg::new(WINDOW)
+                .display_order(DisplayOrder::Window as usize)
+                .required(false)
+                .long(WINDOW)
+                .takes_value(true)
+                .value_name("N_BLOCKS")
+                .conflicts_with(TOP)
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(0) => Err("window must be greater than 0".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Output a JSON array with the statistics of each window of \
+                    N_BLOCKS consecutive heights instead of a single summary, \
+                    to see how execution results evolved over time.",
+                ),
+        )
+        .arg(schema::schema_version_arg(
+            DisplayOrder::SchemaVersion as usize,
+        ))
+        .arg(human::human_arg(DisplayOrder::Human as usize))
+        .arg(coverage::partial_arg(DisplayOrder::Partial as usize))
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+        .arg(throttle::io_nice_arg(DisplayOrder::IoNice as usize))
+        .arg(throttle::throttle_arg(DisplayOrder::Throttle as usize))
+        .arg(value_guard::max_value_arg(
+            DisplayOrder::MaxValueSize as usize,
+        ))
+        .args(db::tuning_args(
+            DisplayOrder::EnvTuning as usize,
+            EnvTuning::SEQUENTIAL,
+        ))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
-    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    throttle::init_from_matches(matches);
+    value_guard::init_from_matches(matches);
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let path = db_dir.path();
     let output = matches.value_of(OUTPUT).map(Path::new);
     let overwrite = matches.is_present(OVERWRITE);
-    read_db::execution_results_summary(path, output, overwrite)
+    let compression = Compression::from_matches(matches);
+    let chunk_size = matches
+        .value_of(CHUNK_SIZE)
+        .map(|value| value.parse().expect("should be validated"))
+        .unwrap_or(CHUNK_SIZE_BYTES);
+    let top_count = matches
+        .value_of(TOP)
+        .map(|value| value.parse().expect("should be validated"))
+        .unwrap_or_default();
+    let storage = Storage::open(
+        path,
+        Backend::from_matches(matches),
+        EnvTuning::from_matches(matches),
+    )?;
+    if let Some(window_size) = matches
+        .value_of(WINDOW)
+        .map(|value| value.parse().expect("should be validated"))
+    {
+        return read_db::windowed_execution_results_summary(
+            &storage,
+            output,
+            overwrite,
+            compression,
+            chunk_size,
+            window_size,
+            human::is_human(matches),
+            coverage::is_partial(matches),
+        );
+    }
+    read_db::execution_results_summary(
+        &storage,
+        output,
+        overwrite,
+        compression,
+        chunk_size,
+        top_count,
+        SchemaVersion::from_matches(matches),
+        human::is_human(matches),
+        coverage::is_partial(matches),
+    )
 }
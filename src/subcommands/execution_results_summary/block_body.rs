@@ -3,7 +3,7 @@
 use std::fmt::{Display, Formatter, Result as FmtResult};
 
 use casper_hashing::Digest;
-use casper_node::types::DeployHash;
+use casper_node::types::{BlockBody as NodeBlockBody, DeployHash, HashingAlgorithmVersion};
 use casper_types::PublicKey;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
@@ -30,10 +30,39 @@ impl BlockBody {
         }
     }
 
+    /// Creates a body from its parts, as read from the merkle body databases.
+    pub(crate) fn from_parts(
+        proposer: PublicKey,
+        deploy_hashes: Vec<DeployHash>,
+        transfer_hashes: Vec<DeployHash>,
+    ) -> Self {
+        BlockBody {
+            proposer,
+            deploy_hashes,
+            transfer_hashes,
+            hash: OnceCell::new(),
+        }
+    }
+
+    /// Retrieves the public key of the validator which proposed the block.
+    pub(crate) fn proposer(&self) -> &PublicKey {
+        &self.proposer
+    }
+
     /// Retrieves the deploy hashes within the block.
     pub(crate) fn deploy_hashes(&self) -> &Vec<DeployHash> {
         &self.deploy_hashes
     }
+
+    /// Computes the hash of the body under the given hashing algorithm
+    /// version. The body of `casper-node` can't be built outside of the
+    /// crate, but it shares the encoding of this one.
+    pub(crate) fn hash(&self, version: HashingAlgorithmVersion) -> Digest {
+        let raw_body = bincode::serialize(self).expect("should serialize block body");
+        let node_body: NodeBlockBody =
+            bincode::deserialize(&raw_body).expect("should deserialize block body");
+        node_body.hash(version)
+    }
 }
 
 impl Display for BlockBody {
@@ -1,158 +1,297 @@
-use std::{
-    fs::OpenOptions,
-    io::{self, Write},
-    path::Path,
-    result::Result,
-};
+use std::{collections::BTreeMap, io::Write, path::Path, result::Result};
 
-use lmdb::{Cursor, Environment, Transaction};
 use log::{info, warn};
 use serde_json::{self, Error as JsonSerializationError};
 
 use casper_node::types::{BlockHash, BlockHeader, DeployMetadata};
+use casper_types::ExecutionResult;
 
 use crate::common::{
-    db::{
-        self, BlockBodyDatabase, BlockHeaderDatabase, Database, DeployMetadataDatabase,
-        STORAGE_FILE_NAME,
-    },
-    lmdb_utils,
+    block_iter::{self, BlockIterator},
+    coverage::Coverage,
+    db::{BlockBodyDatabase, Database, DeployMetadataDatabase},
+    human::{self, Unit},
+    output::{Compression, OutputWriter},
     progress::ProgressTracker,
+    schema::SchemaVersion,
+    storage::{Storage, StorageReader},
+    value_guard,
 };
 
 use super::{
     block_body::BlockBody,
-    summary::{ExecutionResultsStats, ExecutionResultsSummary},
+    summary::{ExecutionResultsStats, ExecutionResultsSummary, WindowSummary},
     Error,
 };
 
-fn get_execution_results_stats(
-    env: &Environment,
+/// Goes through all the blocks in the database in height order, passing each block's hash,
+/// header, deploy count and execution results to `visit`. Fails on the first block lacking
+/// its body or execution results, unless `maybe_coverage` is set, in which case such blocks
+/// are skipped and recorded in it.
+pub(crate) fn for_each_block<F>(
+    storage: &Storage,
     log_progress: bool,
-) -> Result<ExecutionResultsStats, Error> {
-    let txn = env.begin_ro_txn()?;
-    let block_header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name()))? };
-    let block_body_db = unsafe { txn.open_db(Some(BlockBodyDatabase::db_name()))? };
-    let deploy_metadata_db = unsafe { txn.open_db(Some(DeployMetadataDatabase::db_name()))? };
+    maybe_coverage: Option<&mut Coverage>,
+    visit: F,
+) -> Result<(), Error>
+where
+    F: FnMut(BlockHash, &BlockHeader, usize, Vec<ExecutionResult>) -> Result<(), Error>,
+{
+    read_blocks(&storage.view()?, log_progress, maybe_coverage, visit)
+}
 
-    let maybe_entry_count = lmdb_utils::entry_count(&txn, block_header_db).ok();
-    let mut maybe_progress_tracker = None;
+/// Reads the execution results of the deploys of a block. Returns `None` if
+/// the metadata of any of them is missing, empty or oversized.
+fn read_execution_results<R: StorageReader>(
+    reader: &R,
+    block_hash: BlockHash,
+    block_body: &BlockBody,
+) -> Result<Option<Vec<ExecutionResult>>, Error> {
+    // Set of execution results of this block.
+    let mut execution_results = vec![];
 
-    let mut stats = ExecutionResultsStats::default();
-    if let Ok(mut cursor) = txn.open_ro_cursor(block_header_db) {
-        if log_progress {
-            match maybe_entry_count {
-                Some(entry_count) => {
-                    match ProgressTracker::new(
-                        entry_count,
-                        Box::new(|completion| {
-                            info!("Database parsing {}% complete...", completion)
-                        }),
-                    ) {
-                        Ok(progress_tracker) => maybe_progress_tracker = Some(progress_tracker),
-                        Err(progress_tracker_error) => warn!(
-                            "Couldn't initialize progress tracker: {}",
-                            progress_tracker_error
-                        ),
-                    }
-                }
-                None => warn!("Unable to count db entries, progress will not be logged."),
-            }
+    // Go through all the deploys in this block and get the execution
+    // result of each one.
+    for deploy_hash in block_body.deploy_hashes() {
+        // Get this deploy's metadata.
+        let metadata_raw =
+            match reader.get(DeployMetadataDatabase::db_name(), deploy_hash.as_ref())? {
+                Some(metadata_raw) => metadata_raw,
+                None => return Ok(None),
+            };
+        if !value_guard::is_parsable(
+            DeployMetadataDatabase::db_name(),
+            deploy_hash.as_ref(),
+            &metadata_raw,
+        ) {
+            return Ok(None);
         }
-
-        // Go through all the block headers in the database.
-        for (idx, (block_hash_raw, raw_val)) in cursor.iter().enumerate() {
-            // Deserialize the block hash.
-            let block_hash = BlockHash::new(
-                block_hash_raw
-                    .try_into()
-                    .map_err(|_| Error::InvalidKey(idx))?,
-            );
-            // Deserialize the header.
-            let header: BlockHeader = bincode::deserialize(raw_val).map_err(|bincode_err| {
+        let mut metadata: DeployMetadata =
+            bincode::deserialize(&metadata_raw).map_err(|bincode_err| {
                 Error::Parsing(
                     block_hash,
-                    BlockHeaderDatabase::db_name().to_string(),
+                    DeployMetadataDatabase::db_name().to_string(),
                     bincode_err,
                 )
             })?;
-            // Get the body hash for this block.
-            let block_body_raw = txn.get(block_body_db, header.body_hash())?;
-            // Get the body of this block.
-            let block_body: BlockBody =
-                bincode::deserialize(block_body_raw).map_err(|bincode_err| {
-                    Error::Parsing(
-                        block_hash,
-                        BlockBodyDatabase::db_name().to_string(),
-                        bincode_err,
-                    )
-                })?;
-
-            // Set of execution results of this block.
-            let mut execution_results = vec![];
-
-            // Go through all the deploys in this block and get the execution
-            // result of each one.
-            for deploy_hash in block_body.deploy_hashes() {
-                // Get this deploy's metadata.
-                let metadata_raw = txn.get(deploy_metadata_db, &deploy_hash)?;
-                let mut metadata: DeployMetadata =
-                    bincode::deserialize(metadata_raw).map_err(|bincode_err| {
-                        Error::Parsing(
-                            block_hash,
-                            DeployMetadataDatabase::db_name().to_string(),
-                            bincode_err,
-                        )
-                    })?;
-                // Extract the execution result of this deploy for the current block.
-                if let Some(execution_result) = metadata.execution_results.remove(&block_hash) {
-                    // Add it to this block's set of execution results.
-                    execution_results.push(execution_result);
-                }
+        // Extract the execution result of this deploy for the current block.
+        if let Some(execution_result) = metadata.execution_results.remove(&block_hash) {
+            // Add it to this block's set of execution results.
+            execution_results.push(execution_result);
+        }
+    }
+    Ok(Some(execution_results))
+}
+
+/// Goes through all the blocks `reader` reads from, as described in
+/// [`for_each_block`].
+pub(crate) fn read_blocks<R, F>(
+    reader: &R,
+    log_progress: bool,
+    mut maybe_coverage: Option<&mut Coverage>,
+    mut visit: F,
+) -> Result<(), Error>
+where
+    R: StorageReader,
+    F: FnMut(BlockHash, &BlockHeader, usize, Vec<ExecutionResult>) -> Result<(), Error>,
+{
+    // Headers-only databases may lack the body and metadata tables
+    // altogether.
+    let mut has_metadata = true;
+    if let Some(coverage) = maybe_coverage.as_mut() {
+        if !reader.has_table(BlockBodyDatabase::db_name())? {
+            for (height, _block_hash) in block_iter::blocks_by_height(reader)? {
+                coverage.record_missing_body(height);
             }
+            return Ok(());
+        }
+        has_metadata = reader.has_table(DeployMetadataDatabase::db_name())?;
+    }
+    let blocks = BlockIterator::new(reader)?;
 
-            // Update the statistics with this block's execution results.
-            stats.feed(execution_results)?;
+    let mut maybe_progress_tracker = None;
+    if log_progress {
+        match ProgressTracker::new(
+            blocks.len(),
+            Box::new(|completion| info!("Database parsing {}% complete...", completion)),
+        ) {
+            Ok(progress_tracker) => maybe_progress_tracker = Some(progress_tracker),
+            Err(progress_tracker_error) => warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            ),
+        }
+    }
 
-            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
-                progress_tracker.advance_by(1);
-            }
+    // Go through all the blocks in the database.
+    for block in blocks {
+        let (block_hash, header, maybe_body) = block?;
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
         }
+        let block_body = match (maybe_body, maybe_coverage.as_mut()) {
+            (Some(block_body), _) => block_body,
+            (None, Some(coverage)) => {
+                coverage.record_missing_body(header.height());
+                continue;
+            }
+            (None, None) => {
+                return Err(Error::Missing(
+                    block_hash,
+                    BlockBodyDatabase::db_name().to_string(),
+                ))
+            }
+        };
+        let maybe_execution_results = if has_metadata || block_body.deploy_hashes().is_empty() {
+            read_execution_results(reader, block_hash, &block_body)?
+        } else {
+            None
+        };
+        let execution_results = match (maybe_execution_results, maybe_coverage.as_mut()) {
+            (Some(execution_results), Some(coverage)) => {
+                coverage.record_complete();
+                execution_results
+            }
+            (Some(execution_results), None) => execution_results,
+            (None, Some(coverage)) => {
+                coverage.record_missing_results(header.height());
+                continue;
+            }
+            (None, None) => {
+                return Err(Error::Missing(
+                    block_hash,
+                    DeployMetadataDatabase::db_name().to_string(),
+                ))
+            }
+        };
+
+        visit(
+            block_hash,
+            &header,
+            block_body.deploy_hashes().len(),
+            execution_results,
+        )?;
     }
+    Ok(())
+}
+
+fn get_execution_results_stats(
+    storage: &Storage,
+    log_progress: bool,
+    chunk_size: usize,
+    top_count: usize,
+    maybe_coverage: Option<&mut Coverage>,
+) -> Result<ExecutionResultsStats, Error> {
+    let mut stats = ExecutionResultsStats::new(chunk_size, top_count);
+    for_each_block(
+        storage,
+        log_progress,
+        maybe_coverage,
+        |block_hash, header, deploy_count, execution_results| {
+            // Update the statistics with this block's execution results.
+            stats.feed_block(block_hash, header.height(), deploy_count, execution_results)
+        },
+    )?;
     Ok(stats)
 }
 
+/// Fields of the summary given in units with `--human`.
+const HUMAN_FIELDS: &[(&str, Unit)] = &[
+    ("execution_results_size", Unit::Bytes),
+    ("byte_size", Unit::Bytes),
+];
+
 pub(crate) fn dump_execution_results_summary<W: Write + ?Sized>(
     summary: &ExecutionResultsSummary,
     out_writer: Box<W>,
+    human: bool,
 ) -> Result<(), JsonSerializationError> {
-    serde_json::to_writer_pretty(out_writer, summary)
+    human::to_writer_pretty(out_writer, summary, human, HUMAN_FIELDS)
 }
 
-pub fn execution_results_summary<P1: AsRef<Path>, P2: AsRef<Path>>(
-    db_path: P1,
-    output: Option<P2>,
+#[allow(clippy::too_many_arguments)]
+pub fn execution_results_summary<P: AsRef<Path>>(
+    storage: &Storage,
+    output: Option<P>,
     overwrite: bool,
+    compression: Compression,
+    chunk_size: usize,
+    top_count: usize,
+    schema_version: SchemaVersion,
+    human: bool,
+    partial: bool,
 ) -> Result<(), Error> {
-    let storage_path = db_path.as_ref().join(STORAGE_FILE_NAME);
-    let env = db::db_env(storage_path)?;
-    let mut log_progress = false;
+    if schema_version.is_v1() && top_count > 0 {
+        warn!(
+            "Top blocks aren't part of schema version {}, they won't be output.",
+            schema_version
+        );
+    }
     // Validate the output file early so that, in case this fails
     // we don't unnecessarily read the whole database.
-    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
-        let file = OpenOptions::new()
-            .create_new(!overwrite)
-            .write(true)
-            .open(out_path)?;
-        log_progress = true;
-        Box::new(file)
-    } else {
-        Box::new(io::stdout())
-    };
-
-    let execution_results_stats = get_execution_results_stats(&env, log_progress)?;
-    let execution_results_summary: ExecutionResultsSummary = execution_results_stats.into();
-    dump_execution_results_summary(&execution_results_summary, out_writer)?;
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    let log_progress = !out_writer.is_stdout();
+
+    let mut maybe_coverage = partial.then(Coverage::default);
+    let execution_results_stats = get_execution_results_stats(
+        storage,
+        log_progress,
+        chunk_size,
+        top_count,
+        maybe_coverage.as_mut(),
+    )?;
+    if let Some(coverage) = maybe_coverage.as_ref() {
+        coverage.log();
+    }
+    let mut execution_results_summary =
+        ExecutionResultsSummary::from(execution_results_stats).with_schema_version(schema_version);
+    execution_results_summary.coverage = maybe_coverage;
+    dump_execution_results_summary(&execution_results_summary, Box::new(&mut out_writer), human)?;
+    out_writer.finish()?;
+
+    Ok(())
+}
+
+/// Outputs the statistics of the execution results over windows of
+/// `window_size` consecutive heights as a JSON array, in height order, to see
+/// how they evolved along the chain. Windows without any block in the
+/// statistics are left out.
+#[allow(clippy::too_many_arguments)]
+pub fn windowed_execution_results_summary<P: AsRef<Path>>(
+    storage: &Storage,
+    output: Option<P>,
+    overwrite: bool,
+    compression: Compression,
+    chunk_size: usize,
+    window_size: u64,
+    human: bool,
+    partial: bool,
+) -> Result<(), Error> {
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    let log_progress = !out_writer.is_stdout();
+
+    let mut maybe_coverage = partial.then(Coverage::default);
+    let mut windows: BTreeMap<u64, ExecutionResultsStats> = BTreeMap::new();
+    for_each_block(
+        storage,
+        log_progress,
+        maybe_coverage.as_mut(),
+        |block_hash, header, deploy_count, execution_results| {
+            windows
+                .entry(header.height() / window_size)
+                .or_insert_with(|| ExecutionResultsStats::new(chunk_size, 0))
+                .feed_block(block_hash, header.height(), deploy_count, execution_results)
+        },
+    )?;
+    if let Some(coverage) = maybe_coverage.as_ref() {
+        coverage.log();
+    }
+    let window_summaries: Vec<WindowSummary> = windows
+        .iter()
+        .map(|(window_idx, stats)| WindowSummary::new(window_idx * window_size, window_size, stats))
+        .collect();
+    human::to_writer_pretty(&mut out_writer, &window_summaries, human, HUMAN_FIELDS)?;
+    out_writer.finish()?;
 
     Ok(())
 }
@@ -1,20 +1,25 @@
-use std::collections::BTreeMap;
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, BinaryHeap},
+};
 
+use casper_node::types::BlockHash;
 use casper_types::{bytesrepr::ToBytes, ExecutionResult};
 use serde::{Deserialize, Serialize};
 
 use super::Error;
+use crate::common::{coverage::Coverage, schema::SchemaVersion};
 
+/// Default size of the chunks execution results are split into.
 #[cfg(not(test))]
 pub(crate) const CHUNK_SIZE_BYTES: usize = 8 * 1024 * 1024;
 #[cfg(test)]
 pub(crate) const CHUNK_SIZE_BYTES: usize = 20;
-const LAST_ELEM_INDEX_IN_CHUNK: usize = CHUNK_SIZE_BYTES - 1;
 const FLOAT_TOLERANCE: f64 = 0.1;
 
 #[inline]
-pub(crate) fn chunk_count_after_partition(data_size: usize) -> usize {
-    (data_size + LAST_ELEM_INDEX_IN_CHUNK) / CHUNK_SIZE_BYTES
+pub(crate) fn chunk_count_after_partition(data_size: usize, chunk_size: usize) -> usize {
+    data_size.div_ceil(chunk_size)
 }
 
 pub(crate) fn summarize_map(map: &BTreeMap<usize, usize>) -> CollectionStatistics {
@@ -46,8 +51,36 @@ pub(crate) fn summarize_map(map: &BTreeMap<usize, usize>) -> CollectionStatistic
     CollectionStatistics::new(average, median, max)
 }
 
+/// A block along with the size of its execution results.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub(crate) struct TopBlock {
+    pub(crate) height: u64,
+    pub(crate) block_hash: BlockHash,
+    /// Bincode encoded byte length of the execution results of the block.
+    pub(crate) byte_size: usize,
+    /// Number of chunks the bytesrepr encoded execution results would be
+    /// split into.
+    pub(crate) chunk_count: usize,
+    pub(crate) deploy_count: usize,
+}
+
+impl Ord for TopBlock {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.byte_size
+            .cmp(&other.byte_size)
+            .then_with(|| self.height.cmp(&other.height))
+            .then_with(|| self.block_hash.cmp(&other.block_hash))
+    }
+}
+
+impl PartialOrd for TopBlock {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 /// Holds the statistics of execution results present in a node database.
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct ExecutionResultsStats {
     /// Ordered frequency list of execution results sizes (bincode encoded
     /// byte length).
@@ -56,23 +89,85 @@ pub struct ExecutionResultsStats {
     /// chunks the bytesrepr encoded execution results would be split into,
     /// according to `CHUNK_SIZE_BYTES`).
     pub chunk_count: BTreeMap<usize, usize>,
+    /// Size of the chunks execution results are split into, in bytes.
+    chunk_size: usize,
+    /// Number of blocks with the largest execution results to keep track of.
+    top_count: usize,
+    /// The blocks with the largest execution results seen so far, smallest
+    /// first.
+    top_blocks: BinaryHeap<Reverse<TopBlock>>,
+}
+
+impl Default for ExecutionResultsStats {
+    fn default() -> Self {
+        Self::new(CHUNK_SIZE_BYTES, 0)
+    }
 }
 
 impl ExecutionResultsStats {
+    /// Creates empty statistics which split execution results in chunks of
+    /// `chunk_size` bytes and keep track of the `top_count` blocks with the
+    /// largest execution results.
+    pub fn new(chunk_size: usize, top_count: usize) -> Self {
+        Self {
+            execution_results_size: BTreeMap::new(),
+            chunk_count: BTreeMap::new(),
+            chunk_size,
+            top_count,
+            top_blocks: BinaryHeap::new(),
+        }
+    }
+
+    #[cfg(test)]
     pub fn feed(&mut self, execution_results: Vec<ExecutionResult>) -> Result<(), Error> {
+        self.record_sizes(execution_results).map(|_| ())
+    }
+
+    /// Same as `feed`, but also considers the block for the list of blocks
+    /// with the largest execution results.
+    pub fn feed_block(
+        &mut self,
+        block_hash: BlockHash,
+        height: u64,
+        deploy_count: usize,
+        execution_results: Vec<ExecutionResult>,
+    ) -> Result<(), Error> {
+        let (byte_size, chunk_count) = self.record_sizes(execution_results)?;
+        if self.top_count > 0 {
+            self.top_blocks.push(Reverse(TopBlock {
+                height,
+                block_hash,
+                byte_size,
+                chunk_count,
+                deploy_count,
+            }));
+            if self.top_blocks.len() > self.top_count {
+                let _ = self.top_blocks.pop();
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the size and chunk count frequencies with the execution
+    /// results of a block, returning their size and chunk count.
+    fn record_sizes(
+        &mut self,
+        execution_results: Vec<ExecutionResult>,
+    ) -> Result<(usize, usize), Error> {
         // Calculate the length of the bincode serialized execution
         // results.
-        let bincode_encoded_execution_results_size = bincode::serialized_size(&execution_results)?;
+        let bincode_encoded_execution_results_size =
+            bincode::serialized_size(&execution_results)? as usize;
         // Increment the frequency of the calculated size or create a new entry
         // with frequency 1.
         if let Some(count) = self
             .execution_results_size
-            .get_mut(&(bincode_encoded_execution_results_size as usize))
+            .get_mut(&bincode_encoded_execution_results_size)
         {
             *count += 1;
         } else {
             self.execution_results_size
-                .insert(bincode_encoded_execution_results_size as usize, 1);
+                .insert(bincode_encoded_execution_results_size, 1);
         }
 
         // Calculate the length of the bytesrepr serialized execution
@@ -80,8 +175,10 @@ impl ExecutionResultsStats {
         let bytesrepr_encoded_execution_results_length = execution_results.serialized_length();
         // Calculate the number of chunks this set of execution results would
         // be split into.
-        let chunks_in_execution_results =
-            chunk_count_after_partition(bytesrepr_encoded_execution_results_length);
+        let chunks_in_execution_results = chunk_count_after_partition(
+            bytesrepr_encoded_execution_results_length,
+            self.chunk_size,
+        );
         // Increment the frequency of the calculated chunk count or create a
         // new entry with frequency 1.
         if let Some(count) = self.chunk_count.get_mut(&chunks_in_execution_results) {
@@ -89,7 +186,10 @@ impl ExecutionResultsStats {
         } else {
             self.chunk_count.insert(chunks_in_execution_results, 1);
         }
-        Ok(())
+        Ok((
+            bincode_encoded_execution_results_size,
+            chunks_in_execution_results,
+        ))
     }
 }
 
@@ -122,25 +222,81 @@ impl CollectionStatistics {
     }
 }
 
-/// Summary of statistics of a [`ExecutionResultsStats`].
+/// Summary of statistics of a [`ExecutionResultsStats`]. Fields are output
+/// in declaration order, see [`crate::common::schema`].
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub(crate) struct ExecutionResultsSummary {
+    #[serde(skip_serializing_if = "SchemaVersion::is_v1", default)]
+    pub(crate) schema_version: SchemaVersion,
     /// Statistics of bincode encoded sizes of execution results per block, in
     /// bytes.
     pub(crate) execution_results_size: CollectionStatistics,
     /// Statistics of counts of bytesrepr encoded chunks of execution results
     /// per block.
     pub(crate) chunks_statistics: CollectionStatistics,
+    /// Blocks with the largest execution results, largest first.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub(crate) top_blocks: Vec<TopBlock>,
+    /// Blocks included in the statistics, only output with `--partial`.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) coverage: Option<Coverage>,
 }
 
 impl From<ExecutionResultsStats> for ExecutionResultsSummary {
     fn from(stats: ExecutionResultsStats) -> Self {
         let execution_results_size = summarize_map(&stats.execution_results_size);
         let chunks_statistics = summarize_map(&stats.chunk_count);
+        let top_blocks = stats
+            .top_blocks
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse(top_block)| top_block)
+            .collect();
 
         Self {
+            schema_version: SchemaVersion::CURRENT,
             execution_results_size,
             chunks_statistics,
+            top_blocks,
+            coverage: None,
+        }
+    }
+}
+
+/// Statistics of the execution results of the blocks with heights in
+/// `first_height..=last_height`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub(crate) struct WindowSummary {
+    pub(crate) first_height: u64,
+    pub(crate) last_height: u64,
+    /// Number of blocks of the window included in the statistics.
+    pub(crate) blocks: usize,
+    pub(crate) execution_results_size: CollectionStatistics,
+    pub(crate) chunks_statistics: CollectionStatistics,
+}
+
+impl WindowSummary {
+    /// Summarizes the statistics of the window of `window_size` heights
+    /// starting at `first_height`.
+    pub(crate) fn new(first_height: u64, window_size: u64, stats: &ExecutionResultsStats) -> Self {
+        Self {
+            first_height,
+            last_height: first_height.saturating_add(window_size - 1),
+            blocks: stats.execution_results_size.values().sum(),
+            execution_results_size: summarize_map(&stats.execution_results_size),
+            chunks_statistics: summarize_map(&stats.chunk_count),
+        }
+    }
+}
+
+impl ExecutionResultsSummary {
+    /// Sets the layout of the output. The top blocks aren't part of the
+    /// first layout, so they're dropped for [`SchemaVersion::V1`].
+    pub(crate) fn with_schema_version(mut self, schema_version: SchemaVersion) -> Self {
+        if schema_version.is_v1() {
+            self.top_blocks.clear();
         }
+        self.schema_version = schema_version;
+        self
     }
 }
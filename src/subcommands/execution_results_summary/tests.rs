@@ -1,6 +1,6 @@
 use std::{
     collections::BTreeMap,
-    fs::{self, OpenOptions},
+    fs::{self, File, OpenOptions},
     slice,
 };
 
@@ -12,13 +12,20 @@ use rand::Rng;
 use tempfile::{self, TempDir};
 
 use crate::{
-    common::db::{Database, DeployMetadataDatabase, STORAGE_FILE_NAME},
+    common::{
+        coverage::HeightRange,
+        db::{Database, DeployMetadataDatabase, EnvTuning, STORAGE_FILE_NAME},
+        human,
+        output::Compression,
+        schema::SchemaVersion,
+        storage::{Backend, Storage},
+    },
     subcommands::execution_results_summary::{
         block_body::BlockBody,
         read_db,
         summary::{
             chunk_count_after_partition, summarize_map, CollectionStatistics,
-            ExecutionResultsStats, ExecutionResultsSummary, CHUNK_SIZE_BYTES,
+            ExecutionResultsStats, ExecutionResultsSummary, WindowSummary, CHUNK_SIZE_BYTES,
         },
         Error,
     },
@@ -27,18 +34,55 @@ use crate::{
 
 static OUT_DIR: Lazy<TempDir> = Lazy::new(|| tempfile::tempdir().unwrap());
 
+fn open_storage(fixture: &LmdbTestFixture) -> Storage {
+    Storage::open(
+        fixture.tmp_dir.as_ref(),
+        Backend::Lmdb,
+        EnvTuning::SEQUENTIAL,
+    )
+    .unwrap()
+}
+
 #[test]
 fn check_chunk_count_after_partition() {
-    assert_eq!(chunk_count_after_partition(0), 0);
-    assert_eq!(chunk_count_after_partition(1), 1);
-    assert_eq!(chunk_count_after_partition(CHUNK_SIZE_BYTES / 2), 1);
-    assert_eq!(chunk_count_after_partition(CHUNK_SIZE_BYTES - 1), 1);
-    assert_eq!(chunk_count_after_partition(CHUNK_SIZE_BYTES), 1);
-    assert_eq!(chunk_count_after_partition(CHUNK_SIZE_BYTES + 1), 2);
-    assert_eq!(chunk_count_after_partition((CHUNK_SIZE_BYTES * 3) / 2), 2);
-    assert_eq!(chunk_count_after_partition(2 * CHUNK_SIZE_BYTES - 1), 2);
-    assert_eq!(chunk_count_after_partition(2 * CHUNK_SIZE_BYTES), 2);
-    assert_eq!(chunk_count_after_partition(2 * CHUNK_SIZE_BYTES + 1), 3);
+    assert_eq!(chunk_count_after_partition(0, CHUNK_SIZE_BYTES), 0);
+    assert_eq!(chunk_count_after_partition(1, CHUNK_SIZE_BYTES), 1);
+    assert_eq!(
+        chunk_count_after_partition(CHUNK_SIZE_BYTES / 2, CHUNK_SIZE_BYTES),
+        1
+    );
+    assert_eq!(
+        chunk_count_after_partition(CHUNK_SIZE_BYTES - 1, CHUNK_SIZE_BYTES),
+        1
+    );
+    assert_eq!(
+        chunk_count_after_partition(CHUNK_SIZE_BYTES, CHUNK_SIZE_BYTES),
+        1
+    );
+    assert_eq!(
+        chunk_count_after_partition(CHUNK_SIZE_BYTES + 1, CHUNK_SIZE_BYTES),
+        2
+    );
+    assert_eq!(
+        chunk_count_after_partition((CHUNK_SIZE_BYTES * 3) / 2, CHUNK_SIZE_BYTES),
+        2
+    );
+    assert_eq!(
+        chunk_count_after_partition(2 * CHUNK_SIZE_BYTES - 1, CHUNK_SIZE_BYTES),
+        2
+    );
+    assert_eq!(
+        chunk_count_after_partition(2 * CHUNK_SIZE_BYTES, CHUNK_SIZE_BYTES),
+        2
+    );
+    assert_eq!(
+        chunk_count_after_partition(2 * CHUNK_SIZE_BYTES + 1, CHUNK_SIZE_BYTES),
+        3
+    );
+    // Custom chunk sizes.
+    assert_eq!(chunk_count_after_partition(5, 1), 5);
+    assert_eq!(chunk_count_after_partition(14, 7), 2);
+    assert_eq!(chunk_count_after_partition(15, 7), 3);
 }
 
 #[test]
@@ -126,7 +170,7 @@ fn dump_execution_results_summary() {
             .write(true)
             .open(&out_file_path)
             .unwrap();
-        read_db::dump_execution_results_summary(&summary, Box::new(out_file)).unwrap();
+        read_db::dump_execution_results_summary(&summary, Box::new(out_file), false).unwrap();
     }
     assert_eq!(fs::read_to_string(&out_file_path).unwrap(), reference_json);
 }
@@ -158,6 +202,7 @@ fn different_execution_results_stats_feed() {
         bincode_sizes.push(bincode::serialized_size(&execution_results).unwrap() as usize);
         bytesrepr_sizes.push(chunk_count_after_partition(
             execution_results.serialized_length(),
+            CHUNK_SIZE_BYTES,
         ));
         stats.feed(execution_results).unwrap();
     }
@@ -178,6 +223,25 @@ fn different_execution_results_stats_feed() {
     assert_eq!(summary.chunks_statistics.max, bytesrepr_sizes[2]);
 }
 
+#[test]
+fn custom_chunk_size_stats_feed() {
+    let mut execution_results = vec![];
+    for _ in 0..10 {
+        execution_results.push(test_utils::success_execution_result());
+    }
+    let length = execution_results.serialized_length();
+
+    let mut stats = ExecutionResultsStats::new(1, 0);
+    stats.feed(execution_results.clone()).unwrap();
+    let summary: ExecutionResultsSummary = stats.into();
+    assert_eq!(summary.chunks_statistics.max, length);
+
+    let mut stats = ExecutionResultsStats::new(length, 0);
+    stats.feed(execution_results).unwrap();
+    let summary: ExecutionResultsSummary = stats.into();
+    assert_eq!(summary.chunks_statistics.max, 1);
+}
+
 #[test]
 fn identical_execution_results_stats_feed() {
     let mut stats = ExecutionResultsStats::default();
@@ -192,6 +256,7 @@ fn identical_execution_results_stats_feed() {
         bincode_sizes.push(bincode::serialized_size(&execution_results).unwrap() as usize);
         bytesrepr_sizes.push(chunk_count_after_partition(
             execution_results.serialized_length(),
+            CHUNK_SIZE_BYTES,
         ));
         stats.feed(execution_results).unwrap();
     }
@@ -297,9 +362,15 @@ fn execution_results_stats_should_succeed() {
     // Get the execution results summary and ensure it matches with the
     // expected statistics.
     read_db::execution_results_summary(
-        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
     )
     .unwrap();
     let json_str = fs::read_to_string(&out_file_path).unwrap();
@@ -324,6 +395,141 @@ fn execution_results_stats_should_succeed() {
     }
     let expected_summary: ExecutionResultsSummary = stats.into();
     assert_eq!(execution_results_summary, expected_summary);
+
+    // The compressed output should decode to the same summary.
+    read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::Zstd(3),
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
+    )
+    .unwrap();
+    let json_bytes = zstd::decode_all(File::open(&out_file_path).unwrap()).unwrap();
+    let execution_results_summary: ExecutionResultsSummary =
+        serde_json::from_slice(&json_bytes).unwrap();
+    assert_eq!(execution_results_summary, expected_summary);
+
+    // With `--top 2`, the 2 blocks with the largest execution results are
+    // listed too.
+    read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        2,
+        SchemaVersion::CURRENT,
+        false,
+        false,
+    )
+    .unwrap();
+    let json_str = fs::read_to_string(&out_file_path).unwrap();
+    let execution_results_summary: ExecutionResultsSummary =
+        serde_json::from_str(&json_str).unwrap();
+    let mut stats = ExecutionResultsStats::new(CHUNK_SIZE_BYTES, 2);
+    for (block_idx, (block_hash, block_header)) in block_headers.iter().enumerate() {
+        let execution_results = block_body_deploy_map[block_idx]
+            .iter()
+            .map(|metadata_idx| {
+                deploy_metadatas[*metadata_idx]
+                    .execution_results
+                    .get(block_hash)
+                    .unwrap()
+                    .clone()
+            })
+            .collect::<Vec<_>>();
+        stats
+            .feed_block(
+                *block_hash,
+                block_header.height,
+                execution_results.len(),
+                execution_results,
+            )
+            .unwrap();
+    }
+    let expected_summary: ExecutionResultsSummary = stats.into();
+    assert_eq!(execution_results_summary.top_blocks.len(), 2);
+    assert!(
+        execution_results_summary.top_blocks[0].byte_size
+            >= execution_results_summary.top_blocks[1].byte_size
+    );
+    assert_eq!(execution_results_summary, expected_summary);
+
+    // The first schema version has neither the version nor the top blocks,
+    // and the fields keep their order.
+    read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        2,
+        SchemaVersion::V1,
+        false,
+        false,
+    )
+    .unwrap();
+    let json_value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    let fields: Vec<&str> = json_value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(fields, ["execution_results_size", "chunks_statistics"]);
+    let execution_results_summary: ExecutionResultsSummary =
+        serde_json::from_value(json_value).unwrap();
+    assert_eq!(
+        execution_results_summary,
+        expected_summary
+            .clone()
+            .with_schema_version(SchemaVersion::V1)
+    );
+
+    // With `--human`, the sizes are followed by their formatted values.
+    read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        2,
+        SchemaVersion::CURRENT,
+        true,
+        false,
+    )
+    .unwrap();
+    let json_value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    let fields: Vec<&str> = json_value
+        .as_object()
+        .unwrap()
+        .keys()
+        .map(String::as_str)
+        .collect();
+    assert_eq!(
+        fields,
+        [
+            "schema_version",
+            "execution_results_size",
+            "execution_results_size_human",
+            "chunks_statistics",
+            "top_blocks"
+        ]
+    );
+    assert_eq!(
+        json_value["top_blocks"][0]["byte_size_human"],
+        human::format_size(expected_summary.top_blocks[0].byte_size as f64)
+    );
+    let execution_results_summary: ExecutionResultsSummary =
+        serde_json::from_value(json_value).unwrap();
+    assert_eq!(execution_results_summary, expected_summary);
 }
 
 #[test]
@@ -351,9 +557,15 @@ fn execution_results_summary_invalid_key_should_fail() {
     };
 
     match read_db::execution_results_summary(
-        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
     ) {
         Err(Error::InvalidKey(idx)) => assert_eq!(idx, 0),
         Err(error) => panic!("Got unexpected error: {error:?}"),
@@ -403,9 +615,15 @@ fn execution_results_summary_parsing_should_fail() {
     };
 
     match read_db::execution_results_summary(
-        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
     ) {
         Err(Error::Parsing(hash, db_name, _bincode_err)) => {
             assert_eq!(hash, block_hash);
@@ -422,9 +640,15 @@ fn execution_results_summary_bogus_db_should_fail() {
     let out_file_path = OUT_DIR.as_ref().join("bogus_db.json");
 
     match read_db::execution_results_summary(
-        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
     ) {
         Err(Error::Database(_)) => { /* expected result */ }
         Err(error) => panic!("Got unexpected error: {error:?}"),
@@ -445,12 +669,223 @@ fn execution_results_summary_existing_output_should_fail() {
         .open(&out_file_path)
         .unwrap();
     match read_db::execution_results_summary(
-        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
     ) {
         Err(Error::Output(_)) => { /* expected result */ }
         Err(error) => panic!("Got unexpected error: {error:?}"),
         Ok(_) => panic!("Command unexpectedly succeeded"),
     }
 }
+
+#[test]
+fn execution_results_summary_partial_should_report_coverage() {
+    let fixture = LmdbTestFixture::new(
+        vec!["block_header", "block_body", "deploy_metadata"],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_file_path = OUT_DIR.as_ref().join("partial.json");
+
+    let block_headers: Vec<(BlockHash, MockBlockHeader)> = (0..3)
+        .map(|idx| {
+            let (block_hash, mut block_header) = test_utils::mock_block_header(idx);
+            block_header.height = idx as u64;
+            (block_hash, block_header)
+        })
+        .collect();
+    let block_bodies = [
+        BlockBody::new(vec![test_utils::mock_deploy_hash(1)]),
+        BlockBody::new(vec![test_utils::mock_deploy_hash(2)]),
+    ];
+    let env = &fixture.env;
+    let mut txn = env.begin_rw_txn().unwrap();
+    for (block_hash, block_header) in block_headers.iter() {
+        txn.put(
+            *fixture.db(Some("block_header")).unwrap(),
+            block_hash,
+            &bincode::serialize(block_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    // The first block has no body, the second one lacks the metadata of its
+    // deploy and only the third one is complete.
+    for (idx, block_body) in block_bodies.iter().enumerate() {
+        txn.put(
+            *fixture.db(Some("block_body")).unwrap(),
+            &block_headers[idx + 1].1.body_hash,
+            &bincode::serialize(block_body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.put(
+        *fixture.db(Some("deploy_metadata")).unwrap(),
+        &test_utils::mock_deploy_hash(2),
+        &bincode::serialize(&test_utils::mock_deploy_metadata(slice::from_ref(
+            &block_headers[2].0,
+        )))
+        .unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    // Without `partial`, the first block lacking its body is an error.
+    match read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        false,
+    ) {
+        Err(Error::Missing(block_hash, db_name)) => {
+            assert_eq!(block_hash, block_headers[0].0);
+            assert_eq!(db_name, "block_body");
+        }
+        Err(error) => panic!("Got unexpected error: {error:?}"),
+        Ok(_) => panic!("Command unexpectedly succeeded"),
+    }
+
+    read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        true,
+    )
+    .unwrap();
+    let execution_results_summary: ExecutionResultsSummary =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    let coverage = execution_results_summary.coverage.unwrap();
+    assert_eq!(coverage.blocks, 3);
+    assert_eq!(coverage.complete_blocks, 1);
+    assert_eq!(coverage.missing_bodies, [HeightRange { from: 0, to: 0 }]);
+    assert_eq!(coverage.missing_results, [HeightRange { from: 1, to: 1 }]);
+    assert!(execution_results_summary.execution_results_size.max > 0);
+
+    // A headers-only database has no body or metadata table at all.
+    let fixture = LmdbTestFixture::new(vec!["block_header"], Some(STORAGE_FILE_NAME));
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (block_hash, block_header) in block_headers.iter() {
+        txn.put(
+            *fixture.db(Some("block_header")).unwrap(),
+            block_hash,
+            &bincode::serialize(block_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    read_db::execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        0,
+        SchemaVersion::CURRENT,
+        false,
+        true,
+    )
+    .unwrap();
+    let execution_results_summary: ExecutionResultsSummary =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    let coverage = execution_results_summary.coverage.unwrap();
+    assert!(coverage.is_headers_only());
+    assert_eq!(coverage.missing_bodies, [HeightRange { from: 0, to: 2 }]);
+}
+
+#[test]
+fn windowed_execution_results_summary_should_split_heights() {
+    let fixture = LmdbTestFixture::new(
+        vec!["block_header", "block_body", "deploy_metadata"],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_file_path = OUT_DIR.as_ref().join("windowed.json");
+
+    // Blocks 0, 1 and 5 are empty, block 2 executed a deploy.
+    let block_headers: Vec<(BlockHash, MockBlockHeader)> = [0u8, 1, 2, 5]
+        .into_iter()
+        .map(|idx| {
+            let (block_hash, mut block_header) = test_utils::mock_block_header(idx);
+            block_header.height = idx as u64;
+            (block_hash, block_header)
+        })
+        .collect();
+    let deploy_hash = test_utils::mock_deploy_hash(1);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (block_hash, block_header) in block_headers.iter() {
+        let deploy_hashes = if block_header.height == 2 {
+            vec![deploy_hash]
+        } else {
+            vec![]
+        };
+        txn.put(
+            *fixture.db(Some("block_header")).unwrap(),
+            block_hash,
+            &bincode::serialize(block_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some("block_body")).unwrap(),
+            &block_header.body_hash,
+            &bincode::serialize(&BlockBody::new(deploy_hashes)).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.put(
+        *fixture.db(Some("deploy_metadata")).unwrap(),
+        &deploy_hash,
+        &bincode::serialize(&test_utils::mock_deploy_metadata(slice::from_ref(
+            &block_headers[2].0,
+        )))
+        .unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    read_db::windowed_execution_results_summary(
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        CHUNK_SIZE_BYTES,
+        2,
+        false,
+        false,
+    )
+    .unwrap();
+    let windows: Vec<WindowSummary> =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    // The window of heights 3 and 4 has no blocks.
+    assert_eq!(
+        windows
+            .iter()
+            .map(|window| (window.first_height, window.last_height, window.blocks))
+            .collect::<Vec<_>>(),
+        [(0, 1, 2), (2, 3, 1), (4, 5, 1)]
+    );
+    let empty_size = bincode::serialized_size(&Vec::<()>::new()).unwrap() as usize;
+    assert_eq!(windows[0].execution_results_size.max, empty_size);
+    assert!(windows[1].execution_results_size.max > empty_size);
+    assert_eq!(windows[2].execution_results_size.max, empty_size);
+}
@@ -0,0 +1,150 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use bincode::Error as BincodeError;
+use casper_node::types::DeployHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::latest_block_summary::Error as BodyError;
+use crate::common::{
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "expiry-report";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const THRESHOLD: &str = "threshold";
+
+/// Errors encountered when building the expiry report.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading the body of a block.
+    #[error("Error reading block body: {0}")]
+    Body(#[from] BodyError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on a deploy.
+    #[error("Error parsing deploy {0}: {1}")]
+    DeployParsing(DeployHash, BincodeError),
+    /// Parsing error on entry at index in the block header database.
+    #[error("Error parsing block header at index {0}: {1}")]
+    HeaderParsing(usize, BincodeError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    Threshold,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Joins the headers of the deploys in a storage database with the \
+            timestamps of the blocks which executed them, and outputs the \
+            distribution of deploy TTLs, the distribution of the delays \
+            between deploy creation and execution and the deploys executed \
+            close to their expiry in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(THRESHOLD)
+                .display_order(DisplayOrder::Threshold as usize)
+                .short('t')
+                .long(THRESHOLD)
+                .takes_value(true)
+                .value_name("PERCENT")
+                .default_value("10")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(percent) if percent > 100 => {
+                        Err("threshold must be at most 100".to_string())
+                    }
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Report deploys executed with less than PERCENT of their \
+                    TTL remaining.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let threshold = matches
+        .value_of(THRESHOLD)
+        .expect("should have threshold arg")
+        .parse()
+        .expect("should be a valid percentage");
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::expiry_report(path, threshold)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
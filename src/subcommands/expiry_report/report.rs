@@ -0,0 +1,165 @@
+use std::{collections::BTreeMap, path::Path, result::Result};
+
+use casper_node::types::{BlockHash, BlockHeader, Deploy, DeployHash};
+use casper_types::TimeDiff;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, DeployDatabase, StorageEnv},
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::latest_block_summary::completeness,
+};
+
+use super::Error;
+
+const MINUTE_MILLIS: i64 = 60 * 1000;
+const HOUR_MILLIS: i64 = 60 * MINUTE_MILLIS;
+
+/// Upper bounds of the buckets of the latency histogram, with their labels.
+/// Latencies over the last bound fall in a final, unbounded bucket.
+const LATENCY_BUCKETS: [(i64, &str); 7] = [
+    (MINUTE_MILLIS, "<1m"),
+    (5 * MINUTE_MILLIS, "<5m"),
+    (15 * MINUTE_MILLIS, "<15m"),
+    (30 * MINUTE_MILLIS, "<30m"),
+    (HOUR_MILLIS, "<1h"),
+    (2 * HOUR_MILLIS, "<2h"),
+    (24 * HOUR_MILLIS, "<1day"),
+];
+const LAST_LATENCY_BUCKET: &str = ">=1day";
+
+/// A bucket of a histogram.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Bucket {
+    pub(crate) label: String,
+    pub(crate) count: usize,
+}
+
+/// A deploy executed with less than the threshold of its TTL remaining.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct CloseToExpiry {
+    pub(crate) deploy_hash: DeployHash,
+    pub(crate) block_hash: BlockHash,
+    pub(crate) block_height: u64,
+    pub(crate) ttl: TimeDiff,
+    /// Time left before expiry when the block was proposed. Negative if the
+    /// deploy was executed after its expiry.
+    pub(crate) remaining_millis: i64,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ExpiryReport {
+    pub(crate) blocks: usize,
+    pub(crate) deploys: usize,
+    pub(crate) missing_bodies: usize,
+    pub(crate) missing_deploys: usize,
+    pub(crate) ttl_histogram: Vec<Bucket>,
+    /// Histogram of the time between the creation of deploys and the
+    /// blocks which executed them.
+    pub(crate) latency_histogram: Vec<Bucket>,
+    pub(crate) close_to_expiry: Vec<CloseToExpiry>,
+}
+
+fn latency_bucket(latency_millis: i64) -> usize {
+    LATENCY_BUCKETS
+        .iter()
+        .position(|(bound, _)| latency_millis < *bound)
+        .unwrap_or(LATENCY_BUCKETS.len())
+}
+
+/// Builds the expiry report of the storage database at `db_path`, listing
+/// the deploys executed with less than `threshold` percent of their TTL
+/// remaining.
+pub(crate) fn expiry_report<P: AsRef<Path>>(
+    db_path: P,
+    threshold: u64,
+) -> Result<ExpiryReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+
+    let mut report = ExpiryReport::default();
+    let mut ttls: BTreeMap<TimeDiff, usize> = BTreeMap::new();
+    let mut latencies = vec![0usize; LATENCY_BUCKETS.len() + 1];
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |_raw_key, raw_val| -> Result<(), Error> {
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::HeaderParsing(report.blocks, bincode_err))?;
+            let block_hash = header.hash();
+            report.blocks += 1;
+            let deploy_hashes =
+                match completeness::read_body_deploys(&reader, &block_hash, &header)? {
+                    Some(deploy_hashes) => deploy_hashes,
+                    None => {
+                        warn!("Missing body for block {}.", block_hash);
+                        report.missing_bodies += 1;
+                        return Ok(());
+                    }
+                };
+            let block_millis = header.timestamp().millis() as i64;
+            for deploy_hash in deploy_hashes {
+                let raw_deploy =
+                    match reader.get(DeployDatabase::db_name(), deploy_hash.as_ref())? {
+                        Some(raw_deploy) => raw_deploy,
+                        None => {
+                            warn!("Missing deploy {} of block {}.", deploy_hash, block_hash);
+                            report.missing_deploys += 1;
+                            continue;
+                        }
+                    };
+                let deploy: Deploy = bincode::deserialize(&raw_deploy)
+                    .map_err(|bincode_err| Error::DeployParsing(deploy_hash, bincode_err))?;
+                report.deploys += 1;
+
+                let ttl = deploy.header().ttl();
+                *ttls.entry(ttl).or_default() += 1;
+                let deploy_millis = deploy.header().timestamp().millis() as i64;
+                let latency_millis = block_millis - deploy_millis;
+                latencies[latency_bucket(latency_millis)] += 1;
+
+                let ttl_millis = ttl.millis() as i64;
+                let remaining_millis = ttl_millis - latency_millis;
+                if (remaining_millis as i128) * 100 < (ttl_millis as i128) * (threshold as i128) {
+                    report.close_to_expiry.push(CloseToExpiry {
+                        deploy_hash,
+                        block_hash,
+                        block_height: header.height(),
+                        ttl,
+                        remaining_millis,
+                    });
+                }
+            }
+            Ok(())
+        },
+    )?;
+
+    report.ttl_histogram = ttls
+        .into_iter()
+        .map(|(ttl, count)| Bucket {
+            label: ttl.to_string(),
+            count,
+        })
+        .collect();
+    report.latency_histogram = latencies
+        .into_iter()
+        .enumerate()
+        .map(|(idx, count)| Bucket {
+            label: LATENCY_BUCKETS
+                .get(idx)
+                .map_or(LAST_LATENCY_BUCKET, |(_, label)| label)
+                .to_string(),
+            count,
+        })
+        .collect();
+    info!(
+        "Found {} deploys in {} blocks, {} executed close to expiry.",
+        report.deploys,
+        report.blocks,
+        report.close_to_expiry.len()
+    );
+    Ok(report)
+}
@@ -0,0 +1,124 @@
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::Timestamp;
+use lmdb::{Transaction, WriteFlags};
+
+use super::report::{expiry_report, Bucket, CloseToExpiry};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database, DeployDatabase,
+        DeployMetadataDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{mock_block_header, mock_deploy, mock_deploy_hash, LmdbTestFixture},
+};
+
+const MINUTE_MILLIS: u64 = 60 * 1000;
+
+#[test]
+fn expiry_report_should_find_deploys_close_to_expiry() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+    let deploy_db = *fixture.db(Some(DeployDatabase::db_name())).unwrap();
+
+    // The first deploy is executed a minute after its creation with an hour
+    // of TTL, the second 28 minutes after its creation with 30 minutes of
+    // TTL. The third deploy of the second block isn't stored.
+    let start = 1_000_000 * MINUTE_MILLIS;
+    let deploys = [
+        mock_deploy(Timestamp::from(start), "1h".parse().unwrap()),
+        mock_deploy(Timestamp::from(start), "30m".parse().unwrap()),
+    ];
+    let block_deploys = [
+        vec![*deploys[0].id()],
+        vec![*deploys[1].id(), mock_deploy_hash(3)],
+    ];
+    let headers: Vec<BlockHeader> = [1u64, 28]
+        .iter()
+        .enumerate()
+        .map(|(idx, minutes)| {
+            let (_, mut mock_header) = mock_block_header(idx as u8);
+            mock_header.height = idx as u64;
+            mock_header.timestamp = Timestamp::from(start + minutes * MINUTE_MILLIS);
+            bincode::deserialize(&bincode::serialize(&mock_header).unwrap()).unwrap()
+        })
+        .collect();
+    let hashes: Vec<BlockHash> = headers.iter().map(BlockHeader::hash).collect();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, header) in headers.iter().enumerate() {
+        txn.put(
+            header_db,
+            &hashes[idx],
+            &bincode::serialize(header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            body_db,
+            header.body_hash(),
+            &bincode::serialize(&BlockBody::new(block_deploys[idx].clone())).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    for deploy in deploys.iter() {
+        txn.put(
+            deploy_db,
+            deploy.id(),
+            &bincode::serialize(deploy).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let report = expiry_report(fixture.tmp_dir.path(), 10).unwrap();
+    assert_eq!(report.blocks, 2);
+    assert_eq!(report.deploys, 2);
+    assert_eq!(report.missing_bodies, 0);
+    assert_eq!(report.missing_deploys, 1);
+    let bucket = |label: &str, count| Bucket {
+        label: label.to_string(),
+        count,
+    };
+    assert_eq!(
+        report.ttl_histogram,
+        vec![bucket("30m", 1), bucket("1h", 1)]
+    );
+    assert_eq!(
+        report.latency_histogram,
+        vec![
+            bucket("<1m", 0),
+            bucket("<5m", 1),
+            bucket("<15m", 0),
+            bucket("<30m", 1),
+            bucket("<1h", 0),
+            bucket("<2h", 0),
+            bucket("<1day", 0),
+            bucket(">=1day", 0),
+        ]
+    );
+    assert_eq!(
+        report.close_to_expiry,
+        vec![CloseToExpiry {
+            deploy_hash: *deploys[1].id(),
+            block_hash: hashes[1],
+            block_height: 1,
+            ttl: "30m".parse().unwrap(),
+            remaining_millis: (2 * MINUTE_MILLIS) as i64,
+        }]
+    );
+
+    // With no threshold, no deploy is close to expiry.
+    let report = expiry_report(fixture.tmp_dir.path(), 0).unwrap();
+    assert!(report.close_to_expiry.is_empty());
+}
@@ -0,0 +1,157 @@
+mod export;
+mod parquet_writer;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use arrow_schema::ArrowError;
+use bincode::Error as BincodeError;
+use casper_node::types::BlockHash;
+use clap::{Arg, ArgMatches, Command};
+use parquet::errors::ParquetError;
+use rusqlite::Error as SqliteError;
+use thiserror::Error as ThisError;
+
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    output::{self, Compression},
+    storage::{self, Backend, Error as StorageError},
+};
+
+pub const COMMAND_NAME: &str = "export-sqlite";
+const DB_PATH: &str = "db-path";
+const FORMAT: &str = "format";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when exporting the storage database to SQLite.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error assembling the rows written to a Parquet file.
+    #[error("Error building Parquet rows: {0}")]
+    Arrow(#[from] ArrowError),
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error creating the output file.
+    #[error("Error creating output: {0}")]
+    Output(#[from] IoError),
+    /// Error writing to a Parquet file.
+    #[error("Error writing to Parquet: {0}")]
+    Parquet(#[from] ParquetError),
+    /// Parsing error on an entry of the named database for a block.
+    #[error("Error parsing {1} entry of block {0}: {2}")]
+    Parsing(BlockHash, &'static str, BincodeError),
+    /// Error writing to the SQLite file.
+    #[error("Error writing to SQLite: {0}")]
+    Sqlite(#[from] SqliteError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Format,
+    Output,
+    Overwrite,
+    Backend,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Exports the blocks, deploys, transfers and execution results of \
+            a storage database to an indexed SQLite file, to be queried with \
+            SQL, or to Parquet files. Hashes are stored as lowercase hex, \
+            timestamps in milliseconds and amounts of motes as decimal \
+            strings.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(FORMAT)
+                .display_order(DisplayOrder::Format as usize)
+                .long(FORMAT)
+                .takes_value(true)
+                .value_name("sqlite|parquet")
+                .possible_values(["sqlite", "parquet"])
+                .default_value("sqlite")
+                .help(
+                    "Format of the output. The Parquet output is a directory \
+                    with one `.parquet` file per table, without indices.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .required(true)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("PATH")
+                .help(
+                    "Path of the SQLite file or of the Parquet directory to \
+                    create. With --compress, the SQLite file is compressed \
+                    once complete to this path with the `.zst` extension \
+                    appended, while the columns of the Parquet files are \
+                    compressed as they are written.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .help(
+                    "Overwrite already existing output files in destination \
+                    directory.",
+                ),
+        )
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let output = Path::new(matches.value_of(OUTPUT).expect("should have output arg"));
+    let overwrite = matches.is_present(OVERWRITE);
+    let export = match matches.value_of(FORMAT) {
+        Some("parquet") => export::export_parquet,
+        _ => export::export_sqlite,
+    };
+    export(
+        db_dir.path(),
+        output,
+        overwrite,
+        Backend::from_matches(matches),
+        Compression::from_matches(matches),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,620 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::ErrorKind,
+    path::Path,
+    result::Result,
+};
+
+use casper_node::types::{BlockHash, BlockHeader, Deploy, DeployHash, DeployMetadata};
+use casper_types::{AsymmetricType, ExecutionResult, Transfer};
+use log::{info, warn};
+use rusqlite::{
+    params_from_iter,
+    types::{ToSql, ToSqlOutput},
+    Connection, Result as SqliteResult, Transaction as SqliteTransaction,
+};
+
+use crate::{
+    common::{
+        block_iter::BlockIterator,
+        db::{Database, DeployDatabase, DeployMetadataDatabase, EnvTuning, TransferDatabase},
+        output::{self, Compression},
+        progress::ProgressTracker,
+        storage::{Backend, Storage, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::{parquet_writer::ParquetWriter, Error};
+
+/// Tables of the exported file. Hashes and keys are stored as lowercase hex
+/// and timestamps as milliseconds since the Unix epoch. Motes amounts don't
+/// fit SQLite integers, so they are stored as decimal strings, like the
+/// user-defined ids of transfers.
+const SCHEMA: &str = "
+    PRAGMA journal_mode = OFF;
+    PRAGMA synchronous = OFF;
+    CREATE TABLE blocks (
+        hash TEXT PRIMARY KEY,
+        height INTEGER NOT NULL,
+        era_id INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL,
+        state_root_hash TEXT NOT NULL,
+        protocol_version TEXT NOT NULL,
+        is_switch_block INTEGER NOT NULL,
+        proposer TEXT,
+        deploy_count INTEGER,
+        transfer_count INTEGER
+    );
+    CREATE TABLE deploys (
+        hash TEXT NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        is_transfer INTEGER NOT NULL,
+        account TEXT,
+        timestamp INTEGER,
+        ttl INTEGER,
+        gas_price INTEGER,
+        chain_name TEXT
+    );
+    CREATE TABLE transfers (
+        deploy_hash TEXT NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        from_account TEXT NOT NULL,
+        to_account TEXT,
+        source TEXT NOT NULL,
+        target TEXT NOT NULL,
+        amount TEXT NOT NULL,
+        gas TEXT NOT NULL,
+        id TEXT
+    );
+    CREATE TABLE execution_results (
+        deploy_hash TEXT NOT NULL,
+        block_hash TEXT NOT NULL,
+        block_height INTEGER NOT NULL,
+        success INTEGER NOT NULL,
+        cost TEXT NOT NULL,
+        error_message TEXT,
+        transfer_count INTEGER NOT NULL
+    );
+";
+
+/// Indices created once all the rows are inserted, which is faster than
+/// maintaining them during the export.
+const INDICES: &str = "
+    CREATE INDEX blocks_height ON blocks (height);
+    CREATE INDEX blocks_era_id ON blocks (era_id);
+    CREATE INDEX deploys_hash ON deploys (hash);
+    CREATE INDEX deploys_block_hash ON deploys (block_hash);
+    CREATE INDEX deploys_account ON deploys (account);
+    CREATE INDEX transfers_deploy_hash ON transfers (deploy_hash);
+    CREATE INDEX transfers_block_hash ON transfers (block_hash);
+    CREATE INDEX transfers_from_account ON transfers (from_account);
+    CREATE INDEX transfers_to_account ON transfers (to_account);
+    CREATE INDEX execution_results_deploy_hash ON execution_results (deploy_hash);
+    CREATE INDEX execution_results_block_hash ON execution_results (block_hash);
+";
+
+const INSERT_BLOCK: &str = "INSERT INTO blocks VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_DEPLOY: &str = "INSERT INTO deploys VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_TRANSFER: &str = "INSERT INTO transfers VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)";
+const INSERT_EXECUTION_RESULT: &str = "INSERT INTO execution_results VALUES (?, ?, ?, ?, ?, ?, ?)";
+
+/// Type of a column in Parquet files. SQLite stores booleans as integers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ColumnType {
+    Boolean,
+    UInt64,
+    Utf8,
+}
+
+/// Name and type of a column, and whether it may be null.
+pub(crate) type Column = (&'static str, ColumnType, bool);
+
+const BLOCK_COLUMNS: &[Column] = &[
+    ("hash", ColumnType::Utf8, false),
+    ("height", ColumnType::UInt64, false),
+    ("era_id", ColumnType::UInt64, false),
+    ("timestamp", ColumnType::UInt64, false),
+    ("state_root_hash", ColumnType::Utf8, false),
+    ("protocol_version", ColumnType::Utf8, false),
+    ("is_switch_block", ColumnType::Boolean, false),
+    ("proposer", ColumnType::Utf8, true),
+    ("deploy_count", ColumnType::UInt64, true),
+    ("transfer_count", ColumnType::UInt64, true),
+];
+const DEPLOY_COLUMNS: &[Column] = &[
+    ("hash", ColumnType::Utf8, false),
+    ("block_hash", ColumnType::Utf8, false),
+    ("block_height", ColumnType::UInt64, false),
+    ("is_transfer", ColumnType::Boolean, false),
+    ("account", ColumnType::Utf8, true),
+    ("timestamp", ColumnType::UInt64, true),
+    ("ttl", ColumnType::UInt64, true),
+    ("gas_price", ColumnType::UInt64, true),
+    ("chain_name", ColumnType::Utf8, true),
+];
+const TRANSFER_COLUMNS: &[Column] = &[
+    ("deploy_hash", ColumnType::Utf8, false),
+    ("block_hash", ColumnType::Utf8, false),
+    ("block_height", ColumnType::UInt64, false),
+    ("from_account", ColumnType::Utf8, false),
+    ("to_account", ColumnType::Utf8, true),
+    ("source", ColumnType::Utf8, false),
+    ("target", ColumnType::Utf8, false),
+    ("amount", ColumnType::Utf8, false),
+    ("gas", ColumnType::Utf8, false),
+    ("id", ColumnType::Utf8, true),
+];
+const EXECUTION_RESULT_COLUMNS: &[Column] = &[
+    ("deploy_hash", ColumnType::Utf8, false),
+    ("block_hash", ColumnType::Utf8, false),
+    ("block_height", ColumnType::UInt64, false),
+    ("success", ColumnType::Boolean, false),
+    ("cost", ColumnType::Utf8, false),
+    ("error_message", ColumnType::Utf8, true),
+    ("transfer_count", ColumnType::UInt64, false),
+];
+
+/// A table of the export.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Table {
+    Blocks,
+    Deploys,
+    Transfers,
+    ExecutionResults,
+}
+
+impl Table {
+    pub(crate) const ALL: [Table; 4] = [
+        Table::Blocks,
+        Table::Deploys,
+        Table::Transfers,
+        Table::ExecutionResults,
+    ];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Table::Blocks => "blocks",
+            Table::Deploys => "deploys",
+            Table::Transfers => "transfers",
+            Table::ExecutionResults => "execution_results",
+        }
+    }
+
+    /// Columns of the table, in the order of `SCHEMA`.
+    pub(crate) fn columns(self) -> &'static [Column] {
+        match self {
+            Table::Blocks => BLOCK_COLUMNS,
+            Table::Deploys => DEPLOY_COLUMNS,
+            Table::Transfers => TRANSFER_COLUMNS,
+            Table::ExecutionResults => EXECUTION_RESULT_COLUMNS,
+        }
+    }
+
+    fn insert_statement(self) -> &'static str {
+        match self {
+            Table::Blocks => INSERT_BLOCK,
+            Table::Deploys => INSERT_DEPLOY,
+            Table::Transfers => INSERT_TRANSFER,
+            Table::ExecutionResults => INSERT_EXECUTION_RESULT,
+        }
+    }
+}
+
+/// A value of a row, of the type of its column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Cell {
+    Boolean(bool),
+    UInt64(Option<u64>),
+    Utf8(Option<String>),
+}
+
+impl From<bool> for Cell {
+    fn from(value: bool) -> Self {
+        Cell::Boolean(value)
+    }
+}
+
+impl From<u64> for Cell {
+    fn from(value: u64) -> Self {
+        Cell::UInt64(Some(value))
+    }
+}
+
+impl From<Option<u64>> for Cell {
+    fn from(value: Option<u64>) -> Self {
+        Cell::UInt64(value)
+    }
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Utf8(Some(value))
+    }
+}
+
+impl From<Option<String>> for Cell {
+    fn from(value: Option<String>) -> Self {
+        Cell::Utf8(value)
+    }
+}
+
+impl ToSql for Cell {
+    fn to_sql(&self) -> SqliteResult<ToSqlOutput<'_>> {
+        match self {
+            Cell::Boolean(value) => value.to_sql(),
+            Cell::UInt64(value) => value.to_sql(),
+            Cell::Utf8(value) => value.to_sql(),
+        }
+    }
+}
+
+/// Destination of the rows of the exported tables.
+pub(crate) trait TableWriter {
+    /// Writes a row of `table`, with one cell per column.
+    fn add_row(&mut self, table: Table, row: Vec<Cell>) -> Result<(), Error>;
+}
+
+/// Inserts the rows in a SQLite file created with `SCHEMA`.
+struct SqliteWriter<'a> {
+    sqlite_txn: SqliteTransaction<'a>,
+}
+
+impl<'a> TableWriter for SqliteWriter<'a> {
+    fn add_row(&mut self, table: Table, row: Vec<Cell>) -> Result<(), Error> {
+        self.sqlite_txn
+            .prepare_cached(table.insert_statement())?
+            .execute(params_from_iter(row))?;
+        Ok(())
+    }
+}
+
+/// Number of rows written to each table, and of the entries missing from the
+/// storage database.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct ExportSummary {
+    pub(crate) blocks: usize,
+    pub(crate) missing_bodies: usize,
+    pub(crate) deploys: usize,
+    pub(crate) missing_deploys: usize,
+    pub(crate) transfers: usize,
+    pub(crate) execution_results: usize,
+}
+
+fn hex<T: AsRef<[u8]>>(bytes: T) -> String {
+    bytes
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Removes the file at `path` if it exists.
+fn remove_existing(path: &Path) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(()),
+        Err(io_err) if io_err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(io_err) => Err(Error::Output(io_err)),
+    }
+}
+
+/// Creates the SQLite file at `path`, replacing an existing file only if
+/// `overwrite` is set. With `compression`, the file it is compressed to once
+/// complete must not exist either.
+fn create_output(
+    path: &Path,
+    overwrite: bool,
+    compression: Compression,
+) -> Result<Connection, Error> {
+    if compression != Compression::None {
+        let compressed_path = output::compressed_path(path);
+        if overwrite {
+            remove_existing(&compressed_path)?;
+        } else if compressed_path.exists() {
+            return Err(Error::Output(ErrorKind::AlreadyExists.into()));
+        }
+    }
+    if overwrite {
+        remove_existing(path)?;
+    }
+    OpenOptions::new().write(true).create_new(true).open(path)?;
+    Ok(Connection::open(path)?)
+}
+
+/// Writes the rows of the blocks read from a storage database.
+struct Exporter<'a, R: StorageReader, W: TableWriter> {
+    reader: &'a R,
+    writer: &'a mut W,
+    // Databases extracted from others may lack the tables holding the
+    // deploys, their metadata or the transfers.
+    has_deploys: bool,
+    has_deploy_metadata: bool,
+    has_transfers: bool,
+    summary: ExportSummary,
+}
+
+impl<'a, R: StorageReader, W: TableWriter> Exporter<'a, R, W> {
+    fn new(reader: &'a R, writer: &'a mut W) -> Result<Self, Error> {
+        Ok(Self {
+            reader,
+            writer,
+            has_deploys: reader.has_table(DeployDatabase::db_name())?,
+            has_deploy_metadata: reader.has_table(DeployMetadataDatabase::db_name())?,
+            has_transfers: reader.has_table(TransferDatabase::db_name())?,
+            summary: ExportSummary::default(),
+        })
+    }
+
+    /// Writes a block along with its deploys, their execution results and
+    /// its transfers.
+    fn add_block(
+        &mut self,
+        block_hash: &BlockHash,
+        header: &BlockHeader,
+        maybe_body: Option<&BlockBody>,
+    ) -> Result<(), Error> {
+        self.writer.add_row(
+            Table::Blocks,
+            vec![
+                hex(block_hash).into(),
+                header.height().into(),
+                header.era_id().value().into(),
+                header.timestamp().millis().into(),
+                hex(header.state_root_hash()).into(),
+                header.protocol_version().to_string().into(),
+                header.is_switch_block().into(),
+                maybe_body.map(|body| body.proposer().to_hex()).into(),
+                maybe_body
+                    .map(|body| body.deploy_hashes.len() as u64)
+                    .into(),
+                maybe_body
+                    .map(|body| body.transfer_hashes.len() as u64)
+                    .into(),
+            ],
+        )?;
+        self.summary.blocks += 1;
+
+        match maybe_body {
+            Some(body) => {
+                let deploy_hashes = body.deploy_hashes.iter().map(|hash| (hash, false));
+                let transfer_hashes = body.transfer_hashes.iter().map(|hash| (hash, true));
+                for (deploy_hash, is_transfer) in deploy_hashes.chain(transfer_hashes) {
+                    self.add_deploy(block_hash, header, deploy_hash, is_transfer)?;
+                }
+            }
+            None => self.summary.missing_bodies += 1,
+        }
+        if self.has_transfers {
+            self.add_transfers(block_hash, header)?;
+        }
+        Ok(())
+    }
+
+    fn add_deploy(
+        &mut self,
+        block_hash: &BlockHash,
+        header: &BlockHeader,
+        deploy_hash: &DeployHash,
+        is_transfer: bool,
+    ) -> Result<(), Error> {
+        let mut maybe_deploy: Option<Deploy> = None;
+        if self.has_deploys {
+            if let Some(raw_deploy) = self
+                .reader
+                .get(DeployDatabase::db_name(), deploy_hash.as_ref())?
+            {
+                maybe_deploy = Some(bincode::deserialize(&raw_deploy).map_err(|bincode_err| {
+                    Error::Parsing(*block_hash, DeployDatabase::db_name(), bincode_err)
+                })?);
+            }
+        }
+        let deploy_header = maybe_deploy.as_ref().map(Deploy::header);
+        if deploy_header.is_none() {
+            self.summary.missing_deploys += 1;
+        }
+        self.writer.add_row(
+            Table::Deploys,
+            vec![
+                hex(deploy_hash).into(),
+                hex(block_hash).into(),
+                header.height().into(),
+                is_transfer.into(),
+                deploy_header
+                    .map(|deploy_header| deploy_header.account().to_hex())
+                    .into(),
+                deploy_header
+                    .map(|deploy_header| deploy_header.timestamp().millis())
+                    .into(),
+                deploy_header
+                    .map(|deploy_header| deploy_header.ttl().millis())
+                    .into(),
+                deploy_header
+                    .map(|deploy_header| deploy_header.gas_price())
+                    .into(),
+                deploy_header
+                    .map(|deploy_header| deploy_header.chain_name().to_string())
+                    .into(),
+            ],
+        )?;
+        self.summary.deploys += 1;
+
+        if !self.has_deploy_metadata {
+            return Ok(());
+        }
+        let raw_metadata = match self
+            .reader
+            .get(DeployMetadataDatabase::db_name(), deploy_hash.as_ref())?
+        {
+            Some(raw_metadata) => raw_metadata,
+            None => return Ok(()),
+        };
+        let mut metadata: DeployMetadata =
+            bincode::deserialize(&raw_metadata).map_err(|bincode_err| {
+                Error::Parsing(*block_hash, DeployMetadataDatabase::db_name(), bincode_err)
+            })?;
+        let (success, cost, error_message, transfers) =
+            match metadata.execution_results.remove(block_hash) {
+                Some(ExecutionResult::Success {
+                    cost, transfers, ..
+                }) => (true, cost, None, transfers),
+                Some(ExecutionResult::Failure {
+                    cost,
+                    error_message,
+                    transfers,
+                    ..
+                }) => (false, cost, Some(error_message), transfers),
+                None => return Ok(()),
+            };
+        self.writer.add_row(
+            Table::ExecutionResults,
+            vec![
+                hex(deploy_hash).into(),
+                hex(block_hash).into(),
+                header.height().into(),
+                success.into(),
+                cost.to_string().into(),
+                error_message.into(),
+                (transfers.len() as u64).into(),
+            ],
+        )?;
+        self.summary.execution_results += 1;
+        Ok(())
+    }
+
+    fn add_transfers(&mut self, block_hash: &BlockHash, header: &BlockHeader) -> Result<(), Error> {
+        let raw_transfers = match self
+            .reader
+            .get(TransferDatabase::db_name(), block_hash.as_ref())?
+        {
+            Some(raw_transfers) => raw_transfers,
+            None => return Ok(()),
+        };
+        let transfers: Vec<Transfer> =
+            bincode::deserialize(&raw_transfers).map_err(|bincode_err| {
+                Error::Parsing(*block_hash, TransferDatabase::db_name(), bincode_err)
+            })?;
+        for transfer in transfers {
+            self.writer.add_row(
+                Table::Transfers,
+                vec![
+                    hex(transfer.deploy_hash.value()).into(),
+                    hex(block_hash).into(),
+                    header.height().into(),
+                    transfer.from.to_formatted_string().into(),
+                    transfer.to.map(|to| to.to_formatted_string()).into(),
+                    transfer.source.to_formatted_string().into(),
+                    transfer.target.to_formatted_string().into(),
+                    transfer.amount.to_string().into(),
+                    transfer.gas.to_string().into(),
+                    transfer.id.map(|id| id.to_string()).into(),
+                ],
+            )?;
+            self.summary.transfers += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Writes the rows of every block of `reader` with `writer`.
+fn export_blocks<R: StorageReader, W: TableWriter>(
+    reader: &R,
+    writer: &mut W,
+) -> Result<ExportSummary, Error> {
+    let blocks = BlockIterator::new(reader)?;
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Export {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+
+    let mut exporter = Exporter::new(reader, writer)?;
+    for block in blocks {
+        let (block_hash, header, maybe_body) = block?;
+        exporter.add_block(&block_hash, &header, maybe_body.as_ref())?;
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+    Ok(exporter.summary)
+}
+
+fn log_summary(summary: &ExportSummary, output: &Path) {
+    if summary.missing_bodies > 0 || summary.missing_deploys > 0 {
+        warn!(
+            "{} block bodies and {} deploys are missing from the database.",
+            summary.missing_bodies, summary.missing_deploys
+        );
+    }
+    info!(
+        "Exported {} blocks, {} deploys, {} transfers and {} execution results to {}.",
+        summary.blocks,
+        summary.deploys,
+        summary.transfers,
+        summary.execution_results,
+        output.display()
+    );
+}
+
+/// Writes the blocks, deploys, transfers and execution results of the
+/// storage database at `db_path` to a new SQLite file at `output`, which is
+/// then compressed with `compression`.
+pub(crate) fn export_sqlite<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: P2,
+    overwrite: bool,
+    backend: Backend,
+    compression: Compression,
+) -> Result<ExportSummary, Error> {
+    let storage = Storage::open(&db_path, backend, EnvTuning::SEQUENTIAL)?;
+    let reader = storage.view()?;
+    // Create the output before reading the database so that, in case this
+    // fails, we don't unnecessarily read the whole database.
+    let mut connection = create_output(output.as_ref(), overwrite, compression)?;
+    connection.execute_batch(SCHEMA)?;
+
+    let summary = {
+        let mut writer = SqliteWriter {
+            sqlite_txn: connection.transaction()?,
+        };
+        let summary = export_blocks(&reader, &mut writer)?;
+        writer.sqlite_txn.commit()?;
+        summary
+    };
+    info!("Creating indices.");
+    connection.execute_batch(INDICES)?;
+    connection.close().map_err(|(_, sqlite_err)| sqlite_err)?;
+    let output = output::compress_file(output.as_ref(), compression)?;
+    log_summary(&summary, &output);
+    Ok(summary)
+}
+
+/// Writes the blocks, deploys, transfers and execution results of the
+/// storage database at `db_path` to one Parquet file per table in the
+/// directory `output`, with their columns compressed with `compression`.
+pub(crate) fn export_parquet<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: P2,
+    overwrite: bool,
+    backend: Backend,
+    compression: Compression,
+) -> Result<ExportSummary, Error> {
+    let storage = Storage::open(&db_path, backend, EnvTuning::SEQUENTIAL)?;
+    let reader = storage.view()?;
+    // Create the output before reading the database so that, in case this
+    // fails, we don't unnecessarily read the whole database.
+    let mut writer = ParquetWriter::create(output.as_ref(), overwrite, compression)?;
+    let summary = export_blocks(&reader, &mut writer)?;
+    writer.finish()?;
+    log_summary(&summary, output.as_ref());
+    Ok(summary)
+}
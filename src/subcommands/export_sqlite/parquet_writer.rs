@@ -0,0 +1,206 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    result::Result,
+    sync::Arc,
+};
+
+use arrow_array::{
+    builder::{ArrayBuilder, BooleanBuilder, StringBuilder, UInt64Builder},
+    RecordBatch,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use parquet::{
+    arrow::ArrowWriter,
+    basic::{Compression as ParquetCompression, ZstdLevel},
+    file::properties::WriterProperties,
+};
+
+use crate::common::output::Compression;
+
+use super::{
+    export::{Cell, ColumnType, Table, TableWriter},
+    Error,
+};
+
+/// Extension of the files written for each table.
+const PARQUET_EXTENSION: &str = ".parquet";
+/// Number of rows of a table buffered before they are written to its file.
+const BATCH_SIZE: usize = 64 * 1024;
+
+/// Returns the path of the file of `table` in the output directory `dir`.
+pub(crate) fn table_path(dir: &Path, table: Table) -> PathBuf {
+    dir.join(format!("{}{}", table.name(), PARQUET_EXTENSION))
+}
+
+/// Builder of the values of a column.
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    UInt64(UInt64Builder),
+    Utf8(StringBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(column_type: ColumnType) -> Self {
+        match column_type {
+            ColumnType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::new()),
+            ColumnType::UInt64 => ColumnBuilder::UInt64(UInt64Builder::new()),
+            ColumnType::Utf8 => ColumnBuilder::Utf8(StringBuilder::new()),
+        }
+    }
+
+    fn append(&mut self, cell: Cell) {
+        match (self, cell) {
+            (ColumnBuilder::Boolean(builder), Cell::Boolean(value)) => builder.append_value(value),
+            (ColumnBuilder::UInt64(builder), Cell::UInt64(value)) => builder.append_option(value),
+            (ColumnBuilder::Utf8(builder), Cell::Utf8(value)) => builder.append_option(value),
+            (_, cell) => unreachable!("{:?} should match the type of its column", cell),
+        }
+    }
+
+    fn builder(&mut self) -> &mut dyn ArrayBuilder {
+        match self {
+            ColumnBuilder::Boolean(builder) => builder,
+            ColumnBuilder::UInt64(builder) => builder,
+            ColumnBuilder::Utf8(builder) => builder,
+        }
+    }
+}
+
+/// The Parquet file of a table, with the rows not written yet.
+struct TableFile {
+    schema: SchemaRef,
+    columns: Vec<ColumnBuilder>,
+    writer: ArrowWriter<File>,
+}
+
+impl TableFile {
+    fn new(file: File, table: Table, properties: WriterProperties) -> Result<Self, Error> {
+        let fields: Vec<Field> = table
+            .columns()
+            .iter()
+            .map(|(name, column_type, nullable)| {
+                let data_type = match column_type {
+                    ColumnType::Boolean => DataType::Boolean,
+                    ColumnType::UInt64 => DataType::UInt64,
+                    ColumnType::Utf8 => DataType::Utf8,
+                };
+                Field::new(*name, data_type, *nullable)
+            })
+            .collect();
+        let schema = Arc::new(Schema::new(fields));
+        let columns = table
+            .columns()
+            .iter()
+            .map(|(_, column_type, _)| ColumnBuilder::new(*column_type))
+            .collect();
+        let writer = ArrowWriter::try_new(file, Arc::clone(&schema), Some(properties))?;
+        Ok(Self {
+            schema,
+            columns,
+            writer,
+        })
+    }
+
+    fn buffered_rows(&mut self) -> usize {
+        self.columns
+            .first_mut()
+            .map_or(0, |column| column.builder().len())
+    }
+
+    fn add_row(&mut self, row: Vec<Cell>) -> Result<(), Error> {
+        for (column, cell) in self.columns.iter_mut().zip(row) {
+            column.append(cell);
+        }
+        if self.buffered_rows() >= BATCH_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered rows to the file as a record batch.
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.buffered_rows() == 0 {
+            return Ok(());
+        }
+        let arrays = self
+            .columns
+            .iter_mut()
+            .map(|column| column.builder().finish())
+            .collect();
+        let batch = RecordBatch::try_new(Arc::clone(&self.schema), arrays)?;
+        self.writer.write(&batch)?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), Error> {
+        self.flush()?;
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+/// Writes the rows of each table to its own Parquet file, a batch at a time.
+pub(crate) struct ParquetWriter {
+    tables: Vec<TableFile>,
+}
+
+impl ParquetWriter {
+    /// Creates the file of each table in the directory `dir`, replacing
+    /// existing files only if `overwrite` is set.
+    pub(crate) fn create(
+        dir: &Path,
+        overwrite: bool,
+        compression: Compression,
+    ) -> Result<Self, Error> {
+        let parquet_compression = match compression {
+            Compression::None => ParquetCompression::UNCOMPRESSED,
+            Compression::Zstd(level) => ParquetCompression::ZSTD(ZstdLevel::try_new(level)?),
+        };
+        let properties = WriterProperties::builder()
+            .set_compression(parquet_compression)
+            .build();
+        fs::create_dir_all(dir)?;
+        // Check all the files first so that none is created unless they all
+        // can be.
+        if !overwrite
+            && Table::ALL
+                .iter()
+                .any(|table| table_path(dir, *table).exists())
+        {
+            return Err(Error::Output(ErrorKind::AlreadyExists.into()));
+        }
+        let mut files = vec![];
+        for table in Table::ALL {
+            files.push(
+                OpenOptions::new()
+                    .write(true)
+                    .create_new(!overwrite)
+                    .create(overwrite)
+                    .truncate(overwrite)
+                    .open(table_path(dir, table))?,
+            );
+        }
+        let tables = Table::ALL
+            .into_iter()
+            .zip(files)
+            .map(|(table, file)| TableFile::new(file, table, properties.clone()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { tables })
+    }
+
+    /// Writes the remaining rows and the footers of the files.
+    pub(crate) fn finish(self) -> Result<(), Error> {
+        for table_file in self.tables {
+            table_file.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl TableWriter for ParquetWriter {
+    fn add_row(&mut self, table: Table, row: Vec<Cell>) -> Result<(), Error> {
+        self.tables[table as usize].add_row(row)
+    }
+}
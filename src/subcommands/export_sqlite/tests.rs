@@ -0,0 +1,392 @@
+use std::{
+    fs::{self, File},
+    path::Path,
+};
+
+use arrow_array::{Array, BooleanArray, RecordBatch, StringArray, UInt64Array};
+
+use casper_types::{
+    account::AccountHash, AccessRights, DeployHash as TransferDeployHash, ExecutionEffect,
+    ExecutionResult, TimeDiff, Timestamp, Transfer, URef,
+};
+use lmdb::{Transaction, WriteFlags};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use rusqlite::Connection;
+
+use super::{
+    export::{export_parquet, export_sqlite, ExportSummary, Table},
+    parquet_writer::table_path,
+};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase,
+            DeployMetadataDatabase, TransferDatabase, STORAGE_FILE_NAME,
+        },
+        output::{self, Compression},
+        storage::Backend,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+/// Returns a storage database with two blocks. Block 0 executes a stored
+/// deploy which fails, and a deploy missing from the deploys database.
+/// Block 1 has a transfer but no body.
+fn mock_chain() -> LmdbTestFixture {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            TransferDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    let deploy = test_utils::mock_deploy(Timestamp::from(1000), TimeDiff::from_seconds(60));
+    let missing_deploy_hash = test_utils::mock_deploy_hash(9);
+    let blocks: Vec<_> = (0..2u8)
+        .map(|idx| {
+            let (block_hash, mut header) = test_utils::mock_block_header(idx);
+            header.height = idx as u64;
+            (block_hash, header)
+        })
+        .collect();
+    let mut metadata = test_utils::mock_deploy_metadata(&[]);
+    metadata.execution_results.insert(
+        blocks[0].0,
+        ExecutionResult::Failure {
+            effect: ExecutionEffect::default(),
+            transfers: vec![],
+            cost: 400.into(),
+            error_message: "failed".to_string(),
+        },
+    );
+    let transfer = Transfer::new(
+        TransferDeployHash::new([7; 32]),
+        AccountHash::new([1; 32]),
+        None,
+        URef::new([2; 32], AccessRights::READ_ADD_WRITE),
+        URef::new([3; 32], AccessRights::READ_ADD_WRITE),
+        u128::MAX.into(),
+        10.into(),
+        Some(u64::MAX),
+    );
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (block_hash, header) in blocks.iter() {
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            block_hash,
+            &bincode::serialize(header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &blocks[0].1.body_hash,
+        &bincode::serialize(&BlockBody::new(vec![*deploy.id(), missing_deploy_hash])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+        deploy.id(),
+        &bincode::serialize(&deploy).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+        deploy.id(),
+        &bincode::serialize(&metadata).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(TransferDatabase::db_name())).unwrap(),
+        &blocks[1].0,
+        &bincode::serialize(&vec![transfer]).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    fixture
+}
+
+#[test]
+fn export_sqlite_should_write_tables() {
+    let fixture = mock_chain();
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("export.sqlite");
+    let summary = export_sqlite(
+        fixture.tmp_dir.path(),
+        &out_file_path,
+        false,
+        Backend::Lmdb,
+        Compression::None,
+    )
+    .unwrap();
+    assert_eq!(
+        summary,
+        ExportSummary {
+            blocks: 2,
+            missing_bodies: 1,
+            deploys: 2,
+            missing_deploys: 1,
+            transfers: 1,
+            execution_results: 1,
+        }
+    );
+
+    let connection = Connection::open(&out_file_path).unwrap();
+    let count = |table: &str| -> usize {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+    assert_eq!(count("blocks"), 2);
+    assert_eq!(count("deploys"), 2);
+    assert_eq!(count("transfers"), 1);
+    assert_eq!(count("execution_results"), 1);
+
+    let (height, deploy_count): (u64, Option<usize>) = connection
+        .query_row(
+            "SELECT height, deploy_count FROM blocks WHERE hash = ?",
+            [&"01".repeat(32)],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!((height, deploy_count), (1, None));
+
+    let (timestamp, ttl, chain_name): (u64, u64, String) = connection
+        .query_row(
+            "SELECT timestamp, ttl, chain_name FROM deploys WHERE account IS NOT NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        (timestamp, ttl, chain_name.as_str()),
+        (1000, 60_000, "casper-test")
+    );
+
+    let (success, cost, error_message): (bool, String, Option<String>) = connection
+        .query_row(
+            "SELECT success, cost, error_message FROM execution_results",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .unwrap();
+    assert_eq!(
+        (success, cost.as_str(), error_message.as_deref()),
+        (false, "400", Some("failed"))
+    );
+
+    let (amount, id, to_account): (String, String, Option<String>) = connection
+        .query_row("SELECT amount, id, to_account FROM transfers", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .unwrap();
+    assert_eq!(amount, u128::MAX.to_string());
+    assert_eq!(id, u64::MAX.to_string());
+    assert_eq!(to_account, None);
+
+    // The indices were created.
+    let index_count: usize = connection
+        .query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND name NOT LIKE 'sqlite_%'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(index_count, 11);
+}
+
+#[test]
+fn export_sqlite_existing_output_should_fail() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("export.sqlite");
+    fs::write(&out_file_path, b"existing").unwrap();
+
+    assert!(export_sqlite(
+        fixture.tmp_dir.path(),
+        &out_file_path,
+        false,
+        Backend::Lmdb,
+        Compression::None
+    )
+    .is_err());
+    assert_eq!(fs::read(&out_file_path).unwrap(), b"existing");
+
+    let summary = export_sqlite(
+        fixture.tmp_dir.path(),
+        &out_file_path,
+        true,
+        Backend::Lmdb,
+        Compression::None,
+    )
+    .unwrap();
+    assert_eq!(summary, ExportSummary::default());
+}
+
+#[test]
+fn export_sqlite_should_compress_output() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("export.sqlite");
+    let compressed_path = output::compressed_path(&out_file_path);
+    fs::write(&compressed_path, b"existing").unwrap();
+
+    // The compressed file already exists.
+    assert!(export_sqlite(
+        fixture.tmp_dir.path(),
+        &out_file_path,
+        false,
+        Backend::Lmdb,
+        Compression::Zstd(3)
+    )
+    .is_err());
+    assert!(!out_file_path.exists());
+
+    export_sqlite(
+        fixture.tmp_dir.path(),
+        &out_file_path,
+        true,
+        Backend::Lmdb,
+        Compression::Zstd(3),
+    )
+    .unwrap();
+    assert!(!out_file_path.exists());
+    let decoded = zstd::decode_all(fs::File::open(&compressed_path).unwrap()).unwrap();
+    fs::write(&out_file_path, decoded).unwrap();
+    let connection = Connection::open(&out_file_path).unwrap();
+    let block_count: usize = connection
+        .query_row("SELECT COUNT(*) FROM blocks", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(block_count, 0);
+}
+
+/// Reads the rows of the Parquet file at `path`, which fit a single batch.
+fn read_parquet(path: &Path) -> RecordBatch {
+    let mut reader = ParquetRecordBatchReaderBuilder::try_new(File::open(path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let batch = reader.next().unwrap().unwrap();
+    assert!(reader.next().is_none());
+    batch
+}
+
+#[test]
+fn export_parquet_should_write_tables() {
+    let fixture = mock_chain();
+    let out_dir = tempfile::tempdir().unwrap();
+    let parquet_dir = out_dir.path().join("export");
+    let summary = export_parquet(
+        fixture.tmp_dir.path(),
+        &parquet_dir,
+        false,
+        Backend::Lmdb,
+        Compression::Zstd(3),
+    )
+    .unwrap();
+    assert_eq!(summary.blocks, 2);
+
+    // The columns are those of the SQLite tables.
+    let sqlite_path = out_dir.path().join("export.sqlite");
+    export_sqlite(
+        fixture.tmp_dir.path(),
+        &sqlite_path,
+        false,
+        Backend::Lmdb,
+        Compression::None,
+    )
+    .unwrap();
+    let connection = Connection::open(&sqlite_path).unwrap();
+    for table in Table::ALL {
+        let sqlite_columns: Vec<String> = connection
+            .prepare(&format!(
+                "SELECT name FROM pragma_table_info('{}')",
+                table.name()
+            ))
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        let batch = read_parquet(&table_path(&parquet_dir, table));
+        let parquet_columns: Vec<String> = batch
+            .schema()
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect();
+        assert_eq!(parquet_columns, sqlite_columns);
+    }
+
+    let column = |batch: &RecordBatch, name: &str| batch.column_by_name(name).unwrap().clone();
+    let blocks = read_parquet(&table_path(&parquet_dir, Table::Blocks));
+    assert_eq!(blocks.num_rows(), 2);
+    let heights = column(&blocks, "height");
+    let heights = heights.as_any().downcast_ref::<UInt64Array>().unwrap();
+    let deploy_counts = column(&blocks, "deploy_count");
+    let deploy_counts = deploy_counts
+        .as_any()
+        .downcast_ref::<UInt64Array>()
+        .unwrap();
+    let row_at = |height: u64| heights.values().iter().position(|h| *h == height).unwrap();
+    assert_eq!(deploy_counts.value(row_at(0)), 2);
+    assert!(deploy_counts.is_null(row_at(1)));
+
+    let deploys = read_parquet(&table_path(&parquet_dir, Table::Deploys));
+    assert_eq!(deploys.num_rows(), 2);
+    let chain_names = column(&deploys, "chain_name");
+    let chain_names = chain_names.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(chain_names.null_count(), 1);
+
+    let execution_results = read_parquet(&table_path(&parquet_dir, Table::ExecutionResults));
+    let successes = column(&execution_results, "success");
+    let successes = successes.as_any().downcast_ref::<BooleanArray>().unwrap();
+    assert_eq!(successes.len(), 1);
+    assert!(!successes.value(0));
+
+    let transfers = read_parquet(&table_path(&parquet_dir, Table::Transfers));
+    let amounts = column(&transfers, "amount");
+    let amounts = amounts.as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(amounts.value(0), u128::MAX.to_string());
+
+    // Existing files are only replaced with `overwrite`.
+    assert!(export_parquet(
+        fixture.tmp_dir.path(),
+        &parquet_dir,
+        false,
+        Backend::Lmdb,
+        Compression::None
+    )
+    .is_err());
+    export_parquet(
+        fixture.tmp_dir.path(),
+        &parquet_dir,
+        true,
+        Backend::Lmdb,
+        Compression::None,
+    )
+    .unwrap();
+    assert_eq!(
+        read_parquet(&table_path(&parquet_dir, Table::Blocks)).num_rows(),
+        2
+    );
+}
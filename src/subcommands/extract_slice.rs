@@ -1,7 +1,7 @@
-mod db_helpers;
+pub(crate) mod db_helpers;
 mod extract;
-mod global_state;
-mod storage;
+pub(crate) mod global_state;
+pub(crate) mod storage;
 #[cfg(test)]
 mod tests;
 
@@ -10,35 +10,67 @@ use std::{io::Error as IoError, path::Path};
 use bincode::Error as BincodeError;
 use casper_hashing::Digest;
 use casper_node::types::BlockHash;
+use casper_types::bytesrepr::Error as BytesreprError;
 use clap::{Arg, ArgMatches, Command};
 use lmdb::Error as LmdbError;
 use thiserror::Error as ThisError;
 
 use self::extract::SliceIdentifier;
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    disk_space::Error as DiskSpaceError, merkle_body::Error as MerkleBodyError,
+    stamp::Error as StampError,
+};
+use crate::subcommands::purge_signatures::Error as EraWeightsError;
 
 pub const COMMAND_NAME: &str = "extract-slice";
 const BLOCK_HASH: &str = "block-hash";
+const FORCE: &str = "force";
+const INCLUDE_ERA_SUMMARY: &str = "include-era-summary";
 const STATE_ROOT_HASH: &str = "state-root-hash";
 const OUTPUT: &str = "output";
+const PREFER_FINALIZED_APPROVALS: &str = "prefer-finalized-approvals";
 const SOURCE_DB_PATH: &str = "source-db-path";
+const VERIFY_SIGNATURES: &str = "verify-signatures";
 
 /// Errors encountered when running the `extract-slice` subcommand.
 #[derive(Debug, ThisError)]
 pub enum Error {
+    #[error("Error opening the source database: {0}")]
+    Archive(#[from] UnpackError),
     #[error("Error (de)serializing items with bincode: {0}")]
     Bincode(#[from] BincodeError),
     #[error("Error creating the destination execution engine: {0}")]
     CreateExecutionEngine(anyhow::Error),
     #[error("Error operating the database: {0}")]
     Database(#[from] LmdbError),
+    #[error("Disk space check failed: {0}")]
+    DiskSpace(#[from] DiskSpaceError),
+    #[error("Error looking up the validator weights: {0}")]
+    EraWeights(#[from] EraWeightsError),
+    #[error("Signatures of block {0} don't reach weak finality")]
+    InsufficientSignatures(BlockHash),
     #[error("Error loading the source execution engine: {0}")]
     LoadExecutionEngine(anyhow::Error),
+    #[error("Error reading merkle block body: {0}")]
+    MerkleBody(#[from] MerkleBodyError),
+    #[error("No signatures found for block {0}")]
+    MissingSignatures(BlockHash),
+    #[error("Trie {0} is missing from the source trie store")]
+    MissingTrie(Digest),
     #[error("Error writing output: {0}")]
     Output(#[from] IoError),
     #[error("Error parsing element for block hash {0} in {1} DB: {2}")]
     Parsing(BlockHash, String, BincodeError),
+    #[error("Error stamping the output: {0}")]
+    Stamp(#[from] StampError),
     #[error("Error transferring state root: {0}")]
     StateRootTransfer(anyhow::Error),
+    #[error("Error parsing trie {0}: {1}")]
+    TrieParsing(Digest, BytesreprError),
 }
 
 enum DisplayOrder {
@@ -46,6 +78,10 @@ enum DisplayOrder {
     Output,
     BlockHash,
     StateRootHash,
+    VerifySignatures,
+    PreferFinalizedApprovals,
+    IncludeEraSummary,
+    Force,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -53,8 +89,8 @@ pub fn command(display_order: usize) -> Command<'static> {
         .display_order(display_order)
         .about(
             "Reads all data for a given block hash (block, deploys, execution \
-                results, global state) from a storage directory and stores \
-                them to a new directory in two LMDB files. If a state root \
+                results, signatures, global state) from a storage directory \
+                and stores them to a new directory in two LMDB files. If a state root \
                 hash is provided instead of a block hash, only the global \
                 state under that root hash will be stored in the new \
                 directory",
@@ -69,7 +105,7 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .value_name("SOURCE_DB_PATH")
                 .help(
                     "Path of the directory with the `storage.lmdb` and \
-                `data.lmdb` files.",
+                `data.lmdb` files, or of a `.tar.zst` archive of them.",
                 ),
         )
         .arg(
@@ -104,14 +140,65 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .value_name("STATE_ROOT_HASH")
                 .help("State root hash to be copied over to the new database."),
         )
+        .arg(
+            Arg::new(VERIFY_SIGNATURES)
+                .display_order(DisplayOrder::VerifySignatures as usize)
+                .long(VERIFY_SIGNATURES)
+                .takes_value(false)
+                .requires(BLOCK_HASH)
+                .help(
+                    "Refuse to extract the block unless its signatures reach \
+                    weak finality under the validator weights of its era, \
+                    read from the preceding switch block.",
+                ),
+        )
+        .arg(
+            Arg::new(PREFER_FINALIZED_APPROVALS)
+                .display_order(DisplayOrder::PreferFinalizedApprovals as usize)
+                .long(PREFER_FINALIZED_APPROVALS)
+                .takes_value(false)
+                .requires(BLOCK_HASH)
+                .help(
+                    "Store the deploys of the block with the approvals they \
+                    were finalized with in place of their original ones, \
+                    instead of copying the finalized approvals alongside them.",
+                ),
+        )
+        .arg(
+            Arg::new(INCLUDE_ERA_SUMMARY)
+                .display_order(DisplayOrder::IncludeEraSummary as usize)
+                .long(INCLUDE_ERA_SUMMARY)
+                .takes_value(false)
+                .requires(BLOCK_HASH)
+                .help(
+                    "Also copy the header of the switch block of the block's \
+                    era, and the era summary and era info records of the era \
+                    from its global state, so that the rewards of the era can \
+                    be looked up in the slice. Only the tries leading to these \
+                    records are copied from under that state root.",
+                ),
+        )
+        .arg(
+            Arg::new(FORCE)
+                .display_order(DisplayOrder::Force as usize)
+                .long(FORCE)
+                .takes_value(false)
+                .help(
+                    "Proceed even if the output directory doesn't seem to have \
+                    enough free space for the global state under the extracted \
+                    state roots.",
+                ),
+        )
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
-    let path = Path::new(
+    let db_dir = DbDir::open(
         matches
             .value_of(SOURCE_DB_PATH)
             .expect("should have db-path arg"),
-    );
+        &[Include::Storage, Include::Trie],
+    )?;
+    let path = db_dir.path();
     let output = Path::new(matches.value_of(OUTPUT).expect("should have output arg"));
     let slice_identifier = matches
         .value_of(BLOCK_HASH)
@@ -132,5 +219,13 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
                 .expect("should have either BLOCK_HASH or STATE_ROOT_HASH arg")
         });
 
-    extract::extract_slice(path, output, slice_identifier)
+    extract::extract_slice(
+        path,
+        output,
+        slice_identifier,
+        matches.is_present(VERIFY_SIGNATURES),
+        matches.is_present(PREFER_FINALIZED_APPROVALS),
+        matches.is_present(INCLUDE_ERA_SUMMARY),
+        matches.is_present(FORCE),
+    )
 }
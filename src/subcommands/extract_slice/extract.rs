@@ -3,6 +3,11 @@ use std::path::Path;
 use casper_hashing::Digest;
 use casper_node::types::BlockHash;
 
+use crate::common::{
+    disk_space,
+    stamp::{self, Stamp},
+};
+
 use super::{global_state, storage, Error};
 
 pub enum SliceIdentifier {
@@ -14,14 +19,61 @@ pub fn extract_slice<P1: AsRef<Path>, P2: AsRef<Path>>(
     db_path: P1,
     output: P2,
     slice_identifier: SliceIdentifier,
+    verify_signatures: bool,
+    prefer_finalized_approvals: bool,
+    include_era_summary: bool,
+    force: bool,
 ) -> Result<(), Error> {
     storage::create_output_db(&output)?;
-    let state_root_hash = match slice_identifier {
+    let mut stamp = Stamp {
+        chain_name: stamp::chain_name(&db_path)?,
+        ..Default::default()
+    };
+    let mut maybe_switch_block_header = None;
+    let state_root_hashes = match slice_identifier {
         SliceIdentifier::BlockHash(block_hash) => {
-            storage::transfer_block_info(&db_path, &output, block_hash)?
+            let block_header = storage::transfer_block_info(
+                &db_path,
+                &output,
+                block_hash,
+                verify_signatures,
+                prefer_finalized_approvals,
+            )?;
+            stamp.protocol_version = Some(block_header.protocol_version());
+            stamp.lowest_height = Some(block_header.height());
+            stamp.highest_height = Some(block_header.height());
+            let mut state_root_hashes = vec![*block_header.state_root_hash()];
+            // The state the block was executed on, so that it can be
+            // replayed.
+            if let Some(parent_header) =
+                storage::transfer_parent_header(&db_path, &output, &block_header)?
+            {
+                state_root_hashes.push(*parent_header.state_root_hash());
+            }
+            if include_era_summary {
+                maybe_switch_block_header = storage::transfer_switch_block_header(
+                    &db_path,
+                    &output,
+                    block_header.era_id(),
+                )?;
+            }
+            state_root_hashes
         }
-        SliceIdentifier::StateRootHash(state_root_hash) => state_root_hash,
+        SliceIdentifier::StateRootHash(state_root_hash) => vec![state_root_hash],
     };
-    global_state::transfer_global_state(&db_path, &output, state_root_hash)?;
+    let required_space = global_state::global_state_size(&db_path, &state_root_hashes)?;
+    disk_space::check_available_space(&output, required_space, force)?;
+    global_state::transfer_global_state(&db_path, &output, &state_root_hashes)?;
+    // The era summary is written to the global state by the switch block
+    // ending the era.
+    if let Some(switch_block_header) = maybe_switch_block_header {
+        global_state::transfer_era_summary(
+            &db_path,
+            &output,
+            *switch_block_header.state_root_hash(),
+            switch_block_header.era_id(),
+        )?;
+    }
+    stamp.write(&output)?;
     Ok(())
 }
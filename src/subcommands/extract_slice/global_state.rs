@@ -1,20 +1,29 @@
-use std::{path::Path, result::Result};
+use std::{collections::HashSet, path::Path, result::Result};
 
+use casper_execution_engine::storage::trie::Trie;
 use casper_hashing::Digest;
-use log::info;
+use casper_types::{
+    bytesrepr::{self, ToBytes},
+    EraId, Key, StoredValue,
+};
+use lmdb::{Database, Transaction, WriteFlags};
+use log::{info, warn};
 
-use crate::subcommands::trie_compact::{
-    copy_state_root, create_execution_engine, load_execution_engine, DEFAULT_MAX_DB_SIZE,
+use crate::{
+    common::db::{self, TrieEnv},
+    subcommands::trie_compact::{
+        copy_state_root, create_execution_engine, load_execution_engine, DEFAULT_MAX_DB_SIZE,
+    },
 };
 
 use super::Error;
 
-/// Transfers the global state under a state root hash from a trie store to a
-/// new one.
+/// Transfers the global state under each of `state_root_hashes` from a trie
+/// store to a new one.
 pub(crate) fn transfer_global_state<P1: AsRef<Path>, P2: AsRef<Path>>(
     source: P1,
     destination: P2,
-    state_root_hash: Digest,
+    state_root_hashes: &[Digest],
 ) -> Result<(), Error> {
     let max_db_size = DEFAULT_MAX_DB_SIZE
         .parse()
@@ -26,12 +35,149 @@ pub(crate) fn transfer_global_state<P1: AsRef<Path>, P2: AsRef<Path>>(
     // Create the destination trie store.
     let (destination_state, _env) = create_execution_engine(destination, max_db_size, true)
         .map_err(Error::CreateExecutionEngine)?;
-    info!("Starting transfer process for state root hash {state_root_hash}");
-    // Copy the state root along with missing descendants over to the new trie
-    // store.
-    copy_state_root(state_root_hash, &source_state, &destination_state)
-        .map_err(Error::StateRootTransfer)?;
+    for state_root_hash in state_root_hashes {
+        info!("Starting transfer process for state root hash {state_root_hash}");
+        // Copy the state root along with missing descendants over to the new
+        // trie store.
+        copy_state_root(*state_root_hash, &source_state, &destination_state)
+            .map_err(Error::StateRootTransfer)?;
+    }
     destination_state.flush_environment()?;
 
     Ok(())
 }
+
+/// Returns the number of bytes of keys and values of the distinct tries
+/// under each of `state_root_hashes` in a trie store, which is about the
+/// space their copy needs.
+pub(crate) fn global_state_size<P: AsRef<Path>>(
+    source: P,
+    state_root_hashes: &[Digest],
+) -> Result<u64, Error> {
+    let source_env = TrieEnv::open(&source)?;
+    let source_db = source_env.db()?;
+    source_env.read(|txn| {
+        let mut size = 0u64;
+        let mut visited = HashSet::new();
+        let mut pending = state_root_hashes.to_vec();
+        while let Some(trie_key) = pending.pop() {
+            if !visited.insert(trie_key) {
+                continue;
+            }
+            let raw_trie =
+                db::get_optional(txn, source_db, &trie_key)?.ok_or(Error::MissingTrie(trie_key))?;
+            size += (Digest::LENGTH + raw_trie.len()) as u64;
+            // A first byte of `0` indicates a leaf, which has no children.
+            if raw_trie.first() == Some(&0) {
+                continue;
+            }
+            match bytesrepr::deserialize::<Trie<Key, StoredValue>>(raw_trie.to_vec())
+                .map_err(|bytesrepr_err| Error::TrieParsing(trie_key, bytesrepr_err))?
+            {
+                Trie::Leaf { .. } => {}
+                Trie::Node { pointer_block } => pending.extend(
+                    pointer_block
+                        .as_indexed_pointers()
+                        .map(|(_index, pointer)| pointer.into_hash()),
+                ),
+                Trie::Extension { pointer, .. } => pending.push(pointer.into_hash()),
+            }
+        }
+        Ok(size)
+    })
+}
+
+/// Hashes and serialized tries on the path from a state root to a leaf.
+type TriePath = Vec<(Digest, Vec<u8>)>;
+
+/// Returns the tries on the path from the root `state_root_hash` down to the
+/// leaf under `key`, root first, or `None` if the global state has no such
+/// leaf.
+fn key_path<T: Transaction>(
+    txn: &T,
+    trie_db: Database,
+    state_root_hash: Digest,
+    key: &Key,
+) -> Result<Option<TriePath>, Error> {
+    let path = key.to_bytes().expect("should serialize key");
+    let mut depth = 0;
+    let mut trie_key = state_root_hash;
+    let mut tries = vec![];
+    loop {
+        let raw_trie =
+            db::get_optional(txn, trie_db, &trie_key)?.ok_or(Error::MissingTrie(trie_key))?;
+        tries.push((trie_key, raw_trie.to_vec()));
+        // A first byte of `0` indicates a leaf, followed by its key and its
+        // value.
+        if raw_trie.first() == Some(&0) {
+            return Ok(raw_trie[1..].starts_with(&path).then_some(tries));
+        }
+        match bytesrepr::deserialize::<Trie<Key, StoredValue>>(raw_trie.to_vec())
+            .map_err(|bytesrepr_err| Error::TrieParsing(trie_key, bytesrepr_err))?
+        {
+            Trie::Leaf { .. } => return Ok(None),
+            Trie::Node { pointer_block } => {
+                match path
+                    .get(depth)
+                    .and_then(|index| pointer_block[usize::from(*index)])
+                {
+                    Some(pointer) => trie_key = pointer.into_hash(),
+                    None => return Ok(None),
+                }
+                depth += 1;
+            }
+            Trie::Extension { affix, pointer } => {
+                if !path[depth..].starts_with(&affix) {
+                    return Ok(None);
+                }
+                trie_key = pointer.into_hash();
+                depth += affix.len();
+            }
+        }
+    }
+}
+
+/// Copies the era summary and the legacy era info of the era `era_id` from
+/// the global state under `state_root_hash` in a trie store to a new one,
+/// along with the tries on their paths from the root, but none of the rest of
+/// that global state. Returns the number of records found.
+pub(crate) fn transfer_era_summary<P1: AsRef<Path>, P2: AsRef<Path>>(
+    source: P1,
+    destination: P2,
+    state_root_hash: Digest,
+    era_id: EraId,
+) -> Result<usize, Error> {
+    let keys = [Key::EraSummary, Key::EraInfo(era_id)];
+    let source_env = TrieEnv::open(&source)?;
+    let source_db = source_env.db()?;
+    let paths = source_env.read(|txn| {
+        keys.iter()
+            .map(|key| key_path(txn, source_db, state_root_hash, key))
+            .collect::<Result<Vec<_>, Error>>()
+    })?;
+
+    let max_db_size = DEFAULT_MAX_DB_SIZE
+        .parse()
+        .expect("should be able to parse max db size");
+    let destination_env = TrieEnv::create(&destination, max_db_size)?;
+    let destination_db = destination_env.db()?;
+    let mut found = 0;
+    destination_env.write(|txn| {
+        for (key, maybe_tries) in keys.iter().zip(paths) {
+            match maybe_tries {
+                Some(tries) => {
+                    for (trie_key, raw_trie) in tries {
+                        txn.put(destination_db, &trie_key, &raw_trie, WriteFlags::empty())?;
+                    }
+                    found += 1;
+                }
+                None => info!("No {key} under state root {state_root_hash}"),
+            }
+        }
+        Ok::<_, Error>(())
+    })?;
+    if found == 0 {
+        warn!("No era summary of era {era_id} found under state root {state_root_hash}");
+    }
+    Ok(found)
+}
@@ -1,17 +1,32 @@
-use std::{fs, io::ErrorKind, path::Path, result::Result};
+use std::{collections::BTreeSet, fs, io::ErrorKind, path::Path, result::Result};
 
-use casper_hashing::Digest;
+use casper_types::{EraId, U512};
 use lmdb::{DatabaseFlags, Error as LmdbError, Transaction};
 
-use casper_node::types::{BlockHash, BlockHeader, DeployMetadata};
-use log::info;
+use casper_node::types::{
+    BlockHash, BlockHeader, Deploy, DeployMetadata, DeployWithFinalizedApprovals,
+    FinalizedApprovals,
+};
+use log::{info, warn};
 
 use crate::{
-    common::db::{
-        self, BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase,
-        DeployMetadataDatabase, TransferDatabase, STORAGE_FILE_NAME,
+    common::{
+        db::{
+            self, BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase,
+            BlockMetadataDatabase, Database, DeployDatabase, DeployMetadataDatabase,
+            FinalizedApprovalsDatabase, StorageEnv, TransferDatabase, STORAGE_FILE_NAME,
+        },
+        merkle_body::{self, MerkleBody},
+        storage::LmdbReader,
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        purge_signatures::{
+            block_signatures::BlockSignatures,
+            purge::{self, EraWeights},
+            signatures::is_weak_finality,
+        },
     },
-    subcommands::execution_results_summary::block_body::BlockBody,
 };
 
 use super::{db_helpers, Error};
@@ -27,30 +42,91 @@ pub(crate) fn create_output_db<P: AsRef<Path>>(output_path: P) -> Result<(), Err
 
     storage_env.create_db(Some(BlockHeaderDatabase::db_name()), DatabaseFlags::empty())?;
     storage_env.create_db(Some(BlockBodyDatabase::db_name()), DatabaseFlags::empty())?;
+    storage_env.create_db(
+        Some(BlockBodyMerkleDatabase::db_name()),
+        DatabaseFlags::empty(),
+    )?;
+    for part_db_name in MerkleBody::part_db_names() {
+        storage_env.create_db(Some(part_db_name), DatabaseFlags::empty())?;
+    }
     storage_env.create_db(Some(DeployDatabase::db_name()), DatabaseFlags::empty())?;
     storage_env.create_db(Some(TransferDatabase::db_name()), DatabaseFlags::empty())?;
     storage_env.create_db(
         Some(DeployMetadataDatabase::db_name()),
         DatabaseFlags::empty(),
     )?;
+    storage_env.create_db(
+        Some(BlockMetadataDatabase::db_name()),
+        DatabaseFlags::empty(),
+    )?;
+    storage_env.create_db(
+        Some(FinalizedApprovalsDatabase::db_name()),
+        DatabaseFlags::empty(),
+    )?;
+
+    Ok(())
+}
+
+/// Checks that the signatures of a block reach weak finality under the
+/// validator weights of its era, as recorded in the preceding switch block.
+fn verify_block_signatures(
+    env: &StorageEnv,
+    block_header: &BlockHeader,
+    signatures: &BlockSignatures,
+) -> Result<(), Error> {
+    let era_id = block_header.era_id();
+    if era_id.is_genesis() {
+        warn!("Skipping signature verification for block in the genesis era");
+        return Ok(());
+    }
+    let indices = purge::initialize_indices(env, &BTreeSet::new())?;
+    let header_db = env.db::<BlockHeaderDatabase>()?;
+    let txn = env.begin_ro_txn()?;
+    let mut era_weights = EraWeights::default();
+    era_weights.refresh_weights_for_era(&txn, header_db, &indices, era_id)?;
+    txn.commit()?;
 
+    let weights = era_weights.weights();
+    let total_weight = weights
+        .values()
+        .fold(U512::zero(), |acc, weight| acc + *weight);
+    let signed_weight = signatures
+        .proofs
+        .keys()
+        .filter_map(|public_key| weights.get(public_key))
+        .fold(U512::zero(), |acc, weight| acc + *weight);
+    info!(
+        "Signatures carry {} of the {} total weight of era {}",
+        signed_weight, total_weight, era_id
+    );
+    if !is_weak_finality(signed_weight, total_weight) {
+        return Err(Error::InsufficientSignatures(signatures.block_hash));
+    }
     Ok(())
 }
 
 /// Given a block hash, reads the information related to the associated block
-/// (block header, block body, deploys, transfers, execution results) and
-/// copies them over to a new database. Returns the state root hash associated
-/// with the block.
+/// (block header, block body, deploys, transfers, execution results,
+/// signatures) and copies them over to a new database. Returns the header of
+/// the block.
+///
+/// If `verify_signatures` is set, nothing is written unless the signatures of
+/// the block reach weak finality. If `prefer_finalized_approvals` is set, the
+/// deploys are written with the approvals they were finalized with in place of
+/// their original ones, instead of copying the finalized approvals alongside.
 pub(crate) fn transfer_block_info<P1: AsRef<Path>, P2: AsRef<Path>>(
     source: P1,
     destination: P2,
     block_hash: BlockHash,
-) -> Result<Digest, Error> {
+    verify_signatures: bool,
+    prefer_finalized_approvals: bool,
+) -> Result<BlockHeader, Error> {
     let source_path = source.as_ref().join(STORAGE_FILE_NAME);
-    let source_env = db::db_env(&source_path)?;
+    let source_env = StorageEnv::open(&source)?;
     let destination_path = destination.as_ref().join(STORAGE_FILE_NAME);
     let destination_env = db::db_env(&destination_path)?;
 
+    let deploy_metadata_db = source_env.db::<DeployMetadataDatabase>()?;
     let mut source_txn = source_env.begin_ro_txn()?;
     let mut destination_txn = destination_env.begin_rw_txn()?;
 
@@ -70,15 +146,34 @@ pub(crate) fn transfer_block_info<P1: AsRef<Path>, P2: AsRef<Path>>(
     info!("Successfully transferred block header");
     let block_header: BlockHeader = bincode::deserialize(&block_header_bytes)?;
 
-    // Read the block body associated with the previously read block header.
-    let block_body_bytes = db_helpers::transfer_to_new_db(
+    // Read the block body associated with the previously read block header,
+    // either from the legacy database or from the merkle body databases.
+    let block_body: BlockBody = match db_helpers::transfer_to_new_db(
         &mut source_txn,
         &mut destination_txn,
         BlockBodyDatabase::db_name(),
         block_header.body_hash(),
-    )?;
+    ) {
+        Ok(block_body_bytes) => bincode::deserialize(&block_body_bytes)?,
+        Err(LmdbError::NotFound) => {
+            let merkle_body = merkle_body::read_merkle_body(
+                &LmdbReader::new(&source_txn),
+                block_header.body_hash(),
+            )?
+            .ok_or(LmdbError::NotFound)?;
+            for (db_name, key) in merkle_body.entries() {
+                db_helpers::transfer_to_new_db(
+                    &mut source_txn,
+                    &mut destination_txn,
+                    db_name,
+                    &key,
+                )?;
+            }
+            merkle_body.body
+        }
+        Err(lmdb_error) => return Err(Error::Database(lmdb_error)),
+    };
     info!("Successfully transferred block body");
-    let block_body: BlockBody = bincode::deserialize(&block_body_bytes)?;
 
     // Attempt to copy over all entries in the transfer database for the given
     // block hash. If we have no entry under the block hash, we move on.
@@ -93,19 +188,77 @@ pub(crate) fn transfer_block_info<P1: AsRef<Path>, P2: AsRef<Path>>(
         Err(lmdb_error) => return Err(Error::Database(lmdb_error)),
     }
 
-    // Copy over all the deploys in this block and construct the execution
-    // results to be stored in the new database.
-    let deploy_metadata_db =
-        unsafe { source_txn.open_db(Some(DeployMetadataDatabase::db_name()))? };
-    for deploy_hash in block_body.deploy_hashes() {
-        // Copy the deploy to the new database.
-        db_helpers::transfer_to_new_db(
+    // Copy over all the deploys and transfers in this block and construct the
+    // execution results to be stored in the new database.
+    for deploy_hash in block_body
+        .deploy_hashes()
+        .iter()
+        .chain(&block_body.transfer_hashes)
+    {
+        let maybe_raw_approvals = match db_helpers::read_from_db(
             &mut source_txn,
-            &mut destination_txn,
-            DeployDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
             deploy_hash,
-        )?;
-        info!("Successfully transferred deploy {deploy_hash}");
+        ) {
+            Ok(raw_approvals) => Some(raw_approvals),
+            Err(LmdbError::NotFound) => None,
+            Err(lmdb_error) => return Err(Error::Database(lmdb_error)),
+        };
+        match maybe_raw_approvals {
+            // Copy the deploy to the new database with the approvals it was
+            // executed with.
+            Some(raw_approvals) if prefer_finalized_approvals => {
+                let raw_deploy = db_helpers::read_from_db(
+                    &mut source_txn,
+                    DeployDatabase::db_name(),
+                    deploy_hash,
+                )?;
+                let deploy: Deploy = bincode::deserialize(&raw_deploy).map_err(|bincode_err| {
+                    Error::Parsing(
+                        block_hash,
+                        DeployDatabase::db_name().to_string(),
+                        bincode_err,
+                    )
+                })?;
+                let approvals: FinalizedApprovals =
+                    bincode::deserialize(&raw_approvals).map_err(|bincode_err| {
+                        Error::Parsing(
+                            block_hash,
+                            FinalizedApprovalsDatabase::db_name().to_string(),
+                            bincode_err,
+                        )
+                    })?;
+                let deploy =
+                    DeployWithFinalizedApprovals::new(deploy, Some(approvals)).into_naive();
+                db_helpers::write_to_db(
+                    &mut destination_txn,
+                    DeployDatabase::db_name(),
+                    deploy_hash,
+                    &bincode::serialize(&deploy)?,
+                )?;
+                info!("Successfully transferred deploy {deploy_hash} with its finalized approvals");
+            }
+            // Copy the deploy to the new database, along with the approvals
+            // it was executed with if they differ from its own.
+            maybe_raw_approvals => {
+                db_helpers::transfer_to_new_db(
+                    &mut source_txn,
+                    &mut destination_txn,
+                    DeployDatabase::db_name(),
+                    deploy_hash,
+                )?;
+                info!("Successfully transferred deploy {deploy_hash}");
+                if let Some(raw_approvals) = maybe_raw_approvals {
+                    db_helpers::write_to_db(
+                        &mut destination_txn,
+                        FinalizedApprovalsDatabase::db_name(),
+                        deploy_hash,
+                        &raw_approvals,
+                    )?;
+                    info!("Successfully transferred finalized approvals for {deploy_hash}");
+                }
+            }
+        }
 
         // Get this deploy's metadata.
         let metadata_raw = source_txn.get(deploy_metadata_db, &deploy_hash)?;
@@ -135,9 +288,112 @@ pub(crate) fn transfer_block_info<P1: AsRef<Path>, P2: AsRef<Path>>(
             info!("Successfully transferred execution results for {deploy_hash}");
         }
     }
-    // Commit the transactions.
+
+    // Copy over the finality signatures of the block, if any.
+    let maybe_signatures: Option<BlockSignatures> = match db_helpers::transfer_to_new_db(
+        &mut source_txn,
+        &mut destination_txn,
+        BlockMetadataDatabase::db_name(),
+        &block_hash,
+    ) {
+        Ok(raw_signatures) => {
+            info!("Successfully transferred block signatures");
+            Some(
+                bincode::deserialize(&raw_signatures).map_err(|bincode_err| {
+                    Error::Parsing(
+                        block_hash,
+                        BlockMetadataDatabase::db_name().to_string(),
+                        bincode_err,
+                    )
+                })?,
+            )
+        }
+        Err(LmdbError::NotFound) => {
+            info!("No block signatures found in the source DB");
+            None
+        }
+        Err(lmdb_error) => return Err(Error::Database(lmdb_error)),
+    };
+
+    // Commit the transactions, verifying the signatures in between so that
+    // nothing is written if they don't hold.
     source_txn.commit()?;
+    if verify_signatures {
+        let signatures = maybe_signatures.ok_or(Error::MissingSignatures(block_hash))?;
+        verify_block_signatures(&source_env, &block_header, &signatures)?;
+    }
     destination_txn.commit()?;
     info!("Storage transfer complete");
-    Ok(*block_header.state_root_hash())
+    Ok(block_header)
+}
+
+/// Copies the header of the parent of the block of `block_header` over to the
+/// new database. Returns the parent header, or `None` if the block is the
+/// genesis block or the source database doesn't have its parent.
+pub(crate) fn transfer_parent_header<P1: AsRef<Path>, P2: AsRef<Path>>(
+    source: P1,
+    destination: P2,
+    block_header: &BlockHeader,
+) -> Result<Option<BlockHeader>, Error> {
+    if block_header.height() == 0 {
+        return Ok(None);
+    }
+    let source_env = db::db_env(source.as_ref().join(STORAGE_FILE_NAME))?;
+    let destination_env = db::db_env(destination.as_ref().join(STORAGE_FILE_NAME))?;
+    let mut source_txn = source_env.begin_ro_txn()?;
+    let mut destination_txn = destination_env.begin_rw_txn()?;
+    let parent_hash = block_header.parent_hash();
+    let parent_header_bytes = match db_helpers::transfer_to_new_db(
+        &mut source_txn,
+        &mut destination_txn,
+        BlockHeaderDatabase::db_name(),
+        parent_hash,
+    ) {
+        Ok(parent_header_bytes) => parent_header_bytes,
+        Err(LmdbError::NotFound) => {
+            warn!("Parent block {parent_hash} not found in the source DB");
+            return Ok(None);
+        }
+        Err(lmdb_error) => return Err(Error::Database(lmdb_error)),
+    };
+    let parent_header: BlockHeader = bincode::deserialize(&parent_header_bytes)?;
+    source_txn.commit()?;
+    destination_txn.commit()?;
+    info!("Successfully transferred parent block header");
+    Ok(Some(parent_header))
+}
+
+/// Copies the header of the switch block of the era `era_id` over to the new
+/// database. Returns the switch block header, or `None` if the source
+/// database doesn't have it.
+pub(crate) fn transfer_switch_block_header<P1: AsRef<Path>, P2: AsRef<Path>>(
+    source: P1,
+    destination: P2,
+    era_id: EraId,
+) -> Result<Option<BlockHeader>, Error> {
+    let source_env = db::db_env(source.as_ref().join(STORAGE_FILE_NAME))?;
+    let indices = purge::initialize_indices(&source_env, &BTreeSet::new())?;
+    // Switch blocks are indexed by the era they hold the weights of, the one
+    // after their own.
+    let switch_block_hash = match indices.switch_blocks.get(&era_id.successor()) {
+        Some(switch_block_hash) => *switch_block_hash,
+        None => {
+            warn!("Switch block of era {era_id} not found in the source DB");
+            return Ok(None);
+        }
+    };
+    let destination_env = db::db_env(destination.as_ref().join(STORAGE_FILE_NAME))?;
+    let mut source_txn = source_env.begin_ro_txn()?;
+    let mut destination_txn = destination_env.begin_rw_txn()?;
+    let switch_block_header_bytes = db_helpers::transfer_to_new_db(
+        &mut source_txn,
+        &mut destination_txn,
+        BlockHeaderDatabase::db_name(),
+        &switch_block_hash,
+    )?;
+    let switch_block_header: BlockHeader = bincode::deserialize(&switch_block_header_bytes)?;
+    source_txn.commit()?;
+    destination_txn.commit()?;
+    info!("Successfully transferred header of switch block {switch_block_hash}");
+    Ok(Some(switch_block_header))
 }
@@ -3,28 +3,35 @@ use std::slice;
 use casper_execution_engine::storage::{
     store::StoreExt,
     transaction_source::{lmdb::LmdbEnvironment, TransactionSource},
-    trie::Trie,
+    trie::{Pointer, PointerBlock, Trie},
     trie_store::lmdb::LmdbTrieStore,
 };
 use casper_hashing::Digest;
-use casper_node::types::{BlockHash, DeployHash, DeployMetadata};
-use casper_types::bytesrepr::{Bytes, ToBytes};
+use casper_node::types::{BlockHash, Deploy, DeployHash, DeployMetadata, FinalizedApprovals};
+use casper_types::{
+    bytesrepr::{Bytes, ToBytes},
+    system::auction::EraInfo,
+    CLValue, EraId, Key, Signature, StoredValue, Timestamp,
+};
 use lmdb::{DatabaseFlags, Error as LmdbError, Transaction, WriteFlags};
 
 use crate::{
     common::db::{
-        BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase, DeployMetadataDatabase,
-        TransferDatabase, STORAGE_FILE_NAME,
+        self, BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database,
+        DeployDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase, TransferDatabase,
+        TrieEnv, STORAGE_FILE_NAME,
     },
     subcommands::{
         execution_results_summary::block_body::BlockBody,
-        extract_slice::{db_helpers, global_state, storage},
+        extract_slice::{db_helpers, global_state, storage, Error},
+        purge_signatures::block_signatures::BlockSignatures,
         trie_compact::{
             create_execution_engine, load_execution_engine, tests::create_data, DEFAULT_MAX_DB_SIZE,
         },
     },
     test_utils::{
-        mock_block_header, mock_deploy_hash, mock_deploy_metadata, LmdbTestFixture, MockBlockHeader,
+        self, mock_block_header, mock_deploy_hash, mock_deploy_metadata, mock_switch_block_header,
+        LmdbTestFixture, MockBlockHeader, KEYS,
     },
 };
 
@@ -242,12 +249,15 @@ fn transfer_blocks() {
 
     let block_hash_0 = block_headers[0].0;
     let expected_state_root_hash = block_headers[0].1.state_root_hash;
-    let actual_state_root_hash = storage::transfer_block_info(
+    let actual_state_root_hash = *storage::transfer_block_info(
         source_fixture.tmp_dir.path(),
         destination_fixture.tmp_dir.path(),
         block_hash_0,
+        false,
+        false,
     )
-    .unwrap();
+    .unwrap()
+    .state_root_hash();
     assert_eq!(expected_state_root_hash, actual_state_root_hash);
 
     {
@@ -347,12 +357,15 @@ fn transfer_blocks() {
     }
 
     let expected_state_root_hash = block_headers[1].1.state_root_hash;
-    let actual_state_root_hash = storage::transfer_block_info(
+    let actual_state_root_hash = *storage::transfer_block_info(
         source_fixture.tmp_dir.path(),
         destination_fixture.tmp_dir.path(),
         block_hash_1,
+        false,
+        false,
     )
-    .unwrap();
+    .unwrap()
+    .state_root_hash();
     assert_eq!(expected_state_root_hash, actual_state_root_hash);
 
     {
@@ -437,6 +450,241 @@ fn transfer_blocks() {
     }
 }
 
+#[test]
+fn transfer_block_signatures() {
+    let db_names = vec![
+        BlockHeaderDatabase::db_name(),
+        BlockBodyDatabase::db_name(),
+        BlockMetadataDatabase::db_name(),
+        DeployMetadataDatabase::db_name(),
+        DeployDatabase::db_name(),
+        TransferDatabase::db_name(),
+    ];
+    let source_fixture = LmdbTestFixture::new(db_names.clone(), Some(STORAGE_FILE_NAME));
+
+    // The switch block of era 0 holds equal weights for 3 validators of era 1.
+    let (switch_block_hash, mut switch_block_header) = mock_switch_block_header(0);
+    for key in KEYS.iter().take(3) {
+        switch_block_header.insert_key_weight(key.clone(), 100.into());
+    }
+    let (block_hash, mut block_header) = mock_block_header(1);
+    block_header.era_id = EraId::new(1);
+    block_header.height = 1;
+    // Only one of the 3 validators signed the block.
+    let mut signatures = BlockSignatures::new(block_hash, EraId::new(1));
+    signatures.proofs.insert(KEYS[0].clone(), Signature::System);
+
+    let env = &source_fixture.env;
+    let mut txn = env.begin_rw_txn().unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockHeaderDatabase::db_name()))
+            .unwrap(),
+        &switch_block_hash,
+        &bincode::serialize(&switch_block_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockHeaderDatabase::db_name()))
+            .unwrap(),
+        &block_hash,
+        &bincode::serialize(&block_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockBodyDatabase::db_name()))
+            .unwrap(),
+        &block_header.body_hash,
+        &bincode::serialize(&BlockBody::new(vec![])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockMetadataDatabase::db_name()))
+            .unwrap(),
+        &block_hash,
+        &bincode::serialize(&signatures).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let destination_fixture = LmdbTestFixture::new(db_names, Some(STORAGE_FILE_NAME));
+
+    // A third of the weight doesn't reach weak finality, so nothing is
+    // written when verifying.
+    assert!(matches!(
+        storage::transfer_block_info(
+            source_fixture.tmp_dir.path(),
+            destination_fixture.tmp_dir.path(),
+            block_hash,
+            true,
+            false,
+        ),
+        Err(Error::InsufficientSignatures(hash)) if hash == block_hash
+    ));
+    {
+        let txn = destination_fixture.env.begin_ro_txn().unwrap();
+        assert_eq!(
+            txn.get(
+                *destination_fixture
+                    .db(Some(BlockHeaderDatabase::db_name()))
+                    .unwrap(),
+                &block_hash,
+            )
+            .unwrap_err(),
+            LmdbError::NotFound
+        );
+        txn.commit().unwrap();
+    }
+
+    // Once a second validator signed, the block and its signatures are copied.
+    signatures.proofs.insert(KEYS[1].clone(), Signature::System);
+    let mut txn = env.begin_rw_txn().unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockMetadataDatabase::db_name()))
+            .unwrap(),
+        &block_hash,
+        &bincode::serialize(&signatures).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    storage::transfer_block_info(
+        source_fixture.tmp_dir.path(),
+        destination_fixture.tmp_dir.path(),
+        block_hash,
+        true,
+        false,
+    )
+    .unwrap();
+    let txn = destination_fixture.env.begin_ro_txn().unwrap();
+    let actual_signatures: BlockSignatures = txn
+        .get(
+            *destination_fixture
+                .db(Some(BlockMetadataDatabase::db_name()))
+                .unwrap(),
+            &block_hash,
+        )
+        .map(bincode::deserialize)
+        .unwrap()
+        .unwrap();
+    assert_eq!(actual_signatures, signatures);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn transfer_block_with_finalized_approvals() {
+    let source_fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let (block_hash, block_header) = mock_block_header(0);
+    let deploy = test_utils::mock_deploy(Timestamp::from(0), "1h".parse().unwrap());
+    // The deploy was finalized with the approvals of another one.
+    let finalized_approvals = FinalizedApprovals::new(
+        test_utils::mock_deploy(Timestamp::from(1), "1h".parse().unwrap())
+            .approvals()
+            .clone(),
+    );
+
+    let mut txn = source_fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockHeaderDatabase::db_name()))
+            .unwrap(),
+        &block_hash,
+        &bincode::serialize(&block_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockBodyDatabase::db_name()))
+            .unwrap(),
+        &block_header.body_hash,
+        &bincode::serialize(&BlockBody::new(vec![*deploy.id()])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+        deploy.id(),
+        &bincode::serialize(&deploy).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(DeployMetadataDatabase::db_name()))
+            .unwrap(),
+        deploy.id(),
+        &bincode::serialize(&mock_deploy_metadata(slice::from_ref(&block_hash))).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(FinalizedApprovalsDatabase::db_name()))
+            .unwrap(),
+        deploy.id(),
+        &bincode::serialize(&finalized_approvals).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let out_dir = tempfile::tempdir().unwrap();
+    for prefer_finalized_approvals in [false, true] {
+        let output = out_dir
+            .path()
+            .join(format!("prefer_{}", prefer_finalized_approvals));
+        storage::create_output_db(&output).unwrap();
+        storage::transfer_block_info(
+            source_fixture.tmp_dir.path(),
+            &output,
+            block_hash,
+            false,
+            prefer_finalized_approvals,
+        )
+        .unwrap();
+
+        let env = db::db_env(output.join(STORAGE_FILE_NAME)).unwrap();
+        let txn = env.begin_ro_txn().unwrap();
+        let deploy_db = unsafe { txn.open_db(Some(DeployDatabase::db_name())).unwrap() };
+        let approvals_db = unsafe {
+            txn.open_db(Some(FinalizedApprovalsDatabase::db_name()))
+                .unwrap()
+        };
+        let stored_deploy: Deploy =
+            bincode::deserialize(txn.get(deploy_db, deploy.id()).unwrap()).unwrap();
+        let maybe_raw_approvals = txn.get(approvals_db, deploy.id());
+        if prefer_finalized_approvals {
+            // The approvals are substituted and not copied separately.
+            assert_eq!(stored_deploy.approvals(), finalized_approvals.as_ref());
+            assert_eq!(maybe_raw_approvals.unwrap_err(), LmdbError::NotFound);
+        } else {
+            assert_eq!(stored_deploy, deploy);
+            let stored_approvals: FinalizedApprovals =
+                bincode::deserialize(maybe_raw_approvals.unwrap()).unwrap();
+            assert_eq!(stored_approvals, finalized_approvals);
+        }
+        txn.commit().unwrap();
+    }
+}
+
 #[test]
 fn transfer_global_state_information() {
     let source_tmp_dir = tempfile::tempdir().unwrap();
@@ -468,7 +716,7 @@ fn transfer_global_state_information() {
     global_state::transfer_global_state(
         source_tmp_dir.path(),
         destination_tmp_dir.path(),
-        data[4].0,
+        &[data[4].0],
     )
     .unwrap();
 
@@ -500,3 +748,223 @@ fn transfer_global_state_information() {
     source_tmp_dir.close().unwrap();
     destination_tmp_dir.close().unwrap();
 }
+
+#[test]
+fn global_state_size_should_count_tries_under_roots() {
+    let source_tmp_dir = tempfile::tempdir().unwrap();
+    let max_db_size = DEFAULT_MAX_DB_SIZE
+        .parse()
+        .expect("should be able to parse max db size");
+    let source_env = LmdbEnvironment::new(source_tmp_dir.path(), max_db_size, 512, true).unwrap();
+    let source_store = LmdbTrieStore::new(&source_env, None, DatabaseFlags::empty()).unwrap();
+    let data = create_data();
+    {
+        let mut txn = source_env.create_read_write_txn().unwrap();
+        let items = data.iter().map(Into::into);
+        source_store.put_many(&mut txn, items).unwrap();
+        txn.commit().unwrap();
+    }
+    let trie_size =
+        |index: usize| (Digest::LENGTH + data[index].1.to_bytes().unwrap().len()) as u64;
+
+    // Node 2 has leaf 2 and 3 under it.
+    let expected = trie_size(1) + trie_size(2) + trie_size(4);
+    assert_eq!(
+        global_state::global_state_size(source_tmp_dir.path(), &[data[4].0]).unwrap(),
+        expected
+    );
+    // Tries under several roots are only counted once.
+    assert_eq!(
+        global_state::global_state_size(source_tmp_dir.path(), &[data[4].0, data[4].0, data[1].0])
+            .unwrap(),
+        expected
+    );
+    // A root missing from the store is an error.
+    let missing_root = Digest::hash([0u8; 3]);
+    assert!(matches!(
+        global_state::global_state_size(source_tmp_dir.path(), &[missing_root]),
+        Err(Error::MissingTrie(root)) if root == missing_root
+    ));
+}
+
+#[test]
+fn transfer_parent_block_header() {
+    let db_names = vec![BlockHeaderDatabase::db_name()];
+    let source_fixture = LmdbTestFixture::new(db_names.clone(), Some(STORAGE_FILE_NAME));
+    let destination_fixture = LmdbTestFixture::new(db_names, Some(STORAGE_FILE_NAME));
+    let header_db = *source_fixture
+        .db(Some(BlockHeaderDatabase::db_name()))
+        .unwrap();
+
+    let (parent_hash, mut parent_header) = mock_block_header(0);
+    parent_header.state_root_hash = Digest::hash([1; 32]);
+    let (_, mut block_header) = mock_block_header(1);
+    block_header.parent_hash = parent_hash;
+    block_header.height = 1;
+    let (_, mut orphan_header) = mock_block_header(2);
+    orphan_header.parent_hash = mock_block_header(3).0;
+    orphan_header.height = 2;
+    let mut txn = source_fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        header_db,
+        &parent_hash,
+        &bincode::serialize(&parent_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let transfer_parent = |header: &MockBlockHeader| {
+        let header = bincode::deserialize(&bincode::serialize(header).unwrap()).unwrap();
+        storage::transfer_parent_header(
+            source_fixture.tmp_dir.path(),
+            destination_fixture.tmp_dir.path(),
+            &header,
+        )
+        .unwrap()
+    };
+    // The genesis block has no parent, and the parent of the orphan is
+    // missing.
+    assert!(transfer_parent(&parent_header).is_none());
+    assert!(transfer_parent(&orphan_header).is_none());
+    let copied_header = transfer_parent(&block_header).unwrap();
+    assert_eq!(
+        *copied_header.state_root_hash(),
+        parent_header.state_root_hash
+    );
+
+    let txn = destination_fixture.env.begin_ro_txn().unwrap();
+    let raw_header = txn
+        .get(
+            *destination_fixture
+                .db(Some(BlockHeaderDatabase::db_name()))
+                .unwrap(),
+            &parent_hash,
+        )
+        .unwrap();
+    let header: MockBlockHeader = bincode::deserialize(raw_header).unwrap();
+    assert_eq!(header, parent_header);
+    txn.commit().unwrap();
+}
+
+#[test]
+fn transfer_era_summary_of_switch_block() {
+    let source_fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let destination_fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let era_id = EraId::new(3);
+    let max_db_size = DEFAULT_MAX_DB_SIZE
+        .parse()
+        .expect("should be able to parse max db size");
+
+    // The global state after the switch block holds the era summary, the
+    // era info of the era and an unrelated record, all under a single node.
+    let leaves = [
+        (Key::EraSummary, StoredValue::EraInfo(EraInfo::new())),
+        (Key::EraInfo(era_id), StoredValue::EraInfo(EraInfo::new())),
+        (
+            Key::Hash([1; 32]),
+            StoredValue::CLValue(CLValue::from_t(1u8).unwrap()),
+        ),
+    ];
+    let mut pointer_block = PointerBlock::new();
+    let mut tries = vec![];
+    for (key, value) in leaves {
+        let index = usize::from(key.to_bytes().unwrap()[0]);
+        let raw_leaf = Trie::Leaf { key, value }.to_bytes().unwrap();
+        let leaf_hash = Digest::hash(&raw_leaf);
+        pointer_block[index] = Some(Pointer::LeafPointer(leaf_hash));
+        tries.push((leaf_hash, raw_leaf));
+    }
+    let raw_node = Trie::<Key, StoredValue>::Node {
+        pointer_block: Box::new(pointer_block),
+    }
+    .to_bytes()
+    .unwrap();
+    let state_root_hash = Digest::hash(&raw_node);
+    tries.push((state_root_hash, raw_node));
+    let source_trie_env = TrieEnv::create(source_fixture.tmp_dir.path(), max_db_size).unwrap();
+    let source_trie_db = source_trie_env.db().unwrap();
+    source_trie_env
+        .write(|txn| {
+            for (trie_key, raw_trie) in &tries {
+                txn.put(source_trie_db, trie_key, raw_trie, WriteFlags::empty())?;
+            }
+            Ok::<_, LmdbError>(())
+        })
+        .unwrap();
+
+    let (switch_block_hash, mut switch_block_header) = mock_switch_block_header(0);
+    switch_block_header.era_id = era_id;
+    switch_block_header.state_root_hash = state_root_hash;
+    let mut txn = source_fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *source_fixture
+            .db(Some(BlockHeaderDatabase::db_name()))
+            .unwrap(),
+        &switch_block_hash,
+        &bincode::serialize(&switch_block_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    // The switch block ending the era is found, but not one of a later era.
+    assert!(storage::transfer_switch_block_header(
+        source_fixture.tmp_dir.path(),
+        destination_fixture.tmp_dir.path(),
+        era_id.successor(),
+    )
+    .unwrap()
+    .is_none());
+    let copied_header = storage::transfer_switch_block_header(
+        source_fixture.tmp_dir.path(),
+        destination_fixture.tmp_dir.path(),
+        era_id,
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(*copied_header.state_root_hash(), state_root_hash);
+    let txn = destination_fixture.env.begin_ro_txn().unwrap();
+    assert!(txn
+        .get(
+            *destination_fixture
+                .db(Some(BlockHeaderDatabase::db_name()))
+                .unwrap(),
+            &switch_block_hash,
+        )
+        .is_ok());
+    txn.commit().unwrap();
+
+    assert_eq!(
+        global_state::transfer_era_summary(
+            source_fixture.tmp_dir.path(),
+            destination_fixture.tmp_dir.path(),
+            state_root_hash,
+            era_id,
+        )
+        .unwrap(),
+        2
+    );
+    // Only the root and the two era records are copied.
+    let destination_trie_env = TrieEnv::open(destination_fixture.tmp_dir.path()).unwrap();
+    let destination_trie_db = destination_trie_env.db().unwrap();
+    let txn = destination_trie_env.begin_ro_txn().unwrap();
+    for (trie_key, raw_trie) in &tries[..2] {
+        assert_eq!(
+            txn.get(destination_trie_db, trie_key).unwrap(),
+            raw_trie.as_slice()
+        );
+    }
+    assert_eq!(
+        txn.get(destination_trie_db, &tries[2].0),
+        Err(LmdbError::NotFound)
+    );
+    assert!(txn.get(destination_trie_db, &state_root_hash).is_ok());
+    txn.commit().unwrap();
+}
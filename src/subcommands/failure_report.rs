@@ -0,0 +1,149 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::{
+    archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+    execution_results_summary::Error as ExecutionResultsError,
+};
+use crate::common::{
+    coverage,
+    output::{self, Compression, OutputWriter},
+    storage::{self, Backend, Error as StorageError},
+};
+
+pub const COMMAND_NAME: &str = "failure-report";
+const BY_MESSAGE: &str = "by-message";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when building the failure report.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error reading the execution results of a block.
+    #[error("Error reading execution results: {0}")]
+    ExecutionResults(#[from] ExecutionResultsError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    ByMessage,
+    Partial,
+    Backend,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Classifies the failed execution results in a storage database by \
+            error category and outputs the number of failures of each \
+            category per era, along with the first and last era each category \
+            was seen in, in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(BY_MESSAGE)
+                .display_order(DisplayOrder::ByMessage as usize)
+                .required(false)
+                .short('m')
+                .long(BY_MESSAGE)
+                .takes_value(false)
+                .help(
+                    "Classify failures by their full error message instead of \
+                    by category, e.g. \"ApiError::User(1) [65537]\" instead of \
+                    \"ApiError::User\".",
+                ),
+        )
+        .arg(coverage::partial_arg(DisplayOrder::Partial as usize))
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let by_message = matches.is_present(BY_MESSAGE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::failure_report(
+        db_dir.path(),
+        by_message,
+        Backend::from_matches(matches),
+        output.is_some(),
+        coverage::is_partial(matches),
+    )?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,137 @@
+use std::{cmp::Reverse, collections::BTreeMap, path::Path, result::Result};
+
+use casper_types::{EraId, ExecutionResult};
+use log::info;
+use serde::Serialize;
+
+use crate::{
+    common::{
+        coverage::Coverage,
+        db::EnvTuning,
+        storage::{Backend, Storage},
+    },
+    subcommands::execution_results_summary::read_db,
+};
+
+use super::Error;
+
+const API_ERROR_PREFIX: &str = "ApiError::";
+
+/// Failures of an era, per category.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct EraFailures {
+    pub(crate) era_id: EraId,
+    pub(crate) executions: usize,
+    pub(crate) failures: usize,
+    pub(crate) categories: BTreeMap<String, usize>,
+}
+
+/// Failures of a category over all eras.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct CategoryTotal {
+    pub(crate) category: String,
+    pub(crate) failures: usize,
+    pub(crate) first_era: EraId,
+    pub(crate) last_era: EraId,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct FailureReport {
+    /// Categories by descending number of failures.
+    pub(crate) categories: Vec<CategoryTotal>,
+    pub(crate) eras: Vec<EraFailures>,
+    /// Blocks the report covers, only output with `--partial`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) coverage: Option<Coverage>,
+}
+
+/// Returns the category of an error message, which is the message without
+/// the details specific to the failing deploy: the variant of API errors,
+/// e.g. "ApiError::User" for "ApiError::User(1) [65537]", and the text
+/// before the first colon for other errors, e.g. "Interpreter error" for
+/// "Interpreter error: trap: Unreachable".
+pub(crate) fn category(error_message: &str) -> &str {
+    let end = if let Some(variant) = error_message.strip_prefix(API_ERROR_PREFIX) {
+        API_ERROR_PREFIX.len()
+            + variant
+                .find(|c: char| !c.is_alphanumeric() && c != '_')
+                .unwrap_or(variant.len())
+    } else {
+        error_message.find(':').unwrap_or(error_message.len())
+    };
+    error_message[..end].trim()
+}
+
+/// Counts the failed execution results in the storage database at `db_path`
+/// per era and per category, or per error message if `by_message` is set.
+/// With `partial`, the blocks lacking their body or execution results are
+/// skipped and listed in the coverage of the report.
+pub(crate) fn failure_report<P: AsRef<Path>>(
+    db_path: P,
+    by_message: bool,
+    backend: Backend,
+    log_progress: bool,
+    partial: bool,
+) -> Result<FailureReport, Error> {
+    let storage = Storage::open(&db_path, backend, EnvTuning::default())?;
+
+    let mut eras: BTreeMap<EraId, EraFailures> = BTreeMap::new();
+    let mut maybe_coverage = partial.then(Coverage::default);
+    read_db::for_each_block(
+        &storage,
+        log_progress,
+        maybe_coverage.as_mut(),
+        |_block_hash, header, _deploy_count, execution_results| {
+            let era = eras.entry(header.era_id()).or_insert_with(|| EraFailures {
+                era_id: header.era_id(),
+                ..Default::default()
+            });
+            era.executions += execution_results.len();
+            for execution_result in execution_results {
+                if let ExecutionResult::Failure { error_message, .. } = execution_result {
+                    let key = if by_message {
+                        error_message
+                    } else {
+                        category(&error_message).to_string()
+                    };
+                    era.failures += 1;
+                    *era.categories.entry(key).or_default() += 1;
+                }
+            }
+            Ok(())
+        },
+    )?;
+
+    let mut totals: BTreeMap<&str, CategoryTotal> = BTreeMap::new();
+    for era in eras.values() {
+        for (category, count) in era.categories.iter() {
+            let total = totals
+                .entry(category.as_str())
+                .or_insert_with(|| CategoryTotal {
+                    category: category.clone(),
+                    failures: 0,
+                    first_era: era.era_id,
+                    last_era: era.era_id,
+                });
+            total.failures += count;
+            total.last_era = era.era_id;
+        }
+    }
+    let mut categories: Vec<CategoryTotal> = totals.into_values().collect();
+    categories.sort_by_key(|total| Reverse(total.failures));
+    let eras: Vec<EraFailures> = eras.into_values().collect();
+    info!(
+        "Found {} failures in {} categories over {} eras.",
+        categories.iter().map(|total| total.failures).sum::<usize>(),
+        categories.len(),
+        eras.len()
+    );
+    if let Some(coverage) = maybe_coverage.as_ref() {
+        coverage.log();
+    }
+    Ok(FailureReport {
+        categories,
+        eras,
+        coverage: maybe_coverage,
+    })
+}
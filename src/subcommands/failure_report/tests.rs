@@ -0,0 +1,138 @@
+use casper_types::{EraId, ExecutionEffect, ExecutionResult};
+use lmdb::{Transaction, WriteFlags};
+
+use super::report::{category, failure_report, CategoryTotal};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployMetadataDatabase,
+            STORAGE_FILE_NAME,
+        },
+        storage::Backend,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn failure(error_message: &str) -> ExecutionResult {
+    ExecutionResult::Failure {
+        effect: ExecutionEffect::default(),
+        transfers: vec![],
+        cost: 100.into(),
+        error_message: error_message.to_string(),
+    }
+}
+
+#[test]
+fn error_categories() {
+    assert_eq!(category("ApiError::User(1) [65537]"), "ApiError::User");
+    assert_eq!(
+        category("ApiError::InvalidArgument [3]"),
+        "ApiError::InvalidArgument"
+    );
+    assert_eq!(category("ApiError::Mint(Foo)"), "ApiError::Mint");
+    assert_eq!(
+        category("Interpreter error: trap: Unreachable"),
+        "Interpreter error"
+    );
+    assert_eq!(category("Out of gas error"), "Out of gas error");
+    assert_eq!(category(""), "");
+}
+
+#[test]
+fn failure_report_should_count_categories_per_era() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    // Block 0 is in era 0, blocks 1 and 2 in era 2.
+    let execution_results = [
+        vec![
+            test_utils::success_execution_result(),
+            failure("ApiError::User(1) [65537]"),
+        ],
+        vec![failure("Out of gas error")],
+        vec![
+            failure("ApiError::User(2) [65538]"),
+            failure("ApiError::User(1) [65537]"),
+        ],
+    ];
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    let mut deploy_idx = 0u8;
+    for (idx, results) in execution_results.iter().enumerate() {
+        let (block_hash, mut header) = test_utils::mock_block_header(idx as u8);
+        header.height = idx as u64;
+        header.era_id = EraId::new(if idx == 0 { 0 } else { 2 });
+        let mut deploy_hashes = vec![];
+        for result in results {
+            let deploy_hash = test_utils::mock_deploy_hash(deploy_idx);
+            deploy_idx += 1;
+            let mut metadata = test_utils::mock_deploy_metadata(&[]);
+            metadata
+                .execution_results
+                .insert(block_hash, result.clone());
+            txn.put(
+                *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+                &deploy_hash,
+                &bincode::serialize(&metadata).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+            deploy_hashes.push(deploy_hash);
+        }
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &header.body_hash,
+            &bincode::serialize(&BlockBody::new(deploy_hashes)).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let report =
+        failure_report(fixture.tmp_dir.path(), false, Backend::Lmdb, false, false).unwrap();
+    assert_eq!(report.eras.len(), 2);
+    assert_eq!(report.eras[0].era_id, EraId::new(0));
+    assert_eq!(report.eras[0].executions, 2);
+    assert_eq!(report.eras[0].failures, 1);
+    assert_eq!(report.eras[1].era_id, EraId::new(2));
+    assert_eq!(report.eras[1].executions, 3);
+    assert_eq!(report.eras[1].failures, 3);
+    assert_eq!(report.eras[1].categories.get("ApiError::User"), Some(&2));
+    assert_eq!(report.eras[1].categories.get("Out of gas error"), Some(&1));
+    assert_eq!(
+        report.categories,
+        vec![
+            CategoryTotal {
+                category: "ApiError::User".to_string(),
+                failures: 3,
+                first_era: EraId::new(0),
+                last_era: EraId::new(2),
+            },
+            CategoryTotal {
+                category: "Out of gas error".to_string(),
+                failures: 1,
+                first_era: EraId::new(2),
+                last_era: EraId::new(2),
+            },
+        ]
+    );
+
+    let report = failure_report(fixture.tmp_dir.path(), true, Backend::Lmdb, false, false).unwrap();
+    assert_eq!(report.categories.len(), 3);
+    assert_eq!(report.categories[0].category, "ApiError::User(1) [65537]");
+    assert_eq!(report.categories[0].failures, 2);
+}
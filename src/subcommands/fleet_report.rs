@@ -0,0 +1,226 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::{
+    archive::UnpackError, manifest::content::Error as ManifestError, stats::Error as StatsError,
+};
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    human::{self, Unit},
+    output::{self, Compression, OutputWriter},
+    stamp::Error as StampError,
+    storage::Error as StorageError,
+};
+use report::{NodeSource, Thresholds};
+
+pub const COMMAND_NAME: &str = "fleet-report";
+const COMPLETENESS: &str = "completeness";
+const LAG_THRESHOLD: &str = "lag-threshold";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const SIZE_DEVIATION: &str = "size-deviation";
+const SOURCES: &str = "sources";
+
+/// Fields of the report given in units with `--human`.
+const HUMAN_FIELDS: &[(&str, Unit)] = &[
+    ("median", Unit::Bytes),
+    ("median_storage_size", Unit::Bytes),
+    ("median_trie_store_size", Unit::Bytes),
+    ("size", Unit::Bytes),
+    ("storage_size", Unit::Bytes),
+    ("trie_store_size", Unit::Bytes),
+];
+
+/// Errors encountered when building the fleet report. Only writing the
+/// report fails the command, the errors reading a node are reported along
+/// with it.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the databases: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error reading the highest block.
+    #[error("Error reading the highest block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error reading a manifest file.
+    #[error("Error reading manifest {0}: {1}")]
+    Manifest(PathBuf, IoError),
+    /// Error parsing a manifest or checking its signature.
+    #[error("Invalid manifest {0}: {1}")]
+    ManifestContent(PathBuf, ManifestError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Error reading the stamp of the database directory.
+    #[error("Error reading the stamp: {0}")]
+    Stamp(#[from] StampError),
+    /// Error checking which parts of the blocks are present.
+    #[error("{0}")]
+    Stats(#[from] StatsError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    Sources,
+    Completeness,
+    LagThreshold,
+    SizeDeviation,
+    Output,
+    Overwrite,
+    Compress,
+    Human,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs a combined report of the latest block, the block \
+            completeness and the database sizes of several nodes in JSON \
+            format, flagging the nodes which stand out from the others.",
+        )
+        .arg(
+            Arg::new(SOURCES)
+                .display_order(DisplayOrder::Sources as usize)
+                .required(true)
+                .multiple_values(true)
+                .value_name("[NAME=]PATH")
+                .validator(|value| match value.split_once('=') {
+                    Some(("", _)) => Err("node name must not be empty".to_string()),
+                    _ => Ok(()),
+                })
+                .help(
+                    "Databases of the nodes, each the path of a directory \
+                    with the `storage.lmdb` and `data.lmdb` files, of a \
+                    `.tar.zst` archive of it, or of a `.json` signed manifest \
+                    created on the node with `manifest create`, like one \
+                    fetched over SSH. Optionally prefixed with the name of \
+                    the node in the report, the path being used otherwise.",
+                ),
+        )
+        .arg(
+            Arg::new(COMPLETENESS)
+                .display_order(DisplayOrder::Completeness as usize)
+                .required(false)
+                .short('c')
+                .long(COMPLETENESS)
+                .takes_value(false)
+                .help(
+                    "Also check which parts of every block are present in \
+                    the databases of the nodes, which reads all of their \
+                    blocks. Manifests don't hold this information.",
+                ),
+        )
+        .arg(
+            Arg::new(LAG_THRESHOLD)
+                .display_order(DisplayOrder::LagThreshold as usize)
+                .short('l')
+                .long(LAG_THRESHOLD)
+                .takes_value(true)
+                .value_name("BLOCKS")
+                .default_value("100")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help(
+                    "Flag the nodes whose latest block is more than BLOCKS \
+                    below the highest one of all nodes.",
+                ),
+        )
+        .arg(
+            Arg::new(SIZE_DEVIATION)
+                .display_order(DisplayOrder::SizeDeviation as usize)
+                .short('s')
+                .long(SIZE_DEVIATION)
+                .takes_value(true)
+                .value_name("PERCENT")
+                .default_value("50")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help(
+                    "Flag the nodes whose storage or trie store is more than \
+                    PERCENT larger or smaller than the median of all nodes. \
+                    Sizes are only compared with at least 3 nodes.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(human::human_arg(DisplayOrder::Human as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let sources: Vec<NodeSource> = matches
+        .values_of(SOURCES)
+        .expect("should have sources arg")
+        .map(NodeSource::parse)
+        .collect();
+    let thresholds = Thresholds {
+        lag: matches
+            .value_of(LAG_THRESHOLD)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated"),
+        size_deviation: matches
+            .value_of(SIZE_DEVIATION)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated"),
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::fleet_report(&sources, thresholds, matches.is_present(COMPLETENESS));
+    human::to_writer_pretty(
+        &mut out_writer,
+        &report,
+        human::is_human(matches),
+        HUMAN_FIELDS,
+    )?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,392 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display, Formatter},
+    fs::{self, File},
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use casper_types::{AsymmetricType, Timestamp};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        block_iter,
+        db::{
+            BlockHeaderDatabase, Database, EnvTuning, StorageEnv, TrieEnv, STORAGE_FILE_NAME,
+            TRIE_STORE_FILE_NAME,
+        },
+        lmdb_utils, stamp,
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::{
+        archive::snapshot::{DbDir, Include},
+        manifest::content::{HighestBlock, SignedManifest},
+        stats::{self, CompletenessProfile, STATS_DATABASES},
+    },
+};
+
+use super::Error;
+
+/// Extension of the manifest files given as sources.
+const MANIFEST_EXTENSION: &str = "json";
+/// Minimum number of known sizes for the sizes of the nodes to be compared to
+/// their median.
+const MIN_SIZES_COMPARED: usize = 3;
+/// Percentage points by which the share of the blocks of a node having a
+/// part present may be below the one of the most complete node.
+const COMPLETENESS_TOLERANCE: f64 = 5.0;
+
+/// A node given on the command line as `[NAME=]PATH`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct NodeSource {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+}
+
+impl NodeSource {
+    /// Parses `[NAME=]PATH`, the name defaulting to the path.
+    pub(crate) fn parse(value: &str) -> Self {
+        match value.split_once('=') {
+            Some((name, path)) => Self {
+                name: name.to_string(),
+                path: PathBuf::from(path),
+            },
+            None => Self {
+                name: value.to_string(),
+                path: PathBuf::from(value),
+            },
+        }
+    }
+
+    fn is_manifest(&self) -> bool {
+        self.path.is_file()
+            && self
+                .path
+                .extension()
+                .is_some_and(|extension| extension == MANIFEST_EXTENSION)
+    }
+}
+
+/// Limits beyond which a node is flagged.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Thresholds {
+    /// Number of blocks the latest block of a node may be below the highest
+    /// one of all nodes.
+    pub(crate) lag: u64,
+    /// Percentage by which the size of a database may differ from the
+    /// median of all nodes.
+    pub(crate) size_deviation: u64,
+}
+
+/// Reason for flagging a node.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Outlier {
+    /// The node has no blocks.
+    NoBlocks,
+    /// The latest block of the node is below the highest one of all nodes.
+    Lagging { blocks_behind: u64 },
+    /// The storage database is much larger or smaller than the median.
+    StorageSize { size: u64, median: u64 },
+    /// The trie store is much larger or smaller than the median.
+    TrieStoreSize { size: u64, median: u64 },
+    /// Fewer blocks of the node have `part` present than those of the most
+    /// complete node.
+    Incomplete {
+        part: &'static str,
+        percentage: f64,
+        best_percentage: f64,
+    },
+}
+
+impl Display for Outlier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoBlocks => write!(f, "no blocks"),
+            Self::Lagging { blocks_behind } => {
+                write!(f, "{} blocks behind the highest node", blocks_behind)
+            }
+            Self::StorageSize { size, median } => {
+                write!(f, "storage of {} bytes, median is {}", size, median)
+            }
+            Self::TrieStoreSize { size, median } => {
+                write!(f, "trie store of {} bytes, median is {}", size, median)
+            }
+            Self::Incomplete {
+                part,
+                percentage,
+                best_percentage,
+            } => write!(
+                f,
+                "{:.2}% of blocks {}, up to {:.2}% on other nodes",
+                percentage, part, best_percentage
+            ),
+        }
+    }
+}
+
+/// What is known of the databases of a node.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct NodeReport {
+    pub(crate) name: String,
+    pub(crate) path: PathBuf,
+    pub(crate) chain_name: Option<String>,
+    pub(crate) latest_block: Option<HighestBlock>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) completeness: Option<CompletenessProfile>,
+    /// Entry counts of the storage databases, by name.
+    pub(crate) entry_counts: BTreeMap<String, usize>,
+    pub(crate) trie_entries: Option<usize>,
+    /// Size of the `storage.lmdb` file, in bytes. Unknown for manifests.
+    pub(crate) storage_size: Option<u64>,
+    /// Size of the `data.lmdb` file, in bytes. Unknown for manifests.
+    pub(crate) trie_store_size: Option<u64>,
+    /// Time at which the manifest was created, for nodes read from one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) manifest_created_at: Option<Timestamp>,
+    /// Error reading the node, in which case nothing else is known of it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<String>,
+    pub(crate) outliers: Vec<Outlier>,
+}
+
+/// Report on several nodes, flagging the ones standing out.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct FleetReport {
+    /// Height of the highest block of all nodes.
+    pub(crate) highest_height: Option<u64>,
+    /// Median size of the storage databases, if at least 3 are known.
+    pub(crate) median_storage_size: Option<u64>,
+    /// Median size of the trie stores, if at least 3 are known.
+    pub(crate) median_trie_store_size: Option<u64>,
+    /// Number of nodes with at least one outlier.
+    pub(crate) flagged: usize,
+    /// Number of nodes which couldn't be read.
+    pub(crate) unreadable: usize,
+    pub(crate) nodes: Vec<NodeReport>,
+}
+
+fn file_size(path: &Path) -> Option<u64> {
+    fs::metadata(path).ok().map(|metadata| metadata.len())
+}
+
+/// Reads the databases of a node from a directory or an archive.
+fn read_databases(path: &Path, completeness: bool) -> Result<NodeReport, Error> {
+    let db_dir = DbDir::open(path, &[Include::Storage, Include::Trie])?;
+    let path = db_dir.path();
+    let mut node = NodeReport {
+        chain_name: stamp::chain_name(path)?,
+        ..Default::default()
+    };
+
+    let storage_path = path.join(STORAGE_FILE_NAME);
+    if storage_path.exists() {
+        let env = StorageEnv::open_with_tuning(path, EnvTuning::SEQUENTIAL)?;
+        env.read(|txn| -> Result<(), Error> {
+            let reader = LmdbReader::new(txn);
+            node.entry_counts = stats::entry_counts(&reader)?
+                .into_iter()
+                .map(|(db_name, count)| (db_name.to_string(), count))
+                .collect();
+            if reader.has_table(BlockHeaderDatabase::db_name())? {
+                node.latest_block = block_iter::blocks_by_height(&reader)?
+                    .pop()
+                    .map(|(height, hash)| HighestBlock { height, hash });
+                if completeness {
+                    node.completeness = Some(stats::completeness_profile(&reader)?);
+                }
+            }
+            Ok(())
+        })?;
+        node.storage_size = file_size(&storage_path);
+    }
+
+    let trie_store_path = path.join(TRIE_STORE_FILE_NAME);
+    if trie_store_path.exists() {
+        let env = TrieEnv::open_with_tuning(path, EnvTuning::SEQUENTIAL)?;
+        node.trie_entries =
+            Some(env.read(|txn| -> Result<_, Error> {
+                Ok(lmdb_utils::entry_count(txn, env.db()?)?)
+            })?);
+        node.trie_store_size = file_size(&trie_store_path);
+    }
+    Ok(node)
+}
+
+/// Reads a node from a signed manifest created on it, checking the
+/// signature.
+fn read_manifest(path: &Path) -> Result<NodeReport, Error> {
+    let file = File::open(path).map_err(|io_err| Error::Manifest(path.to_path_buf(), io_err))?;
+    let signed_manifest: SignedManifest = serde_json::from_reader(file)
+        .map_err(|serde_err| Error::ManifestContent(path.to_path_buf(), serde_err.into()))?;
+    signed_manifest
+        .verify_signature()
+        .map_err(|manifest_err| Error::ManifestContent(path.to_path_buf(), manifest_err))?;
+    info!(
+        "Manifest {} is signed by {}.",
+        path.display(),
+        signed_manifest.signer.to_hex()
+    );
+    let manifest = signed_manifest.manifest;
+    Ok(NodeReport {
+        chain_name: manifest.chain_name,
+        latest_block: manifest.highest_block,
+        entry_counts: manifest
+            .storage
+            .into_iter()
+            .filter(|(db_name, _)| STATS_DATABASES.contains(&db_name.as_str()))
+            .map(|(db_name, digest)| (db_name, digest.entries))
+            .collect(),
+        trie_entries: manifest.trie_store.map(|digest| digest.entries),
+        manifest_created_at: Some(manifest.created_at),
+        ..Default::default()
+    })
+}
+
+/// Returns the median of `values`, or `None` if there are too few of them
+/// to tell outliers apart.
+fn median<I: Iterator<Item = u64>>(values: I) -> Option<u64> {
+    let mut values: Vec<u64> = values.collect();
+    if values.len() < MIN_SIZES_COMPARED {
+        return None;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some(values[mid - 1] + (values[mid] - values[mid - 1]) / 2)
+    } else {
+        Some(values[mid])
+    }
+}
+
+/// Returns the shares of blocks having each part present, in percent.
+fn shares(profile: &CompletenessProfile) -> [(&'static str, f64); 4] {
+    [
+        ("with body", profile.with_body.percentage),
+        ("with deploys", profile.with_deploys.percentage),
+        (
+            "with execution results",
+            profile.with_execution_results.percentage,
+        ),
+        ("with signatures", profile.with_signatures.percentage),
+    ]
+}
+
+fn is_size_outlier(size: u64, median: u64, size_deviation: u64) -> bool {
+    size.abs_diff(median) as u128 * 100 > median as u128 * size_deviation as u128
+}
+
+fn find_outliers(
+    node: &NodeReport,
+    report: &FleetReport,
+    thresholds: Thresholds,
+    best_shares: Option<[(&'static str, f64); 4]>,
+) -> Vec<Outlier> {
+    let mut outliers = vec![];
+    match (node.latest_block.as_ref(), report.highest_height) {
+        (None, _) => outliers.push(Outlier::NoBlocks),
+        (Some(block), Some(highest_height)) if highest_height - block.height > thresholds.lag => {
+            outliers.push(Outlier::Lagging {
+                blocks_behind: highest_height - block.height,
+            })
+        }
+        _ => (),
+    }
+    if let (Some(size), Some(median)) = (node.storage_size, report.median_storage_size) {
+        if is_size_outlier(size, median, thresholds.size_deviation) {
+            outliers.push(Outlier::StorageSize { size, median });
+        }
+    }
+    if let (Some(size), Some(median)) = (node.trie_store_size, report.median_trie_store_size) {
+        if is_size_outlier(size, median, thresholds.size_deviation) {
+            outliers.push(Outlier::TrieStoreSize { size, median });
+        }
+    }
+    if let (Some(profile), Some(best_shares)) = (node.completeness.as_ref(), best_shares) {
+        for ((part, percentage), (_, best_percentage)) in
+            shares(profile).into_iter().zip(best_shares)
+        {
+            if percentage + COMPLETENESS_TOLERANCE < best_percentage {
+                outliers.push(Outlier::Incomplete {
+                    part,
+                    percentage,
+                    best_percentage,
+                });
+            }
+        }
+    }
+    outliers
+}
+
+/// Reads every node of `sources` and compares them, checking the
+/// completeness of their blocks if `completeness` is set. Nodes which can't
+/// be read are reported with the error.
+pub(crate) fn fleet_report(
+    sources: &[NodeSource],
+    thresholds: Thresholds,
+    completeness: bool,
+) -> FleetReport {
+    let mut nodes = vec![];
+    for source in sources {
+        info!("Reading node {}.", source.name);
+        let result = if source.is_manifest() {
+            read_manifest(&source.path)
+        } else {
+            read_databases(&source.path, completeness)
+        };
+        let node = result.unwrap_or_else(|error| {
+            warn!("Couldn't read node {}: {}", source.name, error);
+            NodeReport {
+                error: Some(error.to_string()),
+                ..Default::default()
+            }
+        });
+        nodes.push(NodeReport {
+            name: source.name.clone(),
+            path: source.path.clone(),
+            ..node
+        });
+    }
+
+    let mut report = FleetReport {
+        highest_height: nodes
+            .iter()
+            .filter_map(|node| node.latest_block.as_ref())
+            .map(|block| block.height)
+            .max(),
+        median_storage_size: median(nodes.iter().filter_map(|node| node.storage_size)),
+        median_trie_store_size: median(nodes.iter().filter_map(|node| node.trie_store_size)),
+        unreadable: nodes.iter().filter(|node| node.error.is_some()).count(),
+        ..Default::default()
+    };
+    let best_shares = nodes
+        .iter()
+        .filter_map(|node| node.completeness.as_ref())
+        .map(shares)
+        .reduce(|mut best, shares| {
+            for ((_, best_percentage), (_, percentage)) in best.iter_mut().zip(shares) {
+                *best_percentage = best_percentage.max(percentage);
+            }
+            best
+        });
+    for node in nodes.iter_mut().filter(|node| node.error.is_none()) {
+        node.outliers = find_outliers(node, &report, thresholds, best_shares);
+        for outlier in node.outliers.iter() {
+            warn!("Node {}: {}.", node.name, outlier);
+        }
+    }
+    report.flagged = nodes
+        .iter()
+        .filter(|node| !node.outliers.is_empty())
+        .count();
+    info!(
+        "Read {} nodes, {} flagged and {} unreadable.",
+        nodes.len(),
+        report.flagged,
+        report.unreadable
+    );
+    report.nodes = nodes;
+    report
+}
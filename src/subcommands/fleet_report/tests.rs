@@ -0,0 +1,101 @@
+use std::fs;
+
+use casper_types::SecretKey;
+use lmdb::{Transaction, WriteFlags};
+
+use super::report::{fleet_report, NodeSource, Outlier, Thresholds};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database, DeployDatabase,
+        DeployMetadataDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::manifest::content::{ContentManifest, SignedManifest},
+    test_utils::{mock_block_header, LmdbTestFixture},
+};
+
+fn node_with_blocks(heights: &[u8]) -> LmdbTestFixture {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for height in heights {
+        let (block_hash, mut header) = mock_block_header(*height);
+        header.height = *height as u64;
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    fixture
+}
+
+#[test]
+fn fleet_report_should_flag_lagging_nodes() {
+    let synced = node_with_blocks(&[0, 1, 2, 3]);
+    let lagging = node_with_blocks(&[0]);
+    // The manifest of the synced node, as fetched from it.
+    let manifest_dir = tempfile::tempdir().unwrap();
+    let manifest_path = manifest_dir.path().join("manifest.json");
+    let secret_key = SecretKey::ed25519_from_bytes([1u8; 32]).unwrap();
+    let manifest = ContentManifest::new(synced.tmp_dir.path()).unwrap();
+    let signed_manifest = SignedManifest::sign(manifest, &secret_key).unwrap();
+    fs::write(
+        &manifest_path,
+        serde_json::to_vec(&signed_manifest).unwrap(),
+    )
+    .unwrap();
+
+    let sources = vec![
+        NodeSource::parse(&format!("synced={}", synced.tmp_dir.path().display())),
+        NodeSource::parse(lagging.tmp_dir.path().to_str().unwrap()),
+        NodeSource::parse(&format!("remote={}", manifest_path.display())),
+        NodeSource::parse("missing=/nonexistent"),
+    ];
+    assert_eq!(sources[1].name, lagging.tmp_dir.path().to_str().unwrap());
+    let thresholds = Thresholds {
+        lag: 1,
+        size_deviation: 50,
+    };
+    let report = fleet_report(&sources, thresholds, true);
+    assert_eq!(report.highest_height, Some(3));
+    // Sizes aren't compared with only two of them known.
+    assert_eq!(report.median_storage_size, None);
+    assert_eq!(report.flagged, 1);
+    assert_eq!(report.unreadable, 1);
+    assert_eq!(report.nodes.len(), 4);
+
+    let synced_node = &report.nodes[0];
+    assert_eq!(synced_node.name, "synced");
+    assert_eq!(synced_node.latest_block.as_ref().unwrap().height, 3);
+    assert_eq!(synced_node.completeness.as_ref().unwrap().blocks, 4);
+    assert!(synced_node.storage_size.is_some());
+    assert!(synced_node.outliers.is_empty());
+
+    assert_eq!(
+        report.nodes[1].outliers,
+        vec![Outlier::Lagging { blocks_behind: 3 }]
+    );
+
+    let remote_node = &report.nodes[2];
+    assert_eq!(remote_node.latest_block, synced_node.latest_block);
+    assert_eq!(remote_node.entry_counts, synced_node.entry_counts);
+    assert!(remote_node.manifest_created_at.is_some());
+    assert!(remote_node.completeness.is_none());
+    assert!(remote_node.storage_size.is_none());
+    assert!(remote_node.outliers.is_empty());
+
+    let missing_node = &report.nodes[3];
+    assert!(missing_node.error.is_some());
+    assert!(missing_node.outliers.is_empty());
+}
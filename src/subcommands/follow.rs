@@ -0,0 +1,164 @@
+mod follower;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, io::Write, path::Path, thread, time::Duration};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    db::StorageEnv,
+    output::{self, Compression, OutputWriter},
+    storage::{Error as StorageError, LmdbReader},
+};
+use follower::Follower;
+
+pub const COMMAND_NAME: &str = "follow";
+const DB_PATH: &str = "db-path";
+const FROM_HEIGHT: &str = "from-height";
+const INTERVAL: &str = "interval";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when following the blocks of a storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Self::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    Interval,
+    FromHeight,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Watches the storage database of a running node for new blocks, \
+            outputting a JSON record per line for each one as it's stored, \
+            with its height, hash, era, timestamp and number of deploys. \
+            Runs until interrupted.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the records. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(INTERVAL)
+                .display_order(DisplayOrder::Interval as usize)
+                .short('i')
+                .long(INTERVAL)
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("2")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(0) => Err("interval must be at least 1 second".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help("Time between two checks of the database for new blocks."),
+        )
+        .arg(
+            Arg::new(FROM_HEIGHT)
+                .display_order(DisplayOrder::FromHeight as usize)
+                .long(FROM_HEIGHT)
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help(
+                    "Also output the blocks already in the database from this \
+                    height on, and ignore new blocks below it. By default, \
+                    only the blocks stored after the start are output.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let interval = Duration::from_secs(
+        matches
+            .value_of(INTERVAL)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated"),
+    );
+    let maybe_from_height = matches
+        .value_of(FROM_HEIGHT)
+        .map(|value| value.parse().expect("should be validated"));
+
+    let env = StorageEnv::open(db_path)?;
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let mut follower = Follower::new(maybe_from_height);
+    loop {
+        // Each poll reads from a new transaction to see the blocks committed
+        // by the node since the last one.
+        let records =
+            env.read(|txn| -> Result<_, Error> { Ok(follower.poll(&LmdbReader::new(txn))?) })?;
+        for record in records {
+            serde_json::to_writer(&mut out_writer, &record)?;
+            writeln!(out_writer)?;
+        }
+        out_writer.flush()?;
+        thread::sleep(interval);
+    }
+}
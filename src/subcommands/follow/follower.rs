@@ -0,0 +1,109 @@
+use std::{collections::HashSet, result::Result};
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{EraId, Timestamp};
+use log::info;
+use serde::Serialize;
+
+use crate::common::{
+    block_iter::{self, Error},
+    db::{BlockHeaderDatabase, Database},
+    storage::StorageReader,
+};
+
+/// A block newly observed in the database.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct BlockRecord {
+    pub(crate) height: u64,
+    pub(crate) hash: BlockHash,
+    pub(crate) era: EraId,
+    pub(crate) timestamp: Timestamp,
+    /// Number of deploys other than native transfers, `None` if the body of
+    /// the block is missing.
+    pub(crate) deploy_count: Option<usize>,
+    /// Number of native transfers, `None` if the body of the block is
+    /// missing.
+    pub(crate) transfer_count: Option<usize>,
+}
+
+/// Keeps track of the blocks of a storage database which were already seen,
+/// to report the new ones.
+pub(crate) struct Follower {
+    seen: HashSet<BlockHash>,
+    /// Number of headers in the database at the last poll.
+    maybe_header_count: Option<usize>,
+    /// Lowest height of the reported blocks. If set, the blocks found in the
+    /// first poll are reported too.
+    maybe_from_height: Option<u64>,
+}
+
+impl Follower {
+    pub(crate) fn new(maybe_from_height: Option<u64>) -> Self {
+        Self {
+            seen: HashSet::new(),
+            maybe_header_count: None,
+            maybe_from_height,
+        }
+    }
+
+    /// Returns the blocks which were stored since the last poll, by ascending
+    /// height. The first poll only returns blocks if a starting height was
+    /// given, unless the header database didn't exist yet.
+    ///
+    /// The header database is keyed by hash, so new blocks are looked for in
+    /// all of it, but only when its number of entries changed.
+    pub(crate) fn poll<R: StorageReader>(&mut self, reader: &R) -> Result<Vec<BlockRecord>, Error> {
+        let first_poll = self.maybe_header_count.is_none();
+        if !reader.has_table(BlockHeaderDatabase::db_name())? {
+            self.maybe_header_count = Some(0);
+            return Ok(vec![]);
+        }
+        let header_count = reader.entry_count(BlockHeaderDatabase::db_name())?;
+        if self.maybe_header_count == Some(header_count) {
+            return Ok(vec![]);
+        }
+        self.maybe_header_count = Some(header_count);
+
+        let mut new_blocks: Vec<(BlockHash, BlockHeader)> = vec![];
+        let seen = &mut self.seen;
+        let mut idx = 0;
+        reader.scan(
+            BlockHeaderDatabase::db_name(),
+            |raw_key, raw_val| -> Result<(), Error> {
+                let block_hash =
+                    BlockHash::new(raw_key.try_into().map_err(|_| Error::InvalidKey(idx))?);
+                idx += 1;
+                if seen.insert(block_hash) {
+                    let header: BlockHeader = bincode::deserialize(raw_val)
+                        .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+                    new_blocks.push((block_hash, header));
+                }
+                Ok(())
+            },
+        )?;
+        if first_poll {
+            info!("Found {} blocks in the database.", new_blocks.len());
+            if self.maybe_from_height.is_none() {
+                return Ok(vec![]);
+            }
+        }
+
+        let from_height = self.maybe_from_height.unwrap_or_default();
+        new_blocks.retain(|(_, header)| header.height() >= from_height);
+        new_blocks.sort_unstable_by_key(|(_, header)| header.height());
+        new_blocks
+            .into_iter()
+            .map(|(block_hash, header)| {
+                let maybe_body = block_iter::read_body(reader, &block_hash, &header)?;
+                Ok(BlockRecord {
+                    height: header.height(),
+                    hash: block_hash,
+                    era: header.era_id(),
+                    timestamp: header.timestamp(),
+                    deploy_count: maybe_body.as_ref().map(|body| body.deploy_hashes.len()),
+                    transfer_count: maybe_body.as_ref().map(|body| body.transfer_hashes.len()),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,103 @@
+use casper_types::EraId;
+use lmdb::{Transaction, WriteFlags};
+
+use super::follower::{BlockRecord, Follower};
+use crate::{
+    common::{
+        db::{BlockBodyDatabase, BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+        storage::LmdbReader,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+/// Stores the block with hash index `idx` at `height` in era `height / 2`,
+/// with `deploy_count` deploys. The body is left out if `deploy_count` is
+/// `None`.
+fn put_block(
+    fixture: &LmdbTestFixture,
+    idx: u8,
+    height: u8,
+    maybe_deploy_count: Option<u8>,
+) -> BlockRecord {
+    let (block_hash, mut header) = test_utils::mock_block_header(idx);
+    header.height = height as u64;
+    header.era_id = EraId::new(height as u64 / 2);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    if let Some(deploy_count) = maybe_deploy_count {
+        let deploy_hashes = (0..deploy_count)
+            .map(test_utils::mock_deploy_hash)
+            .collect();
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &header.body_hash,
+            &bincode::serialize(&BlockBody::new(deploy_hashes)).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    BlockRecord {
+        height: header.height,
+        hash: block_hash,
+        era: header.era_id,
+        timestamp: header.timestamp,
+        deploy_count: maybe_deploy_count.map(usize::from),
+        transfer_count: maybe_deploy_count.map(|_| 0),
+    }
+}
+
+fn poll(fixture: &LmdbTestFixture, follower: &mut Follower) -> Vec<BlockRecord> {
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let records = follower.poll(&LmdbReader::new(&txn)).unwrap();
+    txn.commit().unwrap();
+    records
+}
+
+fn new_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+#[test]
+fn follow_should_output_new_blocks_in_height_order() {
+    let fixture = new_fixture();
+    put_block(&fixture, 0, 0, Some(1));
+    put_block(&fixture, 1, 1, Some(2));
+
+    // The blocks stored before the start aren't output.
+    let mut follower = Follower::new(None);
+    assert!(poll(&fixture, &mut follower).is_empty());
+
+    let expected = vec![
+        put_block(&fixture, 2, 2, Some(3)),
+        put_block(&fixture, 9, 3, None),
+        put_block(&fixture, 5, 4, Some(0)),
+    ];
+    assert_eq!(poll(&fixture, &mut follower), expected);
+    assert!(poll(&fixture, &mut follower).is_empty());
+
+    let expected = vec![put_block(&fixture, 3, 5, Some(1))];
+    assert_eq!(poll(&fixture, &mut follower), expected);
+}
+
+#[test]
+fn follow_from_height_should_output_stored_blocks() {
+    let fixture = new_fixture();
+    put_block(&fixture, 0, 0, Some(1));
+    let mut expected = vec![put_block(&fixture, 1, 1, Some(2))];
+    expected.push(put_block(&fixture, 2, 2, Some(0)));
+
+    let mut follower = Follower::new(Some(1));
+    assert_eq!(poll(&fixture, &mut follower), expected);
+    assert!(poll(&fixture, &mut follower).is_empty());
+}
@@ -0,0 +1,164 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::{
+    archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+    execution_results_summary::Error as ExecutionResultsError,
+};
+use crate::common::{
+    coverage,
+    output::{self, Compression, OutputWriter},
+    storage::{self, Backend, Error as StorageError},
+};
+use report::{Format, Granularity};
+
+pub const COMMAND_NAME: &str = "gas-report";
+const DB_PATH: &str = "db-path";
+const FORMAT: &str = "format";
+const GRANULARITY: &str = "by";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when building the gas report.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error reading the execution results of a block.
+    #[error("Error reading execution results: {0}")]
+    ExecutionResults(#[from] ExecutionResultsError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    Granularity,
+    Format,
+    Partial,
+    Backend,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Aggregates the costs of the execution results in a storage \
+            database per block or per era, and outputs them as a time series \
+            of total, average and maximum cost along with the share of failed \
+            executions.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(GRANULARITY)
+                .display_order(DisplayOrder::Granularity as usize)
+                .short('b')
+                .long(GRANULARITY)
+                .takes_value(true)
+                .value_name("block|era")
+                .possible_values(["block", "era"])
+                .default_value("era")
+                .help("Aggregate the costs per block or per era."),
+        )
+        .arg(
+            Arg::new(FORMAT)
+                .display_order(DisplayOrder::Format as usize)
+                .short('f')
+                .long(FORMAT)
+                .takes_value(true)
+                .value_name("json|csv")
+                .possible_values(["json", "csv"])
+                .default_value("json")
+                .help("Format of the output."),
+        )
+        .arg(coverage::partial_arg(DisplayOrder::Partial as usize))
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let granularity = match matches.value_of(GRANULARITY) {
+        Some("block") => Granularity::Block,
+        _ => Granularity::Era,
+    };
+    let format = match matches.value_of(FORMAT) {
+        Some("csv") => Format::Csv,
+        _ => Format::Json,
+    };
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let points = report::gas_report(
+        db_dir.path(),
+        granularity,
+        Backend::from_matches(matches),
+        output.is_some(),
+        coverage::is_partial(matches),
+    )?;
+    report::write_points(&points, format, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
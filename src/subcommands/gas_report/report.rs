@@ -0,0 +1,212 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    io::Write,
+    path::Path,
+    result::Result,
+};
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{EraId, ExecutionResult, Timestamp, U512};
+use log::info;
+use serde::Serialize;
+
+use crate::{
+    common::{
+        coverage::Coverage,
+        db::EnvTuning,
+        storage::{Backend, Storage},
+    },
+    subcommands::execution_results_summary::read_db,
+};
+
+use super::Error;
+
+/// Header of the CSV output, in the order `GasPoint::write_csv` writes the
+/// fields.
+const CSV_HEADER: &str = "era_id,height,block_hash,timestamp,blocks,executions,failed,\
+    total_cost,average_cost,max_cost,failed_share";
+
+/// What each point of the time series aggregates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Granularity {
+    Block,
+    Era,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Csv,
+}
+
+/// Costs of the execution results of a block or of an era.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct GasPoint {
+    pub(crate) era_id: EraId,
+    /// Height of the block, or of the first block of the era.
+    pub(crate) height: u64,
+    /// Hash of the block, absent when aggregating per era.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) block_hash: Option<BlockHash>,
+    /// Timestamp of the block, or of the first block of the era.
+    pub(crate) timestamp: Timestamp,
+    pub(crate) blocks: usize,
+    pub(crate) executions: usize,
+    pub(crate) failed: usize,
+    pub(crate) total_cost: U512,
+    pub(crate) average_cost: U512,
+    pub(crate) max_cost: U512,
+    /// Share of the executions which failed, between 0 and 1.
+    pub(crate) failed_share: f64,
+}
+
+impl GasPoint {
+    fn new(
+        block_hash: BlockHash,
+        header: &BlockHeader,
+        execution_results: &[ExecutionResult],
+    ) -> Self {
+        let mut point = Self {
+            era_id: header.era_id(),
+            height: header.height(),
+            block_hash: Some(block_hash),
+            timestamp: header.timestamp(),
+            blocks: 1,
+            executions: execution_results.len(),
+            failed: 0,
+            total_cost: U512::zero(),
+            average_cost: U512::zero(),
+            max_cost: U512::zero(),
+            failed_share: 0.0,
+        };
+        for execution_result in execution_results {
+            let cost = match execution_result {
+                ExecutionResult::Failure { cost, .. } => {
+                    point.failed += 1;
+                    *cost
+                }
+                ExecutionResult::Success { cost, .. } => *cost,
+            };
+            point.total_cost += cost;
+            point.max_cost = point.max_cost.max(cost);
+        }
+        point.update_ratios();
+        point
+    }
+
+    /// Adds the costs of `other` to the costs of this point, which become
+    /// the costs of the era of both.
+    fn merge(&mut self, other: Self) {
+        if other.height < self.height {
+            self.height = other.height;
+            self.timestamp = other.timestamp;
+        }
+        self.block_hash = None;
+        self.blocks += other.blocks;
+        self.executions += other.executions;
+        self.failed += other.failed;
+        self.total_cost += other.total_cost;
+        self.max_cost = self.max_cost.max(other.max_cost);
+        self.update_ratios();
+    }
+
+    fn update_ratios(&mut self) {
+        if self.executions > 0 {
+            self.average_cost = self.total_cost / self.executions;
+            self.failed_share = self.failed as f64 / self.executions as f64;
+        }
+    }
+
+    fn write_csv<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), Error> {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.era_id.value(),
+            self.height,
+            self.block_hash
+                .map(|block_hash| block_hash.to_string())
+                .unwrap_or_default(),
+            self.timestamp,
+            self.blocks,
+            self.executions,
+            self.failed,
+            self.total_cost,
+            self.average_cost,
+            self.max_cost,
+            self.failed_share
+        )?;
+        Ok(())
+    }
+}
+
+/// Aggregates the costs of the execution results in the storage database at
+/// `db_path` per block or per era, in ascending height order. With
+/// `partial`, the blocks lacking their body or execution results are skipped
+/// and the ranges of heights they span are logged.
+pub(crate) fn gas_report<P: AsRef<Path>>(
+    db_path: P,
+    granularity: Granularity,
+    backend: Backend,
+    log_progress: bool,
+    partial: bool,
+) -> Result<Vec<GasPoint>, Error> {
+    let storage = Storage::open(&db_path, backend, EnvTuning::default())?;
+
+    let mut blocks = vec![];
+    let mut eras: BTreeMap<EraId, GasPoint> = BTreeMap::new();
+    let mut maybe_coverage = partial.then(Coverage::default);
+    read_db::for_each_block(
+        &storage,
+        log_progress,
+        maybe_coverage.as_mut(),
+        |block_hash, header, _deploy_count, execution_results| {
+            let point = GasPoint::new(block_hash, header, &execution_results);
+            match granularity {
+                Granularity::Block => blocks.push(point),
+                Granularity::Era => match eras.entry(point.era_id) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(GasPoint {
+                            block_hash: None,
+                            ..point
+                        });
+                    }
+                    Entry::Occupied(mut entry) => entry.get_mut().merge(point),
+                },
+            }
+            Ok(())
+        },
+    )?;
+
+    let points = match granularity {
+        Granularity::Block => {
+            blocks.sort_by_key(|point| point.height);
+            blocks
+        }
+        Granularity::Era => eras.into_values().collect(),
+    };
+    info!("Aggregated costs into {} points.", points.len());
+    if let Some(coverage) = maybe_coverage.as_ref() {
+        coverage.log();
+    }
+    Ok(points)
+}
+
+pub(crate) fn write_points<W: Write + ?Sized>(
+    points: &[GasPoint],
+    format: Format,
+    writer: &mut W,
+) -> Result<(), Error> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *writer, points)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => {
+            writeln!(writer, "{}", CSV_HEADER)?;
+            for point in points {
+                point.write_csv(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use casper_types::{EraId, ExecutionEffect, ExecutionResult, Timestamp, U512};
+use lmdb::{Transaction, WriteFlags};
+
+use super::report::{gas_report, write_points, Format, Granularity};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployMetadataDatabase,
+            STORAGE_FILE_NAME,
+        },
+        storage::Backend,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+#[test]
+fn gas_report_should_aggregate_costs() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    // Blocks 0 and 1 are in era 0, block 2 in era 1. Each block executes
+    // the deploy with its index, and block 1 also executes deploy 3, which
+    // fails.
+    let blocks: Vec<_> = (0..3u8)
+        .map(|idx| {
+            let (block_hash, mut header) = test_utils::mock_block_header(idx);
+            header.height = idx as u64;
+            header.era_id = EraId::new(idx as u64 / 2);
+            header.timestamp = Timestamp::from(idx as u64 * 1000);
+            (block_hash, header)
+        })
+        .collect();
+    let mut failed_metadata = test_utils::mock_deploy_metadata(&[]);
+    failed_metadata.execution_results.insert(
+        blocks[1].0,
+        ExecutionResult::Failure {
+            effect: ExecutionEffect::default(),
+            transfers: vec![],
+            cost: 400.into(),
+            error_message: "failed".to_string(),
+        },
+    );
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, (block_hash, header)) in blocks.iter().enumerate() {
+        let mut deploy_hashes = vec![test_utils::mock_deploy_hash(idx as u8)];
+        if idx == 1 {
+            deploy_hashes.push(test_utils::mock_deploy_hash(3));
+        }
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            block_hash,
+            &bincode::serialize(header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &header.body_hash,
+            &bincode::serialize(&BlockBody::new(deploy_hashes)).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            &test_utils::mock_deploy_hash(idx as u8),
+            &bincode::serialize(&test_utils::mock_deploy_metadata(&[*block_hash])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.put(
+        *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+        &test_utils::mock_deploy_hash(3),
+        &bincode::serialize(&failed_metadata).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let points = gas_report(
+        fixture.tmp_dir.path(),
+        Granularity::Block,
+        Backend::Lmdb,
+        false,
+        false,
+    )
+    .unwrap();
+    assert_eq!(points.len(), 3);
+    assert_eq!(points[1].block_hash, Some(blocks[1].0));
+    assert_eq!(points[1].executions, 2);
+    assert_eq!(points[1].failed, 1);
+    assert_eq!(points[1].total_cost, U512::from(500));
+    assert_eq!(points[1].average_cost, U512::from(250));
+    assert_eq!(points[1].max_cost, U512::from(400));
+    assert_eq!(points[1].failed_share, 0.5);
+
+    let points = gas_report(
+        fixture.tmp_dir.path(),
+        Granularity::Era,
+        Backend::Lmdb,
+        false,
+        false,
+    )
+    .unwrap();
+    assert_eq!(points.len(), 2);
+    let era_0 = &points[0];
+    assert_eq!(era_0.era_id, EraId::new(0));
+    assert_eq!(era_0.height, 0);
+    assert_eq!(era_0.block_hash, None);
+    assert_eq!(era_0.blocks, 2);
+    assert_eq!(era_0.executions, 3);
+    assert_eq!(era_0.failed, 1);
+    assert_eq!(era_0.total_cost, U512::from(600));
+    assert_eq!(era_0.average_cost, U512::from(200));
+    assert_eq!(era_0.max_cost, U512::from(400));
+    let era_1 = &points[1];
+    assert_eq!(era_1.height, 2);
+    assert_eq!(era_1.blocks, 1);
+    assert_eq!(era_1.total_cost, U512::from(100));
+    assert_eq!(era_1.failed_share, 0.0);
+
+    let mut csv = vec![];
+    write_points(&points, Format::Csv, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("era_id,height,block_hash,"));
+    assert_eq!(
+        lines[2],
+        format!("1,2,,{},1,1,0,100,100,100,0", Timestamp::from(2000))
+    );
+}
@@ -0,0 +1,201 @@
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use clap::{Arg, ArgMatches, Command};
+use roff::{bold, italic, roman, Inline, Roff};
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+
+pub const COMMAND_NAME: &str = "gen-man";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when generating the man page.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+}
+
+enum DisplayOrder {
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs a man page in roff format documenting every subcommand \
+            and option of this program.",
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the man page. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+/// Returns the visible subcommands of `cmd`, recursively and depth first,
+/// along with the names of the subcommands leading to them, starting with
+/// `cmd` itself.
+fn command_paths<'a, 'help>(cmd: &'a Command<'help>) -> Vec<(Vec<&'a str>, &'a Command<'help>)> {
+    let mut paths = vec![(vec![cmd.get_name()], cmd)];
+    let mut idx = 0;
+    while idx < paths.len() {
+        let (path, parent) = paths[idx].clone();
+        idx += 1;
+        for subcmd in parent
+            .get_subcommands()
+            .filter(|subcmd| !subcmd.is_hide_set())
+        {
+            let mut subcmd_path = path.clone();
+            subcmd_path.push(subcmd.get_name());
+            paths.push((subcmd_path, subcmd));
+        }
+    }
+    paths
+}
+
+/// Returns the help of `arg` or the about of `cmd` on a single line.
+fn one_line(text: Option<&str>) -> String {
+    text.unwrap_or_default()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Returns the usage of `arg`, such as `-d, --db-path=DB_PATH`.
+fn arg_usage(arg: &Arg) -> Vec<Inline> {
+    let value_name = arg
+        .get_value_names()
+        .and_then(|value_names| value_names.first())
+        .map(|value_name| value_name.to_string())
+        .unwrap_or_else(|| arg.get_id().to_uppercase());
+    if arg.is_positional() {
+        let repeat = if arg.is_multiple_values_set() {
+            "..."
+        } else {
+            ""
+        };
+        return vec![italic(value_name), roman(repeat)];
+    }
+    let mut usage = vec![];
+    if let Some(short) = arg.get_short() {
+        usage.push(bold(format!("-{}", short)));
+    }
+    if let Some(long) = arg.get_long() {
+        if !usage.is_empty() {
+            usage.push(roman(", "));
+        }
+        usage.push(bold(format!("--{}", long)));
+    }
+    if arg.is_takes_value_set() {
+        usage.push(roman("="));
+        usage.push(italic(value_name));
+    }
+    usage
+}
+
+/// Adds the options and positional arguments of `cmd` as tagged
+/// paragraphs.
+fn add_args(cmd: &Command, roff: &mut Roff) {
+    for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+        let mut help = one_line(arg.get_long_help().or_else(|| arg.get_help()));
+        if let Some(possible_values) = arg.get_possible_values() {
+            let values: Vec<_> = possible_values
+                .iter()
+                .filter(|possible_value| !possible_value.is_hide_set())
+                .map(|possible_value| possible_value.get_name())
+                .collect();
+            help.push_str(&format!(" [possible values: {}]", values.join(", ")));
+        }
+        if let Some(default_value) = arg.get_default_values().first() {
+            help.push_str(&format!(" [default: {}]", default_value.to_string_lossy()));
+        }
+        roff.control("TP", [])
+            .text(arg_usage(arg))
+            .text([roman(help.trim())]);
+    }
+}
+
+/// Writes a man page for `cli` to `out`, with a section documenting each of
+/// its subcommands, nested ones included.
+pub(crate) fn generate<W: Write>(mut cli: Command, out: &mut W) -> Result<(), IoError> {
+    // Building the command adds the help and version arguments and
+    // propagates global arguments to the subcommands.
+    cli.build();
+    let bin_name = cli.get_name();
+    let version = cli
+        .get_version()
+        .and_then(|version| version.lines().next())
+        .unwrap_or_default();
+    let title = bin_name.to_uppercase();
+    let source = format!("{} {}", bin_name, version);
+    let mut roff = Roff::new();
+    // An empty argument would be dropped, so the empty date is quoted.
+    roff.control("TH", [title.as_str(), "1", "\"\"", source.as_str()])
+        .control("SH", ["NAME"])
+        .text([roman(format!(
+            "{} - {}",
+            bin_name,
+            one_line(cli.get_about())
+        ))])
+        .control("SH", ["SYNOPSIS"])
+        .text([
+            bold(bin_name),
+            roman(" ["),
+            italic("OPTIONS"),
+            roman("] "),
+            italic("SUBCOMMAND"),
+        ])
+        .control("SH", ["OPTIONS"]);
+    add_args(&cli, &mut roff);
+    roff.control("SH", ["SUBCOMMANDS"]);
+    for (path, cmd) in command_paths(&cli).into_iter().skip(1) {
+        roff.control("SS", [path[1..].join(" ").as_str()])
+            .text([roman(one_line(
+                cmd.get_long_about().or_else(|| cmd.get_about()),
+            ))]);
+        add_args(cmd, &mut roff);
+    }
+    roff.to_writer(out)
+}
+
+pub fn run(matches: &ArgMatches, cli: Command) -> Result<(), Error> {
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    generate(cli, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,48 @@
+use clap::{Arg, Command};
+
+use super::generate;
+
+#[test]
+fn man_page_should_document_subcommands() {
+    let cli = Command::new("db-tool")
+        .version("1.0.0\nsecond line")
+        .about("Works with databases.")
+        .subcommand(
+            Command::new("archive")
+                .about("Archive utilities.")
+                .subcommand(
+                    Command::new("create")
+                        .about(".Creates an archive.")
+                        .arg(
+                            Arg::new("db-path")
+                                .short('d')
+                                .long("db-path")
+                                .takes_value(true)
+                                .value_name("DB_PATH")
+                                .help("Path of the database."),
+                        )
+                        .arg(
+                            Arg::new("level")
+                                .long("level")
+                                .takes_value(true)
+                                .possible_values(["fast", "best"])
+                                .default_value("fast")
+                                .help("Compression level."),
+                        ),
+                ),
+        );
+    let mut out = vec![];
+    generate(cli, &mut out).unwrap();
+    let man_page = String::from_utf8(out).unwrap();
+
+    assert!(man_page.contains(".TH DB-TOOL 1 \"\" \"db-tool 1.0.0\"\n"));
+    assert!(man_page.contains(".SH NAME\ndb\\-tool \\- Works with databases.\n"));
+    assert!(man_page.contains(".SS \"archive create\"\n\\&.Creates an archive.\n"));
+    assert!(man_page.contains(
+        ".TP\n\\fB\\-d\\fR, \\fB\\-\\-db\\-path\\fR=\\fIDB_PATH\\fR\nPath of the database.\n"
+    ));
+    assert!(man_page.contains(
+        ".TP\n\\fB\\-\\-level\\fR=\\fILEVEL\\fR\nCompression level. \
+        [possible values: fast, best] [default: fast]\n"
+    ));
+}
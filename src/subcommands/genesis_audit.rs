@@ -0,0 +1,167 @@
+mod accounts;
+mod audit;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use casper_execution_engine::storage::error::Error as GlobalStateError;
+use casper_hashing::Digest;
+use casper_types::Key;
+use clap::{Arg, ArgMatches, Command};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+
+pub const COMMAND_NAME: &str = "genesis-audit";
+const ACCOUNTS: &str = "accounts";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT: &str = "state-root";
+
+/// Errors encountered when auditing global state against the genesis
+/// accounts.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Global state differs from the accounts config. The report lists the
+    /// discrepancies.
+    #[error("Found {0} discrepancies with the accounts config")]
+    Discrepancies(usize),
+    /// Error reading global state.
+    #[error("Error reading global state: {0}")]
+    GlobalState(#[from] GlobalStateError),
+    /// Error loading the trie store.
+    #[error("Error loading the execution engine: {0}")]
+    LoadExecutionEngine(anyhow::Error),
+    /// The state root isn't in the trie store.
+    #[error("State root {0} not found in the trie store")]
+    MissingStateRoot(Digest),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on the accounts config.
+    #[error("Error parsing accounts config {0}: {1}")]
+    ParseAccounts(PathBuf, toml::de::Error),
+    /// Error reading the accounts config.
+    #[error("Error reading accounts config {0}: {1}")]
+    ReadAccounts(PathBuf, IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// A value in global state isn't of the type stored under its key.
+    #[error("Unexpected value under {0}")]
+    UnexpectedValue(Key),
+}
+
+enum DisplayOrder {
+    DbPath,
+    StateRoot,
+    Accounts,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Compares the account balances, validator stakes and delegations \
+            in the global state under a state root with the ones set up by a \
+            chainspec `accounts.toml` file, and outputs the discrepancies \
+            found in JSON format. Used to validate genesis or the state \
+            migrations of an upgrade offline.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STATE_ROOT)
+                .display_order(DisplayOrder::StateRoot as usize)
+                .required(true)
+                .short('r')
+                .long(STATE_ROOT)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help("Hash of the state root to audit, in hex."),
+        )
+        .arg(
+            Arg::new(ACCOUNTS)
+                .display_order(DisplayOrder::Accounts as usize)
+                .required(true)
+                .short('a')
+                .long(ACCOUNTS)
+                .takes_value(true)
+                .value_name("ACCOUNTS_PATH")
+                .help(
+                    "Path of the `accounts.toml` file, or of the directory \
+                    holding it next to the chainspec.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let state_root_hash = Digest::from_hex(
+        matches
+            .value_of(STATE_ROOT)
+            .expect("should have state-root arg"),
+    )
+    .expect("should be validated");
+    let accounts_path = matches
+        .value_of(ACCOUNTS)
+        .expect("should have accounts arg");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let accounts_config = accounts::read_accounts(accounts_path)?;
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = audit::audit_genesis(path, state_root_hash, &accounts_config)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+
+    if !report.discrepancies.is_empty() {
+        return Err(Error::Discrepancies(report.discrepancies.len()));
+    }
+    Ok(())
+}
@@ -0,0 +1,56 @@
+// The accounts config types of `casper-node` aren't available in its public
+// interface, so the parts of them needed to audit global state are
+// redefined here.
+
+use std::{fs, path::Path, result::Result};
+
+use casper_types::{system::auction::DelegationRate, PublicKey, U512};
+use serde::Deserialize;
+
+use super::Error;
+
+/// Name of the file holding the accounts created at genesis, kept next to
+/// the chainspec.
+pub(crate) const ACCOUNTS_FILE_NAME: &str = "accounts.toml";
+
+/// The accounts, validators and delegators set up at genesis.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct AccountsConfig {
+    #[serde(default)]
+    pub(crate) accounts: Vec<AccountConfig>,
+    #[serde(default)]
+    pub(crate) delegators: Vec<DelegatorConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct AccountConfig {
+    pub(crate) public_key: PublicKey,
+    pub(crate) balance: U512,
+    pub(crate) validator: Option<ValidatorConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ValidatorConfig {
+    pub(crate) bonded_amount: U512,
+    pub(crate) delegation_rate: DelegationRate,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DelegatorConfig {
+    pub(crate) validator_public_key: PublicKey,
+    pub(crate) delegator_public_key: PublicKey,
+    pub(crate) balance: U512,
+    pub(crate) delegated_amount: U512,
+}
+
+/// Reads the accounts config at `path`, or in the `accounts.toml` file in it
+/// if it's a directory, such as the one holding the chainspec.
+pub(crate) fn read_accounts<P: AsRef<Path>>(path: P) -> Result<AccountsConfig, Error> {
+    let mut path = path.as_ref().to_path_buf();
+    if path.is_dir() {
+        path.push(ACCOUNTS_FILE_NAME);
+    }
+    let contents =
+        fs::read_to_string(&path).map_err(|io_err| Error::ReadAccounts(path.clone(), io_err))?;
+    toml::from_str(&contents).map_err(|toml_err| Error::ParseAccounts(path, toml_err))
+}
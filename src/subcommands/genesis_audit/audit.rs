@@ -0,0 +1,238 @@
+use std::{path::Path, result::Result};
+
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId,
+    storage::global_state::{lmdb::LmdbGlobalStateView, StateProvider, StateReader},
+};
+use casper_hashing::Digest;
+use casper_types::{
+    system::auction::{Bid, DelegationRate},
+    Key, PublicKey, StoredValue, URef, U512,
+};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::subcommands::trie_compact::{load_execution_engine, DEFAULT_MAX_DB_SIZE};
+
+use super::{accounts::AccountsConfig, Error};
+
+/// A difference between the accounts config and global state.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub(crate) enum Problem {
+    /// The account isn't in global state.
+    MissingAccount,
+    /// The main purse of the account has no balance.
+    MissingBalance { purse: URef },
+    /// The main purse of the account holds a different balance.
+    BalanceMismatch { expected: U512, actual: U512 },
+    /// The validator has no bid.
+    MissingBid,
+    /// The bid of the validator stakes a different amount.
+    StakeMismatch { expected: U512, actual: U512 },
+    /// The bid of the validator has a different delegation rate.
+    DelegationRateMismatch {
+        expected: DelegationRate,
+        actual: DelegationRate,
+    },
+    /// The bid of the validator has no delegation from the delegator.
+    MissingDelegation { validator_public_key: PublicKey },
+    /// The delegator stakes a different amount with the validator.
+    DelegationMismatch {
+        validator_public_key: PublicKey,
+        expected: U512,
+        actual: U512,
+    },
+}
+
+/// A difference found for the account of `public_key`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Discrepancy {
+    pub(crate) public_key: PublicKey,
+    pub(crate) problem: Problem,
+}
+
+impl Discrepancy {
+    fn new(public_key: &PublicKey, problem: Problem) -> Self {
+        Self {
+            public_key: public_key.clone(),
+            problem,
+        }
+    }
+}
+
+/// The accounts audited under a state root and the discrepancies found.
+#[derive(Debug, Serialize)]
+pub(crate) struct AuditReport {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) accounts: usize,
+    pub(crate) validators: usize,
+    pub(crate) delegators: usize,
+    pub(crate) discrepancies: Vec<Discrepancy>,
+}
+
+/// Reads the accounts and bids of global state under a state root.
+struct GlobalState {
+    view: LmdbGlobalStateView,
+    correlation_id: CorrelationId,
+}
+
+impl GlobalState {
+    fn read(&self, key: &Key) -> Result<Option<StoredValue>, Error> {
+        Ok(self.view.read(self.correlation_id, key)?)
+    }
+
+    /// Returns the balance of the main purse of the account of `public_key`,
+    /// or the problem preventing to read it.
+    fn balance(&self, public_key: &PublicKey) -> Result<Result<U512, Problem>, Error> {
+        let account_key = Key::Account(public_key.to_account_hash());
+        let main_purse = match self.read(&account_key)? {
+            Some(StoredValue::Account(account)) => account.main_purse(),
+            Some(_) => return Err(Error::UnexpectedValue(account_key)),
+            None => return Ok(Err(Problem::MissingAccount)),
+        };
+        let balance_key = Key::Balance(main_purse.addr());
+        match self.read(&balance_key)? {
+            Some(StoredValue::CLValue(cl_value)) => Ok(Ok(cl_value
+                .into_t()
+                .map_err(|_| Error::UnexpectedValue(balance_key))?)),
+            Some(_) => Err(Error::UnexpectedValue(balance_key)),
+            None => Ok(Err(Problem::MissingBalance { purse: main_purse })),
+        }
+    }
+
+    fn bid(&self, validator_public_key: &PublicKey) -> Result<Option<Bid>, Error> {
+        let bid_key = Key::Bid(validator_public_key.to_account_hash());
+        match self.read(&bid_key)? {
+            Some(StoredValue::Bid(bid)) => Ok(Some(*bid)),
+            Some(_) => Err(Error::UnexpectedValue(bid_key)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Compares the balance of the account of `public_key` with `expected`.
+fn audit_balance(
+    global_state: &GlobalState,
+    public_key: &PublicKey,
+    expected: U512,
+) -> Result<Option<Problem>, Error> {
+    Ok(match global_state.balance(public_key)? {
+        Ok(actual) if actual == expected => None,
+        Ok(actual) => Some(Problem::BalanceMismatch { expected, actual }),
+        Err(problem) => Some(problem),
+    })
+}
+
+/// Compares the accounts, validator stakes and delegations of
+/// `accounts_config` with the global state under `state_root_hash` in the
+/// trie store in `db_path`.
+pub(crate) fn audit_genesis<P: AsRef<Path>>(
+    db_path: P,
+    state_root_hash: Digest,
+    accounts_config: &AccountsConfig,
+) -> Result<AuditReport, Error> {
+    let max_db_size = DEFAULT_MAX_DB_SIZE
+        .parse()
+        .expect("should be able to parse max db size");
+    let (engine_state, _env) = load_execution_engine(db_path, max_db_size, Digest::default(), true)
+        .map_err(Error::LoadExecutionEngine)?;
+    let global_state = GlobalState {
+        view: engine_state
+            .get_state()
+            .checkout(state_root_hash)?
+            .ok_or(Error::MissingStateRoot(state_root_hash))?,
+        correlation_id: CorrelationId::new(),
+    };
+
+    let mut report = AuditReport {
+        state_root_hash,
+        accounts: accounts_config.accounts.len(),
+        validators: 0,
+        delegators: accounts_config.delegators.len(),
+        discrepancies: vec![],
+    };
+    for account in accounts_config.accounts.iter() {
+        report.discrepancies.extend(
+            audit_balance(&global_state, &account.public_key, account.balance)?
+                .map(|problem| Discrepancy::new(&account.public_key, problem)),
+        );
+        let validator = match account.validator.as_ref() {
+            Some(validator) => validator,
+            None => continue,
+        };
+        report.validators += 1;
+        let bid = match global_state.bid(&account.public_key)? {
+            Some(bid) => bid,
+            None => {
+                report
+                    .discrepancies
+                    .push(Discrepancy::new(&account.public_key, Problem::MissingBid));
+                continue;
+            }
+        };
+        if *bid.staked_amount() != validator.bonded_amount {
+            report.discrepancies.push(Discrepancy::new(
+                &account.public_key,
+                Problem::StakeMismatch {
+                    expected: validator.bonded_amount,
+                    actual: *bid.staked_amount(),
+                },
+            ));
+        }
+        if *bid.delegation_rate() != validator.delegation_rate {
+            report.discrepancies.push(Discrepancy::new(
+                &account.public_key,
+                Problem::DelegationRateMismatch {
+                    expected: validator.delegation_rate,
+                    actual: *bid.delegation_rate(),
+                },
+            ));
+        }
+    }
+
+    for delegator in accounts_config.delegators.iter() {
+        let public_key = &delegator.delegator_public_key;
+        report.discrepancies.extend(
+            audit_balance(&global_state, public_key, delegator.balance)?
+                .map(|problem| Discrepancy::new(public_key, problem)),
+        );
+        let validator_public_key = delegator.validator_public_key.clone();
+        let maybe_staked_amount = global_state.bid(&validator_public_key)?.and_then(|bid| {
+            bid.delegators()
+                .get(public_key)
+                .map(|delegation| *delegation.staked_amount())
+        });
+        match maybe_staked_amount {
+            Some(actual) if actual == delegator.delegated_amount => {}
+            Some(actual) => report.discrepancies.push(Discrepancy::new(
+                public_key,
+                Problem::DelegationMismatch {
+                    validator_public_key,
+                    expected: delegator.delegated_amount,
+                    actual,
+                },
+            )),
+            None => report.discrepancies.push(Discrepancy::new(
+                public_key,
+                Problem::MissingDelegation {
+                    validator_public_key,
+                },
+            )),
+        }
+    }
+
+    for discrepancy in report.discrepancies.iter() {
+        warn!("Discrepancy: {:?}", discrepancy);
+    }
+    info!(
+        "Audited {} accounts, {} validators and {} delegators under state root {}, found {} \
+        discrepancies.",
+        report.accounts,
+        report.validators,
+        report.delegators,
+        state_root_hash,
+        report.discrepancies.len()
+    );
+    Ok(report)
+}
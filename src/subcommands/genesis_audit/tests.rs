@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId, storage::global_state::StateProvider,
+};
+use casper_hashing::Digest;
+use casper_types::{
+    account::Account,
+    system::auction::{Bid, Delegator},
+    AccessRights, AsymmetricType, CLValue, Key, PublicKey, StoredValue, URef, U512,
+};
+use tempfile::TempDir;
+
+use super::{
+    accounts::AccountsConfig,
+    audit::{audit_genesis, Discrepancy, Problem},
+    Error,
+};
+use crate::{
+    subcommands::trie_compact::{create_execution_engine, DEFAULT_MAX_DB_SIZE},
+    test_utils::KEYS,
+};
+
+fn put_account(
+    stored_values: &mut HashMap<Key, StoredValue>,
+    public_key: &PublicKey,
+    purse_idx: u8,
+    balance: u64,
+) {
+    let main_purse = URef::new([purse_idx; 32], AccessRights::READ_ADD_WRITE);
+    let account_hash = public_key.to_account_hash();
+    stored_values.insert(
+        Key::Account(account_hash),
+        StoredValue::Account(Account::create(
+            account_hash,
+            Default::default(),
+            main_purse,
+        )),
+    );
+    stored_values.insert(
+        Key::Balance(main_purse.addr()),
+        StoredValue::CLValue(CLValue::from_t(U512::from(balance)).unwrap()),
+    );
+}
+
+/// Creates a trie store with a validator of `KEYS[0]` with a delegation from
+/// `KEYS[2]`, and an account of `KEYS[1]`.
+fn new_global_state() -> (TempDir, Digest) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), DEFAULT_MAX_DB_SIZE.parse().unwrap(), true)
+            .unwrap();
+
+    let mut stored_values = HashMap::new();
+    put_account(&mut stored_values, &KEYS[0], 0, 100);
+    put_account(&mut stored_values, &KEYS[1], 1, 200);
+    put_account(&mut stored_values, &KEYS[2], 2, 300);
+    let bonding_purse = URef::new([3; 32], AccessRights::READ_ADD_WRITE);
+    let mut bid = Bid::unlocked(KEYS[0].clone(), bonding_purse, U512::from(50), 10);
+    bid.delegators_mut().insert(
+        KEYS[2].clone(),
+        Delegator::unlocked(
+            KEYS[2].clone(),
+            U512::from(20),
+            bonding_purse,
+            KEYS[0].clone(),
+        ),
+    );
+    stored_values.insert(
+        Key::Bid(KEYS[0].to_account_hash()),
+        StoredValue::Bid(Box::new(bid)),
+    );
+
+    let global_state = engine_state.get_state();
+    let state_root_hash = global_state
+        .put_stored_values(
+            CorrelationId::new(),
+            global_state.empty_root(),
+            stored_values,
+        )
+        .unwrap();
+    engine_state.flush_environment().unwrap();
+    (tmp_dir, state_root_hash)
+}
+
+fn accounts_config(
+    validator_balance: u64,
+    bonded_amount: u64,
+    delegated_amount: u64,
+) -> AccountsConfig {
+    toml::from_str(&format!(
+        r#"
+        [[accounts]]
+        public_key = "{}"
+        balance = "{}"
+
+        [accounts.validator]
+        bonded_amount = "{}"
+        delegation_rate = 10
+
+        [[accounts]]
+        public_key = "{}"
+        balance = "200"
+
+        [[delegators]]
+        validator_public_key = "{}"
+        delegator_public_key = "{}"
+        balance = "300"
+        delegated_amount = "{}"
+        "#,
+        KEYS[0].to_hex(),
+        validator_balance,
+        bonded_amount,
+        KEYS[1].to_hex(),
+        KEYS[0].to_hex(),
+        KEYS[2].to_hex(),
+        delegated_amount,
+    ))
+    .unwrap()
+}
+
+#[test]
+fn audit_should_match_accounts_config() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    let report = audit_genesis(
+        tmp_dir.path(),
+        state_root_hash,
+        &accounts_config(100, 50, 20),
+    )
+    .unwrap();
+    assert_eq!(report.accounts, 2);
+    assert_eq!(report.validators, 1);
+    assert_eq!(report.delegators, 1);
+    assert!(report.discrepancies.is_empty());
+
+    assert!(matches!(
+        audit_genesis(
+            tmp_dir.path(),
+            Digest::hash([0u8]),
+            &accounts_config(100, 50, 20)
+        ),
+        Err(Error::MissingStateRoot(_))
+    ));
+}
+
+#[test]
+fn audit_should_report_discrepancies() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    let mut config = accounts_config(101, 60, 25);
+    // An account which isn't in global state, as a validator without a bid.
+    let mut missing_account = accounts_config(0, 0, 0).accounts.remove(0);
+    missing_account.public_key = KEYS[3].clone();
+    config.accounts.push(missing_account);
+
+    let report = audit_genesis(tmp_dir.path(), state_root_hash, &config).unwrap();
+    assert_eq!(
+        report.discrepancies,
+        vec![
+            Discrepancy {
+                public_key: KEYS[0].clone(),
+                problem: Problem::BalanceMismatch {
+                    expected: U512::from(101),
+                    actual: U512::from(100)
+                }
+            },
+            Discrepancy {
+                public_key: KEYS[0].clone(),
+                problem: Problem::StakeMismatch {
+                    expected: U512::from(60),
+                    actual: U512::from(50)
+                }
+            },
+            Discrepancy {
+                public_key: KEYS[3].clone(),
+                problem: Problem::MissingAccount
+            },
+            Discrepancy {
+                public_key: KEYS[3].clone(),
+                problem: Problem::MissingBid
+            },
+            Discrepancy {
+                public_key: KEYS[2].clone(),
+                problem: Problem::DelegationMismatch {
+                    validator_public_key: KEYS[0].clone(),
+                    expected: U512::from(25),
+                    actual: U512::from(20)
+                }
+            },
+        ]
+    );
+}
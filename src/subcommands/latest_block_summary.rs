@@ -1,4 +1,5 @@
-mod block_info;
+pub(crate) mod block_info;
+pub(crate) mod completeness;
 mod read_db;
 #[cfg(test)]
 mod tests;
@@ -6,39 +7,95 @@ mod tests;
 use std::{array::TryFromSliceError, io::Error as IoError, path::Path};
 
 use bincode::Error as BincodeError;
+use casper_node::types::{BlockHash, DeployHash};
 use clap::{Arg, ArgMatches, Command};
 use lmdb::Error as LmdbError;
 use serde_json::Error as SerializationError;
 use thiserror::Error as ThisError;
 
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    db::{self, EnvTuning},
+    merkle_body::Error as MerkleBodyError,
+    output::{self, Compression},
+    schema::{self, SchemaVersion},
+    stamp::Error as StampError,
+    state_store::Error as StateStoreError,
+    storage::{self, Backend, Error as StorageError, Storage},
+    throttle, value_guard,
+};
+
 pub const COMMAND_NAME: &str = "latest-block-summary";
+const COMPLETE_ONLY: &str = "complete-only";
 const DB_PATH: &str = "db-path";
 const OVERWRITE: &str = "overwrite";
 const OUTPUT: &str = "output";
+const REQUIRE_SIGNATURES: &str = "require-signatures";
 
 /// Errors encountered when operating on the storage database.
 #[derive(Debug, ThisError)]
 pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Parsing error on the body of a block.
+    #[error("Error parsing body of block {0}: {1}")]
+    BodyParsing(BlockHash, BincodeError),
     #[error("No blocks found in the block header database")]
     EmptyDatabase,
-    /// Parsing error on entry at index in the database.
-    #[error("Error parsing element {0}: {1}")]
-    Parsing(usize, BincodeError),
+    /// Parsing error on the execution results of a deploy.
+    #[error("Error parsing execution results for deploy {0}: {1}")]
+    ExecutionResultsParsing(DeployHash, BincodeError),
+    /// Parsing error on the header of a block.
+    #[error("Error parsing header of block {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    /// Error reading the merkle body of a block.
+    #[error("Error reading merkle body of block {0}: {1}")]
+    MerkleBody(BlockHash, MerkleBodyError),
+    /// The header of a block is missing from the block header database.
+    #[error("Header of block {0} is missing")]
+    MissingHeader(BlockHash),
+    #[error("No complete blocks found in the database")]
+    NoCompleteBlock,
     /// Database operation error.
     #[error("Error operating the database: {0}")]
-    Database(#[from] LmdbError),
+    Database(#[from] StorageError),
     #[error("Error serializing output: {0}")]
     Serialize(#[from] SerializationError),
     #[error("Error writing output: {0}")]
     Output(#[from] IoError),
     #[error("Invalid block hash {err:?} {val}")]
     InvalidBlockHash { err: TryFromSliceError, val: String },
+    /// Error decoding the state store.
+    #[error("Error reading the state store: {0}")]
+    StateStore(#[from] StateStoreError),
+    /// Error reading the stamp of the database directory.
+    #[error("Error reading the stamp: {0}")]
+    Stamp(#[from] StampError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
 }
 
 enum DisplayOrder {
     DbPath,
     Output,
     Overwrite,
+    Compress,
+    CompleteOnly,
+    RequireSignatures,
+    SchemaVersion,
+    Backend,
+    IoNice,
+    Throttle,
+    MaxValueSize,
+    EnvTuning,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -56,7 +113,10 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .long(DB_PATH)
                 .takes_value(true)
                 .value_name("DB_PATH")
-                .help("Path of the directory with the `storage.lmdb` file."),
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
         )
         .arg(
             Arg::new(OUTPUT)
@@ -64,10 +124,13 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .short('o')
                 .long(OUTPUT)
                 .takes_value(true)
-                .value_name("FILE_PATH")
+                .value_name("FILE_PATH|URL")
                 .help(
-                    "Path to where the program will output the metadata. \
-                    If unspecified, defaults to standard output.",
+                    "Path to where the program will output the metadata, \
+                    optionally prefixed with `file://`, `-` for standard \
+                    output, or an `http://` or `https://` URL to post it to, \
+                    retrying on failures. If unspecified, defaults to \
+                    standard output.",
                 ),
         )
         .arg(
@@ -83,11 +146,71 @@ pub fn command(display_order: usize) -> Command<'static> {
                     directory.",
                 ),
         )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(COMPLETE_ONLY)
+                .display_order(DisplayOrder::CompleteOnly as usize)
+                .required(false)
+                .short('c')
+                .long(COMPLETE_ONLY)
+                .takes_value(false)
+                .help(
+                    "Output the highest block which has its body, deploys and \
+                    execution results in the database rather than the \
+                    highest block header.",
+                ),
+        )
+        .arg(
+            Arg::new(REQUIRE_SIGNATURES)
+                .display_order(DisplayOrder::RequireSignatures as usize)
+                .required(false)
+                .short('s')
+                .long(REQUIRE_SIGNATURES)
+                .takes_value(false)
+                .requires(COMPLETE_ONLY)
+                .help("Also require finality signatures for a block to be considered complete."),
+        )
+        .arg(schema::schema_version_arg(
+            DisplayOrder::SchemaVersion as usize,
+        ))
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+        .arg(throttle::io_nice_arg(DisplayOrder::IoNice as usize))
+        .arg(throttle::throttle_arg(DisplayOrder::Throttle as usize))
+        .arg(value_guard::max_value_arg(
+            DisplayOrder::MaxValueSize as usize,
+        ))
+        .args(db::tuning_args(
+            DisplayOrder::EnvTuning as usize,
+            EnvTuning::SEQUENTIAL,
+        ))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
-    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    throttle::init_from_matches(matches);
+    value_guard::init_from_matches(matches);
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let path = db_dir.path();
     let output = matches.value_of(OUTPUT).map(Path::new);
     let overwrite = matches.is_present(OVERWRITE);
-    read_db::latest_block_summary(path, output, overwrite)
+    let complete_only = matches.is_present(COMPLETE_ONLY);
+    let require_signatures = matches.is_present(REQUIRE_SIGNATURES);
+    let compression = Compression::from_matches(matches);
+    let storage = Storage::open(
+        path,
+        Backend::from_matches(matches),
+        EnvTuning::from_matches(matches),
+    )?;
+    read_db::latest_block_summary(
+        path,
+        &storage,
+        output,
+        overwrite,
+        compression,
+        complete_only,
+        require_signatures,
+        SchemaVersion::from_matches(matches),
+    )
 }
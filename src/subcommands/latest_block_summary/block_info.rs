@@ -11,11 +11,16 @@ use casper_hashing::Digest;
 use casper_node::types::{BlockHash, BlockHeader};
 use casper_types::{EraId, ProtocolVersion, Timestamp};
 
+use crate::common::{schema::SchemaVersion, state_store::StateStoreSummary};
 #[cfg(test)]
 use crate::test_utils::MockBlockHeader;
 
+/// Summary of a block. Fields are output in declaration order, see
+/// [`crate::common::schema`].
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
 pub struct BlockInfo {
+    #[serde(skip_serializing_if = "SchemaVersion::is_v1", default)]
+    schema_version: SchemaVersion,
     network_name: Option<String>,
     block_hash: BlockHash,
     body_hash: Digest,
@@ -24,6 +29,8 @@ pub struct BlockInfo {
     protocol_version: ProtocolVersion,
     state_root_hash: Digest,
     timestamp: Timestamp,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    state_store: Option<StateStoreSummary>,
 }
 
 impl BlockInfo {
@@ -33,6 +40,7 @@ impl BlockInfo {
         block_header: BlockHeader,
     ) -> Self {
         Self {
+            schema_version: SchemaVersion::CURRENT,
             block_hash,
             network_name,
             body_hash: *block_header.body_hash(),
@@ -41,9 +49,27 @@ impl BlockInfo {
             protocol_version: block_header.protocol_version(),
             state_root_hash: *block_header.state_root_hash(),
             timestamp: block_header.timestamp(),
+            state_store: None,
         }
     }
 
+    /// Attaches the decoded contents of the state store, which determine
+    /// where a restarting node picks up from.
+    pub fn with_state_store(mut self, state_store: Option<StateStoreSummary>) -> Self {
+        self.state_store = state_store;
+        self
+    }
+
+    /// Sets the layout of the output. The state store isn't part of the
+    /// first layout, so it's dropped for [`SchemaVersion::V1`].
+    pub fn with_schema_version(mut self, schema_version: SchemaVersion) -> Self {
+        if schema_version.is_v1() {
+            self.state_store = None;
+        }
+        self.schema_version = schema_version;
+        self
+    }
+
     #[cfg(test)]
     pub fn into_mock(self) -> (MockBlockHeader, Option<String>) {
         (
@@ -0,0 +1,128 @@
+use casper_node::types::{BlockHash, BlockHeader, DeployHash, DeployMetadata};
+
+use crate::{
+    common::{
+        block_iter::{self, Error as BlockIterError},
+        db::{BlockMetadataDatabase, Database, DeployDatabase, DeployMetadataDatabase},
+        storage::StorageReader,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+/// Reads the body of a block from either the legacy or the merkle body
+/// databases. Returns `None` if any part of the body is missing.
+pub(crate) fn read_body<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+) -> Result<Option<BlockBody>, Error> {
+    block_iter::read_body(reader, block_hash, header).map_err(|block_err| match block_err {
+        BlockIterError::Database(storage_err) => Error::Database(storage_err),
+        BlockIterError::BodyParsing(block_hash, bincode_err) => {
+            Error::BodyParsing(block_hash, bincode_err)
+        }
+        BlockIterError::MerkleBody(block_hash, merkle_body_err) => {
+            Error::MerkleBody(block_hash, merkle_body_err)
+        }
+        BlockIterError::HeaderParsing(block_hash, bincode_err) => {
+            Error::HeaderParsing(block_hash, bincode_err)
+        }
+        BlockIterError::MissingHeader(block_hash) => Error::MissingHeader(block_hash),
+        BlockIterError::InvalidKey(_) => unreachable!("reading a body doesn't scan headers"),
+    })
+}
+
+/// Reads the deploy and transfer hashes of a block from either the legacy or
+/// the merkle body databases. Returns `None` if any part of the body is
+/// missing.
+pub(crate) fn read_body_deploys<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+) -> Result<Option<Vec<DeployHash>>, Error> {
+    Ok(read_body(reader, block_hash, header)?.map(|body| {
+        body.deploy_hashes
+            .into_iter()
+            .chain(body.transfer_hashes)
+            .collect()
+    }))
+}
+
+/// The parts of a block which are present in the database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct BlockCompleteness {
+    /// The whole body of the block is present.
+    pub(crate) has_body: bool,
+    /// The body and all the deploys it references are present.
+    pub(crate) has_deploys: bool,
+    /// The body and the execution results of all its deploys for this block
+    /// are present.
+    pub(crate) has_execution_results: bool,
+    /// The finality signatures of the block are present.
+    pub(crate) has_signatures: bool,
+}
+
+/// Checks which parts of the block with the given hash and header are
+/// present in the database.
+pub(crate) fn block_completeness<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+) -> Result<BlockCompleteness, Error> {
+    let mut completeness = BlockCompleteness {
+        has_signatures: reader
+            .get(BlockMetadataDatabase::db_name(), block_hash.as_ref())?
+            .is_some(),
+        ..Default::default()
+    };
+    let deploy_hashes = match read_body_deploys(reader, block_hash, header)? {
+        Some(deploy_hashes) => deploy_hashes,
+        None => return Ok(completeness),
+    };
+    completeness.has_body = true;
+    completeness.has_deploys = true;
+    completeness.has_execution_results = true;
+    for deploy_hash in deploy_hashes {
+        if reader
+            .get(DeployDatabase::db_name(), deploy_hash.as_ref())?
+            .is_none()
+        {
+            completeness.has_deploys = false;
+        }
+        let has_execution_results =
+            match reader.get(DeployMetadataDatabase::db_name(), deploy_hash.as_ref())? {
+                Some(raw_metadata) => {
+                    let metadata: DeployMetadata =
+                        bincode::deserialize(&raw_metadata).map_err(|bincode_err| {
+                            Error::ExecutionResultsParsing(deploy_hash, bincode_err)
+                        })?;
+                    metadata.execution_results.contains_key(block_hash)
+                }
+                None => false,
+            };
+        if !has_execution_results {
+            completeness.has_execution_results = false;
+        }
+        if !completeness.has_deploys && !completeness.has_execution_results {
+            break;
+        }
+    }
+    Ok(completeness)
+}
+
+/// Returns whether the block with the given hash and header has its body,
+/// all of its deploys, all of their execution results and, if
+/// `require_signatures` is set, its finality signatures in the database.
+pub(super) fn is_block_complete<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+    require_signatures: bool,
+) -> Result<bool, Error> {
+    let completeness = block_completeness(reader, block_hash, header)?;
+    Ok(completeness.has_deploys
+        && completeness.has_execution_results
+        && (completeness.has_signatures || !require_signatures))
+}
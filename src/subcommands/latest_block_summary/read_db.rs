@@ -1,96 +1,142 @@
-use std::{
-    fs::OpenOptions,
-    io::{self, Write},
-    path::Path,
-    result::Result,
-};
+use std::{io::Write, path::Path, result::Result};
 
 use casper_hashing::Digest;
-use lmdb::{Cursor, Environment, Transaction};
 use log::{info, warn};
 use serde_json::{self, Error as SerializationError};
 
 use casper_node::types::{BlockHash, BlockHeader};
 
 use crate::common::{
-    db::{self, BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
-    lmdb_utils,
+    db::{BlockHeaderDatabase, Database},
+    output::{Compression, OutputWriter},
     progress::ProgressTracker,
+    schema::SchemaVersion,
+    stamp, state_store,
+    storage::{Storage, StorageReader},
+    value_guard::SkipCounter,
 };
 
-use super::{
-    block_info::{parse_network_name, BlockInfo},
-    Error,
-};
+use super::{block_info::BlockInfo, completeness::is_block_complete, Error};
 
-fn get_highest_block(
-    env: &Environment,
+fn progress_tracker<R: StorageReader>(reader: &R, log_progress: bool) -> Option<ProgressTracker> {
+    if !log_progress {
+        return None;
+    }
+    match reader.entry_count(BlockHeaderDatabase::db_name()).ok() {
+        Some(entry_count) => {
+            match ProgressTracker::new(
+                entry_count,
+                Box::new(|completion| info!("Database parsing {}% complete...", completion)),
+            ) {
+                Ok(progress_tracker) => Some(progress_tracker),
+                Err(progress_tracker_error) => {
+                    warn!(
+                        "Couldn't initialize progress tracker: {}",
+                        progress_tracker_error
+                    );
+                    None
+                }
+            }
+        }
+        None => {
+            warn!("Unable to count db entries, progress will not be logged.");
+            None
+        }
+    }
+}
+
+fn parse_block_hash(raw_key: &[u8]) -> Result<BlockHash, Error> {
+    Ok(Digest::try_from(raw_key)
+        .map_err(|err| Error::InvalidBlockHash {
+            err,
+            val: String::from_utf8_lossy(raw_key).to_string(),
+        })?
+        .into())
+}
+
+fn get_highest_block<R: StorageReader>(
+    reader: &R,
     log_progress: bool,
 ) -> Result<(BlockHash, BlockHeader), Error> {
-    let txn = env.begin_ro_txn()?;
-    let db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name()))? };
-
-    let mut max_height = 0u64;
-    let mut max_height_key = None;
-
-    let maybe_entry_count = lmdb_utils::entry_count(&txn, db).ok();
-    let mut maybe_progress_tracker = None;
-
-    if let Ok(mut cursor) = txn.open_ro_cursor(db) {
-        if log_progress {
-            match maybe_entry_count {
-                Some(entry_count) => {
-                    match ProgressTracker::new(
-                        entry_count,
-                        Box::new(|completion| {
-                            info!("Database parsing {}% complete...", completion)
-                        }),
-                    ) {
-                        Ok(progress_tracker) => maybe_progress_tracker = Some(progress_tracker),
-                        Err(progress_tracker_error) => warn!(
-                            "Couldn't initialize progress tracker: {}",
-                            progress_tracker_error
-                        ),
-                    }
+    let mut highest_block: Option<(BlockHash, BlockHeader)> = None;
+    let mut maybe_progress_tracker = progress_tracker(reader, log_progress);
+    let mut skip_counter = SkipCounter::default();
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            if skip_counter.is_parsable(BlockHeaderDatabase::db_name(), raw_key, raw_val) {
+                let block_hash = parse_block_hash(raw_key)?;
+                let header: BlockHeader = bincode::deserialize(raw_val)
+                    .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+                let is_highest = match highest_block.as_ref() {
+                    Some((_, highest_header)) => header.height() >= highest_header.height(),
+                    None => true,
+                };
+                if is_highest {
+                    highest_block = Some((block_hash, header));
                 }
-                None => warn!("Unable to count db entries, progress will not be logged."),
             }
-        }
 
-        for (idx, (raw_key, raw_val)) in cursor.iter().enumerate() {
-            let header: BlockHeader = bincode::deserialize(raw_val)
-                .map_err(|bincode_err| Error::Parsing(idx, bincode_err))?;
-            if header.height() >= max_height {
-                max_height = header.height();
-                let _ = max_height_key.replace(raw_key);
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+            Ok(())
+        },
+    )?;
+    skip_counter.log(BlockHeaderDatabase::db_name());
+
+    highest_block.ok_or(Error::EmptyDatabase)
+}
+
+fn get_highest_complete_block<R: StorageReader>(
+    reader: &R,
+    log_progress: bool,
+    require_signatures: bool,
+) -> Result<(BlockHash, BlockHeader), Error> {
+    // Only keep the heights and hashes around, the headers are read again
+    // when checking each block for completeness.
+    let mut blocks: Vec<(u64, BlockHash)> = vec![];
+    let mut maybe_progress_tracker = progress_tracker(reader, log_progress);
+    let mut skip_counter = SkipCounter::default();
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            if skip_counter.is_parsable(BlockHeaderDatabase::db_name(), raw_key, raw_val) {
+                let block_hash = parse_block_hash(raw_key)?;
+                let header: BlockHeader = bincode::deserialize(raw_val)
+                    .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+                blocks.push((header.height(), block_hash));
             }
 
             if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
                 progress_tracker.advance_by(1);
             }
-        }
+            Ok(())
+        },
+    )?;
+    skip_counter.log(BlockHeaderDatabase::db_name());
+    if blocks.is_empty() {
+        return Err(Error::EmptyDatabase);
     }
 
-    let max_height_key = max_height_key.ok_or(Error::EmptyDatabase)?;
-    let raw_bytes = txn.get(db, &max_height_key)?;
-    let highest_block_header: BlockHeader =
-        bincode::deserialize(raw_bytes).map_err(|bincode_err| {
-            Error::Parsing(
-                max_height
-                    .try_into()
-                    .expect("block height doesn't fit in usize"),
-                bincode_err,
-            )
-        })?;
-
-    let block_hash = Digest::try_from(max_height_key)
-        .map_err(|err| Error::InvalidBlockHash {
-            err,
-            val: String::from_utf8_lossy(max_height_key).to_string(),
-        })?
-        .into();
-
-    Ok((block_hash, highest_block_header))
+    // Walk the blocks from the highest down until we find one with all its
+    // data present.
+    blocks
+        .sort_unstable_by(|(first_height, _), (second_height, _)| second_height.cmp(first_height));
+    for (idx, (height, block_hash)) in blocks.into_iter().enumerate() {
+        let raw_header = reader
+            .get(BlockHeaderDatabase::db_name(), block_hash.as_ref())?
+            .ok_or(Error::MissingHeader(block_hash))?;
+        let header: BlockHeader = bincode::deserialize(&raw_header)
+            .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+        if is_block_complete(reader, &block_hash, &header, require_signatures)? {
+            if idx > 0 {
+                info!("Skipped {} incomplete blocks above height {}.", idx, height);
+            }
+            return Ok((block_hash, header));
+        }
+    }
+    Err(Error::NoCompleteBlock)
 }
 
 pub(crate) fn dump_block_info<W: Write + ?Sized>(
@@ -100,37 +146,37 @@ pub(crate) fn dump_block_info<W: Write + ?Sized>(
     serde_json::to_writer_pretty(out_writer, block_header)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn latest_block_summary<P1: AsRef<Path>, P2: AsRef<Path>>(
     db_path: P1,
+    storage: &Storage,
     output: Option<P2>,
     overwrite: bool,
+    compression: Compression,
+    complete_only: bool,
+    require_signatures: bool,
+    schema_version: SchemaVersion,
 ) -> Result<(), Error> {
-    let storage_path = db_path.as_ref().join(STORAGE_FILE_NAME);
-    let env = db::db_env(storage_path)?;
-    let mut log_progress = false;
     // Validate the output file early so that, in case this fails
     // we don't unnecessarily read the whole database.
-    let out_writer: Box<dyn Write> = if let Some(out_path) = output {
-        let file = OpenOptions::new()
-            .create_new(!overwrite)
-            .write(true)
-            .open(out_path)?;
-        log_progress = true;
-        Box::new(file)
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    let log_progress = !out_writer.is_stdout();
+    // Archives unpacked into a temporary directory carry their chain name
+    // in a stamp.
+    let network_name = stamp::chain_name(db_path)?;
+
+    let reader = storage.view()?;
+    let (block_hash, highest_block) = if complete_only {
+        get_highest_complete_block(&reader, log_progress, require_signatures)?
     } else {
-        Box::new(io::stdout())
+        get_highest_block(&reader, log_progress)?
     };
-    let network_name = match parse_network_name(db_path) {
-        Ok(name) => Some(name),
-        Err(io_err) => {
-            warn!("Couldn't derive network name from path: {}", io_err);
-            None
-        }
-    };
-
-    let (block_hash, highest_block) = get_highest_block(&env, log_progress)?;
-    let block_info = BlockInfo::new(network_name, block_hash, highest_block);
-    dump_block_info(&block_info, out_writer)?;
+    let state_store = state_store::read_state_store(&reader)?;
+    let block_info = BlockInfo::new(network_name, block_hash, highest_block)
+        .with_state_store(state_store)
+        .with_schema_version(schema_version);
+    dump_block_info(&block_info, Box::new(&mut out_writer))?;
+    out_writer.finish()?;
 
     Ok(())
 }
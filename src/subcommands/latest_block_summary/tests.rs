@@ -6,18 +6,41 @@ use tempfile::{self, NamedTempFile, TempDir};
 
 use casper_node::{
     rpcs::docs::DocExample,
-    types::{BlockHeader, JsonBlockHeader},
+    types::{BlockHash, BlockHeader, JsonBlockHeader},
 };
 
 use super::block_info::BlockInfo;
 use crate::{
-    common::db::STORAGE_FILE_NAME,
-    subcommands::latest_block_summary::{block_info, read_db},
-    test_utils::{LmdbTestFixture, MockBlockHeader},
+    common::{
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, BlockMetadataDatabase,
+            Database, DeployDatabase, DeployHashesDatabase, DeployMetadataDatabase, EnvTuning,
+            TransferHashesDatabase, STORAGE_FILE_NAME,
+        },
+        output::Compression,
+        schema::SchemaVersion,
+        storage::{Backend, Storage},
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        latest_block_summary::{block_info, read_db, Error},
+    },
+    test_utils::{
+        mock_block_header, mock_deploy_hash, mock_deploy_metadata, LmdbTestFixture, MockBlockHeader,
+    },
 };
 
 static OUT_DIR: Lazy<TempDir> = Lazy::new(|| tempfile::tempdir().unwrap());
 
+fn open_storage(fixture: &LmdbTestFixture) -> Storage {
+    Storage::open(
+        fixture.tmp_dir.as_ref(),
+        Backend::Lmdb,
+        EnvTuning::SEQUENTIAL,
+    )
+    .unwrap()
+}
+
 #[test]
 fn parse_network_name_input() {
     let root_dir = tempfile::tempdir().unwrap();
@@ -118,8 +141,13 @@ fn latest_block_should_succeed() {
     // Get the latest block information and ensure it matches with the second block.
     read_db::latest_block_summary(
         fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         false,
+        Compression::None,
+        false,
+        false,
+        SchemaVersion::CURRENT,
     )
     .unwrap();
     let json_str = fs::read_to_string(&out_file_path).unwrap();
@@ -137,15 +165,25 @@ fn latest_block_should_succeed() {
     // Given that the output exists, another run on the same destination path should fail.
     assert!(read_db::latest_block_summary(
         fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
-        false
+        false,
+        Compression::None,
+        false,
+        false,
+        SchemaVersion::CURRENT,
     )
     .is_err());
     // We use `overwrite` on the previous output file.
     read_db::latest_block_summary(
         fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
         true,
+        Compression::None,
+        false,
+        false,
+        SchemaVersion::CURRENT,
     )
     .unwrap();
     let json_str = fs::read_to_string(&out_file_path).unwrap();
@@ -160,12 +198,54 @@ fn latest_block_empty_db_should_fail() {
     let out_file_path = OUT_DIR.as_ref().join("empty.json");
     assert!(read_db::latest_block_summary(
         fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
-        false
+        false,
+        Compression::None,
+        false,
+        false,
+        SchemaVersion::CURRENT,
     )
     .is_err());
 }
 
+#[test]
+fn latest_block_corrupt_header_should_name_block() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let (block_hash, _) = mock_block_header(0);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &[1, 2, 3],
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    for complete_only in [false, true] {
+        let out_file_path = OUT_DIR
+            .as_ref()
+            .join(format!("corrupt_{}.json", complete_only));
+        assert!(matches!(
+            read_db::latest_block_summary(
+                fixture.tmp_dir.as_ref(),
+                &open_storage(&fixture),
+                Some(out_file_path.as_path()),
+                false,
+                Compression::None,
+                complete_only,
+                false,
+                SchemaVersion::CURRENT,
+            ),
+            Err(Error::HeaderParsing(hash, _)) if hash == block_hash
+        ));
+    }
+}
+
 #[test]
 fn latest_block_existing_output_should_fail() {
     let fixture = LmdbTestFixture::new(vec!["block_header_faulty"], Some(STORAGE_FILE_NAME));
@@ -177,8 +257,167 @@ fn latest_block_existing_output_should_fail() {
         .unwrap();
     assert!(read_db::latest_block_summary(
         fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
         Some(out_file_path.as_path()),
-        false
+        false,
+        Compression::None,
+        false,
+        false,
+        SchemaVersion::CURRENT,
     )
     .is_err());
 }
+
+#[test]
+fn latest_complete_block_should_skip_incomplete_blocks() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_file_path = OUT_DIR.as_ref().join("latest_complete_block.json");
+
+    // Create 3 blocks of heights 0, 1 and 2.
+    let blocks: Vec<(BlockHash, MockBlockHeader)> = (0..3u8)
+        .map(|idx| {
+            let (block_hash, mut block_header) = mock_block_header(idx);
+            block_header.height = idx as u64;
+            (block_hash, block_header)
+        })
+        .collect();
+    let deploy_hash = mock_deploy_hash(0);
+
+    let env = &fixture.env;
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        for (block_hash, block_header) in blocks.iter() {
+            txn.put(
+                *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+                block_hash,
+                &bincode::serialize(block_header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        // Block 0 has an empty body and signatures.
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &blocks[0].1.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+            &blocks[0].0,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        // Block 1 has a body with a deploy and its execution results, but no
+        // signatures.
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &blocks[1].1.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![deploy_hash])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            &deploy_hash,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            &deploy_hash,
+            &bincode::serialize(&mock_deploy_metadata(&[blocks[1].0])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        // Block 2 has no body.
+        txn.commit().unwrap();
+    };
+
+    // Without the flag, the highest header is reported.
+    read_db::latest_block_summary(
+        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        false,
+        false,
+        SchemaVersion::CURRENT,
+    )
+    .unwrap();
+    let json_str = fs::read_to_string(&out_file_path).unwrap();
+    let block_info: BlockInfo = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[2].1);
+
+    // Block 2 is missing its body, so block 1 should be reported.
+    read_db::latest_block_summary(
+        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        true,
+        false,
+        SchemaVersion::CURRENT,
+    )
+    .unwrap();
+    let json_str = fs::read_to_string(&out_file_path).unwrap();
+    let block_info: BlockInfo = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[1].1);
+
+    // Block 1 is missing its signatures, so block 0 should be reported.
+    read_db::latest_block_summary(
+        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        true,
+        true,
+        SchemaVersion::CURRENT,
+    )
+    .unwrap();
+    let json_str = fs::read_to_string(&out_file_path).unwrap();
+    let block_info: BlockInfo = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[0].1);
+
+    // Without the execution results of its deploy, block 1 is incomplete.
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        txn.del(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            &deploy_hash,
+            None,
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    };
+    read_db::latest_block_summary(
+        fixture.tmp_dir.as_ref(),
+        &open_storage(&fixture),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        true,
+        false,
+        SchemaVersion::CURRENT,
+    )
+    .unwrap();
+    let json_str = fs::read_to_string(&out_file_path).unwrap();
+    let block_info: BlockInfo = serde_json::from_str(&json_str).unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[0].1);
+}
@@ -0,0 +1,156 @@
+mod seed;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use super::{
+    archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+    extract_slice::Error as ExtractSliceError,
+};
+use crate::common::{
+    db::TRIE_STORE_FILE_NAME,
+    disk_space::{self, Error as DiskSpaceError},
+    stamp::Error as StampError,
+};
+
+pub const COMMAND_NAME: &str = "make-sync-seed";
+const DB_PATH: &str = "db-path";
+const FORCE: &str = "force";
+const OUTPUT: &str = "output";
+const TRUSTED_HEIGHT: &str = "trusted-height";
+
+/// Errors encountered when running the `make-sync-seed` subcommand.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the source database.
+    #[error("Error opening the source database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error copying the blocks or the global state to the seed.
+    #[error("Error copying to the seed: {0}")]
+    Copy(#[from] ExtractSliceError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Not enough space for the seed.
+    #[error("Disk space check failed: {0}")]
+    DiskSpace(#[from] DiskSpaceError),
+    /// The source database has no blocks.
+    #[error("No blocks found in the block header database")]
+    EmptyDatabase,
+    /// Parsing error on entry at index in the block header database.
+    #[error("Error parsing block header at index {0}: {1}")]
+    HeaderParsing(usize, BincodeError),
+    /// Key of an entry in the block header database is not a valid hash.
+    #[error("Invalid key at index {0} in block header DB")]
+    InvalidKey(usize),
+    /// Error stamping the seed.
+    #[error("Error stamping the seed: {0}")]
+    Stamp(#[from] StampError),
+    /// Error reading the size of the source trie store.
+    #[error("Error reading the size of the source trie store: {0}")]
+    TrieStoreSize(IoError),
+    /// The trusted height is above the latest block.
+    #[error("Trusted height {0} is above the latest block, at height {1}")]
+    TrustedHeightTooHigh(u64, u64),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    TrustedHeight,
+    Force,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Creates a minimal database for a new node to fast-sync from, with \
+            the latest block, the switch blocks back to a trusted height and \
+            their signatures, and the global state of the latest block.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` and \
+                    `data.lmdb` files, or of a `.tar.zst` archive of them.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .required(true)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("OUTPUT_DB_PATH")
+                .help(
+                    "Path of the directory where the seed `storage.lmdb` and \
+                    `data.lmdb` files are created. The directory must not exist \
+                    when running this command.",
+                ),
+        )
+        .arg(
+            Arg::new(TRUSTED_HEIGHT)
+                .display_order(DisplayOrder::TrustedHeight as usize)
+                .required(true)
+                .short('t')
+                .long(TRUSTED_HEIGHT)
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help(
+                    "Height of the block the new node trusts. The seed holds \
+                    the switch blocks from the last one at or below this \
+                    height, whose validator weights prove the finality of the \
+                    eras after it.",
+                ),
+        )
+        .arg(
+            Arg::new(FORCE)
+                .display_order(DisplayOrder::Force as usize)
+                .long(FORCE)
+                .takes_value(false)
+                .help(
+                    "Proceed even if the output directory doesn't seem to have \
+                    enough free space for a copy of the source `data.lmdb` file.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage, Include::Trie],
+    )?;
+    let path = db_dir.path();
+    let output = Path::new(matches.value_of(OUTPUT).expect("should have output arg"));
+    let trusted_height: u64 = matches
+        .value_of(TRUSTED_HEIGHT)
+        .expect("should have trusted-height arg")
+        .parse()
+        .expect("should be validated");
+
+    // The global state under a single root can be as large as the whole
+    // source trie store.
+    let required_space = disk_space::allocated_size(path.join(TRIE_STORE_FILE_NAME))
+        .map_err(Error::TrieStoreSize)?;
+    disk_space::check_available_space(output, required_space, matches.is_present(FORCE))?;
+
+    seed::make_sync_seed(path, output, trusted_height).map(|_seed_blocks| ())
+}
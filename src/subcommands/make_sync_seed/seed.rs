@@ -0,0 +1,159 @@
+use std::{collections::BTreeMap, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader};
+use lmdb::{Cursor, Error as LmdbError, Transaction};
+use log::{info, warn};
+
+use crate::{
+    common::{
+        db::{
+            self, BlockHeaderDatabase, BlockMetadataDatabase, Database, StorageEnv,
+            STORAGE_FILE_NAME,
+        },
+        stamp::{self, Stamp},
+    },
+    subcommands::extract_slice::{db_helpers, global_state, storage, Error as ExtractSliceError},
+};
+
+use super::Error;
+
+/// Blocks copied into a sync seed.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct SeedBlocks {
+    /// Hash and header of the highest block.
+    pub(crate) latest: (BlockHash, BlockHeader),
+    /// Heights and hashes of the switch blocks below the latest block, from
+    /// the last one at or below the trusted height.
+    pub(crate) switch_blocks: Vec<(u64, BlockHash)>,
+}
+
+/// Finds the latest block of the storage database in `db_path` and the
+/// switch blocks a node trusting the block at `trusted_height` needs to
+/// follow the validator changes up to it.
+pub(crate) fn select_blocks<P: AsRef<Path>>(
+    db_path: P,
+    trusted_height: u64,
+) -> Result<SeedBlocks, Error> {
+    let env = StorageEnv::open(db_path)?;
+    let header_db = env.db::<BlockHeaderDatabase>()?;
+    let txn = env.begin_ro_txn()?;
+
+    let mut maybe_latest: Option<(BlockHash, BlockHeader)> = None;
+    let mut switch_blocks: BTreeMap<u64, BlockHash> = BTreeMap::new();
+    for (idx, (raw_key, raw_val)) in txn.open_ro_cursor(header_db)?.iter().enumerate() {
+        let header: BlockHeader = bincode::deserialize(raw_val)
+            .map_err(|bincode_err| Error::HeaderParsing(idx, bincode_err))?;
+        let block_hash: BlockHash = Digest::try_from(raw_key)
+            .map_err(|_| Error::InvalidKey(idx))?
+            .into();
+        if header.is_switch_block() {
+            switch_blocks.insert(header.height(), block_hash);
+        }
+        match &maybe_latest {
+            Some((_, latest_header)) if latest_header.height() >= header.height() => {}
+            _ => maybe_latest = Some((block_hash, header)),
+        }
+    }
+    let latest = maybe_latest.ok_or(Error::EmptyDatabase)?;
+    let latest_height = latest.1.height();
+    if trusted_height > latest_height {
+        return Err(Error::TrustedHeightTooHigh(trusted_height, latest_height));
+    }
+    // Blocks of the genesis era are only preceded by the chainspec.
+    let first_height = switch_blocks
+        .range(..=trusted_height)
+        .next_back()
+        .map(|(height, _)| *height)
+        .unwrap_or_default();
+    let switch_blocks = switch_blocks
+        .range(first_height..latest_height)
+        .map(|(height, block_hash)| (*height, *block_hash))
+        .collect();
+    Ok(SeedBlocks {
+        latest,
+        switch_blocks,
+    })
+}
+
+/// Copies the headers of the switch blocks `switch_blocks`, along with their
+/// signatures, from the storage database in `source` to the one in
+/// `destination`.
+fn transfer_switch_blocks<P1: AsRef<Path>, P2: AsRef<Path>>(
+    source: P1,
+    destination: P2,
+    switch_blocks: &[(u64, BlockHash)],
+) -> Result<(), ExtractSliceError> {
+    let source_env = db::db_env(source.as_ref().join(STORAGE_FILE_NAME))?;
+    let destination_env = db::db_env(destination.as_ref().join(STORAGE_FILE_NAME))?;
+    let mut source_txn = source_env.begin_ro_txn()?;
+    let mut destination_txn = destination_env.begin_rw_txn()?;
+    for (height, block_hash) in switch_blocks {
+        db_helpers::transfer_to_new_db(
+            &mut source_txn,
+            &mut destination_txn,
+            BlockHeaderDatabase::db_name(),
+            block_hash,
+        )?;
+        match db_helpers::transfer_to_new_db(
+            &mut source_txn,
+            &mut destination_txn,
+            BlockMetadataDatabase::db_name(),
+            block_hash,
+        ) {
+            Ok(_) => (),
+            Err(LmdbError::NotFound) => {
+                warn!("No signatures found for switch block {block_hash} at height {height}")
+            }
+            Err(lmdb_error) => return Err(ExtractSliceError::Database(lmdb_error)),
+        }
+    }
+    source_txn.commit()?;
+    destination_txn.commit()?;
+    info!("Transferred {} switch blocks", switch_blocks.len());
+    Ok(())
+}
+
+/// Creates in `output` a storage database with the latest block of the one
+/// in `db_path`, its signatures and the switch blocks back to
+/// `trusted_height` with their signatures, and a trie store with the global
+/// state of the latest block.
+pub(crate) fn make_sync_seed<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: P2,
+    trusted_height: u64,
+) -> Result<SeedBlocks, Error> {
+    let seed_blocks = select_blocks(&db_path, trusted_height)?;
+    let (latest_hash, latest_header) = &seed_blocks.latest;
+    info!(
+        "Seeding from block {} at height {} with {} switch blocks from height {}.",
+        latest_hash,
+        latest_header.height(),
+        seed_blocks.switch_blocks.len(),
+        seed_blocks
+            .switch_blocks
+            .first()
+            .map(|(height, _)| *height)
+            .unwrap_or_else(|| latest_header.height())
+    );
+
+    storage::create_output_db(&output)?;
+    storage::transfer_block_info(&db_path, &output, *latest_hash, false, false)?;
+    transfer_switch_blocks(&db_path, &output, &seed_blocks.switch_blocks)?;
+    global_state::transfer_global_state(&db_path, &output, &[*latest_header.state_root_hash()])?;
+
+    Stamp {
+        chain_name: stamp::chain_name(&db_path)?,
+        protocol_version: Some(latest_header.protocol_version()),
+        lowest_height: Some(
+            seed_blocks
+                .switch_blocks
+                .first()
+                .map(|(height, _)| *height)
+                .unwrap_or_else(|| latest_header.height()),
+        ),
+        highest_height: Some(latest_header.height()),
+    }
+    .write(&output)?;
+    Ok(seed_blocks)
+}
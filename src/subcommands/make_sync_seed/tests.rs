@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use casper_execution_engine::storage::{
+    store::StoreExt,
+    transaction_source::{lmdb::LmdbEnvironment, TransactionSource},
+    trie::Trie,
+    trie_store::lmdb::LmdbTrieStore,
+};
+use casper_node::types::BlockHash;
+use casper_types::{bytesrepr::Bytes, EraId};
+use lmdb::{DatabaseFlags, Error as LmdbError, Transaction, WriteFlags};
+use serde::Serialize;
+
+use super::{
+    seed::{self, SeedBlocks},
+    Error,
+};
+use crate::{
+    common::{
+        db::{
+            self, BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database,
+            DeployDatabase, DeployMetadataDatabase, TransferDatabase, STORAGE_FILE_NAME,
+        },
+        stamp::Stamp,
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        purge_signatures::block_signatures::BlockSignatures,
+        trie_compact::{load_execution_engine, tests::create_data, DEFAULT_MAX_DB_SIZE},
+    },
+    test_utils::{self, LmdbTestFixture, MockSwitchBlockHeader},
+};
+
+fn put_header<H: Serialize>(fixture: &LmdbTestFixture, block_hash: &BlockHash, header: &H) {
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        block_hash,
+        &bincode::serialize(header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn select_blocks_should_walk_switch_blocks_back_to_trusted_height() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Eras of uneven lengths, written out of height order, with blocks in
+    // between the switch blocks.
+    let switch_heights = [17u64, 4, 30, 5];
+    let mut switch_hashes = BTreeMap::new();
+    for (idx, height) in switch_heights.into_iter().enumerate() {
+        let (block_hash, mut header) = test_utils::mock_switch_block_header(idx as u8);
+        header.era_id = EraId::new(idx as u64);
+        header.height = height;
+        put_header(&fixture, &block_hash, &header);
+        switch_hashes.insert(height, block_hash);
+    }
+    for (idx, height) in [(10u8, 33u64), (11, 12), (12, 25)] {
+        let (block_hash, mut header) = test_utils::mock_block_header(idx);
+        header.height = height;
+        put_header(&fixture, &block_hash, &header);
+    }
+    let switch_blocks_from = |trusted_height: u64| -> Vec<u64> {
+        seed::select_blocks(fixture.tmp_dir.path(), trusted_height)
+            .unwrap()
+            .switch_blocks
+            .into_iter()
+            .map(|(height, block_hash)| {
+                assert_eq!(switch_hashes[&height], block_hash);
+                height
+            })
+            .collect()
+    };
+
+    // The walk starts at the last switch block at or below the trusted
+    // height, which holds the validators of the era of the trusted block.
+    assert_eq!(switch_blocks_from(16), vec![5, 17, 30]);
+    assert_eq!(switch_blocks_from(17), vec![17, 30]);
+    assert_eq!(switch_blocks_from(5), vec![5, 17, 30]);
+    assert_eq!(switch_blocks_from(0), vec![4, 5, 17, 30]);
+    // Trusting the latest block only needs the switch block of its era.
+    assert_eq!(switch_blocks_from(33), vec![30]);
+
+    // The latest block being a switch block, it isn't part of the walk.
+    let (block_hash, mut header) = test_utils::mock_switch_block_header(4);
+    header.era_id = EraId::new(4);
+    header.height = 40;
+    put_header(&fixture, &block_hash, &header);
+    let seed_blocks = seed::select_blocks(fixture.tmp_dir.path(), 40).unwrap();
+    assert_eq!(seed_blocks.latest.0, block_hash);
+    assert!(seed_blocks.switch_blocks.is_empty());
+    assert_eq!(switch_blocks_from(35), vec![30]);
+}
+
+#[test]
+fn make_sync_seed_should_copy_switch_blocks_from_trusted_height() {
+    let source_fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            TransferDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    // Switch blocks at the end of eras 0 to 3, every 10 blocks.
+    let switch_blocks: Vec<(BlockHash, MockSwitchBlockHeader)> = (0..4u8)
+        .map(|idx| {
+            let (block_hash, mut header) = test_utils::mock_switch_block_header(idx);
+            header.era_id = EraId::new(idx.into());
+            header.height = 10 * u64::from(idx) + 9;
+            (block_hash, header)
+        })
+        .collect();
+    for (block_hash, header) in &switch_blocks {
+        put_header(&source_fixture, block_hash, header);
+    }
+    // Only the switch block of era 2 has signatures.
+    {
+        let block_signatures = BlockSignatures {
+            block_hash: switch_blocks[2].0,
+            era_id: EraId::new(2),
+            proofs: BTreeMap::new(),
+        };
+        let mut txn = source_fixture.env.begin_rw_txn().unwrap();
+        txn.put(
+            *source_fixture
+                .db(Some(BlockMetadataDatabase::db_name()))
+                .unwrap(),
+            &block_signatures.block_hash,
+            &bincode::serialize(&block_signatures).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    }
+
+    let data = create_data();
+    let (latest_hash, mut latest_header) = test_utils::mock_block_header(4);
+    latest_header.era_id = EraId::new(4);
+    latest_header.height = 45;
+    latest_header.state_root_hash = data[4].0;
+    put_header(&source_fixture, &latest_hash, &latest_header);
+    {
+        let mut txn = source_fixture.env.begin_rw_txn().unwrap();
+        txn.put(
+            *source_fixture
+                .db(Some(BlockBodyDatabase::db_name()))
+                .unwrap(),
+            &latest_header.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    }
+    let max_db_size = DEFAULT_MAX_DB_SIZE
+        .parse()
+        .expect("should be able to parse max db size");
+    {
+        let trie_env =
+            LmdbEnvironment::new(source_fixture.tmp_dir.path(), max_db_size, 512, true).unwrap();
+        let trie_store = LmdbTrieStore::new(&trie_env, None, DatabaseFlags::empty()).unwrap();
+        let mut txn = trie_env.create_read_write_txn().unwrap();
+        trie_store
+            .put_many(&mut txn, data.iter().map(Into::into))
+            .unwrap();
+        txn.commit().unwrap();
+    }
+
+    let expected_latest = (
+        latest_hash,
+        bincode::deserialize(&bincode::serialize(&latest_header).unwrap()).unwrap(),
+    );
+    // Trusting a block in era 2 requires the switch block of era 1 onwards.
+    assert_eq!(
+        seed::select_blocks(source_fixture.tmp_dir.path(), 25).unwrap(),
+        SeedBlocks {
+            latest: expected_latest,
+            switch_blocks: vec![
+                (19, switch_blocks[1].0),
+                (29, switch_blocks[2].0),
+                (39, switch_blocks[3].0),
+            ],
+        }
+    );
+    // Trusting a switch block requires no earlier one.
+    assert_eq!(
+        seed::select_blocks(source_fixture.tmp_dir.path(), 29)
+            .unwrap()
+            .switch_blocks,
+        vec![(29, switch_blocks[2].0), (39, switch_blocks[3].0)]
+    );
+    // Trusting a block of the genesis era requires all switch blocks.
+    assert_eq!(
+        seed::select_blocks(source_fixture.tmp_dir.path(), 3)
+            .unwrap()
+            .switch_blocks
+            .len(),
+        4
+    );
+    assert!(matches!(
+        seed::select_blocks(source_fixture.tmp_dir.path(), 46),
+        Err(Error::TrustedHeightTooHigh(46, 45))
+    ));
+
+    let output_dir = tempfile::tempdir().unwrap();
+    let output = output_dir.path().join("seed");
+    let seed_blocks = seed::make_sync_seed(source_fixture.tmp_dir.path(), &output, 32).unwrap();
+    assert_eq!(
+        seed_blocks.switch_blocks,
+        vec![(29, switch_blocks[2].0), (39, switch_blocks[3].0)]
+    );
+
+    let output_env = db::db_env(output.join(STORAGE_FILE_NAME)).unwrap();
+    let txn = output_env.begin_ro_txn().unwrap();
+    let header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name())).unwrap() };
+    let signatures_db = unsafe { txn.open_db(Some(BlockMetadataDatabase::db_name())).unwrap() };
+    for (block_hash, header) in switch_blocks.iter().skip(2) {
+        assert_eq!(
+            txn.get(header_db, block_hash).unwrap(),
+            bincode::serialize(header).unwrap()
+        );
+    }
+    for (block_hash, _) in switch_blocks.iter().take(2) {
+        assert_eq!(txn.get(header_db, block_hash), Err(LmdbError::NotFound));
+    }
+    assert!(txn.get(header_db, &latest_hash).is_ok());
+    assert!(txn.get(signatures_db, &switch_blocks[2].0).is_ok());
+    assert_eq!(
+        txn.get(signatures_db, &switch_blocks[3].0),
+        Err(LmdbError::NotFound)
+    );
+    txn.commit().unwrap();
+
+    let stamp = Stamp::read(&output).unwrap().unwrap();
+    assert_eq!(stamp.lowest_height, Some(29));
+    assert_eq!(stamp.highest_height, Some(45));
+
+    // The global state under the state root of the latest block is copied.
+    let (_output_state, output_env) =
+        load_execution_engine(&output, max_db_size, data[4].0, true).unwrap();
+    let output_store = LmdbTrieStore::new(&output_env, None, DatabaseFlags::empty()).unwrap();
+    let txn = output_env.create_read_write_txn().unwrap();
+    let keys = [data[1].0, data[2].0, data[4].0];
+    let entries: Vec<Option<Trie<Bytes, Bytes>>> =
+        output_store.get_many(&txn, keys.iter()).unwrap();
+    assert!(entries.iter().all(Option::is_some));
+    txn.commit().unwrap();
+}
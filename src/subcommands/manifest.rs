@@ -0,0 +1,62 @@
+use std::process;
+
+use clap::{ArgMatches, Command};
+use thiserror::Error as ThisError;
+
+pub use create::Error as CreateError;
+pub use verify::Error as VerifyError;
+
+use super::Error as SubcommandError;
+
+pub(crate) mod content;
+mod create;
+#[cfg(test)]
+mod tests;
+mod verify;
+
+pub const COMMAND_NAME: &str = "manifest";
+
+enum DisplayOrder {
+    Create,
+    Verify,
+}
+
+#[derive(ThisError, Debug)]
+pub enum Error {
+    #[error("create: {0}")]
+    Create(#[from] CreateError),
+    #[error("verify: {0}")]
+    Verify(#[from] VerifyError),
+}
+
+impl From<Error> for SubcommandError {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Create(create_err) => SubcommandError::ManifestCreate(create_err),
+            Error::Verify(verify_err) => SubcommandError::ManifestVerify(verify_err),
+        }
+    }
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Signed manifests attesting the contents of the databases, for \
+            publishers of archives to create and their consumers to verify.",
+        )
+        .subcommand(create::command(DisplayOrder::Create as usize))
+        .subcommand(verify::command(DisplayOrder::Verify as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let (subcommand_name, matches) = matches.subcommand().unwrap_or_else(|| {
+        process::exit(1);
+    });
+
+    match subcommand_name {
+        create::COMMAND_NAME => create::run(matches).map_err(Error::Create),
+        verify::COMMAND_NAME => verify::run(matches).map_err(Error::Verify),
+        _ => unreachable!("{} should be handled above", subcommand_name),
+    }
+}
@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::BlockHash;
+use casper_types::{
+    crypto::{self, Error as CryptoError},
+    PublicKey, SecretKey, Signature, Timestamp,
+};
+use lmdb::{Cursor, Database as LmdbDatabase, Error as LmdbError, RoTransaction, Transaction};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::{self, Error as BlockIterError},
+    db::{
+        BlockHeaderDatabase, Database, EnvTuning, StorageEnv, TrieEnv, STORAGE_FILE_NAME,
+        TRIE_STORE_FILE_NAME,
+    },
+    lmdb_utils,
+    stamp::{self, Error as StampError},
+    storage::{Error as StorageError, LmdbReader, StorageReader},
+};
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Error reading the highest block: {0}")]
+    Block(#[from] BlockIterError),
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    #[error("Invalid database name in {0}")]
+    InvalidDatabaseName(&'static str),
+    #[error("Error (de)serializing the manifest: {0}")]
+    Serialization(#[from] SerializationError),
+    #[error("Invalid manifest signature: {0}")]
+    Signature(CryptoError),
+    #[error("Error reading the stamp of the database directory: {0}")]
+    Stamp(#[from] StampError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Self::Database(lmdb_err.into())
+    }
+}
+
+/// Computes the root of a binary Merkle tree over a stream of leaves, keeping
+/// only the roots of the complete subtrees built so far. Pairs of subtrees
+/// of equal size are merged as soon as possible, and the remaining ones are
+/// merged from the right at the end, so the root only depends on the leaves
+/// and their order.
+#[derive(Default)]
+pub(crate) struct MerkleHasher {
+    /// Roots of complete subtrees along with their heights, the heights
+    /// strictly decreasing.
+    subtrees: Vec<(u32, Digest)>,
+}
+
+impl MerkleHasher {
+    pub(crate) fn push(&mut self, leaf: Digest) {
+        let mut node = (0, leaf);
+        while let Some((height, left)) = self.subtrees.last().copied() {
+            if height != node.0 {
+                break;
+            }
+            self.subtrees.pop();
+            node = (height + 1, Digest::hash_pair(left, node.1));
+        }
+        self.subtrees.push(node);
+    }
+
+    /// Returns the root of the tree, or `Digest::SENTINEL_NONE` if it has no
+    /// leaves.
+    pub(crate) fn finish(mut self) -> Digest {
+        let mut root = match self.subtrees.pop() {
+            Some((_height, root)) => root,
+            None => return Digest::SENTINEL_NONE,
+        };
+        while let Some((_height, left)) = self.subtrees.pop() {
+            root = Digest::hash_pair(left, root);
+        }
+        root
+    }
+}
+
+/// Entry count and content hash of a database.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DatabaseDigest {
+    pub entries: usize,
+    /// Root of the Merkle tree over the hashes of the entries sorted by key,
+    /// each entry hashed as the pair of the hashes of its key and value.
+    pub root_hash: Digest,
+}
+
+impl DatabaseDigest {
+    fn compute(txn: &RoTransaction, db: LmdbDatabase) -> Result<Self, Error> {
+        let mut hasher = MerkleHasher::default();
+        let mut cursor = txn.open_ro_cursor(db)?;
+        for (raw_key, raw_val) in cursor.iter() {
+            hasher.push(Digest::hash_pair(
+                Digest::hash(raw_key),
+                Digest::hash(raw_val),
+            ));
+        }
+        Ok(Self {
+            entries: lmdb_utils::entry_count(txn, db)?,
+            root_hash: hasher.finish(),
+        })
+    }
+}
+
+/// The highest block found in the storage database.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HighestBlock {
+    pub height: u64,
+    pub hash: BlockHash,
+}
+
+/// Describes the contents of the databases in a directory.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ContentManifest {
+    /// Name of the chain, taken from the stamp of the database directory or
+    /// derived from its name.
+    pub chain_name: Option<String>,
+    /// Highest block in the storage database, if any.
+    pub highest_block: Option<HighestBlock>,
+    /// Digests of the databases of `storage.lmdb`, by name. Empty if there
+    /// is no storage database.
+    pub storage: BTreeMap<String, DatabaseDigest>,
+    /// Digest of the trie store, `data.lmdb`, if any.
+    pub trie_store: Option<DatabaseDigest>,
+    /// Time at which the manifest was created.
+    pub created_at: Timestamp,
+}
+
+impl ContentManifest {
+    /// Builds the manifest of the databases in `db_dir`.
+    pub fn new<P: AsRef<Path>>(db_dir: P) -> Result<Self, Error> {
+        let db_dir = db_dir.as_ref();
+        let mut manifest = Self {
+            chain_name: stamp::chain_name(db_dir)?,
+            highest_block: None,
+            storage: BTreeMap::new(),
+            trie_store: None,
+            created_at: Timestamp::now(),
+        };
+
+        if db_dir.join(STORAGE_FILE_NAME).exists() {
+            let env = StorageEnv::open_with_tuning(db_dir, EnvTuning::SEQUENTIAL)?;
+            env.read(|txn| -> Result<(), Error> {
+                let reader = LmdbReader::new(txn);
+                if reader.has_table(BlockHeaderDatabase::db_name())? {
+                    manifest.highest_block = block_iter::blocks_by_height(&reader)?
+                        .pop()
+                        .map(|(height, hash)| HighestBlock { height, hash });
+                }
+                // The keys of the unnamed database are the names of the
+                // others.
+                let mut db_names = vec![];
+                let mut cursor = txn.open_ro_cursor(unsafe { txn.open_db(None)? })?;
+                for (raw_name, _) in cursor.iter() {
+                    let db_name = std::str::from_utf8(raw_name)
+                        .map_err(|_| Error::InvalidDatabaseName(STORAGE_FILE_NAME))?;
+                    db_names.push(db_name.to_string());
+                }
+                drop(cursor);
+                for db_name in db_names {
+                    info!("Hashing the {} database.", db_name);
+                    let db = unsafe { txn.open_db(Some(&db_name))? };
+                    manifest
+                        .storage
+                        .insert(db_name, DatabaseDigest::compute(txn, db)?);
+                }
+                Ok(())
+            })?;
+        }
+
+        if db_dir.join(TRIE_STORE_FILE_NAME).exists() {
+            info!("Hashing the trie store.");
+            let env = TrieEnv::open_with_tuning(db_dir, EnvTuning::SEQUENTIAL)?;
+            manifest.trie_store = Some(env.read(|txn| DatabaseDigest::compute(txn, env.db()?))?);
+        }
+        Ok(manifest)
+    }
+}
+
+/// A manifest along with the signature of its JSON serialization.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SignedManifest {
+    pub manifest: ContentManifest,
+    pub signer: PublicKey,
+    pub signature: Signature,
+}
+
+impl SignedManifest {
+    /// Signs `manifest` with `secret_key`.
+    pub fn sign(manifest: ContentManifest, secret_key: &SecretKey) -> Result<Self, Error> {
+        let signer = PublicKey::from(secret_key);
+        let signature = crypto::sign(serde_json::to_vec(&manifest)?, secret_key, &signer);
+        Ok(Self {
+            manifest,
+            signer,
+            signature,
+        })
+    }
+
+    /// Checks the signature of the manifest against its signer.
+    pub fn verify_signature(&self) -> Result<(), Error> {
+        crypto::verify(
+            serde_json::to_vec(&self.manifest)?,
+            &self.signature,
+            &self.signer,
+        )
+        .map_err(Error::Signature)
+    }
+}
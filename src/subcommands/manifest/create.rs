@@ -0,0 +1,127 @@
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+    result::Result,
+};
+
+use casper_types::{crypto::ErrorExt as CryptoError, SecretKey};
+use clap::{Arg, ArgMatches, Command};
+use thiserror::Error as ThisError;
+
+use super::content::{ContentManifest, Error as ContentError, SignedManifest};
+use crate::{
+    common::output::{self, Compression, OutputWriter},
+    subcommands::archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+};
+
+pub const COMMAND_NAME: &str = "create";
+const DB_PATH: &str = "db-path";
+const SECRET_KEY: &str = "secret-key";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when creating a manifest.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the databases: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error building or signing the manifest.
+    #[error("{0}")]
+    Content(#[from] ContentError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the secret key.
+    #[error("Error reading the secret key: {0}")]
+    SecretKey(CryptoError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    SecretKey,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Computes the entry count and a Merkle root hash of the entries \
+            of every database, along with the highest block, and outputs them \
+            in a manifest signed with the given key, in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` and \
+                    `data.lmdb` files, or of a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(SECRET_KEY)
+                .display_order(DisplayOrder::SecretKey as usize)
+                .required(true)
+                .short('k')
+                .long(SECRET_KEY)
+                .takes_value(true)
+                .value_name("SECRET_KEY_PATH")
+                .help("Path of the PEM file of the secret key signing the manifest."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the manifest. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let secret_key_path = matches
+        .value_of(SECRET_KEY)
+        .expect("should have secret-key arg");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    // Fail on a bad key before spending time hashing.
+    let secret_key = SecretKey::from_file(secret_key_path).map_err(Error::SecretKey)?;
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let db_dir = DbDir::open(db_path, &[Include::Storage, Include::Trie])?;
+    let signed_manifest = SignedManifest::sign(ContentManifest::new(db_dir.path())?, &secret_key)?;
+    serde_json::to_writer_pretty(&mut out_writer, &signed_manifest).map_err(ContentError::from)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
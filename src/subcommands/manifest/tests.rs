@@ -0,0 +1,110 @@
+use casper_hashing::Digest;
+use casper_types::SecretKey;
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    content::{ContentManifest, DatabaseDigest, HighestBlock, MerkleHasher, SignedManifest},
+    verify::{self, Mismatch},
+};
+use crate::{
+    common::db::{BlockHeaderDatabase, Database, DeployDatabase, STORAGE_FILE_NAME},
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn put_block(fixture: &LmdbTestFixture, height: u8) -> HighestBlock {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    HighestBlock {
+        height: height as u64,
+        hash: block_hash,
+    }
+}
+
+fn put_deploy(fixture: &LmdbTestFixture, idx: u8, value: u8) {
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+        &test_utils::mock_deploy_hash(idx),
+        &[value],
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn merkle_hasher_should_merge_subtrees() {
+    let leaves: Vec<Digest> = (0..4u8).map(|idx| Digest::hash([idx])).collect();
+    let root = |count: usize| {
+        let mut hasher = MerkleHasher::default();
+        for leaf in &leaves[..count] {
+            hasher.push(*leaf);
+        }
+        hasher.finish()
+    };
+    assert_eq!(root(0), Digest::SENTINEL_NONE);
+    assert_eq!(root(1), leaves[0]);
+    let left = Digest::hash_pair(leaves[0], leaves[1]);
+    assert_eq!(root(3), Digest::hash_pair(left, leaves[2]));
+    assert_eq!(
+        root(4),
+        Digest::hash_pair(left, Digest::hash_pair(leaves[2], leaves[3]))
+    );
+}
+
+#[test]
+fn manifest_should_detect_changes() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), DeployDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    put_block(&fixture, 0);
+    let highest_block = put_block(&fixture, 1);
+    for idx in 0..3 {
+        put_deploy(&fixture, idx, idx);
+    }
+
+    let manifest = ContentManifest::new(fixture.tmp_dir.path()).unwrap();
+    assert_eq!(manifest.highest_block, Some(highest_block));
+    assert_eq!(manifest.storage[DeployDatabase::db_name()].entries, 3);
+    assert_eq!(manifest.trie_store, None);
+    let unchanged_manifest = ContentManifest::new(fixture.tmp_dir.path()).unwrap();
+    assert!(verify::compare(&manifest, &unchanged_manifest).is_empty());
+
+    let secret_key = SecretKey::ed25519_from_bytes([1u8; 32]).unwrap();
+    let mut signed_manifest = SignedManifest::sign(manifest, &secret_key).unwrap();
+    signed_manifest.verify_signature().unwrap();
+
+    put_deploy(&fixture, 1, 7);
+    let changed_manifest = ContentManifest::new(fixture.tmp_dir.path()).unwrap();
+    let expected_digest = signed_manifest.manifest.storage[DeployDatabase::db_name()].clone();
+    let actual_digest = changed_manifest.storage[DeployDatabase::db_name()].clone();
+    assert_eq!(actual_digest.entries, 3);
+    assert_eq!(
+        verify::compare(&signed_manifest.manifest, &changed_manifest),
+        vec![Mismatch::Database {
+            name: DeployDatabase::db_name().to_string(),
+            expected: Some(expected_digest),
+            actual: Some(actual_digest),
+        }]
+    );
+
+    // Claiming the changed contents invalidates the signature.
+    signed_manifest.manifest.storage.insert(
+        DeployDatabase::db_name().to_string(),
+        DatabaseDigest {
+            entries: 3,
+            root_hash: changed_manifest.storage[DeployDatabase::db_name()].root_hash,
+        },
+    );
+    assert!(signed_manifest.verify_signature().is_err());
+}
@@ -0,0 +1,245 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::Error as IoError,
+    path::PathBuf,
+    result::Result,
+};
+
+use casper_types::{crypto::Error as CryptoError, AsymmetricType, PublicKey};
+use clap::{Arg, ArgMatches, Command};
+use log::{info, warn};
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::content::{
+    ContentManifest, DatabaseDigest, Error as ContentError, HighestBlock, SignedManifest,
+};
+use crate::{
+    common::db::TRIE_STORE_FILE_NAME,
+    subcommands::archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+};
+
+pub const COMMAND_NAME: &str = "verify";
+const DB_PATH: &str = "db-path";
+const MANIFEST: &str = "manifest";
+const SIGNER: &str = "signer";
+
+/// Errors encountered when verifying databases against a manifest.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the databases: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error building the manifest of the databases or checking the
+    /// signature of the given one.
+    #[error("{0}")]
+    Content(#[from] ContentError),
+    /// The given signer isn't a valid public key.
+    #[error("Invalid signer: {0}")]
+    InvalidSigner(CryptoError),
+    /// Error reading the manifest.
+    #[error("Error reading manifest {0}: {1}")]
+    Manifest(PathBuf, IoError),
+    /// The databases differ from the manifest.
+    #[error("Found {0} differences from the manifest")]
+    Mismatches(usize),
+    /// Error parsing the manifest.
+    #[error("Error parsing the manifest: {0}")]
+    Parsing(#[from] SerializationError),
+    /// The manifest was signed by another key than the expected one.
+    #[error("The manifest is signed by {0}, not by {1}")]
+    SignerMismatch(String, String),
+}
+
+/// A difference between the databases and their manifest.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Mismatch {
+    HighestBlock {
+        expected: Option<HighestBlock>,
+        actual: Option<HighestBlock>,
+    },
+    Database {
+        name: String,
+        expected: Option<DatabaseDigest>,
+        actual: Option<DatabaseDigest>,
+    },
+}
+
+fn describe_block(maybe_block: &Option<HighestBlock>) -> String {
+    match maybe_block {
+        Some(block) => format!("block {} at height {}", block.hash, block.height),
+        None => "no block".to_string(),
+    }
+}
+
+fn describe_digest(maybe_digest: &Option<DatabaseDigest>) -> String {
+    match maybe_digest {
+        Some(digest) => format!(
+            "{} entries with root hash {}",
+            digest.entries, digest.root_hash
+        ),
+        None => "no database".to_string(),
+    }
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::HighestBlock { expected, actual } => write!(
+                f,
+                "highest block: expected {}, found {}",
+                describe_block(expected),
+                describe_block(actual)
+            ),
+            Mismatch::Database {
+                name,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "{}: expected {}, found {}",
+                name,
+                describe_digest(expected),
+                describe_digest(actual)
+            ),
+        }
+    }
+}
+
+/// Lists the differences between the `expected` manifest and the `actual`
+/// one of the databases. The chain names and creation times aren't
+/// compared, as they depend on where and when the manifests were created.
+pub(crate) fn compare(expected: &ContentManifest, actual: &ContentManifest) -> Vec<Mismatch> {
+    let mut mismatches = vec![];
+    if expected.highest_block != actual.highest_block {
+        mismatches.push(Mismatch::HighestBlock {
+            expected: expected.highest_block.clone(),
+            actual: actual.highest_block.clone(),
+        });
+    }
+    let db_names: BTreeSet<&String> = expected
+        .storage
+        .keys()
+        .chain(actual.storage.keys())
+        .collect();
+    for db_name in db_names {
+        let expected_digest = expected.storage.get(db_name);
+        let actual_digest = actual.storage.get(db_name);
+        if expected_digest != actual_digest {
+            mismatches.push(Mismatch::Database {
+                name: db_name.clone(),
+                expected: expected_digest.cloned(),
+                actual: actual_digest.cloned(),
+            });
+        }
+    }
+    if expected.trie_store != actual.trie_store {
+        mismatches.push(Mismatch::Database {
+            name: TRIE_STORE_FILE_NAME.to_string(),
+            expected: expected.trie_store.clone(),
+            actual: actual.trie_store.clone(),
+        });
+    }
+    mismatches
+}
+
+enum DisplayOrder {
+    DbPath,
+    Manifest,
+    Signer,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Checks the signature of a manifest, then recomputes the digests \
+            of the databases and lists every difference from the manifest.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` and \
+                    `data.lmdb` files, or of a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(MANIFEST)
+                .display_order(DisplayOrder::Manifest as usize)
+                .required(true)
+                .short('m')
+                .long(MANIFEST)
+                .takes_value(true)
+                .value_name("MANIFEST_PATH")
+                .help("Path of the signed manifest."),
+        )
+        .arg(
+            Arg::new(SIGNER)
+                .display_order(DisplayOrder::Signer as usize)
+                .short('s')
+                .long(SIGNER)
+                .takes_value(true)
+                .value_name("PUBLIC_KEY_HEX")
+                .help(
+                    "Hex encoded public key the manifest must be signed with. \
+                    Without it, any valid signature is accepted.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let manifest_path = PathBuf::from(
+        matches
+            .value_of(MANIFEST)
+            .expect("should have manifest arg"),
+    );
+    let maybe_signer = matches
+        .value_of(SIGNER)
+        .map(PublicKey::from_hex)
+        .transpose()
+        .map_err(Error::InvalidSigner)?;
+
+    let file =
+        File::open(&manifest_path).map_err(|io_err| Error::Manifest(manifest_path, io_err))?;
+    let signed_manifest: SignedManifest = serde_json::from_reader(file)?;
+    signed_manifest.verify_signature()?;
+    match maybe_signer {
+        Some(signer) if signer != signed_manifest.signer => {
+            return Err(Error::SignerMismatch(
+                signed_manifest.signer.to_hex(),
+                signer.to_hex(),
+            ))
+        }
+        Some(_) => {}
+        None => warn!(
+            "The manifest is signed by {}, pass --{} to make sure it's the \
+            expected key.",
+            signed_manifest.signer.to_hex(),
+            SIGNER
+        ),
+    }
+
+    let db_dir = DbDir::open(db_path, &[Include::Storage, Include::Trie])?;
+    let manifest = ContentManifest::new(db_dir.path())?;
+    let mismatches = compare(&signed_manifest.manifest, &manifest);
+    for mismatch in mismatches.iter() {
+        warn!("Mismatch in {}", mismatch);
+    }
+    if !mismatches.is_empty() {
+        return Err(Error::Mismatches(mismatches.len()));
+    }
+    info!("The databases match the manifest.");
+    Ok(())
+}
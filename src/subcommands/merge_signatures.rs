@@ -0,0 +1,140 @@
+mod merge;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use casper_node::types::BlockHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as JsonError;
+use thiserror::Error as ThisError;
+
+use super::{
+    archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+    purge_signatures::Error as EraWeightsError,
+};
+
+use merge::Source;
+
+pub const COMMAND_NAME: &str = "merge-signatures";
+const DB_PATH: &str = "db-path";
+const PROOFS: &str = "proofs";
+const SOURCE_DB_PATH: &str = "source-db-path";
+
+/// Errors encountered when running the `merge-signatures` subcommand.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the source database.
+    #[error("Error opening the source database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error looking up the validator weights of an era.
+    #[error("Error looking up the validator weights: {0}")]
+    EraWeights(#[from] EraWeightsError),
+    /// Parsing error on entry in the block header database.
+    #[error("Error parsing block header with hash {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    /// Error reading the proofs file.
+    #[error("Error reading proofs file: {0}")]
+    ProofsFile(#[from] IoError),
+    /// Error parsing the proofs file.
+    #[error("Error parsing proofs file: {0}")]
+    ProofsParsing(#[from] JsonError),
+    /// Serialization error for an entry in the signatures database.
+    #[error("Error serializing block signatures for block hash {0}: {1}")]
+    Serialize(BlockHash, BincodeError),
+    /// Parsing error on entry in the signatures database of the target.
+    #[error("Error parsing block signatures for block hash {0}: {1}")]
+    SignaturesParsing(BlockHash, BincodeError),
+    /// Parsing error on entry in the signatures database of the source.
+    #[error("Error parsing source block signatures with key {0}: {1}")]
+    SourceSignaturesParsing(String, BincodeError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    SourceDbPath,
+    Proofs,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Merges the finality signatures of another storage database, or of \
+            a JSON file of proofs, into the signatures of the blocks of a \
+            storage database.",
+        )
+        .long_about(
+            "Merges the finality signatures of another storage database, or of \
+            a JSON file of proofs, into the signatures of the blocks of a \
+            storage database. Signatures are only added for blocks of the \
+            target database, in the era of the block, by validators of that \
+            era, and if they are valid. A validator already having signed a \
+            block in the target database keeps its signature.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file to merge \
+                    the signatures into.",
+                ),
+        )
+        .arg(
+            Arg::new(SOURCE_DB_PATH)
+                .display_order(DisplayOrder::SourceDbPath as usize)
+                .required_unless_present(PROOFS)
+                .conflicts_with(PROOFS)
+                .short('s')
+                .long(SOURCE_DB_PATH)
+                .takes_value(true)
+                .value_name("SOURCE_DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of a \
+                    `.tar.zst` archive of it, whose signatures are merged.",
+                ),
+        )
+        .arg(
+            Arg::new(PROOFS)
+                .display_order(DisplayOrder::Proofs as usize)
+                .required_unless_present(SOURCE_DB_PATH)
+                .short('p')
+                .long(PROOFS)
+                .takes_value(true)
+                .value_name("JSON_FILE")
+                .help(
+                    "Path of a JSON file holding an array of finality signatures \
+                    to merge, as objects with the `block_hash`, `era_id`, \
+                    `signature` and `public_key` fields, like the node emits \
+                    them.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    match matches.value_of(SOURCE_DB_PATH) {
+        Some(source_path) => {
+            let source_dir = DbDir::open(source_path, &[Include::Storage])?;
+            merge::merge_signatures(path, Source::Storage(source_dir.path()))
+        }
+        None => {
+            let proofs_path = matches.value_of(PROOFS).expect("should have proofs arg");
+            merge::merge_signatures(path, Source::Proofs(Path::new(proofs_path)))
+        }
+    }
+    .map(|_report| ())
+}
@@ -0,0 +1,270 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+    result::Result,
+};
+
+use casper_node::types::{BlockHash, BlockHeader, FinalitySignature};
+use casper_types::EraId;
+use lmdb::{Cursor, Database, Error as LmdbError, RwTransaction, Transaction, WriteFlags};
+use log::{info, warn};
+
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, BlockMetadataDatabase, StorageEnv},
+        lmdb_utils,
+        progress::ProgressTracker,
+    },
+    subcommands::purge_signatures::{
+        block_signatures::BlockSignatures,
+        purge::{self, EraWeights, Indices},
+        Error as EraWeightsError,
+    },
+};
+
+use super::Error;
+
+/// Where the signatures to merge come from.
+pub(crate) enum Source<'a> {
+    /// The directory of another storage database.
+    Storage(&'a Path),
+    /// A JSON file holding an array of finality signatures.
+    Proofs(&'a Path),
+}
+
+/// Counts of the signatures considered for merging.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct MergeReport {
+    /// Blocks which got new signatures.
+    pub(crate) blocks: usize,
+    /// Signatures added to the target database.
+    pub(crate) added: usize,
+    /// Signatures by validators which already signed the block.
+    pub(crate) duplicates: usize,
+    /// Signatures of blocks missing from the target database.
+    pub(crate) skipped: usize,
+    /// Signatures for the wrong era, by a signer which isn't a validator of
+    /// the era, or which don't verify.
+    pub(crate) rejected: usize,
+}
+
+/// The target database, along with the validator weights of its eras.
+struct Target<'a> {
+    txn: RwTransaction<'a>,
+    header_db: Database,
+    signatures_db: Database,
+    indices: Indices,
+    era_weights: EraWeights,
+}
+
+impl<'a> Target<'a> {
+    /// Adds the signatures of `incoming` to the ones of its block, if the
+    /// block is in the target database. Only the signatures by validators of
+    /// the era of the block which didn't sign it yet are added, once verified.
+    fn merge(&mut self, incoming: BlockSignatures, report: &mut MergeReport) -> Result<(), Error> {
+        let block_hash = incoming.block_hash;
+        let header: BlockHeader = match self.txn.get(self.header_db, &block_hash) {
+            Ok(raw_header) => bincode::deserialize(raw_header)
+                .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?,
+            Err(LmdbError::NotFound) => {
+                warn!(
+                    "Skipping {} signatures of block {}, which isn't in the database.",
+                    incoming.proofs.len(),
+                    block_hash
+                );
+                report.skipped += incoming.proofs.len();
+                return Ok(());
+            }
+            Err(lmdb_err) => return Err(lmdb_err.into()),
+        };
+        let era_id = header.era_id();
+        if incoming.era_id != era_id {
+            warn!(
+                "Rejecting {} signatures of block {} for era {}, as the block \
+                is in era {}.",
+                incoming.proofs.len(),
+                block_hash,
+                incoming.era_id,
+                era_id
+            );
+            report.rejected += incoming.proofs.len();
+            return Ok(());
+        }
+        // The validators of the genesis era are only in the chainspec.
+        let weights_result = if era_id.is_genesis() {
+            Err(EraWeightsError::MissingEraWeights(era_id))
+        } else {
+            self.era_weights.refresh_weights_for_era(
+                &self.txn,
+                self.header_db,
+                &self.indices,
+                era_id,
+            )
+        };
+        match weights_result {
+            Ok(_) => (),
+            Err(EraWeightsError::MissingEraWeights(_)) => {
+                warn!(
+                    "Rejecting {} signatures of block {}, as the validators of \
+                    era {} aren't in the database.",
+                    incoming.proofs.len(),
+                    block_hash,
+                    era_id
+                );
+                report.rejected += incoming.proofs.len();
+                return Ok(());
+            }
+            Err(era_weights_err) => return Err(era_weights_err.into()),
+        }
+
+        let mut signatures: BlockSignatures = match self.txn.get(self.signatures_db, &block_hash) {
+            Ok(raw_signatures) => bincode::deserialize(raw_signatures)
+                .map_err(|bincode_err| Error::SignaturesParsing(block_hash, bincode_err))?,
+            Err(LmdbError::NotFound) => BlockSignatures {
+                block_hash,
+                era_id,
+                proofs: BTreeMap::new(),
+            },
+            Err(lmdb_err) => return Err(lmdb_err.into()),
+        };
+        let mut added = 0;
+        for (public_key, signature) in incoming.proofs {
+            if signatures.proofs.contains_key(&public_key) {
+                report.duplicates += 1;
+                continue;
+            }
+            if !self.era_weights.weights().contains_key(&public_key) {
+                warn!(
+                    "Rejecting signature of block {} by {}, which isn't a \
+                    validator of era {}.",
+                    block_hash, public_key, era_id
+                );
+                report.rejected += 1;
+                continue;
+            }
+            let finality_signature = FinalitySignature {
+                block_hash,
+                era_id,
+                signature,
+                public_key,
+            };
+            if let Err(crypto_err) = finality_signature.verify() {
+                warn!(
+                    "Rejecting invalid signature of block {} by {}: {}",
+                    block_hash, finality_signature.public_key, crypto_err
+                );
+                report.rejected += 1;
+                continue;
+            }
+            signatures
+                .proofs
+                .insert(finality_signature.public_key, finality_signature.signature);
+            added += 1;
+        }
+        if added > 0 {
+            let serialized_signatures = bincode::serialize(&signatures)
+                .map_err(|bincode_err| Error::Serialize(block_hash, bincode_err))?;
+            self.txn.put(
+                self.signatures_db,
+                &block_hash,
+                &serialized_signatures,
+                WriteFlags::default(),
+            )?;
+            report.blocks += 1;
+            report.added += added;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the finality signatures of the JSON file at `path`, grouped by block.
+/// Repeated signatures by the same validator are counted as duplicates.
+fn read_proofs(path: &Path, report: &mut MergeReport) -> Result<Vec<BlockSignatures>, Error> {
+    let finality_signatures: Vec<FinalitySignature> = serde_json::from_slice(&fs::read(path)?)?;
+    let mut grouped: BTreeMap<(BlockHash, EraId), BlockSignatures> = BTreeMap::new();
+    for finality_signature in finality_signatures {
+        let block_hash = finality_signature.block_hash;
+        let era_id = finality_signature.era_id;
+        let block_signatures =
+            grouped
+                .entry((block_hash, era_id))
+                .or_insert_with(|| BlockSignatures {
+                    block_hash,
+                    era_id,
+                    proofs: BTreeMap::new(),
+                });
+        if block_signatures
+            .proofs
+            .insert(finality_signature.public_key, finality_signature.signature)
+            .is_some()
+        {
+            report.duplicates += 1;
+        }
+    }
+    Ok(grouped.into_values().collect())
+}
+
+/// Merges the finality signatures of `source` into the signatures database of
+/// the storage database in `db_path`, in a single transaction.
+pub(crate) fn merge_signatures<P: AsRef<Path>>(
+    db_path: P,
+    source: Source,
+) -> Result<MergeReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let indices = purge::initialize_indices(&env, &BTreeSet::new())?;
+    let header_db = env.db::<BlockHeaderDatabase>()?;
+    let signatures_db = env.db::<BlockMetadataDatabase>()?;
+    let txn = env.begin_rw_txn()?;
+    let mut target = Target {
+        txn,
+        header_db,
+        signatures_db,
+        indices,
+        era_weights: EraWeights::default(),
+    };
+
+    let mut report = MergeReport::default();
+    match source {
+        Source::Storage(source_path) => {
+            let source_env = StorageEnv::open(source_path)?;
+            let source_db = source_env.db::<BlockMetadataDatabase>()?;
+            let source_txn = source_env.begin_ro_txn()?;
+            let mut maybe_progress_tracker = lmdb_utils::entry_count(&source_txn, source_db)
+                .ok()
+                .and_then(|entry_count| {
+                    ProgressTracker::new(
+                        entry_count,
+                        Box::new(|completion| {
+                            info!("Signatures merging {}% complete...", completion)
+                        }),
+                    )
+                    .ok()
+                });
+            let mut cursor = source_txn.open_ro_cursor(source_db)?;
+            for (raw_key, raw_signatures) in cursor.iter() {
+                let incoming: BlockSignatures =
+                    bincode::deserialize(raw_signatures).map_err(|bincode_err| {
+                        Error::SourceSignaturesParsing(hex::encode(raw_key), bincode_err)
+                    })?;
+                target.merge(incoming, &mut report)?;
+                if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                    progress_tracker.advance_by(1);
+                }
+            }
+        }
+        Source::Proofs(proofs_path) => {
+            for incoming in read_proofs(proofs_path, &mut report)? {
+                target.merge(incoming, &mut report)?;
+            }
+        }
+    }
+    target.txn.commit()?;
+    info!(
+        "Added {} signatures to {} blocks, skipping {} duplicate signatures and \
+        {} signatures of blocks missing from the database, and rejecting {} \
+        signatures.",
+        report.added, report.blocks, report.duplicates, report.skipped, report.rejected
+    );
+    Ok(report)
+}
@@ -0,0 +1,336 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::PathBuf,
+};
+
+use casper_node::types::{BlockHash, FinalitySignature};
+use casper_types::{EraId, PublicKey, SecretKey, U256};
+use lmdb::{Transaction, WriteFlags};
+
+use super::merge::{merge_signatures, MergeReport, Source};
+use crate::{
+    common::db::{BlockHeaderDatabase, BlockMetadataDatabase, Database, STORAGE_FILE_NAME},
+    subcommands::purge_signatures::block_signatures::BlockSignatures,
+    test_utils::{self, LmdbTestFixture, KEYS},
+};
+
+// Returns the secret key of `KEYS[idx]`.
+fn secret_key(idx: usize) -> SecretKey {
+    let mut bytes = [0u8; 32];
+    U256::from(idx).to_big_endian(&mut bytes);
+    SecretKey::ed25519_from_bytes(bytes).unwrap()
+}
+
+fn sign(block_hash: BlockHash, era_id: EraId, idx: usize) -> FinalitySignature {
+    FinalitySignature::new(block_hash, era_id, &secret_key(idx), KEYS[idx].clone())
+}
+
+fn put_signatures(fixture: &LmdbTestFixture, signatures: &[FinalitySignature]) {
+    let mut block_signatures = BlockSignatures {
+        block_hash: signatures[0].block_hash,
+        era_id: signatures[0].era_id,
+        proofs: BTreeMap::new(),
+    };
+    for signature in signatures {
+        block_signatures
+            .proofs
+            .insert(signature.public_key.clone(), signature.signature);
+    }
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+        &block_signatures.block_hash,
+        &bincode::serialize(&block_signatures).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+fn get_signers(fixture: &LmdbTestFixture, block_hash: &BlockHash) -> BTreeSet<PublicKey> {
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let block_signatures: BlockSignatures = bincode::deserialize(
+        txn.get(
+            *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+            block_hash,
+        )
+        .unwrap(),
+    )
+    .unwrap();
+    block_signatures.proofs.into_keys().collect()
+}
+
+/// Writes the headers of a switch block closing era 1 with `KEYS[0..3]` as
+/// the validators of era 2, of a switch block closing era 2 with `KEYS[3..5]`
+/// as the validators of era 3, and of two blocks of era 2. Returns the hashes
+/// of the two blocks of era 2.
+fn put_era_2_blocks(fixture: &LmdbTestFixture) -> (BlockHash, BlockHash) {
+    let mut headers = vec![];
+    for (idx, (era, keys)) in [(1, 0..3), (2, 3..5)].into_iter().enumerate() {
+        let (hash, mut header) = test_utils::mock_switch_block_header(idx as u8);
+        header.era_id = EraId::new(era);
+        header.height = era * 10;
+        for key in &KEYS[keys] {
+            header.insert_key_weight(key.clone(), 100.into());
+        }
+        headers.push((hash, bincode::serialize(&header).unwrap()));
+    }
+    let mut block_hashes = vec![];
+    for idx in 2..4u8 {
+        let (hash, mut header) = test_utils::mock_block_header(idx);
+        header.era_id = EraId::new(2);
+        header.height = 10 + idx as u64;
+        block_hashes.push(hash);
+        headers.push((hash, bincode::serialize(&header).unwrap()));
+    }
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (hash, raw_header) in headers {
+        txn.put(header_db, &hash, &raw_header, WriteFlags::empty())
+            .unwrap();
+    }
+    txn.commit().unwrap();
+    (block_hashes[0], block_hashes[1])
+}
+
+fn write_proofs(fixture: &LmdbTestFixture, proofs: &[FinalitySignature]) -> PathBuf {
+    let proofs_path = fixture.tmp_dir.path().join("proofs.json");
+    fs::write(&proofs_path, serde_json::to_vec(proofs).unwrap()).unwrap();
+    proofs_path
+}
+
+#[test]
+fn merge_signatures_should_reject_signers_outside_the_era() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let (block_hash, _) = put_era_2_blocks(&fixture);
+    let era_id = EraId::new(2);
+    let proofs_path = write_proofs(
+        &fixture,
+        &[
+            sign(block_hash, era_id, 1),
+            // A validator of the next era only.
+            sign(block_hash, era_id, 3),
+            // Not a validator of any era.
+            sign(block_hash, era_id, 6),
+        ],
+    );
+    let report = merge_signatures(fixture.tmp_dir.path(), Source::Proofs(&proofs_path)).unwrap();
+    assert_eq!(
+        report,
+        MergeReport {
+            blocks: 1,
+            added: 1,
+            rejected: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        get_signers(&fixture, &block_hash),
+        BTreeSet::from([KEYS[1].clone()])
+    );
+
+    // The same goes for the signatures of another storage database, which
+    // leave the block alone if none is by a validator of the era.
+    let source_fixture = LmdbTestFixture::new(
+        vec![BlockMetadataDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    put_signatures(
+        &source_fixture,
+        &[sign(block_hash, era_id, 4), sign(block_hash, era_id, 7)],
+    );
+    let report = merge_signatures(
+        fixture.tmp_dir.path(),
+        Source::Storage(source_fixture.tmp_dir.path()),
+    )
+    .unwrap();
+    assert_eq!(
+        report,
+        MergeReport {
+            rejected: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        get_signers(&fixture, &block_hash),
+        BTreeSet::from([KEYS[1].clone()])
+    );
+}
+
+#[test]
+fn merge_signatures_should_deduplicate_per_validator() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let (block_hash, other_block_hash) = put_era_2_blocks(&fixture);
+    let era_id = EraId::new(2);
+    put_signatures(&fixture, &[sign(block_hash, era_id, 0)]);
+    let proofs_path = write_proofs(
+        &fixture,
+        &[
+            // Already in the database.
+            sign(block_hash, era_id, 0),
+            // Given twice.
+            sign(block_hash, era_id, 1),
+            sign(block_hash, era_id, 1),
+            // The same validators signing another block aren't duplicates.
+            sign(other_block_hash, era_id, 0),
+            sign(other_block_hash, era_id, 1),
+        ],
+    );
+    let report = merge_signatures(fixture.tmp_dir.path(), Source::Proofs(&proofs_path)).unwrap();
+    assert_eq!(
+        report,
+        MergeReport {
+            blocks: 2,
+            added: 3,
+            duplicates: 2,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        get_signers(&fixture, &block_hash),
+        BTreeSet::from([KEYS[0].clone(), KEYS[1].clone()])
+    );
+    assert_eq!(
+        get_signers(&fixture, &other_block_hash),
+        BTreeSet::from([KEYS[0].clone(), KEYS[1].clone()])
+    );
+
+    // Merging the same signatures again adds nothing.
+    let report = merge_signatures(fixture.tmp_dir.path(), Source::Proofs(&proofs_path)).unwrap();
+    assert_eq!(
+        report,
+        MergeReport {
+            duplicates: 5,
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn merge_signatures_should_validate_and_deduplicate() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let era_id = EraId::new(2);
+    // The switch block of era 1 holds the weights of the validators of era 2.
+    let (switch_block_hash, mut switch_block_header) = test_utils::mock_switch_block_header(0);
+    switch_block_header.era_id = EraId::new(1);
+    switch_block_header.height = 5;
+    for key in KEYS.iter().take(3) {
+        switch_block_header.insert_key_weight(key.clone(), 100.into());
+    }
+    let (block_hash, mut block_header) = test_utils::mock_block_header(1);
+    block_header.era_id = era_id;
+    block_header.height = 10;
+    // No weights are known for era 3.
+    let (later_block_hash, mut later_block_header) = test_utils::mock_block_header(2);
+    later_block_header.era_id = EraId::new(3);
+    later_block_header.height = 20;
+    let (missing_block_hash, _) = test_utils::mock_block_header(3);
+    {
+        let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        txn.put(
+            header_db,
+            &switch_block_hash,
+            &bincode::serialize(&switch_block_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        for (hash, header) in [
+            (block_hash, &block_header),
+            (later_block_hash, &later_block_header),
+        ] {
+            txn.put(
+                header_db,
+                &hash,
+                &bincode::serialize(header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    }
+    put_signatures(&fixture, &[sign(block_hash, era_id, 0)]);
+
+    let mut forged_signature = sign(block_hash, era_id, 2);
+    forged_signature.signature = sign(later_block_hash, era_id, 2).signature;
+    let proofs = vec![
+        // Already in the database.
+        sign(block_hash, era_id, 0),
+        sign(block_hash, era_id, 1),
+        // Not a validator of the era.
+        sign(block_hash, era_id, 4),
+        // Signature of another block.
+        forged_signature,
+        // Wrong era.
+        sign(block_hash, EraId::new(3), 2),
+        // Unknown validators.
+        sign(later_block_hash, EraId::new(3), 0),
+        // Skipped.
+        sign(missing_block_hash, era_id, 0),
+    ];
+    let proofs_path = fixture.tmp_dir.path().join("proofs.json");
+    fs::write(&proofs_path, serde_json::to_vec(&proofs).unwrap()).unwrap();
+
+    let report = merge_signatures(fixture.tmp_dir.path(), Source::Proofs(&proofs_path)).unwrap();
+    assert_eq!(
+        report,
+        MergeReport {
+            blocks: 1,
+            added: 1,
+            duplicates: 1,
+            skipped: 1,
+            rejected: 4,
+        }
+    );
+    assert_eq!(
+        get_signers(&fixture, &block_hash),
+        BTreeSet::from([KEYS[0].clone(), KEYS[1].clone()])
+    );
+
+    // The signatures of another storage database are merged the same way.
+    let source_fixture = LmdbTestFixture::new(
+        vec![BlockMetadataDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    put_signatures(
+        &source_fixture,
+        &[sign(block_hash, era_id, 1), sign(block_hash, era_id, 2)],
+    );
+    let report = merge_signatures(
+        fixture.tmp_dir.path(),
+        Source::Storage(source_fixture.tmp_dir.path()),
+    )
+    .unwrap();
+    assert_eq!(
+        report,
+        MergeReport {
+            blocks: 1,
+            added: 1,
+            duplicates: 1,
+            ..Default::default()
+        }
+    );
+    assert_eq!(
+        get_signers(&fixture, &block_hash),
+        KEYS.iter().take(3).cloned().collect()
+    );
+}
@@ -0,0 +1,155 @@
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use serde::Serialize;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+    network::{self, Error as NetworkError, DATA_DIR_OPTIONS},
+    output::{self, Compression, OutputWriter},
+};
+
+pub const COMMAND_NAME: &str = "networks";
+/// Long name of the global option selecting the network.
+pub const NETWORK: &str = "network";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when listing networks.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error listing the networks.
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+/// A network with databases in a subdirectory of the listed directory.
+#[derive(Debug, Serialize)]
+struct NetworkEntry {
+    name: String,
+    path: PathBuf,
+    /// Whether the directory holds a storage database.
+    storage: bool,
+    /// Whether the directory holds a trie store.
+    trie_store: bool,
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Lists the networks with databases in the subdirectories of a \
+            directory, such as the storage directory of a node which ran on \
+            several networks, in JSON format. Pass `--network` to other \
+            subcommands to select which network's databases they operate on.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with a subdirectory per network."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the list in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+/// Makes the options of the subcommands of `cli` taking the path of a
+/// directory with databases accept a directory with a subdirectory per
+/// network, selecting the one of `maybe_network`, or the only one if none is
+/// given. This subcommand keeps the path as given.
+pub fn select_network(cli: Command<'static>, maybe_network: Option<String>) -> Command<'static> {
+    fn select_in(mut cmd: Command<'static>, maybe_network: &Option<String>) -> Command<'static> {
+        let ids: Vec<&'static str> = cmd
+            .get_arguments()
+            .filter(|arg| {
+                arg.get_long()
+                    .is_some_and(|long| DATA_DIR_OPTIONS.contains(&long))
+            })
+            .map(|arg| arg.get_id())
+            .collect();
+        for id in ids {
+            let maybe_network = maybe_network.clone();
+            cmd = cmd.mut_arg(id, |arg| {
+                arg.value_parser(move |path: &str| {
+                    network::select_network(path, maybe_network.as_deref())
+                        .map(|dir| dir.to_string_lossy().into_owned())
+                })
+            });
+        }
+        for subcmd in cmd.get_subcommands_mut() {
+            if subcmd.get_name() != COMMAND_NAME {
+                *subcmd = select_in(std::mem::take(subcmd), maybe_network);
+            }
+        }
+        cmd
+    }
+    select_in(cli, &maybe_network)
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let networks: Vec<NetworkEntry> = network::list_networks(path)?
+        .into_iter()
+        .map(|(name, path)| NetworkEntry {
+            storage: path.join(STORAGE_FILE_NAME).is_file(),
+            trie_store: path.join(TRIE_STORE_FILE_NAME).is_file(),
+            name,
+            path,
+        })
+        .collect();
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    serde_json::to_writer_pretty(&mut out_writer, &networks)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+mod scan;
+#[cfg(test)]
+mod tests;
+
+use std::path::Path;
+
+use bincode::Error as BincodeError;
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, DeployHash};
+use casper_types::bytesrepr::Error as BytesreprError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+pub const COMMAND_NAME: &str = "orphans";
+const DB_PATH: &str = "db-path";
+const PURGE: &str = "purge";
+
+/// Errors encountered when operating on the storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Parsing error on entry in the block body database.
+    #[error("Error parsing block body with hash {0}: {1}")]
+    BodyParsing(Digest, BincodeError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Parsing error on entry in the deploy metadata database.
+    #[error("Error parsing execution results for deploy {0}: {1}")]
+    ExecutionResultsParsing(DeployHash, BincodeError),
+    /// Parsing error on entry in the block header database.
+    #[error("Error parsing block header with hash {0}: {1}")]
+    HeaderParsing(BlockHash, BincodeError),
+    /// Key of an entry is not a valid hash.
+    #[error("Invalid key at index {1} in {0} DB")]
+    InvalidKey(&'static str, usize),
+    /// Parsing error on entry in one of the merkle block body databases.
+    #[error("Error parsing merkle block body part with hash {0}: {1}")]
+    MerkleParsing(Digest, BytesreprError),
+    /// Serialization error on entry in the deploy metadata database.
+    #[error("Error serializing execution results for deploy {0}: {1}")]
+    Serialization(DeployHash, BincodeError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Purge,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Finds block bodies not referenced by any block header, deploys \
+            not referenced by any block body and execution results for \
+            unknown blocks in a storage database, optionally deleting them.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(PURGE)
+                .display_order(DisplayOrder::Purge as usize)
+                .required(false)
+                .short('p')
+                .long(PURGE)
+                .takes_value(false)
+                .help("Delete the orphaned entries from the database."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let purge = matches.is_present(PURGE);
+    scan::find_orphans(path, purge).map(|_report| ())
+}
@@ -0,0 +1,371 @@
+use std::{collections::HashSet, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader, DeployHash, DeployMetadata};
+use casper_types::bytesrepr::FromBytes;
+use lmdb::{Cursor, Database, Error as LmdbError, RwTransaction, Transaction, WriteFlags};
+use log::{info, warn};
+
+use crate::{
+    common::{
+        db::{
+            self, BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database as _,
+            DeployDatabase, DeployHashesDatabase, DeployMetadataDatabase, ProposerDatabase,
+            StorageEnv, TransferHashesDatabase,
+        },
+        merkle_body::{MerkleBody, PARTS_COUNT},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+/// Counts of unreferenced entries found in a storage database.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct OrphanReport {
+    /// Block bodies in `block_body` not referenced by any block header.
+    pub(crate) orphan_bodies: usize,
+    /// Linked list nodes in `block_body_merkle` not reachable from any block
+    /// header.
+    pub(crate) orphan_merkle_nodes: usize,
+    /// Parts in `deploy_hashes`, `transfer_hashes` and `proposers` not
+    /// referenced by any reachable linked list node.
+    pub(crate) orphan_merkle_parts: usize,
+    /// Body hashes of block headers stored both in `block_body` and in
+    /// `block_body_merkle`. Both copies are kept, as the node reads
+    /// whichever it finds.
+    pub(crate) duplicate_bodies: usize,
+    /// Bodies referenced by a block header which are missing or incomplete
+    /// in both layouts. While there are any, no body data nor deploy is
+    /// purged, as it may belong to them.
+    pub(crate) incomplete_bodies: usize,
+    /// Deploys not referenced by any block body. Only looked for when no
+    /// body is incomplete.
+    pub(crate) orphan_deploys: usize,
+    /// Execution results stored for blocks which are not in the database.
+    pub(crate) orphan_execution_results: usize,
+}
+
+/// Keys and values to be deleted or rewritten when purging orphans.
+#[derive(Default)]
+struct PurgeSet {
+    bodies: Vec<Digest>,
+    merkle_nodes: Vec<Digest>,
+    /// Merkle body parts along with the index of the part they hold.
+    merkle_parts: Vec<(usize, Digest)>,
+    deploys: Vec<DeployHash>,
+    /// Deploy metadata with the orphaned execution results already removed.
+    metadata: Vec<(DeployHash, DeployMetadata)>,
+}
+
+/// Handles of the databases the scan reads, `None` for those which don't
+/// exist.
+struct Databases {
+    header: Database,
+    body: Option<Database>,
+    merkle: Option<Database>,
+    /// The databases holding each part of a merkle body, in the order of the
+    /// parts.
+    parts: [Option<Database>; PARTS_COUNT],
+    deploys: Option<Database>,
+    deploy_metadata: Option<Database>,
+}
+
+impl Databases {
+    fn open(env: &StorageEnv) -> Result<Self, LmdbError> {
+        Ok(Self {
+            header: env.db::<BlockHeaderDatabase>()?,
+            body: env.optional_db::<BlockBodyDatabase>()?,
+            merkle: env.optional_db::<BlockBodyMerkleDatabase>()?,
+            parts: [
+                env.optional_db::<DeployHashesDatabase>()?,
+                env.optional_db::<TransferHashesDatabase>()?,
+                env.optional_db::<ProposerDatabase>()?,
+            ],
+            deploys: env.optional_db::<DeployDatabase>()?,
+            deploy_metadata: env.optional_db::<DeployMetadataDatabase>()?,
+        })
+    }
+}
+
+/// Nodes and parts of the merkle bodies reached from the block headers, and
+/// the deploys they reference. Parts are marked along with their index, as
+/// different parts can have the same hash.
+#[derive(Default)]
+struct MerkleWalk {
+    live_nodes: HashSet<Digest>,
+    live_parts: HashSet<(usize, Digest)>,
+    referenced_deploys: HashSet<DeployHash>,
+}
+
+impl MerkleWalk {
+    /// Walks the merkle linked list starting at `body_hash`, the same way
+    /// the node reads it. Returns `None` if there is no list starting there,
+    /// and otherwise whether the list and all its parts are present.
+    fn walk<T: Transaction>(
+        &mut self,
+        txn: &T,
+        dbs: &Databases,
+        body_hash: Digest,
+    ) -> Result<Option<bool>, Error> {
+        let merkle_db = match dbs.merkle {
+            Some(merkle_db) => merkle_db,
+            None => return Ok(None),
+        };
+        let mut current_node = body_hash;
+        let mut complete = true;
+        for part_idx in 0..PARTS_COUNT {
+            let raw_node = match db::get_optional(txn, merkle_db, &current_node)? {
+                Some(raw_node) => raw_node,
+                None if part_idx == 0 => return Ok(None),
+                // Incomplete bodies are expected on fast synced nodes.
+                None => return Ok(Some(false)),
+            };
+            let (part_hash, rest_hash): (Digest, Digest) = FromBytes::from_bytes(raw_node)
+                .map_err(|bytesrepr_err| Error::MerkleParsing(current_node, bytesrepr_err))?
+                .0;
+            self.live_nodes.insert(current_node);
+            let newly_reached = self.live_parts.insert((part_idx, part_hash));
+            let raw_part = match dbs.parts[part_idx] {
+                Some(part_db) => db::get_optional(txn, part_db, &part_hash)?,
+                None => None,
+            };
+            // The rest of the list is still walked past a missing part, so
+            // that its nodes aren't taken for orphans.
+            match raw_part {
+                // The first two parts of a merkle body hold the deploy and
+                // transfer hashes respectively.
+                Some(raw_part) if newly_reached && part_idx < 2 => {
+                    let deploy_hashes = Vec::<DeployHash>::from_bytes(raw_part)
+                        .map_err(|bytesrepr_err| Error::MerkleParsing(part_hash, bytesrepr_err))?
+                        .0;
+                    self.referenced_deploys.extend(deploy_hashes);
+                }
+                Some(_) => {}
+                None => complete = false,
+            }
+            current_node = rest_hash;
+        }
+        Ok(Some(complete))
+    }
+}
+
+fn scan<T: Transaction>(txn: &T, dbs: &Databases) -> Result<(OrphanReport, PurgeSet), Error> {
+    let mut report = OrphanReport::default();
+    let mut purge_set = PurgeSet::default();
+
+    // Collect all block hashes along with the body hashes they reference.
+    // Bodies are looked up by hash in both layouts, as the node does, since
+    // the hashing version switchover differs between networks.
+    info!("Scanning {} database.", BlockHeaderDatabase::db_name());
+    let mut block_hashes: HashSet<BlockHash> = HashSet::new();
+    let mut body_hashes: HashSet<Digest> = HashSet::new();
+    for (idx, (raw_key, raw_val)) in txn.open_ro_cursor(dbs.header)?.iter().enumerate() {
+        let block_hash: BlockHash = Digest::try_from(raw_key)
+            .map_err(|_| Error::InvalidKey(BlockHeaderDatabase::db_name(), idx))?
+            .into();
+        let header: BlockHeader = bincode::deserialize(raw_val)
+            .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?;
+        body_hashes.insert(*header.body_hash());
+        block_hashes.insert(block_hash);
+    }
+
+    let mut merkle_walk = MerkleWalk::default();
+    let mut legacy_bodies: HashSet<Digest> = HashSet::new();
+    if let Some(body_db) = dbs.body {
+        info!("Scanning {} database.", BlockBodyDatabase::db_name());
+        for (idx, (raw_key, raw_val)) in txn.open_ro_cursor(body_db)?.iter().enumerate() {
+            let body_hash = Digest::try_from(raw_key)
+                .map_err(|_| Error::InvalidKey(BlockBodyDatabase::db_name(), idx))?;
+            if !body_hashes.contains(&body_hash) {
+                purge_set.bodies.push(body_hash);
+                continue;
+            }
+            let body: BlockBody = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::BodyParsing(body_hash, bincode_err))?;
+            merkle_walk
+                .referenced_deploys
+                .extend(body.deploy_hashes.iter().copied());
+            merkle_walk
+                .referenced_deploys
+                .extend(body.transfer_hashes.iter().copied());
+            legacy_bodies.insert(body_hash);
+        }
+    }
+
+    info!("Scanning {} database.", BlockBodyMerkleDatabase::db_name());
+    for body_hash in body_hashes.iter() {
+        let maybe_complete = merkle_walk.walk(txn, dbs, *body_hash)?;
+        let has_legacy_body = legacy_bodies.contains(body_hash);
+        if has_legacy_body && maybe_complete.is_some() {
+            report.duplicate_bodies += 1;
+        }
+        if !has_legacy_body && maybe_complete != Some(true) {
+            report.incomplete_bodies += 1;
+        }
+    }
+    if let Some(merkle_db) = dbs.merkle {
+        for (idx, (raw_key, _raw_val)) in txn.open_ro_cursor(merkle_db)?.iter().enumerate() {
+            let node_hash = Digest::try_from(raw_key)
+                .map_err(|_| Error::InvalidKey(BlockBodyMerkleDatabase::db_name(), idx))?;
+            if !merkle_walk.live_nodes.contains(&node_hash) {
+                purge_set.merkle_nodes.push(node_hash);
+            }
+        }
+    }
+    for (part_idx, (maybe_part_db, db_name)) in dbs
+        .parts
+        .iter()
+        .zip(MerkleBody::part_db_names())
+        .enumerate()
+    {
+        let part_db = match maybe_part_db {
+            Some(part_db) => *part_db,
+            None => continue,
+        };
+        info!("Scanning {} database.", db_name);
+        for (idx, (raw_key, _raw_val)) in txn.open_ro_cursor(part_db)?.iter().enumerate() {
+            let part_hash =
+                Digest::try_from(raw_key).map_err(|_| Error::InvalidKey(db_name, idx))?;
+            if !merkle_walk.live_parts.contains(&(part_idx, part_hash)) {
+                purge_set.merkle_parts.push((part_idx, part_hash));
+            }
+        }
+    }
+
+    if report.incomplete_bodies > 0 {
+        warn!(
+            "{} block bodies are missing or incomplete, so no deploy is treated as \
+            orphaned, as they may reference it.",
+            report.incomplete_bodies
+        );
+    } else if let Some(deploys_db) = dbs.deploys {
+        info!("Scanning {} database.", DeployDatabase::db_name());
+        for (idx, (raw_key, _raw_val)) in txn.open_ro_cursor(deploys_db)?.iter().enumerate() {
+            let deploy_hash = DeployHash::new(
+                Digest::try_from(raw_key)
+                    .map_err(|_| Error::InvalidKey(DeployDatabase::db_name(), idx))?,
+            );
+            if !merkle_walk.referenced_deploys.contains(&deploy_hash) {
+                purge_set.deploys.push(deploy_hash);
+            }
+        }
+    }
+
+    if let Some(deploy_metadata_db) = dbs.deploy_metadata {
+        info!("Scanning {} database.", DeployMetadataDatabase::db_name());
+        for (idx, (raw_key, raw_val)) in txn.open_ro_cursor(deploy_metadata_db)?.iter().enumerate()
+        {
+            let deploy_hash = DeployHash::new(
+                Digest::try_from(raw_key)
+                    .map_err(|_| Error::InvalidKey(DeployMetadataDatabase::db_name(), idx))?,
+            );
+            let mut metadata: DeployMetadata = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::ExecutionResultsParsing(deploy_hash, bincode_err))?;
+            let results_before = metadata.execution_results.len();
+            metadata
+                .execution_results
+                .retain(|block_hash, _| block_hashes.contains(block_hash));
+            let orphaned_results = results_before - metadata.execution_results.len();
+            if orphaned_results > 0 {
+                report.orphan_execution_results += orphaned_results;
+                purge_set.metadata.push((deploy_hash, metadata));
+            }
+        }
+    }
+
+    report.orphan_bodies = purge_set.bodies.len();
+    report.orphan_merkle_nodes = purge_set.merkle_nodes.len();
+    report.orphan_merkle_parts = purge_set.merkle_parts.len();
+    report.orphan_deploys = purge_set.deploys.len();
+    info!(
+        "Found {} orphaned block bodies, {} orphaned merkle body nodes, \
+        {} orphaned merkle body parts, {} bodies stored in both layouts, \
+        {} missing or incomplete bodies, {} orphaned deploys and {} \
+        execution results for unknown blocks.",
+        report.orphan_bodies,
+        report.orphan_merkle_nodes,
+        report.orphan_merkle_parts,
+        report.duplicate_bodies,
+        report.incomplete_bodies,
+        report.orphan_deploys,
+        report.orphan_execution_results
+    );
+    Ok((report, purge_set))
+}
+
+/// Deletes all entries in the purge set from the database, rewriting deploy
+/// metadata entries which still hold valid execution results. Body data is
+/// only deleted if no body is missing, as it may belong to one of them.
+fn purge(
+    txn: &mut RwTransaction,
+    dbs: &Databases,
+    report: &OrphanReport,
+    purge_set: PurgeSet,
+) -> Result<(), Error> {
+    if report.incomplete_bodies > 0 {
+        warn!(
+            "{} block bodies are missing or incomplete, so no block body data \
+            is purged.",
+            report.incomplete_bodies
+        );
+    } else {
+        for (maybe_db, keys) in [
+            (dbs.body, &purge_set.bodies),
+            (dbs.merkle, &purge_set.merkle_nodes),
+        ] {
+            if let Some(db) = maybe_db {
+                for key in keys.iter() {
+                    txn.del(db, key, None)?;
+                }
+            }
+        }
+        for (part_idx, part_hash) in purge_set.merkle_parts.iter() {
+            if let Some(part_db) = dbs.parts[*part_idx] {
+                txn.del(part_db, part_hash, None)?;
+            }
+        }
+    }
+    if let Some(deploys_db) = dbs.deploys {
+        for deploy_hash in purge_set.deploys.iter() {
+            txn.del(deploys_db, deploy_hash, None)?;
+        }
+    }
+    if let Some(deploy_metadata_db) = dbs.deploy_metadata {
+        for (deploy_hash, metadata) in purge_set.metadata.iter() {
+            if metadata.execution_results.is_empty() {
+                txn.del(deploy_metadata_db, deploy_hash, None)?;
+            } else {
+                let encoded_metadata = bincode::serialize(metadata)
+                    .map_err(|bincode_err| Error::Serialization(*deploy_hash, bincode_err))?;
+                txn.put(
+                    deploy_metadata_db,
+                    deploy_hash,
+                    &encoded_metadata,
+                    WriteFlags::default(),
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Scans a storage database for orphaned entries and reports them. If `purge`
+/// is set, the orphaned entries are also deleted, in the same transaction as
+/// the scan so that nothing written in between is deleted.
+pub(crate) fn find_orphans<P: AsRef<Path>>(
+    db_path: P,
+    purge_orphans: bool,
+) -> Result<OrphanReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let dbs = Databases::open(&env)?;
+    if !purge_orphans {
+        return env.read(|txn| Ok(scan(txn, &dbs)?.0));
+    }
+    env.write(|txn| {
+        let (report, purge_set) = scan(&*txn, &dbs)?;
+        purge(txn, &dbs, &report, purge_set)?;
+        info!("Purged the orphaned entries.");
+        Ok(report)
+    })
+}
@@ -0,0 +1,429 @@
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, DeployHash, DeployMetadata};
+use lmdb::{Error as LmdbError, Transaction, WriteFlags};
+
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+            DeployDatabase, DeployHashesDatabase, DeployMetadataDatabase, ProposerDatabase,
+            TransferHashesDatabase, STORAGE_FILE_NAME,
+        },
+        merkle_body::{self, MerkleEntry},
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        orphans::scan::{find_orphans, OrphanReport},
+    },
+    test_utils::{
+        mock_block_header, mock_deploy_hash, mock_deploy_metadata, mock_merkle_body,
+        LmdbTestFixture, MockBlockHeader, KEYS,
+    },
+};
+
+fn merkle_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            ProposerDatabase::db_name(),
+            DeployDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+fn mock_body(idx: u8) -> BlockBody {
+    BlockBody::from_parts(
+        KEYS[idx as usize].clone(),
+        vec![mock_deploy_hash(idx)],
+        vec![mock_deploy_hash(idx + 100)],
+    )
+}
+
+/// Stores the header of the block at `height`, referencing the body hash
+/// `body_hash`.
+fn put_header(fixture: &LmdbTestFixture, height: u8, body_hash: Digest) {
+    let (block_hash, mut header) = mock_block_header(height);
+    header.body_hash = body_hash;
+    put(
+        fixture,
+        BlockHeaderDatabase::db_name(),
+        block_hash.as_ref(),
+        &bincode::serialize(&header).unwrap(),
+    );
+}
+
+fn put(fixture: &LmdbTestFixture, db_name: &str, key: &[u8], value: &[u8]) {
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(db_name)).unwrap(),
+        &key,
+        &value,
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+fn put_merkle_entries(fixture: &LmdbTestFixture, entries: &[MerkleEntry]) {
+    for (db_name, key, value) in entries {
+        put(fixture, db_name, key.as_ref(), value);
+    }
+}
+
+fn put_deploys(fixture: &LmdbTestFixture, deploy_hashes: &[DeployHash]) {
+    for deploy_hash in deploy_hashes {
+        put(
+            fixture,
+            DeployDatabase::db_name(),
+            deploy_hash.as_ref(),
+            &[0u8],
+        );
+    }
+}
+
+fn has_entry(fixture: &LmdbTestFixture, db_name: &str, key: &[u8]) -> bool {
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    match txn.get(*fixture.db(Some(db_name)).unwrap(), &key) {
+        Ok(_) => true,
+        Err(LmdbError::NotFound) => false,
+        Err(lmdb_err) => panic!("{}", lmdb_err),
+    }
+}
+
+#[test]
+fn orphans_should_be_found_and_purged() {
+    const BLOCK_COUNT: usize = 2;
+    const DEPLOY_COUNT: usize = 4;
+
+    let test_fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    let deploy_hashes: Vec<DeployHash> = (0..DEPLOY_COUNT as u8).map(mock_deploy_hash).collect();
+    let block_headers: Vec<(BlockHash, MockBlockHeader)> =
+        (0..BLOCK_COUNT as u8).map(mock_block_header).collect();
+    let block_bodies = [
+        BlockBody::new(vec![deploy_hashes[0]]),
+        BlockBody::new(vec![deploy_hashes[1]]),
+    ];
+    // A body which no header references.
+    let orphan_body_hash: Digest = [100u8; Digest::LENGTH].into();
+    let orphan_body = BlockBody::new(vec![deploy_hashes[3]]);
+    // A block which is not in the database.
+    let unknown_block_hash: BlockHash = Digest::from([200u8; Digest::LENGTH]).into();
+
+    let deploy_metadatas = [
+        mock_deploy_metadata(&[block_headers[0].0, unknown_block_hash]),
+        mock_deploy_metadata(&[block_headers[1].0]),
+        mock_deploy_metadata(&[unknown_block_hash]),
+    ];
+
+    {
+        let mut txn = test_fixture.env.begin_rw_txn().unwrap();
+        for i in 0..BLOCK_COUNT {
+            txn.put(
+                *test_fixture
+                    .db(Some(BlockHeaderDatabase::db_name()))
+                    .unwrap(),
+                &block_headers[i].0,
+                &bincode::serialize(&block_headers[i].1).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+            txn.put(
+                *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+                &block_headers[i].1.body_hash,
+                &bincode::serialize(&block_bodies[i]).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.put(
+            *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &orphan_body_hash,
+            &bincode::serialize(&orphan_body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        for deploy_hash in deploy_hashes.iter() {
+            txn.put(
+                *test_fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+                deploy_hash,
+                &[0u8],
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        for (i, deploy_metadata) in deploy_metadatas.iter().enumerate() {
+            txn.put(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[i],
+                &bincode::serialize(deploy_metadata).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    }
+
+    let expected_report = OrphanReport {
+        orphan_bodies: 1,
+        orphan_merkle_nodes: 0,
+        orphan_merkle_parts: 0,
+        duplicate_bodies: 0,
+        incomplete_bodies: 0,
+        orphan_deploys: 2,
+        orphan_execution_results: 2,
+    };
+    // A run without purging shouldn't modify the database.
+    assert_eq!(
+        find_orphans(test_fixture.tmp_dir.path(), false).unwrap(),
+        expected_report
+    );
+    assert_eq!(
+        find_orphans(test_fixture.tmp_dir.path(), true).unwrap(),
+        expected_report
+    );
+    // After purging, there should be nothing left to find.
+    assert_eq!(
+        find_orphans(test_fixture.tmp_dir.path(), false).unwrap(),
+        OrphanReport::default()
+    );
+
+    {
+        let txn = test_fixture.env.begin_ro_txn().unwrap();
+        assert_eq!(
+            txn.get(
+                *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+                &orphan_body_hash
+            )
+            .unwrap_err(),
+            LmdbError::NotFound
+        );
+        for deploy_hash in &deploy_hashes[..2] {
+            assert!(txn
+                .get(
+                    *test_fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+                    deploy_hash
+                )
+                .is_ok());
+        }
+        for deploy_hash in &deploy_hashes[2..] {
+            assert_eq!(
+                txn.get(
+                    *test_fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+                    deploy_hash
+                )
+                .unwrap_err(),
+                LmdbError::NotFound
+            );
+        }
+
+        let deploy_metadata: DeployMetadata = bincode::deserialize(
+            txn.get(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[0],
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!(deploy_metadata
+            .execution_results
+            .contains_key(&block_headers[0].0));
+        assert!(!deploy_metadata
+            .execution_results
+            .contains_key(&unknown_block_hash));
+        assert_eq!(
+            txn.get(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[2]
+            )
+            .unwrap_err(),
+            LmdbError::NotFound
+        );
+        txn.commit().unwrap();
+    }
+}
+
+#[test]
+fn orphans_empty_db() {
+    let test_fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    assert_eq!(
+        find_orphans(test_fixture.tmp_dir.path(), true).unwrap(),
+        OrphanReport::default()
+    );
+}
+
+#[test]
+fn orphans_should_look_bodies_up_in_both_layouts() {
+    let fixture = merkle_fixture();
+    // Block 0 has its body in the merkle layout only, under a header of the
+    // legacy hashing version, as on networks which switched to merkle bodies
+    // before merkle headers.
+    let (merkle_body_hash, merkle_entries) = mock_merkle_body(&mock_body(0));
+    put_header(&fixture, 0, merkle_body_hash);
+    put_merkle_entries(&fixture, &merkle_entries);
+    // Block 1 has its body in both layouts.
+    let duplicate_body_hash: Digest = [1u8; Digest::LENGTH].into();
+    put_header(&fixture, 1, duplicate_body_hash);
+    put(
+        &fixture,
+        BlockBodyDatabase::db_name(),
+        duplicate_body_hash.as_ref(),
+        &bincode::serialize(&mock_body(1)).unwrap(),
+    );
+    let duplicate_entries =
+        merkle_body::merkle_entries(&mock_body(1), duplicate_body_hash).unwrap();
+    put_merkle_entries(&fixture, &duplicate_entries);
+    // A merkle body and a legacy body which no header references.
+    let (_orphan_body_hash, orphan_entries) = mock_merkle_body(&mock_body(2));
+    put_merkle_entries(&fixture, &orphan_entries);
+    let orphan_body_hash: Digest = [3u8; Digest::LENGTH].into();
+    put(
+        &fixture,
+        BlockBodyDatabase::db_name(),
+        orphan_body_hash.as_ref(),
+        &bincode::serialize(&mock_body(3)).unwrap(),
+    );
+    let deploy_hashes: Vec<DeployHash> = [0, 100, 1, 101, 2, 102]
+        .into_iter()
+        .map(mock_deploy_hash)
+        .collect();
+    put_deploys(&fixture, &deploy_hashes);
+
+    let expected_report = OrphanReport {
+        orphan_bodies: 1,
+        orphan_merkle_nodes: 3,
+        orphan_merkle_parts: 3,
+        duplicate_bodies: 1,
+        incomplete_bodies: 0,
+        orphan_deploys: 2,
+        orphan_execution_results: 0,
+    };
+    assert_eq!(
+        find_orphans(fixture.tmp_dir.path(), true).unwrap(),
+        expected_report
+    );
+    // Duplicates are kept, as the node reads whichever it finds.
+    assert_eq!(
+        find_orphans(fixture.tmp_dir.path(), false).unwrap(),
+        OrphanReport {
+            duplicate_bodies: 1,
+            ..Default::default()
+        }
+    );
+
+    for (db_name, key, _value) in merkle_entries.iter().chain(duplicate_entries.iter()) {
+        assert!(has_entry(&fixture, db_name, key.as_ref()));
+    }
+    assert!(has_entry(
+        &fixture,
+        BlockBodyDatabase::db_name(),
+        duplicate_body_hash.as_ref()
+    ));
+    assert!(!has_entry(
+        &fixture,
+        BlockBodyDatabase::db_name(),
+        orphan_body_hash.as_ref()
+    ));
+    for (db_name, key, _value) in orphan_entries.iter() {
+        assert!(!has_entry(&fixture, db_name, key.as_ref()));
+    }
+    for deploy_hash in &deploy_hashes[..4] {
+        assert!(has_entry(
+            &fixture,
+            DeployDatabase::db_name(),
+            deploy_hash.as_ref()
+        ));
+    }
+    for deploy_hash in &deploy_hashes[4..] {
+        assert!(!has_entry(
+            &fixture,
+            DeployDatabase::db_name(),
+            deploy_hash.as_ref()
+        ));
+    }
+}
+
+#[test]
+fn orphans_should_not_purge_body_data_while_bodies_are_missing() {
+    let fixture = merkle_fixture();
+    // Block 0 has a merkle body without its transfer hashes.
+    let (body_hash, entries) = mock_merkle_body(&mock_body(0));
+    put_header(&fixture, 0, body_hash);
+    put_merkle_entries(
+        &fixture,
+        &entries
+            .into_iter()
+            .filter(|(db_name, _key, _value)| *db_name != TransferHashesDatabase::db_name())
+            .collect::<Vec<_>>(),
+    );
+    // Block 1 has no body at all.
+    put_header(&fixture, 1, [1u8; Digest::LENGTH].into());
+    // Body data no header references, which may still belong to block 1 if
+    // its header were rewritten by a node catching up.
+    let (_orphan_body_hash, orphan_entries) = mock_merkle_body(&mock_body(2));
+    put_merkle_entries(&fixture, &orphan_entries);
+    let orphan_body_hash: Digest = [3u8; Digest::LENGTH].into();
+    put(
+        &fixture,
+        BlockBodyDatabase::db_name(),
+        orphan_body_hash.as_ref(),
+        &bincode::serialize(&mock_body(3)).unwrap(),
+    );
+    // The transfer of block 0, which no complete body references.
+    let deploy_hashes = [mock_deploy_hash(0), mock_deploy_hash(100)];
+    put_deploys(&fixture, &deploy_hashes);
+
+    let expected_report = OrphanReport {
+        orphan_bodies: 1,
+        orphan_merkle_nodes: 3,
+        orphan_merkle_parts: 3,
+        incomplete_bodies: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        find_orphans(fixture.tmp_dir.path(), true).unwrap(),
+        expected_report
+    );
+    // Nothing was purged.
+    assert_eq!(
+        find_orphans(fixture.tmp_dir.path(), false).unwrap(),
+        expected_report
+    );
+    for (db_name, key, _value) in orphan_entries.iter() {
+        assert!(has_entry(&fixture, db_name, key.as_ref()));
+    }
+    assert!(has_entry(
+        &fixture,
+        BlockBodyDatabase::db_name(),
+        orphan_body_hash.as_ref()
+    ));
+    for deploy_hash in deploy_hashes.iter() {
+        assert!(has_entry(
+            &fixture,
+            DeployDatabase::db_name(),
+            deploy_hash.as_ref()
+        ));
+    }
+}
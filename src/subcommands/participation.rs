@@ -0,0 +1,174 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use casper_node::types::BlockHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+use report::Format;
+
+pub const COMMAND_NAME: &str = "participation";
+const DB_PATH: &str = "db-path";
+const FORMAT: &str = "format";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const THRESHOLD: &str = "threshold";
+
+/// Errors encountered when building the participation report.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on entry at index in the block header database.
+    #[error("Error parsing block header at index {0}: {1}")]
+    HeaderParsing(usize, BincodeError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Parsing error on the signatures of a block.
+    #[error("Error parsing signatures of block {0}: {1}")]
+    SignaturesParsing(BlockHash, BincodeError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Threshold,
+    Format,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs, for each era in a storage database, the share of the \
+            blocks of the era each of its validators signed, flagging the \
+            validators which signed less than a threshold.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(THRESHOLD)
+                .display_order(DisplayOrder::Threshold as usize)
+                .short('t')
+                .long(THRESHOLD)
+                .takes_value(true)
+                .value_name("PERCENT")
+                .default_value("90")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(percent) if percent > 100 => {
+                        Err("threshold must be at most 100".to_string())
+                    }
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Flag the validators which signed less than PERCENT of \
+                    the blocks of an era.",
+                ),
+        )
+        .arg(
+            Arg::new(FORMAT)
+                .display_order(DisplayOrder::Format as usize)
+                .short('f')
+                .long(FORMAT)
+                .takes_value(true)
+                .value_name("json|csv")
+                .possible_values(["json", "csv"])
+                .default_value("json")
+                .help(
+                    "Format of the output. The CSV output has a row per \
+                    validator of each era, without the totals.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let threshold = matches
+        .value_of(THRESHOLD)
+        .expect("should have threshold arg")
+        .parse()
+        .expect("should be a valid percentage");
+    let format = match matches.value_of(FORMAT) {
+        Some("csv") => Format::Csv,
+        _ => Format::Json,
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::participation_report(db_dir.path(), threshold)?;
+    report::write_report(&report, format, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
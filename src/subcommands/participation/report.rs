@@ -0,0 +1,221 @@
+use std::{collections::BTreeMap, io::Write, path::Path, result::Result};
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{AsymmetricType, EraId, PublicKey, U512};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, BlockMetadataDatabase, Database, StorageEnv},
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::purge_signatures::block_signatures::BlockSignatures,
+};
+
+use super::Error;
+
+/// Header of the CSV output, in the order `ValidatorParticipation::write_csv`
+/// writes the fields, after the era.
+const CSV_HEADER: &str = "era_id,public_key,weight,signed_blocks,blocks,participation,\
+    below_threshold";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Csv,
+}
+
+/// The blocks of an era signed by one of its validators.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct ValidatorParticipation {
+    pub(crate) public_key: PublicKey,
+    /// Weight of the validator in the era, if known.
+    pub(crate) weight: Option<U512>,
+    pub(crate) signed_blocks: u64,
+    /// Share of the blocks of the era signed by the validator, between 0 and
+    /// 1.
+    pub(crate) participation: f64,
+    pub(crate) below_threshold: bool,
+}
+
+impl ValidatorParticipation {
+    fn write_csv<W: Write + ?Sized>(
+        &self,
+        writer: &mut W,
+        era_id: EraId,
+        blocks: usize,
+    ) -> Result<(), Error> {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{}",
+            era_id.value(),
+            self.public_key.to_hex(),
+            self.weight
+                .map(|weight| weight.to_string())
+                .unwrap_or_default(),
+            self.signed_blocks,
+            blocks,
+            self.participation,
+            self.below_threshold
+        )?;
+        Ok(())
+    }
+}
+
+/// Signature participation of the validators of an era.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct EraParticipation {
+    pub(crate) era_id: EraId,
+    /// Number of blocks of the era in the database.
+    pub(crate) blocks: usize,
+    /// Number of these blocks which have signatures in the database.
+    pub(crate) blocks_with_signatures: usize,
+    /// Whether the validators were read from the preceding switch block.
+    /// Otherwise, the validators are the signers found in the era.
+    pub(crate) weights_known: bool,
+    /// Signatures by keys which aren't validators of the era.
+    pub(crate) unknown_signatures: u64,
+    pub(crate) validators: Vec<ValidatorParticipation>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ParticipationReport {
+    /// Participation percentage under which validators are flagged.
+    pub(crate) threshold: u64,
+    /// Number of validators flagged over all eras.
+    pub(crate) flagged: usize,
+    pub(crate) eras: Vec<EraParticipation>,
+}
+
+/// Computes, for each era of the storage database at `db_path`, the share of
+/// the blocks of the era each of its validators signed, flagging the ones
+/// which signed less than `threshold` percent of them.
+pub(crate) fn participation_report<P: AsRef<Path>>(
+    db_path: P,
+    threshold: u64,
+) -> Result<ParticipationReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+
+    let mut header_count = 0;
+    let mut era_blocks: BTreeMap<EraId, Vec<BlockHash>> = BTreeMap::new();
+    // Weights of the validators of each era, from the switch block of the
+    // era before it.
+    let mut era_weights: BTreeMap<EraId, BTreeMap<PublicKey, U512>> = BTreeMap::new();
+    // The headers are scanned in batches, so that the scan survives a live
+    // node growing the map.
+    env.scan::<BlockHeaderDatabase, _, _>(|_raw_key, raw_val| -> Result<(), Error> {
+        let header: BlockHeader = bincode::deserialize(raw_val)
+            .map_err(|bincode_err| Error::HeaderParsing(header_count, bincode_err))?;
+        header_count += 1;
+        era_blocks
+            .entry(header.era_id())
+            .or_default()
+            .push(header.hash());
+        if let Some(weights) = header.next_era_validator_weights() {
+            era_weights.insert(header.era_id().successor(), weights.clone());
+        }
+        Ok(())
+    })?;
+
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+
+    let mut report = ParticipationReport {
+        threshold,
+        ..Default::default()
+    };
+    for (era_id, block_hashes) in era_blocks {
+        let maybe_weights = era_weights.remove(&era_id);
+        if maybe_weights.is_none() {
+            warn!(
+                "Switch block before era {} not found, using the signers as its validators.",
+                era_id
+            );
+        }
+        let mut signed_blocks: BTreeMap<PublicKey, u64> = maybe_weights
+            .iter()
+            .flat_map(|weights| weights.keys())
+            .map(|public_key| (public_key.clone(), 0))
+            .collect();
+        let mut era = EraParticipation {
+            era_id,
+            blocks: block_hashes.len(),
+            blocks_with_signatures: 0,
+            weights_known: maybe_weights.is_some(),
+            unknown_signatures: 0,
+            validators: vec![],
+        };
+        for block_hash in block_hashes {
+            let raw_signatures =
+                match reader.get(BlockMetadataDatabase::db_name(), block_hash.as_ref())? {
+                    Some(raw_signatures) => raw_signatures,
+                    None => continue,
+                };
+            let signatures: BlockSignatures = bincode::deserialize(&raw_signatures)
+                .map_err(|bincode_err| Error::SignaturesParsing(block_hash, bincode_err))?;
+            era.blocks_with_signatures += 1;
+            for public_key in signatures.proofs.into_keys() {
+                match signed_blocks.get_mut(&public_key) {
+                    Some(count) => *count += 1,
+                    None if maybe_weights.is_some() => era.unknown_signatures += 1,
+                    None => {
+                        signed_blocks.insert(public_key, 1);
+                    }
+                }
+            }
+        }
+        era.validators = signed_blocks
+            .into_iter()
+            .map(|(public_key, signed_blocks)| {
+                let participation = signed_blocks as f64 / era.blocks as f64;
+                ValidatorParticipation {
+                    weight: maybe_weights
+                        .as_ref()
+                        .and_then(|weights| weights.get(&public_key).copied()),
+                    public_key,
+                    signed_blocks,
+                    participation,
+                    below_threshold: participation * 100.0 < threshold as f64,
+                }
+            })
+            .collect();
+        report.flagged += era
+            .validators
+            .iter()
+            .filter(|validator| validator.below_threshold)
+            .count();
+        report.eras.push(era);
+    }
+    info!(
+        "Computed the participation over {} eras, {} validators below {}%.",
+        report.eras.len(),
+        report.flagged,
+        threshold
+    );
+    Ok(report)
+}
+
+/// Writes `report` to `writer` in `format`. The CSV output has a row per
+/// validator of each era.
+pub(crate) fn write_report<W: Write + ?Sized>(
+    report: &ParticipationReport,
+    format: Format,
+    writer: &mut W,
+) -> Result<(), Error> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *writer, report)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => {
+            writeln!(writer, "{}", CSV_HEADER)?;
+            for era in report.eras.iter() {
+                for validator in era.validators.iter() {
+                    validator.write_csv(writer, era.era_id, era.blocks)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
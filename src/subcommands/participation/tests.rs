@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{AsymmetricType, EraId, PublicKey, Signature};
+use lmdb::{Transaction, WriteFlags};
+use serde::Serialize;
+
+use super::report::{participation_report, write_report, Format};
+use crate::{
+    common::db::{BlockHeaderDatabase, BlockMetadataDatabase, Database, STORAGE_FILE_NAME},
+    subcommands::purge_signatures::block_signatures::BlockSignatures,
+    test_utils::{mock_block_header, mock_switch_block_header, LmdbTestFixture, KEYS},
+};
+
+// Stores `mock_header` as a `BlockHeader` and returns its hash.
+fn put_header<H: Serialize>(fixture: &LmdbTestFixture, mock_header: &H) -> BlockHash {
+    let header: BlockHeader =
+        bincode::deserialize(&bincode::serialize(mock_header).unwrap()).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &header.hash(),
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    header.hash()
+}
+
+fn put_signatures(
+    fixture: &LmdbTestFixture,
+    block_hash: BlockHash,
+    era_id: EraId,
+    signers: &[&PublicKey],
+) {
+    let block_signatures = BlockSignatures {
+        block_hash,
+        era_id,
+        proofs: signers
+            .iter()
+            .map(|public_key| ((*public_key).clone(), Signature::System))
+            .collect::<BTreeMap<_, _>>(),
+    };
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&block_signatures).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+#[test]
+fn participation_report_should_flag_validators_below_threshold() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    // The switch block of era 0 sets the weights of the validators 0, 1 and
+    // 2 for era 1.
+    let (_, mut switch_block_header) = mock_switch_block_header(0);
+    for key in KEYS.iter().take(3) {
+        switch_block_header.insert_key_weight(key.clone(), 100.into());
+    }
+    let switch_block_hash = put_header(&fixture, &switch_block_header);
+    put_signatures(&fixture, switch_block_hash, EraId::new(0), &[&KEYS[0]]);
+    // Of the 4 blocks of era 1, validator 0 signed 3, validator 1 signed 2,
+    // validator 2 none, and the last block has no signatures.
+    let signers: [&[&PublicKey]; 3] = [
+        &[&KEYS[0], &KEYS[1]],
+        &[&KEYS[0], &KEYS[1]],
+        &[&KEYS[0], &KEYS[4]],
+    ];
+    for idx in 1..=4u8 {
+        let (_, mut block_header) = mock_block_header(idx);
+        block_header.era_id = EraId::new(1);
+        block_header.height = idx.into();
+        let block_hash = put_header(&fixture, &block_header);
+        if let Some(block_signers) = signers.get(usize::from(idx) - 1) {
+            put_signatures(&fixture, block_hash, EraId::new(1), block_signers);
+        }
+    }
+
+    let report = participation_report(fixture.tmp_dir.path(), 50).unwrap();
+    assert_eq!(report.threshold, 50);
+    assert_eq!(report.flagged, 1);
+    assert_eq!(report.eras.len(), 2);
+
+    // No switch block precedes era 0, so its signers are its validators.
+    let genesis_era = &report.eras[0];
+    assert!(!genesis_era.weights_known);
+    assert_eq!(genesis_era.blocks, 1);
+    assert_eq!(genesis_era.validators.len(), 1);
+    assert_eq!(genesis_era.validators[0].public_key, KEYS[0]);
+    assert_eq!(genesis_era.validators[0].weight, None);
+    assert_eq!(genesis_era.validators[0].participation, 1.0);
+
+    let era = &report.eras[1];
+    assert!(era.weights_known);
+    assert_eq!(era.blocks, 4);
+    assert_eq!(era.blocks_with_signatures, 3);
+    assert_eq!(era.unknown_signatures, 1);
+    let participation: BTreeMap<PublicKey, (u64, bool)> = era
+        .validators
+        .iter()
+        .map(|validator| {
+            assert_eq!(validator.weight, Some(100.into()));
+            (
+                validator.public_key.clone(),
+                (validator.signed_blocks, validator.below_threshold),
+            )
+        })
+        .collect();
+    assert_eq!(
+        participation,
+        BTreeMap::from([
+            (KEYS[0].clone(), (3, false)),
+            (KEYS[1].clone(), (2, false)),
+            (KEYS[2].clone(), (0, true)),
+        ])
+    );
+
+    let mut csv = vec![];
+    write_report(&report, Format::Csv, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert!(lines[0].starts_with("era_id,public_key,"));
+    assert!(lines.iter().any(|line| line.starts_with("1,")
+        && line.contains(&KEYS[1].to_hex())
+        && line.ends_with(",100,2,4,0.5,false")));
+}
@@ -1,6 +1,6 @@
 pub(crate) mod block_signatures;
-mod purge;
-mod signatures;
+pub(crate) mod purge;
+pub(crate) mod signatures;
 #[cfg(test)]
 mod tests;
 
@@ -13,6 +13,8 @@ use clap::{Arg, ArgMatches, Command};
 use lmdb::Error as LmdbError;
 use thiserror::Error as ThisError;
 
+use crate::common::db::{self, EnvTuning};
+
 pub const COMMAND_NAME: &str = "purge-signatures";
 const DB_PATH: &str = "db-path";
 const NO_FINALITY: &str = "no-finality";
@@ -47,6 +49,7 @@ enum DisplayOrder {
     DbPath,
     WeakFinality,
     NoFinality,
+    EnvTuning,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -93,6 +96,10 @@ pub fn command(display_order: usize) -> Command<'static> {
                     all signatures will be stripped.",
                 ),
         )
+        .args(db::tuning_args(
+            DisplayOrder::EnvTuning as usize,
+            EnvTuning::RANDOM_ACCESS,
+        ))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
@@ -121,5 +128,10 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
         })
         .map(|list| list.collect())
         .unwrap_or_default();
-    purge::purge_signatures(path, weak_finality_block_list, no_finality_block_list)
+    purge::purge_signatures(
+        path,
+        weak_finality_block_list,
+        no_finality_block_list,
+        EnvTuning::from_matches(matches),
+    )
 }
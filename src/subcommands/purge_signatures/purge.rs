@@ -1,6 +1,12 @@
 use std::{
     collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    num::NonZeroUsize,
     path::Path,
+    sync::{
+        mpsc::{self, Receiver},
+        Mutex,
+    },
+    thread,
 };
 
 use casper_hashing::Digest;
@@ -10,9 +16,11 @@ use lmdb::{Cursor, Database, Environment, Error as LmdbError, Transaction, Write
 use log::{error, info, warn};
 
 use crate::common::{
-    db::{self, BlockHeaderDatabase, BlockMetadataDatabase, Database as _, STORAGE_FILE_NAME},
+    cache::LruCache,
+    db::{BlockHeaderDatabase, BlockMetadataDatabase, Database as _, EnvTuning, StorageEnv},
     lmdb_utils,
     progress::ProgressTracker,
+    vacuum,
 };
 
 use super::{block_signatures::BlockSignatures, signatures::strip_signatures, Error};
@@ -27,6 +35,8 @@ pub(crate) struct Indices {
     pub(crate) switch_blocks: BTreeMap<EraId, BlockHash>,
     /// Hold the heights of switch blocks before upgrades.
     pub(crate) switch_blocks_before_upgrade: BTreeSet<u64>,
+    /// Cache of the switch block headers used to look up era weights.
+    pub(crate) header_cache: Mutex<LruCache<BlockHash, BlockHeader>>,
 }
 
 /// Cache-like structure to store the validator weights for an era.
@@ -59,10 +69,16 @@ impl EraWeights {
             .switch_blocks
             .get(&era_id)
             .ok_or_else(|| Error::MissingEraWeights(era_id))?;
-        // Deserialize it.
-        let switch_block_header: BlockHeader =
-            bincode::deserialize(txn.get(db, &switch_block_hash)?)
-                .map_err(|bincode_err| Error::HeaderParsing(*switch_block_hash, bincode_err))?;
+        // Deserialize it, unless it was already cached.
+        let switch_block_header = indices
+            .header_cache
+            .lock()
+            .expect("poisoned lock")
+            .get_or_try_insert_with(switch_block_hash, || {
+                bincode::deserialize::<BlockHeader>(txn.get(db, &switch_block_hash)?)
+                    .map_err(|bincode_err| Error::HeaderParsing(*switch_block_hash, bincode_err))
+            })?
+            .clone();
         // Check if this switch block is the last in the era before an upgrade.
         self.era_after_upgrade = indices
             .switch_blocks_before_upgrade
@@ -77,6 +93,10 @@ impl EraWeights {
         Ok(self.era_after_upgrade)
     }
 
+    pub(crate) fn weights(&self) -> &BTreeMap<PublicKey, U512> {
+        &self.weights
+    }
+
     #[cfg(test)]
     pub(crate) fn era_id(&self) -> EraId {
         self.era_id
@@ -176,75 +196,76 @@ pub(crate) fn initialize_indices(
     Ok(indices)
 }
 
-/// Purges finality signatures from a database for all blocks of heights found
-/// in `heights_to_visit`.
-///
-/// If the `full_purge` flag is set, all the signatures for the associated
-/// block will be purged by deleting the record in the block signatures
-/// database.
+/// Number of signature updates applied in a single write transaction.
+const WRITE_BATCH_SIZE: usize = 10_000;
+/// Upper bound on the number of threads computing signature updates, kept
+/// well below the default limit of LMDB reader slots.
+const MAX_WORKERS: usize = 16;
+
+/// Change to the signatures database entry of a block.
+enum SignaturesUpdate {
+    /// Delete the entry, purging all the signatures of the block.
+    Delete(BlockHash),
+    /// Overwrite the entry with the given serialized signatures.
+    Overwrite(BlockHash, Vec<u8>),
+}
+
+/// Groups the heights in `heights_to_visit` by the era of their block.
 ///
-/// If the `full_purge` flag is not set, signatures will be purged until the
-/// remaining set of signatures gives the block weak but not strict finality.
-/// If this is not possible for that block given its signature set and the era
-/// weights, it is skipped and a message is logged.
-pub(crate) fn purge_signatures_for_blocks(
-    env: &Environment,
+/// Heights of blocks which are not in the database or are in the genesis
+/// era are logged and skipped. Returns the grouped heights along with the
+/// number of skipped heights.
+pub(crate) fn group_heights_by_era(
     indices: &Indices,
     heights_to_visit: BTreeSet<u64>,
-    full_purge: bool,
-) -> Result<(), Error> {
-    let mut txn = env.begin_rw_txn()?;
-    let header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name()))? };
-    let signatures_db = unsafe { txn.open_db(Some(BlockMetadataDatabase::db_name()))? };
-
-    let mut era_weights = EraWeights::default();
-
-    let mut progress_tracker = ProgressTracker::new(
-        heights_to_visit.len(),
-        Box::new(if full_purge {
-            |completion| {
-                info!(
-                    "Signature purging to no finality {}% complete...",
-                    completion
-                )
-            }
-        } else {
-            |completion| {
-                info!(
-                    "Signature purging to weak finality {}% complete...",
-                    completion
-                )
-            }
-        }),
-    )
-    .map_err(|_| Error::EmptyBlockList)?;
-
+) -> (BTreeMap<EraId, Vec<u64>>, usize) {
+    let mut heights_by_era: BTreeMap<EraId, Vec<u64>> = BTreeMap::new();
+    let mut skipped = 0;
     for height in heights_to_visit {
-        // Get the block hash and header from the indices for this height.
-        let (block_hash, block_header) = match indices.heights.get(&height) {
-            Some((block_hash, block_header)) => {
-                // We don't strip signatures for the genesis block.
-                if block_header.era_id().is_genesis() {
-                    warn!("Cannot strip signatures for genesis block");
-                    progress_tracker.advance_by(1);
-                    continue;
-                }
-                (block_hash, block_header)
+        match indices.heights.get(&height) {
+            // We don't strip signatures for the genesis block.
+            Some((_, block_header)) if block_header.era_id().is_genesis() => {
+                warn!("Cannot strip signatures for genesis block");
+                skipped += 1;
             }
+            Some((_, block_header)) => heights_by_era
+                .entry(block_header.era_id())
+                .or_default()
+                .push(height),
             None => {
                 // Skip blocks which are not in the database.
                 warn!("Block at height {height} is not present in the database");
-                progress_tracker.advance_by(1);
-                continue;
+                skipped += 1;
             }
-        };
-        let block_height = block_header.height();
-        let era_id = block_header.era_id();
-        // Make sure we have the correct era weights for this block before
-        // trying to strip any signatures.
-        let era_after_upgrade =
-            era_weights.refresh_weights_for_era(&txn, header_db, indices, era_id)?;
+        }
+    }
+    (heights_by_era, skipped)
+}
+
+/// Computes the updates to the signatures database for the blocks at
+/// `heights`, which must all be part of era `era_id`.
+fn era_updates(
+    env: &Environment,
+    header_db: Database,
+    signatures_db: Database,
+    indices: &Indices,
+    era_id: EraId,
+    heights: &[u64],
+    full_purge: bool,
+) -> Result<Vec<SignaturesUpdate>, Error> {
+    let txn = env.begin_ro_txn()?;
+    let mut era_weights = EraWeights::default();
+    // Make sure we have the correct era weights for this era before trying
+    // to strip any signatures.
+    let era_after_upgrade =
+        era_weights.refresh_weights_for_era(&txn, header_db, indices, era_id)?;
 
+    let mut updates = Vec::with_capacity(heights.len());
+    for (block_hash, block_header) in heights
+        .iter()
+        .filter_map(|height| indices.heights.get(height))
+    {
+        let block_height = block_header.height();
         let mut block_signatures: BlockSignatures = match txn.get(signatures_db, &block_hash) {
             Ok(raw_signatures) => bincode::deserialize(raw_signatures)
                 .map_err(|bincode_err| Error::SignaturesParsing(*block_hash, bincode_err))?,
@@ -254,7 +275,6 @@ pub(crate) fn purge_signatures_for_blocks(
                     "No signature entry in the database for block \
                     {block_hash} at height {block_height}"
                 );
-                progress_tracker.advance_by(1);
                 continue;
             }
             Err(lmdb_err) => return Err(Error::Database(lmdb_err)),
@@ -262,7 +282,7 @@ pub(crate) fn purge_signatures_for_blocks(
 
         if full_purge {
             // Delete the record completely from the database.
-            txn.del(signatures_db, &block_hash, None)?;
+            updates.push(SignaturesUpdate::Delete(*block_hash));
         } else if strip_signatures(&mut block_signatures, &era_weights.weights) {
             if era_after_upgrade {
                 warn!(
@@ -270,42 +290,168 @@ pub(crate) fn purge_signatures_for_blocks(
                     for block {block_hash} at height {block_height}"
                 );
             }
-            // Serialize the remaining signatures and overwrite the database
+            // Serialize the remaining signatures to overwrite the database
             // entry.
             let serialized_signatures = bincode::serialize(&block_signatures)
                 .map_err(|bincode_err| Error::Serialize(*block_hash, bincode_err))?;
-            txn.put(
-                signatures_db,
-                &block_hash,
-                &serialized_signatures,
-                WriteFlags::default(),
-            )?;
+            updates.push(SignaturesUpdate::Overwrite(
+                *block_hash,
+                serialized_signatures,
+            ));
         } else {
             warn!("Couldn't strip signatures for block {block_hash} at height {block_height}");
         }
-        progress_tracker.advance_by(1);
+    }
+    txn.commit()?;
+    Ok(updates)
+}
+
+/// Applies the updates received from the workers to the signatures
+/// database, committing every `WRITE_BATCH_SIZE` updates.
+fn apply_updates(
+    env: &Environment,
+    signatures_db: Database,
+    receiver: Receiver<(usize, Result<Vec<SignaturesUpdate>, Error>)>,
+    progress_tracker: &mut ProgressTracker,
+) -> Result<(), Error> {
+    let mut txn = env.begin_rw_txn()?;
+    let mut pending_updates = 0;
+    for (visited_heights, era_result) in receiver {
+        for update in era_result? {
+            match update {
+                SignaturesUpdate::Delete(block_hash) => {
+                    txn.del(signatures_db, &block_hash, None)?
+                }
+                SignaturesUpdate::Overwrite(block_hash, serialized_signatures) => txn.put(
+                    signatures_db,
+                    &block_hash,
+                    &serialized_signatures,
+                    WriteFlags::default(),
+                )?,
+            }
+            pending_updates += 1;
+            if pending_updates == WRITE_BATCH_SIZE {
+                txn.commit()?;
+                txn = env.begin_rw_txn()?;
+                pending_updates = 0;
+            }
+        }
+        progress_tracker.advance_by(visited_heights);
     }
     txn.commit()?;
     Ok(())
 }
 
+/// Purges finality signatures from a database for all blocks of heights found
+/// in `heights_to_visit`.
+///
+/// If the `full_purge` flag is set, all the signatures for the associated
+/// block will be purged by deleting the record in the block signatures
+/// database.
+///
+/// If the `full_purge` flag is not set, signatures will be purged until the
+/// remaining set of signatures gives the block weak but not strict finality.
+/// If this is not possible for that block given its signature set and the era
+/// weights, it is skipped and a message is logged.
+///
+/// Heights are grouped by era and the updates for each era are computed in
+/// parallel by read-only workers, while the writes are applied in batches of
+/// `WRITE_BATCH_SIZE` updates. If an error is encountered, the batches
+/// already committed are kept.
+pub(crate) fn purge_signatures_for_blocks(
+    env: &Environment,
+    indices: &Indices,
+    heights_to_visit: BTreeSet<u64>,
+    full_purge: bool,
+) -> Result<(), Error> {
+    let header_db = env.open_db(Some(BlockHeaderDatabase::db_name()))?;
+    let signatures_db = env.open_db(Some(BlockMetadataDatabase::db_name()))?;
+
+    let mut progress_tracker = ProgressTracker::new(
+        heights_to_visit.len(),
+        Box::new(if full_purge {
+            |completion| {
+                info!(
+                    "Signature purging to no finality {}% complete...",
+                    completion
+                )
+            }
+        } else {
+            |completion| {
+                info!(
+                    "Signature purging to weak finality {}% complete...",
+                    completion
+                )
+            }
+        }),
+    )
+    .map_err(|_| Error::EmptyBlockList)?;
+
+    let (heights_by_era, skipped) = group_heights_by_era(indices, heights_to_visit);
+    progress_tracker.advance_by(skipped);
+
+    let worker_count = thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+        .min(MAX_WORKERS)
+        .min(heights_by_era.len());
+    let eras = Mutex::new(heights_by_era.into_iter());
+    let (sender, receiver) = mpsc::sync_channel(worker_count * 2);
+    thread::scope(|scope| {
+        for _ in 0..worker_count {
+            let sender = sender.clone();
+            let eras = &eras;
+            scope.spawn(move || loop {
+                let (era_id, heights) = match eras.lock().expect("poisoned lock").next() {
+                    Some(era) => era,
+                    None => break,
+                };
+                let era_result = era_updates(
+                    env,
+                    header_db,
+                    signatures_db,
+                    indices,
+                    era_id,
+                    &heights,
+                    full_purge,
+                );
+                let failed = era_result.is_err();
+                // Stop if the writer is gone or there was an error.
+                if sender.send((heights.len(), era_result)).is_err() || failed {
+                    break;
+                }
+            });
+        }
+        // Drop our own sender so the channel closes once all workers are done.
+        drop(sender);
+        apply_updates(env, signatures_db, receiver, &mut progress_tracker)
+    })
+}
+
 pub fn purge_signatures<P: AsRef<Path>>(
     db_path: P,
     weak_finality_block_list: BTreeSet<u64>,
     no_finality_block_list: BTreeSet<u64>,
+    tuning: EnvTuning,
 ) -> Result<(), Error> {
-    let storage_path = db_path.as_ref().join(STORAGE_FILE_NAME);
-    let env = db::db_env(storage_path)?;
+    let env = StorageEnv::open_with_tuning(&db_path, tuning)?;
     let heights_to_visit = weak_finality_block_list
         .union(&no_finality_block_list)
         .copied()
         .collect();
     let indices = initialize_indices(&env, &heights_to_visit)?;
+    let pages_before = vacuum::page_counts(&env, &[BlockMetadataDatabase::db_name()])?;
     if !weak_finality_block_list.is_empty() {
         purge_signatures_for_blocks(&env, &indices, weak_finality_block_list, false)?;
     }
     if !no_finality_block_list.is_empty() {
         purge_signatures_for_blocks(&env, &indices, no_finality_block_list, true)?;
     }
+    indices
+        .header_cache
+        .lock()
+        .expect("poisoned lock")
+        .log_stats("Switch block header");
+    vacuum::vacuum_report(&env, pages_before)?.log();
     Ok(())
 }
@@ -6,7 +6,7 @@ use super::block_signatures::BlockSignatures;
 
 // Returns whether the cumulative `weight` exceeds the weak finality threshold
 // for a `total` weight.
-fn is_weak_finality(weight: U512, total: U512) -> bool {
+pub(crate) fn is_weak_finality(weight: U512, total: U512) -> bool {
     weight * 3 > total
 }
 
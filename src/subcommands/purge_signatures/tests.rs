@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use casper_node::types::BlockHash;
 use casper_types::{ProtocolVersion, Signature, U512};
@@ -7,7 +7,9 @@ use lmdb::{Error as LmdbError, Transaction, WriteFlags};
 use crate::{
     subcommands::purge_signatures::{
         block_signatures::BlockSignatures,
-        purge::{initialize_indices, purge_signatures_for_blocks, EraWeights},
+        purge::{
+            group_heights_by_era, initialize_indices, purge_signatures_for_blocks, EraWeights,
+        },
         Error,
     },
     test_utils::{self, LmdbTestFixture, MockBlockHeader, MockSwitchBlockHeader, KEYS},
@@ -137,6 +139,50 @@ fn indices_initialization() {
     }
 }
 
+#[test]
+fn heights_grouped_by_era() {
+    const BLOCK_COUNT: usize = 4;
+
+    let fixture = LmdbTestFixture::new(vec!["block_header"], None);
+    // Create mock block headers, one of them in the genesis era.
+    let mut block_headers: Vec<(BlockHash, MockBlockHeader)> = (0..BLOCK_COUNT as u8)
+        .map(test_utils::mock_block_header)
+        .collect();
+    block_headers[0].1.era_id = 0.into();
+    block_headers[0].1.height = 0;
+    block_headers[1].1.era_id = 10.into();
+    block_headers[1].1.height = 100;
+    block_headers[2].1.era_id = 10.into();
+    block_headers[2].1.height = 200;
+    block_headers[3].1.era_id = 20.into();
+    block_headers[3].1.height = 300;
+
+    let env = &fixture.env;
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        for (block_hash, block_header) in block_headers.iter() {
+            txn.put(
+                *fixture.db(Some("block_header")).unwrap(),
+                block_hash,
+                &bincode::serialize(block_header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    };
+
+    let heights = BTreeSet::from([0, 100, 200, 300, 400]);
+    let indices = initialize_indices(env, &heights).unwrap();
+    let (heights_by_era, skipped) = group_heights_by_era(&indices, heights);
+    // The genesis block and the block which isn't in the database are
+    // skipped.
+    assert_eq!(skipped, 2);
+    assert_eq!(
+        heights_by_era,
+        BTreeMap::from([(10.into(), vec![100, 200]), (20.into(), vec![300])])
+    );
+}
+
 #[test]
 fn indices_initialization_with_upgrade() {
     const BLOCK_COUNT: usize = 4;
@@ -355,6 +401,8 @@ fn era_weights() {
         .unwrap();
         txn.commit().unwrap();
     };
+    // The header was cached by the previous lookups, so drop it.
+    indices.header_cache.lock().unwrap().clear();
     if let Ok(txn) = env.begin_ro_txn() {
         let db = env.open_db(Some("block_header")).unwrap();
         let expected_missing_era_id = switch_block_headers[0].1.era_id.successor();
@@ -11,13 +11,25 @@ use clap::{Arg, ArgMatches, Command};
 use lmdb::Error as LmdbError;
 use thiserror::Error as ThisError;
 
+use crate::{
+    common::{
+        block_iter::Error as BlockIterError, merkle_body::Error as MerkleBodyError, switch_blocks,
+    },
+    subcommands::remove_era::Error as RemoveBlocksError,
+};
+
 pub const COMMAND_NAME: &str = "remove-block";
 const BLOCK_HASH: &str = "block-hash";
 const DB_PATH: &str = "db-path";
+pub(crate) const DRY_RUN: &str = "dry-run";
+const KEEP_HEADER: &str = "keep-header";
 
 /// Errors encountered when operating on the storage database.
 #[derive(Debug, ThisError)]
 pub enum Error {
+    /// Error reading the block headers.
+    #[error("Error reading blocks: {0}")]
+    Block(#[from] BlockIterError),
     /// Parsing error on entry in the block body database.
     #[error("Error parsing block body for block with hash {0}: {1}")]
     BodyParsing(BlockHash, BincodeError),
@@ -30,28 +42,36 @@ pub enum Error {
     /// Parsing error on entry in the block header database.
     #[error("Error parsing block header with hash {0}: {1}")]
     HeaderParsing(BlockHash, BincodeError),
+    /// Error reading the merkle body of a block.
+    #[error("Error reading merkle body of block {0}: {1}")]
+    MerkleBody(BlockHash, MerkleBodyError),
     /// Missing entry in the deploy metadata database.
     #[error("Deploy with hash {0} not present in the database")]
     MissingDeploy(DeployHash),
     /// Missing entry in the block header database.
     #[error("Block header for block hash {0} not present in the database")]
     MissingHeader(BlockHash),
-    /// Serialization error on entry in the deploy metadata database.
-    #[error("Error serializing execution results for deploy {0}: {1}")]
-    Serialization(DeployHash, BincodeError),
+    /// Error removing the block.
+    #[error("Error removing block: {0}")]
+    RemoveBlocks(#[from] RemoveBlocksError),
 }
 
 enum DisplayOrder {
     DbPath,
     BlockHash,
+    KeepHeader,
+    AllowSwitchBlockRemoval,
+    DryRun,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
     Command::new(COMMAND_NAME)
         .display_order(display_order)
         .about(
-            "Removes the block header, body and execution results for a given \
-            block hash from a storage database.",
+            "Removes the block header, body, execution results, transfers \
+            and finality signatures for a given block hash from a storage \
+            database, along with the deploys no longer executed in any \
+            block. The body is kept if another block references it.",
         )
         .arg(
             Arg::new(DB_PATH)
@@ -72,6 +92,35 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .value_name("BLOCK_HASH")
                 .help("Hash of the block to be removed."),
         )
+        .arg(
+            Arg::new(KEEP_HEADER)
+                .display_order(DisplayOrder::KeepHeader as usize)
+                .required(false)
+                .long(KEEP_HEADER)
+                .takes_value(false)
+                .help(
+                    "Keep the block header and its finality signatures, only \
+                    removing the body, deploys and execution results, so that \
+                    the database can still answer header queries for this \
+                    block.",
+                ),
+        )
+        .arg(switch_blocks::allow_removal_arg(
+            DisplayOrder::AllowSwitchBlockRemoval as usize,
+        ))
+        .arg(
+            Arg::new(DRY_RUN)
+                .display_order(DisplayOrder::DryRun as usize)
+                .required(false)
+                .long(DRY_RUN)
+                .takes_value(false)
+                .help(
+                    "Only report which deploys of the block would have their \
+                    execution results deleted, and which are shared with other \
+                    blocks and would only have them trimmed, without changing \
+                    the database.",
+                ),
+        )
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
@@ -84,5 +133,12 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
                 .into()
         })
         .expect("should have block-hash arg");
-    remove::remove_block(path, block_hash)
+    remove::remove_block(
+        path,
+        block_hash,
+        matches.is_present(KEEP_HEADER),
+        switch_blocks::is_removal_allowed(matches),
+        matches.is_present(DRY_RUN),
+    )
+    .map(|_report| ())
 }
@@ -1,89 +1,198 @@
 use std::path::Path;
 
-use casper_node::types::{BlockHash, BlockHeader, DeployMetadata};
-use lmdb::{Error as LmdbError, Transaction, WriteFlags};
-use log::warn;
+use casper_node::types::{BlockHash, BlockHeader, DeployHash, DeployMetadata};
+use lmdb::{Error as LmdbError, Transaction};
+use log::info;
 
 use crate::{
-    common::db::{
-        self, BlockBodyDatabase, BlockHeaderDatabase, Database, DeployMetadataDatabase,
-        STORAGE_FILE_NAME,
+    common::{
+        block_iter::BlockIndex,
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, BlockMetadataDatabase,
+            Database as _, DeployDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase,
+            StorageEnv, TransferDatabase,
+        },
+        merkle_body,
+        storage::LmdbReader,
+        vacuum,
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        remove_era::remove::{self, BlockDbs, RemovalReport as BlocksRemovalReport, Retention},
     },
-    subcommands::execution_results_summary::block_body::BlockBody,
 };
 
 use super::Error;
 
-pub(crate) fn remove_block<P: AsRef<Path>>(db_path: P, block_hash: BlockHash) -> Result<(), Error> {
-    let storage_path = db_path.as_ref().join(STORAGE_FILE_NAME);
-    let env = db::db_env(storage_path)?;
+/// What removing a block does to the execution results of its deploys.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct RemovalReport {
+    /// Deploys only executed in the block, whose execution results entry is
+    /// deleted along with their finalized approvals.
+    pub(crate) deleted_deploys: Vec<DeployHash>,
+    /// Deploys also executed in other blocks, listed along with these blocks,
+    /// whose execution results entry only loses the result of the block.
+    pub(crate) shared_deploys: Vec<(DeployHash, Vec<BlockHash>)>,
+}
 
-    let mut txn = env.begin_rw_txn()?;
-    let header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name()))? };
-    let body_db = unsafe { txn.open_db(Some(BlockBodyDatabase::db_name()))? };
-    let deploy_metadata_db = unsafe { txn.open_db(Some(DeployMetadataDatabase::db_name()))? };
+impl RemovalReport {
+    /// Logs the deploys shared with other blocks and the counts of deploys.
+    fn log(&self, block_hash: &BlockHash) {
+        for (deploy_hash, other_blocks) in &self.shared_deploys {
+            let other_blocks: Vec<String> = other_blocks
+                .iter()
+                .map(|other_block| other_block.to_string())
+                .collect();
+            info!(
+                "Deploy {} is also executed in blocks [{}], only its execution \
+                result in block {} is removed.",
+                deploy_hash,
+                other_blocks.join(", "),
+                block_hash
+            );
+        }
+        info!(
+            "Block {} has {} deploys only executed in it, whose execution results \
+            are deleted, and {} deploys shared with other blocks, whose execution \
+            results are trimmed.",
+            block_hash,
+            self.deleted_deploys.len(),
+            self.shared_deploys.len()
+        );
+    }
+}
 
-    let header: BlockHeader = match txn.get(header_db, &block_hash) {
-        Ok(raw_header) => bincode::deserialize(raw_header)
-            .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?,
-        Err(LmdbError::NotFound) => {
-            return Err(Error::MissingHeader(block_hash));
+/// Reads the execution result of each deploy and transfer of the block
+/// `block_hash` with `body`, reporting which are only executed in it.
+fn deploy_report<T: Transaction>(
+    txn: &T,
+    dbs: &BlockDbs,
+    block_hash: &BlockHash,
+    body: &BlockBody,
+) -> Result<RemovalReport, Error> {
+    let mut report = RemovalReport::default();
+    for deploy_hash in body.deploy_hashes.iter().chain(body.transfer_hashes.iter()) {
+        // Get this deploy's metadata.
+        let maybe_raw_metadata = dbs
+            .deploy_metadata
+            .map(|deploy_metadata_db| txn.get(deploy_metadata_db, deploy_hash));
+        let mut metadata: DeployMetadata = match maybe_raw_metadata {
+            Some(Ok(raw_metadata)) => {
+                bincode::deserialize(raw_metadata).map_err(|bincode_err| {
+                    Error::ExecutionResultsParsing(*block_hash, *deploy_hash, bincode_err)
+                })?
+            }
+            Some(Err(LmdbError::NotFound)) | None => {
+                return Err(Error::MissingDeploy(*deploy_hash))
+            }
+            Some(Err(lmdb_error)) => return Err(lmdb_error.into()),
+        };
+        // Extract the execution result of this deploy for the current block.
+        if metadata.execution_results.remove(block_hash).is_none() {
+            continue;
         }
-        Err(lmdb_err) => {
-            return Err(lmdb_err.into());
+        if metadata.execution_results.is_empty() {
+            report.deleted_deploys.push(*deploy_hash);
+        } else {
+            report.shared_deploys.push((
+                *deploy_hash,
+                metadata.execution_results.keys().copied().collect(),
+            ));
         }
-    };
+    }
+    Ok(report)
+}
+
+/// Removes the block `block_hash` along with its body, execution results,
+/// transfers and signatures, keeping its header and signatures if
+/// `keep_header` is set. The deploys and finalized approvals of the deploys
+/// left without execution results are removed as well, and the body is kept
+/// if another block references it. The removal is the one `remove-era` does,
+/// so it fails if the block is a switch block whose header would be removed,
+/// unless `allow_switch_block_removal` is set. The deploys of the block are
+/// reported before anything is changed, and nothing is if `dry_run` is set.
+pub(crate) fn remove_block<P: AsRef<Path>>(
+    db_path: P,
+    block_hash: BlockHash,
+    keep_header: bool,
+    allow_switch_block_removal: bool,
+    dry_run: bool,
+) -> Result<RemovalReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let pages_before = vacuum::page_counts(
+        &env,
+        &[
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
+            TransferDatabase::db_name(),
+        ],
+    )?;
 
-    let maybe_body: Option<BlockBody> = match txn.get(body_db, header.body_hash()) {
-        Ok(raw_body) => Some(
-            bincode::deserialize(raw_body)
-                .map_err(|bincode_err| Error::BodyParsing(block_hash, bincode_err))?,
-        ),
+    let dbs = BlockDbs::open(&env)?;
+    let mut txn = env.begin_rw_txn()?;
+    let header: BlockHeader = match txn.get(dbs.header, &block_hash) {
+        Ok(raw_header) => bincode::deserialize(raw_header)
+            .map_err(|bincode_err| Error::HeaderParsing(block_hash, bincode_err))?,
         Err(LmdbError::NotFound) => {
-            warn!(
-                "No block body found for block header with hash {}",
-                block_hash
-            );
-            None
+            return Err(Error::MissingHeader(block_hash));
         }
         Err(lmdb_err) => {
             return Err(lmdb_err.into());
         }
     };
 
-    if let Some(body) = maybe_body {
-        // Go through all the deploys in this block and get the execution
-        // result of each one.
-        for deploy_hash in body.deploy_hashes() {
-            // Get this deploy's metadata.
-            let mut metadata: DeployMetadata = match txn.get(deploy_metadata_db, deploy_hash) {
-                Ok(raw_metadata) => bincode::deserialize(raw_metadata).map_err(|bincode_err| {
-                    Error::ExecutionResultsParsing(block_hash, *deploy_hash, bincode_err)
-                })?,
-                Err(LmdbError::NotFound) => return Err(Error::MissingDeploy(*deploy_hash)),
-                Err(lmdb_error) => return Err(lmdb_error.into()),
-            };
-            // Extract the execution result of this deploy for the current block.
-            if let Some(_execution_result) = metadata.execution_results.remove(&block_hash) {
-                if metadata.execution_results.is_empty() {
-                    txn.del(deploy_metadata_db, deploy_hash, None)?;
-                } else {
-                    let encoded_metadata = bincode::serialize(&metadata)
-                        .map_err(|bincode_err| Error::Serialization(*deploy_hash, bincode_err))?;
-                    txn.put(
-                        deploy_metadata_db,
-                        deploy_hash,
-                        &encoded_metadata,
-                        WriteFlags::default(),
-                    )?;
-                }
+    // The body is either stored as a whole in the legacy database or as a
+    // linked list in the merkle database, looked up by hash in both.
+    let maybe_body: Option<BlockBody> =
+        match dbs.body.map(|body_db| txn.get(body_db, header.body_hash())) {
+            Some(Ok(raw_body)) => Some(
+                bincode::deserialize(raw_body)
+                    .map_err(|bincode_err| Error::BodyParsing(block_hash, bincode_err))?,
+            ),
+            Some(Err(LmdbError::NotFound)) | None => {
+                merkle_body::read_merkle_body(&LmdbReader::new(&txn), header.body_hash())
+                    .map_err(|merkle_body_err| Error::MerkleBody(block_hash, merkle_body_err))?
+                    .map(|merkle_body| merkle_body.body)
             }
-        }
+            Some(Err(lmdb_err)) => {
+                return Err(lmdb_err.into());
+            }
+        };
 
-        txn.del(body_db, header.body_hash(), None)?;
-    }
+    // Go through all the deploys and transfers in this block and get the
+    // execution result of each one, before changing anything.
+    let report = match &maybe_body {
+        Some(body) => deploy_report(&txn, &dbs, &block_hash, body)?,
+        None => RemovalReport::default(),
+    };
+    report.log(&block_hash);
 
-    txn.del(header_db, &block_hash, None)?;
+    let retention = if keep_header {
+        Retention::HeadersAndSignatures
+    } else {
+        Retention::Nothing
+    };
+    let mut index = BlockIndex::new(&LmdbReader::new(&txn))?;
+    remove::remove_block(
+        &mut txn,
+        &dbs,
+        &mut index,
+        &block_hash,
+        &header,
+        retention,
+        allow_switch_block_removal,
+        &mut BlocksRemovalReport::default(),
+    )?;
+    if dry_run {
+        info!("Dry run, leaving the database unchanged.");
+        return Ok(report);
+    }
     txn.commit()?;
-    Ok(())
+    vacuum::vacuum_report(&env, pages_before)?.log();
+    Ok(report)
 }
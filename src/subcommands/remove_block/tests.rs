@@ -4,15 +4,22 @@ use casper_node::types::{BlockHash, DeployHash, DeployMetadata};
 use lmdb::{Error as LmdbError, Transaction, WriteFlags};
 
 use crate::{
-    common::db::{
-        BlockBodyDatabase, BlockHeaderDatabase, Database, DeployMetadataDatabase, STORAGE_FILE_NAME,
+    common::{
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+            DeployHashesDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase,
+            ProposerDatabase, TransferHashesDatabase, STORAGE_FILE_NAME,
+        },
+        switch_blocks::Error as SwitchBlockError,
     },
     subcommands::{
         execution_results_summary::block_body::BlockBody,
         remove_block::{remove::remove_block, Error},
+        remove_era::Error as RemoveBlocksError,
     },
     test_utils::{
-        mock_block_header, mock_deploy_hash, mock_deploy_metadata, LmdbTestFixture, MockBlockHeader,
+        mock_block_header, mock_deploy_hash, mock_deploy_metadata, mock_merkle_body,
+        mock_switch_block_header, LmdbTestFixture, MockBlockHeader, KEYS,
     },
 };
 
@@ -26,6 +33,7 @@ fn remove_block_should_work() {
             BlockHeaderDatabase::db_name(),
             BlockBodyDatabase::db_name(),
             DeployMetadataDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
         ],
         Some(STORAGE_FILE_NAME),
     );
@@ -40,7 +48,7 @@ fn remove_block_should_work() {
     block_bodies.push(BlockBody::new(vec![deploy_hashes[1], deploy_hashes[2]]));
     block_body_deploy_map.push(vec![1, 2]);
 
-    let deploy_metadatas = vec![
+    let deploy_metadatas = [
         mock_deploy_metadata(slice::from_ref(&block_headers[0].0)),
         mock_deploy_metadata(&[block_headers[0].0, block_headers[1].0]),
         mock_deploy_metadata(slice::from_ref(&block_headers[1].0)),
@@ -81,11 +89,64 @@ fn remove_block_should_work() {
                 WriteFlags::empty(),
             )
             .unwrap();
+            // Mock finalized approvals.
+            txn.put(
+                *test_fixture
+                    .db(Some(FinalizedApprovalsDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[i],
+                &[i as u8],
+                WriteFlags::empty(),
+            )
+            .unwrap();
         }
         txn.commit().unwrap();
     };
 
-    assert!(remove_block(test_fixture.tmp_dir.path(), block_headers[0].0).is_ok());
+    // A dry run reports which deploys are shared with other blocks, without
+    // changing anything.
+    let dry_run_report = remove_block(
+        test_fixture.tmp_dir.path(),
+        block_headers[0].0,
+        false,
+        false,
+        true,
+    )
+    .unwrap();
+    assert_eq!(dry_run_report.deleted_deploys, vec![deploy_hashes[0]]);
+    assert_eq!(
+        dry_run_report.shared_deploys,
+        vec![(deploy_hashes[1], vec![block_headers[1].0])]
+    );
+    {
+        let txn = test_fixture.env.begin_ro_txn().unwrap();
+        assert!(txn
+            .get(
+                *test_fixture
+                    .db(Some(BlockHeaderDatabase::db_name()))
+                    .unwrap(),
+                &block_headers[0].0,
+            )
+            .is_ok());
+        assert!(txn
+            .get(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[0],
+            )
+            .is_ok());
+    }
+
+    let report = remove_block(
+        test_fixture.tmp_dir.path(),
+        block_headers[0].0,
+        false,
+        false,
+        false,
+    )
+    .unwrap();
+    assert_eq!(report, dry_run_report);
 
     {
         let txn = test_fixture.env.begin_ro_txn().unwrap();
@@ -133,6 +194,26 @@ fn remove_block_should_work() {
             .unwrap_err(),
             LmdbError::NotFound
         );
+        // Deploy 0 was only executed in the removed block, so its finalized
+        // approvals are removed along with its metadata.
+        assert_eq!(
+            txn.get(
+                *test_fixture
+                    .db(Some(FinalizedApprovalsDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[0]
+            )
+            .unwrap_err(),
+            LmdbError::NotFound
+        );
+        assert!(txn
+            .get(
+                *test_fixture
+                    .db(Some(FinalizedApprovalsDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hashes[1],
+            )
+            .is_ok());
 
         let deploy_metadata: DeployMetadata = bincode::deserialize(
             txn.get(
@@ -195,7 +276,7 @@ fn remove_block_no_deploys() {
     block_bodies.push(BlockBody::new(vec![deploy_hashes[1], deploy_hashes[2]]));
     block_body_deploy_map.push(vec![1, 2]);
 
-    let deploy_metadatas = vec![
+    let deploy_metadatas = [
         mock_deploy_metadata(&[]),
         mock_deploy_metadata(slice::from_ref(&block_headers[1].0)),
         mock_deploy_metadata(slice::from_ref(&block_headers[1].0)),
@@ -241,7 +322,14 @@ fn remove_block_no_deploys() {
         txn.commit().unwrap();
     };
 
-    assert!(remove_block(test_fixture.tmp_dir.path(), block_headers[0].0).is_ok());
+    assert!(remove_block(
+        test_fixture.tmp_dir.path(),
+        block_headers[0].0,
+        false,
+        false,
+        false
+    )
+    .is_ok());
 
     {
         let txn = test_fixture.env.begin_ro_txn().unwrap();
@@ -340,7 +428,7 @@ fn remove_block_missing_header() {
 
     let (block_hash, _block_header) = mock_block_header(0);
     assert!(
-        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash).unwrap_err(), Error::MissingHeader(actual_block_hash) if block_hash == actual_block_hash)
+        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).unwrap_err(), Error::MissingHeader(actual_block_hash) if block_hash == actual_block_hash)
     );
 }
 
@@ -368,7 +456,7 @@ fn remove_block_missing_body() {
     block_bodies.push(BlockBody::new(vec![deploy_hashes[1], deploy_hashes[2]]));
     block_body_deploy_map.push(vec![1, 2]);
 
-    let deploy_metadatas = vec![
+    let deploy_metadatas = [
         mock_deploy_metadata(slice::from_ref(&block_headers[0].0)),
         mock_deploy_metadata(&[block_headers[0].0, block_headers[1].0]),
         mock_deploy_metadata(slice::from_ref(&block_headers[1].0)),
@@ -405,7 +493,14 @@ fn remove_block_missing_body() {
         txn.commit().unwrap();
     };
 
-    assert!(remove_block(test_fixture.tmp_dir.path(), block_headers[0].0).is_ok());
+    assert!(remove_block(
+        test_fixture.tmp_dir.path(),
+        block_headers[0].0,
+        false,
+        false,
+        false
+    )
+    .is_ok());
 
     {
         let txn = test_fixture.env.begin_ro_txn().unwrap();
@@ -490,7 +585,7 @@ fn remove_block_missing_deploys() {
     };
 
     assert!(
-        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash).unwrap_err(), Error::MissingDeploy(actual_deploy_hash) if deploy_hash == actual_deploy_hash)
+        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).unwrap_err(), Error::MissingDeploy(actual_deploy_hash) if deploy_hash == actual_deploy_hash)
     );
 }
 
@@ -526,7 +621,7 @@ fn remove_block_invalid_header() {
     };
 
     assert!(
-        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash).unwrap_err(), Error::HeaderParsing(actual_block_hash, _) if block_hash == actual_block_hash)
+        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).unwrap_err(), Error::HeaderParsing(actual_block_hash, _) if block_hash == actual_block_hash)
     );
 }
 
@@ -570,7 +665,7 @@ fn remove_block_invalid_body() {
     };
 
     assert!(
-        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash).unwrap_err(), Error::BodyParsing(actual_block_hash, _) if block_hash == actual_block_hash)
+        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).unwrap_err(), Error::BodyParsing(actual_block_hash, _) if block_hash == actual_block_hash)
     );
 }
 
@@ -626,6 +721,247 @@ fn remove_block_invalid_deploy_metadata() {
     };
 
     assert!(
-        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash).unwrap_err(), Error::ExecutionResultsParsing(actual_block_hash, actual_deploy_hash, _) if block_hash == actual_block_hash && deploy_hash == actual_deploy_hash)
+        matches!(remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).unwrap_err(), Error::ExecutionResultsParsing(actual_block_hash, actual_deploy_hash, _) if block_hash == actual_block_hash && deploy_hash == actual_deploy_hash)
+    );
+}
+
+#[test]
+fn remove_block_should_remove_merkle_body_root() {
+    let test_fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            ProposerDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    let deploy_hash = mock_deploy_hash(0);
+    let (block_hash, mut block_header) = mock_block_header(0);
+    let (body_hash, merkle_entries) = mock_merkle_body(&BlockBody::new(vec![deploy_hash]));
+    block_header.body_hash = body_hash;
+
+    {
+        let mut txn = test_fixture.env.begin_rw_txn().unwrap();
+        txn.put(
+            *test_fixture
+                .db(Some(BlockHeaderDatabase::db_name()))
+                .unwrap(),
+            &block_hash,
+            &bincode::serialize(&block_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        for (db_name, key, value) in merkle_entries.iter() {
+            txn.put(
+                *test_fixture.db(Some(db_name)).unwrap(),
+                key,
+                value,
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.put(
+            *test_fixture
+                .db(Some(DeployMetadataDatabase::db_name()))
+                .unwrap(),
+            &deploy_hash,
+            &bincode::serialize(&mock_deploy_metadata(slice::from_ref(&block_hash))).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    };
+
+    assert!(remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).is_ok());
+
+    {
+        let txn = test_fixture.env.begin_ro_txn().unwrap();
+        // The execution results of the deploys in the merkle body are removed.
+        assert_eq!(
+            txn.get(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &deploy_hash
+            )
+            .unwrap_err(),
+            LmdbError::NotFound
+        );
+        // Only the root of the merkle body is removed, the rest may be shared
+        // with other bodies.
+        for (db_name, key, _) in merkle_entries.iter() {
+            let result = txn.get(*test_fixture.db(Some(db_name)).unwrap(), key);
+            if *key == body_hash {
+                assert_eq!(result.unwrap_err(), LmdbError::NotFound);
+            } else {
+                assert!(result.is_ok());
+            }
+        }
+        txn.commit().unwrap();
+    };
+}
+
+#[test]
+fn remove_block_should_remove_transfers_and_keep_shared_body() {
+    let test_fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    // Both blocks reference the same body, with a deploy and a transfer only
+    // executed in block 0.
+    let (block_hash, block_header) = mock_block_header(0);
+    let (other_block_hash, mut other_block_header) = mock_block_header(1);
+    other_block_header.body_hash = block_header.body_hash;
+    let deploy_hash = mock_deploy_hash(0);
+    let transfer_hash = mock_deploy_hash(1);
+    let block_body = BlockBody::from_parts(KEYS[0].clone(), vec![deploy_hash], vec![transfer_hash]);
+
+    {
+        let mut txn = test_fixture.env.begin_rw_txn().unwrap();
+        for (hash, header) in [
+            (block_hash, &block_header),
+            (other_block_hash, &other_block_header),
+        ] {
+            txn.put(
+                *test_fixture
+                    .db(Some(BlockHeaderDatabase::db_name()))
+                    .unwrap(),
+                &hash,
+                &bincode::serialize(header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.put(
+            *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &block_header.body_hash,
+            &bincode::serialize(&block_body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        for hash in [deploy_hash, transfer_hash] {
+            txn.put(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &hash,
+                &bincode::serialize(&mock_deploy_metadata(slice::from_ref(&block_hash))).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+    };
+
+    let report =
+        remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, false).unwrap();
+    assert_eq!(report.deleted_deploys, vec![deploy_hash, transfer_hash]);
+    assert!(report.shared_deploys.is_empty());
+
+    let txn = test_fixture.env.begin_ro_txn().unwrap();
+    // The execution results of the transfer are removed along with the ones
+    // of the deploy.
+    for hash in [deploy_hash, transfer_hash] {
+        assert_eq!(
+            txn.get(
+                *test_fixture
+                    .db(Some(DeployMetadataDatabase::db_name()))
+                    .unwrap(),
+                &hash
+            )
+            .unwrap_err(),
+            LmdbError::NotFound
+        );
+    }
+    // Block 1 still references the body.
+    assert!(txn
+        .get(
+            *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &block_header.body_hash,
+        )
+        .is_ok());
+}
+
+#[test]
+fn remove_block_should_protect_switch_blocks() {
+    let test_fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    let (block_hash, block_header) = mock_switch_block_header(0);
+    {
+        let mut txn = test_fixture.env.begin_rw_txn().unwrap();
+        txn.put(
+            *test_fixture
+                .db(Some(BlockHeaderDatabase::db_name()))
+                .unwrap(),
+            &block_hash,
+            &bincode::serialize(&block_header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &block_header.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    };
+
+    // Neither a dry run nor a removal of the header goes through without the
+    // override.
+    for dry_run in [true, false] {
+        assert!(matches!(
+            remove_block(test_fixture.tmp_dir.path(), block_hash, false, false, dry_run),
+            Err(Error::RemoveBlocks(RemoveBlocksError::SwitchBlock(
+                SwitchBlockError::Protected { block_hash: protected_hash, .. }
+            ))) if protected_hash == block_hash
+        ));
+    }
+    {
+        let txn = test_fixture.env.begin_ro_txn().unwrap();
+        assert!(txn
+            .get(
+                *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+                &block_header.body_hash,
+            )
+            .is_ok());
+    }
+
+    // Keeping the header keeps the era end, so only the body is removed.
+    assert!(remove_block(test_fixture.tmp_dir.path(), block_hash, true, false, false).is_ok());
+    let txn = test_fixture.env.begin_ro_txn().unwrap();
+    assert!(txn
+        .get(
+            *test_fixture
+                .db(Some(BlockHeaderDatabase::db_name()))
+                .unwrap(),
+            &block_hash,
+        )
+        .is_ok());
+    assert_eq!(
+        txn.get(
+            *test_fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &block_header.body_hash,
+        )
+        .unwrap_err(),
+        LmdbError::NotFound
     );
 }
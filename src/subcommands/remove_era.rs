@@ -0,0 +1,163 @@
+pub(crate) mod remove;
+#[cfg(test)]
+mod tests;
+
+use std::path::Path;
+
+use bincode::Error as BincodeError;
+use casper_node::types::{BlockHash, DeployHash};
+use casper_types::EraId;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    merkle_body::Error as MerkleBodyError,
+    switch_blocks::{self, Error as SwitchBlockError},
+};
+use remove::Retention;
+
+pub const COMMAND_NAME: &str = "remove-era";
+const DB_PATH: &str = "db-path";
+const ERA_ID: &str = "era-id";
+const KEEP_HEADERS: &str = "keep-headers";
+const KEEP_SIGNATURES: &str = "keep-signatures";
+const KEEP_SWITCH_BLOCK: &str = "keep-switch-block";
+
+/// Errors encountered when removing an era from the storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading the block headers.
+    #[error("Error reading blocks: {0}")]
+    Block(#[from] BlockIterError),
+    /// Parsing error on entry in the block body database.
+    #[error("Error parsing block body for block with hash {0}: {1}")]
+    BodyParsing(BlockHash, BincodeError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// No block of the era is in the database.
+    #[error("No blocks of era {0} found in the database")]
+    EmptyEra(EraId),
+    /// Parsing error on entry in the deploy metadata database.
+    #[error("Error parsing execution results for block with hash {0} at deploy {1}: {2}")]
+    ExecutionResultsParsing(BlockHash, DeployHash, BincodeError),
+    /// Error reading the merkle body of a block.
+    #[error("Error reading merkle body of block {0}: {1}")]
+    MerkleBody(BlockHash, MerkleBodyError),
+    /// Serialization error on entry in the deploy metadata database.
+    #[error("Error serializing execution results for deploy {0}: {1}")]
+    Serialization(DeployHash, BincodeError),
+    /// A switch block would be removed.
+    #[error(transparent)]
+    SwitchBlock(#[from] SwitchBlockError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    EraId,
+    KeepSwitchBlock,
+    KeepHeaders,
+    KeepSignatures,
+    AllowSwitchBlockRemoval,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Removes the blocks of a given era from a storage database, along \
+            with their deploys, execution results, transfers and finality \
+            signatures. The switch block of the era is only removed with \
+            `--allow-switch-block-removal`, unless its header is kept.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(ERA_ID)
+                .display_order(DisplayOrder::EraId as usize)
+                .required(true)
+                .short('e')
+                .long(ERA_ID)
+                .takes_value(true)
+                .value_name("ERA_ID")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help("Era whose blocks should be removed."),
+        )
+        .arg(
+            Arg::new(KEEP_SWITCH_BLOCK)
+                .display_order(DisplayOrder::KeepSwitchBlock as usize)
+                .required(false)
+                .short('k')
+                .long(KEEP_SWITCH_BLOCK)
+                .takes_value(false)
+                .help(
+                    "Keep the switch block of the era, along with everything \
+                    it references, so that the validator weights of the next \
+                    era remain available.",
+                ),
+        )
+        .arg(
+            Arg::new(KEEP_HEADERS)
+                .display_order(DisplayOrder::KeepHeaders as usize)
+                .required(false)
+                .long(KEEP_HEADERS)
+                .takes_value(false)
+                .help(
+                    "Keep the headers of the removed blocks, leaving a light \
+                    archival database which can still answer header queries. \
+                    Reports needing bodies or execution results then require \
+                    `--partial`.",
+                ),
+        )
+        .arg(
+            Arg::new(KEEP_SIGNATURES)
+                .display_order(DisplayOrder::KeepSignatures as usize)
+                .required(false)
+                .long(KEEP_SIGNATURES)
+                .takes_value(false)
+                .requires(KEEP_HEADERS)
+                .help(
+                    "Keep the finality signatures of the removed blocks along with their headers.",
+                ),
+        )
+        .arg(switch_blocks::allow_removal_arg(
+            DisplayOrder::AllowSwitchBlockRemoval as usize,
+        ))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let era_id: EraId = matches
+        .value_of(ERA_ID)
+        .expect("should have era-id arg")
+        .parse::<u64>()
+        .expect("should be validated")
+        .into();
+    let keep_switch_block = matches.is_present(KEEP_SWITCH_BLOCK);
+    let retention = match (
+        matches.is_present(KEEP_HEADERS),
+        matches.is_present(KEEP_SIGNATURES),
+    ) {
+        (false, _) => Retention::Nothing,
+        (true, false) => Retention::Headers,
+        (true, true) => Retention::HeadersAndSignatures,
+    };
+    remove::remove_era(
+        path,
+        era_id,
+        keep_switch_block,
+        retention,
+        switch_blocks::is_removal_allowed(matches),
+    )
+    .map(|_| ())
+}
@@ -0,0 +1,307 @@
+use std::{path::Path, result::Result};
+
+use casper_node::types::{BlockHash, BlockHeader, DeployHash, DeployMetadata};
+use casper_types::EraId;
+use lmdb::{Database, Error as LmdbError, RwTransaction, Transaction, WriteFlags};
+use log::{info, warn};
+
+use crate::{
+    common::{
+        block_iter::BlockIndex,
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, BlockMetadataDatabase,
+            DeployDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase, StorageEnv,
+            TransferDatabase,
+        },
+        merkle_body,
+        storage::LmdbReader,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+/// Counts of the entries removed along with a set of blocks.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct RemovalReport {
+    pub(crate) blocks: usize,
+    /// Deploys which were no longer executed in any remaining block.
+    pub(crate) deploys: usize,
+    pub(crate) execution_results: usize,
+    pub(crate) signatures: usize,
+    pub(crate) transfers: usize,
+    /// Headers kept in the database although their blocks were removed.
+    pub(crate) headers_kept: usize,
+}
+
+/// What is kept of the blocks being removed. Keeping the headers leaves a
+/// light archival database, which can still answer header queries and serve
+/// sync hints, but lacks the bodies, deploys and execution results of these
+/// blocks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Retention {
+    /// Nothing is kept.
+    Nothing,
+    /// The headers are kept.
+    Headers,
+    /// The headers and the finality signatures are kept.
+    HeadersAndSignatures,
+}
+
+impl Retention {
+    fn keeps_headers(self) -> bool {
+        self != Retention::Nothing
+    }
+
+    fn keeps_signatures(self) -> bool {
+        self == Retention::HeadersAndSignatures
+    }
+}
+
+/// Handles to the databases holding the data of a block. All but the block
+/// header database are optional.
+pub(crate) struct BlockDbs {
+    pub(crate) header: Database,
+    pub(crate) body: Option<Database>,
+    pub(crate) body_merkle: Option<Database>,
+    pub(crate) deploys: Option<Database>,
+    pub(crate) deploy_metadata: Option<Database>,
+    pub(crate) finalized_approvals: Option<Database>,
+    pub(crate) block_metadata: Option<Database>,
+    pub(crate) transfers: Option<Database>,
+}
+
+impl BlockDbs {
+    /// Opens the handles, which must be done before any transaction of `env`
+    /// begins.
+    pub(crate) fn open(env: &StorageEnv) -> Result<Self, LmdbError> {
+        Ok(Self {
+            header: env.db::<BlockHeaderDatabase>()?,
+            body: env.optional_db::<BlockBodyDatabase>()?,
+            body_merkle: env.optional_db::<BlockBodyMerkleDatabase>()?,
+            deploys: env.optional_db::<DeployDatabase>()?,
+            deploy_metadata: env.optional_db::<DeployMetadataDatabase>()?,
+            finalized_approvals: env.optional_db::<FinalizedApprovalsDatabase>()?,
+            block_metadata: env.optional_db::<BlockMetadataDatabase>()?,
+            transfers: env.optional_db::<TransferDatabase>()?,
+        })
+    }
+}
+
+/// Deletes the entry under `key`, if both the database and the entry exist.
+/// Returns whether an entry was deleted.
+fn del_optional<K: AsRef<[u8]>>(
+    txn: &mut RwTransaction,
+    maybe_db: Option<Database>,
+    key: &K,
+) -> Result<bool, LmdbError> {
+    let db = match maybe_db {
+        Some(db) => db,
+        None => return Ok(false),
+    };
+    match txn.del(db, key, None) {
+        Ok(()) => Ok(true),
+        Err(LmdbError::NotFound) => Ok(false),
+        Err(lmdb_err) => Err(lmdb_err),
+    }
+}
+
+/// Reads the body of a block from either the legacy or the merkle body
+/// databases, removing the entry specific to this body unless `is_shared` is
+/// set, as the header of a remaining block references it too. In the merkle
+/// case, that's the root of the linked list, as the rest of the list and its
+/// parts may be shared with other bodies.
+fn take_body(
+    txn: &mut RwTransaction,
+    dbs: &BlockDbs,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+    is_shared: bool,
+) -> Result<Option<BlockBody>, Error> {
+    if is_shared {
+        info!(
+            "Keeping body {} of block {}, which is shared with a remaining block.",
+            header.body_hash(),
+            block_hash
+        );
+    }
+    let maybe_raw_body = match dbs.body.map(|body_db| txn.get(body_db, header.body_hash())) {
+        Some(Ok(raw_body)) => Some(raw_body),
+        Some(Err(LmdbError::NotFound)) | None => None,
+        Some(Err(lmdb_err)) => return Err(lmdb_err.into()),
+    };
+    if let Some(raw_body) = maybe_raw_body {
+        let body: BlockBody = bincode::deserialize(raw_body)
+            .map_err(|bincode_err| Error::BodyParsing(*block_hash, bincode_err))?;
+        if !is_shared {
+            del_optional(txn, dbs.body, header.body_hash())?;
+        }
+        return Ok(Some(body));
+    }
+    let maybe_merkle_body =
+        merkle_body::read_merkle_body(&LmdbReader::new(&*txn), header.body_hash())
+            .map_err(|merkle_body_err| Error::MerkleBody(*block_hash, merkle_body_err))?;
+    if maybe_merkle_body.is_some() && !is_shared {
+        del_optional(txn, dbs.body_merkle, header.body_hash())?;
+    }
+    Ok(maybe_merkle_body.map(|merkle_body| merkle_body.body))
+}
+
+/// Removes the execution results of `deploy_hash` for the given block, and
+/// the deploy itself if it's no longer executed in any block.
+fn remove_deploy(
+    txn: &mut RwTransaction,
+    dbs: &BlockDbs,
+    block_hash: &BlockHash,
+    deploy_hash: &DeployHash,
+    report: &mut RemovalReport,
+) -> Result<(), Error> {
+    let maybe_raw_metadata = match dbs.deploy_metadata.map(|db| txn.get(db, deploy_hash)) {
+        Some(Ok(raw_metadata)) => Some(raw_metadata),
+        Some(Err(LmdbError::NotFound)) | None => None,
+        Some(Err(lmdb_err)) => return Err(lmdb_err.into()),
+    };
+    if let Some(raw_metadata) = maybe_raw_metadata {
+        let mut metadata: DeployMetadata =
+            bincode::deserialize(raw_metadata).map_err(|bincode_err| {
+                Error::ExecutionResultsParsing(*block_hash, *deploy_hash, bincode_err)
+            })?;
+        if metadata.execution_results.remove(block_hash).is_some() {
+            report.execution_results += 1;
+        }
+        if !metadata.execution_results.is_empty() {
+            // The deploy was also executed in a block outside the era.
+            let encoded_metadata = bincode::serialize(&metadata)
+                .map_err(|bincode_err| Error::Serialization(*deploy_hash, bincode_err))?;
+            let deploy_metadata_db = dbs
+                .deploy_metadata
+                .expect("should have deploy metadata database");
+            txn.put(
+                deploy_metadata_db,
+                deploy_hash,
+                &encoded_metadata,
+                WriteFlags::default(),
+            )?;
+            return Ok(());
+        }
+        del_optional(txn, dbs.deploy_metadata, deploy_hash)?;
+    }
+    if del_optional(txn, dbs.deploys, deploy_hash)? {
+        report.deploys += 1;
+    }
+    del_optional(txn, dbs.finalized_approvals, deploy_hash)?;
+    Ok(())
+}
+
+/// Removes a block along with its body, the execution results of its
+/// deploys, the deploys which are no longer executed in any block, its
+/// transfers and its signatures. The header, and then the signatures, are
+/// kept as set by `retention`. The body is kept if another block of `index`
+/// still references it. Fails without changing anything if the block is a
+/// switch block whose header would be removed, unless
+/// `allow_switch_block_removal` is set.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn remove_block(
+    txn: &mut RwTransaction,
+    dbs: &BlockDbs,
+    index: &mut BlockIndex,
+    block_hash: &BlockHash,
+    header: &BlockHeader,
+    retention: Retention,
+    allow_switch_block_removal: bool,
+    report: &mut RemovalReport,
+) -> Result<(), Error> {
+    let is_shared = !index.release_block(
+        block_hash,
+        header,
+        retention.keeps_headers(),
+        allow_switch_block_removal,
+    )?;
+    match take_body(txn, dbs, block_hash, header, is_shared)? {
+        Some(body) => {
+            for deploy_hash in body.deploy_hashes.iter().chain(body.transfer_hashes.iter()) {
+                remove_deploy(txn, dbs, block_hash, deploy_hash, report)?;
+            }
+        }
+        None => warn!(
+            "No block body found for block header with hash {}",
+            block_hash
+        ),
+    }
+    if !retention.keeps_signatures() && del_optional(txn, dbs.block_metadata, block_hash)? {
+        report.signatures += 1;
+    }
+    if del_optional(txn, dbs.transfers, block_hash)? {
+        report.transfers += 1;
+    }
+    if retention.keeps_headers() {
+        report.headers_kept += 1;
+    } else {
+        txn.del(dbs.header, block_hash, None)?;
+    }
+    report.blocks += 1;
+    Ok(())
+}
+
+/// Removes the blocks of `era_id`, except its switch block if
+/// `keep_switch_block` is set, along with their bodies, deploys, execution
+/// results, transfers and signatures, in a single transaction. The headers
+/// and signatures are kept as set by `retention`. Fails without changing
+/// anything if the switch block would be removed along with its header,
+/// unless `allow_switch_block_removal` is set.
+pub(crate) fn remove_era<P: AsRef<Path>>(
+    db_path: P,
+    era_id: EraId,
+    keep_switch_block: bool,
+    retention: Retention,
+    allow_switch_block_removal: bool,
+) -> Result<RemovalReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let dbs = BlockDbs::open(&env)?;
+
+    let mut txn = env.begin_rw_txn()?;
+    let mut index = BlockIndex::new(&LmdbReader::new(&txn))?;
+    let mut blocks = index.find(|header| header.era_id() == era_id);
+    if blocks.is_empty() {
+        return Err(Error::EmptyEra(era_id));
+    }
+    if keep_switch_block {
+        blocks.retain(|(block_hash, header)| {
+            if header.is_switch_block() {
+                info!(
+                    "Keeping switch block {} at height {}.",
+                    block_hash,
+                    header.height()
+                );
+            }
+            !header.is_switch_block()
+        });
+    }
+    info!("Removing {} blocks of era {}.", blocks.len(), era_id);
+
+    let mut report = RemovalReport::default();
+    for (block_hash, header) in blocks.iter() {
+        remove_block(
+            &mut txn,
+            &dbs,
+            &mut index,
+            block_hash,
+            header,
+            retention,
+            allow_switch_block_removal,
+            &mut report,
+        )?;
+    }
+    txn.commit()?;
+    info!(
+        "Removed {} blocks, {} deploys, {} execution results, {} transfer \
+        entries and {} signature entries, keeping {} headers.",
+        report.blocks,
+        report.deploys,
+        report.execution_results,
+        report.transfers,
+        report.signatures,
+        report.headers_kept
+    );
+    Ok(report)
+}
@@ -0,0 +1,292 @@
+use casper_node::types::{BlockHash, DeployMetadata};
+use casper_types::EraId;
+use lmdb::{Error as LmdbError, Transaction, WriteFlags};
+
+use super::{
+    remove::{remove_era, RemovalReport, Retention},
+    Error,
+};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database,
+            DeployDatabase, DeployMetadataDatabase, STORAGE_FILE_NAME,
+        },
+        switch_blocks::Error as SwitchBlockError,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{
+        mock_block_header, mock_deploy_hash, mock_deploy_metadata, mock_switch_block_header,
+        LmdbTestFixture,
+    },
+};
+
+#[test]
+fn remove_era_should_remove_blocks_of_era() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    // Block 0 is in era 0, blocks 1 and 2 and the switch block are in era 1.
+    let mut blocks: Vec<(BlockHash, Vec<u8>, BlockBody)> = vec![];
+    let deploy_hashes: Vec<_> = (0..4u8).map(mock_deploy_hash).collect();
+    let (block_hash, header) = mock_block_header(0);
+    blocks.push((
+        block_hash,
+        bincode::serialize(&header).unwrap(),
+        BlockBody::new(vec![deploy_hashes[0]]),
+    ));
+    for idx in 1..3u8 {
+        let (block_hash, mut header) = mock_block_header(idx);
+        header.era_id = EraId::new(1);
+        header.height = idx as u64;
+        blocks.push((
+            block_hash,
+            bincode::serialize(&header).unwrap(),
+            BlockBody::new(vec![deploy_hashes[idx as usize]]),
+        ));
+    }
+    // Block 1 also includes deploy 0.
+    blocks[1].2 = BlockBody::new(vec![deploy_hashes[1], deploy_hashes[0]]);
+    let (switch_block_hash, mut switch_header) = mock_switch_block_header(3);
+    switch_header.era_id = EraId::new(1);
+    switch_header.height = 3;
+    blocks.push((
+        switch_block_hash,
+        bincode::serialize(&switch_header).unwrap(),
+        BlockBody::new(vec![deploy_hashes[3]]),
+    ));
+    let body_hashes = [[0u8; 32], [1u8; 32], [2u8; 32], [3u8; 32]];
+
+    // Deploy 0 was executed in blocks 0 and 1, the others only in the block
+    // which includes them.
+    let metadatas = [
+        mock_deploy_metadata(&[blocks[0].0, blocks[1].0]),
+        mock_deploy_metadata(&[blocks[1].0]),
+        mock_deploy_metadata(&[blocks[2].0]),
+        mock_deploy_metadata(&[blocks[3].0]),
+    ];
+    let env = &fixture.env;
+    let mut txn = env.begin_rw_txn().unwrap();
+    for ((block_hash, raw_header, body), body_hash) in blocks.iter().zip(body_hashes.iter()) {
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            block_hash,
+            raw_header,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            body_hash,
+            &bincode::serialize(body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+            block_hash,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    for (deploy_hash, metadata) in deploy_hashes.iter().zip(metadatas.iter()) {
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            deploy_hash,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            deploy_hash,
+            &bincode::serialize(metadata).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    assert!(matches!(
+        remove_era(fixture.tmp_dir.path(), EraId::new(2), false, Retention::Nothing, false),
+        Err(Error::EmptyEra(era_id)) if era_id == EraId::new(2)
+    ));
+    // The switch block isn't removed without the override.
+    assert!(matches!(
+        remove_era(fixture.tmp_dir.path(), EraId::new(1), false, Retention::Nothing, false),
+        Err(Error::SwitchBlock(SwitchBlockError::Protected { block_hash, .. }))
+            if block_hash == switch_block_hash
+    ));
+
+    let report = remove_era(
+        fixture.tmp_dir.path(),
+        EraId::new(1),
+        true,
+        Retention::Nothing,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        report,
+        RemovalReport {
+            blocks: 2,
+            deploys: 2,
+            execution_results: 3,
+            signatures: 2,
+            transfers: 0,
+            headers_kept: 0,
+        }
+    );
+
+    let txn = env.begin_ro_txn().unwrap();
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+    let deploys_db = *fixture.db(Some(DeployDatabase::db_name())).unwrap();
+    let metadata_db = *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap();
+    let signatures_db = *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap();
+    // Block 0 and the switch block are kept, with all their data.
+    for idx in [0, 3] {
+        assert!(txn.get(header_db, &blocks[idx].0).is_ok());
+        assert!(txn.get(body_db, &body_hashes[idx]).is_ok());
+        assert!(txn.get(signatures_db, &blocks[idx].0).is_ok());
+        assert!(txn.get(deploys_db, &deploy_hashes[idx]).is_ok());
+    }
+    for idx in [1, 2] {
+        assert_eq!(
+            txn.get(header_db, &blocks[idx].0).unwrap_err(),
+            LmdbError::NotFound
+        );
+        assert_eq!(
+            txn.get(body_db, &body_hashes[idx]).unwrap_err(),
+            LmdbError::NotFound
+        );
+        assert_eq!(
+            txn.get(signatures_db, &blocks[idx].0).unwrap_err(),
+            LmdbError::NotFound
+        );
+        assert_eq!(
+            txn.get(deploys_db, &deploy_hashes[idx]).unwrap_err(),
+            LmdbError::NotFound
+        );
+        assert_eq!(
+            txn.get(metadata_db, &deploy_hashes[idx]).unwrap_err(),
+            LmdbError::NotFound
+        );
+    }
+    // Deploy 0 only keeps its execution results for block 0.
+    let metadata: DeployMetadata =
+        bincode::deserialize(txn.get(metadata_db, &deploy_hashes[0]).unwrap()).unwrap();
+    assert_eq!(metadata, mock_deploy_metadata(&[blocks[0].0]));
+    txn.commit().unwrap();
+
+    // Removing era 0 while keeping the headers and signatures only leaves
+    // them of block 0.
+    let report = remove_era(
+        fixture.tmp_dir.path(),
+        EraId::new(0),
+        false,
+        Retention::HeadersAndSignatures,
+        false,
+    )
+    .unwrap();
+    assert_eq!(
+        report,
+        RemovalReport {
+            blocks: 1,
+            deploys: 1,
+            execution_results: 1,
+            signatures: 0,
+            transfers: 0,
+            headers_kept: 1,
+        }
+    );
+    let txn = env.begin_ro_txn().unwrap();
+    assert!(txn.get(header_db, &blocks[0].0).is_ok());
+    assert!(txn.get(signatures_db, &blocks[0].0).is_ok());
+    assert_eq!(
+        txn.get(body_db, &body_hashes[0]).unwrap_err(),
+        LmdbError::NotFound
+    );
+    assert_eq!(
+        txn.get(deploys_db, &deploy_hashes[0]).unwrap_err(),
+        LmdbError::NotFound
+    );
+    assert_eq!(
+        txn.get(metadata_db, &deploy_hashes[0]).unwrap_err(),
+        LmdbError::NotFound
+    );
+    txn.commit().unwrap();
+}
+
+#[test]
+fn remove_era_should_keep_shared_bodies() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Blocks 0 and 1, in eras 0 and 1, have the same empty body.
+    let body_hash = [0u8; 32];
+    let mut block_hashes = vec![];
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for idx in 0..2u8 {
+        let (block_hash, mut header) = mock_block_header(idx);
+        header.era_id = EraId::new(idx as u64);
+        header.height = idx as u64;
+        header.body_hash = body_hash.into();
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        block_hashes.push(block_hash);
+    }
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &body_hash,
+        &bincode::serialize(&BlockBody::new(vec![])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+    // Block 0 still references the body.
+    remove_era(
+        fixture.tmp_dir.path(),
+        EraId::new(1),
+        false,
+        Retention::Nothing,
+        false,
+    )
+    .unwrap();
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    assert!(txn.get(body_db, &body_hash).is_ok());
+    txn.commit().unwrap();
+
+    // No remaining block references it once block 0 is removed.
+    remove_era(
+        fixture.tmp_dir.path(),
+        EraId::new(0),
+        false,
+        Retention::Nothing,
+        false,
+    )
+    .unwrap();
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    assert_eq!(
+        txn.get(body_db, &body_hash).unwrap_err(),
+        LmdbError::NotFound
+    );
+    txn.commit().unwrap();
+}
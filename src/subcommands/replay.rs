@@ -0,0 +1,194 @@
+mod execute;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use bincode::Error as BincodeError;
+use casper_execution_engine::{
+    core::engine_state::{Error as EngineStateError, StepError},
+    storage::error::Error as GlobalStateError,
+};
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, DeployHash};
+use casper_types::bytesrepr::Error as BytesreprError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    chainspec::{self, Error as ChainspecError},
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "replay";
+const CHAINSPEC: &str = "chainspec";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when replaying a block.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Error reading the chainspec.
+    #[error(transparent)]
+    Chainspec(#[from] ChainspecError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on a deploy or its finalized approvals.
+    #[error("Error parsing deploy {0}: {1}")]
+    DeployParsing(DeployHash, BincodeError),
+    /// Error executing the deploys.
+    #[error("Error executing block: {0}")]
+    EngineState(#[from] EngineStateError),
+    /// Error decoding the era report of a switch block.
+    #[error("Error decoding era report: {0}")]
+    EraReport(BytesreprError),
+    /// Error reading global state.
+    #[error("Error reading global state: {0}")]
+    GlobalState(#[from] GlobalStateError),
+    /// Error loading the trie store.
+    #[error("Error loading the execution engine: {0}")]
+    LoadExecutionEngine(anyhow::Error),
+    /// The replayed block differs from the stored one. The report lists the
+    /// differences.
+    #[error("Found {0} mismatches with the stored block")]
+    Mismatches(usize),
+    /// The slice holds no block.
+    #[error("No block found in the slice")]
+    MissingBlock,
+    /// The body of the block is missing.
+    #[error("Body of block {0} is missing")]
+    MissingBody(BlockHash),
+    /// A deploy of the block is missing.
+    #[error("Deploy {0} is missing")]
+    MissingDeploy(DeployHash),
+    /// The header of the parent of the block is missing.
+    #[error("Parent block {0} is missing")]
+    MissingParent(BlockHash),
+    /// The pre-state root of the block isn't in the trie store.
+    #[error("State root {0} not found in the trie store")]
+    MissingStateRoot(Digest),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Error running the end of era step of a switch block.
+    #[error("Error running step: {0}")]
+    Step(StepError),
+    /// The execution engine returned other than one result for a deploy.
+    #[error("Unexpected execution results for deploy {0}")]
+    UnexpectedExecutionResults(DeployHash),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Chainspec,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Re-executes the highest block of a slice created by \
+            `extract-slice` on the state root of its parent, and compares \
+            the resulting state root and execution results with the stored \
+            ones, outputting a report in JSON format. The state root \
+            computed by the replay is written to the trie store of the slice.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the slice directory with the `storage.lmdb` and \
+                    `data.lmdb` files.",
+                ),
+        )
+        .arg(
+            Arg::new(CHAINSPEC)
+                .display_order(DisplayOrder::Chainspec as usize)
+                .required(true)
+                .short('c')
+                .long(CHAINSPEC)
+                .takes_value(true)
+                .value_name("CHAINSPEC_PATH")
+                .help(
+                    "Path of the chainspec of the network at the height of the \
+                    block, or of the directory with its `chainspec.toml` file.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let chainspec = chainspec::read_chainspec(
+        matches
+            .value_of(CHAINSPEC)
+            .expect("should have chainspec arg"),
+    )?;
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = execute::replay_block(path, &chainspec)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+
+    if !report.mismatches.is_empty() {
+        return Err(Error::Mismatches(report.mismatches.len()));
+    }
+    Ok(())
+}
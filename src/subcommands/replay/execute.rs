@@ -0,0 +1,288 @@
+use std::{collections::BTreeMap, path::Path, result::Result};
+
+use casper_execution_engine::{
+    core::engine_state::{
+        step::EvictItem, DeployItem, ExecuteRequest, ExecutionResult as EngineExecutionResult,
+        RewardItem, StepRequest,
+    },
+    shared::{additive_map::AdditiveMap, newtypes::CorrelationId, transform::Transform},
+    storage::global_state::StateProvider,
+};
+use casper_hashing::Digest;
+use casper_node::types::{
+    BlockHash, BlockHeader, Deploy, DeployHash, DeployMetadata, DeployWithFinalizedApprovals,
+    FinalizedApprovals,
+};
+use casper_types::{
+    bytesrepr::{FromBytes, ToBytes},
+    EraId, ExecutionResult, Key, PublicKey,
+};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        block_iter,
+        chainspec::Chainspec,
+        db::{
+            Database, DeployDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase,
+            StorageEnv,
+        },
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::trie_compact::{load_execution_engine_with_config, DEFAULT_MAX_DB_SIZE},
+};
+
+use super::Error;
+
+/// A difference between the replayed block and the stored one.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub(crate) enum Mismatch {
+    /// The state root after the block differs from the one in its header.
+    StateRoot { expected: Digest, actual: Digest },
+    /// The result of a deploy differs from the stored one, or none is
+    /// stored.
+    ExecutionResult {
+        deploy_hash: DeployHash,
+        expected: Option<Box<ExecutionResult>>,
+        actual: Box<ExecutionResult>,
+    },
+}
+
+/// The outcome of replaying a block.
+#[derive(Debug, Serialize)]
+pub(crate) struct ReplayReport {
+    pub(crate) block_hash: BlockHash,
+    pub(crate) height: u64,
+    pub(crate) era_id: EraId,
+    /// State root of the parent block, on which the block is executed.
+    pub(crate) pre_state_root_hash: Digest,
+    /// State root in the header of the block.
+    pub(crate) expected_state_root_hash: Digest,
+    /// State root computed by the replay.
+    pub(crate) actual_state_root_hash: Digest,
+    /// Number of deploys and transfers executed.
+    pub(crate) deploys: usize,
+    pub(crate) mismatches: Vec<Mismatch>,
+}
+
+/// The rewards and evictions of the step ending an era, as in the era report
+/// of a switch block.
+struct EraReport {
+    rewards: BTreeMap<PublicKey, u64>,
+    evictions: Vec<PublicKey>,
+}
+
+impl EraReport {
+    /// Decodes the era report of `header`, if it's a switch block. The fields
+    /// of the era report of the node aren't public, so it's read back from
+    /// its encoding.
+    fn from_header(header: &BlockHeader) -> Result<Option<Self>, Error> {
+        let era_report = match header.era_end() {
+            Some(era_report) => era_report,
+            None => return Ok(None),
+        };
+        let bytes = era_report.to_bytes().map_err(Error::EraReport)?;
+        let (equivocators, remainder) =
+            Vec::<PublicKey>::from_bytes(&bytes).map_err(Error::EraReport)?;
+        let (rewards, remainder) =
+            BTreeMap::<PublicKey, u64>::from_bytes(remainder).map_err(Error::EraReport)?;
+        let (inactive_validators, _) =
+            Vec::<PublicKey>::from_bytes(remainder).map_err(Error::EraReport)?;
+        // Both inactive validators and equivocators are evicted.
+        let evictions = inactive_validators
+            .into_iter()
+            .chain(equivocators)
+            .collect();
+        Ok(Some(Self { rewards, evictions }))
+    }
+}
+
+/// Reads the deploy `deploy_hash` with the approvals it was finalized with.
+fn read_deploy<R: StorageReader>(reader: &R, deploy_hash: &DeployHash) -> Result<Deploy, Error> {
+    let raw_deploy = reader
+        .get(DeployDatabase::db_name(), deploy_hash.as_ref())?
+        .ok_or(Error::MissingDeploy(*deploy_hash))?;
+    let deploy: Deploy = bincode::deserialize(&raw_deploy)
+        .map_err(|bincode_err| Error::DeployParsing(*deploy_hash, bincode_err))?;
+    let maybe_approvals = if reader.has_table(FinalizedApprovalsDatabase::db_name())? {
+        reader
+            .get(FinalizedApprovalsDatabase::db_name(), deploy_hash.as_ref())?
+            .map(|raw_approvals| bincode::deserialize::<FinalizedApprovals>(&raw_approvals))
+            .transpose()
+            .map_err(|bincode_err| Error::DeployParsing(*deploy_hash, bincode_err))?
+    } else {
+        None
+    };
+    Ok(DeployWithFinalizedApprovals::new(deploy, maybe_approvals).into_naive())
+}
+
+/// Reads the stored result of `deploy_hash` in the block `block_hash`.
+fn read_execution_result<R: StorageReader>(
+    reader: &R,
+    deploy_hash: &DeployHash,
+    block_hash: &BlockHash,
+) -> Result<Option<ExecutionResult>, Error> {
+    match reader.get(DeployMetadataDatabase::db_name(), deploy_hash.as_ref())? {
+        Some(raw_metadata) => {
+            let mut metadata: DeployMetadata = bincode::deserialize(&raw_metadata)
+                .map_err(|bincode_err| Error::DeployParsing(*deploy_hash, bincode_err))?;
+            Ok(metadata.execution_results.remove(block_hash))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Re-executes the highest block of the slice in `db_path` on the state root
+/// of its parent, following the execution of finalized blocks by the node,
+/// and compares the outcome with the stored block. The resulting state root
+/// is written to the trie store of the slice.
+pub(crate) fn replay_block<P: AsRef<Path>>(
+    db_path: P,
+    chainspec: &Chainspec,
+) -> Result<ReplayReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+    let (_, block_hash) = block_iter::blocks_by_height(&reader)?
+        .pop()
+        .ok_or(Error::MissingBlock)?;
+    let header = block_iter::read_header(&reader, &block_hash)?;
+    let parent_header = match block_iter::read_header(&reader, header.parent_hash()) {
+        Ok(parent_header) => parent_header,
+        Err(block_iter::Error::MissingHeader(parent_hash)) => {
+            return Err(Error::MissingParent(parent_hash))
+        }
+        Err(block_iter_err) => return Err(block_iter_err.into()),
+    };
+    let body = block_iter::read_body(&reader, &block_hash, &header)?
+        .ok_or(Error::MissingBody(block_hash))?;
+    let deploys = body
+        .deploy_hashes()
+        .iter()
+        .chain(&body.transfer_hashes)
+        .map(|deploy_hash| read_deploy(&reader, deploy_hash))
+        .collect::<Result<Vec<_>, _>>()?;
+    let maybe_era_report = EraReport::from_header(&header)?;
+
+    if chainspec.core.prune_batch_size > 0 {
+        warn!(
+            "The chainspec prunes era infos after every block, which the replay doesn't do, \
+            so the state root of the block may differ."
+        );
+    }
+    let max_db_size = DEFAULT_MAX_DB_SIZE
+        .parse()
+        .expect("should be able to parse max db size");
+    let (engine_state, _env) = load_execution_engine_with_config(
+        &db_path,
+        max_db_size,
+        Digest::default(),
+        true,
+        chainspec.engine_config(),
+    )
+    .map_err(Error::LoadExecutionEngine)?;
+    let pre_state_root_hash = *parent_header.state_root_hash();
+    if engine_state
+        .get_state()
+        .checkout(pre_state_root_hash)?
+        .is_none()
+    {
+        return Err(Error::MissingStateRoot(pre_state_root_hash));
+    }
+
+    info!(
+        "Replaying block {} at height {} with {} deploys.",
+        block_hash,
+        header.height(),
+        deploys.len()
+    );
+    let mut report = ReplayReport {
+        block_hash,
+        height: header.height(),
+        era_id: header.era_id(),
+        pre_state_root_hash,
+        expected_state_root_hash: *header.state_root_hash(),
+        actual_state_root_hash: pre_state_root_hash,
+        deploys: deploys.len(),
+        mismatches: vec![],
+    };
+    // Changes are cached in memory and written to the trie store at once,
+    // as the node does.
+    let scratch_state = engine_state.get_scratch_engine_state();
+    let mut state_root_hash = pre_state_root_hash;
+    for deploy in deploys {
+        let deploy_hash = *deploy.id();
+        let execute_request = ExecuteRequest::new(
+            state_root_hash,
+            header.timestamp().millis(),
+            vec![DeployItem::from(deploy)],
+            header.protocol_version(),
+            body.proposer().clone(),
+        );
+        let mut results = scratch_state.run_execute(CorrelationId::new(), execute_request)?;
+        let ee_result = match (results.pop_front(), results.is_empty()) {
+            (Some(ee_result), true) => ee_result,
+            _ => return Err(Error::UnexpectedExecutionResults(deploy_hash)),
+        };
+        let actual = ExecutionResult::from(&ee_result);
+        let expected = read_execution_result(&reader, &deploy_hash, &block_hash)?;
+        if expected.as_ref() != Some(&actual) {
+            report.mismatches.push(Mismatch::ExecutionResult {
+                deploy_hash,
+                expected: expected.map(Box::new),
+                actual: Box::new(actual),
+            });
+        }
+        let effects: AdditiveMap<Key, Transform> = match ee_result {
+            EngineExecutionResult::Success {
+                execution_journal, ..
+            }
+            | EngineExecutionResult::Failure {
+                execution_journal, ..
+            } => execution_journal.into(),
+        };
+        state_root_hash =
+            scratch_state.apply_effect(CorrelationId::new(), state_root_hash, effects)?;
+    }
+
+    if let Some(era_report) = maybe_era_report {
+        let step_request = StepRequest::new(
+            state_root_hash,
+            header.protocol_version(),
+            vec![],
+            era_report
+                .rewards
+                .into_iter()
+                .map(|(public_key, reward)| RewardItem::new(public_key, reward))
+                .collect(),
+            era_report
+                .evictions
+                .into_iter()
+                .map(EvictItem::new)
+                .collect(),
+            header.era_id().successor(),
+            header.timestamp().millis(),
+        );
+        scratch_state
+            .commit_step(CorrelationId::new(), step_request)
+            .map_err(Error::Step)?;
+    }
+    report.actual_state_root_hash =
+        engine_state.write_scratch_to_db(state_root_hash, scratch_state.into_inner())?;
+    engine_state.flush_environment()?;
+
+    if report.actual_state_root_hash != report.expected_state_root_hash {
+        report.mismatches.push(Mismatch::StateRoot {
+            expected: report.expected_state_root_hash,
+            actual: report.actual_state_root_hash,
+        });
+    }
+    info!(
+        "Replayed block {} with {} mismatches.",
+        block_hash,
+        report.mismatches.len()
+    );
+    Ok(report)
+}
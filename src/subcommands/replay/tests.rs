@@ -0,0 +1,290 @@
+use std::path::PathBuf;
+
+use casper_execution_engine::{
+    core::engine_state::{
+        genesis::GenesisValidator, ExecConfig, ExecutableDeployItem, GenesisAccount,
+    },
+    shared::newtypes::CorrelationId,
+};
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, Deploy, DeployMetadata};
+use casper_types::{
+    bytesrepr::Bytes, runtime_args, Motes, ProtocolVersion, PublicKey, RuntimeArgs, SecretKey,
+    TimeDiff, Timestamp, U512,
+};
+use lmdb::{DatabaseFlags, Transaction, WriteFlags};
+use serde::Serialize;
+use serde_json::json;
+use tempfile::TempDir;
+
+use super::{
+    execute::{self, Mismatch},
+    Error,
+};
+use crate::{
+    common::{
+        chainspec::Chainspec,
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase,
+            DeployMetadataDatabase, StorageEnv,
+        },
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        trie_compact::{create_execution_engine, DEFAULT_MAX_DB_SIZE},
+    },
+    test_utils::{self, KEYS},
+};
+
+const BLOCK_TIMESTAMP_MILLIS: u64 = 1_000_000;
+
+fn sender_secret_key() -> SecretKey {
+    SecretKey::ed25519_from_bytes([1u8; 32]).unwrap()
+}
+
+/// Creates a trie store in `tmp_dir` with the genesis state of a network
+/// with the sender of the transfers as an account and `KEYS[0]` as its only
+/// validator, returning the genesis state root.
+fn commit_genesis(tmp_dir: &TempDir, chainspec: &Chainspec) -> Digest {
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), DEFAULT_MAX_DB_SIZE.parse().unwrap(), true)
+            .unwrap();
+    let accounts = vec![
+        GenesisAccount::account(
+            PublicKey::from(&sender_secret_key()),
+            Motes::new(U512::from(1_000_000_000_000_000u64)),
+            None,
+        ),
+        GenesisAccount::account(
+            KEYS[0].clone(),
+            Motes::new(U512::from(1_000_000_000_000_000u64)),
+            Some(GenesisValidator::new(
+                Motes::new(U512::from(1_000_000_000_000u64)),
+                10,
+            )),
+        ),
+    ];
+    // The seigniorage rate is a ratio of a crate this one doesn't depend on,
+    // so the config is deserialized instead.
+    let exec_config: ExecConfig = serde_json::from_value(json!({
+        "accounts": accounts,
+        "wasm_config": chainspec.wasm,
+        "system_config": chainspec.system_costs,
+        "validator_slots": chainspec.core.validator_slots,
+        "auction_delay": chainspec.core.auction_delay,
+        "locked_funds_period_millis": 0,
+        "round_seigniorage_rate": [7, 175_070_816],
+        "unbonding_delay": 7,
+        "genesis_timestamp_millis": 0,
+    }))
+    .unwrap();
+    let genesis = engine_state
+        .commit_genesis(
+            CorrelationId::new(),
+            Digest::hash([0; 32]),
+            ProtocolVersion::V1_0_0,
+            &exec_config,
+        )
+        .unwrap();
+    engine_state.flush_environment().unwrap();
+    genesis.post_state_hash
+}
+
+/// Returns a native transfer from the sender to `KEYS[1]`.
+fn transfer_deploy() -> Deploy {
+    Deploy::new(
+        Timestamp::from(BLOCK_TIMESTAMP_MILLIS),
+        TimeDiff::from_seconds(60),
+        1,
+        vec![],
+        "casper-test".to_string(),
+        ExecutableDeployItem::ModuleBytes {
+            module_bytes: Bytes::new(),
+            args: runtime_args! { "amount" => U512::from(100_000_000u64) },
+        },
+        ExecutableDeployItem::Transfer {
+            args: runtime_args! {
+                "amount" => U512::from(2_500_000_000u64),
+                "target" => KEYS[1].clone(),
+                "id" => Some(1u64),
+            },
+        },
+        &sender_secret_key(),
+        None,
+    )
+}
+
+/// The storage database of a slice, holding the parent of height 0 of a
+/// block executing a transfer, stored by `put_block`.
+struct Slice {
+    path: PathBuf,
+    block_hash: BlockHash,
+    deploy: Deploy,
+}
+
+impl Slice {
+    fn new(tmp_dir: &TempDir, genesis_root: Digest) -> Self {
+        // The environment is opened on every write, as the replay opens its
+        // own.
+        let env = StorageEnv::create(tmp_dir.path(), 1 << 20).unwrap();
+        for db_name in [
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ] {
+            env.create_db(Some(db_name), DatabaseFlags::empty())
+                .unwrap();
+        }
+        let deploy = transfer_deploy();
+        let (parent_hash, mut parent_header) = test_utils::mock_block_header(0);
+        parent_header.state_root_hash = genesis_root;
+        parent_header.protocol_version = ProtocolVersion::V1_0_0;
+        let (block_hash, _) = test_utils::mock_block_header(1);
+
+        drop(env);
+        let slice = Self {
+            path: tmp_dir.path().to_path_buf(),
+            block_hash,
+            deploy,
+        };
+        slice.put(BlockHeaderDatabase::db_name(), &parent_hash, &parent_header);
+        slice.put(
+            BlockBodyDatabase::db_name(),
+            &parent_header.body_hash,
+            &BlockBody::new(vec![]),
+        );
+        slice.put(DeployDatabase::db_name(), slice.deploy.id(), &slice.deploy);
+        slice
+    }
+
+    fn put<K: AsRef<[u8]>, V: Serialize>(&self, db_name: &str, key: &K, value: &V) {
+        let env = StorageEnv::open(&self.path).unwrap();
+        let db = env.open_db(Some(db_name)).unwrap();
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.put(
+            db,
+            key,
+            &bincode::serialize(value).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    }
+
+    /// Stores the block at height 1 with `state_root_hash`, as a switch
+    /// block rewarding the genesis validator if `switch_block` is set.
+    fn put_block(&self, parent_hash: BlockHash, state_root_hash: Digest, switch_block: bool) {
+        let timestamp = Timestamp::from(BLOCK_TIMESTAMP_MILLIS);
+        let body_hash = if switch_block {
+            let (_, mut header) = test_utils::mock_switch_block_header(1);
+            // The rewards must be given to exactly the validators of the era.
+            header.insert_reward(KEYS[0].clone(), 1_000_000_000_000);
+            header.parent_hash = parent_hash;
+            header.state_root_hash = state_root_hash;
+            header.timestamp = timestamp;
+            header.height = 1;
+            header.protocol_version = ProtocolVersion::V1_0_0;
+            self.put(BlockHeaderDatabase::db_name(), &self.block_hash, &header);
+            header.body_hash
+        } else {
+            let (_, mut header) = test_utils::mock_block_header(1);
+            header.parent_hash = parent_hash;
+            header.state_root_hash = state_root_hash;
+            header.timestamp = timestamp;
+            header.height = 1;
+            header.protocol_version = ProtocolVersion::V1_0_0;
+            self.put(BlockHeaderDatabase::db_name(), &self.block_hash, &header);
+            header.body_hash
+        };
+        let body = BlockBody::from_parts(KEYS[0].clone(), vec![], vec![*self.deploy.id()]);
+        self.put(BlockBodyDatabase::db_name(), &body_hash, &body);
+    }
+
+    fn parent_hash(&self) -> BlockHash {
+        test_utils::mock_block_header(0).0
+    }
+}
+
+/// Replays the block of a new slice, first with a wrong state root and no
+/// stored results, then with the ones computed by the first replay.
+fn replay_until_matching(switch_block: bool) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let chainspec = test_utils::mock_chainspec();
+    let genesis_root = commit_genesis(&tmp_dir, &chainspec);
+    let slice = Slice::new(&tmp_dir, genesis_root);
+    slice.put_block(slice.parent_hash(), Digest::default(), switch_block);
+
+    let report = execute::replay_block(tmp_dir.path(), &chainspec).unwrap();
+    assert_eq!(report.block_hash, slice.block_hash);
+    assert_eq!(report.height, 1);
+    assert_eq!(report.pre_state_root_hash, genesis_root);
+    assert_eq!(report.deploys, 1);
+    assert_ne!(report.actual_state_root_hash, genesis_root);
+    assert_eq!(report.mismatches.len(), 2);
+    let actual_result = match &report.mismatches[0] {
+        Mismatch::ExecutionResult {
+            deploy_hash,
+            expected: None,
+            actual,
+        } if deploy_hash == slice.deploy.id() => *actual.clone(),
+        mismatch => panic!("unexpected mismatch {:?}", mismatch),
+    };
+    assert!(matches!(
+        actual_result,
+        casper_types::ExecutionResult::Success { .. }
+    ));
+    assert_eq!(
+        report.mismatches[1],
+        Mismatch::StateRoot {
+            expected: Digest::default(),
+            actual: report.actual_state_root_hash,
+        }
+    );
+
+    let mut metadata = DeployMetadata::default();
+    metadata
+        .execution_results
+        .insert(slice.block_hash, actual_result);
+    slice.put(
+        DeployMetadataDatabase::db_name(),
+        slice.deploy.id(),
+        &metadata,
+    );
+    slice.put_block(
+        slice.parent_hash(),
+        report.actual_state_root_hash,
+        switch_block,
+    );
+    let second_report = execute::replay_block(tmp_dir.path(), &chainspec).unwrap();
+    assert_eq!(
+        second_report.actual_state_root_hash,
+        report.actual_state_root_hash
+    );
+    assert!(second_report.mismatches.is_empty());
+}
+
+#[test]
+fn replay_should_compare_block_with_stored_results() {
+    replay_until_matching(false);
+}
+
+#[test]
+fn replay_switch_block_should_run_step() {
+    replay_until_matching(true);
+}
+
+#[test]
+fn replay_without_parent_should_fail() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let chainspec = test_utils::mock_chainspec();
+    let genesis_root = commit_genesis(&tmp_dir, &chainspec);
+    let slice = Slice::new(&tmp_dir, genesis_root);
+    let missing_parent_hash = test_utils::mock_block_header(7).0;
+    slice.put_block(missing_parent_hash, Digest::default(), false);
+
+    assert!(matches!(
+        execute::replay_block(tmp_dir.path(), &chainspec),
+        Err(Error::MissingParent(parent_hash)) if parent_hash == missing_parent_hash
+    ));
+}
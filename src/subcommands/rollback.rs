@@ -0,0 +1,192 @@
+mod prune;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use log::info;
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::{
+        db::{self, TRIE_STORE_FILE_NAME},
+        disk_space::{self, Error as DiskSpaceError},
+        state_store::Error as StateStoreError,
+        switch_blocks,
+    },
+    subcommands::{
+        remove_era::Error as RemoveBlocksError,
+        trie_compact::{self, DestinationOptions, Error as TrieCompactError, DEFAULT_MAX_DB_SIZE},
+    },
+};
+
+pub const COMMAND_NAME: &str = "rollback";
+const DB_PATH: &str = "db-path";
+const DESTINATION_TRIE_STORE_PATH: &str = "dest-trie";
+const FORCE: &str = "force";
+const HEIGHT: &str = "height";
+const MAX_DB_SIZE: &str = "max-db-size";
+
+/// Errors encountered when rolling back a database to a given height.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Not enough space for the compacted trie.
+    #[error("Disk space check failed: {0}")]
+    DiskSpace(#[from] DiskSpaceError),
+    /// The destination already holds a trie store.
+    #[error("Output file \"data.lmdb\" already exists at destination {0}")]
+    InvalidDest(PathBuf),
+    /// Path cannot be resolved.
+    #[error("Path {0} cannot be resolved: {1}")]
+    InvalidPath(PathBuf, IoError),
+    /// The block at the target height is not in the database.
+    #[error("No block at height {0} found in the database")]
+    MissingBlock(u64),
+    /// Error removing the blocks above the target height.
+    #[error("Error removing blocks: {0}")]
+    RemoveBlocks(#[from] RemoveBlocksError),
+    /// Error resetting the state store markers.
+    #[error("Error resetting the state store: {0}")]
+    StateStore(#[from] StateStoreError),
+    /// Error compacting the trie store.
+    #[error("Error compacting the trie store: {0}")]
+    TrieCompact(#[from] TrieCompactError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Height,
+    DestinationPath,
+    MaxDbSize,
+    Force,
+    AllowSwitchBlockRemoval,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Rolls a node's databases back to a given height: removes every \
+            block above it from the storage database and resets the linear \
+            chain sync state pointing above it, then writes a trie \
+            store holding only the state roots of the remaining blocks to \
+            the destination. Switch blocks above the height are only \
+            removed with `--allow-switch-block-removal`.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` and `data.lmdb` files."),
+        )
+        .arg(
+            Arg::new(HEIGHT)
+                .display_order(DisplayOrder::Height as usize)
+                .required(true)
+                .short('H')
+                .long(HEIGHT)
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help("Height of the highest block to keep."),
+        )
+        .arg(
+            Arg::new(DESTINATION_TRIE_STORE_PATH)
+                .display_order(DisplayOrder::DestinationPath as usize)
+                .required(true)
+                .short('t')
+                .long(DESTINATION_TRIE_STORE_PATH)
+                .takes_value(true)
+                .value_name("DESTINATION_TRIE_STORE_DIR_PATH")
+                .help(
+                    "Path of the directory where the compacted `data.lmdb` file \
+                    will be created. It should replace the one in the database \
+                    directory before restarting the node.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .required(false)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .help("Maximum size the DB files are allowed to be, in bytes."),
+        )
+        .arg(
+            Arg::new(FORCE)
+                .display_order(DisplayOrder::Force as usize)
+                .required(false)
+                .long(FORCE)
+                .takes_value(false)
+                .help(
+                    "Proceed even if the destination doesn't seem to have enough \
+                    free space for a copy of the source trie store.",
+                ),
+        )
+        .arg(switch_blocks::allow_removal_arg(
+            DisplayOrder::AllowSwitchBlockRemoval as usize,
+        ))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let height = matches
+        .value_of(HEIGHT)
+        .expect("should have height arg")
+        .parse::<u64>()
+        .expect("should be validated");
+    let destination_trie_path = Path::new(
+        matches
+            .value_of(DESTINATION_TRIE_STORE_PATH)
+            .expect("should have dest-trie arg"),
+    );
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .unwrap()
+        .parse()
+        .expect("Value of \"--max-db-size\" must be an integer.");
+
+    // Check the destination before touching the storage, so that a bad
+    // destination doesn't leave a pruned storage without a matching trie.
+    let destination_trie_file = destination_trie_path.join(TRIE_STORE_FILE_NAME);
+    if destination_trie_file.exists() {
+        return Err(Error::InvalidDest(destination_trie_path.to_path_buf()));
+    }
+    let source_trie_file = db::data_file_path(db_path.join(TRIE_STORE_FILE_NAME));
+    let required_space = disk_space::allocated_size(&source_trie_file)
+        .map_err(|io_err| Error::InvalidPath(source_trie_file, io_err))?;
+    disk_space::check_available_space(
+        destination_trie_path,
+        required_space,
+        matches.is_present(FORCE),
+    )?;
+
+    prune::prune_above(db_path, height, switch_blocks::is_removal_allowed(matches))?;
+    info!(
+        "Compacting the trie store to {}.",
+        destination_trie_path.display()
+    );
+    trie_compact::trie_compact(
+        db_path,
+        db_path,
+        destination_trie_path,
+        DestinationOptions::New,
+        max_db_size,
+    )?;
+    Ok(())
+}
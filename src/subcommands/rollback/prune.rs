@@ -0,0 +1,77 @@
+use std::{path::Path, result::Result};
+
+use lmdb::Transaction;
+use log::info;
+
+use crate::{
+    common::{
+        block_iter::BlockIndex,
+        db::StorageEnv,
+        state_store,
+        storage::{LmdbReader, LmdbWriter},
+    },
+    subcommands::remove_era::{
+        remove::{self, BlockDbs, RemovalReport, Retention},
+        Error as RemoveBlocksError,
+    },
+};
+
+use super::Error;
+
+/// Removes every block above `height` from the storage database, along with
+/// their bodies, deploys, execution results, transfers and signatures, in a
+/// single transaction. The linear chain synchronizer entries of the state
+/// store pointing above `height` are reset in the same transaction. Fails
+/// without changing anything if the block at `height` isn't in the database,
+/// or if a switch block is above it unless `allow_switch_block_removal` is
+/// set.
+pub(crate) fn prune_above<P: AsRef<Path>>(
+    db_path: P,
+    height: u64,
+    allow_switch_block_removal: bool,
+) -> Result<RemovalReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let dbs = BlockDbs::open(&env)?;
+
+    let mut txn = env.begin_rw_txn()?;
+    let mut index = BlockIndex::new(&LmdbReader::new(&txn)).map_err(RemoveBlocksError::from)?;
+    let mut blocks = index.find(|header| header.height() >= height);
+    if !blocks.iter().any(|(_, header)| header.height() == height) {
+        return Err(Error::MissingBlock(height));
+    }
+    blocks.retain(|(_, header)| header.height() > height);
+    info!("Removing {} blocks above height {}.", blocks.len(), height);
+
+    let mut report = RemovalReport::default();
+    for (block_hash, header) in blocks.iter() {
+        remove::remove_block(
+            &mut txn,
+            &dbs,
+            &mut index,
+            block_hash,
+            header,
+            Retention::Nothing,
+            allow_switch_block_removal,
+            &mut report,
+        )?;
+    }
+    let reset_networks =
+        state_store::reset_sync_markers_above(&mut LmdbWriter::new(&mut txn), height)?;
+    for network_name in reset_networks.iter() {
+        info!(
+            "Reset the linear chain sync state of network {}, which was above height {}.",
+            network_name, height
+        );
+    }
+    txn.commit()?;
+    info!(
+        "Removed {} blocks, {} deploys, {} execution results, {} transfer \
+        entries and {} signature entries.",
+        report.blocks,
+        report.deploys,
+        report.execution_results,
+        report.transfers,
+        report.signatures
+    );
+    Ok(report)
+}
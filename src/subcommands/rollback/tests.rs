@@ -0,0 +1,331 @@
+use std::fs;
+
+use casper_execution_engine::storage::{
+    store::StoreExt,
+    transaction_source::{lmdb::LmdbEnvironment, TransactionSource},
+    trie_store::lmdb::LmdbTrieStore,
+};
+use casper_hashing::Digest;
+use casper_node::types::{Block, BlockHash, BlockHeader, HashingAlgorithmVersion};
+use lmdb::{DatabaseFlags, Error as LmdbError, Transaction, WriteFlags};
+use tempfile::tempdir;
+
+use super::{prune::prune_above, Error};
+use crate::{
+    common::{
+        db::{
+            get_optional, BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database,
+            DeployDatabase, DeployMetadataDatabase, StateStoreDatabase, TrieEnv, STORAGE_FILE_NAME,
+        },
+        state_store::{
+            self, LinearChainSyncState, LinearChainSyncSummary, SyncStatus, WellKnownKey,
+        },
+        storage::LmdbReader,
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody,
+        remove_era::remove::RemovalReport,
+        trie_compact::{self, tests::create_data, DestinationOptions, DEFAULT_MAX_DB_SIZE},
+    },
+    test_utils::{mock_block_header, mock_deploy_hash, mock_deploy_metadata, LmdbTestFixture},
+};
+
+#[test]
+fn prune_above_should_remove_higher_blocks() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+
+    // Blocks 0 to 3, each including and executing one deploy.
+    let blocks: Vec<_> = (0..4u8)
+        .map(|idx| {
+            let (block_hash, mut header) = mock_block_header(idx);
+            header.height = idx as u64;
+            header.body_hash = [idx; 32].into();
+            (block_hash, header)
+        })
+        .collect();
+    let deploy_hashes: Vec<_> = (0..4u8).map(mock_deploy_hash).collect();
+
+    let env = &fixture.env;
+    let mut txn = env.begin_rw_txn().unwrap();
+    for ((block_hash, header), deploy_hash) in blocks.iter().zip(deploy_hashes.iter()) {
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            block_hash,
+            &bincode::serialize(header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &header.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![*deploy_hash])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+            block_hash,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            deploy_hash,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            deploy_hash,
+            &bincode::serialize(&mock_deploy_metadata(&[*block_hash])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    // Nothing is removed if the target block is missing.
+    assert!(matches!(
+        prune_above(fixture.tmp_dir.path(), 7, false),
+        Err(Error::MissingBlock(7))
+    ));
+
+    let report = prune_above(fixture.tmp_dir.path(), 1, false).unwrap();
+    assert_eq!(
+        report,
+        RemovalReport {
+            blocks: 2,
+            deploys: 2,
+            execution_results: 2,
+            signatures: 2,
+            transfers: 0,
+            headers_kept: 0,
+        }
+    );
+
+    let txn = env.begin_ro_txn().unwrap();
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+    let deploys_db = *fixture.db(Some(DeployDatabase::db_name())).unwrap();
+    let metadata_db = *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap();
+    for (idx, (block_hash, header)) in blocks.iter().enumerate() {
+        let kept = idx <= 1;
+        assert_eq!(txn.get(header_db, block_hash).is_ok(), kept);
+        assert_eq!(txn.get(body_db, &header.body_hash).is_ok(), kept);
+        assert_eq!(txn.get(deploys_db, &deploy_hashes[idx]).is_ok(), kept);
+        if !kept {
+            assert_eq!(
+                txn.get(metadata_db, &deploy_hashes[idx]).unwrap_err(),
+                LmdbError::NotFound
+            );
+        }
+    }
+    txn.commit().unwrap();
+}
+
+/// Stores the block at `height` with an empty body, its header and body
+/// hashing as the node's do so that the node's storage accepts them, and
+/// returns it.
+fn put_hashed_block(
+    fixture: &LmdbTestFixture,
+    height: u8,
+    parent_hash: BlockHash,
+    state_root_hash: Digest,
+) -> Block {
+    let body = BlockBody::new(vec![]);
+    let (_, mut mock_header) = mock_block_header(height);
+    mock_header.height = height as u64;
+    mock_header.parent_hash = parent_hash;
+    mock_header.state_root_hash = state_root_hash;
+    mock_header.body_hash = body.hash(HashingAlgorithmVersion::V1);
+    let header: BlockHeader =
+        bincode::deserialize(&bincode::serialize(&mock_header).unwrap()).unwrap();
+    let block_hash = header.hash();
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        header.body_hash(),
+        &bincode::serialize(&body).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    bincode::serialize(&(block_hash, header, body))
+        .and_then(|raw_block| bincode::deserialize(&raw_block))
+        .unwrap()
+}
+
+fn put_sync_state(fixture: &LmdbTestFixture, network_name: &str, state: &LinearChainSyncState) {
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(StateStoreDatabase::db_name())).unwrap(),
+        &WellKnownKey::LinearChainSync(network_name.to_string()).to_bytes(),
+        &bincode::serialize(state).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+}
+
+fn sync_summaries(fixture: &LmdbTestFixture) -> Vec<LinearChainSyncSummary> {
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    let summary = state_store::read_state_store(&LmdbReader::new(&txn))
+        .unwrap()
+        .unwrap();
+    txn.commit().unwrap();
+    summary.linear_chain_sync
+}
+
+#[test]
+fn prune_above_should_reset_sync_markers_above_height() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            StateStoreDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let blocks: Vec<Block> = (0..4u8).fold(vec![], |mut blocks, height| {
+        let parent_hash = blocks
+            .last()
+            .map(|parent: &Block| *parent.hash())
+            .unwrap_or_default();
+        blocks.push(put_hashed_block(
+            &fixture,
+            height,
+            parent_hash,
+            Digest::hash([height]),
+        ));
+        blocks
+    });
+
+    // The synchronizer of network "a" executed up to block 1 but saw block
+    // 3, the one of "b" last switched at height 2, and the one of "c" is
+    // done at block 1.
+    put_sync_state(
+        &fixture,
+        "a",
+        &LinearChainSyncState::SyncingDescendants {
+            trusted_hash: *blocks[0].hash(),
+            latest_block: Box::new(blocks[1].clone()),
+            highest_block_seen: 3,
+            last_switch_block_height: None,
+        },
+    );
+    put_sync_state(
+        &fixture,
+        "b",
+        &LinearChainSyncState::SyncingTrustedHash {
+            trusted_hash: *blocks[0].hash(),
+            highest_block_header: None,
+            highest_block_seen: 1,
+            linear_chain: vec![],
+            latest_block: Box::new(None),
+            last_switch_block_height: Some(2),
+        },
+    );
+    put_sync_state(
+        &fixture,
+        "c",
+        &LinearChainSyncState::Done(Some(Box::new(blocks[1].clone()))),
+    );
+
+    prune_above(fixture.tmp_dir.path(), 1, false).unwrap();
+
+    let summaries = sync_summaries(&fixture);
+    let statuses: Vec<(&str, SyncStatus)> = summaries
+        .iter()
+        .map(|summary| (summary.network_name.as_str(), summary.status))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("a", SyncStatus::None),
+            ("b", SyncStatus::None),
+            ("c", SyncStatus::Done)
+        ]
+    );
+    assert_eq!(summaries[2].latest_block_hash, Some(*blocks[1].hash()));
+    assert_eq!(summaries[2].latest_block_height, Some(1));
+}
+
+#[test]
+fn rollback_should_only_keep_tries_under_remaining_blocks() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let data = create_data();
+    let max_db_size = DEFAULT_MAX_DB_SIZE.parse().unwrap();
+    let trie_dir = tempdir().unwrap();
+    {
+        use casper_execution_engine::storage::transaction_source::Transaction as TrieTransaction;
+
+        let env = LmdbEnvironment::new(trie_dir.path(), max_db_size, 512, true).unwrap();
+        let store = LmdbTrieStore::new(&env, None, DatabaseFlags::empty()).unwrap();
+        let mut txn = env.create_read_write_txn().unwrap();
+        store
+            .put_many(&mut txn, data.iter().map(Into::into))
+            .unwrap();
+        TrieTransaction::commit(txn).unwrap();
+    }
+
+    // Block 0 is at leaf 1, block 1 at node 2, with leaves 2 and 3 under it,
+    // and block 2 at node 1, the root of the whole trie.
+    let block_0 = put_hashed_block(&fixture, 0, BlockHash::default(), data[0].0);
+    let block_1 = put_hashed_block(&fixture, 1, *block_0.hash(), data[4].0);
+    put_hashed_block(&fixture, 2, *block_1.hash(), data[3].0);
+    // The node's storage, which the compaction reads the blocks through,
+    // moves the files of a node's directory to a network subdirectory in
+    // tests, and fails unless all of them are there. The trie store is kept
+    // apart so that it isn't moved.
+    for file_name in ["data.lmdb", "data.lmdb-lock", "sse_index"] {
+        fs::write(fixture.tmp_dir.path().join(file_name), []).unwrap();
+    }
+
+    // These are the steps of `run`.
+    prune_above(fixture.tmp_dir.path(), 1, false).unwrap();
+    let destination_dir = tempdir().unwrap();
+    let report = trie_compact::trie_compact(
+        fixture.tmp_dir.path(),
+        trie_dir.path(),
+        destination_dir.path(),
+        DestinationOptions::New,
+        max_db_size,
+    )
+    .unwrap();
+    assert_eq!(report.state_roots, 2);
+
+    // Node 1 and the extension under it are only reachable from block 2.
+    let trie_env = TrieEnv::open(destination_dir.path()).unwrap();
+    let trie_db = trie_env.db().unwrap();
+    trie_env
+        .read(|txn| {
+            for (idx, test_data) in data.iter().enumerate() {
+                let is_kept = get_optional(txn, trie_db, &test_data.0)?.is_some();
+                assert_eq!(is_kept, ![3, 5].contains(&idx), "trie {}", idx);
+            }
+            Ok::<_, LmdbError>(())
+        })
+        .unwrap();
+}
@@ -0,0 +1,90 @@
+mod handler;
+#[cfg(test)]
+mod tests;
+
+use std::{net::AddrParseError, path::Path};
+
+use bincode::Error as BincodeError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError, http_server::Error as HttpServerError,
+    merkle_body::Error as MerkleBodyError,
+};
+
+pub const COMMAND_NAME: &str = "rpc-shim";
+const ADDRESS: &str = "address";
+const DB_PATH: &str = "db-path";
+
+/// Errors encountered when serving JSON-RPC requests from the storage
+/// database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error indexing the blocks by height.
+    #[error("Error indexing blocks: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Invalid listening address.
+    #[error("Invalid address: {0}")]
+    InvalidAddress(#[from] AddrParseError),
+    /// Error reading a merkle block body.
+    #[error("Error reading merkle block body: {0}")]
+    MerkleBody(#[from] MerkleBodyError),
+    /// HTTP server error.
+    #[error("{0}")]
+    Server(#[from] HttpServerError),
+    /// Parsing error on the entry with the given key in the database.
+    #[error("Error parsing entry {1} in {0} DB: {2}")]
+    ValueParsing(&'static str, String, BincodeError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Address,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Starts a JSON-RPC server implementing the `chain_get_block` and \
+            `info_get_deploy` methods of the node's RPC API on top of a \
+            storage database.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(ADDRESS)
+                .display_order(DisplayOrder::Address as usize)
+                .short('a')
+                .long(ADDRESS)
+                .takes_value(true)
+                .value_name("IP:PORT")
+                .default_value("127.0.0.1:7777")
+                .help(
+                    "Address on which the server will listen. Requests are \
+                    accepted on the `/rpc` path, as on a node.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let address = matches
+        .value_of(ADDRESS)
+        .expect("should have a default")
+        .parse()?;
+    handler::serve(path, address)
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::BTreeMap, net::SocketAddr, path::Path, result::Result, str::FromStr, sync::Arc,
+};
+
+use casper_node::{
+    rpcs::{
+        chain::{BlockIdentifier, GetBlockParams, GetBlockResult},
+        info::{GetDeployParams, GetDeployResult, JsonExecutionResult},
+    },
+    types::{
+        Block, BlockHash, BlockHeader, BlockSignatures, Deploy, DeployMetadata,
+        DeployWithFinalizedApprovals, FinalizedApprovals, JsonBlock,
+    },
+};
+use casper_types::ProtocolVersion;
+use hyper::{body, header::CONTENT_TYPE, Body, Method, Request, Response, StatusCode};
+use lmdb::{Database as LmdbDatabase, Error as LmdbError, Transaction};
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database,
+            DeployDatabase, DeployMetadataDatabase, FinalizedApprovalsDatabase, StorageEnv,
+        },
+        http_server, merkle_body,
+        storage::LmdbReader,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+const JSON_RPC_VERSION: &str = "2.0";
+const RPC_PATH: &str = "/rpc";
+
+// Standard JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+// Error codes used by the node for the methods we implement.
+const NO_SUCH_DEPLOY: i64 = -32000;
+const NO_SUCH_BLOCK: i64 = -32001;
+const INTERNAL_ERROR: i64 = -32063;
+
+/// A JSON-RPC error object.
+#[derive(Debug)]
+pub(super) struct RpcError {
+    pub(super) code: i64,
+    pub(super) message: String,
+}
+
+impl RpcError {
+    fn new<M: ToString>(code: i64, message: M) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(error: Error) -> Self {
+        Self::new(INTERNAL_ERROR, error)
+    }
+}
+
+impl From<LmdbError> for RpcError {
+    fn from(error: LmdbError) -> Self {
+        Error::from(error).into()
+    }
+}
+
+/// Returns the RPC API version reported by the node this tool was built
+/// against.
+fn api_version() -> ProtocolVersion {
+    let node_version = env!("CASPER_NODE_VERSION");
+    // Strip any pre-release suffix such as `-alt`.
+    let version = node_version.split('-').next().unwrap_or(node_version);
+    ProtocolVersion::from_str(version).unwrap_or(ProtocolVersion::V1_0_0)
+}
+
+/// Deserializes the `params` of a request, if any.
+fn parse_params<T: DeserializeOwned>(maybe_params: Option<Value>) -> Result<Option<T>, RpcError> {
+    match maybe_params {
+        None | Some(Value::Null) => Ok(None),
+        Some(params) => serde_json::from_value(params)
+            .map(Some)
+            .map_err(|json_err| RpcError::new(INVALID_PARAMS, json_err)),
+    }
+}
+
+fn hex_key(key: &[u8]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Reads and deserializes the value stored under `key`, or `None` if there
+/// is no such entry.
+fn get_value<T: Transaction, K: AsRef<[u8]>, V: DeserializeOwned>(
+    txn: &T,
+    db: LmdbDatabase,
+    db_name: &'static str,
+    key: &K,
+) -> Result<Option<V>, Error> {
+    match txn.get(db, key) {
+        Ok(raw_val) => bincode::deserialize(raw_val)
+            .map(Some)
+            .map_err(|bincode_err| {
+                Error::ValueParsing(db_name, hex_key(key.as_ref()), bincode_err)
+            }),
+        Err(LmdbError::NotFound) => Ok(None),
+        Err(lmdb_err) => Err(lmdb_err.into()),
+    }
+}
+
+/// Everything the server needs to answer requests.
+pub(super) struct RpcState {
+    env: StorageEnv,
+    /// Block hashes indexed by height, built when the server starts.
+    heights: BTreeMap<u64, BlockHash>,
+}
+
+impl RpcState {
+    pub(super) fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, Error> {
+        let env = StorageEnv::open(&db_path)?;
+
+        let heights = http_server::index_heights(&env)?;
+
+        Ok(Self { env, heights })
+    }
+
+    fn get_block(&self, maybe_params: Option<Value>) -> Result<GetBlockResult, RpcError> {
+        let maybe_params: Option<GetBlockParams> = parse_params(maybe_params)?;
+        let maybe_block_hash = match maybe_params.map(|params| params.block_identifier) {
+            Some(BlockIdentifier::Hash(block_hash)) => Some(block_hash),
+            Some(BlockIdentifier::Height(height)) => self.heights.get(&height).copied(),
+            None => self.heights.values().next_back().copied(),
+        };
+        let block_hash =
+            maybe_block_hash.ok_or_else(|| RpcError::new(NO_SUCH_BLOCK, "block not known"))?;
+
+        let header_db = self.env.db::<BlockHeaderDatabase>()?;
+        let body_db = self.env.db::<BlockBodyDatabase>()?;
+        let metadata_db = self.env.db::<BlockMetadataDatabase>()?;
+        let txn = self.env.begin_ro_txn()?;
+        let header: BlockHeader =
+            get_value(&txn, header_db, BlockHeaderDatabase::db_name(), &block_hash)?
+                .ok_or_else(|| RpcError::new(NO_SUCH_BLOCK, "block not known"))?;
+        // Bodies stored under the merkle scheme are reconstructed from their
+        // parts.
+        let maybe_body: Option<BlockBody> = match get_value(
+            &txn,
+            body_db,
+            BlockBodyDatabase::db_name(),
+            header.body_hash(),
+        )? {
+            Some(body) => Some(body),
+            None => merkle_body::read_merkle_body(&LmdbReader::new(&txn), header.body_hash())
+                .map_err(Error::from)?
+                .map(|merkle_body| merkle_body.body),
+        };
+        let body =
+            maybe_body.ok_or_else(|| RpcError::new(NO_SUCH_BLOCK, "block body not known"))?;
+        let maybe_signatures: Option<BlockSignatures> = get_value(
+            &txn,
+            metadata_db,
+            BlockMetadataDatabase::db_name(),
+            &block_hash,
+        )?;
+
+        // `Block` can't be constructed from its parts outside of the node,
+        // but its serialized form is just the concatenation of its fields.
+        let block: Block = bincode::serialize(&(block_hash, header, body))
+            .and_then(|raw_block| bincode::deserialize(&raw_block))
+            .map_err(|bincode_err| RpcError::new(INTERNAL_ERROR, bincode_err))?;
+
+        Ok(GetBlockResult {
+            api_version: api_version(),
+            block: Some(JsonBlock::new(block, maybe_signatures)),
+        })
+    }
+
+    fn get_deploy(&self, maybe_params: Option<Value>) -> Result<GetDeployResult, RpcError> {
+        let params: GetDeployParams = parse_params(maybe_params)?
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "missing params"))?;
+
+        let deploys_db = self.env.db::<DeployDatabase>()?;
+        let maybe_approvals_db = self.env.optional_db::<FinalizedApprovalsDatabase>()?;
+        let metadata_db = self.env.db::<DeployMetadataDatabase>()?;
+        let txn = self.env.begin_ro_txn()?;
+        let deploy: Deploy = get_value(
+            &txn,
+            deploys_db,
+            DeployDatabase::db_name(),
+            &params.deploy_hash,
+        )?
+        .ok_or_else(|| RpcError::new(NO_SUCH_DEPLOY, "deploy not known"))?;
+
+        let deploy = match maybe_approvals_db {
+            Some(approvals_db) if params.finalized_approvals => {
+                let maybe_approvals: Option<FinalizedApprovals> = get_value(
+                    &txn,
+                    approvals_db,
+                    FinalizedApprovalsDatabase::db_name(),
+                    &params.deploy_hash,
+                )?;
+                DeployWithFinalizedApprovals::new(deploy, maybe_approvals).into_naive()
+            }
+            _ => deploy,
+        };
+
+        let execution_results = get_value::<_, _, DeployMetadata>(
+            &txn,
+            metadata_db,
+            DeployMetadataDatabase::db_name(),
+            &params.deploy_hash,
+        )?
+        .map(|metadata| {
+            metadata
+                .execution_results
+                .into_iter()
+                .map(|(block_hash, result)| JsonExecutionResult { block_hash, result })
+                .collect()
+        })
+        .unwrap_or_default();
+
+        Ok(GetDeployResult {
+            api_version: api_version(),
+            deploy,
+            execution_results,
+        })
+    }
+
+    fn call(&self, method: &str, maybe_params: Option<Value>) -> Result<Value, RpcError> {
+        fn to_value<T: Serialize>(result: T) -> Result<Value, RpcError> {
+            serde_json::to_value(result).map_err(|json_err| RpcError::new(INTERNAL_ERROR, json_err))
+        }
+        match method {
+            "chain_get_block" => to_value(self.get_block(maybe_params)?),
+            "info_get_deploy" => to_value(self.get_deploy(maybe_params)?),
+            _ => Err(RpcError::new(METHOD_NOT_FOUND, "Method not found")),
+        }
+    }
+
+    /// Answers a raw JSON-RPC request.
+    pub(super) fn handle(&self, raw_request: &[u8]) -> Value {
+        let request: Value = match serde_json::from_slice(raw_request) {
+            Ok(request) => request,
+            Err(json_err) => {
+                return error_response(Value::Null, RpcError::new(PARSE_ERROR, json_err))
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = match request.get("method").and_then(Value::as_str) {
+            Some(method) => method,
+            None => {
+                return error_response(id, RpcError::new(INVALID_REQUEST, "missing method"));
+            }
+        };
+        match self.call(method, request.get("params").cloned()) {
+            Ok(result) => json!({ "jsonrpc": JSON_RPC_VERSION, "id": id, "result": result }),
+            Err(rpc_error) => error_response(id, rpc_error),
+        }
+    }
+}
+
+fn error_response(id: Value, rpc_error: RpcError) -> Value {
+    json!({
+        "jsonrpc": JSON_RPC_VERSION,
+        "id": id,
+        "error": { "code": rpc_error.code, "message": rpc_error.message },
+    })
+}
+
+async fn handle(state: Arc<RpcState>, request: Request<Body>) -> Response<Body> {
+    let response = Response::builder();
+    if request.uri().path() != RPC_PATH {
+        return response
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty())
+            .expect("should build response");
+    }
+    if request.method() != Method::POST {
+        return response
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .body(Body::empty())
+            .expect("should build response");
+    }
+    let raw_request = match body::to_bytes(request.into_body()).await {
+        Ok(raw_request) => raw_request,
+        Err(hyper_err) => {
+            warn!("Error reading request: {}", hyper_err);
+            return response
+                .status(StatusCode::BAD_REQUEST)
+                .body(Body::empty())
+                .expect("should build response");
+        }
+    };
+    let reply = tokio::task::spawn_blocking(move || state.handle(&raw_request))
+        .await
+        .unwrap_or_else(|join_err| {
+            error_response(Value::Null, RpcError::new(INTERNAL_ERROR, join_err))
+        });
+    response
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(reply.to_string()))
+        .expect("should build response")
+}
+
+pub(super) fn serve<P: AsRef<Path>>(db_path: P, address: SocketAddr) -> Result<(), Error> {
+    let state = Arc::new(RpcState::new(db_path)?);
+    http_server::serve(address, RPC_PATH, state, handle)?;
+    Ok(())
+}
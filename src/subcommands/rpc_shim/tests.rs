@@ -0,0 +1,186 @@
+use casper_node::{
+    rpcs::{chain::GetBlockResult, docs::DocExample, info::GetDeployResult},
+    types::{BlockHash, Deploy, FinalizedApprovals},
+};
+use lmdb::{Transaction, WriteFlags};
+use serde_json::{json, Value};
+
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database, DeployDatabase,
+        DeployMetadataDatabase, FinalizedApprovalsDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::{
+        execution_results_summary::block_body::BlockBody as MockBlockBody,
+        purge_signatures::block_signatures::BlockSignatures as MockBlockSignatures,
+        rpc_shim::handler::RpcState,
+    },
+    test_utils::{mock_block_header, mock_deploy_metadata, LmdbTestFixture, MockBlockHeader},
+};
+
+fn call(state: &RpcState, method: &str, params: Value) -> Value {
+    let request = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let response = state.handle(request.to_string().as_bytes());
+    assert_eq!(response["id"], 1);
+    response
+}
+
+fn setup() -> (LmdbTestFixture, Vec<(BlockHash, MockBlockHeader)>, Deploy) {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let blocks: Vec<(BlockHash, MockBlockHeader)> = (0..2u8)
+        .map(|idx| {
+            let (block_hash, mut block_header) = mock_block_header(idx);
+            block_header.height = idx as u64;
+            (block_hash, block_header)
+        })
+        .collect();
+    let deploy = Deploy::doc_example().clone();
+    {
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        for (block_hash, block_header) in blocks.iter() {
+            txn.put(
+                *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+                block_hash,
+                &bincode::serialize(block_header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+            txn.put(
+                *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+                &block_header.body_hash,
+                &bincode::serialize(&MockBlockBody::new(vec![*deploy.id()])).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        // Only the first block has signatures.
+        txn.put(
+            *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+            &blocks[0].0,
+            &bincode::serialize(&MockBlockSignatures::new(blocks[0].0, 0.into())).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            deploy.id(),
+            &bincode::serialize(&deploy).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            deploy.id(),
+            &bincode::serialize(&mock_deploy_metadata(&[blocks[1].0])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture
+                .db(Some(FinalizedApprovalsDatabase::db_name()))
+                .unwrap(),
+            deploy.id(),
+            &bincode::serialize(&FinalizedApprovals::new(Default::default())).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    }
+    (fixture, blocks, deploy)
+}
+
+#[test]
+fn chain_get_block_should_succeed() {
+    let (fixture, blocks, deploy) = setup();
+    let state = RpcState::new(fixture.tmp_dir.path()).unwrap();
+
+    // Without params, the highest block is returned.
+    let response = call(&state, "chain_get_block", Value::Null);
+    let result: GetBlockResult = serde_json::from_value(response["result"].clone()).unwrap();
+    let block = result.block.unwrap();
+    assert_eq!(block.hash, blocks[1].0);
+    assert_eq!(block.deploy_hashes(), &vec![*deploy.id()]);
+    assert!(block.proofs.is_empty());
+
+    let response = call(
+        &state,
+        "chain_get_block",
+        json!({ "block_identifier": { "Height": 0 } }),
+    );
+    let result: GetBlockResult = serde_json::from_value(response["result"].clone()).unwrap();
+    let block = result.block.unwrap();
+    assert_eq!(block.hash, blocks[0].0);
+
+    let response = call(
+        &state,
+        "chain_get_block",
+        json!({ "block_identifier": { "Hash": blocks[1].0 } }),
+    );
+    let result: GetBlockResult = serde_json::from_value(response["result"].clone()).unwrap();
+    assert_eq!(result.block.unwrap().hash, blocks[1].0);
+
+    let response = call(
+        &state,
+        "chain_get_block",
+        json!({ "block_identifier": { "Height": 2 } }),
+    );
+    assert_eq!(response["error"]["code"], -32001);
+}
+
+#[test]
+fn info_get_deploy_should_succeed() {
+    let (fixture, blocks, deploy) = setup();
+    let state = RpcState::new(fixture.tmp_dir.path()).unwrap();
+
+    let response = call(
+        &state,
+        "info_get_deploy",
+        json!({ "deploy_hash": deploy.id() }),
+    );
+    let result: GetDeployResult = serde_json::from_value(response["result"].clone()).unwrap();
+    assert_eq!(result.deploy, deploy);
+    assert_eq!(result.execution_results.len(), 1);
+    assert_eq!(result.execution_results[0].block_hash, blocks[1].0);
+
+    // The stored finalized approvals are empty, so they should replace the
+    // original ones.
+    let response = call(
+        &state,
+        "info_get_deploy",
+        json!({ "deploy_hash": deploy.id(), "finalized_approvals": true }),
+    );
+    let result: GetDeployResult = serde_json::from_value(response["result"].clone()).unwrap();
+    assert!(result.deploy.approvals().is_empty());
+
+    let response = call(
+        &state,
+        "info_get_deploy",
+        json!({ "deploy_hash": blocks[0].0 }),
+    );
+    assert_eq!(response["error"]["code"], -32000);
+}
+
+#[test]
+fn invalid_requests_should_fail() {
+    let (fixture, _blocks, _deploy) = setup();
+    let state = RpcState::new(fixture.tmp_dir.path()).unwrap();
+
+    let response = call(&state, "state_get_item", Value::Null);
+    assert_eq!(response["error"]["code"], -32601);
+    let response = call(&state, "info_get_deploy", Value::Null);
+    assert_eq!(response["error"]["code"], -32602);
+    let response = call(&state, "chain_get_block", json!({ "block_identifier": 1 }));
+    assert_eq!(response["error"]["code"], -32602);
+    let response = state.handle(b"not json");
+    assert_eq!(response["error"]["code"], -32700);
+}
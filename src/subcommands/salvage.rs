@@ -0,0 +1,85 @@
+pub(crate) mod copy;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use super::scan_pages::Error as ScanPagesError;
+use crate::common::stamp::Error as StampError;
+
+pub const COMMAND_NAME: &str = "salvage";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+
+/// Errors encountered when salvaging the entries of a storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error operating the output database.
+    #[error("Error operating the output database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error creating the output directory.
+    #[error("Error creating the output: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the pages of the source database.
+    #[error("Error reading the source database: {0}")]
+    Scan(#[from] ScanPagesError),
+    /// Error reading the size of the source database.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+    /// Error stamping the output.
+    #[error("Error stamping the output: {0}")]
+    Stamp(#[from] StampError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Copies every entry of a possibly corrupt storage database which \
+            can still be read and decoded into a fresh database. Entries under \
+            damaged pages and entries which can't be decoded are skipped and \
+            logged.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .required(true)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("OUTPUT_DB_PATH")
+                .help(
+                    "Path of the directory where the program will output the \
+                    salvaged `storage.lmdb` file. The directory must not exist \
+                    when running this command.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let output = Path::new(matches.value_of(OUTPUT).expect("should have output arg"));
+    copy::salvage(db_path, output).map(|_| ())
+}
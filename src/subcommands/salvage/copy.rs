@@ -0,0 +1,162 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::ErrorKind,
+    path::Path,
+    result::Result,
+};
+
+use lmdb::{
+    Database, DatabaseFlags, Environment, Error as LmdbError, RwTransaction, Transaction,
+    WriteFlags,
+};
+use log::{info, warn};
+
+use crate::{
+    common::{
+        db::{StorageEnv, STORAGE_FILE_NAME},
+        stamp::{self, Stamp},
+    },
+    subcommands::{check, scan_pages::scan},
+};
+
+use super::Error;
+
+/// Number of entries written to the output database per transaction.
+const COMMIT_INTERVAL: usize = 10_000;
+
+/// Counts of the entries of a database found while salvaging.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct SalvagedDb {
+    pub(crate) copied: usize,
+    /// Entries which were read but couldn't be decoded.
+    pub(crate) skipped: usize,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SalvageReport {
+    pub(crate) databases: BTreeMap<String, SalvagedDb>,
+    /// Damaged pages of the source, whose entries couldn't be read.
+    pub(crate) damaged_pages: usize,
+}
+
+/// Writes salvaged entries to the output database, committing every
+/// `COMMIT_INTERVAL` entries to keep transactions small.
+pub(crate) struct Writer<'a> {
+    env: &'a Environment,
+    txn: Option<RwTransaction<'a>>,
+    dbs: HashMap<String, Database>,
+    pending: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub(crate) fn new(env: &'a Environment) -> Result<Self, LmdbError> {
+        Ok(Self {
+            env,
+            txn: Some(env.begin_rw_txn()?),
+            dbs: HashMap::new(),
+            pending: 0,
+        })
+    }
+
+    pub(crate) fn put(&mut self, db_name: &str, key: &[u8], value: &[u8]) -> Result<(), LmdbError> {
+        let txn = self.txn.as_mut().expect("should have a transaction");
+        let db = match self.dbs.get(db_name) {
+            Some(db) => *db,
+            None => {
+                let db = unsafe { txn.create_db(Some(db_name), DatabaseFlags::empty())? };
+                self.dbs.insert(db_name.to_string(), db);
+                db
+            }
+        };
+        txn.put(db, &key, &value, WriteFlags::empty())?;
+        self.pending += 1;
+        if self.pending == COMMIT_INTERVAL {
+            self.commit()?;
+            self.txn = Some(self.env.begin_rw_txn()?);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn commit(&mut self) -> Result<(), LmdbError> {
+        self.pending = 0;
+        match self.txn.take() {
+            Some(txn) => txn.commit(),
+            None => Ok(()),
+        }
+    }
+}
+
+fn hex_key(key: &[u8]) -> String {
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Copies every readable entry of the storage database at `db_path` to a new
+/// one at `output`, skipping the entries of known databases which can't be
+/// decoded.
+pub(crate) fn salvage<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: P2,
+) -> Result<SalvageReport, Error> {
+    let output = output.as_ref();
+    if output.exists() {
+        return Err(Error::Output(ErrorKind::AlreadyExists.into()));
+    }
+    let source_path = db_path.as_ref().join(STORAGE_FILE_NAME);
+    let map_size = fs::metadata(&source_path)
+        .map_err(|io_err| Error::Source(source_path.clone(), io_err))?
+        .len() as usize;
+    fs::create_dir_all(output)?;
+    let env = StorageEnv::create(output, map_size)?;
+
+    let mut writer = Writer::new(&env)?;
+    let mut report = SalvageReport::default();
+    let mut maybe_write_error = None;
+    let scan_report = scan::read_entries(&source_path, &mut |db_name, key, value| {
+        if maybe_write_error.is_some() {
+            return;
+        }
+        let counts = report.databases.entry(db_name.to_string()).or_default();
+        if let Some(parse) = check::entry_parser(db_name) {
+            if let Err(parsing_err) = parse(key, value) {
+                warn!(
+                    "Skipping entry {} of {} database: {}",
+                    hex_key(key),
+                    db_name,
+                    parsing_err
+                );
+                counts.skipped += 1;
+                return;
+            }
+        }
+        match writer.put(db_name, key, value) {
+            Ok(()) => counts.copied += 1,
+            Err(lmdb_err) => maybe_write_error = Some(lmdb_err),
+        }
+    })?;
+    if let Some(lmdb_err) = maybe_write_error {
+        return Err(lmdb_err.into());
+    }
+    writer.commit()?;
+
+    Stamp {
+        chain_name: stamp::chain_name(&db_path)?,
+        ..Default::default()
+    }
+    .write(output)?;
+
+    report.damaged_pages = scan_report.damaged_pages.len();
+    for (db_name, counts) in report.databases.iter() {
+        info!(
+            "{} database: copied {} entries, skipped {}.",
+            db_name, counts.copied, counts.skipped
+        );
+    }
+    if report.damaged_pages > 0 {
+        warn!(
+            "Entries under {} damaged pages couldn't be read.",
+            report.damaged_pages
+        );
+    }
+    Ok(report)
+}
@@ -0,0 +1,101 @@
+use std::{fs::OpenOptions, os::unix::fs::FileExt};
+
+use lmdb::{Transaction, WriteFlags};
+
+use super::copy::{salvage, SalvagedDb};
+use crate::{
+    common::db::{self, BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+    subcommands::scan_pages::scan,
+    test_utils::{mock_block_header, LmdbTestFixture},
+};
+
+const CUSTOM_DB_NAME: &str = "custom";
+const DAMAGED_DB_NAME: &str = "damaged";
+
+#[test]
+fn salvage_should_copy_readable_entries() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            CUSTOM_DB_NAME,
+            DAMAGED_DB_NAME,
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let custom_db = *fixture.db(Some(CUSTOM_DB_NAME)).unwrap();
+    let damaged_db = *fixture.db(Some(DAMAGED_DB_NAME)).unwrap();
+    let (first_hash, first_header) = mock_block_header(0);
+    let (second_hash, second_header) = mock_block_header(1);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        header_db,
+        &first_hash,
+        &bincode::serialize(&first_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        header_db,
+        &second_hash,
+        &bincode::serialize(&second_header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    // An entry which can't be decoded as a block header.
+    txn.put(header_db, &[0xaau8; 32], &[0u8; 3], WriteFlags::empty())
+        .unwrap();
+    // Entries of unknown databases are copied as they are.
+    txn.put(custom_db, b"key", b"value", WriteFlags::empty())
+        .unwrap();
+    txn.put(damaged_db, b"key", b"value", WriteFlags::empty())
+        .unwrap();
+    txn.commit().unwrap();
+    fixture.env.sync(true).unwrap();
+
+    // Corrupt the only page of the damaged database.
+    let scan_report = scan::scan_pages(&fixture.file_path).unwrap();
+    let damaged_root = scan_report
+        .databases
+        .iter()
+        .find(|scan| scan.name == DAMAGED_DB_NAME)
+        .and_then(|scan| scan.root)
+        .unwrap();
+    let file = OpenOptions::new()
+        .write(true)
+        .open(&fixture.file_path)
+        .unwrap();
+    file.write_all_at(&[0xff; 16], damaged_root * scan_report.page_size as u64)
+        .unwrap();
+    drop(file);
+
+    let out_dir = tempfile::tempdir().unwrap();
+    let output = out_dir.path().join("salvaged");
+    let report = salvage(fixture.tmp_dir.path(), &output).unwrap();
+    assert_eq!(report.damaged_pages, 1);
+    assert_eq!(
+        report.databases.get(BlockHeaderDatabase::db_name()),
+        Some(&SalvagedDb {
+            copied: 2,
+            skipped: 1
+        })
+    );
+    assert_eq!(
+        report.databases.get(CUSTOM_DB_NAME),
+        Some(&SalvagedDb {
+            copied: 1,
+            skipped: 0
+        })
+    );
+    assert!(!report.databases.contains_key(DAMAGED_DB_NAME));
+
+    let env = db::db_env(output.join(STORAGE_FILE_NAME)).unwrap();
+    let txn = env.begin_ro_txn().unwrap();
+    let header_db = unsafe { txn.open_db(Some(BlockHeaderDatabase::db_name())).unwrap() };
+    assert!(txn.get(header_db, &first_hash).is_ok());
+    assert!(txn.get(header_db, &second_hash).is_ok());
+    assert!(txn.get(header_db, &[0xaau8; 32]).is_err());
+    let custom_db = unsafe { txn.open_db(Some(CUSTOM_DB_NAME)).unwrap() };
+    assert_eq!(txn.get(custom_db, b"key").unwrap(), b"value");
+    txn.commit().unwrap();
+}
@@ -0,0 +1,139 @@
+pub(crate) mod scan;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use log::info;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+    output::{self, Compression, OutputWriter},
+};
+
+pub const COMMAND_NAME: &str = "scan-pages";
+const DB_PATH: &str = "db-path";
+const FILE: &str = "file";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when scanning the pages of an LMDB file.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// The file is damaged. The report lists the problems found.
+    #[error("Found {0} problems, affecting {1} pages")]
+    Damaged(usize, usize),
+    /// Neither of the meta pages of the file is valid, so its trees can't be
+    /// found.
+    #[error("No valid meta page found")]
+    NoValidMeta,
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Error reading the scanned file.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    File,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Checks the page headers, b-tree structure and free list of an \
+            LMDB file by reading its pages directly, and outputs a report \
+            listing the damaged pages in JSON format. Unlike `check`, this \
+            doesn't crash on page-level corruption.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the LMDB files."),
+        )
+        .arg(
+            Arg::new(FILE)
+                .display_order(DisplayOrder::File as usize)
+                .short('f')
+                .long(FILE)
+                .takes_value(true)
+                .value_name("FILE")
+                .possible_values(["storage", "trie"])
+                .default_value("storage")
+                .help(
+                    "Which file to scan: `storage.lmdb` or the global state \
+                    `data.lmdb`.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let file_name = match matches.value_of(FILE).expect("should have a default") {
+        "trie" => TRIE_STORE_FILE_NAME,
+        _ => STORAGE_FILE_NAME,
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = scan::scan_pages(db_path.join(file_name))?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+
+    if !report.problems.is_empty() {
+        return Err(Error::Damaged(
+            report.problems.len(),
+            report.damaged_pages.len(),
+        ));
+    }
+    info!("No damaged pages found.");
+    Ok(())
+}
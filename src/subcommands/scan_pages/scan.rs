@@ -0,0 +1,727 @@
+//! Reader for the on-disk format of LMDB 0.9 data files, as written on 64-bit
+//! little-endian platforms. Pages are read with plain file reads rather than
+//! through a memory map, so damaged pages are reported instead of crashing
+//! the process.
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::Error as IoError,
+    os::unix::fs::FileExt,
+    path::{Path, PathBuf},
+    result::Result,
+};
+
+use log::{info, warn};
+use serde::Serialize;
+
+use super::Error;
+
+const PAGE_HEADER_SIZE: usize = 16;
+const NODE_HEADER_SIZE: usize = 8;
+const DB_RECORD_SIZE: usize = 48;
+const META_SIZE: usize = 2 * DB_RECORD_SIZE + 40;
+const META_MAGIC: u32 = 0xBEEF_C0DE;
+const META_VERSION: u32 = 1;
+const MIN_PAGE_SIZE: u32 = 512;
+const MAX_PAGE_SIZE: u32 = 0x10000;
+/// Page size assumed when looking for the second meta page because the first
+/// one is unreadable.
+const META_PAGE_SIZE_CANDIDATES: [u32; 5] = [4096, 8192, 16384, 32768, 65536];
+const INVALID_PAGE: u64 = u64::MAX;
+/// Name under which the free page database is reported.
+const FREE_DB_NAME: &str = "(free pages)";
+/// Name under which the main, unnamed, database is reported.
+const MAIN_DB_NAME: &str = "(main)";
+
+// Page flags.
+const P_BRANCH: u16 = 0x01;
+const P_LEAF: u16 = 0x02;
+const P_OVERFLOW: u16 = 0x04;
+const P_META: u16 = 0x08;
+const P_LEAF2: u16 = 0x20;
+const PAGE_KIND_MASK: u16 = P_BRANCH | P_LEAF | P_OVERFLOW | P_META;
+
+// Node flags.
+const F_BIGDATA: u16 = 0x01;
+const F_SUBDATA: u16 = 0x02;
+const F_DUPDATA: u16 = 0x04;
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(bytes[offset..offset + 2].try_into().unwrap())
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// Header of a database tree, as stored in the meta pages for the free page
+/// and main databases, and in the main database for named ones.
+#[derive(Clone, Copy, Debug)]
+struct DbRecord {
+    /// Page size for the free page database, key size for `MDB_DUPFIXED`
+    /// ones.
+    pad: u32,
+    depth: u16,
+    branch_pages: u64,
+    leaf_pages: u64,
+    overflow_pages: u64,
+    entries: u64,
+    root: u64,
+}
+
+impl DbRecord {
+    fn parse(bytes: &[u8]) -> Self {
+        Self {
+            pad: read_u32(bytes, 0),
+            depth: read_u16(bytes, 6),
+            branch_pages: read_u64(bytes, 8),
+            leaf_pages: read_u64(bytes, 16),
+            overflow_pages: read_u64(bytes, 24),
+            entries: read_u64(bytes, 32),
+            root: read_u64(bytes, 40),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Meta {
+    free: DbRecord,
+    main: DbRecord,
+    last_page: u64,
+    txn_id: u64,
+}
+
+impl Meta {
+    /// Parses a meta page, returning why it's invalid if it is.
+    fn parse(page: &[u8], page_number: u64) -> Result<Self, String> {
+        let page_header_number = read_u64(page, 0);
+        if page_header_number != page_number {
+            return Err(format!("header has page number {page_header_number}"));
+        }
+        if read_u16(page, 10) & P_META == 0 {
+            return Err("not flagged as a meta page".to_string());
+        }
+        let meta = &page[PAGE_HEADER_SIZE..];
+        let magic = read_u32(meta, 0);
+        if magic != META_MAGIC {
+            return Err(format!("invalid magic {magic:#x}"));
+        }
+        let version = read_u32(meta, 4);
+        if version != META_VERSION {
+            return Err(format!("unsupported version {version}"));
+        }
+        let free = DbRecord::parse(&meta[24..]);
+        if !free.pad.is_power_of_two() || !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&free.pad) {
+            return Err(format!("invalid page size {}", free.pad));
+        }
+        Ok(Self {
+            free,
+            main: DbRecord::parse(&meta[24 + DB_RECORD_SIZE..]),
+            last_page: read_u64(meta, 24 + 2 * DB_RECORD_SIZE),
+            txn_id: read_u64(meta, 32 + 2 * DB_RECORD_SIZE),
+        })
+    }
+
+    fn page_size(&self) -> u32 {
+        self.free.pad
+    }
+}
+
+/// Set of page numbers, stored as a bitmap.
+struct PageSet {
+    bits: Vec<u64>,
+    len: u64,
+}
+
+impl PageSet {
+    fn new(page_count: u64) -> Self {
+        Self {
+            bits: vec![0; (page_count / 64 + 1) as usize],
+            len: 0,
+        }
+    }
+
+    /// Adds `page`, returning whether it wasn't already in the set.
+    fn insert(&mut self, page: u64) -> bool {
+        let (word, bit) = ((page / 64) as usize, page % 64);
+        if self.bits[word] & (1 << bit) != 0 {
+            return false;
+        }
+        self.bits[word] |= 1 << bit;
+        self.len += 1;
+        true
+    }
+
+    fn contains(&self, page: u64) -> bool {
+        self.bits[(page / 64) as usize] & (1 << (page % 64)) != 0
+    }
+}
+
+/// Page counts and entries of a database, as found by walking its tree.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct DbScan {
+    pub(crate) name: String,
+    pub(crate) root: Option<u64>,
+    pub(crate) entries: u64,
+    pub(crate) branch_pages: u64,
+    pub(crate) leaf_pages: u64,
+    pub(crate) overflow_pages: u64,
+}
+
+/// A structural problem found in the file.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Problem {
+    /// Database whose tree the problem was found in.
+    pub(crate) database: String,
+    /// Damaged page, if the problem is specific to one.
+    pub(crate) page: Option<u64>,
+    pub(crate) description: String,
+}
+
+/// Outcome of scanning all the pages of an LMDB data file.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ScanReport {
+    pub(crate) page_size: u32,
+    pub(crate) last_page: u64,
+    pub(crate) txn_id: u64,
+    pub(crate) databases: Vec<DbScan>,
+    pub(crate) free_pages: u64,
+    /// Pages up to the last one which are neither in use nor in the free
+    /// list.
+    pub(crate) unreferenced_pages: u64,
+    pub(crate) damaged_pages: BTreeSet<u64>,
+    pub(crate) problems: Vec<Problem>,
+}
+
+/// Callback receiving the name of a database along with the key and value of
+/// each of its readable entries.
+pub(crate) type EntryVisitor<'a> = dyn FnMut(&str, &[u8], &[u8]) + 'a;
+
+/// Callback receiving the node flags, key and value of each readable entry of
+/// a tree.
+type NodeVisitor<'a> = dyn FnMut(u16, &[u8], &[u8]) + 'a;
+
+/// Leaf entry of the free page or main database, kept for further
+/// processing.
+struct LeafEntry {
+    key: Vec<u8>,
+    flags: u16,
+    data: Vec<u8>,
+}
+
+impl LeafEntry {
+    fn collect(entries: &mut Vec<LeafEntry>) -> impl FnMut(u16, &[u8], &[u8]) + '_ {
+        |flags, key, data| {
+            entries.push(LeafEntry {
+                key: key.to_vec(),
+                flags,
+                data: data.to_vec(),
+            })
+        }
+    }
+}
+
+struct Scanner {
+    file: File,
+    path: PathBuf,
+    page_size: usize,
+    last_page: u64,
+    file_pages: u64,
+    used: PageSet,
+    report: ScanReport,
+}
+
+impl Scanner {
+    fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<(), Error> {
+        self.file
+            .read_exact_at(buf, offset)
+            .map_err(|io_err| Error::Source(self.path.clone(), io_err))
+    }
+
+    fn read_page(&self, page: u64) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![0; self.page_size];
+        self.read_at(&mut buf, page * self.page_size as u64)?;
+        Ok(buf)
+    }
+
+    fn problem(&mut self, database: &str, page: Option<u64>, description: String) {
+        warn!(
+            "{}{}: {}",
+            database,
+            page.map(|page| format!(" page {page}")).unwrap_or_default(),
+            description
+        );
+        if let Some(page) = page {
+            self.report.damaged_pages.insert(page);
+        }
+        self.report.problems.push(Problem {
+            database: database.to_string(),
+            page,
+            description,
+        });
+    }
+
+    /// Marks `count` pages starting at `first` as used, returning whether
+    /// they were all valid and unused so far.
+    fn claim_pages(&mut self, database: &str, parent: Option<u64>, first: u64, count: u64) -> bool {
+        let last = first.saturating_add(count.max(1) - 1);
+        if last > self.last_page || last >= self.file_pages {
+            let description = format!(
+                "references page {last}, beyond the last page {}",
+                self.last_page.min(self.file_pages.saturating_sub(1))
+            );
+            self.problem(database, parent, description);
+            return false;
+        }
+        for page in first..=last {
+            if !self.used.insert(page) {
+                self.problem(
+                    database,
+                    Some(page),
+                    "referenced more than once".to_string(),
+                );
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks the overflow pages holding a value of `size` bytes and returns
+    /// the value if `read` is set.
+    fn walk_overflow(
+        &mut self,
+        database: &str,
+        parent: u64,
+        first: u64,
+        size: usize,
+        read: bool,
+        scan: &mut DbScan,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        if !self.claim_pages(database, Some(parent), first, 1) {
+            return Ok(None);
+        }
+        let header = self.read_page(first)?;
+        let flags = read_u16(&header, 10);
+        let page_count = read_u32(&header, 12) as u64;
+        let expected_count = ((PAGE_HEADER_SIZE - 1 + size) / self.page_size + 1) as u64;
+        if read_u64(&header, 0) != first || flags & PAGE_KIND_MASK != P_OVERFLOW {
+            self.problem(
+                database,
+                Some(first),
+                "not a valid overflow page".to_string(),
+            );
+            return Ok(None);
+        }
+        if page_count != expected_count {
+            self.problem(
+                database,
+                Some(first),
+                format!("spans {page_count} pages, expected {expected_count}"),
+            );
+            return Ok(None);
+        }
+        if !self.claim_pages(database, Some(first), first + 1, page_count - 1) {
+            return Ok(None);
+        }
+        scan.overflow_pages += page_count;
+        if !read {
+            return Ok(None);
+        }
+        let mut data = vec![0; size];
+        self.read_at(
+            &mut data,
+            first * self.page_size as u64 + PAGE_HEADER_SIZE as u64,
+        )?;
+        Ok(Some(data))
+    }
+
+    /// Walks the tree described by `record`, checking the structure of every
+    /// page in it. Readable leaf entries are passed to `visit`, if given.
+    fn walk_tree(
+        &mut self,
+        name: &str,
+        record: &DbRecord,
+        mut visit: Option<&mut NodeVisitor>,
+    ) -> Result<DbScan, Error> {
+        let mut scan = DbScan {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let collect = visit.is_some();
+        if record.root == INVALID_PAGE {
+            return Ok(scan);
+        }
+        scan.root = Some(record.root);
+        // Pages to visit, along with the page referencing them and their
+        // level in the tree, leaves being at level 1.
+        let mut pending = vec![(record.root, None, record.depth)];
+        while let Some((page_number, parent, level)) = pending.pop() {
+            if !self.claim_pages(name, parent, page_number, 1) {
+                continue;
+            }
+            let page = self.read_page(page_number)?;
+            let header_number = read_u64(&page, 0);
+            if header_number != page_number {
+                self.problem(
+                    name,
+                    Some(page_number),
+                    format!("header has page number {header_number}"),
+                );
+                continue;
+            }
+            let flags = read_u16(&page, 10);
+            let expected_kind = if level > 1 { P_BRANCH } else { P_LEAF };
+            if flags & PAGE_KIND_MASK != expected_kind {
+                self.problem(
+                    name,
+                    Some(page_number),
+                    format!("unexpected page flags {flags:#x} at tree level {level}"),
+                );
+                continue;
+            }
+            let lower = read_u16(&page, 12) as usize;
+            let upper = read_u16(&page, 14) as usize;
+            if lower < PAGE_HEADER_SIZE
+                || lower > upper
+                || upper > self.page_size
+                || !(lower - PAGE_HEADER_SIZE).is_multiple_of(2)
+            {
+                self.problem(
+                    name,
+                    Some(page_number),
+                    format!("invalid free space bounds {lower}..{upper}"),
+                );
+                continue;
+            }
+            let key_count = (lower - PAGE_HEADER_SIZE) / 2;
+            if expected_kind == P_BRANCH {
+                scan.branch_pages += 1;
+            } else {
+                scan.leaf_pages += 1;
+            }
+            if flags & P_LEAF2 != 0 {
+                // Keys of `MDB_DUPFIXED` sub-databases are packed without
+                // node headers.
+                if PAGE_HEADER_SIZE + key_count * record.pad as usize > self.page_size {
+                    self.problem(
+                        name,
+                        Some(page_number),
+                        format!("{key_count} keys of {} bytes overflow the page", record.pad),
+                    );
+                    continue;
+                }
+                scan.entries += key_count as u64;
+                continue;
+            }
+
+            for index in 0..key_count {
+                let offset = read_u16(&page, PAGE_HEADER_SIZE + 2 * index) as usize;
+                if offset < upper || offset + NODE_HEADER_SIZE > self.page_size {
+                    self.problem(
+                        name,
+                        Some(page_number),
+                        format!("node {index} at invalid offset {offset}"),
+                    );
+                    break;
+                }
+                let low = read_u16(&page, offset) as u64;
+                let high = read_u16(&page, offset + 2) as u64;
+                let node_flags = read_u16(&page, offset + 4);
+                let key_size = read_u16(&page, offset + 6) as usize;
+                let key_start = offset + NODE_HEADER_SIZE;
+                let data_start = key_start + key_size;
+                if data_start > self.page_size {
+                    self.problem(
+                        name,
+                        Some(page_number),
+                        format!("key of node {index} overflows the page"),
+                    );
+                    break;
+                }
+                if expected_kind == P_BRANCH {
+                    let child = low | (high << 16) | ((node_flags as u64) << 32);
+                    pending.push((child, Some(page_number), level - 1));
+                    continue;
+                }
+
+                let data_size = (low | (high << 16)) as usize;
+                let stored_size = if node_flags & F_BIGDATA != 0 {
+                    8
+                } else {
+                    data_size
+                };
+                if data_start + stored_size > self.page_size {
+                    self.problem(
+                        name,
+                        Some(page_number),
+                        format!("data of node {index} overflows the page"),
+                    );
+                    break;
+                }
+                let inline_data = &page[data_start..data_start + stored_size];
+                let data = if node_flags & F_BIGDATA != 0 {
+                    let first = read_u64(inline_data, 0);
+                    match self.walk_overflow(
+                        name,
+                        page_number,
+                        first,
+                        data_size,
+                        collect,
+                        &mut scan,
+                    )? {
+                        Some(data) => data,
+                        None if collect => continue,
+                        None => vec![],
+                    }
+                } else if collect || node_flags & (F_SUBDATA | F_DUPDATA) != 0 {
+                    inline_data.to_vec()
+                } else {
+                    vec![]
+                };
+
+                if node_flags & F_DUPDATA != 0 {
+                    if node_flags & F_SUBDATA != 0 && data.len() == DB_RECORD_SIZE {
+                        let sub_record = DbRecord::parse(&data);
+                        let sub_scan = self.walk_tree(name, &sub_record, None)?;
+                        scan.entries += sub_scan.entries;
+                        self.check_counts(&sub_record, &sub_scan, Some(page_number));
+                    } else if data.len() >= PAGE_HEADER_SIZE {
+                        // The duplicates are stored in a sub-page.
+                        let sub_lower = read_u16(&data, 12) as usize;
+                        scan.entries += (sub_lower.saturating_sub(PAGE_HEADER_SIZE) / 2) as u64;
+                    } else {
+                        self.problem(
+                            name,
+                            Some(page_number),
+                            format!("invalid duplicates of node {index}"),
+                        );
+                    }
+                    continue;
+                }
+                if node_flags & F_SUBDATA != 0 && data.len() != DB_RECORD_SIZE {
+                    self.problem(
+                        name,
+                        Some(page_number),
+                        format!("invalid sub-database record in node {index}"),
+                    );
+                    continue;
+                }
+                scan.entries += 1;
+                if let Some(visit) = visit.as_mut() {
+                    visit(node_flags, &page[key_start..data_start], &data);
+                }
+            }
+        }
+        Ok(scan)
+    }
+
+    /// Compares the page and entry counts of a tree with those recorded in
+    /// its header.
+    fn check_counts(&mut self, record: &DbRecord, scan: &DbScan, page: Option<u64>) {
+        let counts = [
+            ("entries", record.entries, scan.entries),
+            ("branch pages", record.branch_pages, scan.branch_pages),
+            ("leaf pages", record.leaf_pages, scan.leaf_pages),
+            ("overflow pages", record.overflow_pages, scan.overflow_pages),
+        ];
+        for (what, recorded, found) in counts {
+            if recorded != found {
+                self.problem(
+                    &scan.name,
+                    page,
+                    format!("{found} {what} found, {recorded} recorded"),
+                );
+            }
+        }
+    }
+}
+
+/// Reads the meta pages of the file, returning the most recent valid one.
+/// Invalid meta pages are reported as problems.
+fn read_meta(file: &File, path: &Path, report: &mut ScanReport) -> Result<Meta, Error> {
+    let read_page = |offset: u64| -> Result<Vec<u8>, IoError> {
+        let mut buf = vec![0; PAGE_HEADER_SIZE + META_SIZE];
+        file.read_exact_at(&mut buf, offset)?;
+        Ok(buf)
+    };
+    let first = read_page(0)
+        .map_err(|io_err| Error::Source(path.to_path_buf(), io_err))
+        .map(|page| Meta::parse(&page, 0))?;
+    let page_sizes = match &first {
+        Ok(meta) => vec![meta.page_size()],
+        Err(_) => META_PAGE_SIZE_CANDIDATES.to_vec(),
+    };
+    let mut second = Err("no valid meta page found".to_string());
+    for page_size in page_sizes {
+        second = match read_page(page_size as u64) {
+            Ok(page) => Meta::parse(&page, 1),
+            Err(io_err) => Err(io_err.to_string()),
+        };
+        if second.is_ok() {
+            break;
+        }
+    }
+
+    let mut valid = vec![];
+    for (page_number, maybe_meta) in [(0, first), (1, second)] {
+        match maybe_meta {
+            Ok(meta) => valid.push(meta),
+            Err(description) => {
+                warn!("meta page {page_number}: {description}");
+                report.damaged_pages.insert(page_number);
+                report.problems.push(Problem {
+                    database: "(meta)".to_string(),
+                    page: Some(page_number),
+                    description,
+                });
+            }
+        }
+    }
+    valid
+        .into_iter()
+        .max_by_key(|meta| meta.txn_id)
+        .ok_or(Error::NoValidMeta)
+}
+
+/// Walks every page reachable from the meta pages of the LMDB data file at
+/// `path`, checking page headers, b-tree structure and free list
+/// consistency.
+pub(crate) fn scan_pages<P: AsRef<Path>>(path: P) -> Result<ScanReport, Error> {
+    scan(path, None)
+}
+
+/// Like `scan_pages`, also passing every readable entry of the named
+/// databases to `visit`. Entries under damaged pages are skipped, as are
+/// those of `MDB_DUPSORT` databases.
+pub(crate) fn read_entries<P: AsRef<Path>>(
+    path: P,
+    visit: &mut EntryVisitor,
+) -> Result<ScanReport, Error> {
+    scan(path, Some(visit))
+}
+
+fn scan<P: AsRef<Path>>(
+    path: P,
+    mut visit: Option<&mut EntryVisitor>,
+) -> Result<ScanReport, Error> {
+    let path = path.as_ref().to_path_buf();
+    let file = File::open(&path).map_err(|io_err| Error::Source(path.clone(), io_err))?;
+    let file_len = file
+        .metadata()
+        .map_err(|io_err| Error::Source(path.clone(), io_err))?
+        .len();
+    let mut report = ScanReport::default();
+    let meta = read_meta(&file, &path, &mut report)?;
+    report.page_size = meta.page_size();
+    report.last_page = meta.last_page;
+    report.txn_id = meta.txn_id;
+    info!(
+        "Scanning {} pages of {} bytes as of transaction {}.",
+        meta.last_page + 1,
+        meta.page_size(),
+        meta.txn_id
+    );
+
+    let page_size = meta.page_size() as usize;
+    let mut scanner = Scanner {
+        file,
+        path,
+        page_size,
+        last_page: meta.last_page,
+        file_pages: file_len / page_size as u64,
+        used: PageSet::new(meta.last_page + 1),
+        report,
+    };
+    scanner.used.insert(0);
+    scanner.used.insert(1);
+
+    let mut main_entries = vec![];
+    let main_scan = scanner.walk_tree(
+        MAIN_DB_NAME,
+        &meta.main,
+        Some(&mut LeafEntry::collect(&mut main_entries)),
+    )?;
+    scanner.check_counts(&meta.main, &main_scan, None);
+    scanner.report.databases.push(main_scan);
+    for entry in main_entries {
+        if entry.flags & F_SUBDATA == 0 {
+            continue;
+        }
+        let name = String::from_utf8_lossy(&entry.key).to_string();
+        let record = DbRecord::parse(&entry.data);
+        let scan = match visit.as_mut() {
+            Some(visit) => scanner.walk_tree(
+                &name,
+                &record,
+                Some(&mut |_flags, key: &[u8], data: &[u8]| visit(&name, key, data)),
+            )?,
+            None => scanner.walk_tree(&name, &record, None)?,
+        };
+        scanner.check_counts(&record, &scan, None);
+        scanner.report.databases.push(scan);
+    }
+
+    let mut free_entries = vec![];
+    let free_scan = scanner.walk_tree(
+        FREE_DB_NAME,
+        &meta.free,
+        Some(&mut LeafEntry::collect(&mut free_entries)),
+    )?;
+    scanner.check_counts(&meta.free, &free_scan, None);
+    scanner.report.databases.push(free_scan);
+    let mut free = PageSet::new(meta.last_page + 1);
+    for entry in free_entries {
+        // Each value is a list of page numbers prefixed by its length.
+        let page_count = if entry.data.len() >= 8 {
+            read_u64(&entry.data, 0)
+        } else {
+            0
+        };
+        if entry.data.len() < 8 || entry.data.len() as u64 != (page_count + 1) * 8 {
+            scanner.problem(
+                FREE_DB_NAME,
+                None,
+                format!("invalid page list of {} bytes", entry.data.len()),
+            );
+            continue;
+        }
+        for index in 1..=page_count as usize {
+            let page = read_u64(&entry.data, index * 8);
+            if page > meta.last_page {
+                scanner.problem(
+                    FREE_DB_NAME,
+                    None,
+                    format!("free page {page} is beyond the last page"),
+                );
+            } else if scanner.used.contains(page) {
+                scanner.problem(
+                    FREE_DB_NAME,
+                    Some(page),
+                    "both in use and in the free list".to_string(),
+                );
+            } else if !free.insert(page) {
+                scanner.problem(
+                    FREE_DB_NAME,
+                    Some(page),
+                    "in the free list more than once".to_string(),
+                );
+            }
+        }
+    }
+
+    let mut report = scanner.report;
+    report.free_pages = free.len;
+    report.unreferenced_pages = (meta.last_page + 1).saturating_sub(scanner.used.len + free.len);
+    if report.unreferenced_pages > 0 {
+        warn!(
+            "{} pages are neither in use nor in the free list.",
+            report.unreferenced_pages
+        );
+    }
+    Ok(report)
+}
@@ -0,0 +1,76 @@
+use std::{fs::OpenOptions, os::unix::fs::FileExt};
+
+use lmdb::{Transaction, WriteFlags};
+
+use super::scan::scan_pages;
+use crate::{
+    common::db::{BlockHeaderDatabase, Database, DeployDatabase, STORAGE_FILE_NAME},
+    test_utils::LmdbTestFixture,
+};
+
+#[test]
+fn scan_pages_should_report_damaged_pages() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), DeployDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let deploy_db = *fixture.db(Some(DeployDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    // Enough entries to need branch pages, plus a value large enough to be
+    // stored on overflow pages.
+    for idx in 0..500u32 {
+        txn.put(
+            header_db,
+            &idx.to_be_bytes(),
+            &[idx as u8; 64],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.put(deploy_db, &[0u8; 32], &[1u8; 10_000], WriteFlags::empty())
+        .unwrap();
+    txn.commit().unwrap();
+    fixture.env.sync(true).unwrap();
+
+    let report = scan_pages(&fixture.file_path).unwrap();
+    assert!(report.problems.is_empty(), "{:?}", report.problems);
+    assert!(report.damaged_pages.is_empty());
+    let headers_scan = report
+        .databases
+        .iter()
+        .find(|scan| scan.name == BlockHeaderDatabase::db_name())
+        .unwrap();
+    assert_eq!(headers_scan.entries, 500);
+    assert!(headers_scan.branch_pages > 0);
+    let deploys_scan = report
+        .databases
+        .iter()
+        .find(|scan| scan.name == DeployDatabase::db_name())
+        .unwrap();
+    assert_eq!(deploys_scan.entries, 1);
+    assert_eq!(deploys_scan.overflow_pages, 3);
+
+    // Corrupt the header of the root page of the block header database.
+    let root = headers_scan.root.unwrap();
+    let file = OpenOptions::new()
+        .write(true)
+        .open(&fixture.file_path)
+        .unwrap();
+    file.write_all_at(&[0xff; 16], root * report.page_size as u64)
+        .unwrap();
+    drop(file);
+
+    let report = scan_pages(&fixture.file_path).unwrap();
+    assert!(report.damaged_pages.contains(&root));
+    assert!(report
+        .problems
+        .iter()
+        .any(|problem| problem.database == BlockHeaderDatabase::db_name()
+            && problem.page == Some(root)));
+    // The other database is unaffected.
+    assert!(report
+        .problems
+        .iter()
+        .all(|problem| problem.database != DeployDatabase::db_name()));
+}
@@ -0,0 +1,225 @@
+pub(crate) mod search;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
+
+use casper_hashing::Digest;
+use casper_types::{bytesrepr::Error as BytesreprError, checksummed_hex};
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+use search::{Filter, KEY_TAGS};
+
+pub const COMMAND_NAME: &str = "search-state";
+const DB_PATH: &str = "db-path";
+const KEY_PREFIX: &str = "key-prefix";
+const KEY_TAG: &str = "key-tag";
+const LIMIT: &str = "limit";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT: &str = "state-root";
+const VALUE_CONTAINS: &str = "value-contains";
+
+/// Errors encountered when searching global state.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the trie store: {0}")]
+    Database(#[from] LmdbError),
+    /// A node of the trie is missing.
+    #[error("Trie node {0} is missing")]
+    MissingNode(Digest),
+    /// The trie store is missing from the database directory.
+    #[error("Trie store not found at {0}")]
+    MissingTrieStore(PathBuf),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on a trie node.
+    #[error("Error parsing trie node {0}: {1}")]
+    Parsing(Digest, BytesreprError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    StateRoot,
+    KeyTag,
+    KeyPrefix,
+    ValueContains,
+    Limit,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+fn validate_hex(value: &str) -> Result<(), String> {
+    checksummed_hex::decode(value)
+        .map(|_| ())
+        .map_err(|decode_err| decode_err.to_string())
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Scans the leaves of the global state under a state root for keys \
+            with a given tag or prefix, or values containing given bytes, and \
+            outputs the matches as they are found, one JSON object per line. \
+            Useful to find dictionary items or named keys whose exact key is \
+            unknown.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STATE_ROOT)
+                .display_order(DisplayOrder::StateRoot as usize)
+                .required(true)
+                .short('r')
+                .long(STATE_ROOT)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help("Hash of the state root to search, in hex."),
+        )
+        .arg(
+            Arg::new(KEY_TAG)
+                .display_order(DisplayOrder::KeyTag as usize)
+                .short('t')
+                .long(KEY_TAG)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .value_name("TAG")
+                .possible_values(KEY_TAGS.map(|(name, _)| name))
+                .help(
+                    "Only match keys of this kind. Can be repeated or given as \
+                    a comma separated list to match keys of any of the kinds.",
+                ),
+        )
+        .arg(
+            Arg::new(KEY_PREFIX)
+                .display_order(DisplayOrder::KeyPrefix as usize)
+                .short('p')
+                .long(KEY_PREFIX)
+                .takes_value(true)
+                .value_name("HEX")
+                .validator(validate_hex)
+                .help(
+                    "Only match keys whose address starts with these bytes, \
+                    in hex, such as the start of the hash of a dictionary \
+                    item or of an account.",
+                ),
+        )
+        .arg(
+            Arg::new(VALUE_CONTAINS)
+                .display_order(DisplayOrder::ValueContains as usize)
+                .short('c')
+                .long(VALUE_CONTAINS)
+                .takes_value(true)
+                .value_name("HEX")
+                .validator(validate_hex)
+                .help(
+                    "Only match values whose serialized bytes contain these \
+                    bytes, in hex.",
+                ),
+        )
+        .arg(
+            Arg::new(LIMIT)
+                .display_order(DisplayOrder::Limit as usize)
+                .short('n')
+                .long(LIMIT)
+                .takes_value(true)
+                .value_name("COUNT")
+                .validator(|value| value.parse::<usize>().map(|_| ()))
+                .help("Stop after finding this many matches."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the matches. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let state_root_hash = Digest::from_hex(
+        matches
+            .value_of(STATE_ROOT)
+            .expect("should have state-root arg"),
+    )
+    .expect("should be validated");
+    let hex_arg = |name| {
+        matches
+            .value_of(name)
+            .map(|value| checksummed_hex::decode(value).expect("should be validated"))
+            .unwrap_or_default()
+    };
+    let filter = Filter {
+        key_tags: matches
+            .values_of(KEY_TAG)
+            .into_iter()
+            .flatten()
+            .filter_map(|value| {
+                KEY_TAGS
+                    .iter()
+                    .find(|(name, _)| *name == value)
+                    .map(|(_, key_tag)| *key_tag)
+            })
+            .collect(),
+        key_prefix: hex_arg(KEY_PREFIX),
+        value_pattern: hex_arg(VALUE_CONTAINS),
+    };
+    let maybe_limit = matches
+        .value_of(LIMIT)
+        .map(|value| value.parse().expect("should be validated"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    search::search_state(path, state_root_hash, &filter, maybe_limit, |found| {
+        serde_json::to_writer(&mut out_writer, &found)?;
+        writeln!(out_writer)?;
+        Ok(())
+    })?;
+    out_writer.finish()?;
+    Ok(())
+}
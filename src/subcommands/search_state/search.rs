@@ -0,0 +1,142 @@
+use std::{path::Path, result::Result};
+
+use casper_execution_engine::storage::trie::Trie;
+use casper_hashing::Digest;
+use casper_types::{
+    bytesrepr::{self, FromBytes},
+    Key, KeyTag, StoredValue,
+};
+use log::info;
+use serde::Serialize;
+
+use crate::common::db::{self, TrieEnv, TRIE_STORE_FILE_NAME};
+
+use super::Error;
+
+/// Number of leaves visited between two progress messages.
+const LEAVES_PER_PROGRESS_LOG: usize = 1_000_000;
+
+/// Names of the key tags, as taken by `--key-tag`.
+pub(crate) const KEY_TAGS: [(&str, KeyTag); 12] = [
+    ("account", KeyTag::Account),
+    ("hash", KeyTag::Hash),
+    ("uref", KeyTag::URef),
+    ("transfer", KeyTag::Transfer),
+    ("deploy-info", KeyTag::DeployInfo),
+    ("era-info", KeyTag::EraInfo),
+    ("balance", KeyTag::Balance),
+    ("bid", KeyTag::Bid),
+    ("withdraw", KeyTag::Withdraw),
+    ("dictionary", KeyTag::Dictionary),
+    ("system-contract-registry", KeyTag::SystemContractRegistry),
+    ("era-summary", KeyTag::EraSummary),
+];
+
+/// Criteria the leaves of a trie must all meet to match.
+#[derive(Debug, Default)]
+pub(crate) struct Filter {
+    /// The tags the key may have, any if empty.
+    pub(crate) key_tags: Vec<KeyTag>,
+    /// The start of the serialized key after its tag, such as the address
+    /// of a dictionary item.
+    pub(crate) key_prefix: Vec<u8>,
+    /// Bytes the serialized value must contain.
+    pub(crate) value_pattern: Vec<u8>,
+}
+
+impl Filter {
+    /// Returns whether a leaf with the serialized `key` and `value` matches.
+    fn matches(&self, key: &[u8], value: &[u8]) -> bool {
+        if !self.key_tags.is_empty()
+            && !self
+                .key_tags
+                .iter()
+                .any(|key_tag| key.first() == Some(&(*key_tag as u8)))
+        {
+            return false;
+        }
+        key.get(1..)
+            .unwrap_or_default()
+            .starts_with(&self.key_prefix)
+            && (self.value_pattern.is_empty()
+                || value
+                    .windows(self.value_pattern.len())
+                    .any(|window| window == self.value_pattern))
+    }
+}
+
+/// A leaf of the trie matching the search.
+#[derive(Debug, Serialize)]
+pub(crate) struct Match {
+    pub(crate) key: Key,
+    pub(crate) value: StoredValue,
+}
+
+/// Walks the leaves of the trie under `state_root_hash` in the trie store in
+/// `db_path`, passing the ones matching `filter` to `on_match` as they are
+/// found, until `maybe_limit` of them are. Only the matching leaves are
+/// parsed. Returns the number of matches.
+pub(crate) fn search_state<P, F>(
+    db_path: P,
+    state_root_hash: Digest,
+    filter: &Filter,
+    maybe_limit: Option<usize>,
+    mut on_match: F,
+) -> Result<usize, Error>
+where
+    P: AsRef<Path>,
+    F: FnMut(Match) -> Result<(), Error>,
+{
+    let trie_path = db_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    if !trie_path.exists() {
+        return Err(Error::MissingTrieStore(trie_path));
+    }
+    let trie_env = TrieEnv::open(&db_path)?;
+    let trie_db = trie_env.db()?;
+    let txn = trie_env.begin_ro_txn()?;
+
+    let mut leaf_count = 0;
+    let mut match_count = 0;
+    let mut pending = vec![state_root_hash];
+    while let Some(trie_key) = pending.pop() {
+        if maybe_limit == Some(match_count) {
+            break;
+        }
+        let raw_trie =
+            db::get_optional(&txn, trie_db, &trie_key)?.ok_or(Error::MissingNode(trie_key))?;
+        // A first byte of `0` indicates a leaf, followed by its key and its
+        // value.
+        if raw_trie.first() == Some(&0) {
+            leaf_count += 1;
+            if leaf_count % LEAVES_PER_PROGRESS_LOG == 0 {
+                info!("Searched {} leaves...", leaf_count);
+            }
+            let (key, raw_value) = Key::from_bytes(&raw_trie[1..])
+                .map_err(|bytesrepr_err| Error::Parsing(trie_key, bytesrepr_err))?;
+            let raw_key = &raw_trie[1..raw_trie.len() - raw_value.len()];
+            if filter.matches(raw_key, raw_value) {
+                let value = bytesrepr::deserialize(raw_value.to_vec())
+                    .map_err(|bytesrepr_err| Error::Parsing(trie_key, bytesrepr_err))?;
+                on_match(Match { key, value })?;
+                match_count += 1;
+            }
+            continue;
+        }
+        match bytesrepr::deserialize::<Trie<Key, StoredValue>>(raw_trie.to_vec())
+            .map_err(|bytesrepr_err| Error::Parsing(trie_key, bytesrepr_err))?
+        {
+            Trie::Leaf { .. } => {}
+            Trie::Node { pointer_block } => pending.extend(
+                pointer_block
+                    .as_indexed_pointers()
+                    .map(|(_index, pointer)| pointer.into_hash()),
+            ),
+            Trie::Extension { pointer, .. } => pending.push(pointer.into_hash()),
+        }
+    }
+    info!(
+        "Found {} matches in {} leaves under state root {}.",
+        match_count, leaf_count, state_root_hash
+    );
+    Ok(match_count)
+}
@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId, storage::global_state::StateProvider,
+};
+use casper_hashing::Digest;
+use casper_types::{CLValue, Key, KeyTag, StoredValue};
+use tempfile::TempDir;
+
+use super::search::{search_state, Filter};
+use crate::subcommands::trie_compact::{create_execution_engine, DEFAULT_MAX_DB_SIZE};
+
+/// Creates a trie store holding the string `item-<idx>` under dictionary
+/// keys `[idx; 32]` and `u64` values under hash keys `[idx; 32]`.
+fn new_global_state() -> (TempDir, Digest) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), DEFAULT_MAX_DB_SIZE.parse().unwrap(), true)
+            .unwrap();
+
+    let mut stored_values = HashMap::new();
+    for idx in 0..4u8 {
+        stored_values.insert(
+            Key::Dictionary([idx; 32]),
+            StoredValue::CLValue(CLValue::from_t(format!("item-{}", idx)).unwrap()),
+        );
+        stored_values.insert(
+            Key::Hash([idx; 32]),
+            StoredValue::CLValue(CLValue::from_t(idx as u64).unwrap()),
+        );
+    }
+    let global_state = engine_state.get_state();
+    let state_root_hash = global_state
+        .put_stored_values(
+            CorrelationId::new(),
+            global_state.empty_root(),
+            stored_values,
+        )
+        .unwrap();
+    engine_state.flush_environment().unwrap();
+    (tmp_dir, state_root_hash)
+}
+
+fn found_keys(
+    tmp_dir: &TempDir,
+    state_root_hash: Digest,
+    filter: &Filter,
+    maybe_limit: Option<usize>,
+) -> Vec<Key> {
+    let mut keys = vec![];
+    let match_count = search_state(
+        tmp_dir.path(),
+        state_root_hash,
+        filter,
+        maybe_limit,
+        |found| {
+            keys.push(found.key);
+            Ok(())
+        },
+    )
+    .unwrap();
+    assert_eq!(match_count, keys.len());
+    keys.sort();
+    keys
+}
+
+#[test]
+fn search_should_filter_keys() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    assert_eq!(
+        found_keys(&tmp_dir, state_root_hash, &Filter::default(), None).len(),
+        8
+    );
+
+    let filter = Filter {
+        key_tags: vec![KeyTag::Dictionary],
+        ..Default::default()
+    };
+    assert_eq!(
+        found_keys(&tmp_dir, state_root_hash, &filter, None),
+        (0..4)
+            .map(|idx| Key::Dictionary([idx; 32]))
+            .collect::<Vec<_>>()
+    );
+
+    let filter = Filter {
+        key_prefix: vec![2, 2],
+        ..Default::default()
+    };
+    assert_eq!(
+        found_keys(&tmp_dir, state_root_hash, &filter, None),
+        vec![Key::Hash([2; 32]), Key::Dictionary([2; 32])]
+    );
+
+    let filter = Filter {
+        key_tags: vec![KeyTag::Hash],
+        key_prefix: vec![3],
+        ..Default::default()
+    };
+    assert_eq!(
+        found_keys(&tmp_dir, state_root_hash, &filter, None),
+        vec![Key::Hash([3; 32])]
+    );
+}
+
+#[test]
+fn search_should_filter_values() {
+    let (tmp_dir, state_root_hash) = new_global_state();
+    let filter = Filter {
+        value_pattern: b"item-1".to_vec(),
+        ..Default::default()
+    };
+    assert_eq!(
+        found_keys(&tmp_dir, state_root_hash, &filter, None),
+        vec![Key::Dictionary([1; 32])]
+    );
+
+    // Only the `u64` values start with a length of 8 bytes.
+    let filter = Filter {
+        value_pattern: 8u32.to_le_bytes().to_vec(),
+        ..Default::default()
+    };
+    assert_eq!(
+        found_keys(&tmp_dir, state_root_hash, &filter, Some(3)).len(),
+        3
+    );
+}
@@ -0,0 +1,93 @@
+mod routes;
+#[cfg(test)]
+mod tests;
+
+use std::{net::AddrParseError, path::Path};
+
+use bincode::Error as BincodeError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError, http_server::Error as HttpServerError,
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "serve";
+const ADDRESS: &str = "address";
+const DB_PATH: &str = "db-path";
+
+/// Errors encountered when serving queries from the storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error indexing the blocks by height.
+    #[error("Error indexing blocks: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Invalid listening address.
+    #[error("Invalid address: {0}")]
+    InvalidAddress(#[from] AddrParseError),
+    /// Error serializing a response.
+    #[error("Error serializing response: {0}")]
+    Serialize(#[from] SerializationError),
+    /// HTTP server error.
+    #[error("{0}")]
+    Server(#[from] HttpServerError),
+    /// Parsing error on the entry with the given key in the database.
+    #[error("Error parsing entry {1} in {0} DB: {2}")]
+    ValueParsing(&'static str, String, BincodeError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Address,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Starts an HTTP server exposing read-only JSON queries on a \
+            storage database: `/block/{hash|height}`, `/deploy/{hash}`, \
+            `/latest` and `/stats`.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(ADDRESS)
+                .display_order(DisplayOrder::Address as usize)
+                .short('a')
+                .long(ADDRESS)
+                .takes_value(true)
+                .value_name("IP:PORT")
+                .default_value("127.0.0.1:8080")
+                .help("Address on which the server will listen."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let address = matches
+        .value_of(ADDRESS)
+        .expect("should have a default")
+        .parse()?;
+    routes::serve(path, address)
+}
@@ -0,0 +1,224 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::SocketAddr,
+    path::Path,
+    result::Result,
+    sync::Arc,
+};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader, Deploy, DeployHash, DeployMetadata};
+use casper_types::ExecutionResult;
+use hyper::{header::CONTENT_TYPE, Body, Method, Request, Response, StatusCode};
+use lmdb::{Error as LmdbError, Transaction};
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, DeployDatabase, DeployMetadataDatabase, StorageEnv},
+        http_server,
+        storage::LmdbReader,
+    },
+    subcommands::{
+        latest_block_summary::block_info::{parse_network_name, BlockInfo},
+        stats,
+    },
+};
+
+use super::Error;
+
+/// A deploy along with the execution results stored for it.
+#[derive(Serialize)]
+pub(super) struct DeployInfo {
+    pub(super) deploy: Deploy,
+    pub(super) execution_results: HashMap<BlockHash, ExecutionResult>,
+}
+
+/// The outcome of routing a request.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Reply {
+    /// JSON body of a successful response.
+    Json(String),
+    /// The requested item doesn't exist.
+    NotFound(String),
+    /// The request path is malformed.
+    BadRequest(String),
+}
+
+/// Everything the server needs to answer requests.
+pub(super) struct ServerState {
+    env: StorageEnv,
+    network_name: Option<String>,
+    /// Block hashes indexed by height, built when the server starts.
+    heights: BTreeMap<u64, BlockHash>,
+}
+
+impl ServerState {
+    pub(super) fn new<P: AsRef<Path>>(db_path: P) -> Result<Self, Error> {
+        let env = StorageEnv::open(&db_path)?;
+        let network_name = match parse_network_name(db_path) {
+            Ok(name) => Some(name),
+            Err(io_err) => {
+                warn!("Couldn't derive network name from path: {}", io_err);
+                None
+            }
+        };
+
+        let heights = http_server::index_heights(&env)?;
+
+        Ok(Self {
+            env,
+            network_name,
+            heights,
+        })
+    }
+
+    fn block_info(&self, block_hash: &BlockHash) -> Result<Reply, Error> {
+        let db = self.env.db::<BlockHeaderDatabase>()?;
+        let txn = self.env.begin_ro_txn()?;
+        let header: BlockHeader = match txn.get(db, block_hash) {
+            Ok(raw_header) => bincode::deserialize(raw_header).map_err(|bincode_err| {
+                Error::ValueParsing(
+                    BlockHeaderDatabase::db_name(),
+                    block_hash.to_string(),
+                    bincode_err,
+                )
+            })?,
+            Err(LmdbError::NotFound) => {
+                return Ok(Reply::NotFound(format!("block {} not found", block_hash)))
+            }
+            Err(lmdb_err) => return Err(lmdb_err.into()),
+        };
+        let block_info = BlockInfo::new(self.network_name.clone(), *block_hash, header);
+        Ok(Reply::Json(serde_json::to_string_pretty(&block_info)?))
+    }
+
+    fn block(&self, id: &str) -> Result<Reply, Error> {
+        if let Ok(height) = id.parse::<u64>() {
+            return match self.heights.get(&height) {
+                Some(block_hash) => self.block_info(block_hash),
+                None => Ok(Reply::NotFound(format!("no block at height {}", height))),
+            };
+        }
+        match Digest::from_hex(id) {
+            Ok(digest) => self.block_info(&digest.into()),
+            Err(_) => Ok(Reply::BadRequest(format!(
+                "{} is neither a block height nor a block hash",
+                id
+            ))),
+        }
+    }
+
+    fn deploy(&self, id: &str) -> Result<Reply, Error> {
+        let deploy_hash = match Digest::from_hex(id) {
+            Ok(digest) => DeployHash::new(digest),
+            Err(_) => return Ok(Reply::BadRequest(format!("{} is not a deploy hash", id))),
+        };
+        let deploys_db = self.env.db::<DeployDatabase>()?;
+        let metadata_db = self.env.db::<DeployMetadataDatabase>()?;
+        let txn = self.env.begin_ro_txn()?;
+        let deploy: Deploy = match txn.get(deploys_db, &deploy_hash) {
+            Ok(raw_deploy) => bincode::deserialize(raw_deploy).map_err(|bincode_err| {
+                Error::ValueParsing(
+                    DeployDatabase::db_name(),
+                    deploy_hash.to_string(),
+                    bincode_err,
+                )
+            })?,
+            Err(LmdbError::NotFound) => {
+                return Ok(Reply::NotFound(format!("deploy {} not found", deploy_hash)))
+            }
+            Err(lmdb_err) => return Err(lmdb_err.into()),
+        };
+        let execution_results = match txn.get(metadata_db, &deploy_hash) {
+            Ok(raw_metadata) => {
+                let metadata: DeployMetadata =
+                    bincode::deserialize(raw_metadata).map_err(|bincode_err| {
+                        Error::ValueParsing(
+                            DeployMetadataDatabase::db_name(),
+                            deploy_hash.to_string(),
+                            bincode_err,
+                        )
+                    })?;
+                metadata.execution_results
+            }
+            Err(LmdbError::NotFound) => HashMap::new(),
+            Err(lmdb_err) => return Err(lmdb_err.into()),
+        };
+        let deploy_info = DeployInfo {
+            deploy,
+            execution_results,
+        };
+        Ok(Reply::Json(serde_json::to_string_pretty(&deploy_info)?))
+    }
+
+    fn latest(&self) -> Result<Reply, Error> {
+        match self.heights.values().next_back() {
+            Some(block_hash) => self.block_info(block_hash),
+            None => Ok(Reply::NotFound(
+                "no blocks found in the block header database".to_string(),
+            )),
+        }
+    }
+
+    fn stats(&self) -> Result<Reply, Error> {
+        let txn = self.env.begin_ro_txn()?;
+        let entry_counts = stats::entry_counts(&LmdbReader::new(&txn))?;
+        Ok(Reply::Json(serde_json::to_string_pretty(&entry_counts)?))
+    }
+
+    /// Answers a `GET` request for the given path.
+    pub(super) fn route(&self, path: &str) -> Result<Reply, Error> {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match segments.as_slice() {
+            ["block", id] => self.block(id),
+            ["deploy", id] => self.deploy(id),
+            ["latest"] => self.latest(),
+            ["stats"] => self.stats(),
+            _ => Ok(Reply::NotFound(format!("unknown path {}", path))),
+        }
+    }
+}
+
+async fn handle(state: Arc<ServerState>, request: Request<Body>) -> Response<Body> {
+    let (status, body) = if request.method() != Method::GET {
+        (
+            StatusCode::METHOD_NOT_ALLOWED,
+            "only GET requests are supported".to_string(),
+        )
+    } else {
+        // The database reads block, so they are kept off the executor.
+        let path = request.uri().path().to_string();
+        let route_path = path.clone();
+        match tokio::task::spawn_blocking(move || state.route(&route_path)).await {
+            Ok(Ok(Reply::Json(json))) => (StatusCode::OK, json),
+            Ok(Ok(Reply::NotFound(msg))) => (StatusCode::NOT_FOUND, msg),
+            Ok(Ok(Reply::BadRequest(msg))) => (StatusCode::BAD_REQUEST, msg),
+            Ok(Err(error)) => {
+                warn!("Error serving {}: {}", path, error);
+                (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+            }
+            Err(join_err) => {
+                warn!("Error serving {}: {}", path, join_err);
+                (StatusCode::INTERNAL_SERVER_ERROR, join_err.to_string())
+            }
+        }
+    };
+    let content_type = if status == StatusCode::OK {
+        "application/json"
+    } else {
+        "text/plain"
+    };
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, content_type)
+        .body(Body::from(body))
+        .expect("should build response")
+}
+
+pub(super) fn serve<P: AsRef<Path>>(db_path: P, address: SocketAddr) -> Result<(), Error> {
+    let state = Arc::new(ServerState::new(db_path)?);
+    http_server::serve(address, "", state, handle)?;
+    Ok(())
+}
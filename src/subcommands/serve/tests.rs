@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use casper_node::{rpcs::docs::DocExample, types::Deploy};
+use lmdb::{Transaction, WriteFlags};
+
+use crate::{
+    common::db::{
+        BlockHeaderDatabase, Database, DeployDatabase, DeployMetadataDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::{
+        latest_block_summary::block_info::BlockInfo,
+        serve::routes::{Reply, ServerState},
+    },
+    test_utils::{mock_block_header, mock_deploy_metadata, LmdbTestFixture},
+};
+
+fn json_reply(reply: Reply) -> String {
+    match reply {
+        Reply::Json(json) => json,
+        other => panic!("expected JSON reply, got {:?}", other),
+    }
+}
+
+#[test]
+fn routes_should_serve_db_contents() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let blocks: Vec<_> = (0..3u8)
+        .map(|idx| {
+            let (block_hash, mut block_header) = mock_block_header(idx);
+            block_header.height = idx as u64;
+            (block_hash, block_header)
+        })
+        .collect();
+    let deploy = Deploy::doc_example().clone();
+    {
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        for (block_hash, block_header) in blocks.iter() {
+            txn.put(
+                *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+                block_hash,
+                &bincode::serialize(block_header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            deploy.id(),
+            &bincode::serialize(&deploy).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            deploy.id(),
+            &bincode::serialize(&mock_deploy_metadata(&[blocks[1].0])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    }
+
+    let state = ServerState::new(fixture.tmp_dir.path()).unwrap();
+
+    // The latest block is the one at height 2.
+    let block_info: BlockInfo =
+        serde_json::from_str(&json_reply(state.route("/latest").unwrap())).unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[2].1);
+
+    // Blocks can be queried both by height and by hash.
+    let block_info: BlockInfo =
+        serde_json::from_str(&json_reply(state.route("/block/1").unwrap())).unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[1].1);
+    let block_info: BlockInfo = serde_json::from_str(&json_reply(
+        state
+            .route(&format!("/block/{}", blocks[0].0.inner()))
+            .unwrap(),
+    ))
+    .unwrap();
+    assert_eq!(block_info.into_mock().0, blocks[0].1);
+
+    let deploy_json: serde_json::Value = serde_json::from_str(&json_reply(
+        state
+            .route(&format!("/deploy/{}", deploy.id().inner()))
+            .unwrap(),
+    ))
+    .unwrap();
+    assert_eq!(
+        deploy_json["deploy"],
+        serde_json::to_value(&deploy).unwrap()
+    );
+    assert!(deploy_json["execution_results"]
+        .get(blocks[1].0.inner().to_string())
+        .is_some());
+
+    let entry_counts: BTreeMap<String, usize> =
+        serde_json::from_str(&json_reply(state.route("/stats").unwrap())).unwrap();
+    assert_eq!(entry_counts.get("block_header"), Some(&3));
+    assert_eq!(entry_counts.get("deploys"), Some(&1));
+    assert_eq!(entry_counts.get("deploy_metadata"), Some(&1));
+    assert!(!entry_counts.contains_key("block_body"));
+
+    assert!(matches!(
+        state.route("/block/3").unwrap(),
+        Reply::NotFound(_)
+    ));
+    assert!(matches!(
+        state
+            .route(&format!("/deploy/{}", blocks[0].0.inner()))
+            .unwrap(),
+        Reply::NotFound(_)
+    ));
+    assert!(matches!(
+        state.route("/block/not-a-hash").unwrap(),
+        Reply::BadRequest(_)
+    ));
+    assert!(matches!(
+        state.route("/unknown").unwrap(),
+        Reply::NotFound(_)
+    ));
+}
+
+#[test]
+fn latest_on_empty_db_should_be_not_found() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let state = ServerState::new(fixture.tmp_dir.path()).unwrap();
+    assert!(matches!(
+        state.route("/latest").unwrap(),
+        Reply::NotFound(_)
+    ));
+}
@@ -0,0 +1,213 @@
+mod edit;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use casper_hashing::Digest;
+use casper_node::types::BlockHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::{
+    common::{
+        db::DeserializationError,
+        state_store::{Error as StateStoreError, SyncMarkers, WellKnownKey},
+    },
+    subcommands::latest_block_summary::block_info::parse_network_name,
+};
+use edit::Action;
+
+pub const COMMAND_NAME: &str = "set-state-store";
+const BLOCK_PROPOSER: &str = "block-proposer";
+const DB_PATH: &str = "db-path";
+const DELETE: &str = "delete";
+const HIGHEST_BLOCK_SEEN: &str = "highest-block-seen";
+const KEY: &str = "key";
+const LAST_SWITCH_BLOCK_HEIGHT: &str = "last-switch-block-height";
+const LINEAR_CHAIN_SYNC: &str = "linear-chain-sync";
+const NETWORK_NAME: &str = "network-name";
+const RESET: &str = "reset";
+const TRUSTED_HASH: &str = "trusted-hash";
+const MARKERS: [&str; 3] = [TRUSTED_HASH, HIGHEST_BLOCK_SEEN, LAST_SWITCH_BLOCK_HEIGHT];
+
+/// Errors encountered when editing the state store.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] LmdbError),
+    /// Error encoding the new value of the entry.
+    #[error("Error encoding state store entry: {0}")]
+    Encoding(BincodeError),
+    /// The network name was neither given nor derivable from the path.
+    #[error("Couldn't derive network name from path, use `--network-name`: {0}")]
+    NetworkName(IoError),
+    /// Markers were given for an entry which has none.
+    #[error("State store entry {0} has no markers to set")]
+    NoMarkers(String),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on the current value of the entry.
+    #[error("Error parsing state store entry: {0}")]
+    Parsing(#[from] DeserializationError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Error setting the markers of the entry.
+    #[error("Error setting markers: {0}")]
+    StateStore(#[from] StateStoreError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Key,
+    NetworkName,
+    Reset,
+    Delete,
+    TrustedHash,
+    HighestBlockSeen,
+    LastSwitchBlockHeight,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Shows, resets, deletes or sets the markers of a well-known entry \
+            of the state store, printing its decoded value before and after \
+            the change in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(KEY)
+                .display_order(DisplayOrder::Key as usize)
+                .required(true)
+                .short('k')
+                .long(KEY)
+                .takes_value(true)
+                .value_name("KEY")
+                .possible_values([LINEAR_CHAIN_SYNC, BLOCK_PROPOSER])
+                .help(
+                    "Entry to operate on: the saved state of the linear chain \
+                    synchronizer or the pending deploys of the block proposer.",
+                ),
+        )
+        .arg(
+            Arg::new(NETWORK_NAME)
+                .display_order(DisplayOrder::NetworkName as usize)
+                .short('n')
+                .long(NETWORK_NAME)
+                .takes_value(true)
+                .value_name("NETWORK_NAME")
+                .help(
+                    "Network whose linear chain synchronizer state to operate \
+                    on. Defaults to the name of the database directory.",
+                ),
+        )
+        .arg(
+            Arg::new(RESET)
+                .display_order(DisplayOrder::Reset as usize)
+                .required(false)
+                .short('r')
+                .long(RESET)
+                .takes_value(false)
+                .conflicts_with(DELETE)
+                .conflicts_with_all(&MARKERS)
+                .help(
+                    "Overwrite the entry with the state the node starts from \
+                    when there is nothing to carry over: no linear chain \
+                    synchronization or no pending deploys.",
+                ),
+        )
+        .arg(
+            Arg::new(DELETE)
+                .display_order(DisplayOrder::Delete as usize)
+                .required(false)
+                .long(DELETE)
+                .takes_value(false)
+                .conflicts_with(RESET)
+                .conflicts_with_all(&MARKERS)
+                .help("Delete the entry."),
+        )
+        .arg(
+            Arg::new(TRUSTED_HASH)
+                .display_order(DisplayOrder::TrustedHash as usize)
+                .long(TRUSTED_HASH)
+                .takes_value(true)
+                .value_name("BLOCK_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help(
+                    "Set the hash of the block the linear chain synchronizer \
+                    syncs from. A synchronizer which isn't syncing starts over \
+                    from this block.",
+                ),
+        )
+        .arg(
+            Arg::new(HIGHEST_BLOCK_SEEN)
+                .display_order(DisplayOrder::HighestBlockSeen as usize)
+                .long(HIGHEST_BLOCK_SEEN)
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help("Set the height of the highest block the linear chain synchronizer saw."),
+        )
+        .arg(
+            Arg::new(LAST_SWITCH_BLOCK_HEIGHT)
+                .display_order(DisplayOrder::LastSwitchBlockHeight as usize)
+                .long(LAST_SWITCH_BLOCK_HEIGHT)
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help(
+                    "Set the height of the last switch block the linear chain \
+                    synchronizer executed.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let key = match matches.value_of(KEY).expect("should have key arg") {
+        LINEAR_CHAIN_SYNC => {
+            let network_name = match matches.value_of(NETWORK_NAME) {
+                Some(network_name) => network_name.to_string(),
+                None => parse_network_name(path).map_err(Error::NetworkName)?,
+            };
+            WellKnownKey::LinearChainSync(network_name)
+        }
+        BLOCK_PROPOSER => WellKnownKey::BlockProposer,
+        _ => unreachable!("should be validated"),
+    };
+    let markers = SyncMarkers {
+        trusted_hash: matches
+            .value_of(TRUSTED_HASH)
+            .map(|value| BlockHash::new(Digest::from_hex(value).expect("should be validated"))),
+        highest_block_seen: matches
+            .value_of(HIGHEST_BLOCK_SEEN)
+            .map(|value| value.parse().expect("should be validated")),
+        last_switch_block_height: matches
+            .value_of(LAST_SWITCH_BLOCK_HEIGHT)
+            .map(|value| value.parse().expect("should be validated")),
+    };
+    let action = match matches {
+        _ if matches.is_present(RESET) => Action::Reset,
+        _ if matches.is_present(DELETE) => Action::Delete,
+        _ if markers != SyncMarkers::default() => Action::Set(markers),
+        _ => Action::Show,
+    };
+    edit::set_state_store(path, &key, action).map(|_| ())
+}
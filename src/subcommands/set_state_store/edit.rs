@@ -0,0 +1,150 @@
+use std::{io::Write, path::Path, result::Result};
+
+use lmdb::{Database as LmdbDatabase, Error as LmdbError, Transaction, WriteFlags};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    db::{StateStoreDatabase, StorageEnv},
+    output::{Compression, OutputWriter},
+    state_store::{self, LinearChainSyncState, StateStoreEntry, SyncMarkers, WellKnownKey},
+};
+
+use super::Error;
+
+/// What to do with the state store entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Action {
+    Show,
+    Reset,
+    Delete,
+    /// Set the markers of a linear chain synchronizer entry.
+    Set(SyncMarkers),
+}
+
+/// Decoded value of a state store entry before and after the edit. `None`
+/// means the entry doesn't exist, or, before the edit, that it didn't decode,
+/// in which case its raw value is given in hex.
+#[derive(Debug, Serialize)]
+pub(crate) struct EntryChange {
+    pub(crate) key: String,
+    pub(crate) before: Option<StateStoreEntry>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) undecodable_before: Option<String>,
+    pub(crate) after: Option<StateStoreEntry>,
+}
+
+fn read_entry<T: Transaction>(
+    txn: &T,
+    db: LmdbDatabase,
+    key: &[u8],
+) -> Result<Option<StateStoreEntry>, Error> {
+    match txn.get(db, &key) {
+        Ok(raw_val) => Ok(Some(state_store::parse_entry(key, raw_val)?)),
+        Err(LmdbError::NotFound) => Ok(None),
+        Err(lmdb_err) => Err(lmdb_err.into()),
+    }
+}
+
+/// Reads the current value of the entry under `key`. A value which doesn't
+/// decode is logged and returned in hex instead, as replacing or removing it
+/// is what the edit is for.
+fn read_current_entry<T: Transaction>(
+    txn: &T,
+    db: LmdbDatabase,
+    key: &[u8],
+) -> Result<(Option<StateStoreEntry>, Option<String>), Error> {
+    let raw_val = match txn.get(db, &key) {
+        Ok(raw_val) => raw_val,
+        Err(LmdbError::NotFound) => return Ok((None, None)),
+        Err(lmdb_err) => return Err(lmdb_err.into()),
+    };
+    match state_store::parse_entry(key, raw_val) {
+        Ok(entry) => Ok((Some(entry), None)),
+        Err(parsing_err) => {
+            let hex_val = hex::encode(raw_val);
+            warn!(
+                "Couldn't decode state store entry {}: {}, raw value: {}",
+                String::from_utf8_lossy(key),
+                parsing_err,
+                hex_val
+            );
+            Ok((None, Some(hex_val)))
+        }
+    }
+}
+
+/// Applies `action` to the state store entry under `key` and prints the
+/// entry before and after the change.
+pub(crate) fn set_state_store<P: AsRef<Path>>(
+    db_path: P,
+    key: &WellKnownKey,
+    action: Action,
+) -> Result<EntryChange, Error> {
+    let raw_key = key.to_bytes();
+    if matches!((key, action), (WellKnownKey::BlockProposer, Action::Set(_))) {
+        return Err(Error::NoMarkers(
+            String::from_utf8_lossy(&raw_key).to_string(),
+        ));
+    }
+    let env = StorageEnv::open(&db_path)?;
+
+    // The node creates the database on startup, but there's no harm in
+    // creating it here for a reset or new markers.
+    let maybe_db = match env.optional_db::<StateStoreDatabase>()? {
+        None if matches!(action, Action::Reset | Action::Set(_)) => {
+            Some(env.get_or_create_db::<StateStoreDatabase>()?)
+        }
+        maybe_db => maybe_db,
+    };
+    let mut txn = env.begin_rw_txn()?;
+    let (before, undecodable_before) = match maybe_db {
+        Some(db) => read_current_entry(&txn, db, &raw_key)?,
+        None => (None, None),
+    };
+    let after = match (action, maybe_db) {
+        (Action::Reset, Some(db)) => {
+            let raw_val = key.reset_value().map_err(Error::Encoding)?;
+            txn.put(db, &raw_key, &raw_val, WriteFlags::empty())?;
+            read_entry(&txn, db, &raw_key)?
+        }
+        (Action::Set(markers), Some(db)) => {
+            // An undecodable state was logged above and is replaced.
+            let maybe_state = match txn.get(db, &raw_key) {
+                Ok(raw_val) => bincode::deserialize::<LinearChainSyncState>(raw_val).ok(),
+                Err(LmdbError::NotFound) => None,
+                Err(lmdb_err) => return Err(lmdb_err.into()),
+            };
+            let raw_val = markers.apply(maybe_state)?;
+            txn.put(db, &raw_key, &raw_val, WriteFlags::empty())?;
+            read_entry(&txn, db, &raw_key)?
+        }
+        (Action::Delete, Some(db)) => {
+            if before.is_some() || undecodable_before.is_some() {
+                txn.del(db, &raw_key, None)?;
+            }
+            None
+        }
+        _ => before.clone(),
+    };
+    txn.commit()?;
+    if action != Action::Show {
+        info!(
+            "Applied {:?} to state store entry {}.",
+            action,
+            String::from_utf8_lossy(&raw_key)
+        );
+    }
+
+    let change = EntryChange {
+        key: String::from_utf8_lossy(&raw_key).to_string(),
+        before,
+        undecodable_before,
+        after,
+    };
+    let mut out_writer = OutputWriter::new(None::<&Path>, false, Compression::None)?;
+    serde_json::to_writer_pretty(&mut out_writer, &change)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(change)
+}
@@ -0,0 +1,212 @@
+use casper_node::types::BlockHash;
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    edit::{set_state_store, Action},
+    Error,
+};
+use crate::{
+    common::{
+        db::{Database, StateStoreDatabase, STORAGE_FILE_NAME},
+        state_store::{
+            BlockProposerSummary, Error as StateStoreError, LinearChainSyncSummary,
+            StateStoreEntry, SyncMarkers, SyncStatus, WellKnownKey,
+        },
+    },
+    test_utils::LmdbTestFixture,
+};
+
+#[test]
+fn set_state_store_should_reset_and_delete_entries() {
+    let fixture =
+        LmdbTestFixture::new(vec![StateStoreDatabase::db_name()], Some(STORAGE_FILE_NAME));
+    let sync_key = WellKnownKey::LinearChainSync("casper".to_string());
+
+    // A missing entry is reported as such and left alone.
+    let change = set_state_store(fixture.tmp_dir.path(), &sync_key, Action::Show).unwrap();
+    assert_eq!(change.key, "linear_chain_sync:network_name=casper");
+    assert!(change.before.is_none());
+    assert!(change.after.is_none());
+
+    let change = set_state_store(fixture.tmp_dir.path(), &sync_key, Action::Reset).unwrap();
+    assert!(change.before.is_none());
+    match change.after {
+        Some(StateStoreEntry::LinearChainSync(summary)) => {
+            assert_eq!(summary.network_name, "casper");
+            assert_eq!(summary.status, SyncStatus::None);
+        }
+        other => panic!("unexpected entry {:?}", other),
+    }
+
+    let env = &fixture.env;
+    // Garbage in the block proposer entry, too short for the length of its
+    // first map, is replaced by an empty state.
+    let garbage = [1u8, 2, 3];
+    let proposer_key = WellKnownKey::BlockProposer.to_bytes();
+    let put_garbage = || {
+        let mut txn = env.begin_rw_txn().unwrap();
+        txn.put(
+            *fixture.db(Some(StateStoreDatabase::db_name())).unwrap(),
+            &proposer_key,
+            &garbage,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    };
+    put_garbage();
+    let change = set_state_store(
+        fixture.tmp_dir.path(),
+        &WellKnownKey::BlockProposer,
+        Action::Reset,
+    )
+    .unwrap();
+    assert!(change.before.is_none());
+    assert_eq!(change.undecodable_before.as_deref(), Some("010203"));
+    assert_eq!(
+        change.after,
+        Some(StateStoreEntry::BlockProposer(
+            BlockProposerSummary::default()
+        ))
+    );
+
+    // It can be deleted as well.
+    put_garbage();
+    let change = set_state_store(
+        fixture.tmp_dir.path(),
+        &WellKnownKey::BlockProposer,
+        Action::Delete,
+    )
+    .unwrap();
+    assert_eq!(change.undecodable_before.as_deref(), Some("010203"));
+    assert!(change.after.is_none());
+    let txn = env.begin_ro_txn().unwrap();
+    assert!(txn
+        .get(
+            *fixture.db(Some(StateStoreDatabase::db_name())).unwrap(),
+            &proposer_key
+        )
+        .is_err());
+    txn.commit().unwrap();
+
+    let change = set_state_store(fixture.tmp_dir.path(), &sync_key, Action::Delete).unwrap();
+    assert!(change.before.is_some());
+    assert!(change.after.is_none());
+    let txn = env.begin_ro_txn().unwrap();
+    assert!(txn
+        .get(
+            *fixture.db(Some(StateStoreDatabase::db_name())).unwrap(),
+            &sync_key.to_bytes()
+        )
+        .is_err());
+    txn.commit().unwrap();
+}
+
+#[test]
+fn set_state_store_should_set_sync_markers() {
+    let fixture =
+        LmdbTestFixture::new(vec![StateStoreDatabase::db_name()], Some(STORAGE_FILE_NAME));
+    let casper_key = WellKnownKey::LinearChainSync("casper".to_string());
+    let test_key = WellKnownKey::LinearChainSync("casper-test".to_string());
+    let trusted_hash = BlockHash::new([1u8; 32].into());
+    let sync_summary = |entry: Option<StateStoreEntry>| match entry {
+        Some(StateStoreEntry::LinearChainSync(summary)) => summary,
+        other => panic!("unexpected entry {:?}", other),
+    };
+
+    // Heights alone can't be set on a synchronizer which isn't syncing.
+    let height_markers = SyncMarkers {
+        highest_block_seen: Some(42),
+        ..Default::default()
+    };
+    assert!(matches!(
+        set_state_store(
+            fixture.tmp_dir.path(),
+            &casper_key,
+            Action::Set(height_markers)
+        ),
+        Err(Error::StateStore(StateStoreError::NotSyncing))
+    ));
+
+    // With a trusted hash, a missing entry starts syncing from it.
+    let change = set_state_store(
+        fixture.tmp_dir.path(),
+        &casper_key,
+        Action::Set(SyncMarkers {
+            trusted_hash: Some(trusted_hash),
+            last_switch_block_height: Some(40),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    assert!(change.before.is_none());
+    assert_eq!(
+        sync_summary(change.after),
+        LinearChainSyncSummary {
+            network_name: "casper".to_string(),
+            status: SyncStatus::SyncingTrustedHash,
+            trusted_hash: Some(trusted_hash),
+            highest_block_seen: Some(0),
+            latest_block_hash: None,
+            latest_block_height: None,
+            last_switch_block_height: Some(40),
+        }
+    );
+
+    // Setting a height keeps the other markers.
+    let change = set_state_store(
+        fixture.tmp_dir.path(),
+        &casper_key,
+        Action::Set(height_markers),
+    )
+    .unwrap();
+    let before = sync_summary(change.before);
+    let after = sync_summary(change.after);
+    assert_eq!(before.highest_block_seen, Some(0));
+    assert_eq!(
+        after,
+        LinearChainSyncSummary {
+            highest_block_seen: Some(42),
+            ..before
+        }
+    );
+
+    // An undecodable entry of another network is replaced, leaving the first
+    // one alone.
+    let env = &fixture.env;
+    let mut txn = env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(StateStoreDatabase::db_name())).unwrap(),
+        &test_key.to_bytes(),
+        &[7u8],
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    let other_hash = BlockHash::new([2u8; 32].into());
+    let change = set_state_store(
+        fixture.tmp_dir.path(),
+        &test_key,
+        Action::Set(SyncMarkers {
+            trusted_hash: Some(other_hash),
+            ..Default::default()
+        }),
+    )
+    .unwrap();
+    assert_eq!(change.undecodable_before.as_deref(), Some("07"));
+    let after = sync_summary(change.after);
+    assert_eq!(after.network_name, "casper-test");
+    assert_eq!(after.trusted_hash, Some(other_hash));
+    let change = set_state_store(fixture.tmp_dir.path(), &casper_key, Action::Show).unwrap();
+    assert_eq!(sync_summary(change.after).trusted_hash, Some(trusted_hash));
+
+    // The block proposer entry has no markers.
+    assert!(matches!(
+        set_state_store(
+            fixture.tmp_dir.path(),
+            &WellKnownKey::BlockProposer,
+            Action::Set(height_markers)
+        ),
+        Err(Error::NoMarkers(_))
+    ));
+}
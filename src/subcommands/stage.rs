@@ -0,0 +1,112 @@
+mod staging;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use clap::{Arg, ArgMatches, Command};
+use thiserror::Error as ThisError;
+
+use crate::common::disk_space::Error as DiskSpaceError;
+
+pub const COMMAND_NAME: &str = "stage";
+const DB_PATH: &str = "db-path";
+const FORCE: &str = "force";
+const OUTPUT: &str = "output";
+const TRIE: &str = "trie";
+
+/// Errors encountered when staging a copy of a database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error copying a file.
+    #[error("Error copying {0} to {1}: {2}")]
+    Copy(PathBuf, PathBuf, IoError),
+    /// Not enough space for a full copy.
+    #[error(transparent)]
+    DiskSpace(#[from] DiskSpaceError),
+    /// The staging directory already holds a database.
+    #[error("{0} already exists")]
+    Existing(PathBuf),
+    /// Error creating the staging directory.
+    #[error("Error creating {0}: {1}")]
+    Output(PathBuf, IoError),
+    /// Error reading a source file.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Trie,
+    Force,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Creates a staging copy of a storage database to run destructive \
+            experiments on, like purges or migrations, and throw away \
+            afterwards. The files are cloned where the file system supports \
+            it, like on btrfs or XFS, which is instant and only takes space \
+            for the pages written to afterwards, and copied otherwise. The \
+            database must not be in use by a node while staging it.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file to stage."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .required(true)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("DIR_PATH")
+                .help(
+                    "Path of the staging directory, created if needed. It must \
+                    not hold a database already.",
+                ),
+        )
+        .arg(
+            Arg::new(TRIE)
+                .display_order(DisplayOrder::Trie as usize)
+                .long(TRIE)
+                .takes_value(false)
+                .help("Stage the `data.lmdb` trie store along with the storage database."),
+        )
+        .arg(
+            Arg::new(FORCE)
+                .display_order(DisplayOrder::Force as usize)
+                .long(FORCE)
+                .takes_value(false)
+                .help(
+                    "Proceed even if the staging directory doesn't seem to have \
+                    enough free space for a full copy when the files can't be \
+                    cloned.",
+                ),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let output = Path::new(matches.value_of(OUTPUT).expect("should have output arg"));
+    staging::stage(
+        db_path,
+        output,
+        matches.is_present(TRIE),
+        matches.is_present(FORCE),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,164 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::Error as IoError,
+    path::Path,
+    result::Result,
+};
+
+use log::{info, warn};
+
+use crate::{
+    common::{
+        db::{STORAGE_FILE_NAME, TRIE_STORE_FILE_NAME},
+        disk_space::{self, Error as DiskSpaceError},
+        stamp::STAMP_FILE_NAME,
+    },
+    subcommands::archive::unpack::sparse_file,
+};
+
+use super::Error;
+
+/// How a file was staged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Method {
+    /// The file shares its extents with the source until either is written
+    /// to.
+    Clone,
+    /// The file was copied, leaving the pages of zeros as holes.
+    Copy,
+}
+
+/// Clones `source` into `dest`, sharing their extents. Returns `false` if
+/// the file system can't clone files, or not between these two.
+#[cfg(target_os = "linux")]
+fn clone_file(source: &File, dest: &File) -> Result<bool, IoError> {
+    use std::os::unix::io::AsRawFd;
+
+    let result = unsafe { libc::ioctl(dest.as_raw_fd(), libc::FICLONE as _, source.as_raw_fd()) };
+    if result == 0 {
+        return Ok(true);
+    }
+    let io_err = IoError::last_os_error();
+    match io_err.raw_os_error() {
+        Some(libc::EOPNOTSUPP | libc::EXDEV | libc::EINVAL | libc::ENOTTY) => Ok(false),
+        _ => Err(io_err),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn clone_file(_source: &File, _dest: &File) -> Result<bool, IoError> {
+    Ok(false)
+}
+
+/// Copies `source`, the file at `source_path`, to the empty file at
+/// `dest_path` once `check_space` confirms there is room at `output` for the
+/// bytes allocated to the source. The destination is removed if the copy
+/// doesn't go through, so that staging can be retried.
+pub(super) fn copy_file<F>(
+    source: &mut File,
+    source_path: &Path,
+    dest_path: &Path,
+    output: &Path,
+    check_space: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&Path, u64) -> Result<(), DiskSpaceError>,
+{
+    let copy = |source: &mut File| -> Result<u64, Error> {
+        let len = source
+            .metadata()
+            .map_err(|io_err| Error::Source(source_path.to_path_buf(), io_err))?
+            .len();
+        let required = disk_space::allocated_size(source_path)
+            .map_err(|io_err| Error::Source(source_path.to_path_buf(), io_err))?;
+        check_space(output, required)?;
+        sparse_file::write_file(source, len, dest_path, true).map_err(|io_err| {
+            Error::Copy(source_path.to_path_buf(), dest_path.to_path_buf(), io_err)
+        })
+    };
+    match copy(source) {
+        Ok(holes_len) => {
+            info!(
+                "Copied {} to {}, {} bytes left as holes.",
+                source_path.display(),
+                dest_path.display(),
+                holes_len
+            );
+            Ok(())
+        }
+        Err(error) => {
+            if let Err(io_err) = fs::remove_file(dest_path) {
+                warn!("Couldn't remove {}: {}", dest_path.display(), io_err);
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Stages the LMDB file `file_name` of `db_dir` into `output`. Hard links
+/// aren't an option, as LMDB writes its pages in place and the experiments
+/// would then alter the source.
+fn stage_file(db_dir: &Path, output: &Path, file_name: &str, force: bool) -> Result<Method, Error> {
+    let source_path = db_dir.join(file_name);
+    let dest_path = output.join(file_name);
+    let copy_err = |io_err| Error::Copy(source_path.clone(), dest_path.clone(), io_err);
+    let mut source =
+        File::open(&source_path).map_err(|io_err| Error::Source(source_path.clone(), io_err))?;
+    let dest = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&dest_path)
+    {
+        Ok(dest) => dest,
+        Err(_) if dest_path.exists() => return Err(Error::Existing(dest_path)),
+        Err(io_err) => return Err(copy_err(io_err)),
+    };
+    if clone_file(&source, &dest).map_err(copy_err)? {
+        info!(
+            "Cloned {} to {}.",
+            source_path.display(),
+            dest_path.display()
+        );
+        return Ok(Method::Clone);
+    }
+    drop(dest);
+
+    copy_file(
+        &mut source,
+        &source_path,
+        &dest_path,
+        output,
+        |path, required| disk_space::check_available_space(path, required, force),
+    )?;
+    Ok(Method::Copy)
+}
+
+/// Stages the storage database in `db_dir`, and its trie store if `trie` is
+/// set, into the `output` directory. The stamp of the database is copied
+/// along. Returns how each LMDB file was staged.
+pub(crate) fn stage<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_dir: P1,
+    output: P2,
+    trie: bool,
+    force: bool,
+) -> Result<Vec<(&'static str, Method)>, Error> {
+    let db_dir = db_dir.as_ref();
+    let output = output.as_ref();
+    fs::create_dir_all(output).map_err(|io_err| Error::Output(output.to_path_buf(), io_err))?;
+
+    let mut file_names = vec![STORAGE_FILE_NAME];
+    if trie {
+        file_names.push(TRIE_STORE_FILE_NAME);
+    }
+    let mut staged = vec![];
+    for file_name in file_names {
+        let method = stage_file(db_dir, output, file_name, force)?;
+        staged.push((file_name, method));
+    }
+    let stamp_path = db_dir.join(STAMP_FILE_NAME);
+    if stamp_path.exists() {
+        fs::copy(&stamp_path, output.join(STAMP_FILE_NAME))
+            .map_err(|io_err| Error::Copy(stamp_path, output.join(STAMP_FILE_NAME), io_err))?;
+    }
+    Ok(staged)
+}
@@ -0,0 +1,144 @@
+use std::fs::{self, File, OpenOptions};
+
+use lmdb::{Transaction, WriteFlags};
+
+use super::{
+    staging::{copy_file, stage},
+    Error,
+};
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, StorageEnv, STORAGE_FILE_NAME},
+        disk_space::{self, Error as DiskSpaceError},
+        stamp::{self, Stamp},
+    },
+    test_utils::LmdbTestFixture,
+};
+
+#[test]
+fn staged_copy_should_be_independent() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(db, &[1u8; 32], &[2u8; 64], WriteFlags::empty())
+        .unwrap();
+    txn.commit().unwrap();
+    Stamp {
+        chain_name: Some("casper-test".to_string()),
+        ..Default::default()
+    }
+    .write(fixture.tmp_dir.path())
+    .unwrap();
+
+    let stage_dir = tempfile::tempdir().unwrap();
+    let output = stage_dir.path().join("stage");
+    let staged = stage(fixture.tmp_dir.path(), &output, false, false).unwrap();
+    assert_eq!(staged.len(), 1);
+    assert_eq!(staged[0].0, STORAGE_FILE_NAME);
+    assert_eq!(
+        stamp::chain_name(&output).unwrap().as_deref(),
+        Some("casper-test")
+    );
+
+    // Purging the staged copy leaves the source untouched.
+    let staged_env = StorageEnv::open(&output).unwrap();
+    let staged_db = staged_env.db::<BlockHeaderDatabase>().unwrap();
+    let mut txn = staged_env.begin_rw_txn().unwrap();
+    txn.del(staged_db, &[1u8; 32], None).unwrap();
+    txn.commit().unwrap();
+    let txn = staged_env.begin_ro_txn().unwrap();
+    assert!(txn.get(staged_db, &[1u8; 32]).is_err());
+    drop(txn);
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    assert_eq!(txn.get(db, &[1u8; 32]).unwrap(), [2u8; 64]);
+    drop(txn);
+
+    // A staging directory is never overwritten.
+    match stage(fixture.tmp_dir.path(), &output, false, false) {
+        Err(Error::Existing(path)) => assert_eq!(path, output.join(STORAGE_FILE_NAME)),
+        Err(error) => panic!("Got unexpected error: {error:?}"),
+        Ok(_) => panic!("Staging unexpectedly succeeded"),
+    }
+    // There is no trie store to stage.
+    assert!(matches!(
+        stage(
+            fixture.tmp_dir.path(),
+            stage_dir.path().join("trie"),
+            true,
+            false
+        ),
+        Err(Error::Source(..))
+    ));
+}
+
+#[test]
+fn copy_should_stop_when_space_check_fails() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(db, &[1u8; 32], &[2u8; 64], WriteFlags::empty())
+        .unwrap();
+    txn.commit().unwrap();
+    let source_path = fixture.tmp_dir.path().join(STORAGE_FILE_NAME);
+    let source_bytes = fs::read(&source_path).unwrap();
+
+    let stage_dir = tempfile::tempdir().unwrap();
+    let dest_path = stage_dir.path().join(STORAGE_FILE_NAME);
+    let create_dest = || {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&dest_path)
+            .unwrap()
+    };
+
+    // The check is given the staging directory and the bytes allocated to
+    // the source. Failing it leaves no empty file behind.
+    let allocated = disk_space::allocated_size(&source_path).unwrap();
+    create_dest();
+    let mut source = File::open(&source_path).unwrap();
+    let result = copy_file(
+        &mut source,
+        &source_path,
+        &dest_path,
+        stage_dir.path(),
+        |path, required| {
+            assert_eq!(path, stage_dir.path());
+            assert_eq!(required, allocated);
+            Err(DiskSpaceError::Insufficient {
+                path: path.to_path_buf(),
+                required,
+                available: 0,
+            })
+        },
+    );
+    assert!(matches!(
+        result,
+        Err(Error::DiskSpace(DiskSpaceError::Insufficient { .. }))
+    ));
+    assert!(!dest_path.exists());
+
+    // With `--force`, a lack of space only warns and the copy goes through.
+    create_dest();
+    copy_file(
+        &mut source,
+        &source_path,
+        &dest_path,
+        stage_dir.path(),
+        |path, _| disk_space::check_available_space(path, u64::MAX, true),
+    )
+    .unwrap();
+    assert_eq!(fs::read(&dest_path).unwrap(), source_bytes);
+
+    // Staging can be retried after a failed check.
+    fs::remove_file(&dest_path).unwrap();
+    let staged = stage(fixture.tmp_dir.path(), stage_dir.path(), false, false).unwrap();
+    assert_eq!(staged.len(), 1);
+    assert_eq!(fs::read(&dest_path).unwrap(), source_bytes);
+}
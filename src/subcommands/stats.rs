@@ -0,0 +1,165 @@
+pub(crate) mod collect;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path};
+
+use bincode::Error as BincodeError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+pub(crate) use collect::{
+    completeness_profile, entry_counts, CompletenessProfile, STATS_DATABASES,
+};
+
+use super::{
+    archive::{
+        snapshot::{DbDir, Include},
+        UnpackError,
+    },
+    latest_block_summary::Error as CompletenessError,
+};
+use crate::common::{
+    human,
+    output::{self, Compression},
+    state_store::Error as StateStoreError,
+    storage::{self, Backend, Error as StorageError},
+};
+
+pub const COMMAND_NAME: &str = "stats";
+const COMPLETENESS: &str = "completeness";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when gathering statistics on the storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error checking which parts of a block are present.
+    #[error("Error checking block completeness: {0}")]
+    Completeness(#[from] CompletenessError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Key of an entry is not a valid hash.
+    #[error("Invalid key at index {0} in block header DB")]
+    InvalidKey(usize),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on entry at index in the block header database.
+    #[error("Error parsing block header at index {0}: {1}")]
+    Parsing(usize, BincodeError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Error decoding the state store.
+    #[error("Error reading the state store: {0}")]
+    StateStore(#[from] StateStoreError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    Completeness,
+    Human,
+    Backend,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Outputs the number of entries in each database of a storage \
+            instance and, optionally, how complete the stored blocks are.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the statistics in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(COMPLETENESS)
+                .display_order(DisplayOrder::Completeness as usize)
+                .required(false)
+                .short('c')
+                .long(COMPLETENESS)
+                .takes_value(false)
+                .help(
+                    "Also report the share of block headers which have their \
+                    body, all their deploys, all their execution results and \
+                    their finality signatures in the database. This reads \
+                    every block, so it can take a while.",
+                ),
+        )
+        .arg(human::human_arg(DisplayOrder::Human as usize))
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let path = db_dir.path();
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let compression = Compression::from_matches(matches);
+    let completeness = matches.is_present(COMPLETENESS);
+    collect::stats(
+        path,
+        output,
+        overwrite,
+        compression,
+        completeness,
+        human::is_human(matches),
+        Backend::from_matches(matches),
+    )
+}
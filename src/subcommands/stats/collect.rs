@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, fs, io::Write, path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, BlockHeader};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        db::{
+            BlockHeaderDatabase, Database, EnvTuning, FinalizedApprovalsDatabase, STORAGE_FILE_NAME,
+        },
+        human::{self, Unit},
+        output::{Compression, OutputWriter},
+        progress::ProgressTracker,
+        state_store::{self, StateStoreSummary},
+        storage::{Backend, Error as StorageError, Storage, StorageReader},
+    },
+    subcommands::latest_block_summary::completeness,
+};
+
+use super::Error;
+
+/// Names of the databases for which entries are counted.
+pub(crate) const STATS_DATABASES: [&str; 12] = [
+    "block_body",
+    "block_body_merkle",
+    "block_header",
+    "block_metadata",
+    "deploy_hashes",
+    "deploy_metadata",
+    "deploys",
+    "finalized_approvals",
+    "proposers",
+    "state_store",
+    "transfer",
+    "transfer_hashes",
+];
+
+/// Fields of the statistics given in units with `--human`.
+const HUMAN_FIELDS: &[(&str, Unit)] = &[
+    ("finalized_approvals_size", Unit::Bytes),
+    ("storage_size", Unit::Bytes),
+];
+
+/// Number of blocks with some property, and their share of all blocks.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Share {
+    pub(crate) count: usize,
+    pub(crate) percentage: f64,
+}
+
+impl Share {
+    fn new(count: usize, total: usize) -> Self {
+        let percentage = if total == 0 {
+            0.0
+        } else {
+            count as f64 * 100.0 / total as f64
+        };
+        Self { count, percentage }
+    }
+}
+
+/// How many of the block headers in the database have the rest of their
+/// block stored alongside them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct CompletenessProfile {
+    pub(crate) blocks: usize,
+    pub(crate) with_body: Share,
+    pub(crate) with_deploys: Share,
+    pub(crate) with_execution_results: Share,
+    pub(crate) with_signatures: Share,
+}
+
+/// Statistics on a storage database.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Stats {
+    pub(crate) entry_counts: BTreeMap<String, usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) completeness: Option<CompletenessProfile>,
+    /// Decoded contents of the state store, which drive how the node
+    /// restarts.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) state_store: Option<StateStoreSummary>,
+    /// Total size of the finalized approvals, in bytes, if the database
+    /// has them.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) finalized_approvals_size: Option<u64>,
+    /// Size of the `storage.lmdb` file, in bytes. Not reported for the
+    /// other backends.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub(crate) storage_size: Option<u64>,
+}
+
+/// Counts the entries of each database `reader` reads from, skipping the
+/// ones which don't exist.
+pub(crate) fn entry_counts<R: StorageReader>(
+    reader: &R,
+) -> Result<BTreeMap<&'static str, usize>, StorageError> {
+    let mut counts = BTreeMap::new();
+    for db_name in STATS_DATABASES {
+        if reader.has_table(db_name)? {
+            counts.insert(db_name, reader.entry_count(db_name)?);
+        }
+    }
+    Ok(counts)
+}
+
+/// Sums the sizes of the values of the finalized approvals database, or
+/// returns `None` if it doesn't exist.
+fn finalized_approvals_size<R: StorageReader>(reader: &R) -> Result<Option<u64>, StorageError> {
+    if !reader.has_table(FinalizedApprovalsDatabase::db_name())? {
+        return Ok(None);
+    }
+    let mut size = 0;
+    reader.scan(
+        FinalizedApprovalsDatabase::db_name(),
+        |_raw_key, raw_val| -> Result<(), StorageError> {
+            size += raw_val.len() as u64;
+            Ok(())
+        },
+    )?;
+    Ok(Some(size))
+}
+
+/// Checks which parts of each block of the database `reader` reads from are
+/// present.
+pub(crate) fn completeness_profile<R: StorageReader>(
+    reader: &R,
+) -> Result<CompletenessProfile, Error> {
+    let header_count = reader.entry_count(BlockHeaderDatabase::db_name())?;
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        header_count,
+        Box::new(|completion| info!("Block completeness {}% checked...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+
+    let mut blocks = 0;
+    let mut with_body = 0;
+    let mut with_deploys = 0;
+    let mut with_execution_results = 0;
+    let mut with_signatures = 0;
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            let block_hash: BlockHash = Digest::try_from(raw_key)
+                .map_err(|_| Error::InvalidKey(blocks))?
+                .into();
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::Parsing(blocks, bincode_err))?;
+            let block = completeness::block_completeness(reader, &block_hash, &header)?;
+            blocks += 1;
+            with_body += block.has_body as usize;
+            with_deploys += block.has_deploys as usize;
+            with_execution_results += block.has_execution_results as usize;
+            with_signatures += block.has_signatures as usize;
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+            Ok(())
+        },
+    )?;
+
+    Ok(CompletenessProfile {
+        blocks,
+        with_body: Share::new(with_body, blocks),
+        with_deploys: Share::new(with_deploys, blocks),
+        with_execution_results: Share::new(with_execution_results, blocks),
+        with_signatures: Share::new(with_signatures, blocks),
+    })
+}
+
+pub fn stats<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    output: Option<P2>,
+    overwrite: bool,
+    compression: Compression,
+    completeness: bool,
+    human: bool,
+    backend: Backend,
+) -> Result<(), Error> {
+    let storage = Storage::open(&db_path, backend, EnvTuning::default())?;
+    let (entry_counts, state_store, finalized_approvals_size) = {
+        let reader = storage.view()?;
+        let entry_counts = entry_counts(&reader)?
+            .into_iter()
+            .map(|(db_name, count)| (db_name.to_string(), count))
+            .collect();
+        (
+            entry_counts,
+            state_store::read_state_store(&reader)?,
+            finalized_approvals_size(&reader)?,
+        )
+    };
+    let completeness = if completeness {
+        Some(completeness_profile(&storage.view()?)?)
+    } else {
+        None
+    };
+    let storage_size = match backend {
+        Backend::Lmdb => fs::metadata(db_path.as_ref().join(STORAGE_FILE_NAME))
+            .ok()
+            .map(|metadata| metadata.len()),
+        Backend::RocksDb => None,
+    };
+    let stats = Stats {
+        entry_counts,
+        completeness,
+        state_store,
+        finalized_approvals_size,
+        storage_size,
+    };
+
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    human::to_writer_pretty(&mut out_writer, &stats, human, HUMAN_FIELDS)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
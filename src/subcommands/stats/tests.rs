@@ -0,0 +1,266 @@
+use std::fs;
+
+use casper_node::types::BlockHash;
+use lmdb::{Transaction, WriteFlags};
+
+use super::collect::{self, CompletenessProfile, Share, Stats};
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, BlockMetadataDatabase,
+            Database, DeployDatabase, DeployHashesDatabase, DeployMetadataDatabase,
+            FinalizedApprovalsDatabase, TransferHashesDatabase, STORAGE_FILE_NAME,
+        },
+        human,
+        output::Compression,
+        storage::Backend,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{
+        mock_block_header, mock_deploy_hash, mock_deploy_metadata, LmdbTestFixture, MockBlockHeader,
+    },
+};
+
+#[test]
+fn stats_should_report_entry_counts_and_completeness() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("stats.json");
+
+    let blocks: Vec<(BlockHash, MockBlockHeader)> = (0..4u8)
+        .map(|idx| {
+            let (block_hash, mut block_header) = mock_block_header(idx);
+            block_header.height = idx as u64;
+            (block_hash, block_header)
+        })
+        .collect();
+    let stored_deploy = mock_deploy_hash(0);
+    let missing_deploy = mock_deploy_hash(1);
+
+    let env = &fixture.env;
+    if let Ok(mut txn) = env.begin_rw_txn() {
+        for (block_hash, block_header) in blocks.iter() {
+            txn.put(
+                *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+                block_hash,
+                &bincode::serialize(block_header).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        // Block 0 has an empty body and signatures.
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &blocks[0].1.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(BlockMetadataDatabase::db_name())).unwrap(),
+            &blocks[0].0,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        // Block 1 has a body with a deploy and its execution results.
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &blocks[1].1.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![stored_deploy])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            &stored_deploy,
+            &[0u8],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployMetadataDatabase::db_name())).unwrap(),
+            &stored_deploy,
+            &bincode::serialize(&mock_deploy_metadata(&[blocks[1].0])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        // Block 2 has a body referencing a deploy which isn't stored.
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &blocks[2].1.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![missing_deploy])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        // Block 3 has no body.
+        txn.commit().unwrap();
+    };
+
+    collect::stats(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        false,
+        false,
+        Backend::Lmdb,
+    )
+    .unwrap();
+    let stats: Stats = serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    assert!(stats.completeness.is_none());
+    assert_eq!(stats.entry_counts.get("block_header"), Some(&4));
+    assert_eq!(stats.entry_counts.get("block_body"), Some(&3));
+    assert_eq!(stats.entry_counts.get("deploys"), Some(&1));
+    // Databases which don't exist aren't reported.
+    assert!(!stats.entry_counts.contains_key("state_store"));
+    assert!(stats.finalized_approvals_size.is_none());
+    let storage_size = fs::metadata(fixture.tmp_dir.path().join(STORAGE_FILE_NAME))
+        .unwrap()
+        .len();
+    assert_eq!(stats.storage_size, Some(storage_size));
+
+    // The output file exists already.
+    assert!(collect::stats(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        true,
+        false,
+        Backend::Lmdb,
+    )
+    .is_err());
+    collect::stats(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        true,
+        false,
+        Backend::Lmdb,
+    )
+    .unwrap();
+    let stats: Stats = serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    assert_eq!(
+        stats.completeness,
+        Some(CompletenessProfile {
+            blocks: 4,
+            with_body: Share {
+                count: 3,
+                percentage: 75.0
+            },
+            with_deploys: Share {
+                count: 2,
+                percentage: 50.0
+            },
+            with_execution_results: Share {
+                count: 2,
+                percentage: 50.0
+            },
+            with_signatures: Share {
+                count: 1,
+                percentage: 25.0
+            },
+        })
+    );
+    // With `--human`, the size is followed by its formatted value.
+    collect::stats(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        true,
+        Compression::None,
+        false,
+        true,
+        Backend::Lmdb,
+    )
+    .unwrap();
+    let json_value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    assert_eq!(json_value["storage_size"], storage_size);
+    assert_eq!(
+        json_value["storage_size_human"],
+        human::format_size(storage_size as f64)
+    );
+}
+
+#[test]
+fn stats_should_report_finalized_approvals_size() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_dir = tempfile::tempdir().unwrap();
+    let out_file_path = out_dir.path().join("stats.json");
+
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, size) in [3, 5].into_iter().enumerate() {
+        txn.put(
+            *fixture
+                .db(Some(FinalizedApprovalsDatabase::db_name()))
+                .unwrap(),
+            &mock_deploy_hash(idx as u8),
+            &vec![0u8; size],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    collect::stats(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::None,
+        false,
+        true,
+        Backend::Lmdb,
+    )
+    .unwrap();
+    let json_value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(&out_file_path).unwrap()).unwrap();
+    assert_eq!(json_value["entry_counts"]["finalized_approvals"], 2);
+    assert_eq!(json_value["finalized_approvals_size"], 8);
+    assert_eq!(
+        json_value["finalized_approvals_size_human"],
+        human::format_size(8.0)
+    );
+}
+
+#[test]
+fn stats_should_compress_output() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let out_file_path = fixture.tmp_dir.as_ref().join("stats.json.zst");
+
+    collect::stats(
+        fixture.tmp_dir.as_ref(),
+        Some(out_file_path.as_path()),
+        false,
+        Compression::Zstd(3),
+        false,
+        false,
+        Backend::Lmdb,
+    )
+    .unwrap();
+    let json_bytes = zstd::decode_all(fs::File::open(&out_file_path).unwrap()).unwrap();
+    let stats: Stats = serde_json::from_slice(&json_bytes).unwrap();
+    assert_eq!(stats.entry_counts.get("block_header"), Some(&0));
+}
@@ -0,0 +1,208 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path, str::FromStr};
+
+use bincode::Error as BincodeError;
+use casper_execution_engine::{
+    core::engine_state::Error as EngineStateError, storage::error::Error as GlobalStateError,
+};
+use casper_hashing::Digest;
+use casper_types::{Key, Timestamp};
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+use report::Format;
+
+pub const COMMAND_NAME: &str = "supply";
+const DB_PATH: &str = "db-path";
+const FORMAT: &str = "format";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const PER_ERA: &str = "per-era";
+const STATE_ROOT: &str = "state-root";
+const TIMESTAMP: &str = "timestamp";
+
+/// Errors encountered when computing the supply.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error reading the system contracts.
+    #[error("Error reading system contracts: {0}")]
+    EngineState(#[from] EngineStateError),
+    /// Error reading global state.
+    #[error("Error reading global state: {0}")]
+    GlobalState(#[from] GlobalStateError),
+    /// Error loading the trie store.
+    #[error("Error loading the execution engine: {0}")]
+    LoadExecutionEngine(anyhow::Error),
+    /// The state root isn't in the trie store.
+    #[error("State root {0} not found in the trie store")]
+    MissingStateRoot(Digest),
+    /// A key listed in global state has no value.
+    #[error("No value under {0}")]
+    MissingValue(Key),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on a block header.
+    #[error("Error parsing block header at index {0}: {1}")]
+    Parsing(usize, BincodeError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// A value in global state isn't of the type stored under its key.
+    #[error("Unexpected value under {0}")]
+    UnexpectedValue(Key),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    StateRoot,
+    Timestamp,
+    PerEra,
+    Format,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Computes the total supply recorded by the mint under a state \
+            root, along with the staked, vesting-locked and unbonding amounts \
+            and the circulating supply. With `--per-era`, computes it at the \
+            state root of every switch block instead, giving a time series of \
+            the supply.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `data.lmdb` file, and the \
+                    `storage.lmdb` file with `--per-era`.",
+                ),
+        )
+        .arg(
+            Arg::new(STATE_ROOT)
+                .display_order(DisplayOrder::StateRoot as usize)
+                .required_unless_present(PER_ERA)
+                .conflicts_with(PER_ERA)
+                .short('r')
+                .long(STATE_ROOT)
+                .takes_value(true)
+                .value_name("STATE_ROOT_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help("Hash of the state root to compute the supply at, in hex."),
+        )
+        .arg(
+            Arg::new(TIMESTAMP)
+                .display_order(DisplayOrder::Timestamp as usize)
+                .short('t')
+                .long(TIMESTAMP)
+                .takes_value(true)
+                .value_name("TIMESTAMP")
+                .requires(STATE_ROOT)
+                .validator(|value| Timestamp::from_str(value).map(|_| ()))
+                .help(
+                    "Time at which the vesting schedules of the stakes are \
+                    evaluated, such as `2021-03-31T15:00:00Z`. Defaults to \
+                    now.",
+                ),
+        )
+        .arg(
+            Arg::new(PER_ERA)
+                .display_order(DisplayOrder::PerEra as usize)
+                .long(PER_ERA)
+                .takes_value(false)
+                .help(
+                    "Compute the supply at the end of every era found in the \
+                    storage database, at the timestamp of its switch block.",
+                ),
+        )
+        .arg(
+            Arg::new(FORMAT)
+                .display_order(DisplayOrder::Format as usize)
+                .short('f')
+                .long(FORMAT)
+                .takes_value(true)
+                .value_name("json|csv")
+                .possible_values(["json", "csv"])
+                .default_value("json")
+                .help("Format of the output."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the supply. If \
+                    unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let format = match matches.value_of(FORMAT) {
+        Some("csv") => Format::Csv,
+        _ => Format::Json,
+    };
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let points = match matches.value_of(STATE_ROOT) {
+        Some(state_root) => {
+            let state_root_hash = Digest::from_hex(state_root).expect("should be validated");
+            let timestamp = matches
+                .value_of(TIMESTAMP)
+                .map(|value| Timestamp::from_str(value).expect("should be validated"))
+                .unwrap_or_else(Timestamp::now);
+            vec![report::supply_at(path, state_root_hash, timestamp)?]
+        }
+        None => report::supply_per_era(path)?,
+    };
+    report::write_points(&points, format, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,294 @@
+use std::{io::Write, path::Path, result::Result, sync::Arc};
+
+use casper_execution_engine::{
+    core::engine_state::EngineState,
+    shared::newtypes::CorrelationId,
+    storage::global_state::{
+        lmdb::{LmdbGlobalState, LmdbGlobalStateView},
+        StateProvider, StateReader,
+    },
+};
+use casper_hashing::Digest;
+use casper_node::types::BlockHeader;
+use casper_types::{
+    system::{auction::Bid, mint::TOTAL_SUPPLY_KEY},
+    EraId, Key, KeyTag, StoredValue, Timestamp, U512,
+};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, StorageEnv},
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::trie_compact::{load_execution_engine, DEFAULT_MAX_DB_SIZE},
+};
+
+use super::Error;
+
+/// Header of the CSV output, in the order `SupplyPoint::write_csv` writes
+/// the fields.
+const CSV_HEADER: &str = "era_id,height,state_root_hash,timestamp,total_supply,staked,locked,\
+    unbonding,circulating";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Format {
+    Json,
+    Csv,
+}
+
+/// The supply of motes in the global state under a state root.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct SupplyPoint {
+    /// Era ended by the switch block of the state root, absent when the
+    /// state root was given directly.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) era_id: Option<EraId>,
+    /// Height of the switch block of the state root.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) height: Option<u64>,
+    pub(crate) state_root_hash: Digest,
+    /// Time at which the vesting schedules are evaluated.
+    pub(crate) timestamp: Timestamp,
+    /// Total supply recorded by the mint.
+    pub(crate) total_supply: U512,
+    /// Stake of the validators and delegators.
+    pub(crate) staked: U512,
+    /// Part of the stake still locked by a vesting schedule.
+    pub(crate) locked: U512,
+    /// Amount of the unbonding purses.
+    pub(crate) unbonding: U512,
+    /// Total supply minus the locked stake.
+    pub(crate) circulating: U512,
+}
+
+impl SupplyPoint {
+    fn write_csv<W: Write + ?Sized>(&self, writer: &mut W) -> Result<(), Error> {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            self.era_id
+                .map(|era_id| era_id.value().to_string())
+                .unwrap_or_default(),
+            self.height
+                .map(|height| height.to_string())
+                .unwrap_or_default(),
+            self.state_root_hash,
+            self.timestamp,
+            self.total_supply,
+            self.staked,
+            self.locked,
+            self.unbonding,
+            self.circulating
+        )?;
+        Ok(())
+    }
+}
+
+/// Reads the supply from the global state of a trie store.
+struct GlobalState {
+    engine_state: Arc<EngineState<LmdbGlobalState>>,
+    correlation_id: CorrelationId,
+}
+
+impl GlobalState {
+    fn open<P: AsRef<Path>>(db_path: P) -> Result<Self, Error> {
+        let max_db_size = DEFAULT_MAX_DB_SIZE
+            .parse()
+            .expect("should be able to parse max db size");
+        let (engine_state, _env) =
+            load_execution_engine(db_path, max_db_size, Digest::default(), true)
+                .map_err(Error::LoadExecutionEngine)?;
+        Ok(Self {
+            engine_state,
+            correlation_id: CorrelationId::new(),
+        })
+    }
+
+    /// Returns the view of the global state under `state_root_hash`, or
+    /// `None` if the trie store doesn't have it.
+    fn checkout(&self, state_root_hash: Digest) -> Result<Option<LmdbGlobalStateView>, Error> {
+        Ok(self.engine_state.get_state().checkout(state_root_hash)?)
+    }
+
+    fn read(&self, view: &LmdbGlobalStateView, key: &Key) -> Result<StoredValue, Error> {
+        view.read(self.correlation_id, key)?
+            .ok_or(Error::MissingValue(*key))
+    }
+
+    /// Returns the values of the keys with `key_tag` under `view`.
+    fn values_with_tag(
+        &self,
+        view: &LmdbGlobalStateView,
+        key_tag: KeyTag,
+    ) -> Result<Vec<StoredValue>, Error> {
+        view.keys_with_prefix(self.correlation_id, &[key_tag as u8])?
+            .iter()
+            .map(|key| self.read(view, key))
+            .collect()
+    }
+
+    fn total_supply(
+        &self,
+        view: &LmdbGlobalStateView,
+        state_root_hash: Digest,
+    ) -> Result<U512, Error> {
+        let mint_hash = self
+            .engine_state
+            .get_system_mint_hash(self.correlation_id, state_root_hash)?;
+        let mint_key = Key::Hash(mint_hash.value());
+        let total_supply_key = match self.read(view, &mint_key)? {
+            StoredValue::Contract(mint) => mint
+                .named_keys()
+                .get(TOTAL_SUPPLY_KEY)
+                .map(|key| key.normalize())
+                .ok_or(Error::UnexpectedValue(mint_key))?,
+            _ => return Err(Error::UnexpectedValue(mint_key)),
+        };
+        match self.read(view, &total_supply_key)? {
+            StoredValue::CLValue(cl_value) => cl_value
+                .into_t()
+                .map_err(|_| Error::UnexpectedValue(total_supply_key)),
+            _ => Err(Error::UnexpectedValue(total_supply_key)),
+        }
+    }
+
+    /// Computes the supply under `state_root_hash`, evaluating the vesting
+    /// schedules at `timestamp`. Returns `None` if the trie store doesn't
+    /// have the state root.
+    fn supply(
+        &self,
+        state_root_hash: Digest,
+        timestamp: Timestamp,
+    ) -> Result<Option<SupplyPoint>, Error> {
+        let view = match self.checkout(state_root_hash)? {
+            Some(view) => view,
+            None => return Ok(None),
+        };
+        let total_supply = self.total_supply(&view, state_root_hash)?;
+        let mut staked = U512::zero();
+        let mut locked = U512::zero();
+        for value in self.values_with_tag(&view, KeyTag::Bid)? {
+            let bid: Bid = match value {
+                StoredValue::Bid(bid) => *bid,
+                _ => continue,
+            };
+            // The whole stake is locked until its vesting schedule starts.
+            let stakes = bid
+                .delegators()
+                .values()
+                .map(|delegator| (*delegator.staked_amount(), delegator.vesting_schedule()))
+                .chain([(*bid.staked_amount(), bid.vesting_schedule())]);
+            for (staked_amount, maybe_vesting_schedule) in stakes {
+                staked += staked_amount;
+                if let Some(vesting_schedule) = maybe_vesting_schedule {
+                    locked += vesting_schedule
+                        .locked_amount(timestamp.millis())
+                        .unwrap_or(staked_amount);
+                }
+            }
+        }
+        let mut unbonding = U512::zero();
+        for value in self.values_with_tag(&view, KeyTag::Withdraw)? {
+            if let StoredValue::Withdraw(unbonding_purses) = value {
+                for unbonding_purse in unbonding_purses {
+                    unbonding += *unbonding_purse.amount();
+                }
+            }
+        }
+        Ok(Some(SupplyPoint {
+            era_id: None,
+            height: None,
+            state_root_hash,
+            timestamp,
+            total_supply,
+            staked,
+            locked,
+            unbonding,
+            circulating: total_supply.saturating_sub(locked),
+        }))
+    }
+}
+
+/// Computes the supply in the global state under `state_root_hash` in the
+/// trie store in `db_path`, evaluating the vesting schedules at `timestamp`.
+pub(crate) fn supply_at<P: AsRef<Path>>(
+    db_path: P,
+    state_root_hash: Digest,
+    timestamp: Timestamp,
+) -> Result<SupplyPoint, Error> {
+    GlobalState::open(db_path)?
+        .supply(state_root_hash, timestamp)?
+        .ok_or(Error::MissingStateRoot(state_root_hash))
+}
+
+/// Computes the supply at the state root of every switch block in the
+/// storage database in `db_path`, in ascending era order. The vesting
+/// schedules are evaluated at the timestamp of each switch block. Switch
+/// blocks whose state root isn't in the trie store are skipped.
+pub(crate) fn supply_per_era<P: AsRef<Path>>(db_path: P) -> Result<Vec<SupplyPoint>, Error> {
+    let mut switch_blocks = vec![];
+    {
+        let env = StorageEnv::open(&db_path)?;
+        let txn = env.begin_ro_txn()?;
+        let reader = LmdbReader::new(&txn);
+        let mut idx = 0;
+        reader.scan(
+            BlockHeaderDatabase::db_name(),
+            |_raw_key, raw_val| -> Result<(), Error> {
+                let header: BlockHeader = bincode::deserialize(raw_val)
+                    .map_err(|bincode_err| Error::Parsing(idx, bincode_err))?;
+                idx += 1;
+                if header.is_switch_block() {
+                    switch_blocks.push((
+                        header.era_id(),
+                        header.height(),
+                        *header.state_root_hash(),
+                        header.timestamp(),
+                    ));
+                }
+                Ok(())
+            },
+        )?;
+    }
+    switch_blocks.sort_unstable();
+
+    let global_state = GlobalState::open(&db_path)?;
+    let mut points = Vec::with_capacity(switch_blocks.len());
+    for (era_id, height, state_root_hash, timestamp) in switch_blocks {
+        match global_state.supply(state_root_hash, timestamp)? {
+            Some(mut point) => {
+                point.era_id = Some(era_id);
+                point.height = Some(height);
+                points.push(point);
+            }
+            None => warn!(
+                "State root {} of the switch block of era {} is missing, skipping it.",
+                state_root_hash, era_id
+            ),
+        }
+    }
+    info!("Computed the supply at {} switch blocks.", points.len());
+    Ok(points)
+}
+
+pub(crate) fn write_points<W: Write + ?Sized>(
+    points: &[SupplyPoint],
+    format: Format,
+    writer: &mut W,
+) -> Result<(), Error> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *writer, points)?;
+            writeln!(writer)?;
+        }
+        Format::Csv => {
+            writeln!(writer, "{}", CSV_HEADER)?;
+            for point in points {
+                point.write_csv(writer)?;
+            }
+        }
+    }
+    Ok(())
+}
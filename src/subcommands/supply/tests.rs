@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, HashMap};
+
+use casper_execution_engine::{
+    shared::newtypes::CorrelationId, storage::global_state::StateProvider,
+};
+use casper_hashing::Digest;
+use casper_types::{
+    contracts::NamedKeys,
+    system::{
+        auction::{Bid, Delegator, UnbondingPurse},
+        mint::TOTAL_SUPPLY_KEY,
+        MINT,
+    },
+    AccessRights, CLValue, Contract, ContractHash, ContractPackageHash, ContractWasmHash,
+    EntryPoints, EraId, Key, ProtocolVersion, StoredValue, Timestamp, URef, U512,
+};
+use lmdb::{DatabaseFlags, Transaction, WriteFlags};
+use tempfile::TempDir;
+
+use super::{
+    report::{supply_at, supply_per_era, write_points, Format},
+    Error,
+};
+use crate::{
+    common::db::{BlockHeaderDatabase, Database, StorageEnv},
+    subcommands::trie_compact::{create_execution_engine, DEFAULT_MAX_DB_SIZE},
+    test_utils::{self, KEYS},
+};
+
+const RELEASE_TIMESTAMP_MILLIS: u64 = 1_000;
+const WEEK_MILLIS: u64 = 7 * 24 * 60 * 60 * 1_000;
+
+/// Creates a trie store with a mint recording a total supply of 1000, a
+/// validator of `KEYS[0]` staking 140 under a vesting schedule released at
+/// `RELEASE_TIMESTAMP_MILLIS`, with a delegation of 60 from `KEYS[1]`, and an
+/// unbonding purse of 25.
+fn new_global_state(tmp_dir: &TempDir) -> Digest {
+    let (engine_state, _env) =
+        create_execution_engine(tmp_dir.path(), DEFAULT_MAX_DB_SIZE.parse().unwrap(), true)
+            .unwrap();
+
+    let mint_hash = ContractHash::new([1; 32]);
+    let total_supply_uref = URef::new([2; 32], AccessRights::READ_ADD_WRITE);
+    let mut named_keys = NamedKeys::new();
+    named_keys.insert(TOTAL_SUPPLY_KEY.to_string(), Key::URef(total_supply_uref));
+    let mint = Contract::new(
+        ContractPackageHash::new([3; 32]),
+        ContractWasmHash::new([4; 32]),
+        named_keys,
+        EntryPoints::new(),
+        ProtocolVersion::V1_0_0,
+    );
+    let mut registry = BTreeMap::new();
+    registry.insert(MINT.to_string(), mint_hash);
+
+    let bonding_purse = URef::new([5; 32], AccessRights::READ_ADD_WRITE);
+    let mut bid = Bid::locked(
+        KEYS[0].clone(),
+        bonding_purse,
+        U512::from(140),
+        10,
+        RELEASE_TIMESTAMP_MILLIS,
+    );
+    bid.vesting_schedule_mut()
+        .unwrap()
+        .initialize(U512::from(140));
+    bid.delegators_mut().insert(
+        KEYS[1].clone(),
+        Delegator::unlocked(
+            KEYS[1].clone(),
+            U512::from(60),
+            bonding_purse,
+            KEYS[0].clone(),
+        ),
+    );
+
+    let mut stored_values = HashMap::new();
+    stored_values.insert(
+        Key::SystemContractRegistry,
+        StoredValue::CLValue(CLValue::from_t(registry).unwrap()),
+    );
+    stored_values.insert(Key::Hash(mint_hash.value()), StoredValue::Contract(mint));
+    stored_values.insert(
+        Key::URef(total_supply_uref).normalize(),
+        StoredValue::CLValue(CLValue::from_t(U512::from(1_000)).unwrap()),
+    );
+    stored_values.insert(
+        Key::Bid(KEYS[0].to_account_hash()),
+        StoredValue::Bid(Box::new(bid)),
+    );
+    stored_values.insert(
+        Key::Withdraw(KEYS[1].to_account_hash()),
+        StoredValue::Withdraw(vec![UnbondingPurse::new(
+            bonding_purse,
+            KEYS[0].clone(),
+            KEYS[1].clone(),
+            EraId::new(1),
+            U512::from(25),
+        )]),
+    );
+
+    let global_state = engine_state.get_state();
+    let state_root_hash = global_state
+        .put_stored_values(
+            CorrelationId::new(),
+            global_state.empty_root(),
+            stored_values,
+        )
+        .unwrap();
+    engine_state.flush_environment().unwrap();
+    state_root_hash
+}
+
+#[test]
+fn supply_at_should_subtract_locked_stake() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let state_root_hash = new_global_state(&tmp_dir);
+
+    // Before the release, the whole stake of the validator is locked.
+    let point = supply_at(
+        tmp_dir.path(),
+        state_root_hash,
+        Timestamp::from(RELEASE_TIMESTAMP_MILLIS - 1),
+    )
+    .unwrap();
+    assert_eq!(point.era_id, None);
+    assert_eq!(point.state_root_hash, state_root_hash);
+    assert_eq!(point.total_supply, U512::from(1_000));
+    assert_eq!(point.staked, U512::from(200));
+    assert_eq!(point.locked, U512::from(140));
+    assert_eq!(point.unbonding, U512::from(25));
+    assert_eq!(point.circulating, U512::from(860));
+
+    // A fourteenth of it is released every week.
+    let point = supply_at(
+        tmp_dir.path(),
+        state_root_hash,
+        Timestamp::from(RELEASE_TIMESTAMP_MILLIS + WEEK_MILLIS),
+    )
+    .unwrap();
+    assert_eq!(point.locked, U512::from(120));
+    assert_eq!(point.circulating, U512::from(880));
+
+    let missing_root = Digest::hash([9; 32]);
+    assert!(matches!(
+        supply_at(tmp_dir.path(), missing_root, Timestamp::zero()),
+        Err(Error::MissingStateRoot(root)) if root == missing_root
+    ));
+}
+
+#[test]
+fn supply_per_era_should_read_switch_block_state_roots() {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let state_root_hash = new_global_state(&tmp_dir);
+
+    // Era 1 ends long after the release, era 0 before it. The switch block
+    // of era 2 has a state root missing from the trie store, and the other
+    // block isn't a switch block.
+    let env = StorageEnv::create(tmp_dir.path(), 1 << 20).unwrap();
+    let db = env
+        .create_db(Some(BlockHeaderDatabase::db_name()), DatabaseFlags::empty())
+        .unwrap();
+    let mut txn = env.begin_rw_txn().unwrap();
+    for (idx, timestamp_millis, maybe_root) in [
+        (
+            1,
+            RELEASE_TIMESTAMP_MILLIS + 20 * WEEK_MILLIS,
+            Some(state_root_hash),
+        ),
+        (0, 0, Some(state_root_hash)),
+        (2, RELEASE_TIMESTAMP_MILLIS + 21 * WEEK_MILLIS, None),
+    ] {
+        let (block_hash, mut header) = test_utils::mock_switch_block_header(idx);
+        header.era_id = EraId::new(idx as u64);
+        header.height = idx as u64 * 10;
+        header.timestamp = Timestamp::from(timestamp_millis);
+        header.state_root_hash = maybe_root.unwrap_or_else(|| Digest::hash([idx; 32]));
+        txn.put(
+            db,
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    let (block_hash, header) = test_utils::mock_block_header(7);
+    txn.put(
+        db,
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    drop(env);
+
+    let points = supply_per_era(tmp_dir.path()).unwrap();
+    assert_eq!(points.len(), 2);
+    assert_eq!(points[0].era_id, Some(EraId::new(0)));
+    assert_eq!(points[0].height, Some(0));
+    assert_eq!(points[0].locked, U512::from(140));
+    assert_eq!(points[1].era_id, Some(EraId::new(1)));
+    assert_eq!(points[1].height, Some(10));
+    assert_eq!(points[1].locked, U512::zero());
+    assert_eq!(points[1].circulating, U512::from(1_000));
+
+    let mut csv = vec![];
+    write_points(&points, Format::Csv, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("era_id,height,"));
+    assert_eq!(
+        lines[2],
+        format!(
+            "1,10,{},{},1000,200,0,25,1000",
+            state_root_hash,
+            Timestamp::from(RELEASE_TIMESTAMP_MILLIS + 20 * WEEK_MILLIS)
+        )
+    );
+}
@@ -0,0 +1,166 @@
+pub(crate) mod rate;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::Path, time::Duration};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    human,
+    output::{self, Compression},
+    state_store::Error as StateStoreError,
+    storage::{self, Backend, Error as StorageError},
+};
+
+pub const COMMAND_NAME: &str = "sync-rate";
+const DB_PATH: &str = "db-path";
+const INTERVAL: &str = "interval";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const TARGET_HEIGHT: &str = "target-height";
+
+/// Errors encountered when measuring the sync rate of a node.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Error decoding the state store.
+    #[error("Error reading the state store: {0}")]
+    StateStore(#[from] StateStoreError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Interval,
+    TargetHeight,
+    Output,
+    Overwrite,
+    Compress,
+    Human,
+    Backend,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Measures how fast a running node stores blocks by reading its \
+            storage database twice, some time apart, and estimates how long \
+            it will take to reach a target height.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(INTERVAL)
+                .display_order(DisplayOrder::Interval as usize)
+                .short('i')
+                .long(INTERVAL)
+                .takes_value(true)
+                .value_name("SECONDS")
+                .default_value("60")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(0) => Err("interval must be at least 1 second".to_string()),
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help("Time between the two reads of the database."),
+        )
+        .arg(
+            Arg::new(TARGET_HEIGHT)
+                .display_order(DisplayOrder::TargetHeight as usize)
+                .short('t')
+                .long(TARGET_HEIGHT)
+                .takes_value(true)
+                .value_name("HEIGHT")
+                .validator(|value| value.parse::<u64>().map(|_| ()))
+                .help(
+                    "Height the node should reach. If unspecified, defaults to \
+                    the highest block the linear chain synchronizer saw, as \
+                    saved in the state store.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the sync rate in \
+                    JSON format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(human::human_arg(DisplayOrder::Human as usize))
+        .arg(storage::backend_arg(DisplayOrder::Backend as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_path = matches.value_of(DB_PATH).expect("should have db-path arg");
+    let interval = Duration::from_secs(
+        matches
+            .value_of(INTERVAL)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated"),
+    );
+    let maybe_target_height = matches
+        .value_of(TARGET_HEIGHT)
+        .map(|value| value.parse().expect("should be validated"));
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let compression = Compression::from_matches(matches);
+    rate::sync_rate(
+        db_path,
+        interval,
+        maybe_target_height,
+        output,
+        overwrite,
+        compression,
+        human::is_human(matches),
+        Backend::from_matches(matches),
+    )
+}
@@ -0,0 +1,180 @@
+use std::{
+    io::Write,
+    path::Path,
+    result::Result,
+    thread,
+    time::{Duration, Instant},
+};
+
+use casper_node::types::{BlockHash, BlockHeader};
+use log::info;
+use serde::Serialize;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    db::{BlockHeaderDatabase, Database, EnvTuning},
+    human::{self, Unit},
+    output::{Compression, OutputWriter},
+    state_store,
+    storage::{Backend, Storage, StorageReader},
+};
+
+use super::Error;
+
+const SECONDS_PER_HOUR: f64 = 3600.0;
+/// Fields of the sync rate given in units with `--human`.
+const HUMAN_FIELDS: &[(&str, Unit)] =
+    &[("elapsed_secs", Unit::Seconds), ("eta_secs", Unit::Seconds)];
+
+/// Blocks found in the storage database at some point in time.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Sample {
+    pub(crate) block_count: usize,
+    pub(crate) maybe_highest_height: Option<u64>,
+}
+
+/// Counts the block headers `reader` reads and finds the highest one.
+pub(crate) fn sample<R: StorageReader>(reader: &R) -> Result<Sample, BlockIterError> {
+    let mut sample = Sample::default();
+    if !reader.has_table(BlockHeaderDatabase::db_name())? {
+        return Ok(sample);
+    }
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), BlockIterError> {
+            let block_hash = BlockHash::new(
+                raw_key
+                    .try_into()
+                    .map_err(|_| BlockIterError::InvalidKey(sample.block_count))?,
+            );
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| BlockIterError::HeaderParsing(block_hash, bincode_err))?;
+            sample.block_count += 1;
+            sample.maybe_highest_height = sample.maybe_highest_height.max(Some(header.height()));
+            Ok(())
+        },
+    )?;
+    Ok(sample)
+}
+
+/// Returns the highest block the linear chain synchronizer saw, according
+/// to the state store, if any.
+pub(crate) fn sync_target<R: StorageReader>(reader: &R) -> Result<Option<u64>, Error> {
+    Ok(state_store::read_state_store(reader)?.and_then(|summary| {
+        summary
+            .linear_chain_sync
+            .iter()
+            .filter_map(|sync_summary| sync_summary.highest_block_seen)
+            .max()
+    }))
+}
+
+/// How fast blocks were stored between two samples, and when the target
+/// height would be reached at that pace.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub(crate) struct SyncRate {
+    pub(crate) elapsed_secs: f64,
+    pub(crate) start_height: Option<u64>,
+    pub(crate) end_height: Option<u64>,
+    /// Number of blocks stored between the samples.
+    pub(crate) blocks_stored: usize,
+    pub(crate) blocks_per_hour: f64,
+    pub(crate) target_height: Option<u64>,
+    pub(crate) remaining_blocks: Option<u64>,
+    /// Estimated time to reach the target height, `None` if there is no
+    /// target or no block was stored.
+    pub(crate) eta_secs: Option<u64>,
+}
+
+impl SyncRate {
+    /// Computes the rate from the samples taken `elapsed` apart. The rate is
+    /// based on the number of stored blocks rather than the heights, as a
+    /// node syncing from a trusted hash stores blocks below the highest one
+    /// first.
+    pub(crate) fn new(
+        start: Sample,
+        end: Sample,
+        elapsed: Duration,
+        maybe_target_height: Option<u64>,
+    ) -> Self {
+        let elapsed_secs = elapsed.as_secs_f64();
+        let blocks_stored = end.block_count.saturating_sub(start.block_count);
+        let blocks_per_hour = if elapsed_secs > 0.0 {
+            blocks_stored as f64 * SECONDS_PER_HOUR / elapsed_secs
+        } else {
+            0.0
+        };
+        let remaining_blocks = maybe_target_height.map(|target_height| {
+            target_height.saturating_sub(end.maybe_highest_height.unwrap_or_default())
+        });
+        let eta_secs = remaining_blocks.and_then(|remaining_blocks| {
+            if remaining_blocks == 0 {
+                Some(0)
+            } else if blocks_per_hour > 0.0 {
+                Some((remaining_blocks as f64 * SECONDS_PER_HOUR / blocks_per_hour).ceil() as u64)
+            } else {
+                None
+            }
+        });
+        Self {
+            elapsed_secs,
+            start_height: start.maybe_highest_height,
+            end_height: end.maybe_highest_height,
+            blocks_stored,
+            blocks_per_hour,
+            target_height: maybe_target_height,
+            remaining_blocks,
+            eta_secs,
+        }
+    }
+}
+
+/// Samples the storage database in `db_path` twice, `interval` apart, and
+/// outputs the resulting sync rate. The target height defaults to the one
+/// in the state store.
+#[allow(clippy::too_many_arguments)]
+pub fn sync_rate<P1: AsRef<Path>, P2: AsRef<Path>>(
+    db_path: P1,
+    interval: Duration,
+    maybe_target_height: Option<u64>,
+    output: Option<P2>,
+    overwrite: bool,
+    compression: Compression,
+    human: bool,
+    backend: Backend,
+) -> Result<(), Error> {
+    let storage = Storage::open(&db_path, backend, EnvTuning::default())?;
+    let start = sample(&storage.view()?)?;
+    let start_time = Instant::now();
+    info!(
+        "Found {} blocks, waiting {} seconds...",
+        start.block_count,
+        interval.as_secs()
+    );
+    thread::sleep(interval);
+    let (end, maybe_target_height) = {
+        let reader = storage.view()?;
+        let end = sample(&reader)?;
+        let maybe_target_height = match maybe_target_height {
+            Some(target_height) => Some(target_height),
+            None => sync_target(&reader)?,
+        };
+        (end, maybe_target_height)
+    };
+    let rate = SyncRate::new(start, end, start_time.elapsed(), maybe_target_height);
+    match rate.eta_secs {
+        Some(eta_secs) => info!(
+            "Storing {:.0} blocks per hour, height {} should be reached in {:.1} hours.",
+            rate.blocks_per_hour,
+            rate.target_height.unwrap_or_default(),
+            eta_secs as f64 / SECONDS_PER_HOUR
+        ),
+        None => info!("Storing {:.0} blocks per hour.", rate.blocks_per_hour),
+    }
+
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    human::to_writer_pretty(&mut out_writer, &rate, human, HUMAN_FIELDS)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
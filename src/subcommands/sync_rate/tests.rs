@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use lmdb::{Transaction, WriteFlags};
+
+use super::rate::{self, Sample, SyncRate};
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+        storage::LmdbReader,
+    },
+    test_utils::{self, LmdbTestFixture},
+};
+
+#[test]
+fn sample_should_find_highest_block() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, height) in [(0u8, 7u64), (1, 3), (2, 5)] {
+        let (block_hash, mut header) = test_utils::mock_block_header(idx);
+        header.height = height;
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let txn = fixture.env.begin_ro_txn().unwrap();
+    assert_eq!(
+        rate::sample(&LmdbReader::new(&txn)).unwrap(),
+        Sample {
+            block_count: 3,
+            maybe_highest_height: Some(7),
+        }
+    );
+    txn.commit().unwrap();
+}
+
+#[test]
+fn sync_rate_should_estimate_time_to_target() {
+    let start = Sample {
+        block_count: 100,
+        maybe_highest_height: Some(99),
+    };
+    let end = Sample {
+        block_count: 110,
+        maybe_highest_height: Some(109),
+    };
+    let rate = SyncRate::new(start, end, Duration::from_secs(60), Some(1_009));
+    assert_eq!(rate.blocks_stored, 10);
+    assert_eq!(rate.blocks_per_hour, 600.0);
+    assert_eq!(rate.remaining_blocks, Some(900));
+    assert_eq!(rate.eta_secs, Some(5_400));
+
+    // Without progress there is no estimate, unless the target is reached.
+    let rate = SyncRate::new(end, end, Duration::from_secs(60), Some(1_009));
+    assert_eq!(rate.blocks_per_hour, 0.0);
+    assert_eq!(rate.eta_secs, None);
+    let rate = SyncRate::new(end, end, Duration::from_secs(60), Some(100));
+    assert_eq!(rate.remaining_blocks, Some(0));
+    assert_eq!(rate.eta_secs, Some(0));
+    let rate = SyncRate::new(start, end, Duration::from_secs(60), None);
+    assert_eq!(rate.eta_secs, None);
+}
@@ -0,0 +1,130 @@
+mod sync;
+#[cfg(test)]
+mod tests;
+
+use std::{io::Error as IoError, path::PathBuf};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError, stamp::Error as StampError, storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "sync-storage";
+const DESTINATION_STORAGE_PATH: &str = "dest-storage";
+const FROM_HEIGHT: &str = "from-height";
+const SOURCE_STORAGE_PATH: &str = "src-storage";
+const TO_HEIGHT: &str = "to-height";
+
+/// Errors encountered when syncing two storage databases.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block of the source.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// The height range is empty.
+    #[error("Invalid height range: {0} is above {1}")]
+    InvalidRange(u64, u64),
+    /// Error creating the destination directory.
+    #[error("Error creating the destination: {0}")]
+    Output(#[from] IoError),
+    /// Error reading the size of a database.
+    #[error("Error reading {0}: {1}")]
+    Source(PathBuf, IoError),
+    /// The directories hold data of different chains.
+    #[error("Stamp validation failed: {0}")]
+    Stamp(#[from] StampError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Self::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    SourcePath,
+    DestinationPath,
+    FromHeight,
+    ToHeight,
+}
+
+fn height_arg(name: &'static str, short: char, display_order: DisplayOrder) -> Arg<'static> {
+    Arg::new(name)
+        .display_order(display_order as usize)
+        .required(false)
+        .short(short)
+        .long(name)
+        .takes_value(true)
+        .value_name("HEIGHT")
+        .validator(|value| value.parse::<u64>().map(|_| ()))
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Copies the headers, bodies, signatures, transfers and deploys of \
+            the blocks in the source storage database which are missing from \
+            the destination, merging two partial databases collected from \
+            different nodes. Entries already in the destination are kept as \
+            they are.",
+        )
+        .arg(
+            Arg::new(SOURCE_STORAGE_PATH)
+                .display_order(DisplayOrder::SourcePath as usize)
+                .required(true)
+                .short('s')
+                .long(SOURCE_STORAGE_PATH)
+                .takes_value(true)
+                .value_name("SOURCE_STORAGE_DIR_PATH")
+                .help("Path of the directory with the source `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(DESTINATION_STORAGE_PATH)
+                .display_order(DisplayOrder::DestinationPath as usize)
+                .required(true)
+                .short('d')
+                .long(DESTINATION_STORAGE_PATH)
+                .takes_value(true)
+                .value_name("DESTINATION_STORAGE_DIR_PATH")
+                .help(
+                    "Path of the directory with the destination `storage.lmdb` \
+                    file. It is created if it doesn't exist.",
+                ),
+        )
+        .arg(
+            height_arg(FROM_HEIGHT, 'f', DisplayOrder::FromHeight)
+                .help("Height of the lowest block to sync. Defaults to the genesis block."),
+        )
+        .arg(
+            height_arg(TO_HEIGHT, 't', DisplayOrder::ToHeight)
+                .help("Height of the highest block to sync. Defaults to the highest block."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let source = matches
+        .value_of(SOURCE_STORAGE_PATH)
+        .expect("should have src-storage arg");
+    let destination = matches
+        .value_of(DESTINATION_STORAGE_PATH)
+        .expect("should have dest-storage arg");
+    let height = |name| {
+        matches
+            .value_of(name)
+            .map(|value| value.parse::<u64>().expect("should be validated"))
+    };
+    let from = height(FROM_HEIGHT).unwrap_or_default();
+    let to = height(TO_HEIGHT).unwrap_or(u64::MAX);
+    if from > to {
+        return Err(Error::InvalidRange(from, to));
+    }
+    sync::sync_storage(source, destination, from..=to)?;
+    Ok(())
+}
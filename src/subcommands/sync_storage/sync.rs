@@ -0,0 +1,148 @@
+use std::{collections::BTreeMap, fs, ops::RangeInclusive, path::Path, result::Result};
+
+use lmdb::Transaction;
+use log::{info, warn};
+
+use crate::common::{
+    block_iter,
+    db::{BlockHeaderDatabase, Database, StorageEnv, STORAGE_FILE_NAME},
+    progress::ProgressTracker,
+    stamp,
+    storage::{Error as StorageError, LmdbReader, LmdbWriter, StorageReader, StorageWriter},
+};
+
+use super::Error;
+
+/// Number of blocks synced per transaction.
+const BLOCKS_PER_COMMIT: usize = 100;
+
+/// Counts of the blocks and entries synced to the destination.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct SyncSummary {
+    /// Blocks of the height range found in the source.
+    pub(crate) blocks: usize,
+    /// Blocks for which at least one entry was copied.
+    pub(crate) synced_blocks: usize,
+    /// Entries copied, by database.
+    pub(crate) copied: BTreeMap<&'static str, usize>,
+}
+
+fn file_size(path: &Path) -> Result<usize, Error> {
+    fs::metadata(path)
+        .map(|metadata| metadata.len() as usize)
+        .map_err(|io_err| Error::Source(path.to_path_buf(), io_err))
+}
+
+/// Copies the entry under `key` of the database `db_name` from `reader` to
+/// `writer`, unless it's missing from the former or already in the latter.
+/// Returns whether the entry was copied.
+fn copy_missing<R: StorageReader, W: StorageWriter>(
+    reader: &R,
+    writer: &mut W,
+    db_name: &str,
+    key: &[u8],
+) -> Result<bool, StorageError> {
+    if !reader.has_table(db_name)? {
+        return Ok(false);
+    }
+    if writer.has_table(db_name)? && writer.get(db_name, key)?.is_some() {
+        return Ok(false);
+    }
+    match reader.get(db_name, key)? {
+        Some(raw_val) => {
+            writer.put(db_name, key, &raw_val)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Copies the entries of the blocks within `heights` in the storage database
+/// in `source` which are missing from the one in `destination`.
+pub(crate) fn sync_storage<P1: AsRef<Path>, P2: AsRef<Path>>(
+    source: P1,
+    destination: P2,
+    heights: RangeInclusive<u64>,
+) -> Result<SyncSummary, Error> {
+    let source = source.as_ref();
+    let destination = destination.as_ref();
+    stamp::ensure_same_chain(&[source, destination])?;
+
+    let source_size = file_size(&source.join(STORAGE_FILE_NAME))?;
+    let destination_path = destination.join(STORAGE_FILE_NAME);
+    let destination_size = if destination_path.exists() {
+        file_size(&destination_path)?
+    } else {
+        0
+    };
+    fs::create_dir_all(destination)?;
+
+    let source_env = StorageEnv::open(source)?;
+    let source_txn = source_env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&source_txn);
+    let blocks: Vec<_> = if reader.has_table(BlockHeaderDatabase::db_name())? {
+        block_iter::blocks_by_height(&reader)?
+            .into_iter()
+            .filter(|(height, _)| heights.contains(height))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    // The destination may need room for every entry of the source.
+    let env = StorageEnv::create(destination, source_size + destination_size)?;
+    let mut summary = SyncSummary {
+        blocks: blocks.len(),
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Sync {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    for batch in blocks.chunks(BLOCKS_PER_COMMIT) {
+        let mut txn = env.begin_rw_txn()?;
+        let mut writer = LmdbWriter::new(&mut txn);
+        for (_height, block_hash) in batch {
+            let header = block_iter::read_header(&reader, block_hash)?;
+            let records = block_iter::block_records(&reader, block_hash, &header)?;
+            if !records.has_body {
+                warn!(
+                    "Block {} has no body in the source, its deploys aren't synced.",
+                    block_hash
+                );
+            }
+            let mut synced = false;
+            for (db_name, key) in records.keys {
+                if copy_missing(&reader, &mut writer, db_name, key.as_ref())? {
+                    *summary.copied.entry(db_name).or_default() += 1;
+                    synced = true;
+                }
+            }
+            if synced {
+                summary.synced_blocks += 1;
+            }
+            if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+                progress_tracker.advance_by(1);
+            }
+        }
+        txn.commit()?;
+    }
+
+    for (db_name, count) in summary.copied.iter() {
+        info!("{} database: copied {} entries.", db_name, count);
+    }
+    info!(
+        "Synced {} of the {} blocks found in the source.",
+        summary.synced_blocks, summary.blocks
+    );
+    Ok(summary)
+}
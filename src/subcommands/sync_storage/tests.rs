@@ -0,0 +1,141 @@
+use casper_node::types::BlockHash;
+use lmdb::{Transaction, WriteFlags};
+
+use super::sync::sync_storage;
+use crate::{
+    common::{
+        db::{
+            BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase, StorageEnv,
+            STORAGE_FILE_NAME,
+        },
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn new_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+/// Stores the header of the block at `height` and, if `deploy` is set, its
+/// body executing a single deploy stored with the given value.
+fn put_block(fixture: &LmdbTestFixture, height: u8, deploy: Option<u8>) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    if let Some(deploy_value) = deploy {
+        let deploy_hash = test_utils::mock_deploy_hash(height);
+        txn.put(
+            *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+            &header.body_hash,
+            &bincode::serialize(&BlockBody::new(vec![deploy_hash])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            &deploy_hash,
+            &[deploy_value],
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    block_hash
+}
+
+#[test]
+fn sync_should_copy_missing_entries_in_range() {
+    let source_fixture = new_fixture();
+    let destination_fixture = new_fixture();
+    for height in 0..4 {
+        put_block(&source_fixture, height, Some(1));
+    }
+    // The destination has the header of block 1 without its body, and all
+    // of block 2 with a deploy differing from the source.
+    put_block(&destination_fixture, 1, None);
+    put_block(&destination_fixture, 2, Some(2));
+
+    let summary = sync_storage(
+        source_fixture.tmp_dir.path(),
+        destination_fixture.tmp_dir.path(),
+        1..=2,
+    )
+    .unwrap();
+    assert_eq!(summary.blocks, 2);
+    assert_eq!(summary.synced_blocks, 1);
+    assert_eq!(
+        summary.copied.into_iter().collect::<Vec<_>>(),
+        vec![
+            (BlockBodyDatabase::db_name(), 1),
+            (DeployDatabase::db_name(), 1)
+        ]
+    );
+
+    let env = StorageEnv::open(destination_fixture.tmp_dir.path()).unwrap();
+    let txn = env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    assert_eq!(
+        reader.entry_count(BlockHeaderDatabase::db_name()).unwrap(),
+        2
+    );
+    assert_eq!(reader.entry_count(BlockBodyDatabase::db_name()).unwrap(), 2);
+    for (height, deploy_value) in [(1, 1), (2, 2)] {
+        let deploy_hash = test_utils::mock_deploy_hash(height);
+        assert_eq!(
+            reader
+                .get(DeployDatabase::db_name(), deploy_hash.as_ref())
+                .unwrap()
+                .unwrap()
+                .as_ref(),
+            &[deploy_value]
+        );
+    }
+    txn.commit().unwrap();
+}
+
+#[test]
+fn sync_to_new_destination_should_copy_everything() {
+    let source_fixture = new_fixture();
+    let block_hashes: Vec<BlockHash> = (0..3)
+        .map(|height| put_block(&source_fixture, height, Some(height)))
+        .collect();
+    let destination_dir = tempfile::tempdir().unwrap();
+    let destination_path = destination_dir.path().join("merged");
+
+    let summary = sync_storage(
+        source_fixture.tmp_dir.path(),
+        &destination_path,
+        0..=u64::MAX,
+    )
+    .unwrap();
+    assert_eq!(summary.blocks, 3);
+    assert_eq!(summary.synced_blocks, 3);
+
+    let env = StorageEnv::open(&destination_path).unwrap();
+    let txn = env.begin_ro_txn().unwrap();
+    let reader = LmdbReader::new(&txn);
+    for block_hash in block_hashes {
+        assert!(reader
+            .get(BlockHeaderDatabase::db_name(), block_hash.as_ref())
+            .unwrap()
+            .is_some());
+    }
+    assert_eq!(reader.entry_count(DeployDatabase::db_name()).unwrap(), 3);
+    txn.commit().unwrap();
+}
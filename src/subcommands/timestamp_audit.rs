@@ -0,0 +1,224 @@
+mod audit;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use casper_types::TimeDiff;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    chainspec::{self, Error as ChainspecError},
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "timestamp-audit";
+const CHAINSPEC: &str = "chainspec";
+const DB_PATH: &str = "db-path";
+const FROM_HEIGHT: &str = "from-height";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const ROUND_LENGTH: &str = "round-length";
+const TO_HEIGHT: &str = "to-height";
+const TOLERANCE: &str = "tolerance";
+
+/// Errors encountered when auditing the timestamps of block headers.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Error reading the chainspec.
+    #[error(transparent)]
+    Chainspec(#[from] ChainspecError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// The height range is empty.
+    #[error("Invalid height range: {0} is above {1}")]
+    InvalidRange(u64, u64),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    FromHeight,
+    ToHeight,
+    RoundLength,
+    Chainspec,
+    Tolerance,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+fn height_arg(name: &'static str, short: char, display_order: DisplayOrder) -> Arg<'static> {
+    Arg::new(name)
+        .display_order(display_order as usize)
+        .required(false)
+        .short(short)
+        .long(name)
+        .takes_value(true)
+        .value_name("HEIGHT")
+        .validator(|value| value.parse::<u64>().map(|_| ()))
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Scans consecutive block headers for timestamps which don't \
+            increase or which follow their parent by implausibly less than \
+            the round length, and outputs the offending blocks and their \
+            proposers in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            height_arg(FROM_HEIGHT, 'f', DisplayOrder::FromHeight)
+                .help("Height of the lowest block to audit. Defaults to the genesis block."),
+        )
+        .arg(
+            height_arg(TO_HEIGHT, 't', DisplayOrder::ToHeight)
+                .help("Height of the highest block to audit. Defaults to the highest block."),
+        )
+        .arg(
+            Arg::new(ROUND_LENGTH)
+                .display_order(DisplayOrder::RoundLength as usize)
+                .short('r')
+                .long(ROUND_LENGTH)
+                .takes_value(true)
+                .value_name("DURATION")
+                .default_value("32768ms")
+                .validator(|value| value.parse::<TimeDiff>().map(|_| ()))
+                .help(
+                    "Shortest round length of the network, like \"32768ms\" or \
+                    \"16s 384ms\", which is the shortest time between two \
+                    blocks.",
+                ),
+        )
+        .arg(
+            Arg::new(CHAINSPEC)
+                .display_order(DisplayOrder::Chainspec as usize)
+                .short('c')
+                .long(CHAINSPEC)
+                .takes_value(true)
+                .value_name("CHAINSPEC_PATH")
+                .conflicts_with(ROUND_LENGTH)
+                .help(
+                    "Path of the chainspec of the network, or of the directory \
+                    with its `chainspec.toml` file, to take the round length \
+                    from.",
+                ),
+        )
+        .arg(
+            Arg::new(TOLERANCE)
+                .display_order(DisplayOrder::Tolerance as usize)
+                .long(TOLERANCE)
+                .takes_value(true)
+                .value_name("PERCENT")
+                .default_value("10")
+                .validator(|value| match value.parse::<u64>() {
+                    Ok(percent) if percent > 100 => {
+                        Err("tolerance must be at most 100".to_string())
+                    }
+                    Ok(_) => Ok(()),
+                    Err(parse_err) => Err(parse_err.to_string()),
+                })
+                .help(
+                    "Report blocks following their parent by less than the \
+                    round length minus PERCENT of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let height = |name| {
+        matches
+            .value_of(name)
+            .map(|value| value.parse::<u64>().expect("should be validated"))
+    };
+    let from = height(FROM_HEIGHT).unwrap_or_default();
+    let to = height(TO_HEIGHT).unwrap_or(u64::MAX);
+    if from > to {
+        return Err(Error::InvalidRange(from, to));
+    }
+    let round_length = match matches.value_of(CHAINSPEC) {
+        Some(chainspec_path) => chainspec::read_chainspec(chainspec_path)?
+            .highway
+            .minimum_block_time(),
+        None => matches
+            .value_of(ROUND_LENGTH)
+            .expect("should have a default")
+            .parse()
+            .expect("should be validated"),
+    };
+    let tolerance = matches
+        .value_of(TOLERANCE)
+        .expect("should have a default")
+        .parse()
+        .expect("should be a valid percentage");
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = audit::timestamp_audit(path, from..=to, round_length, tolerance)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
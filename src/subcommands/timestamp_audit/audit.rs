@@ -0,0 +1,191 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    mem,
+    ops::RangeInclusive,
+    path::Path,
+    result::Result,
+};
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{PublicKey, TimeDiff, Timestamp};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    block_iter,
+    db::{BlockHeaderDatabase, Database, StorageEnv},
+    progress::ProgressTracker,
+    storage::{LmdbReader, StorageReader},
+};
+
+use super::Error;
+
+/// How the timestamp of a block is off from the one of its parent.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Skew {
+    /// The block isn't newer than its parent.
+    NonMonotonic,
+    /// The block follows its parent by less than the round length allows.
+    TooClose,
+}
+
+/// A block whose timestamp is off from the one of its parent.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct SkewedBlock {
+    pub(crate) block_hash: BlockHash,
+    pub(crate) height: u64,
+    /// Proposer of the block, unless its body is missing.
+    pub(crate) proposer: Option<PublicKey>,
+    pub(crate) skew: Skew,
+    pub(crate) timestamp: Timestamp,
+    pub(crate) parent_timestamp: Timestamp,
+    /// Time between the parent and the block, negative if the block is
+    /// older.
+    pub(crate) block_time_millis: i64,
+}
+
+/// Number of skewed blocks of a proposer.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ProposerSkews {
+    pub(crate) proposer: PublicKey,
+    pub(crate) skewed_blocks: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct TimestampReport {
+    pub(crate) blocks: usize,
+    pub(crate) round_length: TimeDiff,
+    /// Shortest time between two blocks not reported as too close.
+    pub(crate) minimum_block_time: TimeDiff,
+    pub(crate) skewed: Vec<SkewedBlock>,
+    /// Proposers of the skewed blocks, most frequent first.
+    pub(crate) proposers: Vec<ProposerSkews>,
+}
+
+/// Returns how `header` is skewed from `parent`, if at all.
+fn skew(header: &BlockHeader, parent: &BlockHeader, minimum_block_time: TimeDiff) -> Option<Skew> {
+    if header.timestamp() <= parent.timestamp() {
+        Some(Skew::NonMonotonic)
+    } else if header.timestamp().saturating_diff(parent.timestamp()) < minimum_block_time {
+        Some(Skew::TooClose)
+    } else {
+        None
+    }
+}
+
+/// Audits the timestamps of the blocks within `heights` in the storage
+/// database at `db_path`, reporting the blocks which aren't newer than
+/// their parent or follow it by less than `round_length` minus `tolerance`
+/// percent of it.
+pub(crate) fn timestamp_audit<P: AsRef<Path>>(
+    db_path: P,
+    heights: RangeInclusive<u64>,
+    round_length: TimeDiff,
+    tolerance: u64,
+) -> Result<TimestampReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+    // The parents of the lowest blocks are read too, but not audited.
+    let parent_heights = heights.start().saturating_sub(1)..=*heights.end();
+    let blocks: Vec<_> = if reader.has_table(BlockHeaderDatabase::db_name())? {
+        block_iter::blocks_by_height(&reader)?
+            .into_iter()
+            .filter(|(height, _)| parent_heights.contains(height))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let minimum_block_time = TimeDiff::from_millis(round_length.millis() * (100 - tolerance) / 100);
+    let mut report = TimestampReport {
+        blocks: blocks
+            .iter()
+            .filter(|(height, _)| heights.contains(height))
+            .count(),
+        round_length,
+        minimum_block_time,
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Audit {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    // The headers of the blocks at the previous and the current height,
+    // several if the database holds orphaned blocks.
+    let mut previous_height = None;
+    let mut current_height = None;
+    let mut previous_headers: HashMap<BlockHash, BlockHeader> = HashMap::new();
+    let mut current_headers = HashMap::new();
+    let mut proposer_counts: BTreeMap<PublicKey, usize> = BTreeMap::new();
+    for (height, block_hash) in blocks {
+        if current_height != Some(height) {
+            previous_height = current_height;
+            previous_headers = mem::take(&mut current_headers);
+            current_height = Some(height);
+        }
+        let header = block_iter::read_header(&reader, &block_hash)?;
+        let maybe_parent =
+            if height > 0 && heights.contains(&height) && previous_height == Some(height - 1) {
+                previous_headers.get(header.parent_hash())
+            } else {
+                None
+            };
+        if let Some(parent) = maybe_parent {
+            if let Some(skew) = skew(&header, parent, minimum_block_time) {
+                let proposer = block_iter::read_body(&reader, &block_hash, &header)?
+                    .map(|body| body.proposer().clone());
+                warn!(
+                    "Block {} at height {} is {:?}, proposed by {:?}.",
+                    block_hash, height, skew, proposer
+                );
+                if let Some(proposer) = proposer.as_ref() {
+                    *proposer_counts.entry(proposer.clone()).or_default() += 1;
+                }
+                report.skewed.push(SkewedBlock {
+                    block_hash,
+                    height,
+                    proposer,
+                    skew,
+                    timestamp: header.timestamp(),
+                    parent_timestamp: parent.timestamp(),
+                    block_time_millis: header.timestamp().millis() as i64
+                        - parent.timestamp().millis() as i64,
+                });
+            }
+        }
+        current_headers.insert(block_hash, header);
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+
+    report.proposers = proposer_counts
+        .into_iter()
+        .map(|(proposer, skewed_blocks)| ProposerSkews {
+            proposer,
+            skewed_blocks,
+        })
+        .collect();
+    // Stable, so proposers with as many skewed blocks stay in key order.
+    report
+        .proposers
+        .sort_by_key(|proposer_skews| Reverse(proposer_skews.skewed_blocks));
+    info!(
+        "Audited {} block headers, {} have skewed timestamps.",
+        report.blocks,
+        report.skewed.len()
+    );
+    Ok(report)
+}
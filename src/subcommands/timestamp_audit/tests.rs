@@ -0,0 +1,121 @@
+use casper_node::types::BlockHash;
+use casper_types::{PublicKey, SecretKey, TimeDiff, Timestamp};
+use lmdb::{Transaction, WriteFlags};
+
+use super::audit::{timestamp_audit, ProposerSkews, Skew};
+use crate::{
+    common::db::{BlockBodyDatabase, BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn proposer(idx: u8) -> PublicKey {
+    PublicKey::from(&SecretKey::ed25519_from_bytes([idx; 32]).unwrap())
+}
+
+/// Stores the block at `height`, child of `parent_hash`, proposed by
+/// `proposer`.
+fn put_block(
+    fixture: &LmdbTestFixture,
+    height: u8,
+    parent_hash: BlockHash,
+    timestamp_millis: u64,
+    proposer: PublicKey,
+) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    header.parent_hash = parent_hash;
+    header.timestamp = Timestamp::from(timestamp_millis);
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &header.body_hash,
+        &bincode::serialize(&BlockBody::from_parts(proposer, vec![], vec![])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    block_hash
+}
+
+#[test]
+fn timestamp_audit_should_report_skewed_blocks() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name(), BlockBodyDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Block 3 follows its parent by 10 seconds and block 5 by 15 seconds,
+    // both less than 90% of the round length, while block 4 is older than
+    // its parent.
+    let timestamps = [0, 40_000, 80_000, 90_000, 85_000, 100_000, 140_000];
+    let proposers = [1, 1, 2, 1, 1, 2, 2];
+    let mut parent_hash = BlockHash::default();
+    let mut block_hashes = vec![];
+    for height in 0..timestamps.len() {
+        parent_hash = put_block(
+            &fixture,
+            height as u8,
+            parent_hash,
+            timestamps[height],
+            proposer(proposers[height]),
+        );
+        block_hashes.push(parent_hash);
+    }
+    let round_length = TimeDiff::from_millis(32_768);
+
+    let report = timestamp_audit(fixture.tmp_dir.path(), 0..=u64::MAX, round_length, 10).unwrap();
+    assert_eq!(report.blocks, 7);
+    assert_eq!(report.minimum_block_time, TimeDiff::from_millis(29_491));
+    let skewed: Vec<(u64, Skew, i64)> = report
+        .skewed
+        .iter()
+        .map(|block| (block.height, block.skew, block.block_time_millis))
+        .collect();
+    assert_eq!(
+        skewed,
+        [
+            (3, Skew::TooClose, 10_000),
+            (4, Skew::NonMonotonic, -5_000),
+            (5, Skew::TooClose, 15_000)
+        ]
+    );
+    assert_eq!(report.skewed[1].block_hash, block_hashes[4]);
+    assert_eq!(report.skewed[1].proposer, Some(proposer(1)));
+    assert_eq!(
+        report.skewed[1].parent_timestamp,
+        Timestamp::from(timestamps[3])
+    );
+    assert_eq!(
+        report.proposers,
+        [
+            ProposerSkews {
+                proposer: proposer(1),
+                skewed_blocks: 2,
+            },
+            ProposerSkews {
+                proposer: proposer(2),
+                skewed_blocks: 1,
+            }
+        ]
+    );
+
+    // The lowest block of the range is audited against its parent, which
+    // is outside of it.
+    let report = timestamp_audit(fixture.tmp_dir.path(), 4..=5, round_length, 10).unwrap();
+    assert_eq!(report.blocks, 2);
+    let heights: Vec<u64> = report.skewed.iter().map(|block| block.height).collect();
+    assert_eq!(heights, [4, 5]);
+
+    // With a full tolerance, only the blocks older than their parent are
+    // reported.
+    let report = timestamp_audit(fixture.tmp_dir.path(), 0..=u64::MAX, round_length, 100).unwrap();
+    let heights: Vec<u64> = report.skewed.iter().map(|block| block.height).collect();
+    assert_eq!(heights, [4]);
+}
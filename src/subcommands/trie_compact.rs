@@ -6,7 +6,10 @@ pub(crate) mod tests;
 // public interface.
 mod utils;
 
-use std::{io::Error as IoError, path::PathBuf};
+use std::{
+    io::{Error as IoError, Write},
+    path::{Path, PathBuf},
+};
 
 use anyhow::Error as AnyError;
 use clap::{Arg, ArgMatches, Command};
@@ -16,14 +19,25 @@ use thiserror::Error as ThisError;
 use casper_hashing::Digest;
 use casper_node::storage::Error as StorageError;
 
-use compact::DestinationOptions;
+use crate::common::{
+    db::{self, TRIE_STORE_FILE_NAME},
+    disk_space::{self, Error as DiskSpaceError},
+    output::{self, Compression, OutputWriter},
+    stamp::Error as StampError,
+    throttle,
+};
+pub(crate) use compact::{trie_compact, DestinationOptions};
 pub use helpers::copy_state_root;
-pub use utils::{create_execution_engine, load_execution_engine};
+pub use utils::{
+    create_execution_engine, load_execution_engine, load_execution_engine_with_config,
+};
 
 pub const COMMAND_NAME: &str = "compact-trie";
 const APPEND: &str = "append";
 const DESTINATION_TRIE_STORE_PATH: &str = "dest-trie";
+const FORCE: &str = "force";
 const OVERWRITE: &str = "overwrite";
+const OUTPUT_REPORT: &str = "output-report";
 const MAX_DB_SIZE: &str = "max-db-size";
 pub const DEFAULT_MAX_DB_SIZE: &str = "483183820800"; // 450 gb
 const SOURCE_TRIE_STORE_PATH: &str = "src-trie";
@@ -38,6 +52,9 @@ pub enum Error {
     /// Error creating the execution engine for the destination trie.
     #[error("Error loading the execution engine: {0}")]
     CreateDestTrie(AnyError),
+    /// Not enough space for the destination trie.
+    #[error("Disk space check failed: {0}")]
+    DiskSpace(#[from] DiskSpaceError),
     /// Error working with the destination trie path.
     #[error("Invalid destination: {0}")]
     InvalidDest(String),
@@ -56,6 +73,12 @@ pub enum Error {
     /// Error opening the block/deploys LMDB store.
     #[error("Error opening the block/deploy storage: {0}")]
     OpenStorage(AnyError),
+    /// Error writing the compaction report.
+    #[error("Error writing the report: {0}")]
+    Report(IoError),
+    /// The directories hold data of different chains.
+    #[error("Stamp validation failed: {0}")]
+    Stamp(#[from] StampError),
     /// Error while getting a block of specific height from storage.
     #[error("Storage error while trying to retrieve block {0}: {1}")]
     Storage(u64, StorageError),
@@ -68,6 +91,11 @@ enum DisplayOrder {
     Append,
     Overwrite,
     MaxDbSize,
+    Force,
+    OutputReport,
+    Compress,
+    IoNice,
+    Throttle,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -147,9 +175,37 @@ pub fn command(display_order: usize) -> Command<'static> {
                 .value_name("MAX_DB_SIZE")
                 .help("Maximum size the DB files are allowed to be, in bytes."),
         )
+        .arg(
+            Arg::new(FORCE)
+                .display_order(DisplayOrder::Force as usize)
+                .required(false)
+                .long(FORCE)
+                .takes_value(false)
+                .help(
+                    "Proceed even if the destination doesn't seem to have enough free \
+                    space for a copy of the source trie store.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT_REPORT)
+                .display_order(DisplayOrder::OutputReport as usize)
+                .required(false)
+                .long(OUTPUT_REPORT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path of a file to write a JSON report of the compaction to, \
+                    with the sizes of the source and destination trie stores \
+                    and the counts of tries copied. The file must not exist.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(throttle::io_nice_arg(DisplayOrder::IoNice as usize))
+        .arg(throttle::throttle_arg(DisplayOrder::Throttle as usize))
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    throttle::init_from_matches(matches);
     let storage_path = matches.value_of(STORAGE_PATH).unwrap();
     let source_trie_path = matches.value_of(SOURCE_TRIE_STORE_PATH).unwrap();
     let destination_trie_path = matches.value_of(DESTINATION_TRIE_STORE_PATH).unwrap();
@@ -165,11 +221,37 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
         .parse()
         .expect("Value of \"--max-db-size\" must be an integer.");
 
-    compact::trie_compact(
+    // The compacted trie can't be larger than the source one.
+    let source_trie_file =
+        db::data_file_path(Path::new(source_trie_path).join(TRIE_STORE_FILE_NAME));
+    let required_space = disk_space::allocated_size(&source_trie_file)
+        .map_err(|io_err| Error::InvalidPath(source_trie_file, io_err))?;
+    disk_space::check_available_space(
+        destination_trie_path,
+        required_space,
+        matches.is_present(FORCE),
+    )?;
+
+    // Open the report up front so that a bad path doesn't waste a whole
+    // compaction.
+    let maybe_report_writer = matches
+        .value_of(OUTPUT_REPORT)
+        .map(|path| OutputWriter::new(Some(path), false, Compression::from_matches(matches)))
+        .transpose()
+        .map_err(Error::Report)?;
+
+    let report = trie_compact(
         storage_path,
         source_trie_path,
         destination_trie_path,
         dest_opt,
         max_db_size,
-    )
+    )?;
+    if let Some(mut report_writer) = maybe_report_writer {
+        serde_json::to_writer_pretty(&mut report_writer, &report)
+            .map_err(|json_err| Error::Report(json_err.into()))?;
+        writeln!(report_writer).map_err(Error::Report)?;
+        report_writer.finish().map_err(Error::Report)?;
+    }
+    Ok(())
 }
@@ -5,16 +5,82 @@ use std::{
 };
 
 use log::info;
+use serde::Serialize;
 
 use casper_hashing::Digest;
 
-use crate::common::db::TRIE_STORE_FILE_NAME;
+use crate::common::{
+    db::{self, TRIE_STORE_FILE_NAME},
+    disk_space, human, stamp,
+};
 
 use super::{
+    helpers::CopyStats,
     utils::{create_execution_engine, create_storage, load_execution_engine},
     Error,
 };
 
+/// Outcome of a compaction, comparing the sizes of the trie stores.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct CompactionReport {
+    /// Bytes allocated on disk for the source `data.lmdb` file.
+    pub source_size: u64,
+    /// Bytes allocated on disk for the destination `data.lmdb` file.
+    pub destination_size: u64,
+    /// Share of the source size the destination doesn't take, in percent.
+    pub saved_percent: f64,
+    /// Number of distinct state roots copied.
+    pub state_roots: usize,
+    /// Counts of the tries copied under all the state roots.
+    pub tries: CopyStats,
+}
+
+impl CompactionReport {
+    fn new(source_size: u64, destination_size: u64, state_roots: usize, tries: CopyStats) -> Self {
+        Self {
+            source_size,
+            destination_size,
+            saved_percent: saved_percent(source_size, destination_size),
+            state_roots,
+            tries,
+        }
+    }
+
+    /// Logs the sizes of the trie stores and the counts of tries copied.
+    pub fn log(&self) {
+        info!(
+            "Compacted the trie store from {} to {}, saving {:.2}%.",
+            human::format_size(self.source_size as f64),
+            human::format_size(self.destination_size as f64),
+            self.saved_percent
+        );
+        info!(
+            "Copied {} tries totalling {} under {} state roots, {} shared tries \
+            being copied only once.",
+            self.tries.tries,
+            human::format_size(self.tries.bytes as f64),
+            self.state_roots,
+            self.tries.deduplicated
+        );
+    }
+}
+
+/// Returns the share of `source_size` that `destination_size` saves, in
+/// percent. Nothing is saved when the source is empty.
+pub(crate) fn saved_percent(source_size: u64, destination_size: u64) -> f64 {
+    if source_size == 0 {
+        return 0.0;
+    }
+    (1.0 - destination_size as f64 / source_size as f64) * 100.0
+}
+
+/// Returns the number of bytes allocated on disk for the trie store in
+/// `trie_path`.
+fn trie_store_size<P: AsRef<Path>>(trie_path: P) -> Result<u64, Error> {
+    let trie_file = db::data_file_path(trie_path.as_ref().join(TRIE_STORE_FILE_NAME));
+    disk_space::allocated_size(&trie_file).map_err(|io_err| Error::InvalidPath(trie_file, io_err))
+}
+
 /// Defines behavior for opening destination trie store.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum DestinationOptions {
@@ -88,7 +154,9 @@ fn validate_trie_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
                     let _f: File = OpenOptions::new()
                         .truncate(true)
                         .write(true)
-                        .open(destination_trie_path.as_ref().join(TRIE_STORE_FILE_NAME))
+                        .open(db::data_file_path(
+                            destination_trie_path.as_ref().join(TRIE_STORE_FILE_NAME),
+                        ))
                         .map_err(|io_err| {
                             Error::InvalidDest(format!(
                                 "Couldn't overwrite destination file: {io_err}"
@@ -131,64 +199,77 @@ fn validate_trie_paths<P1: AsRef<Path>, P2: AsRef<Path>>(
 /// The function first retrieves the highest block hash from storage and
 /// compacting starts from that state root hash. Each descendant of that
 /// block's hash is copied to the destination trie. This process is repeated
-/// for all the remaining blocks, from highest to lowest.
+/// for all the remaining blocks, from highest to lowest. Returns the sizes
+/// of the trie stores and the counts of tries copied.
 pub fn trie_compact<P1: AsRef<Path>, P2: AsRef<Path>, P3: AsRef<Path>>(
     storage_path: P1,
     source_trie_path: P2,
     destination_trie_path: P3,
     dest_opt: DestinationOptions,
     max_db_size: usize,
-) -> Result<(), Error> {
+) -> Result<CompactionReport, Error> {
     validate_trie_paths(&source_trie_path, &destination_trie_path, dest_opt)?;
+    stamp::ensure_same_chain(&[
+        storage_path.as_ref(),
+        source_trie_path.as_ref(),
+        destination_trie_path.as_ref(),
+    ])?;
 
     let (source_state, _env) =
-        load_execution_engine(source_trie_path, max_db_size, Digest::default(), true)
+        load_execution_engine(&source_trie_path, max_db_size, Digest::default(), true)
             .map_err(Error::OpenSourceTrie)?;
 
     let (destination_state, _env) =
-        create_execution_engine(destination_trie_path, max_db_size, true)
+        create_execution_engine(&destination_trie_path, max_db_size, true)
             .map_err(Error::CreateDestTrie)?;
 
     // Create a separate lmdb for block/deploy storage at chain_download_path.
     let storage = create_storage(&storage_path).map_err(Error::OpenStorage)?;
 
-    let mut block = match storage
-        .read_highest_block()
-        .map_err(|err| Error::Storage(0, err))?
-    {
-        Some(block) => block,
-        None => {
-            info!("No blocks found in storage, exiting.");
-            return Ok(());
-        }
-    };
     let mut visited_roots = HashSet::new();
-    let mut block_height;
-
-    info!("Copying state roots from source to destination.");
-    loop {
-        block_height = block.height();
-        let state_root = *block.take_header().state_root_hash();
-        if !visited_roots.contains(&state_root) {
-            super::helpers::copy_state_root(state_root, &source_state, &destination_state)
-                .map_err(|err| Error::CopyStateRoot(state_root, err))?;
-            destination_state
-                .flush_environment()
-                .map_err(Error::LmdbOperation)?;
-            visited_roots.insert(state_root);
-        }
-        if block_height == 0 {
-            break;
+    let mut copy_stats = CopyStats::default();
+    let maybe_highest_block = storage
+        .read_highest_block()
+        .map_err(|err| Error::Storage(0, err))?;
+    if let Some(mut block) = maybe_highest_block {
+        let mut block_height;
+
+        info!("Copying state roots from source to destination.");
+        loop {
+            block_height = block.height();
+            let state_root = *block.take_header().state_root_hash();
+            if !visited_roots.contains(&state_root) {
+                let root_stats =
+                    super::helpers::copy_state_root(state_root, &source_state, &destination_state)
+                        .map_err(|err| Error::CopyStateRoot(state_root, err))?;
+                copy_stats.add(root_stats);
+                destination_state
+                    .flush_environment()
+                    .map_err(Error::LmdbOperation)?;
+                visited_roots.insert(state_root);
+            }
+            if block_height == 0 {
+                break;
+            }
+            block = storage
+                .read_block_by_height(block_height - 1)
+                .map_err(|storage_err| Error::Storage(block_height - 1, storage_err))?
+                .ok_or(Error::MissingBlock(block_height - 1))?;
         }
-        block = storage
-            .read_block_by_height(block_height - 1)
-            .map_err(|storage_err| Error::Storage(block_height - 1, storage_err))?
-            .ok_or(Error::MissingBlock(block_height - 1))?;
+        info!(
+            "Finished copying {} state roots to new database.",
+            visited_roots.len()
+        );
+    } else {
+        info!("No blocks found in storage.");
     }
-    info!(
-        "Finished copying {} state roots to new database.",
-        visited_roots.len()
-    );
 
-    Ok(())
+    let report = CompactionReport::new(
+        trie_store_size(&source_trie_path)?,
+        trie_store_size(&destination_trie_path)?,
+        visited_roots.len(),
+        copy_stats,
+    );
+    report.log();
+    Ok(report)
 }
@@ -2,6 +2,7 @@ use std::time::{Duration, Instant};
 
 use lmdb::{RwTransaction, Transaction};
 use log::{info, warn};
+use serde::Serialize;
 
 use casper_execution_engine::{
     core::engine_state::EngineState,
@@ -18,11 +19,35 @@ use casper_types::{
     Key, StoredValue,
 };
 
+use crate::common::throttle;
+
+/// Counts of the tries copied under one or more state roots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct CopyStats {
+    /// Number of tries written to the destination.
+    pub tries: u64,
+    /// Number of bytes of the keys and values of the tries written.
+    pub bytes: u64,
+    /// Number of tries not copied again because they were already in the
+    /// destination, shared with a state root copied before.
+    pub deduplicated: u64,
+}
+
+impl CopyStats {
+    /// Adds the counts of `other` to these.
+    pub fn add(&mut self, other: CopyStats) {
+        self.tries += other.tries;
+        self.bytes += other.bytes;
+        self.deduplicated += other.deduplicated;
+    }
+}
+
 fn memoized_find_missing_descendants(
     value_bytes: Bytes,
     trie_store: &LmdbTrieStore,
     txn: &RwTransaction<'_>,
     missing_trie_keys: &mut Vec<Digest>,
+    deduplicated: &mut u64,
     time_in_missing_trie_keys: &mut Duration,
 ) -> Result<(), anyhow::Error> {
     // A first bytes of `0` indicates a leaf. We short-circuit the function here to speed things up.
@@ -40,11 +65,11 @@ fn memoized_find_missing_descendants(
         }
         Trie::Node { pointer_block } => {
             for (_index, ptr) in pointer_block.as_indexed_pointers() {
-                find_missing_trie_keys(ptr, missing_trie_keys, trie_store, txn)?;
+                find_missing_trie_keys(ptr, missing_trie_keys, deduplicated, trie_store, txn)?;
             }
         }
         Trie::Extension { affix: _, pointer } => {
-            find_missing_trie_keys(pointer, missing_trie_keys, trie_store, txn)?;
+            find_missing_trie_keys(pointer, missing_trie_keys, deduplicated, trie_store, txn)?;
         }
     }
     *time_in_missing_trie_keys += start_trie_keys.elapsed();
@@ -54,6 +79,7 @@ fn memoized_find_missing_descendants(
 fn find_missing_trie_keys(
     ptr: Pointer,
     missing_trie_keys: &mut Vec<Digest>,
+    deduplicated: &mut u64,
     handle: &LmdbTrieStore,
     txn: &RwTransaction<'_>,
 ) -> Result<(), anyhow::Error> {
@@ -67,21 +93,26 @@ fn find_missing_trie_keys(
     )?;
     if existing.is_none() {
         missing_trie_keys.push(ptr);
+    } else {
+        *deduplicated += 1;
     }
     Ok(())
 }
 
+/// Copies the tries under `state_root` which are missing from `destination`,
+/// returning how many were copied and how many were already there.
 pub fn copy_state_root(
     state_root: Digest,
     source: &EngineState<LmdbGlobalState>,
     destination: &EngineState<LmdbGlobalState>,
-) -> Result<(), anyhow::Error> {
+) -> Result<CopyStats, anyhow::Error> {
     let mut missing_trie_keys = vec![state_root];
     let start_time = Instant::now();
     let mut heartbeat_interval = Instant::now();
 
     let mut total_tries: u64 = 0;
     let mut total_bytes: u64 = 0;
+    let mut deduplicated: u64 = 0;
 
     let mut time_searching_for_trie_keys = Duration::from_secs(0);
 
@@ -114,6 +145,7 @@ pub fn copy_state_root(
                     .map_err(|err| anyhow::anyhow!("couldn't serialize trie key: {:?}", err))?;
                 let read_bytes = key_bytes.len() as u64 + value_bytes.len() as u64;
                 total_bytes += read_bytes;
+                throttle::consume(read_bytes as usize);
                 total_tries += 1;
 
                 write_txn.write(destination_store.get_db(), &key_bytes, &value_bytes)?;
@@ -123,6 +155,7 @@ pub fn copy_state_root(
                     destination_store,
                     &write_txn,
                     &mut missing_trie_keys,
+                    &mut deduplicated,
                     &mut time_searching_for_trie_keys,
                 )?;
             }
@@ -147,5 +180,9 @@ pub fn copy_state_root(
         start_time.elapsed().as_micros(),
         time_searching_for_trie_keys.as_micros(),
     );
-    Ok(())
+    Ok(CopyStats {
+        tries: total_tries,
+        bytes: total_bytes,
+        deduplicated,
+    })
 }
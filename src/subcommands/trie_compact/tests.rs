@@ -1,6 +1,6 @@
 use std::fs::{self, File};
 
-use lmdb::DatabaseFlags;
+use lmdb::{DatabaseFlags, WriteFlags};
 use once_cell::sync::Lazy;
 use tempfile::{tempdir, TempDir};
 
@@ -16,10 +16,11 @@ use casper_types::bytesrepr::{Bytes, ToBytes};
 
 static DEFAULT_MAX_DB_SIZE: Lazy<usize> = Lazy::new(|| super::DEFAULT_MAX_DB_SIZE.parse().unwrap());
 
-use crate::common::db::TRIE_STORE_FILE_NAME;
+use crate::common::db::{get_optional, TrieEnv, DIR_LAYOUT_DATA_FILE_NAME, TRIE_STORE_FILE_NAME};
 
 use super::{
     compact::{self, DestinationOptions},
+    helpers::CopyStats,
     utils::{create_execution_engine, create_storage, load_execution_engine},
     Error,
 };
@@ -249,6 +250,56 @@ fn check_no_extra_tries() {
     dst_tmp_dir.close().unwrap();
 }
 
+#[test]
+fn copy_state_root_should_count_shared_tries() {
+    let (src_tmp_dir, data) = create_test_trie_store();
+    let dst_tmp_dir = tempdir().unwrap();
+    let (source_state, _env) = load_execution_engine(
+        src_tmp_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        Digest::default(),
+        true,
+    )
+    .unwrap();
+    let (destination_state, _dst_env) =
+        create_execution_engine(dst_tmp_dir.path(), *DEFAULT_MAX_DB_SIZE, true).unwrap();
+
+    // `node2` and its two leaves.
+    let stats =
+        super::helpers::copy_state_root(data[4].0, &source_state, &destination_state).unwrap();
+    assert_eq!(stats.tries, 3);
+    assert_eq!(stats.deduplicated, 0);
+    // `node1`, `leaf1` and the extension, whose child `node2` is shared.
+    let stats =
+        super::helpers::copy_state_root(data[3].0, &source_state, &destination_state).unwrap();
+    assert_eq!(stats.tries, 3);
+    assert_eq!(stats.deduplicated, 1);
+    assert!(stats.bytes > 0);
+}
+
+#[test]
+fn compaction_report_should_compare_sizes() {
+    assert_eq!(compact::saved_percent(200, 50), 75.0);
+    assert_eq!(compact::saved_percent(200, 200), 0.0);
+    assert_eq!(compact::saved_percent(0, 0), 0.0);
+
+    // Without blocks, nothing is copied but the sizes are still reported.
+    let (src_dir, _) = create_test_trie_store();
+    let dst_dir = tempdir().unwrap();
+    let (storage_dir, _store) = create_empty_test_storage();
+    let report = compact::trie_compact(
+        &storage_dir,
+        &src_dir,
+        &dst_dir,
+        DestinationOptions::New,
+        *DEFAULT_MAX_DB_SIZE,
+    )
+    .unwrap();
+    assert_eq!(report.state_roots, 0);
+    assert_eq!(report.tries, CopyStats::default());
+    assert!(report.source_size > 0);
+}
+
 #[test]
 fn missing_source_trie() {
     match compact::trie_compact(
@@ -414,3 +465,57 @@ fn missing_dst_with_destination_options() {
         Ok(_) => panic!("Unexpected successful trie compact"),
     }
 }
+
+#[test]
+fn copy_state_root_from_directory_layout() {
+    let src_tmp_dir = tempdir().unwrap();
+    let dst_tmp_dir = tempdir().unwrap();
+    let data = create_data();
+    // The trie store is created in the directory layout, as
+    // `data.lmdb/data.mdb`, as `data.lmdb` is a directory.
+    fs::create_dir(src_tmp_dir.path().join(TRIE_STORE_FILE_NAME)).unwrap();
+    {
+        let env = TrieEnv::create(src_tmp_dir.path(), 1 << 20).unwrap();
+        let db = env.db().unwrap();
+        env.write(|txn| -> Result<(), lmdb::Error> {
+            for TestData(key, trie) in data.iter() {
+                txn.put(db, key, &trie.to_bytes().unwrap(), WriteFlags::empty())?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+    assert!(src_tmp_dir
+        .path()
+        .join(TRIE_STORE_FILE_NAME)
+        .join(DIR_LAYOUT_DATA_FILE_NAME)
+        .exists());
+
+    let (source_state, _src_env) = load_execution_engine(
+        src_tmp_dir.path(),
+        *DEFAULT_MAX_DB_SIZE,
+        Digest::default(),
+        true,
+    )
+    .unwrap();
+    let (destination_state, dst_env) =
+        create_execution_engine(dst_tmp_dir.path(), *DEFAULT_MAX_DB_SIZE, true).unwrap();
+    super::helpers::copy_state_root(data[3].0, &source_state, &destination_state).unwrap();
+
+    let dst_store = LmdbTrieStore::new(&dst_env, None, DatabaseFlags::empty()).unwrap();
+    let txn = dst_env.create_read_txn().unwrap();
+    let keys: Vec<_> = data.iter().map(|test_data| test_data.0).collect();
+    let entries: Vec<Option<Trie<Bytes, Bytes>>> = dst_store.get_many(&txn, keys.iter()).unwrap();
+    assert!(entries.iter().all(Option::is_some));
+    txn.commit().unwrap();
+
+    // The storage side opens it in the same layout.
+    let env = TrieEnv::open(src_tmp_dir.path()).unwrap();
+    let db = env.db().unwrap();
+    let raw_trie = env
+        .read(|txn| {
+            get_optional(txn, db, &data[3].0).map(|maybe_raw| maybe_raw.map(<[u8]>::to_vec))
+        })
+        .unwrap();
+    assert_eq!(raw_trie, Some(data[3].1.to_bytes().unwrap()));
+}
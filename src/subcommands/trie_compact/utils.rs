@@ -1,5 +1,6 @@
 use std::{
     env, fs,
+    os::unix::fs::symlink,
     path::{Path, PathBuf},
     sync::Arc,
 };
@@ -18,7 +19,10 @@ use casper_node::{storage::Storage, StorageConfig, WithDir};
 use casper_types::ProtocolVersion;
 use lmdb::DatabaseFlags;
 
-use crate::common::db::TRIE_STORE_FILE_NAME;
+use crate::common::{
+    db::{self, TRIE_STORE_FILE_NAME},
+    temp::TempDir,
+};
 
 /// LMDB max readers
 ///
@@ -31,6 +35,24 @@ pub fn load_execution_engine(
     default_max_db_size: usize,
     state_root_hash: Digest,
     manual_sync_enabled: bool,
+) -> Result<(Arc<EngineState<LmdbGlobalState>>, Arc<LmdbEnvironment>), anyhow::Error> {
+    load_execution_engine_with_config(
+        ee_lmdb_path,
+        default_max_db_size,
+        state_root_hash,
+        manual_sync_enabled,
+        EngineConfig::default(),
+    )
+}
+
+/// Loads an existing execution engine which executes deploys under
+/// `engine_config`.
+pub fn load_execution_engine_with_config(
+    ee_lmdb_path: impl AsRef<Path>,
+    default_max_db_size: usize,
+    state_root_hash: Digest,
+    manual_sync_enabled: bool,
+    engine_config: EngineConfig,
 ) -> Result<(Arc<EngineState<LmdbGlobalState>>, Arc<LmdbEnvironment>), anyhow::Error> {
     let lmdb_data_file = ee_lmdb_path.as_ref().join(TRIE_STORE_FILE_NAME);
     if !ee_lmdb_path.as_ref().join(TRIE_STORE_FILE_NAME).exists() {
@@ -48,19 +70,43 @@ pub fn load_execution_engine(
         state_root_hash,
     );
     Ok((
-        Arc::new(EngineState::new(global_state, EngineConfig::default())),
+        Arc::new(EngineState::new(global_state, engine_config)),
         lmdb_environment,
     ))
 }
 
 /// Create an lmdb environment at a given path.
+///
+/// The execution engine only opens trie stores in the layout of the node, so
+/// a trie store in the directory layout is opened through a link to its data
+/// file in a temporary directory. The link is only needed while opening the
+/// environment, which then keeps the data file open.
 fn create_lmdb_environment(
     lmdb_path: impl AsRef<Path>,
     default_max_db_size: usize,
     manual_sync_enabled: bool,
 ) -> Result<Arc<LmdbEnvironment>, anyhow::Error> {
+    let trie_store_path = lmdb_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    let maybe_link_dir = if db::is_dir_layout(&trie_store_path) {
+        let link_dir = TempDir::new()?;
+        symlink(
+            db::data_file_path(&trie_store_path).canonicalize()?,
+            link_dir.path().join(TRIE_STORE_FILE_NAME),
+        )?;
+        info!(
+            "Opening trie store {} in the directory layout.",
+            trie_store_path.display()
+        );
+        Some(link_dir)
+    } else {
+        None
+    };
+    let env_path = match maybe_link_dir.as_ref() {
+        Some(link_dir) => link_dir.path(),
+        None => lmdb_path.as_ref(),
+    };
     let lmdb_environment = Arc::new(LmdbEnvironment::new(
-        &lmdb_path,
+        env_path,
         default_max_db_size,
         DEFAULT_MAX_READERS,
         manual_sync_enabled,
@@ -0,0 +1,132 @@
+mod export;
+#[cfg(test)]
+pub(crate) mod tests;
+
+use std::{
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use casper_hashing::Digest;
+use casper_types::bytesrepr::Error as BytesreprError;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use crate::common::output::{self, Compression, OutputWriter};
+pub(crate) use export::export_tries;
+
+pub const COMMAND_NAME: &str = "trie-export";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const STATE_ROOT: &str = "state-root";
+
+/// Errors encountered when exporting tries.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the trie store: {0}")]
+    Database(#[from] LmdbError),
+    /// A node of a trie to export is missing.
+    #[error("Trie node {0} under state root {1} is missing")]
+    MissingNode(Digest, Digest),
+    /// The trie store is missing from the database directory.
+    #[error("Trie store not found at {0}")]
+    MissingTrieStore(PathBuf),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on a trie node, whose children can't be found.
+    #[error("Error parsing trie node {0}: {1}")]
+    Parsing(Digest, BytesreprError),
+}
+
+enum DisplayOrder {
+    DbPath,
+    StateRoot,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Writes every trie node reachable from the given state roots to \
+            a flat file, which `trie-import` loads into a new trie store. The \
+            file doesn't depend on the LMDB version or the machine, so it can \
+            move global state between them.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `data.lmdb` file."),
+        )
+        .arg(
+            Arg::new(STATE_ROOT)
+                .display_order(DisplayOrder::StateRoot as usize)
+                .required(true)
+                .short('r')
+                .long(STATE_ROOT)
+                .takes_value(true)
+                .multiple_occurrences(true)
+                .use_value_delimiter(true)
+                .value_name("STATE_ROOT_HASH")
+                .validator(|value| Digest::from_hex(value).map(|_| ()))
+                .help(
+                    "Hash of a state root whose trie to export, in hex. Can be \
+                    repeated or given as a comma separated list.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the trie file. If \
+                    unspecified, defaults to standard output, in which case \
+                    `--quiet` keeps log messages out of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let state_roots: Vec<Digest> = matches
+        .values_of(STATE_ROOT)
+        .expect("should have state-root arg")
+        .map(|value| Digest::from_hex(value).expect("should be validated"))
+        .collect();
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let compression = Compression::from_matches(matches);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, compression)?;
+    export_tries(path, &state_roots, &mut out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
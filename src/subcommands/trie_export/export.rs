@@ -0,0 +1,73 @@
+use std::{collections::HashSet, io::Write, path::Path, result::Result};
+
+use casper_execution_engine::storage::trie::Trie;
+use casper_hashing::Digest;
+use casper_types::{bytesrepr, Key, StoredValue};
+use log::info;
+
+use crate::common::{
+    db::{self, TrieEnv, TRIE_STORE_FILE_NAME},
+    trie_file::TrieFileWriter,
+};
+
+use super::Error;
+
+/// Number of nodes exported between two progress messages.
+const NODES_PER_PROGRESS_LOG: u64 = 1_000_000;
+
+/// Writes the nodes of the tries under `state_roots` in the trie store in
+/// `db_path` to `writer`, in the trie file format. Nodes shared between the
+/// tries are written once. Returns the number of nodes written.
+pub(crate) fn export_tries<P: AsRef<Path>, W: Write>(
+    db_path: P,
+    state_roots: &[Digest],
+    writer: W,
+) -> Result<u64, Error> {
+    let trie_path = db_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    if !trie_path.exists() {
+        return Err(Error::MissingTrieStore(trie_path));
+    }
+    let trie_env = TrieEnv::open(&db_path)?;
+    let trie_db = trie_env.db()?;
+    let txn = trie_env.begin_ro_txn()?;
+
+    let mut trie_writer = TrieFileWriter::new(writer, state_roots)?;
+    let mut visited = HashSet::new();
+    for state_root in state_roots {
+        let mut pending = vec![*state_root];
+        while let Some(trie_key) = pending.pop() {
+            if !visited.insert(trie_key) {
+                continue;
+            }
+            let raw_trie = db::get_optional(&txn, trie_db, &trie_key)?
+                .ok_or(Error::MissingNode(trie_key, *state_root))?;
+            trie_writer.write_node(&trie_key, raw_trie)?;
+            if trie_writer.node_count() % NODES_PER_PROGRESS_LOG == 0 {
+                info!("Exported {} trie nodes...", trie_writer.node_count());
+            }
+            // A first byte of `0` indicates a leaf, which has no children.
+            if raw_trie.first() == Some(&0) {
+                continue;
+            }
+            match bytesrepr::deserialize::<Trie<Key, StoredValue>>(raw_trie.to_vec())
+                .map_err(|bytesrepr_err| Error::Parsing(trie_key, bytesrepr_err))?
+            {
+                Trie::Leaf { .. } => {}
+                Trie::Node { pointer_block } => pending.extend(
+                    pointer_block
+                        .as_indexed_pointers()
+                        .map(|(_index, pointer)| pointer.into_hash()),
+                ),
+                Trie::Extension { pointer, .. } => pending.push(pointer.into_hash()),
+            }
+        }
+    }
+    let node_count = trie_writer.node_count();
+    trie_writer.finish()?;
+    info!(
+        "Exported {} trie nodes under {} state roots.",
+        node_count,
+        state_roots.len()
+    );
+    Ok(node_count)
+}
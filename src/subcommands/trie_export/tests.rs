@@ -0,0 +1,65 @@
+use casper_hashing::Digest;
+use casper_types::bytesrepr::ToBytes;
+use lmdb::{Error as LmdbError, WriteFlags};
+use tempfile::TempDir;
+
+use super::{export_tries, Error};
+use crate::{
+    common::{db::TrieEnv, trie_file::TrieFileReader},
+    subcommands::trie_compact::tests::create_data,
+};
+
+/// Creates a trie store holding the mock trie, leaving out the nodes at
+/// `missing`. Returns the root of the mock trie.
+pub(crate) fn new_trie_store(missing: &[usize]) -> (TempDir, Digest) {
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let data = create_data();
+    let trie_env = TrieEnv::create(tmp_dir.path(), 1 << 20).unwrap();
+    let trie_db = trie_env.db().unwrap();
+    trie_env
+        .write(|txn| -> Result<(), LmdbError> {
+            for (idx, test_data) in data.iter().enumerate() {
+                if !missing.contains(&idx) {
+                    let raw_trie = test_data.1.to_bytes().unwrap();
+                    txn.put(trie_db, &test_data.0, &raw_trie, WriteFlags::empty())?;
+                }
+            }
+            Ok(())
+        })
+        .unwrap();
+    // `node_1` is the root of the mock trie.
+    (tmp_dir, data[3].0)
+}
+
+#[test]
+fn export_should_write_reachable_nodes() {
+    let (tmp_dir, state_root) = new_trie_store(&[]);
+    let mut bytes = vec![];
+    // The same state root given twice is exported once.
+    let node_count = export_tries(tmp_dir.path(), &[state_root, state_root], &mut bytes).unwrap();
+    let data = create_data();
+    assert_eq!(node_count, data.len() as u64);
+
+    let mut reader = TrieFileReader::new(bytes.as_slice()).unwrap();
+    assert_eq!(reader.state_roots(), [state_root, state_root]);
+    let mut trie_keys = vec![];
+    while let Some((trie_key, raw_trie)) = reader.next_node().unwrap() {
+        assert_eq!(Digest::hash(&raw_trie), trie_key);
+        trie_keys.push(trie_key);
+    }
+    trie_keys.sort();
+    let mut expected_keys: Vec<Digest> = data.iter().map(|test_data| test_data.0).collect();
+    expected_keys.sort();
+    assert_eq!(trie_keys, expected_keys);
+}
+
+#[test]
+fn export_with_missing_node_should_fail() {
+    // `leaf_2` is missing.
+    let (tmp_dir, state_root) = new_trie_store(&[1]);
+    let leaf_2_hash = create_data()[1].0;
+    assert!(matches!(
+        export_tries(tmp_dir.path(), &[state_root], &mut vec![]),
+        Err(Error::MissingNode(trie_key, root)) if trie_key == leaf_2_hash && root == state_root
+    ));
+}
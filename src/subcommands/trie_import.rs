@@ -0,0 +1,110 @@
+mod import;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    fs::File,
+    io::Error as IoError,
+    path::{Path, PathBuf},
+};
+
+use casper_hashing::Digest;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use thiserror::Error as ThisError;
+
+use super::trie_compact::DEFAULT_MAX_DB_SIZE;
+use crate::common::zstd_utils::Error as ZstdError;
+pub(crate) use import::import_tries;
+
+pub const COMMAND_NAME: &str = "trie-import";
+const DB_PATH: &str = "db-path";
+const INPUT: &str = "input";
+const MAX_DB_SIZE: &str = "max-db-size";
+
+/// Errors encountered when importing tries.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Database operation error.
+    #[error("Error operating the trie store: {0}")]
+    Database(#[from] LmdbError),
+    /// Error setting up the decompression of the input.
+    #[error("Error decompressing input: {0}")]
+    Decompress(#[from] ZstdError),
+    /// The destination already has a trie store.
+    #[error("Trie store already exists at {0}")]
+    DestinationExists(PathBuf),
+    /// A node doesn't hash to the key it's stored under.
+    #[error("Trie node {0} hashes to {1}")]
+    HashMismatch(Digest, Digest),
+    /// Error reading the input.
+    #[error("Error reading input: {0}")]
+    Input(#[from] IoError),
+    /// Error creating the destination directory.
+    #[error("Error creating {0}: {1}")]
+    InvalidPath(PathBuf, IoError),
+    /// The input doesn't hold the root of one of its state roots.
+    #[error("State root {0} is missing from the input")]
+    MissingStateRoot(Digest),
+}
+
+enum DisplayOrder {
+    Input,
+    DbPath,
+    MaxDbSize,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Loads a trie file written by `trie-export` into a new trie store, \
+            checking every node against its hash.",
+        )
+        .arg(
+            Arg::new(INPUT)
+                .display_order(DisplayOrder::Input as usize)
+                .required(true)
+                .short('i')
+                .long(INPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help("Path of the trie file, optionally compressed with zstd."),
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory where the `data.lmdb` file will be \
+                    created. It must not already have one.",
+                ),
+        )
+        .arg(
+            Arg::new(MAX_DB_SIZE)
+                .display_order(DisplayOrder::MaxDbSize as usize)
+                .short('m')
+                .long(MAX_DB_SIZE)
+                .takes_value(true)
+                .default_value(DEFAULT_MAX_DB_SIZE)
+                .value_name("MAX_DB_SIZE")
+                .validator(|value| value.parse::<usize>().map(|_| ()))
+                .help("Maximum size the `data.lmdb` file is allowed to be, in bytes."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let input = File::open(matches.value_of(INPUT).expect("should have input arg"))?;
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let max_db_size = matches
+        .value_of(MAX_DB_SIZE)
+        .expect("should have a default")
+        .parse()
+        .expect("should be validated");
+    import_tries(input, path, max_db_size)?;
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, ErrorKind, Read},
+    path::Path,
+    result::Result,
+};
+
+use casper_hashing::Digest;
+use lmdb::{Transaction, WriteFlags};
+use log::{info, warn};
+
+use crate::common::{
+    db::{self, TrieEnv, TRIE_STORE_FILE_NAME},
+    trie_file::TrieFileReader,
+    zstd_utils,
+};
+
+use super::Error;
+
+/// Magic number starting a zstd frame, as stored.
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xB5, 0x2F, 0xFD];
+/// Number of nodes written per transaction.
+const NODES_PER_COMMIT: usize = 10_000;
+
+/// Returns a reader over the decompressed contents of `input`, which may be
+/// compressed with zstd.
+fn decompressed<'a, R: Read + 'a>(input: R) -> Result<Box<dyn Read + 'a>, Error> {
+    let mut reader = BufReader::new(input);
+    if reader.fill_buf()?.starts_with(&ZSTD_MAGIC) {
+        Ok(Box::new(zstd_utils::zstd_decode_stream(reader)?))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
+
+fn import<R: Read, P: AsRef<Path>>(
+    trie_reader: &mut TrieFileReader<R>,
+    db_path: P,
+    max_db_size: usize,
+) -> Result<u64, Error> {
+    let trie_env = TrieEnv::create(&db_path, max_db_size)?;
+    let trie_db = trie_env.db()?;
+    let mut node_count = 0;
+    let mut done = false;
+    while !done {
+        let mut txn = trie_env.begin_rw_txn()?;
+        for _ in 0..NODES_PER_COMMIT {
+            let (trie_key, raw_trie) = match trie_reader.next_node()? {
+                Some(node) => node,
+                None => {
+                    done = true;
+                    break;
+                }
+            };
+            let computed_hash = Digest::hash(&raw_trie);
+            if computed_hash != trie_key {
+                return Err(Error::HashMismatch(trie_key, computed_hash));
+            }
+            txn.put(trie_db, &trie_key, &raw_trie, WriteFlags::empty())?;
+            node_count += 1;
+        }
+        txn.commit()?;
+    }
+
+    let txn = trie_env.begin_ro_txn()?;
+    for state_root in trie_reader.state_roots() {
+        if db::get_optional(&txn, trie_db, state_root)?.is_none() {
+            return Err(Error::MissingStateRoot(*state_root));
+        }
+    }
+    Ok(node_count)
+}
+
+/// Loads the trie file read from `input`, optionally compressed with zstd,
+/// into a new trie store in `db_path`, with room for `max_db_size` bytes.
+/// Every node is checked against its hash. On failure, the new trie store
+/// is removed. Returns the number of nodes imported.
+pub(crate) fn import_tries<R: Read, P: AsRef<Path>>(
+    input: R,
+    db_path: P,
+    max_db_size: usize,
+) -> Result<u64, Error> {
+    let db_path = db_path.as_ref();
+    let trie_path = db_path.join(TRIE_STORE_FILE_NAME);
+    if trie_path.exists() {
+        return Err(Error::DestinationExists(trie_path));
+    }
+    fs::create_dir_all(db_path)
+        .map_err(|io_err| Error::InvalidPath(db_path.to_path_buf(), io_err))?;
+    let mut trie_reader = TrieFileReader::new(decompressed(input)?)?;
+
+    let result = import(&mut trie_reader, db_path, max_db_size);
+    match result.as_ref() {
+        Ok(node_count) => info!(
+            "Imported {} trie nodes under {} state roots.",
+            node_count,
+            trie_reader.state_roots().len()
+        ),
+        Err(_) => {
+            let lock_path = db_path.join(format!("{}-lock", TRIE_STORE_FILE_NAME));
+            for path in [trie_path, lock_path] {
+                match fs::remove_file(&path) {
+                    Err(io_err) if io_err.kind() != ErrorKind::NotFound => {
+                        warn!("Couldn't remove {}: {}", path.display(), io_err)
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+
+use casper_hashing::Digest;
+use lmdb::{Cursor, Transaction};
+
+use super::{import_tries, Error};
+use crate::{
+    common::{
+        db::{TrieEnv, TRIE_STORE_FILE_NAME},
+        trie_file::TrieFileWriter,
+    },
+    subcommands::trie_export::{export_tries, tests::new_trie_store},
+};
+
+fn trie_entries(trie_env: &TrieEnv) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    let trie_db = trie_env.db().unwrap();
+    let txn = trie_env.begin_ro_txn().unwrap();
+    let entries = txn
+        .open_ro_cursor(trie_db)
+        .unwrap()
+        .iter_start()
+        .map(|(key, value)| (key.to_vec(), value.to_vec()))
+        .collect();
+    txn.commit().unwrap();
+    entries
+}
+
+#[test]
+fn import_should_restore_exported_tries() {
+    let (src_dir, state_root) = new_trie_store(&[]);
+    let mut bytes = vec![];
+    export_tries(src_dir.path(), &[state_root], &mut bytes).unwrap();
+    let compressed = zstd::encode_all(bytes.as_slice(), 3).unwrap();
+
+    for input in [bytes, compressed] {
+        let dest_dir = tempfile::tempdir().unwrap();
+        let dest_path = dest_dir.path().join("imported");
+        assert_eq!(
+            import_tries(input.as_slice(), &dest_path, 1 << 20).unwrap(),
+            6
+        );
+        assert_eq!(
+            trie_entries(&TrieEnv::open(&dest_path).unwrap()),
+            trie_entries(&TrieEnv::open(src_dir.path()).unwrap())
+        );
+
+        // The destination must not have a trie store already.
+        assert!(matches!(
+            import_tries(input.as_slice(), &dest_path, 1 << 20),
+            Err(Error::DestinationExists(_))
+        ));
+    }
+}
+
+#[test]
+fn import_with_corrupt_node_should_fail() {
+    let state_root = Digest::hash([1u8]);
+    let mut writer = TrieFileWriter::new(vec![], &[state_root]).unwrap();
+    writer.write_node(&state_root, &[2u8]).unwrap();
+    let bytes = writer.finish().unwrap();
+
+    let dest_dir = tempfile::tempdir().unwrap();
+    assert!(matches!(
+        import_tries(bytes.as_slice(), dest_dir.path(), 1 << 20),
+        Err(Error::HashMismatch(trie_key, computed_hash))
+            if trie_key == state_root && computed_hash == Digest::hash([2u8])
+    ));
+    // The partially imported trie store is removed.
+    assert!(!dest_dir.path().join(TRIE_STORE_FILE_NAME).exists());
+
+    // A file without the nodes of its state root is incomplete.
+    let bytes = TrieFileWriter::new(vec![], &[state_root])
+        .unwrap()
+        .finish()
+        .unwrap();
+    assert!(matches!(
+        import_tries(bytes.as_slice(), dest_dir.path(), 1 << 20),
+        Err(Error::MissingStateRoot(root)) if root == state_root
+    ));
+}
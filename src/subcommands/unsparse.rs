@@ -1,25 +1,40 @@
 use std::{
-    fs,
-    io::Error as IoError,
+    fs::{self, File, OpenOptions},
+    io::{Error as IoError, Read, Write},
     path::{Path, PathBuf},
 };
 
 use clap::{Arg, ArgMatches, Command};
 use lmdb::{Environment, EnvironmentFlags, Error as LmdbError};
-use log::{error, info};
+use log::{error, info, warn};
 use thiserror::Error as ThisError;
 
+use crate::common::{lmdb_utils, progress::ProgressTracker};
+
 pub const COMMAND_NAME: &str = "unsparse";
-const DB_PATH: &str = "file-path";
+pub(crate) const DB_PATH: &str = "file-path";
+pub(crate) const OUTPUT: &str = "output";
+
+/// Size of the chunks copied at once when writing to a new file.
+const COPY_CHUNK_SIZE: usize = 1024 * 1024;
 
 #[derive(ThisError, Debug)]
 pub enum Error {
+    #[error("Failed to copy {0} to {1}: {2}")]
+    Copy(PathBuf, PathBuf, IoError),
     #[error("Failed to get metadata for {0}: {1}")]
     Metadata(PathBuf, IoError),
     #[error("Failed to open lmdb database at {0}: {1}")]
     Lmdb(PathBuf, LmdbError),
     #[error("Failed to reduce size of {0} from {1} bytes")]
     Size(PathBuf, u64),
+    #[error("Size of {0} is {1} bytes but its pages in use take {2} bytes")]
+    Verification(PathBuf, u64, u64),
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
 }
 
 pub fn command(display_order: usize) -> Command<'static> {
@@ -31,11 +46,23 @@ pub fn command(display_order: usize) -> Command<'static> {
         )
         .arg(
             Arg::new(DB_PATH)
-                .display_order(0)
+                .display_order(DisplayOrder::DbPath as usize)
                 .value_name("DB_PATH")
                 .required(true)
                 .help("Path to the storage.lmdb or data.lmdb file."),
         )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Write the reduced database to a new file at this path instead of \
+                    modifying the original file in place. The file must not exist.",
+                ),
+        )
 }
 
 pub fn run(matches: &ArgMatches) -> Result<(), Error> {
@@ -44,24 +71,42 @@ pub fn run(matches: &ArgMatches) -> Result<(), Error> {
             .value_of(DB_PATH)
             .expect("should have file-path arg"),
     );
-    unsparse(path)
+    match matches.value_of(OUTPUT) {
+        Some(output) => unsparse_to_file(path, Path::new(output)),
+        None => unsparse(path),
+    }
 }
 
-fn unsparse(path: &Path) -> Result<(), Error> {
-    let size_before = fs::metadata(path)
+fn file_size(path: &Path) -> Result<u64, Error> {
+    fs::metadata(path)
         .map(|metadata| metadata.len())
-        .map_err(|io_err| Error::Metadata(path.to_path_buf(), io_err))?;
+        .map_err(|io_err| Error::Metadata(path.to_path_buf(), io_err))
+}
+
+/// Checks that the size of the file at `path` is exactly the size of the
+/// pages in use according to the LMDB metadata.
+fn verify(path: &Path, env: &Environment) -> Result<(), Error> {
+    let used_size =
+        lmdb_utils::used_size(env).map_err(|lmdb_err| Error::Lmdb(path.to_path_buf(), lmdb_err))?;
+    let size = file_size(path)?;
+    if size != used_size {
+        return Err(Error::Verification(path.to_path_buf(), size, used_size));
+    }
+    info!("Verified size of {}: {} bytes.", path.display(), size);
+    Ok(())
+}
 
-    let _env = Environment::new()
+fn unsparse(path: &Path) -> Result<(), Error> {
+    let size_before = file_size(path)?;
+
+    let env = Environment::new()
         .set_flags(EnvironmentFlags::WRITE_MAP | EnvironmentFlags::NO_SUB_DIR)
         .set_max_dbs(100)
         .set_map_size(1)
         .open(path)
         .map_err(|lmdb_err| Error::Lmdb(path.to_path_buf(), lmdb_err))?;
 
-    let size_after = fs::metadata(path)
-        .map(|metadata| metadata.len())
-        .map_err(|io_err| Error::Metadata(path.to_path_buf(), io_err))?;
+    let size_after = file_size(path)?;
 
     if size_before > size_after {
         info!(
@@ -70,7 +115,7 @@ fn unsparse(path: &Path) -> Result<(), Error> {
             size_before,
             size_after
         );
-        Ok(())
+        verify(path, &env)
     } else {
         error!(
             "Failed to reduce size of {} from {} bytes.",
@@ -81,6 +126,73 @@ fn unsparse(path: &Path) -> Result<(), Error> {
     }
 }
 
+/// Copies the pages in use of the database at `path` to a new file at
+/// `output`, leaving the original file untouched.
+fn unsparse_to_file(path: &Path, output: &Path) -> Result<(), Error> {
+    let size_before = file_size(path)?;
+    let used_size = {
+        let env = Environment::new()
+            .set_flags(EnvironmentFlags::READ_ONLY | EnvironmentFlags::NO_SUB_DIR)
+            .set_max_dbs(100)
+            .open(path)
+            .map_err(|lmdb_err| Error::Lmdb(path.to_path_buf(), lmdb_err))?;
+        lmdb_utils::used_size(&env).map_err(|lmdb_err| Error::Lmdb(path.to_path_buf(), lmdb_err))?
+    };
+
+    let copy_err = |io_err| Error::Copy(path.to_path_buf(), output.to_path_buf(), io_err);
+    let mut source = File::open(path).map_err(copy_err)?.take(used_size);
+    let mut destination = OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .open(output)
+        .map_err(copy_err)?;
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        used_size as usize,
+        Box::new(|completion| info!("Copy {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    let mut buffer = vec![0u8; COPY_CHUNK_SIZE];
+    loop {
+        let bytes_read = source.read(&mut buffer).map_err(copy_err)?;
+        if bytes_read == 0 {
+            break;
+        }
+        destination
+            .write_all(&buffer[..bytes_read])
+            .map_err(copy_err)?;
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(bytes_read);
+        }
+    }
+    destination.sync_all().map_err(copy_err)?;
+    drop(destination);
+
+    let env = Environment::new()
+        .set_flags(
+            EnvironmentFlags::READ_ONLY | EnvironmentFlags::NO_SUB_DIR | EnvironmentFlags::NO_LOCK,
+        )
+        .set_max_dbs(100)
+        .open(output)
+        .map_err(|lmdb_err| Error::Lmdb(output.to_path_buf(), lmdb_err))?;
+    verify(output, &env)?;
+    info!(
+        "Wrote {} to {}, reducing its size from {} to {} bytes.",
+        path.display(),
+        output.display(),
+        size_before,
+        used_size
+    );
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -105,4 +217,29 @@ mod tests {
         assert!(unsparse(db_path).is_err(), "repeat unsparse should fail");
         assert_eq!(db_size(), size_after, "file size should be unchanged");
     }
+
+    #[test]
+    fn should_write_reduced_copy() {
+        let fixture = LmdbTestFixture::new(vec!["a"], None);
+        let db_path = fixture.file_path.as_path();
+        let size_before = fs::metadata(db_path).unwrap().len();
+        let out_dir = tempfile::tempdir().unwrap();
+        let output = out_dir.path().join("reduced.lmdb");
+
+        unsparse_to_file(db_path, &output).expect("unsparse to file should succeed");
+        assert_eq!(
+            fs::metadata(db_path).unwrap().len(),
+            size_before,
+            "source should be unchanged"
+        );
+        assert!(fs::metadata(&output).unwrap().len() < size_before);
+
+        // The output can be unsparsed in place no further.
+        assert!(unsparse(&output).is_err());
+        // Existing output files aren't overwritten.
+        assert!(matches!(
+            unsparse_to_file(db_path, &output),
+            Err(Error::Copy(..))
+        ));
+    }
 }
@@ -0,0 +1,121 @@
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, HashingAlgorithmVersion};
+use casper_types::bytesrepr::ToBytes;
+use lmdb::{Transaction, WriteFlags};
+
+use super::verify::{verify_bodies, Scheme};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockBodyMerkleDatabase, BlockHeaderDatabase, Database,
+        DeployHashesDatabase, ProposerDatabase, TransferHashesDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture, KEYS},
+};
+
+fn new_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            BlockBodyMerkleDatabase::db_name(),
+            DeployHashesDatabase::db_name(),
+            TransferHashesDatabase::db_name(),
+            ProposerDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+fn mock_body(idx: u8) -> BlockBody {
+    BlockBody::from_parts(
+        KEYS[0].clone(),
+        vec![test_utils::mock_deploy_hash(idx)],
+        vec![],
+    )
+}
+
+/// Stores the header of the block at `height` with the given body hash.
+fn put_header(fixture: &LmdbTestFixture, height: u8, body_hash: Digest) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    header.body_hash = body_hash;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+    block_hash
+}
+
+#[test]
+fn verify_bodies_should_report_mismatches() {
+    let fixture = new_fixture();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+
+    // Block 0 has a valid legacy body, block 1 the legacy body of another
+    // block stored under its body hash.
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for idx in 0..2 {
+        let body_hash = mock_body(idx).hash(HashingAlgorithmVersion::V1);
+        let stored_body = if idx == 1 {
+            mock_body(9)
+        } else {
+            mock_body(idx)
+        };
+        txn.put(
+            body_db,
+            &body_hash,
+            &bincode::serialize(&stored_body).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    put_header(&fixture, 0, mock_body(0).hash(HashingAlgorithmVersion::V1));
+    let legacy_block_hash = put_header(&fixture, 1, mock_body(1).hash(HashingAlgorithmVersion::V1));
+
+    // Block 2 has a valid merkle body, block 3 one whose deploy hashes were
+    // replaced.
+    for idx in 2..4 {
+        let (body_hash, entries) = test_utils::mock_merkle_body(&mock_body(idx));
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        for (db_name, key, value) in entries {
+            let value = if idx == 3 && db_name == DeployHashesDatabase::db_name() {
+                vec![test_utils::mock_deploy_hash(8)].to_bytes().unwrap()
+            } else {
+                value
+            };
+            txn.put(
+                *fixture.db(Some(db_name)).unwrap(),
+                &key,
+                &value,
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.commit().unwrap();
+        put_header(&fixture, idx, body_hash);
+    }
+    // Block 4 has no body.
+    put_header(&fixture, 4, mock_body(4).hash(HashingAlgorithmVersion::V2));
+
+    let report = verify_bodies(fixture.tmp_dir.path()).unwrap();
+    assert_eq!(report.blocks, 5);
+    assert_eq!(report.legacy_bodies, 2);
+    assert_eq!(report.merkle_bodies, 2);
+    assert_eq!(report.missing_bodies, 1);
+    assert_eq!(report.mismatches.len(), 2);
+    assert_eq!(report.mismatches[0].block_hash, legacy_block_hash);
+    assert_eq!(report.mismatches[0].scheme, Scheme::Legacy);
+    assert_eq!(
+        report.mismatches[0].computed_hash,
+        mock_body(9).hash(HashingAlgorithmVersion::V1)
+    );
+    assert_eq!(report.mismatches[1].height, 3);
+    assert_eq!(report.mismatches[1].scheme, Scheme::Merkle);
+}
@@ -0,0 +1,161 @@
+use std::{path::Path, result::Result};
+
+use casper_hashing::Digest;
+use casper_node::types::{BlockHash, HashingAlgorithmVersion};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        block_iter,
+        db::{BlockBodyDatabase, BlockHeaderDatabase, Database, StorageEnv},
+        merkle_body::{self, Error as MerkleBodyError},
+        progress::ProgressTracker,
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use super::Error;
+
+/// The scheme under which a block body is stored.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Scheme {
+    /// The whole body is stored in `block_body`, and hashed as a whole.
+    Legacy,
+    /// The body is split in parts across the merkle body databases, and
+    /// hashed as a merkle linked list of the parts.
+    Merkle,
+}
+
+impl Scheme {
+    fn hashing_algorithm_version(self) -> HashingAlgorithmVersion {
+        match self {
+            Scheme::Legacy => HashingAlgorithmVersion::V1,
+            Scheme::Merkle => HashingAlgorithmVersion::V2,
+        }
+    }
+}
+
+/// A block body whose contents don't hash to the body hash of its header.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Mismatch {
+    pub(crate) block_hash: BlockHash,
+    pub(crate) height: u64,
+    pub(crate) scheme: Scheme,
+    pub(crate) body_hash: Digest,
+    pub(crate) computed_hash: Digest,
+}
+
+/// Counts of the bodies verified and the mismatches found.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct BodyReport {
+    pub(crate) blocks: usize,
+    pub(crate) legacy_bodies: usize,
+    pub(crate) merkle_bodies: usize,
+    pub(crate) missing_bodies: usize,
+    pub(crate) mismatches: Vec<Mismatch>,
+}
+
+/// Reads the bodies of the block `block_hash` stored under its body hash,
+/// under either scheme. A block may have a body under both schemes.
+fn read_bodies<R: StorageReader>(
+    reader: &R,
+    block_hash: &BlockHash,
+    body_hash: &Digest,
+) -> Result<Vec<(Scheme, BlockBody)>, Error> {
+    let mut bodies = vec![];
+    if reader.has_table(BlockBodyDatabase::db_name())? {
+        if let Some(raw_body) = reader.get(BlockBodyDatabase::db_name(), body_hash.as_ref())? {
+            let body = bincode::deserialize(&raw_body)
+                .map_err(|bincode_err| Error::Parsing(*block_hash, bincode_err))?;
+            bodies.push((Scheme::Legacy, body));
+        }
+    }
+    match merkle_body::read_merkle_body(reader, body_hash) {
+        Ok(Some(merkle_body)) => bodies.push((Scheme::Merkle, merkle_body.body)),
+        Ok(None) => {}
+        Err(MerkleBodyError::MissingPart(db_name, part_hash)) => warn!(
+            "Merkle body of block {} is missing part {} from the {} database.",
+            block_hash, part_hash, db_name
+        ),
+        Err(merkle_body_err) => return Err(Error::MerkleBody(*block_hash, merkle_body_err)),
+    }
+    Ok(bodies)
+}
+
+/// Recomputes the hash of every block body in the storage database at
+/// `db_path` and compares it with the body hash of its header. Bodies are
+/// hashed according to the scheme they are stored under rather than the
+/// protocol version of their header, as the version switchover differs
+/// between networks.
+pub(crate) fn verify_bodies<P: AsRef<Path>>(db_path: P) -> Result<BodyReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+    let blocks = if reader.has_table(BlockHeaderDatabase::db_name())? {
+        block_iter::blocks_by_height(&reader)?
+    } else {
+        vec![]
+    };
+
+    let mut report = BodyReport {
+        blocks: blocks.len(),
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Verification {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    for (height, block_hash) in blocks {
+        let header = block_iter::read_header(&reader, &block_hash)?;
+        let body_hash = *header.body_hash();
+        let bodies = read_bodies(&reader, &block_hash, &body_hash)?;
+        if bodies.is_empty() {
+            report.missing_bodies += 1;
+        }
+        for (scheme, body) in bodies {
+            match scheme {
+                Scheme::Legacy => report.legacy_bodies += 1,
+                Scheme::Merkle => report.merkle_bodies += 1,
+            }
+            let computed_hash = body.hash(scheme.hashing_algorithm_version());
+            if computed_hash != body_hash {
+                let mismatch = Mismatch {
+                    block_hash,
+                    height,
+                    scheme,
+                    body_hash,
+                    computed_hash,
+                };
+                warn!("Body hash mismatch: {:?}", mismatch);
+                report.mismatches.push(mismatch);
+            }
+        }
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+
+    if report.missing_bodies > 0 {
+        warn!("{} blocks have no body.", report.missing_bodies);
+    }
+    info!(
+        "Verified {} legacy and {} merkle bodies of {} blocks, {} don't match their header.",
+        report.legacy_bodies,
+        report.merkle_bodies,
+        report.blocks,
+        report.mismatches.len()
+    );
+    Ok(report)
+}
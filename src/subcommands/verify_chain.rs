@@ -0,0 +1,147 @@
+#[cfg(test)]
+mod tests;
+mod verify;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    chainspec::{self, Error as ChainspecError},
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "verify-chain";
+const CHAINSPEC: &str = "chainspec";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+
+/// Errors encountered when verifying the block headers of a storage
+/// database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Error reading the chainspec.
+    #[error(transparent)]
+    Chainspec(#[from] ChainspecError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+    /// Some block headers failed verification. The report lists them.
+    #[error("Found {0} violations in block headers")]
+    Violations(usize),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Chainspec,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Checks that every block header in a storage database is the \
+            child of the block one height below it, and follows it in era, \
+            time and protocol version. With a chainspec, also checks the \
+            era lengths, the time between blocks and the protocol version \
+            activation point. Outputs the violations in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            Arg::new(CHAINSPEC)
+                .display_order(DisplayOrder::Chainspec as usize)
+                .short('c')
+                .long(CHAINSPEC)
+                .takes_value(true)
+                .value_name("CHAINSPEC_PATH")
+                .help(
+                    "Path of the chainspec of the network, or of the directory \
+                    with its `chainspec.toml` file. Only the blocks of its \
+                    protocol version are checked against its era and block \
+                    time parameters.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let maybe_chainspec = matches
+        .value_of(CHAINSPEC)
+        .map(chainspec::read_chainspec)
+        .transpose()?;
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = verify::verify_chain(path, maybe_chainspec.as_ref())?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+
+    if !report.violations.is_empty() {
+        return Err(Error::Violations(report.violations.len()));
+    }
+    Ok(())
+}
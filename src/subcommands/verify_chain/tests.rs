@@ -0,0 +1,231 @@
+use casper_node::types::BlockHash;
+use casper_types::{EraId, ProtocolVersion, TimeDiff, Timestamp};
+use lmdb::{Transaction, WriteFlags};
+
+use super::verify::{verify_chain, InvalidBlock, Violation};
+use crate::{
+    common::{
+        chainspec::ActivationPoint,
+        db::{BlockHeaderDatabase, Database, STORAGE_FILE_NAME},
+    },
+    test_utils::{self, LmdbTestFixture},
+};
+
+const V1_1_0: ProtocolVersion = ProtocolVersion::from_parts(1, 1, 0);
+
+/// The fields of a mock block header which the verification looks at.
+struct MockBlock {
+    idx: u8,
+    height: u64,
+    parent_hash: BlockHash,
+    era_id: u64,
+    switch_block: bool,
+    timestamp_millis: u64,
+    protocol_version: ProtocolVersion,
+}
+
+impl MockBlock {
+    /// Returns the block following `parent` in the same era, 400 seconds
+    /// later.
+    fn child(parent: &MockBlock, idx: u8) -> Self {
+        MockBlock {
+            idx,
+            height: parent.height + 1,
+            parent_hash: parent.hash(),
+            era_id: parent.era_id + parent.switch_block as u64,
+            switch_block: false,
+            timestamp_millis: parent.timestamp_millis + 400_000,
+            protocol_version: parent.protocol_version,
+        }
+    }
+
+    fn hash(&self) -> BlockHash {
+        if self.switch_block {
+            test_utils::mock_switch_block_header(self.idx).0
+        } else {
+            test_utils::mock_block_header(self.idx).0
+        }
+    }
+
+    fn put(&self, fixture: &LmdbTestFixture) {
+        let raw_header = if self.switch_block {
+            let (_, mut header) = test_utils::mock_switch_block_header(self.idx);
+            header.height = self.height;
+            header.parent_hash = self.parent_hash;
+            header.era_id = EraId::new(self.era_id);
+            header.timestamp = Timestamp::from(self.timestamp_millis);
+            header.protocol_version = self.protocol_version;
+            bincode::serialize(&header).unwrap()
+        } else {
+            let (_, mut header) = test_utils::mock_block_header(self.idx);
+            header.height = self.height;
+            header.parent_hash = self.parent_hash;
+            header.era_id = EraId::new(self.era_id);
+            header.timestamp = Timestamp::from(self.timestamp_millis);
+            header.protocol_version = self.protocol_version;
+            bincode::serialize(&header).unwrap()
+        };
+        let mut txn = fixture.env.begin_rw_txn().unwrap();
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &self.hash(),
+            &raw_header,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        txn.commit().unwrap();
+    }
+}
+
+fn genesis_block(protocol_version: ProtocolVersion) -> MockBlock {
+    MockBlock {
+        idx: 0,
+        height: 0,
+        parent_hash: BlockHash::default(),
+        era_id: 0,
+        switch_block: true,
+        timestamp_millis: 0,
+        protocol_version,
+    }
+}
+
+fn invalid_block(block: &MockBlock, violation: Violation) -> InvalidBlock {
+    InvalidBlock {
+        block_hash: block.hash(),
+        height: block.height,
+        violation,
+    }
+}
+
+#[test]
+fn verify_chain_should_check_parents() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    // Blocks 0 to 5 and 7, block 2 having an unknown parent, block 3 being
+    // in the wrong era and block 4 older than block 3. Block 4 has a
+    // sibling, and block 5 is the child of the first one.
+    let mut blocks = vec![genesis_block(ProtocolVersion::V1_0_0)];
+    for idx in 1..6 {
+        let mut block = MockBlock::child(&blocks[idx as usize - 1], idx);
+        match idx {
+            2 => block.parent_hash = test_utils::mock_block_header(42).0,
+            3 => block.era_id = 2,
+            4 => block.timestamp_millis = 1,
+            _ => (),
+        }
+        blocks.push(block);
+    }
+    let sibling = MockBlock::child(&blocks[3], 100);
+    let mut block_7 = MockBlock::child(&blocks[5], 7);
+    block_7.height = 7;
+    block_7.parent_hash = test_utils::mock_block_header(6).0;
+    for block in blocks.iter().chain([&sibling, &block_7]) {
+        block.put(&fixture);
+    }
+
+    let report = verify_chain(fixture.tmp_dir.path(), None).unwrap();
+    assert_eq!(report.blocks, 8);
+    assert!(!report.chainspec);
+    assert_eq!(
+        report.violations,
+        vec![
+            invalid_block(
+                &blocks[2],
+                Violation::MissingParent {
+                    parent_hash: test_utils::mock_block_header(42).0
+                }
+            ),
+            invalid_block(
+                &blocks[3],
+                Violation::EraMismatch {
+                    era_id: EraId::new(2),
+                    expected: EraId::new(1)
+                }
+            ),
+            invalid_block(
+                &blocks[4],
+                Violation::TimestampDecrease {
+                    timestamp: Timestamp::from(1),
+                    parent_timestamp: Timestamp::from(1_200_000)
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn verify_chain_should_check_chainspec_parameters() {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let mut chainspec = test_utils::mock_chainspec();
+    chainspec.protocol.version = V1_1_0;
+    chainspec.protocol.activation_point = ActivationPoint::EraId(EraId::new(1));
+    // Era 1 has 10 blocks over 4000 seconds, block 3 following block 2 by a
+    // second. Era 2 has 4 blocks, block 12 having the old protocol version,
+    // as has the genesis block the new one.
+    let mut blocks = vec![genesis_block(V1_1_0)];
+    for idx in 1..15 {
+        let mut block = MockBlock::child(&blocks[idx as usize - 1], idx);
+        match idx {
+            3 => block.timestamp_millis = blocks[2].timestamp_millis + 1_000,
+            4 => block.timestamp_millis = blocks[2].timestamp_millis + 800_000,
+            10 | 14 => block.switch_block = true,
+            12 => block.protocol_version = ProtocolVersion::V1_0_0,
+            13 => block.protocol_version = V1_1_0,
+            _ => (),
+        }
+        blocks.push(block);
+    }
+    for block in &blocks {
+        block.put(&fixture);
+    }
+
+    let report = verify_chain(fixture.tmp_dir.path(), Some(&chainspec)).unwrap();
+    assert_eq!(report.blocks, 15);
+    assert!(report.chainspec);
+    let activation_era_id = EraId::new(1);
+    assert_eq!(
+        report.violations,
+        vec![
+            invalid_block(
+                &blocks[0],
+                Violation::UpgradedEarly {
+                    protocol_version: V1_1_0,
+                    activation_era_id
+                }
+            ),
+            invalid_block(
+                &blocks[3],
+                Violation::BlockTooFast {
+                    block_time: TimeDiff::from_millis(1_000),
+                    minimum_block_time: TimeDiff::from_millis(4_096)
+                }
+            ),
+            invalid_block(
+                &blocks[12],
+                Violation::ProtocolVersionDecrease {
+                    protocol_version: ProtocolVersion::V1_0_0,
+                    parent_protocol_version: V1_1_0
+                }
+            ),
+            invalid_block(
+                &blocks[12],
+                Violation::NotUpgraded {
+                    protocol_version: ProtocolVersion::V1_0_0,
+                    activation_era_id
+                }
+            ),
+            invalid_block(
+                &blocks[14],
+                Violation::EraTooShort {
+                    duration: TimeDiff::from_millis(1_600_000),
+                    blocks: 4
+                }
+            ),
+        ]
+    );
+}
@@ -0,0 +1,251 @@
+use std::{collections::HashMap, mem, path::Path, result::Result};
+
+use casper_node::types::{BlockHash, BlockHeader};
+use casper_types::{EraId, ProtocolVersion, TimeDiff, Timestamp};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    block_iter, chainspec::Chainspec, db::StorageEnv, progress::ProgressTracker,
+    storage::LmdbReader,
+};
+
+use super::Error;
+
+/// The reason a block header failed validation.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub(crate) enum Violation {
+    /// None of the blocks one height below is the parent of the block.
+    MissingParent { parent_hash: BlockHash },
+    /// The era of the block doesn't follow the one of its parent.
+    EraMismatch { era_id: EraId, expected: EraId },
+    /// The block is older than its parent.
+    TimestampDecrease {
+        timestamp: Timestamp,
+        parent_timestamp: Timestamp,
+    },
+    /// The protocol version of the block is lower than the one of its parent.
+    ProtocolVersionDecrease {
+        protocol_version: ProtocolVersion,
+        parent_protocol_version: ProtocolVersion,
+    },
+    /// The block follows its parent by less than the shortest round length
+    /// of the chainspec.
+    BlockTooFast {
+        block_time: TimeDiff,
+        minimum_block_time: TimeDiff,
+    },
+    /// The switch block ends an era shorter than the era duration or with
+    /// fewer blocks than the minimum era height of the chainspec.
+    EraTooShort { duration: TimeDiff, blocks: u64 },
+    /// The block is at or after the activation point of the chainspec but
+    /// has an older protocol version.
+    NotUpgraded {
+        protocol_version: ProtocolVersion,
+        activation_era_id: EraId,
+    },
+    /// The block is before the activation point of the chainspec but
+    /// already has its protocol version or a newer one.
+    UpgradedEarly {
+        protocol_version: ProtocolVersion,
+        activation_era_id: EraId,
+    },
+}
+
+/// A block header which failed validation.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct InvalidBlock {
+    pub(crate) block_hash: BlockHash,
+    pub(crate) height: u64,
+    pub(crate) violation: Violation,
+}
+
+/// Counts of the blocks validated, and the violations found.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct ChainReport {
+    pub(crate) blocks: usize,
+    /// Whether the headers were also checked against a chainspec.
+    pub(crate) chainspec: bool,
+    pub(crate) violations: Vec<InvalidBlock>,
+}
+
+/// Checks `header` against its parent.
+fn check_parent(header: &BlockHeader, parent: &BlockHeader, violations: &mut Vec<Violation>) {
+    let expected_era_id = if parent.is_switch_block() {
+        parent.era_id().successor()
+    } else {
+        parent.era_id()
+    };
+    if header.era_id() != expected_era_id {
+        violations.push(Violation::EraMismatch {
+            era_id: header.era_id(),
+            expected: expected_era_id,
+        });
+    }
+    if header.timestamp() < parent.timestamp() {
+        violations.push(Violation::TimestampDecrease {
+            timestamp: header.timestamp(),
+            parent_timestamp: parent.timestamp(),
+        });
+    }
+    if header.protocol_version() < parent.protocol_version() {
+        violations.push(Violation::ProtocolVersionDecrease {
+            protocol_version: header.protocol_version(),
+            parent_protocol_version: parent.protocol_version(),
+        });
+    }
+}
+
+/// Checks `header` against the parameters of `chainspec`. Only the blocks
+/// of the protocol version of the chainspec are checked against its era and
+/// block time parameters, as earlier versions may have had others.
+fn check_chain_parameters(
+    header: &BlockHeader,
+    maybe_parent: Option<&BlockHeader>,
+    maybe_previous_switch_block: Option<&BlockHeader>,
+    chainspec: &Chainspec,
+    violations: &mut Vec<Violation>,
+) {
+    let activation_era_id = chainspec.protocol.activation_point.era_id();
+    let protocol_version = header.protocol_version();
+    if header.era_id() >= activation_era_id && protocol_version < chainspec.protocol.version {
+        violations.push(Violation::NotUpgraded {
+            protocol_version,
+            activation_era_id,
+        });
+    } else if header.era_id() < activation_era_id && protocol_version >= chainspec.protocol.version
+    {
+        violations.push(Violation::UpgradedEarly {
+            protocol_version,
+            activation_era_id,
+        });
+    }
+    if protocol_version != chainspec.protocol.version {
+        return;
+    }
+
+    if let Some(parent) = maybe_parent {
+        let minimum_block_time = chainspec.highway.minimum_block_time();
+        let block_time = header.timestamp().saturating_diff(parent.timestamp());
+        if block_time < minimum_block_time {
+            violations.push(Violation::BlockTooFast {
+                block_time,
+                minimum_block_time,
+            });
+        }
+    }
+    // An era starts at the timestamp of the switch block ending the previous
+    // one, and ends with the first block reaching both the era duration and
+    // the minimum era height.
+    if let Some(previous_switch_block) = maybe_previous_switch_block {
+        if header.is_switch_block() && previous_switch_block.era_id().successor() == header.era_id()
+        {
+            let duration = header
+                .timestamp()
+                .saturating_diff(previous_switch_block.timestamp());
+            let blocks = header.height() - previous_switch_block.height();
+            if duration < chainspec.core.era_duration || blocks < chainspec.core.minimum_era_height
+            {
+                violations.push(Violation::EraTooShort { duration, blocks });
+            }
+        }
+    }
+}
+
+/// Checks that every block header of the storage database at `db_path` is
+/// the child of a block one height below, when there is any, and follows it
+/// in era, time and protocol version. If `maybe_chainspec` is set, the
+/// headers are also checked against its era lengths, minimum block time and
+/// protocol version activation point.
+pub(crate) fn verify_chain<P: AsRef<Path>>(
+    db_path: P,
+    maybe_chainspec: Option<&Chainspec>,
+) -> Result<ChainReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+    let blocks = block_iter::blocks_by_height(&reader)?;
+
+    let mut report = ChainReport {
+        blocks: blocks.len(),
+        chainspec: maybe_chainspec.is_some(),
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Verification {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    // The headers of the blocks at the previous and the current height,
+    // several if the database holds orphaned blocks.
+    let mut previous_height = None;
+    let mut current_height = None;
+    let mut previous_headers: HashMap<BlockHash, BlockHeader> = HashMap::new();
+    let mut current_headers = HashMap::new();
+    let mut maybe_previous_switch_block: Option<BlockHeader> = None;
+    for (height, block_hash) in blocks {
+        if current_height != Some(height) {
+            previous_height = current_height;
+            previous_headers = mem::take(&mut current_headers);
+            current_height = Some(height);
+        }
+        let header = block_iter::read_header(&reader, &block_hash)?;
+        let mut violations = vec![];
+        let maybe_parent = if height > 0 && previous_height == Some(height - 1) {
+            match previous_headers.get(header.parent_hash()) {
+                Some(parent) => Some(parent),
+                None => {
+                    violations.push(Violation::MissingParent {
+                        parent_hash: *header.parent_hash(),
+                    });
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        if let Some(parent) = maybe_parent {
+            check_parent(&header, parent, &mut violations);
+        }
+        if let Some(chainspec) = maybe_chainspec {
+            check_chain_parameters(
+                &header,
+                maybe_parent,
+                maybe_previous_switch_block.as_ref(),
+                chainspec,
+                &mut violations,
+            );
+        }
+        for violation in violations {
+            warn!("Block {} is invalid: {:?}", block_hash, violation);
+            report.violations.push(InvalidBlock {
+                block_hash,
+                height,
+                violation,
+            });
+        }
+        if header.is_switch_block() {
+            maybe_previous_switch_block = Some(header.clone());
+        }
+        current_headers.insert(block_hash, header);
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+
+    info!(
+        "Verified {} block headers, found {} violations.",
+        report.blocks,
+        report.violations.len()
+    );
+    Ok(report)
+}
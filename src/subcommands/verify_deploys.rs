@@ -0,0 +1,163 @@
+#[cfg(test)]
+mod tests;
+mod verify;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use bincode::Error as BincodeError;
+use casper_node::types::DeployHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "verify-deploys";
+const DB_PATH: &str = "db-path";
+const FROM_HEIGHT: &str = "from-height";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const TO_HEIGHT: &str = "to-height";
+
+/// Errors encountered when verifying the deploys of a storage database.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Some deploys failed verification. The report lists them.
+    #[error("Found {0} invalid deploys")]
+    InvalidDeploys(usize),
+    /// The height range is empty.
+    #[error("Invalid height range: {0} is above {1}")]
+    InvalidRange(u64, u64),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Parsing error on a deploy or its finalized approvals.
+    #[error("Error parsing deploy {0}: {1}")]
+    Parsing(DeployHash, BincodeError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    FromHeight,
+    ToHeight,
+    Output,
+    Overwrite,
+    Compress,
+}
+
+fn height_arg(name: &'static str, short: char, display_order: DisplayOrder) -> Arg<'static> {
+    Arg::new(name)
+        .display_order(display_order as usize)
+        .required(false)
+        .short(short)
+        .long(name)
+        .takes_value(true)
+        .value_name("HEIGHT")
+        .validator(|value| value.parse::<u64>().map(|_| ()))
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Checks that the deploys of the blocks in a storage database are \
+            stored under their hash, that their hashes match their contents \
+            and that every approval, including the finalized ones, is a valid \
+            signature of its signer, and outputs the invalid deploys in JSON \
+            format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help("Path of the directory with the `storage.lmdb` file."),
+        )
+        .arg(
+            height_arg(FROM_HEIGHT, 'f', DisplayOrder::FromHeight)
+                .help("Height of the lowest block to verify. Defaults to the genesis block."),
+        )
+        .arg(
+            height_arg(TO_HEIGHT, 't', DisplayOrder::ToHeight)
+                .help("Height of the highest block to verify. Defaults to the highest block."),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let path = Path::new(matches.value_of(DB_PATH).expect("should have db-path arg"));
+    let height = |name| {
+        matches
+            .value_of(name)
+            .map(|value| value.parse::<u64>().expect("should be validated"))
+    };
+    let from = height(FROM_HEIGHT).unwrap_or_default();
+    let to = height(TO_HEIGHT).unwrap_or(u64::MAX);
+    if from > to {
+        return Err(Error::InvalidRange(from, to));
+    }
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = verify::verify_deploys(path, from..=to)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+
+    if !report.invalid.is_empty() {
+        return Err(Error::InvalidDeploys(report.invalid.len()));
+    }
+    Ok(())
+}
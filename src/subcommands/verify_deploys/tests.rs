@@ -0,0 +1,166 @@
+use casper_node::types::{BlockHash, Deploy, DeployHash, FinalizedApprovals};
+use casper_types::Timestamp;
+use lmdb::{Transaction, WriteFlags};
+
+use super::verify::{verify_deploys, Failure};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, Database, DeployDatabase,
+        FinalizedApprovalsDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{self, LmdbTestFixture},
+};
+
+fn new_fixture() -> LmdbTestFixture {
+    LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            FinalizedApprovalsDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    )
+}
+
+fn mock_deploy(idx: u64) -> Deploy {
+    test_utils::mock_deploy(Timestamp::from(idx), "1h".parse().unwrap())
+}
+
+/// Stores the block at `height` with a body executing the deploy stored
+/// under `deploy_hash` with the given value, if any.
+fn put_block(
+    fixture: &LmdbTestFixture,
+    height: u8,
+    deploy_hash: DeployHash,
+    raw_deploy: Option<&[u8]>,
+) -> BlockHash {
+    let (block_hash, mut header) = test_utils::mock_block_header(height);
+    header.height = height as u64;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+        &block_hash,
+        &bincode::serialize(&header).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.put(
+        *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap(),
+        &header.body_hash,
+        &bincode::serialize(&BlockBody::new(vec![deploy_hash])).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    if let Some(raw_deploy) = raw_deploy {
+        txn.put(
+            *fixture.db(Some(DeployDatabase::db_name())).unwrap(),
+            &deploy_hash,
+            &raw_deploy,
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+    block_hash
+}
+
+#[test]
+fn verify_deploys_should_report_invalid_deploys() {
+    let fixture = new_fixture();
+    let deploys: Vec<Deploy> = (0..5).map(mock_deploy).collect();
+    let raw_deploys: Vec<Vec<u8>> = deploys
+        .iter()
+        .map(|deploy| bincode::serialize(deploy).unwrap())
+        .collect();
+
+    // Block 0 has a valid deploy, block 1 one stored under a key other
+    // than its hash.
+    put_block(&fixture, 0, *deploys[0].id(), Some(&raw_deploys[0]));
+    let wrong_key_block_hash = put_block(
+        &fixture,
+        1,
+        test_utils::mock_deploy_hash(9),
+        Some(&raw_deploys[1]),
+    );
+
+    // Block 2 has a deploy carrying the approval of another deploy, which
+    // is encoded last and has the same size.
+    let approvals_len = bincode::serialized_size(deploys[3].approvals()).unwrap() as usize;
+    let mut forged_deploy = raw_deploys[2].clone();
+    let split = forged_deploy.len() - approvals_len;
+    forged_deploy[split..].copy_from_slice(&raw_deploys[3][split..]);
+    let forged_block_hash = put_block(&fixture, 2, *deploys[2].id(), Some(&forged_deploy));
+
+    // Block 3 has a valid deploy with the finalized approvals of another.
+    let finalized_block_hash = put_block(&fixture, 3, *deploys[4].id(), Some(&raw_deploys[4]));
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    txn.put(
+        *fixture
+            .db(Some(FinalizedApprovalsDatabase::db_name()))
+            .unwrap(),
+        deploys[4].id(),
+        &bincode::serialize(&FinalizedApprovals::new(deploys[0].approvals().clone())).unwrap(),
+        WriteFlags::empty(),
+    )
+    .unwrap();
+    txn.commit().unwrap();
+
+    let report = verify_deploys(fixture.tmp_dir.path(), 0..=u64::MAX).unwrap();
+    assert_eq!(report.blocks, 4);
+    assert_eq!(report.deploys, 4);
+    assert_eq!(report.missing_deploys, 0);
+    assert_eq!(report.invalid.len(), 3);
+
+    assert_eq!(report.invalid[0].height, 1);
+    assert_eq!(report.invalid[0].block_hash, wrong_key_block_hash);
+    assert_eq!(
+        report.invalid[0].deploy_hash,
+        test_utils::mock_deploy_hash(9)
+    );
+    assert_eq!(
+        report.invalid[0].failure,
+        Failure::NotStoredUnderHash {
+            stored_hash: *deploys[1].id()
+        }
+    );
+    assert_eq!(report.invalid[1].block_hash, forged_block_hash);
+    assert!(matches!(report.invalid[1].failure, Failure::Invalid { .. }));
+    assert_eq!(report.invalid[2].block_hash, finalized_block_hash);
+    assert!(matches!(
+        &report.invalid[2].failure,
+        Failure::InvalidFinalizedApproval { signer, .. }
+            if deploys[0].approvals().iter().any(|approval| approval.signer() == signer)
+    ));
+}
+
+#[test]
+fn verify_deploys_should_only_check_height_range() {
+    let fixture = new_fixture();
+    // The deploy of block 0 is forged but outside the range, the one of
+    // block 2 is missing.
+    let deploys: Vec<Deploy> = (0..3).map(mock_deploy).collect();
+    put_block(
+        &fixture,
+        0,
+        test_utils::mock_deploy_hash(9),
+        Some(&bincode::serialize(&deploys[0]).unwrap()),
+    );
+    put_block(
+        &fixture,
+        1,
+        *deploys[1].id(),
+        Some(&bincode::serialize(&deploys[1]).unwrap()),
+    );
+    put_block(&fixture, 2, *deploys[2].id(), None);
+
+    let report = verify_deploys(fixture.tmp_dir.path(), 1..=2).unwrap();
+    assert_eq!(report.blocks, 2);
+    assert_eq!(report.deploys, 1);
+    assert_eq!(report.missing_deploys, 1);
+    assert!(report.invalid.is_empty());
+
+    let report = verify_deploys(fixture.tmp_dir.path(), 0..=0).unwrap();
+    assert_eq!(report.invalid.len(), 1);
+}
@@ -0,0 +1,171 @@
+use std::{ops::RangeInclusive, path::Path, result::Result};
+
+use casper_node::types::{BlockHash, Deploy, DeployHash, FinalizedApprovals};
+use casper_types::{crypto, PublicKey};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    block_iter,
+    db::{BlockHeaderDatabase, Database, DeployDatabase, FinalizedApprovalsDatabase, StorageEnv},
+    progress::ProgressTracker,
+    storage::{LmdbReader, StorageReader},
+};
+
+use super::Error;
+
+/// The reason a deploy failed verification.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub(crate) enum Failure {
+    /// The deploy is stored under a key other than its hash.
+    NotStoredUnderHash { stored_hash: DeployHash },
+    /// The deploy hash, body hash or an approval of the deploy is invalid.
+    Invalid { reason: String },
+    /// A finalized approval of the deploy doesn't verify against its signer.
+    InvalidFinalizedApproval { signer: PublicKey, reason: String },
+}
+
+/// A deploy which failed verification, along with the block including it.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct InvalidDeploy {
+    pub(crate) deploy_hash: DeployHash,
+    pub(crate) block_hash: BlockHash,
+    pub(crate) height: u64,
+    pub(crate) failure: Failure,
+}
+
+/// Counts of the blocks and deploys verified, and the deploys which failed.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct DeployReport {
+    pub(crate) blocks: usize,
+    pub(crate) deploys: usize,
+    pub(crate) missing_deploys: usize,
+    pub(crate) invalid: Vec<InvalidDeploy>,
+}
+
+/// Verifies the deploy stored under `deploy_hash`, returning the reason it
+/// failed, or `None` if it's valid or missing from the database.
+fn verify_deploy<R: StorageReader>(
+    reader: &R,
+    deploy_hash: &DeployHash,
+    report: &mut DeployReport,
+) -> Result<Option<Failure>, Error> {
+    let mut deploy: Deploy = match reader.get(DeployDatabase::db_name(), deploy_hash.as_ref())? {
+        Some(raw_deploy) => bincode::deserialize(&raw_deploy)
+            .map_err(|bincode_err| Error::Parsing(*deploy_hash, bincode_err))?,
+        None => {
+            report.missing_deploys += 1;
+            return Ok(None);
+        }
+    };
+    report.deploys += 1;
+    if deploy.id() != deploy_hash {
+        return Ok(Some(Failure::NotStoredUnderHash {
+            stored_hash: *deploy.id(),
+        }));
+    }
+    if let Err(failure) = deploy.is_valid() {
+        return Ok(Some(Failure::Invalid {
+            reason: failure.to_string(),
+        }));
+    }
+
+    // Approvals finalized in a block may differ from the ones the deploy
+    // was first received with, so they are checked separately.
+    if !reader.has_table(FinalizedApprovalsDatabase::db_name())? {
+        return Ok(None);
+    }
+    if let Some(raw_approvals) =
+        reader.get(FinalizedApprovalsDatabase::db_name(), deploy_hash.as_ref())?
+    {
+        let approvals: FinalizedApprovals = bincode::deserialize(&raw_approvals)
+            .map_err(|bincode_err| Error::Parsing(*deploy_hash, bincode_err))?;
+        for approval in approvals.as_ref() {
+            if let Err(crypto_err) =
+                crypto::verify(deploy_hash, approval.signature(), approval.signer())
+            {
+                return Ok(Some(Failure::InvalidFinalizedApproval {
+                    signer: approval.signer().clone(),
+                    reason: crypto_err.to_string(),
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Checks that every deploy of the blocks within `heights` in the storage
+/// database at `db_path` is stored under its hash, that its hashes match its
+/// contents and that all its approvals, including the finalized ones, are
+/// valid signatures of its hash.
+pub(crate) fn verify_deploys<P: AsRef<Path>>(
+    db_path: P,
+    heights: RangeInclusive<u64>,
+) -> Result<DeployReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+    let blocks: Vec<_> = if reader.has_table(BlockHeaderDatabase::db_name())? {
+        block_iter::blocks_by_height(&reader)?
+            .into_iter()
+            .filter(|(height, _)| heights.contains(height))
+            .collect()
+    } else {
+        vec![]
+    };
+
+    let mut report = DeployReport {
+        blocks: blocks.len(),
+        ..Default::default()
+    };
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        blocks.len(),
+        Box::new(|completion| info!("Verification {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    for (height, block_hash) in blocks {
+        let header = block_iter::read_header(&reader, &block_hash)?;
+        match block_iter::read_body(&reader, &block_hash, &header)? {
+            Some(body) => {
+                for deploy_hash in body.deploy_hashes.iter().chain(body.transfer_hashes.iter()) {
+                    if let Some(failure) = verify_deploy(&reader, deploy_hash, &mut report)? {
+                        warn!("Deploy {} is invalid: {:?}", deploy_hash, failure);
+                        report.invalid.push(InvalidDeploy {
+                            deploy_hash: *deploy_hash,
+                            block_hash,
+                            height,
+                            failure,
+                        });
+                    }
+                }
+            }
+            None => warn!("Block {} has no body, skipping it.", block_hash),
+        }
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+
+    if report.missing_deploys > 0 {
+        warn!(
+            "{} deploys are missing from the database.",
+            report.missing_deploys
+        );
+    }
+    info!(
+        "Verified {} deploys of {} blocks, {} are invalid.",
+        report.deploys,
+        report.blocks,
+        report.invalid.len()
+    );
+    Ok(report)
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    path::Path,
+    result::Result,
+};
+
+use casper_node::types::{BlockHash, BlockHeader, DeployHash};
+use casper_types::EraId;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::{
+    common::{
+        db::{BlockHeaderDatabase, Database, StorageEnv},
+        storage::{LmdbReader, StorageReader},
+    },
+    subcommands::latest_block_summary::completeness,
+};
+
+use super::Error;
+
+/// An entry which would make the node fail to build its indexes.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub(crate) enum Conflict {
+    /// A block header isn't stored under its own hash.
+    NotStoredUnderHash { key: String, block_hash: BlockHash },
+    /// Two blocks have the same height.
+    DuplicateHeight {
+        height: u64,
+        first: BlockHash,
+        second: BlockHash,
+    },
+    /// Two switch blocks end the same era.
+    DuplicateSwitchBlock {
+        era_id: EraId,
+        first: BlockHash,
+        second: BlockHash,
+    },
+    /// A deploy is included in two blocks.
+    DuplicateDeploy {
+        deploy_hash: DeployHash,
+        first: BlockHash,
+        second: BlockHash,
+    },
+    /// The body of a block is missing, so its deploys can't be indexed.
+    MissingBody { block_hash: BlockHash },
+}
+
+/// Sizes of the indexes and the conflicts found while building them.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct IndexReport {
+    pub(crate) heights: usize,
+    pub(crate) switch_blocks: usize,
+    pub(crate) deploys: usize,
+    pub(crate) conflicts: Vec<Conflict>,
+}
+
+/// Adds `value` under `key` to `index`, returning the value already there
+/// if it differs.
+fn insert<K: Ord>(
+    index: &mut BTreeMap<K, BlockHash>,
+    key: K,
+    value: BlockHash,
+) -> Option<BlockHash> {
+    match index.entry(key) {
+        Entry::Vacant(entry) => {
+            entry.insert(value);
+            None
+        }
+        Entry::Occupied(entry) if *entry.get() != value => Some(*entry.get()),
+        Entry::Occupied(_) => None,
+    }
+}
+
+/// Builds the indexes the node keeps in memory from the block headers and
+/// bodies in the storage database at `db_path`, the way the node does at
+/// startup, but collecting every conflict instead of stopping at the first.
+pub(crate) fn verify_indexes<P: AsRef<Path>>(db_path: P) -> Result<IndexReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+
+    let mut height_index = BTreeMap::new();
+    let mut switch_block_index = BTreeMap::new();
+    let mut deploy_index = BTreeMap::new();
+    let mut conflicts = vec![];
+    let mut idx = 0;
+    reader.scan(
+        BlockHeaderDatabase::db_name(),
+        |raw_key, raw_val| -> Result<(), Error> {
+            let header: BlockHeader = bincode::deserialize(raw_val)
+                .map_err(|bincode_err| Error::Parsing(idx, bincode_err))?;
+            idx += 1;
+            let block_hash = header.hash();
+            if raw_key != block_hash.as_ref() {
+                conflicts.push(Conflict::NotStoredUnderHash {
+                    key: raw_key.iter().map(|byte| format!("{:02x}", byte)).collect(),
+                    block_hash,
+                });
+            }
+            if let Some(first) = insert(&mut height_index, header.height(), block_hash) {
+                conflicts.push(Conflict::DuplicateHeight {
+                    height: header.height(),
+                    first,
+                    second: block_hash,
+                });
+            }
+            if header.is_switch_block() {
+                if let Some(first) = insert(&mut switch_block_index, header.era_id(), block_hash) {
+                    conflicts.push(Conflict::DuplicateSwitchBlock {
+                        era_id: header.era_id(),
+                        first,
+                        second: block_hash,
+                    });
+                }
+            }
+            let deploy_hashes =
+                match completeness::read_body_deploys(&reader, &block_hash, &header)? {
+                    Some(deploy_hashes) => deploy_hashes,
+                    None => {
+                        conflicts.push(Conflict::MissingBody { block_hash });
+                        return Ok(());
+                    }
+                };
+            for deploy_hash in deploy_hashes {
+                if let Some(first) = insert(&mut deploy_index, deploy_hash, block_hash) {
+                    conflicts.push(Conflict::DuplicateDeploy {
+                        deploy_hash,
+                        first,
+                        second: block_hash,
+                    });
+                }
+            }
+            Ok(())
+        },
+    )?;
+    for conflict in conflicts.iter() {
+        warn!("Index conflict: {:?}", conflict);
+    }
+    info!(
+        "Indexed {} heights, {} switch blocks and {} deploys.",
+        height_index.len(),
+        switch_block_index.len(),
+        deploy_index.len()
+    );
+    Ok(IndexReport {
+        heights: height_index.len(),
+        switch_blocks: switch_block_index.len(),
+        deploys: deploy_index.len(),
+        conflicts,
+    })
+}
@@ -0,0 +1,105 @@
+use casper_node::types::{BlockHash, BlockHeader};
+use lmdb::{Transaction, WriteFlags};
+
+use super::indexes::{verify_indexes, Conflict};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database, DeployDatabase,
+        DeployMetadataDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{mock_block_header, mock_deploy_hash, LmdbTestFixture},
+};
+
+#[test]
+fn verify_indexes_should_report_conflicts() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+
+    // Blocks 0 and 1 include the same deploy, blocks 1 and 2 have the same
+    // height, block 2 has no body and block 3 isn't stored under its hash.
+    let headers: Vec<BlockHeader> = [0u64, 1, 1, 2]
+        .iter()
+        .enumerate()
+        .map(|(idx, height)| {
+            let (_, mut mock_header) = mock_block_header(idx as u8);
+            mock_header.height = *height;
+            bincode::deserialize(&bincode::serialize(&mock_header).unwrap()).unwrap()
+        })
+        .collect();
+    let hashes: Vec<BlockHash> = headers.iter().map(BlockHeader::hash).collect();
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, header) in headers.iter().enumerate() {
+        let key = if idx == 3 {
+            BlockHash::new([3u8; 32].into())
+        } else {
+            hashes[idx]
+        };
+        txn.put(
+            header_db,
+            &key,
+            &bincode::serialize(header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        if idx == 2 {
+            continue;
+        }
+        let deploy_idx = if idx == 1 { 0 } else { idx as u8 };
+        txn.put(
+            body_db,
+            header.body_hash(),
+            &bincode::serialize(&BlockBody::new(vec![mock_deploy_hash(deploy_idx)])).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let report = verify_indexes(fixture.tmp_dir.path()).unwrap();
+    assert_eq!(report.heights, 3);
+    assert_eq!(report.switch_blocks, 0);
+    assert_eq!(report.deploys, 2);
+    let mut first_of_height = [hashes[1], hashes[2]];
+    first_of_height.sort();
+    let mut first_with_deploy = [hashes[0], hashes[1]];
+    first_with_deploy.sort();
+    let expected_conflicts = [
+        Conflict::NotStoredUnderHash {
+            key: "03".repeat(32),
+            block_hash: hashes[3],
+        },
+        Conflict::DuplicateHeight {
+            height: 1,
+            first: first_of_height[0],
+            second: first_of_height[1],
+        },
+        Conflict::MissingBody {
+            block_hash: hashes[2],
+        },
+        Conflict::DuplicateDeploy {
+            deploy_hash: mock_deploy_hash(0),
+            first: first_with_deploy[0],
+            second: first_with_deploy[1],
+        },
+    ];
+    assert_eq!(report.conflicts.len(), expected_conflicts.len());
+    for conflict in expected_conflicts.iter() {
+        assert!(
+            report.conflicts.contains(conflict),
+            "{:?} not in {:?}",
+            conflict,
+            report.conflicts
+        );
+    }
+}
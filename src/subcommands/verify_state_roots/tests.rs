@@ -0,0 +1,117 @@
+use casper_hashing::Digest;
+use casper_types::bytesrepr::ToBytes;
+use lmdb::{Error as LmdbError, Transaction, WriteFlags};
+
+use super::verify::{sample, verify_state_roots, Problem};
+use crate::{
+    common::db::{BlockHeaderDatabase, Database, TrieEnv, STORAGE_FILE_NAME},
+    subcommands::trie_compact::tests::create_data,
+    test_utils::{self, LmdbTestFixture},
+};
+
+/// Creates a storage database with blocks at heights `0..block_count`, all
+/// with the root of the mock trie as their state root, next to a trie store
+/// holding the mock trie.
+fn new_fixture(block_count: u8) -> (LmdbTestFixture, Digest) {
+    let fixture = LmdbTestFixture::new(
+        vec![BlockHeaderDatabase::db_name()],
+        Some(STORAGE_FILE_NAME),
+    );
+    let data = create_data();
+    // `node_1` is the root of the mock trie.
+    let state_root_hash = data[3].0;
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for height in 0..block_count {
+        let (block_hash, mut header) = test_utils::mock_block_header(height);
+        header.height = height as u64;
+        header.state_root_hash = state_root_hash;
+        txn.put(
+            *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap(),
+            &block_hash,
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let trie_env = TrieEnv::create(fixture.tmp_dir.path(), 1 << 20).unwrap();
+    let trie_db = trie_env.db().unwrap();
+    trie_env
+        .write(|txn| -> Result<(), LmdbError> {
+            for test_data in data.iter() {
+                let raw_trie = test_data.1.to_bytes().unwrap();
+                txn.put(trie_db, &test_data.0, &raw_trie, WriteFlags::empty())?;
+            }
+            Ok(())
+        })
+        .unwrap();
+    (fixture, state_root_hash)
+}
+
+#[test]
+fn sample_should_spread_over_blocks() {
+    let blocks: Vec<u64> = (0..10).collect();
+    assert_eq!(sample(&blocks, 1), vec![9]);
+    assert_eq!(sample(&blocks, 3), vec![0, 4, 9]);
+    assert_eq!(sample(&blocks, 20), blocks);
+}
+
+#[test]
+fn verify_state_roots_should_walk_whole_trie() {
+    let (fixture, state_root_hash) = new_fixture(3);
+    let report = verify_state_roots(fixture.tmp_dir.path(), 2).unwrap();
+    assert_eq!(
+        report
+            .sampled
+            .iter()
+            .map(|sampled_root| (sampled_root.height, sampled_root.state_root_hash))
+            .collect::<Vec<_>>(),
+        vec![(0, state_root_hash), (2, state_root_hash)]
+    );
+    assert_eq!(report.nodes, 3);
+    assert_eq!(report.leaves, 3);
+    assert!(report.faults.is_empty());
+}
+
+#[test]
+fn verify_state_roots_should_report_faults() {
+    let (fixture, state_root_hash) = new_fixture(1);
+    let data = create_data();
+    let (leaf_1_hash, leaf_2_hash, leaf_3_hash) = (data[0].0, data[1].0, data[2].0);
+    // Leaf 2 is overwritten with the contents of leaf 1 and leaf 3 is
+    // deleted.
+    let trie_env = TrieEnv::open(fixture.tmp_dir.path()).unwrap();
+    let trie_db = trie_env.db().unwrap();
+    let mut txn = trie_env.begin_rw_txn().unwrap();
+    let raw_leaf_1 = txn.get(trie_db, &leaf_1_hash).unwrap().to_vec();
+    txn.put(trie_db, &leaf_2_hash, &raw_leaf_1, WriteFlags::empty())
+        .unwrap();
+    txn.del(trie_db, &leaf_3_hash, None).unwrap();
+    txn.commit().unwrap();
+
+    let report = verify_state_roots(fixture.tmp_dir.path(), 10).unwrap();
+    assert_eq!(report.sampled.len(), 1);
+    assert_eq!(report.nodes, 3);
+    assert_eq!(report.leaves, 1);
+    let mut faults: Vec<_> = report
+        .faults
+        .into_iter()
+        .map(|fault| {
+            assert_eq!(fault.state_root_hash, state_root_hash);
+            (fault.trie_key, fault.problem)
+        })
+        .collect();
+    faults.sort_by_key(|(trie_key, _)| *trie_key);
+    let mut expected = vec![
+        (
+            leaf_2_hash,
+            Problem::HashMismatch {
+                computed_hash: leaf_1_hash,
+            },
+        ),
+        (leaf_3_hash, Problem::Missing),
+    ];
+    expected.sort_by_key(|(trie_key, _)| *trie_key);
+    assert_eq!(faults, expected);
+}
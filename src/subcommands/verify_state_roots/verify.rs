@@ -0,0 +1,210 @@
+use std::{collections::HashSet, path::Path, result::Result};
+
+use casper_execution_engine::storage::trie::Trie;
+use casper_hashing::Digest;
+use casper_node::types::BlockHash;
+use casper_types::{bytesrepr, Key, StoredValue};
+use lmdb::{Database as LmdbDatabase, Transaction};
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    block_iter,
+    db::{self, BlockHeaderDatabase, Database, StorageEnv, TrieEnv, TRIE_STORE_FILE_NAME},
+    progress::ProgressTracker,
+    storage::{LmdbReader, StorageReader},
+};
+
+use super::Error;
+
+/// A block whose state root was sampled for verification.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct SampledRoot {
+    pub(crate) height: u64,
+    pub(crate) block_hash: BlockHash,
+    pub(crate) state_root_hash: Digest,
+}
+
+/// A problem found in a trie node.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case", tag = "kind")]
+pub(crate) enum Problem {
+    /// A node referenced by its parent, or the state root itself, is missing.
+    Missing,
+    /// The node doesn't hash to the key it is stored under.
+    HashMismatch { computed_hash: Digest },
+    /// The node can't be parsed, so its children can't be verified.
+    Parsing { error: String },
+}
+
+/// A problem found while walking the trie under a sampled state root.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct Fault {
+    pub(crate) state_root_hash: Digest,
+    pub(crate) trie_key: Digest,
+    pub(crate) problem: Problem,
+}
+
+/// The sampled state roots, the trie nodes verified under them and the
+/// faults found.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct StateRootReport {
+    pub(crate) sampled: Vec<SampledRoot>,
+    pub(crate) nodes: usize,
+    pub(crate) leaves: usize,
+    pub(crate) faults: Vec<Fault>,
+}
+
+/// Picks `count` blocks spread evenly over `blocks`, which are sorted by
+/// height, always including the lowest and highest ones when sampling more
+/// than one. Picks the highest block only when sampling one.
+pub(crate) fn sample<T: Copy>(blocks: &[T], count: usize) -> Vec<T> {
+    if count >= blocks.len() {
+        return blocks.to_vec();
+    }
+    match count {
+        0 => vec![],
+        1 => vec![blocks[blocks.len() - 1]],
+        _ => (0..count)
+            .map(|idx| blocks[idx * (blocks.len() - 1) / (count - 1)])
+            .collect(),
+    }
+}
+
+/// Walks the trie under `state_root_hash`, checking that every node is
+/// present and hashes to the key it is stored under. Nodes in `visited` were
+/// verified under a previous state root and are skipped along with their
+/// children.
+fn verify_trie<T: Transaction>(
+    txn: &T,
+    trie_db: LmdbDatabase,
+    state_root_hash: Digest,
+    visited: &mut HashSet<Digest>,
+    report: &mut StateRootReport,
+) -> Result<(), Error> {
+    let mut pending = vec![state_root_hash];
+    while let Some(trie_key) = pending.pop() {
+        if !visited.insert(trie_key) {
+            continue;
+        }
+        let mut fault = |problem| {
+            report.faults.push(Fault {
+                state_root_hash,
+                trie_key,
+                problem,
+            })
+        };
+        let raw_trie = match db::get_optional(txn, trie_db, &trie_key)? {
+            Some(raw_trie) => raw_trie,
+            None => {
+                fault(Problem::Missing);
+                continue;
+            }
+        };
+        let computed_hash = Digest::hash(raw_trie);
+        if computed_hash != trie_key {
+            fault(Problem::HashMismatch { computed_hash });
+            continue;
+        }
+        // A first byte of `0` indicates a leaf, which has no children, so
+        // its value isn't worth parsing.
+        if raw_trie.first() == Some(&0) {
+            report.leaves += 1;
+            continue;
+        }
+        match bytesrepr::deserialize::<Trie<Key, StoredValue>>(raw_trie.to_vec()) {
+            Ok(Trie::Leaf { .. }) => report.leaves += 1,
+            Ok(Trie::Node { pointer_block }) => {
+                report.nodes += 1;
+                pending.extend(
+                    pointer_block
+                        .as_indexed_pointers()
+                        .map(|(_index, pointer)| pointer.into_hash()),
+                );
+            }
+            Ok(Trie::Extension { pointer, .. }) => {
+                report.nodes += 1;
+                pending.push(pointer.into_hash());
+            }
+            Err(bytesrepr_err) => fault(Problem::Parsing {
+                error: bytesrepr_err.to_string(),
+            }),
+        }
+    }
+    Ok(())
+}
+
+/// Walks the tries under the state roots of `sample_count` blocks of the
+/// storage database in `db_path`, spread evenly over the chain, verifying
+/// every node in the trie store of the same directory against its hash.
+/// Nodes shared between the sampled tries are verified once, so a fault is
+/// reported under the first sampled state root reaching it.
+pub(crate) fn verify_state_roots<P: AsRef<Path>>(
+    db_path: P,
+    sample_count: usize,
+) -> Result<StateRootReport, Error> {
+    let trie_path = db_path.as_ref().join(TRIE_STORE_FILE_NAME);
+    if !trie_path.exists() {
+        return Err(Error::MissingTrieStore(trie_path));
+    }
+    let mut report = StateRootReport::default();
+    {
+        let env = StorageEnv::open(&db_path)?;
+        let txn = env.begin_ro_txn()?;
+        let reader = LmdbReader::new(&txn);
+        let blocks = if reader.has_table(BlockHeaderDatabase::db_name())? {
+            block_iter::blocks_by_height(&reader)?
+        } else {
+            vec![]
+        };
+        for (height, block_hash) in sample(&blocks, sample_count) {
+            let header = block_iter::read_header(&reader, &block_hash)?;
+            report.sampled.push(SampledRoot {
+                height,
+                block_hash,
+                state_root_hash: *header.state_root_hash(),
+            });
+        }
+    }
+
+    let trie_env = TrieEnv::open(&db_path)?;
+    let trie_db = trie_env.db()?;
+    let txn = trie_env.begin_ro_txn()?;
+    let mut visited = HashSet::new();
+    let mut maybe_progress_tracker = match ProgressTracker::new(
+        report.sampled.len(),
+        Box::new(|completion| info!("Verification {}% complete...", completion)),
+    ) {
+        Ok(progress_tracker) => Some(progress_tracker),
+        Err(progress_tracker_error) => {
+            warn!(
+                "Couldn't initialize progress tracker: {}",
+                progress_tracker_error
+            );
+            None
+        }
+    };
+    let state_root_hashes: Vec<Digest> = report
+        .sampled
+        .iter()
+        .map(|sampled_root| sampled_root.state_root_hash)
+        .collect();
+    for state_root_hash in state_root_hashes {
+        verify_trie(&txn, trie_db, state_root_hash, &mut visited, &mut report)?;
+        if let Some(progress_tracker) = maybe_progress_tracker.as_mut() {
+            progress_tracker.advance_by(1);
+        }
+    }
+
+    for fault in report.faults.iter() {
+        warn!("Trie fault: {:?}", fault);
+    }
+    info!(
+        "Verified {} nodes and {} leaves under {} state roots, found {} faults.",
+        report.nodes,
+        report.leaves,
+        report.sampled.len(),
+        report.faults.len()
+    );
+    Ok(report)
+}
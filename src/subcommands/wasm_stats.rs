@@ -0,0 +1,177 @@
+mod report;
+#[cfg(test)]
+mod tests;
+
+use std::{
+    io::{Error as IoError, Write},
+    path::Path,
+};
+
+use bincode::Error as BincodeError;
+use casper_node::types::DeployHash;
+use clap::{Arg, ArgMatches, Command};
+use lmdb::Error as LmdbError;
+use serde_json::Error as SerializationError;
+use thiserror::Error as ThisError;
+
+use super::archive::{
+    snapshot::{DbDir, Include},
+    UnpackError,
+};
+use crate::common::{
+    block_iter::Error as BlockIterError,
+    output::{self, Compression, OutputWriter},
+    storage::Error as StorageError,
+};
+
+pub const COMMAND_NAME: &str = "wasm-stats";
+const BUCKET_SIZE: &str = "bucket-size";
+const DB_PATH: &str = "db-path";
+const OUTPUT: &str = "output";
+const OVERWRITE: &str = "overwrite";
+const TOP: &str = "top";
+
+/// Errors encountered when gathering the wasm statistics.
+#[derive(Debug, ThisError)]
+pub enum Error {
+    /// Error opening the database directory or unpacking it from an archive.
+    #[error("Error opening the database: {0}")]
+    Archive(#[from] UnpackError),
+    /// Error reading a block.
+    #[error("Error reading block: {0}")]
+    Block(#[from] BlockIterError),
+    /// Database operation error.
+    #[error("Error operating the database: {0}")]
+    Database(#[from] StorageError),
+    /// Parsing error on a deploy.
+    #[error("Error parsing deploy {0}: {1}")]
+    DeployParsing(DeployHash, BincodeError),
+    /// Error writing the output.
+    #[error("Error writing output: {0}")]
+    Output(#[from] IoError),
+    /// Error serializing the output.
+    #[error("Error serializing output: {0}")]
+    Serialize(#[from] SerializationError),
+}
+
+impl From<LmdbError> for Error {
+    fn from(lmdb_err: LmdbError) -> Self {
+        Error::Database(lmdb_err.into())
+    }
+}
+
+enum DisplayOrder {
+    DbPath,
+    Output,
+    Overwrite,
+    Compress,
+    BucketSize,
+    Top,
+}
+
+fn positive_count(value: &str) -> Result<(), String> {
+    match value.parse::<u64>() {
+        Ok(0) => Err("value must be greater than 0".to_string()),
+        Ok(_) => Ok(()),
+        Err(parse_err) => Err(parse_err.to_string()),
+    }
+}
+
+pub fn command(display_order: usize) -> Command<'static> {
+    Command::new(COMMAND_NAME)
+        .display_order(display_order)
+        .about(
+            "Hashes the wasm modules carried by the payment and session code \
+            of the deploys in a storage database, and outputs the most common \
+            ones, the average module sizes and how often deploys carried \
+            modules rather than calling stored contracts, aggregated over \
+            buckets of block heights, in JSON format.",
+        )
+        .arg(
+            Arg::new(DB_PATH)
+                .display_order(DisplayOrder::DbPath as usize)
+                .required(true)
+                .short('d')
+                .long(DB_PATH)
+                .takes_value(true)
+                .value_name("DB_PATH")
+                .help(
+                    "Path of the directory with the `storage.lmdb` file, or of \
+                    a `.tar.zst` archive of it.",
+                ),
+        )
+        .arg(
+            Arg::new(OUTPUT)
+                .display_order(DisplayOrder::Output as usize)
+                .short('o')
+                .long(OUTPUT)
+                .takes_value(true)
+                .value_name("FILE_PATH")
+                .help(
+                    "Path to where the program will output the report in JSON \
+                    format. If unspecified, defaults to standard output.",
+                ),
+        )
+        .arg(
+            Arg::new(OVERWRITE)
+                .display_order(DisplayOrder::Overwrite as usize)
+                .required(false)
+                .short('w')
+                .long(OVERWRITE)
+                .takes_value(false)
+                .requires(OUTPUT)
+                .help(
+                    "Overwrite an already existing output file in destination \
+                    directory.",
+                ),
+        )
+        .arg(output::compress_arg(DisplayOrder::Compress as usize))
+        .arg(
+            Arg::new(BUCKET_SIZE)
+                .display_order(DisplayOrder::BucketSize as usize)
+                .short('b')
+                .long(BUCKET_SIZE)
+                .takes_value(true)
+                .value_name("BLOCKS")
+                .default_value("10000")
+                .validator(positive_count)
+                .help("Number of consecutive heights aggregated in each bucket."),
+        )
+        .arg(
+            Arg::new(TOP)
+                .display_order(DisplayOrder::Top as usize)
+                .short('t')
+                .long(TOP)
+                .takes_value(true)
+                .value_name("COUNT")
+                .default_value("20")
+                .validator(positive_count)
+                .help("Number of the most used modules to list."),
+        )
+}
+
+pub fn run(matches: &ArgMatches) -> Result<(), Error> {
+    let db_dir = DbDir::open(
+        matches.value_of(DB_PATH).expect("should have db-path arg"),
+        &[Include::Storage],
+    )?;
+    let output = matches.value_of(OUTPUT).map(Path::new);
+    let overwrite = matches.is_present(OVERWRITE);
+    let bucket_size = matches
+        .value_of(BUCKET_SIZE)
+        .expect("should have bucket-size arg")
+        .parse()
+        .expect("should be validated");
+    let top = matches
+        .value_of(TOP)
+        .expect("should have top arg")
+        .parse()
+        .expect("should be validated");
+
+    let mut out_writer = OutputWriter::new(output, overwrite, Compression::from_matches(matches))?;
+    let report = report::wasm_stats(db_dir.path(), bucket_size, top)?;
+    serde_json::to_writer_pretty(&mut out_writer, &report)?;
+    writeln!(out_writer)?;
+    out_writer.finish()?;
+    Ok(())
+}
@@ -0,0 +1,222 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    result::Result,
+};
+
+use casper_execution_engine::core::engine_state::executable_deploy_item::ExecutableDeployItem;
+use casper_hashing::Digest;
+use casper_node::types::Deploy;
+use log::{info, warn};
+use serde::Serialize;
+
+use crate::common::{
+    block_iter::BlockIterator,
+    db::{Database, DeployDatabase, StorageEnv},
+    storage::{LmdbReader, StorageReader},
+};
+
+use super::Error;
+
+/// Use of a wasm module by the deploys.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct ModuleStats {
+    /// Hash of the module bytes.
+    pub(crate) hash: Digest,
+    pub(crate) size: u64,
+    /// Number of payment and session items carrying the module.
+    pub(crate) count: u64,
+    /// Bytes which would be saved by storing the module only once.
+    pub(crate) duplicate_bytes: u64,
+}
+
+/// How the payment or the session items of deploys supply their code.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct ItemUsage {
+    /// Items carrying non-empty module bytes.
+    pub(crate) module_bytes: u64,
+    /// Total size of the module bytes carried.
+    pub(crate) module_bytes_size: u64,
+    /// Items with empty module bytes, which select the standard payment when
+    /// used as payment.
+    pub(crate) empty_module_bytes: u64,
+    /// Items calling a contract stored in global state, by hash or by name,
+    /// versioned or not.
+    pub(crate) stored_contracts: u64,
+    pub(crate) transfers: u64,
+    /// Share of the items carrying module bytes among the ones carrying
+    /// module bytes or calling a stored contract, between 0 and 1.
+    pub(crate) module_bytes_share: Option<f64>,
+    pub(crate) average_module_size: Option<f64>,
+}
+
+impl ItemUsage {
+    /// Counts `item`, returning its module bytes if it carries any.
+    fn add<'a>(&mut self, item: &'a ExecutableDeployItem) -> Option<&'a [u8]> {
+        match item {
+            ExecutableDeployItem::ModuleBytes { module_bytes, .. } if module_bytes.is_empty() => {
+                self.empty_module_bytes += 1;
+                None
+            }
+            ExecutableDeployItem::ModuleBytes { module_bytes, .. } => {
+                self.module_bytes += 1;
+                self.module_bytes_size += module_bytes.len() as u64;
+                Some(module_bytes.as_ref())
+            }
+            ExecutableDeployItem::StoredContractByHash { .. }
+            | ExecutableDeployItem::StoredContractByName { .. }
+            | ExecutableDeployItem::StoredVersionedContractByHash { .. }
+            | ExecutableDeployItem::StoredVersionedContractByName { .. } => {
+                self.stored_contracts += 1;
+                None
+            }
+            ExecutableDeployItem::Transfer { .. } => {
+                self.transfers += 1;
+                None
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let code_items = self.module_bytes + self.stored_contracts;
+        self.module_bytes_share =
+            (code_items > 0).then(|| self.module_bytes as f64 / code_items as f64);
+        self.average_module_size = (self.module_bytes > 0)
+            .then(|| self.module_bytes_size as f64 / self.module_bytes as f64);
+    }
+}
+
+/// Code usage of the deploys of the blocks with heights in
+/// `first_height..=last_height`.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub(crate) struct HeightBucket {
+    pub(crate) first_height: u64,
+    pub(crate) last_height: u64,
+    /// Number of blocks in the bucket whose body is present.
+    pub(crate) blocks: usize,
+    /// Deploys other than native transfers.
+    pub(crate) deploys: usize,
+    pub(crate) payment: ItemUsage,
+    pub(crate) session: ItemUsage,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct WasmReport {
+    pub(crate) bucket_size: u64,
+    pub(crate) blocks: usize,
+    pub(crate) deploys: usize,
+    pub(crate) missing_bodies: usize,
+    pub(crate) missing_deploys: usize,
+    /// Number of payment and session items carrying module bytes.
+    pub(crate) modules: u64,
+    pub(crate) unique_modules: usize,
+    /// Total size of the module bytes carried by the deploys.
+    pub(crate) module_bytes_size: u64,
+    /// Size of the module bytes if each distinct module was stored once.
+    pub(crate) unique_module_bytes_size: u64,
+    pub(crate) average_module_size: Option<f64>,
+    pub(crate) average_unique_module_size: Option<f64>,
+    /// The most used modules, most used first.
+    pub(crate) top_modules: Vec<ModuleStats>,
+    pub(crate) buckets: Vec<HeightBucket>,
+}
+
+/// Gathers statistics on the payment and session code of the deploys of each
+/// block in the storage database at `db_path`, aggregated over buckets of
+/// `bucket_size` heights, and lists the `top` most used wasm modules.
+pub(crate) fn wasm_stats<P: AsRef<Path>>(
+    db_path: P,
+    bucket_size: u64,
+    top: usize,
+) -> Result<WasmReport, Error> {
+    let env = StorageEnv::open(&db_path)?;
+    let txn = env.begin_ro_txn()?;
+    let reader = LmdbReader::new(&txn);
+
+    let mut report = WasmReport {
+        bucket_size,
+        ..Default::default()
+    };
+    // Size and number of uses of each distinct module, by hash.
+    let mut modules: HashMap<Digest, (u64, u64)> = HashMap::new();
+    let mut buckets: BTreeMap<u64, HeightBucket> = BTreeMap::new();
+    for block in BlockIterator::new(&reader)? {
+        let (block_hash, header, maybe_body) = block?;
+        let body = match maybe_body {
+            Some(body) => body,
+            None => {
+                warn!("Missing body for block {}.", block_hash);
+                report.missing_bodies += 1;
+                continue;
+            }
+        };
+        report.blocks += 1;
+        let bucket_idx = header.height() / bucket_size;
+        let bucket = buckets.entry(bucket_idx).or_insert_with(|| HeightBucket {
+            first_height: bucket_idx * bucket_size,
+            last_height: bucket_idx * bucket_size + bucket_size - 1,
+            ..Default::default()
+        });
+        bucket.blocks += 1;
+        for deploy_hash in body.deploy_hashes {
+            let raw_deploy = match reader.get(DeployDatabase::db_name(), deploy_hash.as_ref())? {
+                Some(raw_deploy) => raw_deploy,
+                None => {
+                    warn!("Missing deploy {} of block {}.", deploy_hash, block_hash);
+                    report.missing_deploys += 1;
+                    continue;
+                }
+            };
+            let deploy: Deploy = bincode::deserialize(&raw_deploy)
+                .map_err(|bincode_err| Error::DeployParsing(deploy_hash, bincode_err))?;
+            report.deploys += 1;
+            bucket.deploys += 1;
+            let payment_module = bucket.payment.add(deploy.payment());
+            let session_module = bucket.session.add(deploy.session());
+            for module_bytes in payment_module.into_iter().chain(session_module) {
+                let (_size, count) = modules
+                    .entry(Digest::hash(module_bytes))
+                    .or_insert((module_bytes.len() as u64, 0));
+                *count += 1;
+            }
+        }
+    }
+
+    report.buckets = buckets
+        .into_values()
+        .map(|mut bucket| {
+            bucket.payment.finish();
+            bucket.session.finish();
+            bucket
+        })
+        .collect();
+    report.unique_modules = modules.len();
+    for (size, count) in modules.values() {
+        report.modules += count;
+        report.module_bytes_size += size * count;
+        report.unique_module_bytes_size += size;
+    }
+    report.average_module_size =
+        (report.modules > 0).then(|| report.module_bytes_size as f64 / report.modules as f64);
+    report.average_unique_module_size = (report.unique_modules > 0)
+        .then(|| report.unique_module_bytes_size as f64 / report.unique_modules as f64);
+    let mut top_modules: Vec<ModuleStats> = modules
+        .into_iter()
+        .map(|(hash, (size, count))| ModuleStats {
+            hash,
+            size,
+            count,
+            duplicate_bytes: size * (count - 1),
+        })
+        .collect();
+    // Break ties by hash for the output to be stable.
+    top_modules.sort_by_key(|module| (Reverse(module.count), Reverse(module.size), module.hash));
+    top_modules.truncate(top);
+    report.top_modules = top_modules;
+    info!(
+        "Found {} distinct modules in {} deploys of {} blocks.",
+        report.unique_modules, report.deploys, report.blocks
+    );
+    Ok(report)
+}
@@ -0,0 +1,155 @@
+use casper_execution_engine::core::engine_state::executable_deploy_item::ExecutableDeployItem;
+use casper_hashing::Digest;
+use casper_node::types::{BlockHeader, Deploy};
+use casper_types::{bytesrepr::Bytes, RuntimeArgs, SecretKey, TimeDiff, Timestamp};
+use lmdb::{Transaction, WriteFlags};
+
+use super::report::{wasm_stats, ItemUsage, ModuleStats};
+use crate::{
+    common::db::{
+        BlockBodyDatabase, BlockHeaderDatabase, BlockMetadataDatabase, Database, DeployDatabase,
+        DeployMetadataDatabase, STORAGE_FILE_NAME,
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+    test_utils::{mock_block_header, mock_deploy_hash, LmdbTestFixture},
+};
+
+fn module_bytes(bytes: &[u8]) -> ExecutableDeployItem {
+    ExecutableDeployItem::ModuleBytes {
+        module_bytes: Bytes::from(bytes.to_vec()),
+        args: RuntimeArgs::new(),
+    }
+}
+
+fn mock_deploy(idx: u64, payment: ExecutableDeployItem, session: ExecutableDeployItem) -> Deploy {
+    let secret_key = SecretKey::ed25519_from_bytes([1u8; 32]).unwrap();
+    Deploy::new(
+        Timestamp::from(idx),
+        TimeDiff::from_seconds(60),
+        1,
+        vec![],
+        "casper-test".to_string(),
+        payment,
+        session,
+        &secret_key,
+        None,
+    )
+}
+
+#[test]
+fn wasm_stats_should_count_modules_and_stored_contracts() {
+    let fixture = LmdbTestFixture::new(
+        vec![
+            BlockHeaderDatabase::db_name(),
+            BlockBodyDatabase::db_name(),
+            DeployDatabase::db_name(),
+            DeployMetadataDatabase::db_name(),
+            BlockMetadataDatabase::db_name(),
+        ],
+        Some(STORAGE_FILE_NAME),
+    );
+    let header_db = *fixture.db(Some(BlockHeaderDatabase::db_name())).unwrap();
+    let body_db = *fixture.db(Some(BlockBodyDatabase::db_name())).unwrap();
+    let deploy_db = *fixture.db(Some(DeployDatabase::db_name())).unwrap();
+
+    let common_module = [1u8; 100];
+    let stored_contract = ExecutableDeployItem::StoredContractByName {
+        name: "contract".to_string(),
+        entry_point: "call".to_string(),
+        args: RuntimeArgs::new(),
+    };
+    // The block at height 0 has two deploys with standard payment and the
+    // same session module, the block at height 1 has a deploy with custom
+    // payment calling a stored contract and a missing deploy, and the block
+    // at height 2 has no body.
+    let deploys = [
+        vec![
+            mock_deploy(0, module_bytes(&[]), module_bytes(&common_module)),
+            mock_deploy(1, module_bytes(&[]), module_bytes(&common_module)),
+        ],
+        vec![mock_deploy(2, module_bytes(&[2u8; 10]), stored_contract)],
+        vec![],
+    ];
+    let mut txn = fixture.env.begin_rw_txn().unwrap();
+    for (idx, block_deploys) in deploys.iter().enumerate() {
+        let (_, mut mock_header) = mock_block_header(idx as u8);
+        mock_header.height = idx as u64;
+        let header: BlockHeader =
+            bincode::deserialize(&bincode::serialize(&mock_header).unwrap()).unwrap();
+        txn.put(
+            header_db,
+            &header.hash(),
+            &bincode::serialize(&header).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+        if idx == 2 {
+            continue;
+        }
+        let mut deploy_hashes: Vec<_> = block_deploys.iter().map(|deploy| *deploy.id()).collect();
+        if idx == 1 {
+            deploy_hashes.push(mock_deploy_hash(100));
+        }
+        for deploy in block_deploys {
+            txn.put(
+                deploy_db,
+                deploy.id(),
+                &bincode::serialize(deploy).unwrap(),
+                WriteFlags::empty(),
+            )
+            .unwrap();
+        }
+        txn.put(
+            body_db,
+            header.body_hash(),
+            &bincode::serialize(&BlockBody::new(deploy_hashes)).unwrap(),
+            WriteFlags::empty(),
+        )
+        .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let report = wasm_stats(fixture.tmp_dir.path(), 1, 1).unwrap();
+    assert_eq!(report.blocks, 2);
+    assert_eq!(report.deploys, 3);
+    assert_eq!(report.missing_bodies, 1);
+    assert_eq!(report.missing_deploys, 1);
+    assert_eq!(report.modules, 3);
+    assert_eq!(report.unique_modules, 2);
+    assert_eq!(report.module_bytes_size, 210);
+    assert_eq!(report.unique_module_bytes_size, 110);
+    assert_eq!(report.average_module_size, Some(70.0));
+    assert_eq!(report.average_unique_module_size, Some(55.0));
+    assert_eq!(
+        report.top_modules,
+        vec![ModuleStats {
+            hash: Digest::hash(common_module),
+            size: 100,
+            count: 2,
+            duplicate_bytes: 100,
+        }]
+    );
+
+    assert_eq!(report.buckets.len(), 2);
+    let first_bucket = &report.buckets[0];
+    assert_eq!(first_bucket.deploys, 2);
+    assert_eq!(first_bucket.payment.empty_module_bytes, 2);
+    assert_eq!(first_bucket.payment.module_bytes_share, None);
+    assert_eq!(
+        first_bucket.session,
+        ItemUsage {
+            module_bytes: 2,
+            module_bytes_size: 200,
+            module_bytes_share: Some(1.0),
+            average_module_size: Some(100.0),
+            ..Default::default()
+        }
+    );
+    let second_bucket = &report.buckets[1];
+    assert_eq!(second_bucket.first_height, 1);
+    assert_eq!(second_bucket.deploys, 1);
+    assert_eq!(second_bucket.payment.module_bytes_share, Some(1.0));
+    assert_eq!(second_bucket.payment.average_module_size, Some(10.0));
+    assert_eq!(second_bucket.session.stored_contracts, 1);
+    assert_eq!(second_bucket.session.module_bytes_share, Some(0.0));
+}
@@ -11,11 +11,23 @@ use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use tempfile::{NamedTempFile, TempDir};
 
+use crate::{
+    common::{
+        chainspec::{ActivationPoint, Chainspec, CoreConfig, HighwayConfig, ProtocolConfig},
+        merkle_body::{self, MerkleEntry},
+    },
+    subcommands::execution_results_summary::block_body::BlockBody,
+};
+
+use casper_execution_engine::{
+    core::engine_state::executable_deploy_item::ExecutableDeployItem,
+    shared::{system_config::SystemConfig, wasm_config::WasmConfig},
+};
 use casper_hashing::Digest;
-use casper_node::types::{BlockHash, DeployHash, DeployMetadata};
+use casper_node::types::{BlockHash, Deploy, DeployHash, DeployMetadata, HashingAlgorithmVersion};
 use casper_types::{
-    EraId, ExecutionEffect, ExecutionResult, ProtocolVersion, PublicKey, SecretKey, Timestamp,
-    U256, U512,
+    bytesrepr::Bytes, EraId, ExecutionEffect, ExecutionResult, ProtocolVersion, PublicKey,
+    RuntimeArgs, SecretKey, TimeDiff, Timestamp, U256, U512,
 };
 
 pub(crate) static KEYS: Lazy<Vec<PublicKey>> = Lazy::new(|| {
@@ -174,6 +186,64 @@ pub(crate) fn mock_deploy_metadata(block_hashes: &[BlockHash]) -> DeployMetadata
     deploy_metadata
 }
 
+/// Returns a deploy with empty payment and session code, signed by a fixed
+/// key.
+pub(crate) fn mock_deploy(timestamp: Timestamp, ttl: TimeDiff) -> Deploy {
+    mock_deploy_with_dependencies(timestamp, ttl, vec![])
+}
+
+/// Returns a deploy like `mock_deploy` which depends on `dependencies`.
+pub(crate) fn mock_deploy_with_dependencies(
+    timestamp: Timestamp,
+    ttl: TimeDiff,
+    dependencies: Vec<DeployHash>,
+) -> Deploy {
+    let secret_key = SecretKey::ed25519_from_bytes([1u8; 32]).expect("should create secret key");
+    let module_bytes = || ExecutableDeployItem::ModuleBytes {
+        module_bytes: Bytes::new(),
+        args: RuntimeArgs::new(),
+    };
+    Deploy::new(
+        timestamp,
+        ttl,
+        1,
+        dependencies,
+        "casper-test".to_string(),
+        module_bytes(),
+        module_bytes(),
+        &secret_key,
+        None,
+    )
+}
+
+/// Returns the chainspec of a network started at era 0 with protocol
+/// version 1.0.0, with eras of at least 10 blocks and 1 hour, and rounds of
+/// at least 4 seconds.
+pub(crate) fn mock_chainspec() -> Chainspec {
+    Chainspec {
+        protocol: ProtocolConfig {
+            version: ProtocolVersion::V1_0_0,
+            activation_point: ActivationPoint::EraId(EraId::new(0)),
+        },
+        core: CoreConfig {
+            era_duration: TimeDiff::from_seconds(3_600),
+            minimum_era_height: 10,
+            validator_slots: 100,
+            auction_delay: 1,
+            max_associated_keys: 100,
+            max_runtime_call_stack_height: 12,
+            max_stored_value_size: 8_388_608,
+            minimum_delegation_amount: 500_000_000_000,
+            prune_batch_size: 0,
+        },
+        highway: HighwayConfig {
+            minimum_round_exponent: 12,
+        },
+        wasm: WasmConfig::default(),
+        system_costs: SystemConfig::default(),
+    }
+}
+
 pub(crate) fn success_execution_result() -> ExecutionResult {
     ExecutionResult::Success {
         effect: ExecutionEffect::default(),
@@ -182,6 +252,16 @@ pub(crate) fn success_execution_result() -> ExecutionResult {
     }
 }
 
+/// Returns the hash of `body` under the merkle scheme, along with the entries
+/// which store it.
+pub(crate) fn mock_merkle_body(body: &BlockBody) -> (Digest, Vec<MerkleEntry>) {
+    let body_hash = body.hash(HashingAlgorithmVersion::V2);
+    (
+        body_hash,
+        merkle_body::merkle_entries(body, body_hash).unwrap(),
+    )
+}
+
 #[derive(Clone, Debug, Default, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub(crate) struct EraReport {
     equivocators: Vec<PublicKey>,
@@ -218,6 +298,16 @@ impl MockSwitchBlockHeader {
             .next_era_validator_weights
             .insert(key, weight);
     }
+
+    pub fn insert_reward(&mut self, key: PublicKey, reward: u64) {
+        let _ = self
+            .era_end
+            .as_mut()
+            .unwrap()
+            .era_report
+            .rewards
+            .insert(key, reward);
+    }
 }
 
 impl Default for MockSwitchBlockHeader {